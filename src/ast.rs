@@ -0,0 +1,327 @@
+/* Abstract syntax tree produced by the parser and consumed by the optimizer
+ * and interpreter. See grammar.md for the grammar.
+ */
+use num_bigint::BigInt;
+use std::rc::Rc;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BinOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    FloorDiv,
+    Mod,
+    Pow,
+    BitAnd,
+    BitOr,
+    BitXor,
+    LShift,
+    RShift,
+}
+
+impl BinOp {
+    /* & | ^ << >>, which only ints (and bools) support among the numbers
+     */
+    pub fn is_bitwise(self) -> bool {
+        return matches!(self, BinOp::BitAnd | BinOp::BitOr | BinOp::BitXor | BinOp::LShift | BinOp::RShift);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UnaryOp {
+    Neg,
+    Pos,
+    Not,
+    // ~x
+    Invert,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CmpOp {
+    Eq,
+    NotEq,
+    Lt,
+    LtE,
+    Gt,
+    GtE,
+    Is,
+    IsNot,
+    In,
+    NotIn,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BoolOp {
+    And,
+    Or,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    NoneT,
+    Bool(bool),
+    Int(i64),
+    // an int literal too big for Int
+    BigInt(Rc<BigInt>),
+    Float(f64),
+    // an imaginary literal such as 2j
+    Imaginary(f64),
+    Str(String),
+    Bytes(Vec<u8>),
+    Name(String),
+    Attribute(Box<Expr>, String),
+    List(Vec<Expr>),
+    Tuple(Vec<Expr>),
+    UnaryOp(UnaryOp, Box<Expr>),
+    BinOp(Box<Expr>, BinOp, Box<Expr>),
+    BoolOp(Box<Expr>, BoolOp, Box<Expr>),
+    // a < b <= c is Compare(a, [(Lt, b), (LtE, c)])
+    Compare(Box<Expr>, Vec<(CmpOp, Expr)>),
+    Call(Box<Expr>, Vec<Arg>),
+    Subscript(Box<Expr>, Box<Expr>),
+    // a[lower:upper:step], only valid as a subscript
+    Slice(Option<Box<Expr>>, Option<Box<Expr>>, Option<Box<Expr>>),
+    Dict(Vec<(Expr, Expr)>),
+    Set(Vec<Expr>),
+    // body is a single Return statement
+    Lambda(Rc<FunctionDef>),
+    // body if test else orelse -> IfExp(test, body, orelse)
+    IfExp(Box<Expr>, Box<Expr>, Box<Expr>),
+    // [x for x in y], {k: v for ...}, {x for ...} or (x for x in y)
+    Comprehension(Rc<Comprehension>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ComprehensionKind {
+    List,
+    Dict,
+    Set,
+    Generator,
+}
+
+/* A comprehension or generator expression. It runs in a scope of its own,
+ * except that the first clause's iterable is evaluated where it appears.
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub struct Comprehension {
+    pub kind: ComprehensionKind,
+    // the item made, or the key of a dict comprehension
+    pub element: Expr,
+    // the value of a dict comprehension
+    pub value: Option<Expr>,
+    pub clauses: Vec<ComprehensionFor>,
+    // where it starts, for the frame it has in tracebacks
+    pub line: usize,
+}
+
+/* for target in iter if condition if ...
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub struct ComprehensionFor {
+    pub target: Expr,
+    pub iter: Expr,
+    pub conditions: Vec<Expr>,
+}
+
+/* an argument in a call: f(a, b=1, *c, **d)
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub enum Arg {
+    Positional(Expr),
+    Keyword(String, Expr),
+    Star(Expr),
+    DoubleStar(Expr),
+}
+
+/* def f(a, b=1, *args, c, d=2, **kwargs) - parameters with their default
+ * expressions
+ */
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Params {
+    pub args: Vec<(String, Option<Expr>)>,
+    pub vararg: Option<String>,
+    pub kwonly: Vec<(String, Option<Expr>)>,
+    pub kwarg: Option<String>,
+}
+
+impl Params {
+    /* every parameter name, in binding order
+     */
+    pub fn names(&self) -> Vec<&String> {
+        let mut names: Vec<&String> = self.args.iter().map(|(name, _)| name).collect();
+        names.extend(&self.vararg);
+        names.extend(self.kwonly.iter().map(|(name, _)| name));
+        names.extend(&self.kwarg);
+        return names;
+    }
+}
+
+/* Shared between the AST and every function object created from it
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub struct FunctionDef {
+    pub name: String,
+    pub params: Params,
+    pub body: Vec<Stmt>,
+    // the names the body binds, sorted: they are local to every call, so
+    // using one before it is bound is an UnboundLocalError
+    pub locals: Vec<String>,
+    // the docstring, a str literal the body starts with
+    pub doc: Option<String>,
+    // @decorators, top to bottom
+    pub decorators: Vec<Expr>,
+}
+
+impl FunctionDef {
+    pub fn build_new(name: String, params: Params, body: Vec<Stmt>) -> FunctionDef {
+        let mut locals: Vec<String> = params.names().into_iter().cloned().collect();
+        let mut declared: Vec<String> = Vec::new();
+        block_names(&body, &mut locals, &mut declared);
+        locals.retain(|name| !declared.contains(name));
+        locals.sort();
+        locals.dedup();
+        let doc = match body.first().map(|stmt| &stmt.kind) {
+            Some(StmtKind::Expr(Expr::Str(doc))) => Some(doc.clone()),
+            _ => None,
+        };
+        return FunctionDef {
+            name,
+            params,
+            body,
+            locals,
+            doc,
+            decorators: Vec::new(),
+        };
+    }
+
+    pub fn is_local(&self, name: &str) -> bool {
+        return self.locals.binary_search_by(|local| local.as_str().cmp(name)).is_ok();
+    }
+}
+
+/* Add the names the statements bind to names, and those they declare
+ * global or nonlocal to declared. A nested def or class binds its name,
+ * but what its body binds is its own.
+ */
+fn block_names(body: &[Stmt], names: &mut Vec<String>, declared: &mut Vec<String>) {
+    for stmt in body {
+        match &stmt.kind {
+            StmtKind::Assign(targets, _) => targets.iter().for_each(|target| target_names(target, names)),
+            StmtKind::AugAssign(target, ..) => target_names(target, names),
+            StmtKind::Delete(targets) => targets.iter().for_each(|target| target_names(target, names)),
+            StmtKind::If(_, body, orelse) | StmtKind::While(_, body, orelse) => {
+                block_names(body, names, declared);
+                block_names(orelse, names, declared);
+            }
+            StmtKind::For(target, _, body, orelse) => {
+                target_names(target, names);
+                block_names(body, names, declared);
+                block_names(orelse, names, declared);
+            }
+            StmtKind::With(items, body) => {
+                items.iter().filter_map(|(_, target)| target.as_ref()).for_each(|target| target_names(target, names));
+                block_names(body, names, declared);
+            }
+            StmtKind::Try(body, handlers, orelse, finalbody) => {
+                block_names(body, names, declared);
+                for handler in handlers {
+                    names.extend(handler.name.iter().cloned());
+                    block_names(&handler.body, names, declared);
+                }
+                block_names(orelse, names, declared);
+                block_names(finalbody, names, declared);
+            }
+            StmtKind::FunctionDef(def) => names.push(def.name.clone()),
+            StmtKind::ClassDef(def) => names.push(def.name.clone()),
+            StmtKind::Global(declarations) | StmtKind::Nonlocal(declarations) => declared.extend(declarations.iter().cloned()),
+            // import a.b binds a
+            StmtKind::Import(modules) => {
+                for (module, alias) in modules {
+                    names.push(alias.clone().unwrap_or_else(|| module.split('.').next().unwrap_or_default().to_string()));
+                }
+            }
+            StmtKind::ImportFrom(_, _, imported) => {
+                for (name, alias) in imported.iter().filter(|(name, _)| name != "*") {
+                    names.push(alias.clone().unwrap_or_else(|| name.clone()));
+                }
+            }
+            _ => (),
+        }
+    }
+}
+
+fn target_names(target: &Expr, names: &mut Vec<String>) {
+    match target {
+        Expr::Name(name) => names.push(name.clone()),
+        Expr::Tuple(items) | Expr::List(items) => items.iter().for_each(|item| target_names(item, names)),
+        _ => (),
+    }
+}
+
+/* class name(bases): body. The bases are call arguments, so keywords
+ * and unpacking parse, though only positional bases run.
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClassDef {
+    pub name: String,
+    pub bases: Vec<Arg>,
+    pub body: Vec<Stmt>,
+    pub decorators: Vec<Expr>,
+}
+
+/* except kind as name: body, on line. A bare except has no kind.
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub struct Handler {
+    pub kind: Option<Expr>,
+    pub name: Option<String>,
+    pub body: Vec<Stmt>,
+    pub line: usize,
+}
+
+/* A statement and the line it starts on, for tracebacks
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub struct Stmt {
+    pub line: usize,
+    pub kind: StmtKind,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum StmtKind {
+    Expr(Expr),
+    // a target is a Name, Attribute, Subscript, or a Tuple/List of targets to
+    // unpack into; a = b = 1 is Assign([a, b], 1)
+    Assign(Vec<Expr>, Expr),
+    // x += 1 -> AugAssign(x, Add, 1); the target is a Name, Attribute or
+    // Subscript
+    AugAssign(Expr, BinOp, Expr),
+    // del a, b[0]
+    Delete(Vec<Expr>),
+    // if/elif chains are nested Ifs in the else branch
+    If(Expr, Vec<Stmt>, Vec<Stmt>),
+    While(Expr, Vec<Stmt>, Vec<Stmt>),
+    // for target in iter: body else: orelse
+    For(Expr, Expr, Vec<Stmt>, Vec<Stmt>),
+    // with a as b, c: body  ->  With([(a, Some(b)), (c, None)], body)
+    With(Vec<(Expr, Option<Expr>)>, Vec<Stmt>),
+    // try: body except...: handlers else: orelse finally: finalbody
+    Try(Vec<Stmt>, Vec<Handler>, Vec<Stmt>, Vec<Stmt>),
+    // raise exc from cause; a bare raise re-raises
+    Raise(Option<Expr>, Option<Expr>),
+    FunctionDef(Rc<FunctionDef>),
+    ClassDef(Rc<ClassDef>),
+    Return(Option<Expr>),
+    Global(Vec<String>),
+    Nonlocal(Vec<String>),
+    Assert(Expr, Option<Expr>),
+    // import a.b as c, d  ->  Import([("a.b", Some("c")), ("d", None)])
+    Import(Vec<(String, Option<String>)>),
+    // from ..a import b as c  ->  ImportFrom(2, "a", [("b", Some("c"))]);
+    // `from a import *` has the single name "*"
+    ImportFrom(usize, String, Vec<(String, Option<String>)>),
+    Pass,
+    Break,
+    Continue,
+}
@@ -0,0 +1,1730 @@
+use crate::interpreter::{as_int, not_an_int, slice_index, EvalResult, Interpreter};
+use crate::limits::{self, TICK_BYTES};
+use crate::value::{new_list, new_tuple, Args, Builtin, BuiltinFn, Class, Exception, Value};
+use std::cell::{Cell, RefCell};
+use std::convert::TryFrom;
+use std::fmt;
+use std::rc::Rc;
+
+/* bytes, bytearray and memoryview.
+ *
+ * bytes is immutable and a bytearray is a mutable, shared buffer. They
+ * have the methods of str that make sense for binary data, which treat
+ * the bytes as ASCII: only ASCII letters have a case, and whitespace is
+ * b' \t\n\r\x0b\x0c'.
+ *
+ * bytes, bytearray and memoryview are "bytes-like": they give their
+ * contents to whatever takes binary data (the buffer protocol). A
+ * memoryview refers to the bytes of the object it was made from, and
+ * slicing it makes another view of them, without copying. A bytearray
+ * can't change size while there are views of it, as in CPython, so the
+ * views stay in bounds.
+ *
+ * As with str, methods that make a long result from a short one count
+ * its memory before building it.
+ *
+ * str.encode() and bytes.decode() know utf-8, ascii and latin-1, with the
+ * strict, replace and ignore error handlers.
+ */
+
+/* A bytearray's contents, and the number of memoryviews of it that
+ * haven't been released
+ */
+pub struct ByteArray {
+    pub data: RefCell<Vec<u8>>,
+    pub exports: Cell<usize>,
+}
+
+/* A view of part of a bytes or bytearray object: the bytes at start,
+ * start + step, ... (len of them)
+ */
+pub struct MemoryView {
+    pub source: Value,
+    pub start: usize,
+    pub step: isize,
+    pub len: usize,
+    pub readonly: bool,
+    pub released: Cell<bool>,
+}
+
+impl MemoryView {
+    /* operations on a released view fail
+     */
+    pub fn check(&self) -> Result<(), Exception> {
+        if self.released.get() {
+            return Err(value_error("operation forbidden on released memoryview object"));
+        }
+        return Ok(());
+    }
+
+    fn position(&self, index: usize) -> usize {
+        return (self.start as isize + index as isize * self.step) as usize;
+    }
+
+    pub fn get(&self, index: usize) -> u8 {
+        match &self.source {
+            Value::Bytes(data) => return data[self.position(index)],
+            Value::ByteArray(array) => return array.data.borrow()[self.position(index)],
+            _ => unreachable!(),
+        }
+    }
+
+    pub fn set(&self, index: usize, byte: u8) {
+        if let Value::ByteArray(array) = &self.source {
+            array.data.borrow_mut()[self.position(index)] = byte;
+        }
+    }
+
+    pub fn to_vec(&self) -> Vec<u8> {
+        return (0..self.len).map(|index| self.get(index)).collect();
+    }
+
+    fn release(&self) {
+        if self.released.replace(true) {
+            return;
+        }
+        if let Value::ByteArray(array) = &self.source {
+            array.exports.set(array.exports.get() - 1);
+        }
+    }
+}
+
+impl Drop for MemoryView {
+    fn drop(&mut self) {
+        self.release();
+    }
+}
+
+impl PartialEq for ByteArray {
+    fn eq(&self, other: &ByteArray) -> bool {
+        return std::ptr::eq(self, other);
+    }
+}
+
+impl PartialEq for MemoryView {
+    fn eq(&self, other: &MemoryView) -> bool {
+        return std::ptr::eq(self, other);
+    }
+}
+
+impl fmt::Debug for ByteArray {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return write!(f, "bytearray({})", quote_bytes(&self.data.borrow(), true));
+    }
+}
+
+impl fmt::Debug for MemoryView {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let state = if self.released.get() { "released memory" } else { "memory" };
+        return write!(f, "<{} at {:#x}>", state, self as *const MemoryView as usize);
+    }
+}
+
+pub fn new_bytes(data: Vec<u8>) -> Value {
+    return Value::Bytes(Rc::new(data));
+}
+
+pub fn new_bytearray(data: Vec<u8>) -> Value {
+    return Value::ByteArray(Rc::new(ByteArray {
+        data: RefCell::new(data),
+        exports: Cell::new(0),
+    }));
+}
+
+fn new_view(source: Value, start: usize, step: isize, len: usize, readonly: bool) -> Value {
+    if let Value::ByteArray(array) = &source {
+        array.exports.set(array.exports.get() + 1);
+    }
+    return Value::MemoryView(Rc::new(MemoryView {
+        source,
+        start,
+        step,
+        len,
+        readonly,
+        released: Cell::new(false),
+    }));
+}
+
+/* view[start::step], len items of it, as a new view of the same bytes
+ */
+pub fn view_slice(view: &MemoryView, start: i64, len: i64, step: i64) -> Value {
+    let start = if len > 0 { view.position(start as usize) } else { view.start };
+    return new_view(view.source.clone(), start, view.step * step as isize, len as usize, view.readonly);
+}
+
+/* The contents of a bytes-like object, or None if the value isn't one
+ * (or is a released memoryview)
+ */
+pub fn contents(value: &Value) -> Option<Vec<u8>> {
+    match value {
+        Value::Bytes(data) => return Some(data.to_vec()),
+        Value::ByteArray(array) => return Some(array.data.borrow().clone()),
+        Value::MemoryView(view) if !view.released.get() => return Some(view.to_vec()),
+        _ => return None,
+    }
+}
+
+/* The contents of a value that has to be bytes-like
+ */
+pub fn buffer(value: &Value) -> Result<Vec<u8>, Exception> {
+    if let Value::MemoryView(view) = value {
+        view.check()?;
+    }
+    return contents(value).ok_or_else(|| not_bytes_like(value));
+}
+
+pub fn byte_len(value: &Value) -> Option<usize> {
+    match value {
+        Value::Bytes(data) => return Some(data.len()),
+        Value::ByteArray(array) => return Some(array.data.borrow().len()),
+        Value::MemoryView(view) => return Some(view.len),
+        _ => return None,
+    }
+}
+
+/* the byte at index, for iterators, which read the object as they go
+ */
+pub fn byte_at(value: &Value, index: usize) -> Option<u8> {
+    match value {
+        Value::Bytes(data) => return data.get(index).copied(),
+        Value::ByteArray(array) => return array.data.borrow().get(index).copied(),
+        Value::MemoryView(view) if index < view.len && !view.released.get() => return Some(view.get(index)),
+        _ => return None,
+    }
+}
+
+fn not_bytes_like(value: &Value) -> Exception {
+    return type_error(format!("a bytes-like object is required, not '{}'", value.type_name()));
+}
+
+fn type_error(message: String) -> Exception {
+    return Exception::new("TypeError", &message);
+}
+
+fn value_error(message: &str) -> Exception {
+    return Exception::new("ValueError", message);
+}
+
+/* An int that is a byte, as bytearray items are
+ */
+pub fn byte_value(value: &Value) -> Result<u8, Exception> {
+    let val = int_arg(value)?;
+    return u8::try_from(val).map_err(|_| value_error("byte must be in range(0, 256)"));
+}
+
+fn int_arg(value: &Value) -> Result<i64, Exception> {
+    return as_int(value).ok_or_else(|| not_an_int(value));
+}
+
+/* repr() of bytes: b'...' with the quotes repr_str() would choose, and
+ * everything but printable ASCII escaped
+ */
+pub fn repr_bytes(data: &[u8]) -> String {
+    return quote_bytes(data, false);
+}
+
+// CPython's repr() of a bytearray escapes single quotes even inside
+// double ones
+fn quote_bytes(data: &[u8], escape_single: bool) -> String {
+    let quote = if data.contains(&b'\'') && !data.contains(&b'"') { b'"' } else { b'\'' };
+    let mut out = String::from("b");
+    out.push(quote as char);
+    for byte in data {
+        match byte {
+            b'\\' => out.push_str("\\\\"),
+            b'\n' => out.push_str("\\n"),
+            b'\r' => out.push_str("\\r"),
+            b'\t' => out.push_str("\\t"),
+            byte if *byte == quote || *byte == b'\'' && escape_single => {
+                out.push('\\');
+                out.push(*byte as char);
+            }
+            0x20..=0x7e => out.push(*byte as char),
+            byte => out.push_str(&format!("\\x{:02x}", byte)),
+        }
+    }
+    out.push(quote as char);
+    return out;
+}
+
+/* equality of two bytes-like objects, or None if either isn't one. A
+ * released memoryview is only equal to itself.
+ */
+pub fn equals(left: &Value, right: &Value) -> Option<bool> {
+    byte_len(left)?;
+    byte_len(right)?;
+    match (contents(left), contents(right)) {
+        (Some(left), Some(right)) => return Some(left == right),
+        _ => return Some(left.identity() == right.identity()),
+    }
+}
+
+/* item in data: an int, or a run of bytes
+ */
+pub fn contains(data: &[u8], item: &Value) -> Result<bool, Exception> {
+    if as_int(item).is_some() {
+        return Ok(data.contains(&byte_value(item)?));
+    }
+    return Ok(find_in(data, &buffer(item)?).is_some());
+}
+
+/* bytes + bytes-like, which has the type of the left operand
+ */
+pub fn concat(left: &Value, right: &Value) -> EvalResult {
+    let mut data = contents(left).unwrap();
+    match contents(right) {
+        Some(more) => data.extend(more),
+        None => return Err(type_error(format!("can't concat {} to {}", right.type_name(), left.type_name()))),
+    };
+    return Ok(like(left, data));
+}
+
+pub fn repeat(value: &Value, count: i64) -> EvalResult {
+    let data = repeated(&contents(value).unwrap(), count, "repeated bytes are too long")?;
+    return Ok(like(value, data));
+}
+
+/* data repeated count times, doubling what's been copied so far:
+ * OverflowError (with message) if the length doesn't fit, MemoryError if
+ * it can't be had
+ */
+pub fn repeated(data: &[u8], count: i64, message: &str) -> Result<Vec<u8>, Exception> {
+    let len = data.len().checked_mul(count.max(0) as usize).filter(|len| *len <= isize::MAX as usize);
+    let len = len.ok_or_else(|| Exception::new("OverflowError", message))?;
+    let mut out = limits::new_buffer(len)?;
+    out.extend_from_slice(&data[..data.len().min(len)]);
+    while out.len() < len {
+        let more = out.len().min(len - out.len());
+        out.extend_from_within(..more);
+    }
+    return Ok(out);
+}
+
+/* data as the same type as value: bytearray or bytes
+ */
+fn like(value: &Value, data: Vec<u8>) -> Value {
+    match value {
+        Value::ByteArray(_) => return new_bytearray(data),
+        _ => return new_bytes(data),
+    }
+}
+
+/* The bytes of value, assigned to a slice of a bytearray (which takes any
+ * iterable of ints) or a memoryview (which takes bytes-like objects)
+ */
+pub fn assigned_bytes(interpreter: &mut Interpreter, target: &Value, value: &Value) -> Result<Vec<u8>, Exception> {
+    match (target, value) {
+        (Value::MemoryView(_), value) => return buffer(value),
+        (_, Value::Str(_) | Value::Int(_) | Value::Bool(_)) => {
+            return Err(type_error(String::from(
+                "can assign only bytes, buffers, or iterables of ints in range(0, 256)",
+            )));
+        }
+        _ => (),
+    };
+    if let Some(data) = contents(value) {
+        return Ok(data);
+    }
+    return interpreter.iterate(value)?.iter().map(byte_value).collect();
+}
+
+/* Codecs
+ */
+
+#[derive(Clone, Copy, PartialEq)]
+enum Codec {
+    Utf8,
+    Ascii,
+    Latin1,
+}
+
+fn codec(encoding: &str) -> Result<Codec, Exception> {
+    match encoding.to_ascii_lowercase().replace(['_', ' '], "-").as_str() {
+        "utf-8" | "utf8" | "u8" | "utf" => return Ok(Codec::Utf8),
+        "ascii" | "us-ascii" | "646" => return Ok(Codec::Ascii),
+        "latin-1" | "latin1" | "latin" | "iso-8859-1" | "iso8859-1" | "8859" | "cp819" | "l1" => return Ok(Codec::Latin1),
+        _ => return Err(Exception::new("LookupError", &format!("unknown encoding: {}", encoding))),
+    }
+}
+
+/* Whether a codec's characters can take more than one byte, for file
+ * objects reading text a character at a time. Raises LookupError for an
+ * unknown codec.
+ */
+pub fn is_multibyte(encoding: &str) -> Result<bool, Exception> {
+    return Ok(codec(encoding)? == Codec::Utf8);
+}
+
+// error handlers are only looked up when there's an error, like CPython
+fn unknown_handler(errors: &str) -> Exception {
+    return Exception::new("LookupError", &format!("unknown error handler name '{}'", errors));
+}
+
+/* a character as an encode error shows it, e.g. '\xe9' or '€'
+ */
+fn escape_char(c: char) -> String {
+    match c as u32 {
+        code if code < 0x100 => return format!("\\x{:02x}", code),
+        code if code < 0x10000 => return format!("\\u{:04x}", code),
+        code => return format!("\\U{:08x}", code),
+    }
+}
+
+pub fn encode(text: &str, encoding: &str, errors: &str) -> Result<Vec<u8>, Exception> {
+    let (name, limit) = match codec(encoding)? {
+        Codec::Utf8 => return Ok(text.as_bytes().to_vec()),
+        Codec::Ascii => ("ascii", 0x80),
+        Codec::Latin1 => ("latin-1", 0x100),
+    };
+    let chars: Vec<char> = text.chars().collect();
+    let mut out: Vec<u8> = Vec::with_capacity(chars.len());
+    let mut pos = 0;
+    while pos < chars.len() {
+        if (chars[pos] as u32) < limit {
+            out.push(chars[pos] as u8);
+            pos += 1;
+            continue;
+        }
+        // a run of characters the codec can't encode is one error
+        let end = pos + chars[pos..].iter().take_while(|c| **c as u32 >= limit).count();
+        match errors {
+            "strict" => {
+                let message = if end - pos == 1 {
+                    format!(
+                        "'{}' codec can't encode character '{}' in position {}: ordinal not in range({})",
+                        name,
+                        escape_char(chars[pos]),
+                        pos,
+                        limit
+                    )
+                } else {
+                    format!(
+                        "'{}' codec can't encode characters in position {}-{}: ordinal not in range({})",
+                        name,
+                        pos,
+                        end - 1,
+                        limit
+                    )
+                };
+                return Err(Exception::new("UnicodeEncodeError", &message));
+            }
+            "replace" => out.extend(std::iter::repeat_n(b'?', end - pos)),
+            "ignore" => (),
+            _ => return Err(unknown_handler(errors)),
+        };
+        pos = end;
+    }
+    return Ok(out);
+}
+
+fn decode_error(name: &str, data: &[u8], start: usize, end: usize, reason: &str) -> Exception {
+    let message = if end - start == 1 {
+        format!("'{}' codec can't decode byte {:#04x} in position {}: {}", name, data[start], start, reason)
+    } else {
+        format!("'{}' codec can't decode bytes in position {}-{}: {}", name, start, end - 1, reason)
+    };
+    return Exception::new("UnicodeDecodeError", &message);
+}
+
+pub fn decode(data: &[u8], encoding: &str, errors: &str) -> Result<String, Exception> {
+    let mut out = String::new();
+    match codec(encoding)? {
+        Codec::Latin1 => return Ok(data.iter().map(|byte| *byte as char).collect()),
+        Codec::Ascii => {
+            for (pos, byte) in data.iter().enumerate() {
+                if byte.is_ascii() {
+                    out.push(*byte as char);
+                    continue;
+                }
+                match errors {
+                    "strict" => return Err(decode_error("ascii", data, pos, pos + 1, "ordinal not in range(128)")),
+                    "replace" => out.push('\u{fffd}'),
+                    "ignore" => (),
+                    _ => return Err(unknown_handler(errors)),
+                };
+            }
+        }
+        Codec::Utf8 => {
+            // Rust and CPython both take the longest invalid sequence
+            // that starts like a valid one as a single error
+            let mut pos = 0;
+            loop {
+                let err = match std::str::from_utf8(&data[pos..]) {
+                    Ok(text) => {
+                        out.push_str(text);
+                        break;
+                    }
+                    Err(err) => err,
+                };
+                let start = pos + err.valid_up_to();
+                out.push_str(std::str::from_utf8(&data[pos..start]).unwrap());
+                let end = start + err.error_len().unwrap_or(data.len() - start);
+                match errors {
+                    "strict" => {
+                        let reason = match err.error_len() {
+                            None => "unexpected end of data",
+                            Some(_) if !(0xc2..=0xf4).contains(&data[start]) => "invalid start byte",
+                            Some(_) => "invalid continuation byte",
+                        };
+                        return Err(decode_error("utf-8", data, start, end, reason));
+                    }
+                    "replace" => out.push('\u{fffd}'),
+                    "ignore" => (),
+                    _ => return Err(unknown_handler(errors)),
+                };
+                pos = end;
+            }
+        }
+    };
+    return Ok(out);
+}
+
+/* The (encoding, errors) arguments at index and index + 1, or by
+ * keyword; utf-8 and strict by default
+ */
+pub fn codec_args(name: &str, args: &mut Args, index: usize) -> Result<(String, String), Exception> {
+    let encoding = optional_arg(args, index, "encoding");
+    let errors = optional_arg(args, index + 1, "errors");
+    let text = |value: Option<Value>, argument: &str, default: &str| match value {
+        None => return Ok(String::from(default)),
+        Some(Value::Str(ref text)) => return Ok(text.clone()),
+        Some(other) => {
+            return Err(type_error(format!(
+                "{}() argument '{}' must be str, not {}",
+                name,
+                argument,
+                other.type_name()
+            )));
+        }
+    };
+    return Ok((text(encoding, "encoding", "utf-8")?, text(errors, "errors", "strict")?));
+}
+
+/* Constructors
+ */
+
+/* The bytes of bytes(source, encoding, errors) and bytearray(...): a str
+ * encoded, a number of zeros, a bytes-like object's contents, or an
+ * iterable of ints
+ */
+fn from_source(name: &str, interpreter: &mut Interpreter, mut args: Args) -> Result<Vec<u8>, Exception> {
+    let source = optional_arg(&mut args, 0, "source");
+    let has_codec = args.positional.len() > 1 || args.keywords.iter().any(|(key, _)| key == "encoding" || key == "errors");
+    let (encoding, errors) = codec_args(name, &mut args, 1)?;
+    args.check(name, 0, 3)?;
+    let source = match source {
+        Some(source) => source,
+        None if has_codec => return Err(type_error(String::from("encoding without a string argument"))),
+        None => return Ok(Vec::new()),
+    };
+    match &source {
+        Value::Str(text) if has_codec => return encode(text, &encoding, &errors),
+        Value::Str(_) => return Err(type_error(String::from("string argument without an encoding"))),
+        _ if has_codec => return Err(type_error(String::from("encoding without a string argument"))),
+        _ => (),
+    };
+    if let Some(data) = contents(&source) {
+        return Ok(data);
+    }
+    if let Some(count) = as_int(&source) {
+        if count < 0 {
+            return Err(value_error("negative count"));
+        }
+        let mut data = interpreter.allocate_buffer(Some(count as usize))?;
+        data.resize(count as usize, 0);
+        return Ok(data);
+    }
+    if let Some(result) = interpreter.call_special(&source, "__bytes__", vec![]) {
+        match result? {
+            Value::Bytes(ref data) => return Ok(data.to_vec()),
+            other => return Err(type_error(format!("__bytes__ returned non-bytes (type {})", other.type_name()))),
+        }
+    }
+    let items = interpreter.iterate(&source).map_err(|err| match err.kind.as_str() {
+        "TypeError" if err.message.ends_with("is not iterable") => {
+            type_error(format!("cannot convert '{}' object to {}", source.type_name(), name))
+        }
+        _ => err,
+    })?;
+    return items.iter().map(byte_value).collect::<Result<Vec<u8>, Exception>>().map_err(|err| match err.kind.as_str() {
+        // CPython's bytes() says so in the plural
+        "ValueError" if name == "bytes" => value_error("bytes must be in range(0, 256)"),
+        _ => err,
+    });
+}
+
+/* bytes(source=b'', encoding='utf-8', errors='strict')
+ */
+pub fn bytes_new(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    return Ok(new_bytes(from_source("bytes", interpreter, args)?));
+}
+
+pub fn bytearray_new(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    return Ok(new_bytearray(from_source("bytearray", interpreter, args)?));
+}
+
+/* memoryview(object): a view of all of a bytes-like object
+ */
+pub fn memoryview_new(_interpreter: &mut Interpreter, mut args: Args) -> EvalResult {
+    let object = optional_arg(&mut args, 0, "object");
+    args.check("memoryview", 1, 1)?;
+    match object.unwrap() {
+        source @ Value::Bytes(_) => return Ok(new_view(source.clone(), 0, 1, byte_len(&source).unwrap(), true)),
+        source @ Value::ByteArray(_) => return Ok(new_view(source.clone(), 0, 1, byte_len(&source).unwrap(), false)),
+        Value::MemoryView(ref view) => {
+            view.check()?;
+            return Ok(new_view(view.source.clone(), view.start, view.step, view.len, view.readonly));
+        }
+        other => {
+            return Err(type_error(format!(
+                "memoryview: a bytes-like object is required, not '{}'",
+                other.type_name()
+            )));
+        }
+    }
+}
+
+/* Methods
+ */
+
+pub fn add_methods(bytes: &Rc<Class>, bytearray: &Rc<Class>, memoryview: &Rc<Class>) {
+    let shared: &[(&str, BuiltinFn)] = &[
+        ("capitalize", capitalize),
+        ("center", center),
+        ("count", count),
+        ("decode", decode_method),
+        ("endswith", endswith),
+        ("expandtabs", expandtabs),
+        ("find", find),
+        ("hex", hex),
+        ("index", index),
+        ("isalnum", isalnum),
+        ("isalpha", isalpha),
+        ("isascii", isascii),
+        ("isdigit", isdigit),
+        ("islower", islower),
+        ("isspace", isspace),
+        ("istitle", istitle),
+        ("isupper", isupper),
+        ("join", join),
+        ("ljust", ljust),
+        ("lower", lower),
+        ("lstrip", lstrip),
+        ("maketrans", maketrans),
+        ("partition", partition),
+        ("removeprefix", removeprefix),
+        ("removesuffix", removesuffix),
+        ("replace", replace),
+        ("rfind", rfind),
+        ("rindex", rindex),
+        ("rjust", rjust),
+        ("rpartition", rpartition),
+        ("rsplit", rsplit),
+        ("rstrip", rstrip),
+        ("split", split),
+        ("splitlines", splitlines),
+        ("startswith", startswith),
+        ("strip", strip),
+        ("swapcase", swapcase),
+        ("title", title),
+        ("translate", translate),
+        ("upper", upper),
+        ("zfill", zfill),
+    ];
+    let mutators: &[(&str, BuiltinFn)] = &[
+        ("append", append),
+        ("clear", clear),
+        ("copy", copy),
+        ("extend", extend),
+        ("insert", insert),
+        ("pop", pop),
+        ("remove", remove),
+        ("reverse", reverse),
+    ];
+    let views: &[(&str, BuiltinFn)] = &[
+        ("hex", view_hex),
+        ("release", release),
+        ("tobytes", tobytes),
+        ("tolist", tolist),
+        ("toreadonly", toreadonly),
+    ];
+    let add = |class: &Rc<Class>, methods: &[(&str, BuiltinFn)]| {
+        for (name, method) in methods {
+            class.attrs.borrow_mut().insert(String::from(*name), Builtin::build_new(name, *method));
+        }
+    };
+    add(bytes, shared);
+    add(bytes, &[("fromhex", bytes_fromhex)]);
+    add(bytearray, shared);
+    add(bytearray, mutators);
+    add(bytearray, &[("fromhex", bytearray_fromhex)]);
+    add(memoryview, views);
+}
+
+/* positional argument index, or the keyword argument name
+ */
+fn optional_arg(args: &mut Args, index: usize, name: &str) -> Option<Value> {
+    let keyword = args.keyword(name);
+    return args.positional.get(index).cloned().or(keyword);
+}
+
+/* the contents of the bytes or bytearray a method was called on
+ */
+fn this(name: &str, args: &Args) -> Result<Vec<u8>, Exception> {
+    match args.positional.first() {
+        Some(value @ (Value::Bytes(_) | Value::ByteArray(_))) => return Ok(contents(value).unwrap()),
+        other => {
+            return Err(type_error(format!(
+                "descriptor '{}' for 'bytes' objects doesn't apply to a '{}' object",
+                name,
+                other.map_or("nothing", Value::type_name)
+            )));
+        }
+    }
+}
+
+/* a method's result, with the type of the object it was called on
+ */
+fn result(args: &Args, data: Vec<u8>) -> Value {
+    return like(&args.positional[0], data);
+}
+
+fn is_space(byte: u8) -> bool {
+    return matches!(byte, b' ' | b'\t' | b'\n' | b'\r' | b'\x0b' | b'\x0c');
+}
+
+fn find_in(data: &[u8], sub: &[u8]) -> Option<usize> {
+    if sub.is_empty() {
+        return Some(0);
+    }
+    return data.windows(sub.len()).position(|window| window == sub);
+}
+
+fn rfind_in(data: &[u8], sub: &[u8]) -> Option<usize> {
+    if sub.is_empty() {
+        return Some(data.len());
+    }
+    return data.windows(sub.len()).rposition(|window| window == sub);
+}
+
+/* the positions of non-overlapping occurrences of a non-empty sub, at
+ * most limit of them, found from the left or the right
+ */
+fn occurrences(data: &[u8], sub: &[u8], limit: usize, from_right: bool) -> Vec<usize> {
+    let mut found: Vec<usize> = Vec::new();
+    if from_right {
+        let mut end = data.len();
+        while found.len() < limit {
+            match rfind_in(&data[..end], sub) {
+                Some(pos) => {
+                    found.push(pos);
+                    end = pos;
+                }
+                None => break,
+            };
+        }
+        found.reverse();
+    } else {
+        let mut start = 0;
+        while found.len() < limit {
+            match find_in(&data[start..], sub) {
+                Some(offset) => {
+                    found.push(start + offset);
+                    start += offset + sub.len();
+                }
+                None => break,
+            };
+        }
+    }
+    return found;
+}
+
+// start and end positions
+type ByteRange = (usize, usize);
+
+// the data, what to find in it, and where
+type Search = (Vec<u8>, Vec<u8>, Option<ByteRange>);
+
+/* The range start and end select, as a slice would. None if start is past
+ * the end, where not even an empty sub is found.
+ */
+fn byte_range(len: usize, start: Option<&Value>, end: Option<&Value>) -> Result<Option<ByteRange>, Exception> {
+    let len = len as i64;
+    let bound = |value: Option<&Value>, default: i64| -> Result<i64, Exception> {
+        match value {
+            None | Some(Value::NoneT) => return Ok(default),
+            Some(value) => {
+                let val = slice_index(value).ok_or_else(|| {
+                    type_error(String::from("slice indices must be integers or None or have an __index__ method"))
+                })?;
+                return Ok(if val < 0 { (val + len).max(0) } else { val });
+            }
+        }
+    };
+    let (start, end) = (bound(start, 0)?, bound(end, len)?.min(len));
+    if start > len {
+        return Ok(None);
+    }
+    return Ok(Some((start as usize, end.max(start) as usize)));
+}
+
+/* The data, sub and range of find() and friends: (sub[, start[, end]]),
+ * where sub is bytes-like or a byte's int
+ */
+fn search_args(name: &str, args: &Args) -> Result<Search, Exception> {
+    args.check(name, 2, 4)?;
+    let data = this(name, args)?;
+    let sub = match &args.positional[1] {
+        value if as_int(value).is_some() => vec![byte_value(value)?],
+        value => contents(value).ok_or_else(|| {
+            type_error(format!("argument should be integer or bytes-like object, not '{}'", value.type_name()))
+        })?,
+    };
+    let range = byte_range(data.len(), args.positional.get(2), args.positional.get(3))?;
+    return Ok((data, sub, range));
+}
+
+fn search(name: &str, args: &Args, last: bool) -> Result<Option<usize>, Exception> {
+    let (data, sub, range) = search_args(name, args)?;
+    let (start, end) = match range {
+        Some(range) => range,
+        None => return Ok(None),
+    };
+    let found = if last { rfind_in(&data[start..end], &sub) } else { find_in(&data[start..end], &sub) };
+    return Ok(found.map(|offset| start + offset));
+}
+
+fn find(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    return Ok(Value::Int(search("find", &args, false)?.map_or(-1, |position| position as i64)));
+}
+
+fn rfind(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    return Ok(Value::Int(search("rfind", &args, true)?.map_or(-1, |position| position as i64)));
+}
+
+fn index(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    match search("index", &args, false)? {
+        Some(position) => return Ok(Value::Int(position as i64)),
+        None => return Err(value_error("subsection not found")),
+    }
+}
+
+fn rindex(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    match search("rindex", &args, true)? {
+        Some(position) => return Ok(Value::Int(position as i64)),
+        None => return Err(value_error("subsection not found")),
+    }
+}
+
+fn count(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    let (data, sub, range) = search_args("count", &args)?;
+    let (start, end) = match range {
+        Some(range) => range,
+        None => return Ok(Value::Int(0)),
+    };
+    if sub.is_empty() {
+        return Ok(Value::Int((end - start) as i64 + 1));
+    }
+    return Ok(Value::Int(occurrences(&data[start..end], &sub, usize::MAX, false).len() as i64));
+}
+
+/* startswith() and endswith(): (prefix[, start[, end]]), where prefix
+ * may be a tuple of them to try
+ */
+fn affix(name: &str, args: Args, end: bool) -> EvalResult {
+    args.check(name, 2, 4)?;
+    let data = this(name, &args)?;
+    let affixes = match &args.positional[1] {
+        Value::Tuple(items) => items.iter().map(buffer).collect::<Result<Vec<Vec<u8>>, Exception>>()?,
+        value => match contents(value) {
+            Some(affix) => vec![affix],
+            None => {
+                return Err(type_error(format!(
+                    "{} first arg must be bytes or a tuple of bytes, not {}",
+                    name,
+                    value.type_name()
+                )));
+            }
+        },
+    };
+    let (from, to) = match byte_range(data.len(), args.positional.get(2), args.positional.get(3))? {
+        Some(range) => range,
+        None => return Ok(Value::Bool(false)),
+    };
+    let data = &data[from..to];
+    let found = affixes.iter().any(|affix| if end { data.ends_with(affix) } else { data.starts_with(affix) });
+    return Ok(Value::Bool(found));
+}
+
+fn startswith(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    return affix("startswith", args, false);
+}
+
+fn endswith(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    return affix("endswith", args, true);
+}
+
+/* a method taking no arguments, mapping the bytes to others
+ */
+fn map(name: &str, args: Args, op: fn(&[u8]) -> Vec<u8>) -> EvalResult {
+    args.check(name, 1, 1)?;
+    return Ok(result(&args, op(&this(name, &args)?)));
+}
+
+fn upper(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    return map("upper", args, <[u8]>::to_ascii_uppercase);
+}
+
+fn lower(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    return map("lower", args, <[u8]>::to_ascii_lowercase);
+}
+
+fn swapcase(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    return map("swapcase", args, |data| {
+        return data
+            .iter()
+            .map(|byte| if byte.is_ascii_uppercase() { byte.to_ascii_lowercase() } else { byte.to_ascii_uppercase() })
+            .collect();
+    });
+}
+
+fn capitalize(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    return map("capitalize", args, |data| {
+        let mut out = data.to_ascii_lowercase();
+        if let Some(first) = out.first_mut() {
+            first.make_ascii_uppercase();
+        }
+        return out;
+    });
+}
+
+/* title(): each word's first letter upper case, the rest lower, where a
+ * word is a run of letters
+ */
+fn title(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    return map("title", args, |data| {
+        let mut out = Vec::with_capacity(data.len());
+        let mut previous_cased = false;
+        for byte in data {
+            out.push(if previous_cased { byte.to_ascii_lowercase() } else { byte.to_ascii_uppercase() });
+            previous_cased = byte.is_ascii_alphabetic();
+        }
+        return out;
+    });
+}
+
+/* a method taking no arguments that tests the bytes
+ */
+fn test(name: &str, args: Args, op: fn(&[u8]) -> bool) -> EvalResult {
+    args.check(name, 1, 1)?;
+    return Ok(Value::Bool(op(&this(name, &args)?)));
+}
+
+fn isalnum(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    return test("isalnum", args, |data| !data.is_empty() && data.iter().all(u8::is_ascii_alphanumeric));
+}
+
+fn isalpha(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    return test("isalpha", args, |data| !data.is_empty() && data.iter().all(u8::is_ascii_alphabetic));
+}
+
+fn isascii(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    return test("isascii", args, <[u8]>::is_ascii);
+}
+
+fn isdigit(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    return test("isdigit", args, |data| !data.is_empty() && data.iter().all(u8::is_ascii_digit));
+}
+
+fn isspace(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    return test("isspace", args, |data| !data.is_empty() && data.iter().all(|byte| is_space(*byte)));
+}
+
+fn islower(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    return test("islower", args, |data| {
+        data.iter().any(u8::is_ascii_lowercase) && !data.iter().any(u8::is_ascii_uppercase)
+    });
+}
+
+fn isupper(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    return test("isupper", args, |data| {
+        data.iter().any(u8::is_ascii_uppercase) && !data.iter().any(u8::is_ascii_lowercase)
+    });
+}
+
+/* istitle(): upper case letters only start words, lower case ones only
+ * continue them, and there's at least one
+ */
+fn istitle(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    return test("istitle", args, |data| {
+        let mut previous_cased = false;
+        let mut any_cased = false;
+        for byte in data {
+            if byte.is_ascii_uppercase() && previous_cased || byte.is_ascii_lowercase() && !previous_cased {
+                return false;
+            }
+            previous_cased = byte.is_ascii_alphabetic();
+            any_cased |= previous_cased;
+        }
+        return any_cased;
+    });
+}
+
+/* strip(), lstrip() and rstrip(): ([bytes]), whitespace by default
+ */
+fn strip_ends(name: &str, mut args: Args, left: bool, right: bool) -> EvalResult {
+    let chars = optional_arg(&mut args, 1, "bytes");
+    args.check(name, 1, 2)?;
+    let data = this(name, &args)?;
+    let chars = match chars {
+        None | Some(Value::NoneT) => None,
+        Some(chars) => Some(buffer(&chars)?),
+    };
+    let strip = |byte: &u8| match &chars {
+        Some(chars) => chars.contains(byte),
+        None => is_space(*byte),
+    };
+    let mut start = 0;
+    let mut end = data.len();
+    if left {
+        start = data.iter().position(|byte| !strip(byte)).unwrap_or(end);
+    }
+    if right {
+        end = data[start..].iter().rposition(|byte| !strip(byte)).map_or(start, |pos| start + pos + 1);
+    }
+    return Ok(result(&args, data[start..end].to_vec()));
+}
+
+fn strip(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    return strip_ends("strip", args, true, true);
+}
+
+fn lstrip(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    return strip_ends("lstrip", args, true, false);
+}
+
+fn rstrip(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    return strip_ends("rstrip", args, false, true);
+}
+
+/* split() and rsplit(): (sep=None, maxsplit=-1). With no separator, runs
+ * of whitespace separate, and there are no empty pieces.
+ */
+fn split_by(name: &str, mut args: Args, from_right: bool) -> EvalResult {
+    let sep = optional_arg(&mut args, 1, "sep");
+    let maxsplit = optional_arg(&mut args, 2, "maxsplit");
+    args.check(name, 1, 3)?;
+    let data = this(name, &args)?;
+    let maxsplit = match maxsplit {
+        Some(maxsplit) => int_arg(&maxsplit)?,
+        None => -1,
+    };
+    let limit = if maxsplit < 0 { usize::MAX } else { maxsplit as usize };
+    let pieces = match sep {
+        None | Some(Value::NoneT) => split_whitespace(&data, limit, from_right),
+        Some(sep) => {
+            let sep = buffer(&sep)?;
+            if sep.is_empty() {
+                return Err(value_error("empty separator"));
+            }
+            let mut pieces: Vec<Vec<u8>> = Vec::new();
+            let mut start = 0;
+            for pos in occurrences(&data, &sep, limit, from_right) {
+                pieces.push(data[start..pos].to_vec());
+                start = pos + sep.len();
+            }
+            pieces.push(data[start..].to_vec());
+            pieces
+        }
+    };
+    return Ok(new_list(pieces.into_iter().map(|piece| result(&args, piece)).collect()));
+}
+
+/* Pieces of data between runs of whitespace, split at most limit times;
+ * the last keeps the rest of the data as it is
+ */
+fn split_whitespace(data: &[u8], limit: usize, from_right: bool) -> Vec<Vec<u8>> {
+    let mut data = data.to_vec();
+    if from_right {
+        data.reverse();
+    }
+    let mut pieces: Vec<Vec<u8>> = Vec::new();
+    let mut pos = 0;
+    loop {
+        while pos < data.len() && is_space(data[pos]) {
+            pos += 1;
+        }
+        if pos == data.len() {
+            break;
+        }
+        if pieces.len() == limit {
+            pieces.push(data[pos..].to_vec());
+            break;
+        }
+        let start = pos;
+        while pos < data.len() && !is_space(data[pos]) {
+            pos += 1;
+        }
+        pieces.push(data[start..pos].to_vec());
+    }
+    if from_right {
+        pieces.reverse();
+        for piece in pieces.iter_mut() {
+            piece.reverse();
+        }
+    }
+    return pieces;
+}
+
+fn split(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    return split_by("split", args, false);
+}
+
+fn rsplit(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    return split_by("rsplit", args, true);
+}
+
+/* splitlines(keepends=False): lines end at \n, \r or \r\n
+ */
+fn splitlines(_interpreter: &mut Interpreter, mut args: Args) -> EvalResult {
+    let keepends = optional_arg(&mut args, 1, "keepends").is_some_and(|keepends| keepends.is_truthy());
+    args.check("splitlines", 1, 2)?;
+    let data = this("splitlines", &args)?;
+    let mut lines: Vec<Value> = Vec::new();
+    let mut start = 0;
+    let mut pos = 0;
+    while pos < data.len() {
+        let boundary = match data[pos] {
+            b'\r' if data.get(pos + 1) == Some(&b'\n') => 2,
+            b'\n' | b'\r' => 1,
+            _ => 0,
+        };
+        if boundary == 0 {
+            pos += 1;
+            continue;
+        }
+        let end = if keepends { pos + boundary } else { pos };
+        lines.push(result(&args, data[start..end].to_vec()));
+        pos += boundary;
+        start = pos;
+    }
+    if start < data.len() {
+        lines.push(result(&args, data[start..].to_vec()));
+    }
+    return Ok(new_list(lines));
+}
+
+fn join(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("join", 2, 2)?;
+    let sep = this("join", &args)?;
+    let mut pieces: Vec<Vec<u8>> = Vec::new();
+    let mut len = Some(0usize);
+    for (index, item) in interpreter.iterate(&args.positional[1])?.into_iter().enumerate() {
+        let piece = contents(&item).ok_or_else(|| {
+            type_error(format!(
+                "sequence item {}: expected a bytes-like object, {} found",
+                index,
+                item.type_name()
+            ))
+        })?;
+        let sep_len = if index > 0 { sep.len() } else { 0 };
+        len = len.and_then(|len| len.checked_add(sep_len)).and_then(|len| len.checked_add(piece.len()));
+        pieces.push(piece);
+    }
+    let mut out = interpreter.allocate_buffer(len)?;
+    for (index, piece) in pieces.iter().enumerate() {
+        if index > 0 {
+            out.extend_from_slice(&sep);
+        }
+        out.extend_from_slice(piece);
+    }
+    return Ok(result(&args, out));
+}
+
+/* replace(old, new, count=-1)
+ */
+fn replace(interpreter: &mut Interpreter, mut args: Args) -> EvalResult {
+    let count = optional_arg(&mut args, 3, "count");
+    args.check("replace", 3, 4)?;
+    let data = this("replace", &args)?;
+    let old = buffer(&args.positional[1])?;
+    let new = buffer(&args.positional[2])?;
+    let limit = match count.map(|count| int_arg(&count)).transpose()? {
+        Some(count) if count >= 0 => count as usize,
+        _ => usize::MAX,
+    };
+    if old.is_empty() {
+        // new goes before each byte and at the end
+        let count = limit.min(data.len() + 1);
+        let mut out = interpreter.allocate_buffer(new.len().checked_mul(count).and_then(|len| len.checked_add(data.len())))?;
+        for (index, byte) in data.iter().enumerate() {
+            if index < limit {
+                out.extend_from_slice(&new);
+            }
+            out.push(*byte);
+        }
+        if data.len() < limit {
+            out.extend_from_slice(&new);
+        }
+        return Ok(result(&args, out));
+    }
+    let found = occurrences(&data, &old, limit, false);
+    let len = match new.len() >= old.len() {
+        true => (new.len() - old.len()).checked_mul(found.len()).and_then(|len| len.checked_add(data.len())),
+        false => Some(data.len() - (old.len() - new.len()) * found.len()),
+    };
+    let mut out = interpreter.allocate_buffer(len)?;
+    let mut start = 0;
+    for pos in found {
+        out.extend_from_slice(&data[start..pos]);
+        out.extend_from_slice(&new);
+        start = pos + old.len();
+    }
+    out.extend_from_slice(&data[start..]);
+    return Ok(result(&args, out));
+}
+
+fn removeprefix(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("removeprefix", 2, 2)?;
+    let data = this("removeprefix", &args)?;
+    let prefix = buffer(&args.positional[1])?;
+    return Ok(result(&args, data.strip_prefix(prefix.as_slice()).unwrap_or(&data).to_vec()));
+}
+
+fn removesuffix(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("removesuffix", 2, 2)?;
+    let data = this("removesuffix", &args)?;
+    let suffix = buffer(&args.positional[1])?;
+    return Ok(result(&args, data.strip_suffix(suffix.as_slice()).unwrap_or(&data).to_vec()));
+}
+
+/* partition() and rpartition(): (before, sep, after) around the first
+ * (or last) sep, or the whole data and two empty ones
+ */
+fn partition_at(name: &str, args: Args, last: bool) -> EvalResult {
+    args.check(name, 2, 2)?;
+    let data = this(name, &args)?;
+    let sep = buffer(&args.positional[1])?;
+    if sep.is_empty() {
+        return Err(value_error("empty separator"));
+    }
+    let found = if last { rfind_in(&data, &sep) } else { find_in(&data, &sep) };
+    let parts = match found {
+        Some(pos) => [&data[..pos], sep.as_slice(), &data[pos + sep.len()..]],
+        None if last => [&[][..], &[][..], data.as_slice()],
+        None => [data.as_slice(), &[][..], &[][..]],
+    };
+    return Ok(new_tuple(parts.iter().map(|part| result(&args, part.to_vec())).collect()));
+}
+
+fn partition(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    return partition_at("partition", args, false);
+}
+
+fn rpartition(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    return partition_at("rpartition", args, true);
+}
+
+/* data padded with left and right fill bytes to make width; center()
+ * gives an odd byte of padding to the left when the width is odd
+ */
+fn justify(interpreter: &mut Interpreter, name: &str, args: Args, left_share: fn(usize, usize) -> usize) -> EvalResult {
+    args.check(name, 2, 3)?;
+    let data = this(name, &args)?;
+    let width = int_arg(&args.positional[1])?.max(0) as usize;
+    let fill = match args.positional.get(2) {
+        None => b' ',
+        Some(fill) => match contents(fill) {
+            Some(fill) if fill.len() == 1 => fill[0],
+            _ => {
+                return Err(type_error(format!(
+                    "{}() argument 2 must be a byte string of length 1, not {}",
+                    name,
+                    fill.type_name()
+                )));
+            }
+        },
+    };
+    let padding = width.saturating_sub(data.len());
+    let left = left_share(padding, width);
+    let mut out = interpreter.allocate_buffer(data.len().checked_add(padding))?;
+    out.resize(left, fill);
+    out.extend(data);
+    out.resize(out.len() + padding - left, fill);
+    return Ok(result(&args, out));
+}
+
+fn ljust(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    return justify(interpreter, "ljust", args, |_, _| 0);
+}
+
+fn rjust(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    return justify(interpreter, "rjust", args, |padding, _| padding);
+}
+
+fn center(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    return justify(interpreter, "center", args, |padding, width| padding / 2 + (padding & width & 1));
+}
+
+/* zfill(width): zeros on the left, after any sign
+ */
+fn zfill(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("zfill", 2, 2)?;
+    let data = this("zfill", &args)?;
+    let width = int_arg(&args.positional[1])?.max(0) as usize;
+    let zeros = width.saturating_sub(data.len());
+    let sign = usize::from(matches!(data.first(), Some(b'+' | b'-')));
+    let mut out = interpreter.allocate_buffer(data.len().checked_add(zeros))?;
+    out.extend_from_slice(&data[..sign]);
+    out.resize(sign + zeros, b'0');
+    out.extend_from_slice(&data[sign..]);
+    return Ok(result(&args, out));
+}
+
+/* expandtabs(tabsize=8): each tab spaces to the next multiple of tabsize
+ * columns, counting from the start of the line
+ */
+fn expandtabs(interpreter: &mut Interpreter, mut args: Args) -> EvalResult {
+    let tabsize = optional_arg(&mut args, 1, "tabsize");
+    args.check("expandtabs", 1, 2)?;
+    let data = this("expandtabs", &args)?;
+    let tabsize = match tabsize {
+        Some(tabsize) => int_arg(&tabsize)?,
+        None => 8,
+    };
+    let mut out: Vec<u8> = Vec::new();
+    let mut column: i64 = 0;
+    for (offset, byte) in data.into_iter().enumerate() {
+        if offset % TICK_BYTES == 0 && offset > 0 {
+            interpreter.tick()?;
+        }
+        match byte {
+            b'\t' if tabsize > 0 => {
+                let spaces = tabsize - column % tabsize;
+                interpreter.allocate(spaces as usize)?;
+                out.try_reserve(spaces as usize).map_err(|_| Exception::new("MemoryError", ""))?;
+                out.resize(out.len() + spaces as usize, b' ');
+                column += spaces;
+            }
+            b'\t' => (),
+            b'\n' | b'\r' => {
+                out.push(byte);
+                column = 0;
+            }
+            byte => {
+                out.push(byte);
+                column += 1;
+            }
+        };
+    }
+    return Ok(result(&args, out));
+}
+
+/* bytes.maketrans(from, to): a table for translate() mapping each byte
+ * of from to the one of to
+ */
+fn maketrans(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    // called on an instance, the instance comes first
+    let skip = args.positional.len().saturating_sub(2);
+    args.check("maketrans", 2, 3)?;
+    let from = buffer(&args.positional[skip])?;
+    let to = buffer(&args.positional[skip + 1])?;
+    if from.len() != to.len() {
+        return Err(value_error("maketrans arguments must have same length"));
+    }
+    let mut table: Vec<u8> = (0..=255).collect();
+    for (from, to) in from.into_iter().zip(to) {
+        table[from as usize] = to;
+    }
+    return Ok(new_bytes(table));
+}
+
+/* translate(table, delete=b''): each byte mapped through the table of 256
+ * bytes (or kept, if it is None), and the bytes in delete left out
+ */
+fn translate(_interpreter: &mut Interpreter, mut args: Args) -> EvalResult {
+    let delete = optional_arg(&mut args, 2, "delete");
+    args.check("translate", 2, 3)?;
+    let data = this("translate", &args)?;
+    let table = match &args.positional[1] {
+        Value::NoneT => None,
+        table => Some(buffer(table)?),
+    };
+    if table.as_ref().is_some_and(|table| table.len() != 256) {
+        return Err(value_error("translation table must be 256 characters long"));
+    }
+    let delete = match delete {
+        Some(delete) => buffer(&delete)?,
+        None => Vec::new(),
+    };
+    let out = data
+        .into_iter()
+        .filter(|byte| !delete.contains(byte))
+        .map(|byte| table.as_ref().map_or(byte, |table| table[byte as usize]))
+        .collect();
+    return Ok(result(&args, out));
+}
+
+/* decode(encoding='utf-8', errors='strict')
+ */
+fn decode_method(_interpreter: &mut Interpreter, mut args: Args) -> EvalResult {
+    let (encoding, errors) = codec_args("decode", &mut args, 1)?;
+    args.check("decode", 1, 3)?;
+    let data = this("decode", &args)?;
+    return Ok(Value::Str(decode(&data, &encoding, &errors)?));
+}
+
+/* The hex digits of data, with sep between groups of bytes_per_sep bytes
+ * counted from the right (or from the left, if it is negative)
+ */
+fn to_hex(name: &str, data: &[u8], mut args: Args) -> Result<String, Exception> {
+    let sep = optional_arg(&mut args, 1, "sep");
+    let bytes_per_sep = optional_arg(&mut args, 2, "bytes_per_sep");
+    args.check(name, 1, 3)?;
+    let digits: Vec<String> = data.iter().map(|byte| format!("{:02x}", byte)).collect();
+    let sep = match sep {
+        None => return Ok(digits.concat()),
+        Some(Value::Str(ref sep)) if sep.chars().count() == 1 => sep.clone(),
+        Some(sep) => match contents(&sep) {
+            Some(sep) if sep.len() == 1 => String::from_utf8_lossy(&sep).into_owned(),
+            Some(_) => return Err(value_error("sep must be length 1.")),
+            None if matches!(sep, Value::Str(_)) => return Err(value_error("sep must be length 1.")),
+            None => return Err(type_error(String::from("sep must be str or bytes."))),
+        },
+    };
+    if !sep.is_ascii() {
+        return Err(value_error("sep must be ASCII."));
+    }
+    let per = match bytes_per_sep {
+        Some(per) => int_arg(&per)?,
+        None => 1,
+    };
+    if per == 0 {
+        return Ok(digits.concat());
+    }
+    let size = per.unsigned_abs() as usize;
+    // groups from the right leave the short one on the left
+    let first = if per > 0 && !digits.len().is_multiple_of(size) { digits.len() % size } else { size };
+    let mut groups: Vec<String> = vec![digits[..first.min(digits.len())].concat()];
+    groups.extend(digits[first.min(digits.len())..].chunks(size).map(|group| group.concat()));
+    return Ok(groups.join(&sep));
+}
+
+/* hex(sep=None, bytes_per_sep=1)
+ */
+fn hex(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    let data = this("hex", &args)?;
+    return Ok(Value::Str(to_hex("hex", &data, args)?));
+}
+
+/* bytes.fromhex(string): two hex digits per byte, which may have ASCII
+ * whitespace between them
+ */
+fn parse_hex(args: &Args) -> Result<Vec<u8>, Exception> {
+    // called on an instance, the instance comes first
+    let text = match args.positional.last() {
+        Some(Value::Str(text)) if args.positional.len() <= 2 => text,
+        Some(other) if args.positional.len() <= 2 => {
+            return Err(type_error(format!("fromhex() argument must be str, not {}", other.type_name())));
+        }
+        _ => return Err(type_error(format!("fromhex() takes exactly one argument ({} given)", args.positional.len()))),
+    };
+    let chars: Vec<char> = text.chars().collect();
+    let mut out: Vec<u8> = Vec::new();
+    let mut pos = 0;
+    loop {
+        while pos < chars.len() && chars[pos].is_ascii() && is_space(chars[pos] as u8) {
+            pos += 1;
+        }
+        if pos == chars.len() {
+            return Ok(out);
+        }
+        let digit = |pos: usize| chars.get(pos).and_then(|c| c.to_digit(16)).ok_or_else(|| {
+            value_error(&format!("non-hexadecimal number found in fromhex() arg at position {}", pos))
+        });
+        out.push((digit(pos)? * 16 + digit(pos + 1)?) as u8);
+        pos += 2;
+    }
+}
+
+fn bytes_fromhex(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    return Ok(new_bytes(parse_hex(&args)?));
+}
+
+fn bytearray_fromhex(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    return Ok(new_bytearray(parse_hex(&args)?));
+}
+
+/* bytearray methods
+ */
+
+fn this_array(name: &str, args: &Args) -> Result<Rc<ByteArray>, Exception> {
+    match args.positional.first() {
+        Some(Value::ByteArray(array)) => return Ok(array.clone()),
+        other => {
+            return Err(type_error(format!(
+                "descriptor '{}' for 'bytearray' objects doesn't apply to a '{}' object",
+                name,
+                other.map_or("nothing", Value::type_name)
+            )));
+        }
+    }
+}
+
+/* A bytearray with memoryviews can't change size
+ */
+pub fn check_resizable(array: &ByteArray) -> Result<(), Exception> {
+    if array.exports.get() > 0 {
+        return Err(Exception::new("BufferError", "Existing exports of data: object cannot be re-sized"));
+    }
+    return Ok(());
+}
+
+fn append(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("append", 2, 2)?;
+    let array = this_array("append", &args)?;
+    let byte = byte_value(&args.positional[1])?;
+    check_resizable(&array)?;
+    array.data.borrow_mut().push(byte);
+    return Ok(Value::NoneT);
+}
+
+fn extend(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("extend", 2, 2)?;
+    let array = this_array("extend", &args)?;
+    let data = match contents(&args.positional[1]) {
+        Some(data) => data,
+        None => interpreter.iterate(&args.positional[1])?.iter().map(byte_value).collect::<Result<Vec<u8>, Exception>>()?,
+    };
+    check_resizable(&array)?;
+    array.data.borrow_mut().extend(data);
+    return Ok(Value::NoneT);
+}
+
+fn insert(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("insert", 3, 3)?;
+    let array = this_array("insert", &args)?;
+    let index = int_arg(&args.positional[1])?;
+    let byte = byte_value(&args.positional[2])?;
+    check_resizable(&array)?;
+    let mut data = array.data.borrow_mut();
+    let len = data.len() as i64;
+    let position = if index < 0 { (index + len).max(0) } else { index.min(len) };
+    data.insert(position as usize, byte);
+    return Ok(Value::NoneT);
+}
+
+/* pop(index=-1)
+ */
+fn pop(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("pop", 1, 2)?;
+    let array = this_array("pop", &args)?;
+    let index = match args.positional.get(1) {
+        Some(index) => int_arg(index)?,
+        None => -1,
+    };
+    check_resizable(&array)?;
+    let mut data = array.data.borrow_mut();
+    if data.is_empty() {
+        return Err(Exception::new("IndexError", "pop from empty bytearray"));
+    }
+    let len = data.len() as i64;
+    let position = if index < 0 { index + len } else { index };
+    if position < 0 || position >= len {
+        return Err(Exception::new("IndexError", "pop index out of range"));
+    }
+    return Ok(Value::Int(data.remove(position as usize) as i64));
+}
+
+fn remove(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("remove", 2, 2)?;
+    let array = this_array("remove", &args)?;
+    let byte = byte_value(&args.positional[1])?;
+    check_resizable(&array)?;
+    let mut data = array.data.borrow_mut();
+    match data.iter().position(|other| *other == byte) {
+        Some(position) => data.remove(position),
+        None => return Err(value_error("value not found in bytearray")),
+    };
+    return Ok(Value::NoneT);
+}
+
+fn reverse(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("reverse", 1, 1)?;
+    this_array("reverse", &args)?.data.borrow_mut().reverse();
+    return Ok(Value::NoneT);
+}
+
+fn clear(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("clear", 1, 1)?;
+    let array = this_array("clear", &args)?;
+    check_resizable(&array)?;
+    array.data.borrow_mut().clear();
+    return Ok(Value::NoneT);
+}
+
+fn copy(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("copy", 1, 1)?;
+    return Ok(new_bytearray(this_array("copy", &args)?.data.borrow().clone()));
+}
+
+/* memoryview methods and attributes
+ */
+
+fn this_view(name: &str, args: &Args) -> Result<Rc<MemoryView>, Exception> {
+    match args.positional.first() {
+        Some(Value::MemoryView(view)) => {
+            if name != "release" {
+                view.check()?;
+            }
+            return Ok(view.clone());
+        }
+        other => {
+            return Err(type_error(format!(
+                "descriptor '{}' for 'memoryview' objects doesn't apply to a '{}' object",
+                name,
+                other.map_or("nothing", Value::type_name)
+            )));
+        }
+    }
+}
+
+fn tobytes(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("tobytes", 1, 1)?;
+    return Ok(new_bytes(this_view("tobytes", &args)?.to_vec()));
+}
+
+fn tolist(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("tolist", 1, 1)?;
+    let data = this_view("tolist", &args)?.to_vec();
+    return Ok(new_list(data.into_iter().map(|byte| Value::Int(byte as i64)).collect()));
+}
+
+fn view_hex(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    let data = this_view("hex", &args)?.to_vec();
+    return Ok(Value::Str(to_hex("hex", &data, args)?));
+}
+
+/* release(): let go of the bytes now, so a bytearray can be resized again
+ */
+fn release(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("release", 1, 1)?;
+    this_view("release", &args)?.release();
+    return Ok(Value::NoneT);
+}
+
+fn toreadonly(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("toreadonly", 1, 1)?;
+    let view = this_view("toreadonly", &args)?;
+    return Ok(new_view(view.source.clone(), view.start, view.step, view.len, true));
+}
+
+pub const VIEW_ATTRIBUTES: &[&str] = &[
+    "obj", "nbytes", "readonly", "itemsize", "format", "ndim", "shape", "strides", "contiguous", "c_contiguous",
+    "f_contiguous",
+];
+
+/* view.attr for the names in VIEW_ATTRIBUTES. A view is one-dimensional,
+ * of unsigned bytes.
+ */
+pub fn view_attribute(view: &MemoryView, attr: &str) -> EvalResult {
+    view.check()?;
+    let contiguous = view.step == 1 || view.len <= 1;
+    match attr {
+        "obj" => return Ok(view.source.clone()),
+        "nbytes" => return Ok(Value::Int(view.len as i64)),
+        "readonly" => return Ok(Value::Bool(view.readonly)),
+        "itemsize" | "ndim" => return Ok(Value::Int(1)),
+        "format" => return Ok(Value::Str(String::from("B"))),
+        "shape" => return Ok(new_tuple(vec![Value::Int(view.len as i64)])),
+        "strides" => return Ok(new_tuple(vec![Value::Int(view.step as i64)])),
+        _ => return Ok(Value::Bool(contiguous)),
+    }
+}
+
+/* The position view[index] refers to
+ */
+pub fn view_index(view: &MemoryView, index: &Value) -> Result<usize, Exception> {
+    view.check()?;
+    let index = as_int(index).ok_or_else(|| type_error(String::from("memoryview: invalid slice key")))?;
+    let position = if index < 0 { index + view.len as i64 } else { index };
+    if position < 0 || position >= view.len as i64 {
+        return Err(Exception::new("IndexError", "index out of bounds on dimension 1"));
+    }
+    return Ok(position as usize);
+}
+
+/* view[index] = value, for views of a bytearray
+ */
+pub fn set_view_item(view: &MemoryView, index: &Value, value: &Value) -> Result<(), Exception> {
+    let position = view_index(view, index)?;
+    if view.readonly {
+        return Err(type_error(String::from("cannot modify read-only memory")));
+    }
+    let byte = match as_int(value) {
+        Some(val) => u8::try_from(val).map_err(|_| value_error("memoryview: invalid value for format 'B'"))?,
+        None => return Err(type_error(String::from("memoryview: invalid type for format 'B'"))),
+    };
+    view.set(position, byte);
+    return Ok(());
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testing::{eval, exec, python};
+
+    #[test]
+    fn test_constructors() {
+        let mut python = python("");
+        assert_eq!(eval(&mut python, "(b'a\\x00\\xff\\'', rb'\\n', b'ab' b'c', bytes(3), bytes([1, 2]), bytes('é', 'utf-8'), bytearray(b'x'))"), "(b\"a\\x00\\xff'\", b'\\\\n', b'abc', b'\\x00\\x00\\x00', b'\\x01\\x02', b'\\xc3\\xa9', bytearray(b'x'))");
+    }
+
+    #[test]
+    fn test_sequence() {
+        let mut python = python("");
+        assert_eq!(eval(&mut python, "(b'abc'[1], b'abc'[-1:], b'abc' + bytearray(b'd'), bytearray(b'a') * 2, b'b' in b'abc', 99 in b'abc', b'a' == bytearray(b'a'), b'a' < b'b')"), "(98, b'c', b'abcd', bytearray(b'aa'), True, True, True, True)");
+    }
+
+    #[test]
+    fn test_methods() {
+        let mut python = python("");
+        assert_eq!(eval(&mut python, "(b'a,b,,c'.split(b','), b' a b '.split(), b' a b '.rsplit(None, 1), b'abc'.find(b'c'), b'abcb'.rfind(98), b'aaa'.count(b'a'), b'-'.join([b'a', bytearray(b'b')]))"), "([b'a', b'b', b'', b'c'], [b'a', b'b'], [b' a', b'b'], 2, 3, 3, b'a-b')");
+        assert_eq!(eval(&mut python, "(b'  x '.strip(), b'xyx'.strip(b'x'), b'Hello'.upper(), b'hello world'.title(), b'ab'.center(5, b'*'), b'-1'.zfill(4), b'a=b'.partition(b'='), b'abc'.replace(b'', b'-'))"), "(b'x', b'y', b'HELLO', b'Hello World', b'**ab*', b'-001', (b'a', b'=', b'b'), b'-a-b-c-')");
+        assert_eq!(eval(&mut python, "(b'\\x01\\xab'.hex(), b'\\x01\\x02\\x03'.hex(':', 2), bytes.fromhex('01 ab'), bytearray.fromhex('ff'), b'abc'.translate(bytes.maketrans(b'ab', b'xy'), b'c'))"), "('01ab', '01:0203', b'\\x01\\xab', bytearray(b'\\xff'), b'xy')");
+        assert_eq!(eval(&mut python, "(b'abab'.replace(b'ab', b'xyz'), b'abab'.replace(b'ab', b'', 1), b'abc'.replace(b'', b'-', 2), bytearray(b'ab').ljust(4, b'.'), b'+1'.zfill(4), b'a\\tb'.expandtabs(4), b''.join([]), bytes(3), b'ab' * 2)"), "(b'xyzxyz', b'ab', b'-a-bc', bytearray(b'ab..'), b'+001', b'a   b', b'', b'\\x00\\x00\\x00', b'abab')");
+    }
+
+    #[test]
+    fn test_codecs() {
+        let mut python = python("");
+        assert_eq!(eval(&mut python, "('héllo'.encode(), 'héllo'.encode('ascii', 'replace'), 'héllo'.encode('latin-1'), b'h\\xc3\\xa9'.decode(), b'h\\xff'.decode('utf-8', 'replace'), b'h\\xff'.decode('ascii', 'ignore'), b'\\xe9'.decode('latin-1'))"), "(b'h\\xc3\\xa9llo', b'h?llo', b'h\\xe9llo', 'hé', 'h\u{fffd}', 'h', 'é')");
+    }
+
+    #[test]
+    fn test_bytearray_and_memoryview() {
+        let mut python = python("");
+        // a bytearray changes in place; views of it see the changes
+        exec(&mut python, "a = bytearray(b'hello')\na.append(33)\na[0] = 72\na[1:3] = b'EE'\nv = memoryview(a)\nw = v[1::2]\nw[0] = 120\n");
+        assert_eq!(eval(&mut python, "(a, bytes(w), w.tolist(), len(w), w.readonly, w.obj is a, v[-1], list(v[:2]), v == b'HxElo!')"), "(bytearray(b'HxElo!'), b'xl!', [120, 108, 33], 3, False, True, 33, [72, 120], True)");
+        assert_eq!(eval(&mut python, "a.pop()"), "BufferError: Existing exports of data: object cannot be re-sized");
+        assert_eq!(eval(&mut python, "(v.release(), w.release(), a.pop(), a)[2:]"), "(33, bytearray(b'HxElo'))");
+        assert_eq!(eval(&mut python, "v[0]"), "ValueError: operation forbidden on released memoryview object");
+        assert_eq!(eval(&mut python, "memoryview(b'ab')[0:1] == b'a', hash(memoryview(b'ab')) == hash(b'ab')"), "(True, True)");
+    }
+
+    #[test]
+    fn test_errors() {
+        let mut python = python("");
+        assert_eq!(eval(&mut python, "'é'.encode('ascii')"), "UnicodeEncodeError: 'ascii' codec can't encode character '\\xe9' in position 0: ordinal not in range(128)");
+        assert_eq!(eval(&mut python, "b'a\\xe4\\xb8'.decode()"), "UnicodeDecodeError: 'utf-8' codec can't decode bytes in position 1-2: unexpected end of data");
+        assert_eq!(eval(&mut python, "b'\\x80'.decode()"), "UnicodeDecodeError: 'utf-8' codec can't decode byte 0x80 in position 0: invalid start byte");
+        assert_eq!(eval(&mut python, "'x'.encode('rot13')"), "LookupError: unknown encoding: rot13");
+        assert_eq!(eval(&mut python, "b'a' + 'b'"), "TypeError: can't concat str to bytes");
+        assert_eq!(eval(&mut python, "bytes([256])"), "ValueError: bytes must be in range(0, 256)");
+        assert_eq!(eval(&mut python, "bytes('a')"), "TypeError: string argument without an encoding");
+        assert_eq!(eval(&mut python, "bytes.fromhex('0g')"), "ValueError: non-hexadecimal number found in fromhex() arg at position 1");
+        assert_eq!(eval(&mut python, "hash(bytearray())"), "TypeError: unhashable type: 'bytearray'");
+        let err = python.exec("memoryview(b'ab')[0] = 1").unwrap_err();
+        assert_eq!(err.to_string(), "TypeError: cannot modify read-only memory");
+    }
+}
@@ -0,0 +1,964 @@
+use crate::ast::{
+    Arg, BinOp, BoolOp, ClassDef, CmpOp, Comprehension, ComprehensionFor, ComprehensionKind, Expr, FunctionDef, Handler,
+    Params, Stmt, StmtKind, UnaryOp,
+};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::time::UNIX_EPOCH;
+
+/* On-disk cache of compiled scripts, the equivalent of CPython's .pyc files.
+ * What is cached is the optimized syntax tree, so a cache hit skips lexing,
+ * parsing and the optimizer pass.
+ *
+ * Cache files live in a __pycache__ directory beside the source, named
+ * <stem>.rebar-<version>.rbc, or <stem>.rebar-<version>.opt-<level>.rbc
+ * for code optimized with -O. Layout (all integers little-endian):
+ *
+ *   magic      4 bytes  b"RBAR"
+ *   version    u16      CACHE_VERSION
+ *   mtime      u64 + u32  source modification time (secs, nanos)
+ *   size       u64      source length in bytes
+ *   code       the module's statements (see encode_block)
+ *
+ * A cache is only used when the recorded mtime and size match the source.
+ * Bump CACHE_VERSION whenever the encoding, the syntax tree, or what the
+ * parser or optimizer produce for the same source changes.
+ */
+const MAGIC: &[u8; 4] = b"RBAR";
+pub const CACHE_VERSION: u16 = 15;
+
+// deepest nesting of expressions/statements a cache file may contain, so a
+// corrupt file can't overflow the stack while it is decoded. Deeper code is
+// still run, it just isn't loaded from the cache.
+const MAX_DEPTH: usize = 200;
+
+// statement tags
+const STMT_EXPR: u8 = 0;
+const STMT_ASSIGN: u8 = 1;
+const STMT_IF: u8 = 2;
+const STMT_WHILE: u8 = 3;
+const STMT_FOR: u8 = 4;
+const STMT_WITH: u8 = 5;
+const STMT_FUNCTION_DEF: u8 = 6;
+const STMT_RETURN: u8 = 7;
+const STMT_GLOBAL: u8 = 8;
+const STMT_NONLOCAL: u8 = 9;
+const STMT_ASSERT: u8 = 10;
+const STMT_IMPORT: u8 = 11;
+const STMT_IMPORT_FROM: u8 = 12;
+const STMT_PASS: u8 = 13;
+const STMT_BREAK: u8 = 14;
+const STMT_CONTINUE: u8 = 15;
+const STMT_AUG_ASSIGN: u8 = 16;
+const STMT_DELETE: u8 = 17;
+const STMT_TRY: u8 = 18;
+const STMT_RAISE: u8 = 19;
+const STMT_CLASS_DEF: u8 = 20;
+
+// expression tags
+const EXPR_NONE: u8 = 0;
+const EXPR_BOOL: u8 = 1;
+const EXPR_INT: u8 = 2;
+const EXPR_FLOAT: u8 = 3;
+const EXPR_IMAGINARY: u8 = 4;
+const EXPR_STR: u8 = 5;
+const EXPR_BYTES: u8 = 6;
+const EXPR_NAME: u8 = 7;
+const EXPR_ATTRIBUTE: u8 = 8;
+const EXPR_LIST: u8 = 9;
+const EXPR_TUPLE: u8 = 10;
+const EXPR_UNARY_OP: u8 = 11;
+const EXPR_BIN_OP: u8 = 12;
+const EXPR_BOOL_OP: u8 = 13;
+const EXPR_COMPARE: u8 = 14;
+const EXPR_CALL: u8 = 15;
+const EXPR_SUBSCRIPT: u8 = 16;
+const EXPR_SLICE: u8 = 17;
+const EXPR_DICT: u8 = 18;
+const EXPR_LAMBDA: u8 = 19;
+const EXPR_IF_EXP: u8 = 20;
+const EXPR_COMPREHENSION: u8 = 21;
+const EXPR_SET: u8 = 22;
+const EXPR_BIG_INT: u8 = 23;
+
+// argument tags
+const ARG_POSITIONAL: u8 = 0;
+const ARG_KEYWORD: u8 = 1;
+const ARG_STAR: u8 = 2;
+const ARG_DOUBLE_STAR: u8 = 3;
+
+/* operators are stored as their index in these tables. Only append to
+ * them - reordering invalidates existing caches.
+ */
+const BIN_OPS: &[BinOp] = &[
+    BinOp::Add,
+    BinOp::Sub,
+    BinOp::Mul,
+    BinOp::Div,
+    BinOp::FloorDiv,
+    BinOp::Mod,
+    BinOp::Pow,
+    BinOp::BitAnd,
+    BinOp::BitOr,
+    BinOp::BitXor,
+    BinOp::LShift,
+    BinOp::RShift,
+];
+const UNARY_OPS: &[UnaryOp] = &[UnaryOp::Neg, UnaryOp::Pos, UnaryOp::Not, UnaryOp::Invert];
+const CMP_OPS: &[CmpOp] = &[
+    CmpOp::Eq,
+    CmpOp::NotEq,
+    CmpOp::Lt,
+    CmpOp::LtE,
+    CmpOp::Gt,
+    CmpOp::GtE,
+    CmpOp::Is,
+    CmpOp::IsNot,
+    CmpOp::In,
+    CmpOp::NotIn,
+];
+const BOOL_OPS: &[BoolOp] = &[BoolOp::And, BoolOp::Or];
+const COMPREHENSION_KINDS: &[ComprehensionKind] =
+    &[ComprehensionKind::List, ComprehensionKind::Dict, ComprehensionKind::Set, ComprehensionKind::Generator];
+
+/* location of the cache file for a given source file and -O level
+ */
+pub fn cache_path(source: &Path, optimize: u8) -> PathBuf {
+    let dir = match source.parent() {
+        Some(parent) => parent.join("__pycache__"),
+        None => PathBuf::from("__pycache__"),
+    };
+    let stem = source
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    if optimize > 0 {
+        return dir.join(format!("{}.rebar-{}.opt-{}.rbc", stem, CACHE_VERSION, optimize));
+    }
+    return dir.join(format!("{}.rebar-{}.rbc", stem, CACHE_VERSION));
+}
+
+/* (mtime secs, mtime nanos, size) of the source, used for invalidation
+ */
+fn source_stamp(source: &Path) -> Option<(u64, u32, u64)> {
+    let metadata = fs::metadata(source).ok()?;
+    let mtime = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+    return Some((mtime.as_secs(), mtime.subsec_nanos(), metadata.len()));
+}
+
+fn encode_len(len: usize, out: &mut Vec<u8>) {
+    out.extend_from_slice(&(len as u64).to_le_bytes());
+}
+
+fn encode_str(val: &str, out: &mut Vec<u8>) {
+    encode_len(val.len(), out);
+    out.extend_from_slice(val.as_bytes());
+}
+
+fn encode_op<T: PartialEq>(op: &T, table: &[T], out: &mut Vec<u8>) {
+    out.push(table.iter().position(|entry| entry == op).unwrap() as u8);
+}
+
+fn encode_names(names: &[String], out: &mut Vec<u8>) {
+    encode_len(names.len(), out);
+    for name in names {
+        encode_str(name, out);
+    }
+}
+
+/* (name, alias) pairs of an import statement
+ */
+fn encode_aliases(names: &[(String, Option<String>)], out: &mut Vec<u8>) {
+    encode_len(names.len(), out);
+    for (name, alias) in names {
+        encode_str(name, out);
+        match alias {
+            Some(alias) => {
+                out.push(1);
+                encode_str(alias, out);
+            }
+            None => out.push(0),
+        };
+    }
+}
+
+fn encode_optional(expr: Option<&Expr>, out: &mut Vec<u8>) {
+    match expr {
+        Some(expr) => {
+            out.push(1);
+            encode_expr(expr, out);
+        }
+        None => out.push(0),
+    };
+}
+
+fn encode_exprs(exprs: &[Expr], out: &mut Vec<u8>) {
+    encode_len(exprs.len(), out);
+    for expr in exprs {
+        encode_expr(expr, out);
+    }
+}
+
+/* count followed by each statement: its line (u32), tag and payload
+ */
+fn encode_block(block: &[Stmt], out: &mut Vec<u8>) {
+    encode_len(block.len(), out);
+    for stmt in block {
+        encode_stmt(stmt, out);
+    }
+}
+
+fn encode_params(params: &[(String, Option<Expr>)], out: &mut Vec<u8>) {
+    encode_len(params.len(), out);
+    for (name, default) in params {
+        encode_str(name, out);
+        encode_optional(default.as_ref(), out);
+    }
+}
+
+fn encode_function(def: &FunctionDef, out: &mut Vec<u8>) {
+    encode_str(&def.name, out);
+    encode_params(&def.params.args, out);
+    encode_names(def.params.vararg.as_slice(), out);
+    encode_params(&def.params.kwonly, out);
+    encode_names(def.params.kwarg.as_slice(), out);
+    encode_block(&def.body, out);
+    encode_names(&def.locals, out);
+    encode_names(def.doc.as_slice(), out);
+    encode_exprs(&def.decorators, out);
+}
+
+fn encode_args(args: &[Arg], out: &mut Vec<u8>) {
+    encode_len(args.len(), out);
+    for arg in args {
+        match arg {
+            Arg::Positional(value) => {
+                out.push(ARG_POSITIONAL);
+                encode_expr(value, out);
+            }
+            Arg::Keyword(name, value) => {
+                out.push(ARG_KEYWORD);
+                encode_str(name, out);
+                encode_expr(value, out);
+            }
+            Arg::Star(value) => {
+                out.push(ARG_STAR);
+                encode_expr(value, out);
+            }
+            Arg::DoubleStar(value) => {
+                out.push(ARG_DOUBLE_STAR);
+                encode_expr(value, out);
+            }
+        };
+    }
+}
+
+fn encode_stmt(stmt: &Stmt, out: &mut Vec<u8>) {
+    out.extend_from_slice(&(stmt.line as u32).to_le_bytes());
+    let stmt = &stmt.kind;
+    match stmt {
+        StmtKind::Expr(expr) => {
+            out.push(STMT_EXPR);
+            encode_expr(expr, out);
+        }
+        StmtKind::Assign(targets, value) => {
+            out.push(STMT_ASSIGN);
+            encode_exprs(targets, out);
+            encode_expr(value, out);
+        }
+        StmtKind::AugAssign(target, op, value) => {
+            out.push(STMT_AUG_ASSIGN);
+            encode_expr(target, out);
+            encode_op(op, BIN_OPS, out);
+            encode_expr(value, out);
+        }
+        StmtKind::Delete(targets) => {
+            out.push(STMT_DELETE);
+            encode_exprs(targets, out);
+        }
+        StmtKind::If(test, body, orelse) | StmtKind::While(test, body, orelse) => {
+            out.push(if let StmtKind::If(..) = stmt { STMT_IF } else { STMT_WHILE });
+            encode_expr(test, out);
+            encode_block(body, out);
+            encode_block(orelse, out);
+        }
+        StmtKind::For(target, iter, body, orelse) => {
+            out.push(STMT_FOR);
+            encode_expr(target, out);
+            encode_expr(iter, out);
+            encode_block(body, out);
+            encode_block(orelse, out);
+        }
+        StmtKind::With(items, body) => {
+            out.push(STMT_WITH);
+            encode_len(items.len(), out);
+            for (context, target) in items {
+                encode_expr(context, out);
+                encode_optional(target.as_ref(), out);
+            }
+            encode_block(body, out);
+        }
+        StmtKind::Try(body, handlers, orelse, finalbody) => {
+            out.push(STMT_TRY);
+            encode_block(body, out);
+            encode_len(handlers.len(), out);
+            for handler in handlers {
+                encode_optional(handler.kind.as_ref(), out);
+                encode_names(handler.name.as_slice(), out);
+                encode_block(&handler.body, out);
+                out.extend_from_slice(&(handler.line as u32).to_le_bytes());
+            }
+            encode_block(orelse, out);
+            encode_block(finalbody, out);
+        }
+        StmtKind::Raise(exc, cause) => {
+            out.push(STMT_RAISE);
+            encode_optional(exc.as_ref(), out);
+            encode_optional(cause.as_ref(), out);
+        }
+        StmtKind::FunctionDef(def) => {
+            out.push(STMT_FUNCTION_DEF);
+            encode_function(def, out);
+        }
+        StmtKind::ClassDef(def) => {
+            out.push(STMT_CLASS_DEF);
+            encode_str(&def.name, out);
+            encode_args(&def.bases, out);
+            encode_block(&def.body, out);
+            encode_exprs(&def.decorators, out);
+        }
+        StmtKind::Return(value) => {
+            out.push(STMT_RETURN);
+            encode_optional(value.as_ref(), out);
+        }
+        StmtKind::Global(names) | StmtKind::Nonlocal(names) => {
+            out.push(if let StmtKind::Global(_) = stmt { STMT_GLOBAL } else { STMT_NONLOCAL });
+            encode_names(names, out);
+        }
+        StmtKind::Assert(test, msg) => {
+            out.push(STMT_ASSERT);
+            encode_expr(test, out);
+            encode_optional(msg.as_ref(), out);
+        }
+        StmtKind::Import(names) => {
+            out.push(STMT_IMPORT);
+            encode_aliases(names, out);
+        }
+        StmtKind::ImportFrom(level, module, names) => {
+            out.push(STMT_IMPORT_FROM);
+            encode_len(*level, out);
+            encode_str(module, out);
+            encode_aliases(names, out);
+        }
+        StmtKind::Pass => out.push(STMT_PASS),
+        StmtKind::Break => out.push(STMT_BREAK),
+        StmtKind::Continue => out.push(STMT_CONTINUE),
+    };
+}
+
+fn encode_expr(expr: &Expr, out: &mut Vec<u8>) {
+    match expr {
+        Expr::NoneT => out.push(EXPR_NONE),
+        Expr::Bool(val) => {
+            out.push(EXPR_BOOL);
+            out.push(*val as u8);
+        }
+        Expr::Int(val) => {
+            out.push(EXPR_INT);
+            out.extend_from_slice(&val.to_le_bytes());
+        }
+        Expr::BigInt(val) => {
+            out.push(EXPR_BIG_INT);
+            encode_str(&val.to_string(), out);
+        }
+        Expr::Float(val) | Expr::Imaginary(val) => {
+            out.push(if let Expr::Float(_) = expr { EXPR_FLOAT } else { EXPR_IMAGINARY });
+            out.extend_from_slice(&val.to_bits().to_le_bytes());
+        }
+        Expr::Str(val) => {
+            out.push(EXPR_STR);
+            encode_str(val, out);
+        }
+        Expr::Bytes(val) => {
+            out.push(EXPR_BYTES);
+            encode_len(val.len(), out);
+            out.extend_from_slice(val);
+        }
+        Expr::Name(name) => {
+            out.push(EXPR_NAME);
+            encode_str(name, out);
+        }
+        Expr::Attribute(value, attr) => {
+            out.push(EXPR_ATTRIBUTE);
+            encode_expr(value, out);
+            encode_str(attr, out);
+        }
+        Expr::List(items) | Expr::Tuple(items) => {
+            out.push(if let Expr::List(_) = expr { EXPR_LIST } else { EXPR_TUPLE });
+            encode_exprs(items, out);
+        }
+        Expr::UnaryOp(op, operand) => {
+            out.push(EXPR_UNARY_OP);
+            encode_op(op, UNARY_OPS, out);
+            encode_expr(operand, out);
+        }
+        Expr::BinOp(left, op, right) => {
+            out.push(EXPR_BIN_OP);
+            encode_expr(left, out);
+            encode_op(op, BIN_OPS, out);
+            encode_expr(right, out);
+        }
+        Expr::BoolOp(left, op, right) => {
+            out.push(EXPR_BOOL_OP);
+            encode_expr(left, out);
+            encode_op(op, BOOL_OPS, out);
+            encode_expr(right, out);
+        }
+        Expr::Compare(first, rest) => {
+            out.push(EXPR_COMPARE);
+            encode_expr(first, out);
+            encode_len(rest.len(), out);
+            for (op, operand) in rest {
+                encode_op(op, CMP_OPS, out);
+                encode_expr(operand, out);
+            }
+        }
+        Expr::Call(func, args) => {
+            out.push(EXPR_CALL);
+            encode_expr(func, out);
+            encode_args(args, out);
+        }
+        Expr::Subscript(value, index) => {
+            out.push(EXPR_SUBSCRIPT);
+            encode_expr(value, out);
+            encode_expr(index, out);
+        }
+        Expr::Slice(lower, upper, step) => {
+            out.push(EXPR_SLICE);
+            for bound in [lower, upper, step] {
+                encode_optional(bound.as_deref(), out);
+            }
+        }
+        Expr::Dict(items) => {
+            out.push(EXPR_DICT);
+            encode_len(items.len(), out);
+            for (key, value) in items {
+                encode_expr(key, out);
+                encode_expr(value, out);
+            }
+        }
+        Expr::Set(items) => {
+            out.push(EXPR_SET);
+            encode_exprs(items, out);
+        }
+        Expr::Lambda(def) => {
+            out.push(EXPR_LAMBDA);
+            encode_function(def, out);
+        }
+        Expr::IfExp(test, body, orelse) => {
+            out.push(EXPR_IF_EXP);
+            encode_expr(test, out);
+            encode_expr(body, out);
+            encode_expr(orelse, out);
+        }
+        Expr::Comprehension(comp) => {
+            out.push(EXPR_COMPREHENSION);
+            encode_op(&comp.kind, COMPREHENSION_KINDS, out);
+            encode_expr(&comp.element, out);
+            encode_optional(comp.value.as_ref(), out);
+            out.extend_from_slice(&(comp.line as u32).to_le_bytes());
+            encode_len(comp.clauses.len(), out);
+            for clause in &comp.clauses {
+                encode_expr(&clause.target, out);
+                encode_expr(&clause.iter, out);
+                encode_exprs(&clause.conditions, out);
+            }
+        }
+    };
+}
+
+/* Cursor over cache file contents. Every read returns None on truncation
+ * or an unknown tag, which callers treat as a stale/corrupt cache.
+ */
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+    // nesting of the statement/expression being decoded
+    depth: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+        let slice = self.bytes.get(self.pos..self.pos.checked_add(len)?)?;
+        self.pos += len;
+        return Some(slice);
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        return Some(self.take(1)?[0]);
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        let mut buf = [0; 2];
+        buf.copy_from_slice(self.take(2)?);
+        return Some(u16::from_le_bytes(buf));
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        let mut buf = [0; 4];
+        buf.copy_from_slice(self.take(4)?);
+        return Some(u32::from_le_bytes(buf));
+    }
+
+    fn u64(&mut self) -> Option<u64> {
+        let mut buf = [0; 8];
+        buf.copy_from_slice(self.take(8)?);
+        return Some(u64::from_le_bytes(buf));
+    }
+
+    /* a count of items, each at least a byte long, so a corrupt count can't
+     * make the caller allocate more than the file holds
+     */
+    fn len(&mut self) -> Option<usize> {
+        let len = self.u64()?;
+        if len > (self.bytes.len() - self.pos) as u64 {
+            return None;
+        }
+        return Some(len as usize);
+    }
+
+    fn bool(&mut self) -> Option<bool> {
+        match self.u8()? {
+            0 => return Some(false),
+            1 => return Some(true),
+            _ => return None,
+        }
+    }
+
+    fn string(&mut self) -> Option<String> {
+        let len = self.len()?;
+        return String::from_utf8(self.take(len)?.to_vec()).ok();
+    }
+
+    fn op<T: Copy>(&mut self, table: &[T]) -> Option<T> {
+        return table.get(self.u8()? as usize).copied();
+    }
+
+    fn names(&mut self) -> Option<Vec<String>> {
+        let len = self.len()?;
+        let mut names: Vec<String> = Vec::with_capacity(len);
+        for _ in 0..len {
+            names.push(self.string()?);
+        }
+        return Some(names);
+    }
+
+    /* at most one name, e.g. a function's *args parameter
+     */
+    fn optional_name(&mut self) -> Option<Option<String>> {
+        let mut names = self.names()?;
+        if names.len() > 1 {
+            return None;
+        }
+        return Some(names.pop());
+    }
+
+    fn aliases(&mut self) -> Option<Vec<(String, Option<String>)>> {
+        let len = self.len()?;
+        let mut names: Vec<(String, Option<String>)> = Vec::with_capacity(len);
+        for _ in 0..len {
+            let name = self.string()?;
+            let alias = if self.bool()? { Some(self.string()?) } else { None };
+            names.push((name, alias));
+        }
+        return Some(names);
+    }
+
+    fn optional(&mut self) -> Option<Option<Expr>> {
+        if self.bool()? {
+            return Some(Some(self.expr()?));
+        }
+        return Some(None);
+    }
+
+    fn boxed(&mut self) -> Option<Box<Expr>> {
+        return Some(Box::new(self.expr()?));
+    }
+
+    fn exprs(&mut self) -> Option<Vec<Expr>> {
+        let len = self.len()?;
+        let mut exprs: Vec<Expr> = Vec::with_capacity(len);
+        for _ in 0..len {
+            exprs.push(self.expr()?);
+        }
+        return Some(exprs);
+    }
+
+    fn block(&mut self) -> Option<Vec<Stmt>> {
+        let len = self.len()?;
+        let mut block: Vec<Stmt> = Vec::with_capacity(len);
+        for _ in 0..len {
+            block.push(self.stmt()?);
+        }
+        return Some(block);
+    }
+
+    fn params(&mut self) -> Option<Vec<(String, Option<Expr>)>> {
+        let len = self.len()?;
+        let mut params: Vec<(String, Option<Expr>)> = Vec::with_capacity(len);
+        for _ in 0..len {
+            let name = self.string()?;
+            params.push((name, self.optional()?));
+        }
+        return Some(params);
+    }
+
+    fn comprehension(&mut self) -> Option<Rc<Comprehension>> {
+        let kind = self.op(COMPREHENSION_KINDS)?;
+        let element = self.expr()?;
+        let value = self.optional()?;
+        let line = self.u32()? as usize;
+        let len = self.len()?;
+        let mut clauses: Vec<ComprehensionFor> = Vec::with_capacity(len);
+        for _ in 0..len {
+            let target = self.expr()?;
+            let iter = self.expr()?;
+            clauses.push(ComprehensionFor { target, iter, conditions: self.exprs()? });
+        }
+        return Some(Rc::new(Comprehension { kind, element, value, clauses, line }));
+    }
+
+    fn args(&mut self) -> Option<Vec<Arg>> {
+        let len = self.len()?;
+        let mut args: Vec<Arg> = Vec::with_capacity(len);
+        for _ in 0..len {
+            let arg = match self.u8()? {
+                ARG_POSITIONAL => Arg::Positional(self.expr()?),
+                ARG_KEYWORD => Arg::Keyword(self.string()?, self.expr()?),
+                ARG_STAR => Arg::Star(self.expr()?),
+                ARG_DOUBLE_STAR => Arg::DoubleStar(self.expr()?),
+                _ => return None,
+            };
+            args.push(arg);
+        }
+        return Some(args);
+    }
+
+    fn function(&mut self) -> Option<Rc<FunctionDef>> {
+        let name = self.string()?;
+        let params = Params {
+            args: self.params()?,
+            vararg: self.optional_name()?,
+            kwonly: self.params()?,
+            kwarg: self.optional_name()?,
+        };
+        let (body, locals) = (self.block()?, self.names()?);
+        let (doc, decorators) = (self.optional_name()?, self.exprs()?);
+        return Some(Rc::new(FunctionDef {
+            name,
+            params,
+            body,
+            locals,
+            doc,
+            decorators,
+        }));
+    }
+
+    fn stmt(&mut self) -> Option<Stmt> {
+        self.depth += 1;
+        if self.depth > MAX_DEPTH {
+            return None;
+        }
+        let stmt = self.stmt_body();
+        self.depth -= 1;
+        return stmt;
+    }
+
+    fn stmt_body(&mut self) -> Option<Stmt> {
+        let line = self.u32()? as usize;
+        return Some(Stmt { line, kind: self.stmt_kind()? });
+    }
+
+    fn stmt_kind(&mut self) -> Option<StmtKind> {
+        match self.u8()? {
+            STMT_EXPR => return Some(StmtKind::Expr(self.expr()?)),
+            STMT_ASSIGN => return Some(StmtKind::Assign(self.exprs()?, self.expr()?)),
+            STMT_AUG_ASSIGN => return Some(StmtKind::AugAssign(self.expr()?, self.op(BIN_OPS)?, self.expr()?)),
+            STMT_DELETE => return Some(StmtKind::Delete(self.exprs()?)),
+            STMT_IF => return Some(StmtKind::If(self.expr()?, self.block()?, self.block()?)),
+            STMT_WHILE => return Some(StmtKind::While(self.expr()?, self.block()?, self.block()?)),
+            STMT_FOR => return Some(StmtKind::For(self.expr()?, self.expr()?, self.block()?, self.block()?)),
+            STMT_WITH => {
+                let len = self.len()?;
+                let mut items: Vec<(Expr, Option<Expr>)> = Vec::with_capacity(len);
+                for _ in 0..len {
+                    items.push((self.expr()?, self.optional()?));
+                }
+                return Some(StmtKind::With(items, self.block()?));
+            }
+            STMT_TRY => {
+                let body = self.block()?;
+                let len = self.len()?;
+                let mut handlers: Vec<Handler> = Vec::with_capacity(len);
+                for _ in 0..len {
+                    let (kind, name, body) = (self.optional()?, self.optional_name()?, self.block()?);
+                    handlers.push(Handler { kind, name, body, line: self.u32()? as usize });
+                }
+                return Some(StmtKind::Try(body, handlers, self.block()?, self.block()?));
+            }
+            STMT_RAISE => return Some(StmtKind::Raise(self.optional()?, self.optional()?)),
+            STMT_FUNCTION_DEF => return Some(StmtKind::FunctionDef(self.function()?)),
+            STMT_CLASS_DEF => {
+                let (name, bases, body, decorators) = (self.string()?, self.args()?, self.block()?, self.exprs()?);
+                return Some(StmtKind::ClassDef(Rc::new(ClassDef {
+                    name,
+                    bases,
+                    body,
+                    decorators,
+                })));
+            }
+            STMT_RETURN => return Some(StmtKind::Return(self.optional()?)),
+            STMT_GLOBAL => return Some(StmtKind::Global(self.names()?)),
+            STMT_NONLOCAL => return Some(StmtKind::Nonlocal(self.names()?)),
+            STMT_ASSERT => return Some(StmtKind::Assert(self.expr()?, self.optional()?)),
+            STMT_IMPORT => return Some(StmtKind::Import(self.aliases()?)),
+            STMT_IMPORT_FROM => {
+                let level = self.u64()? as usize;
+                return Some(StmtKind::ImportFrom(level, self.string()?, self.aliases()?));
+            }
+            STMT_PASS => return Some(StmtKind::Pass),
+            STMT_BREAK => return Some(StmtKind::Break),
+            STMT_CONTINUE => return Some(StmtKind::Continue),
+            _ => return None,
+        }
+    }
+
+    fn expr(&mut self) -> Option<Expr> {
+        self.depth += 1;
+        if self.depth > MAX_DEPTH {
+            return None;
+        }
+        let expr = self.expr_body();
+        self.depth -= 1;
+        return expr;
+    }
+
+    fn expr_body(&mut self) -> Option<Expr> {
+        match self.u8()? {
+            EXPR_NONE => return Some(Expr::NoneT),
+            EXPR_BOOL => return Some(Expr::Bool(self.bool()?)),
+            EXPR_INT => return Some(Expr::Int(self.u64()? as i64)),
+            EXPR_BIG_INT => return Some(Expr::BigInt(Rc::new(self.string()?.parse().ok()?))),
+            EXPR_FLOAT => return Some(Expr::Float(f64::from_bits(self.u64()?))),
+            EXPR_IMAGINARY => return Some(Expr::Imaginary(f64::from_bits(self.u64()?))),
+            EXPR_STR => return Some(Expr::Str(self.string()?)),
+            EXPR_BYTES => {
+                let len = self.len()?;
+                return Some(Expr::Bytes(self.take(len)?.to_vec()));
+            }
+            EXPR_NAME => return Some(Expr::Name(self.string()?)),
+            EXPR_ATTRIBUTE => return Some(Expr::Attribute(self.boxed()?, self.string()?)),
+            EXPR_LIST => return Some(Expr::List(self.exprs()?)),
+            EXPR_TUPLE => return Some(Expr::Tuple(self.exprs()?)),
+            EXPR_UNARY_OP => return Some(Expr::UnaryOp(self.op(UNARY_OPS)?, self.boxed()?)),
+            EXPR_BIN_OP => return Some(Expr::BinOp(self.boxed()?, self.op(BIN_OPS)?, self.boxed()?)),
+            EXPR_BOOL_OP => return Some(Expr::BoolOp(self.boxed()?, self.op(BOOL_OPS)?, self.boxed()?)),
+            EXPR_COMPARE => {
+                let first = self.boxed()?;
+                let len = self.len()?;
+                let mut rest: Vec<(CmpOp, Expr)> = Vec::with_capacity(len);
+                for _ in 0..len {
+                    rest.push((self.op(CMP_OPS)?, self.expr()?));
+                }
+                return Some(Expr::Compare(first, rest));
+            }
+            EXPR_CALL => return Some(Expr::Call(self.boxed()?, self.args()?)),
+            EXPR_SUBSCRIPT => return Some(Expr::Subscript(self.boxed()?, self.boxed()?)),
+            EXPR_SLICE => {
+                let lower = self.optional()?.map(Box::new);
+                let upper = self.optional()?.map(Box::new);
+                let step = self.optional()?.map(Box::new);
+                return Some(Expr::Slice(lower, upper, step));
+            }
+            EXPR_DICT => {
+                let len = self.len()?;
+                let mut items: Vec<(Expr, Expr)> = Vec::with_capacity(len);
+                for _ in 0..len {
+                    items.push((self.expr()?, self.expr()?));
+                }
+                return Some(Expr::Dict(items));
+            }
+            EXPR_SET => return Some(Expr::Set(self.exprs()?)),
+            EXPR_LAMBDA => return Some(Expr::Lambda(self.function()?)),
+            EXPR_IF_EXP => return Some(Expr::IfExp(self.boxed()?, self.boxed()?, self.boxed()?)),
+            EXPR_COMPREHENSION => return Some(Expr::Comprehension(self.comprehension()?)),
+            _ => return None,
+        }
+    }
+}
+
+/* Load the cached code of a source file, as compiled at the given -O
+ * level. Returns None if there is no cache, or if it is stale, from another
+ * cache version, or corrupt.
+ */
+pub fn load(source: &Path, optimize: u8) -> Option<Vec<Stmt>> {
+    let (secs, nanos, size) = source_stamp(source)?;
+    let bytes = fs::read(cache_path(source, optimize)).ok()?;
+    let mut reader = Reader { bytes: &bytes, pos: 0, depth: 0 };
+
+    if reader.take(4)? != MAGIC || reader.u16()? != CACHE_VERSION {
+        return None;
+    }
+    if reader.u64()? != secs || reader.u32()? != nanos || reader.u64()? != size {
+        return None;
+    }
+    let code = reader.block()?;
+    if reader.pos != bytes.len() {
+        return None;
+    }
+    return Some(code);
+}
+
+/* Write the cache for a source file. Failures (read-only directory, ...)
+ * are not errors - the script just gets recompiled next time.
+ */
+pub fn store(source: &Path, optimize: u8, code: &[Stmt]) -> Option<()> {
+    let (secs, nanos, size) = source_stamp(source)?;
+    let mut out: Vec<u8> = Vec::new();
+    out.extend_from_slice(MAGIC);
+    out.extend_from_slice(&CACHE_VERSION.to_le_bytes());
+    out.extend_from_slice(&secs.to_le_bytes());
+    out.extend_from_slice(&nanos.to_le_bytes());
+    out.extend_from_slice(&size.to_le_bytes());
+    encode_block(code, &mut out);
+
+    // write to a temporary file first so a concurrent reader never sees a
+    // partially written cache
+    let path = cache_path(source, optimize);
+    fs::create_dir_all(path.parent()?).ok()?;
+    let tmp_path = path.with_extension(format!("rbc.{}", std::process::id()));
+    let mut file = fs::File::create(&tmp_path).ok()?;
+    if file.write_all(&out).is_err() || fs::rename(&tmp_path, &path).is_err() {
+        let _ = fs::remove_file(&tmp_path);
+        return None;
+    }
+    return Some(());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::Interpreter;
+
+    const SOURCE: &str = "\
+import os.path as p, sys
+from .. import a as b
+x = y = 2.5; x -= 0x1f ^ ~1 << 2
+del x[0], y
+z = [i for i in x if i if not i for j in i], {k: v for k, v in x}, sum(i for i in x)
+y = [x ** 3 + 'a', (1,), {None: b'z'}, -2j, not x, x[1:], x[::2]]
+@decorate(1)
+@other
+def f(a, b=1, *args, c, d=2, **kwargs):
+    'doc'
+    global g
+    assert a < b <= c is not None, 'msg'
+    return lambda: f(*args, a, c=1, **kwargs).attr if a and b else None
+for i, j in x:
+    if i:
+        break
+    elif j:
+        continue
+    else:
+        pass
+while x:
+    with open(x) as f, g:
+        del_ = x
+else:
+    y = 1
+try:
+    raise ValueError(x) from None
+except (KeyError, IndexError) as e:
+    raise
+except:
+    pass
+else:
+    x = 1
+finally:
+    x = 2
+@decorate
+class C(B, *bases, metaclass=M):
+    def __init__(self):
+        super().__init__()
+";
+
+    fn setup(name: &str) -> (PathBuf, PathBuf) {
+        let dir = std::env::temp_dir().join(format!("rebar-cache-{}-{}", name, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let source = dir.join("script.py");
+        fs::write(&source, SOURCE).unwrap();
+        return (dir, source);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let (dir, source) = setup("round-trip");
+        let code = Interpreter::build_new().compile(SOURCE, "script.py").unwrap();
+
+        assert!(load(&source, 0).is_none());
+        store(&source, 0, &code).unwrap();
+        assert_eq!(load(&source, 0).unwrap(), code);
+
+        // changing the source size invalidates the cache
+        fs::write(&source, "x = 2.5\n").unwrap();
+        assert!(load(&source, 0).is_none());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_optimize_levels() {
+        let (dir, source) = setup("levels");
+        let code = vec![Stmt { line: 1, kind: StmtKind::Pass }];
+        store(&source, 0, &code).unwrap();
+        // -O code has its own cache file
+        assert!(load(&source, 1).is_none());
+        assert_ne!(cache_path(&source, 0), cache_path(&source, 1));
+        store(&source, 1, &[]).unwrap();
+        assert_eq!(load(&source, 0).unwrap(), code);
+        assert_eq!(load(&source, 1).unwrap(), vec![]);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_corrupt() {
+        let (dir, source) = setup("corrupt");
+        let code = Interpreter::build_new().compile(SOURCE, "script.py").unwrap();
+        store(&source, 0, &code).unwrap();
+        let path = cache_path(&source, 0);
+        let bytes = fs::read(&path).unwrap();
+
+        // truncated
+        fs::write(&path, &bytes[..bytes.len() - 1]).unwrap();
+        assert!(load(&source, 0).is_none());
+        // trailing garbage
+        fs::write(&path, [&bytes[..], &[0]].concat()).unwrap();
+        assert!(load(&source, 0).is_none());
+        // nested too deeply: a long chain of `not`
+        let mut deep = bytes[..26].to_vec();
+        encode_len(1, &mut deep);
+        deep.extend_from_slice(&1u32.to_le_bytes());
+        deep.push(STMT_EXPR);
+        for _ in 0..MAX_DEPTH {
+            deep.extend_from_slice(&[EXPR_UNARY_OP, 2]);
+        }
+        deep.push(EXPR_NONE);
+        fs::write(&path, deep).unwrap();
+        assert!(load(&source, 0).is_none());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
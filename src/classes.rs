@@ -0,0 +1,438 @@
+use crate::ast::{BinOp, ClassDef, CmpOp, UnaryOp};
+use crate::dict::{Dict, HashKey};
+use crate::interpreter::{check_compare_depth, compare, contains, equals, not_an_int, EvalResult, Interpreter};
+use crate::native::with_native;
+use crate::value::{new_tuple, Args, Class, Exception, Method, Object, Scope, Value};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/* Classes defined by scripts, and the special methods their instances
+ * have operators and built-in functions call.
+ *
+ * A class body runs in a namespace of its own, which becomes the class's
+ * attributes. The functions defined in it don't see that namespace: their
+ * closure is a scope holding just __class__, the class being defined, and
+ * then whatever encloses the class statement. That is how super() finds
+ * the class without being told.
+ *
+ * Instances of script classes (and of exceptions, and object()) are plain
+ * Objects that __init__ sets up. Built-in types with a constructor of their
+ * own, like int or list, can't be subclassed yet.
+ */
+
+// the special method for each operator, and the reflected one that is
+// tried on the right operand
+const BINARY_SPECIALS: &[(BinOp, &str, &str, &str)] = &[
+    (BinOp::Add, "__add__", "__radd__", "__iadd__"),
+    (BinOp::Sub, "__sub__", "__rsub__", "__isub__"),
+    (BinOp::Mul, "__mul__", "__rmul__", "__imul__"),
+    (BinOp::Div, "__truediv__", "__rtruediv__", "__itruediv__"),
+    (BinOp::FloorDiv, "__floordiv__", "__rfloordiv__", "__ifloordiv__"),
+    (BinOp::Mod, "__mod__", "__rmod__", "__imod__"),
+    (BinOp::Pow, "__pow__", "__rpow__", "__ipow__"),
+    (BinOp::BitAnd, "__and__", "__rand__", "__iand__"),
+    (BinOp::BitOr, "__or__", "__ror__", "__ior__"),
+    (BinOp::BitXor, "__xor__", "__rxor__", "__ixor__"),
+    (BinOp::LShift, "__lshift__", "__rlshift__", "__ilshift__"),
+    (BinOp::RShift, "__rshift__", "__rrshift__", "__irshift__"),
+];
+
+// comparisons, and the one the right operand is asked for when the left
+// one doesn't know
+const COMPARE_SPECIALS: &[(CmpOp, &str, &str)] = &[
+    (CmpOp::Eq, "__eq__", "__eq__"),
+    (CmpOp::NotEq, "__ne__", "__ne__"),
+    (CmpOp::Lt, "__lt__", "__gt__"),
+    (CmpOp::LtE, "__le__", "__ge__"),
+    (CmpOp::Gt, "__gt__", "__lt__"),
+    (CmpOp::GtE, "__ge__", "__le__"),
+];
+
+/* What super() gives: attributes looked up on the classes after class in
+ * the MRO of object's class, bound to object
+ */
+struct Super {
+    class: Rc<Class>,
+    object: Value,
+}
+
+impl Interpreter {
+    /* Run a class statement, giving the class
+     */
+    pub(crate) fn make_class(&mut self, def: &ClassDef) -> EvalResult {
+        let args = self.eval_args(&def.bases)?;
+        if let Some((key, _)) = args.keywords.first() {
+            let message = format!("class keyword arguments such as '{}' are not supported", key);
+            return Err(Exception::new("TypeError", &message));
+        }
+        let bases = self.class_bases(&args.positional)?;
+
+        let cell = Rc::new(Scope::build_new(self.scope.clone()));
+        let mut namespace = Scope::build_new(Some(cell.clone()));
+        namespace.class_body = true;
+        let namespace = Rc::new(namespace);
+        let module = self.globals.get("__name__").unwrap_or(Value::NoneT);
+        namespace.vars.borrow_mut().insert(String::from("__module__"), module);
+        namespace.vars.borrow_mut().insert(String::from("__qualname__"), Value::Str(def.name.clone()));
+        let saved_scope = self.scope.replace(namespace.clone());
+        let result = self.exec_block(&def.body);
+        self.scope = saved_scope;
+        if let Err(mut err) = result {
+            err.add_frame(&self.globals, &def.name);
+            return Err(err);
+        }
+
+        let attrs = std::mem::take(&mut *namespace.vars.borrow_mut());
+        let class = Class::build_user(&def.name, bases, attrs)?;
+        cell.vars.borrow_mut().insert(String::from("__class__"), Value::Class(class.clone()));
+        return Ok(Value::Class(class));
+    }
+
+    /* The classes a new class derives from: object if none are given
+     */
+    fn class_bases(&self, bases: &[Value]) -> Result<Vec<Rc<Class>>, Exception> {
+        let mut classes: Vec<Rc<Class>> = Vec::new();
+        for base in bases {
+            match base {
+                Value::Class(class) if class.constructor.is_some() => {
+                    let message = format!("subclassing the built-in type '{}' is not supported", class.name);
+                    return Err(Exception::new("TypeError", &message));
+                }
+                Value::Class(class) => classes.push(class.clone()),
+                _ => return Err(Exception::new("TypeError", "bases must be types")),
+            };
+        }
+        if classes.is_empty() {
+            classes.push(self.types["object"].clone());
+        }
+        return Ok(classes);
+    }
+
+    /* type(name, bases, dict)
+     */
+    pub(crate) fn new_type(&mut self, name: &Value, bases: &Value, dict: &Value) -> EvalResult {
+        let name = match name {
+            Value::Str(name) => name.clone(),
+            other => {
+                let message = format!("type.__new__() argument 1 must be str, not {}", other.type_name());
+                return Err(Exception::new("TypeError", &message));
+            }
+        };
+        let bases = match bases {
+            Value::Tuple(bases) => self.class_bases(bases)?,
+            other => {
+                let message = format!("type.__new__() argument 2 must be tuple, not {}", other.type_name());
+                return Err(Exception::new("TypeError", &message));
+            }
+        };
+        let mut attrs: HashMap<String, Value> = HashMap::new();
+        match dict {
+            Value::Dict(dict) => {
+                for (key, value) in dict.borrow().items() {
+                    match key {
+                        Value::Str(ref key) => attrs.insert(key.clone(), value.clone()),
+                        _ => return Err(Exception::new("TypeError", "type() attribute names must be strings")),
+                    };
+                }
+            }
+            other => {
+                let message = format!("type.__new__() argument 3 must be dict, not {}", other.type_name());
+                return Err(Exception::new("TypeError", &message));
+            }
+        };
+        return Ok(Value::Class(Class::build_user(&name, bases, attrs)?));
+    }
+
+    /* Call a class whose instances are plain objects: make one, and have
+     * __init__ set it up. An exception's args are set first, as
+     * BaseException.__new__ would.
+     */
+    pub(crate) fn instantiate(&mut self, class: &Rc<Class>, mut args: Args) -> EvalResult {
+        let instance = self.new_object(class, &args.positional);
+        // object is always in the MRO, and has an __init__
+        let init = class.lookup("__init__").unwrap();
+        args.positional.insert(0, instance.clone());
+        match self.call(&init, args)? {
+            Value::NoneT => return Ok(instance),
+            other => {
+                let message = format!("__init__() should return None, not '{}'", other.type_name());
+                return Err(Exception::new("TypeError", &message));
+            }
+        }
+    }
+
+    /* A plain object of class that __init__ hasn't set up, as
+     * object.__new__(class, *args) makes
+     */
+    pub(crate) fn new_object(&self, class: &Rc<Class>, args: &[Value]) -> Value {
+        let instance = Object::build_new(class.clone(), None);
+        if self.is_exception_class(class) {
+            instance.attrs.borrow_mut().insert(String::from("args"), new_tuple(args.to_vec()));
+        }
+        return Value::Object(instance);
+    }
+
+    /* Whether calling class makes a plain object for __init__ to set up,
+     * rather than running a native constructor
+     */
+    pub(crate) fn is_plain_class(&self, class: &Rc<Class>) -> bool {
+        return class.constructor.is_none()
+            && (class.user_defined || self.is_exception_class(class) || Rc::ptr_eq(class, &self.types["object"]));
+    }
+
+    /* super(): with no arguments, the class the running method was defined
+     * in and its first argument
+     */
+    pub(crate) fn make_super(&mut self, args: Args) -> EvalResult {
+        args.check("super", 0, 2)?;
+        let (class, object) = match args.positional.len() {
+            0 => {
+                let scope = self.scope.clone();
+                let function = scope.as_ref().and_then(|scope| scope.function.clone());
+                let first = function.as_ref().and_then(|function| function.params.args.first());
+                let object = match (&scope, first) {
+                    (Some(scope), Some((name, _))) => scope.vars.borrow().get(name).cloned(),
+                    _ => None,
+                };
+                let object = object.ok_or_else(|| Exception::new("RuntimeError", "super(): no arguments"))?;
+                match self.lookup("__class__") {
+                    Some(Value::Class(ref class)) => (class.clone(), object),
+                    _ => return Err(Exception::new("RuntimeError", "super(): __class__ cell not found")),
+                }
+            }
+            1 => return Err(Exception::new("TypeError", "super() with one argument is not supported")),
+            _ => match &args.positional[0] {
+                Value::Class(class) => (class.clone(), args.positional[1].clone()),
+                other => {
+                    let message = format!("super() argument 1 must be a type, not {}", other.type_name());
+                    return Err(Exception::new("TypeError", &message));
+                }
+            },
+        };
+        if !self.type_of(&object).is_subclass(&class) {
+            let message = "super(type, obj): obj must be an instance or subtype of type";
+            return Err(Exception::new("TypeError", message));
+        }
+        let super_class = self.types["super"].clone();
+        return Ok(Value::Object(Object::build_new(super_class, Some(Box::new(Super { class, object })))));
+    }
+
+    /* attr of a super() object, if value is one
+     */
+    pub(crate) fn super_attr(&mut self, value: &Value, attr: &str) -> Option<EvalResult> {
+        let (class, object) = with_native(value, |this: &Super| (this.class.clone(), this.object.clone()))?;
+        let object_class = self.type_of(&object);
+        let mro: Vec<Rc<Class>> = std::iter::once(object_class.clone()).chain(object_class.mro.iter().cloned()).collect();
+        let after = mro.iter().position(|other| Rc::ptr_eq(other, &class)).map_or(mro.len(), |i| i + 1);
+        let found = mro[after..].iter().find_map(|other| other.attrs.borrow().get(attr).cloned());
+        match found {
+            Some(function @ (Value::Function(_) | Value::Builtin(_))) => return Some(Ok(Method::build_new(object, function))),
+            Some(Value::Property(ref property)) => {
+                return Some(self.call(&property.getter, Args::build_new(vec![object])));
+            }
+            Some(value) => return Some(Ok(value)),
+            None => {
+                let message = format!("'super' object has no attribute '{}'", attr);
+                return Some(Err(Exception::new("AttributeError", &message)));
+            }
+        }
+    }
+
+    /* left op right for instances whose classes define the operator: the
+     * left operand's method, then the right's reflected one. None if
+     * neither does, or both return NotImplemented.
+     */
+    pub(crate) fn binary_special(&mut self, op: BinOp, left: &Value, right: &Value) -> Option<EvalResult> {
+        let (_, method, reflected, _) = BINARY_SPECIALS.iter().find(|(other, ..)| *other == op)?;
+        return self.either_special(left, method, right, reflected);
+    }
+
+    /* left op= right: __iadd__ and friends, which may update left in place
+     */
+    pub(crate) fn inplace_special(&mut self, op: BinOp, left: &Value, right: &Value) -> Option<EvalResult> {
+        let (_, _, _, inplace) = BINARY_SPECIALS.iter().find(|(other, ..)| *other == op)?;
+        return match self.call_special(left, inplace, vec![right.clone()])? {
+            Ok(result) if self.is_not_implemented(&result) => None,
+            result => Some(result),
+        };
+    }
+
+    pub(crate) fn unary_special(&mut self, op: UnaryOp, operand: &Value) -> Option<EvalResult> {
+        let method = match op {
+            UnaryOp::Neg => "__neg__",
+            UnaryOp::Pos => "__pos__",
+            UnaryOp::Invert => "__invert__",
+            UnaryOp::Not => return None,
+        };
+        return self.call_special(operand, method, vec![]);
+    }
+
+    /* left op right for comparisons instances define. A != with only __eq__
+     * defined is its negation.
+     */
+    pub(crate) fn compare_special(&mut self, op: CmpOp, left: &Value, right: &Value) -> Option<Result<bool, Exception>> {
+        let (_, method, reflected) = COMPARE_SPECIALS.iter().find(|(other, ..)| *other == op)?;
+        let result = match self.either_special(left, method, right, reflected) {
+            None if op == CmpOp::NotEq => match self.either_special(left, "__eq__", right, "__eq__")? {
+                Ok(equal) => self.truthy(&equal).map(|equal| !equal),
+                Err(err) => Err(err),
+            },
+            result => result?.and_then(|result| self.truthy(&result)),
+        };
+        return Some(result);
+    }
+
+    /* left op right the way the comparison operators work it out: the
+     * instances' special methods, or else the built-in comparison
+     */
+    pub(crate) fn rich_compare(&mut self, op: CmpOp, left: &Value, right: &Value) -> Result<bool, Exception> {
+        match self.compare_special(op, left, right) {
+            Some(result) => return result,
+            None => return compare(op, left, right),
+        }
+    }
+
+    /* items_equal() with the instances' __eq__, for searching a container
+     * for target
+     */
+    pub(crate) fn item_matches(&mut self, item: &Value, target: &Value) -> Result<bool, Exception> {
+        if item.identity() != 0 && item.identity() == target.identity() {
+            return Ok(true);
+        }
+        match self.compare_special(CmpOp::Eq, item, target) {
+            Some(result) => return result,
+            None => {
+                let equal = equals(item, target);
+                check_compare_depth()?;
+                return Ok(equal);
+            }
+        }
+    }
+
+    /* item in container, finding instances by their __eq__ and __hash__
+     */
+    pub(crate) fn contains_item(&mut self, container: &Value, item: &Value) -> Result<bool, Exception> {
+        let items = match container {
+            Value::List(items) => items.borrow().clone(),
+            Value::Tuple(items) => items.to_vec(),
+            _ => return contains(container, &self.container_key(container, item)?),
+        };
+        for other in &items {
+            if self.item_matches(other, item)? {
+                return Ok(true);
+            }
+        }
+        return Ok(false);
+    }
+
+    /* hash(value): __hash__ for instances whose class defines it
+     */
+    pub(crate) fn hash_value(&mut self, value: &Value) -> Result<i64, Exception> {
+        match self.call_special(value, "__hash__", vec![]) {
+            Some(result) => match result? {
+                Value::Int(hash) => return Ok(hash),
+                Value::Bool(hash) => return Ok(hash as i64),
+                _ => return Err(Exception::new("TypeError", "__hash__ method should return an integer")),
+            },
+            None => return Ok(HashKey::from_value(value)?.hash_value()),
+        }
+    }
+
+    /* The key a dict, set or frozenset has for key. Dicts hash instances by
+     * identity, so an instance whose class defines __hash__ stands for an
+     * equal key already there with the same __hash__(), the one CPython's
+     * dict would find. A key's __hash__() is taken once, when it is first
+     * looked up, and kept as CPython's dicts keep it.
+     */
+    pub(crate) fn container_key(&mut self, container: &Value, key: &Value) -> EvalResult {
+        match container {
+            Value::Dict(dict) | Value::Set(dict) | Value::FrozenSet(dict) => return self.dict_key(dict, key),
+            _ => return Ok(key.clone()),
+        }
+    }
+
+    pub(crate) fn dict_key(&mut self, dict: &RefCell<Dict>, key: &Value) -> EvalResult {
+        let hash = match key {
+            Value::Object(object) if object.class.lookup("__hash__").is_some() => match object.key_hash.get() {
+                Some(hash) => hash,
+                None => {
+                    let hash = self.hash_value(key)?;
+                    object.key_hash.set(Some(hash));
+                    hash
+                }
+            },
+            _ => return Ok(key.clone()),
+        };
+        let candidates: Vec<Value> = dict
+            .borrow()
+            .entries()
+            .filter(|(other, _)| matches!(other, Value::Object(object) if object.key_hash.get() == Some(hash)))
+            .map(|(other, _)| other.clone())
+            .collect();
+        for candidate in candidates {
+            if self.item_matches(&candidate, key)? {
+                return Ok(candidate);
+            }
+        }
+        return Ok(key.clone());
+    }
+
+    fn either_special(&mut self, left: &Value, method: &str, right: &Value, reflected: &str) -> Option<EvalResult> {
+        if let Some(result) = self.call_special(left, method, vec![right.clone()]) {
+            match result {
+                Ok(result) if self.is_not_implemented(&result) => (),
+                result => return Some(result),
+            };
+        }
+        return match self.call_special(right, reflected, vec![left.clone()])? {
+            Ok(result) if self.is_not_implemented(&result) => None,
+            result => Some(result),
+        };
+    }
+
+    fn is_not_implemented(&self, value: &Value) -> bool {
+        return self.builtins.get("NotImplemented").is_some_and(|not_implemented| value.identity() == not_implemented.identity());
+    }
+
+    /* bool(value): __bool__, or else __len__, for instances
+     */
+    pub fn truthy(&mut self, value: &Value) -> Result<bool, Exception> {
+        if let Some(result) = self.call_special(value, "__bool__", vec![]) {
+            match result? {
+                Value::Bool(result) => return Ok(result),
+                other => {
+                    let message = format!("__bool__ should return bool, returned {}", other.type_name());
+                    return Err(Exception::new("TypeError", &message));
+                }
+            }
+        }
+        if let Some(len) = self.call_special(value, "__len__", vec![]) {
+            match len? {
+                Value::Int(len) if len >= 0 => return Ok(len > 0),
+                Value::Bool(len) => return Ok(len),
+                Value::Int(_) => return Err(Exception::new("ValueError", "__len__() should return >= 0")),
+                other => return Err(not_an_int(&other)),
+            }
+        }
+        return Ok(value.is_truthy());
+    }
+}
+
+/* object.__init__, which takes nothing but the instance unless a subclass
+ * defines an __init__ of its own
+ */
+pub fn object_init(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    if args.positional.len() <= 1 && args.keywords.is_empty() {
+        return Ok(Value::NoneT);
+    }
+    let class = match args.positional.first() {
+        Some(instance) => interpreter.type_of(instance),
+        None => return Err(Exception::new("TypeError", "descriptor '__init__' of 'object' object needs an argument")),
+    };
+    let object_init = interpreter.types["object"].attrs.borrow().get("__init__").cloned();
+    if class.lookup("__init__").map(|init| init.identity()) == object_init.map(|init| init.identity()) {
+        return Err(Exception::new("TypeError", &format!("{}() takes no arguments", class.name)));
+    }
+    return Err(Exception::new("TypeError", "object.__init__() takes exactly one argument (the instance to initialize)"));
+}
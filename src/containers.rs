@@ -0,0 +1,1063 @@
+use crate::ast::{BinOp, CmpOp};
+use crate::dict::{new_dict, new_set, Dict};
+use crate::exceptions::key_error;
+use crate::interpreter::{adjust_slice, as_int, items_equal, not_an_int, slice_index, EvalResult, Interpreter};
+use crate::iterator::{new_iterator, Iter};
+use crate::modules::builtins::sort_values;
+use crate::native::{instance, with_native, NativeClass};
+use crate::value::{new_list, new_tuple, Args, Builtin, BuiltinFn, Class, Exception, NativeFn, Object, Value};
+use std::cell::RefCell;
+use std::mem::size_of;
+use std::rc::Rc;
+
+/* The methods of list, tuple, dict, set and frozenset, which are
+ * functions on their classes, the set operations, slice objects, and
+ * the views dict.keys(), values() and items() return.
+ *
+ * The views are live: they show the dict as it is when they are used,
+ * and iterating one iterates a snapshot of it, like iterating the dict.
+ * dict.fromkeys() can only be called on the class.
+ */
+
+fn type_error(message: String) -> Exception {
+    return Exception::new("TypeError", &message);
+}
+
+fn value_error(message: &str) -> Exception {
+    return Exception::new("ValueError", message);
+}
+
+fn descriptor_error(name: &str, kind: &str, given: Option<&Value>) -> Exception {
+    return type_error(format!(
+        "descriptor '{}' for '{}' objects doesn't apply to a '{}' object",
+        name,
+        kind,
+        given.map_or("nothing", Value::type_name)
+    ));
+}
+
+fn int_arg(value: &Value) -> Result<i64, Exception> {
+    return as_int(value).ok_or_else(|| not_an_int(value));
+}
+
+/* the list, tuple or dict a method was called on
+ */
+fn this_list(name: &str, args: &Args) -> Result<Rc<RefCell<Vec<Value>>>, Exception> {
+    match args.positional.first() {
+        Some(Value::List(items)) => return Ok(items.clone()),
+        other => return Err(descriptor_error(name, "list", other)),
+    }
+}
+
+fn this_dict(name: &str, args: &Args) -> Result<Rc<RefCell<Dict>>, Exception> {
+    match args.positional.first().and_then(Value::as_dict) {
+        Some(dict) => return Ok(dict),
+        None => return Err(descriptor_error(name, "dict", args.positional.first())),
+    }
+}
+
+pub fn add_methods(list: &Rc<Class>, tuple: &Rc<Class>, dict: &Rc<Class>, set: &Rc<Class>, frozenset: &Rc<Class>, object: &Rc<Class>) {
+    let list_methods: &[(&str, BuiltinFn)] = &[
+        ("append", append),
+        ("clear", clear),
+        ("copy", copy),
+        ("count", count),
+        ("extend", extend),
+        ("index", index),
+        ("insert", insert),
+        ("pop", pop),
+        ("remove", remove),
+        ("reverse", reverse),
+        ("sort", sort),
+    ];
+    let tuple_methods: &[(&str, BuiltinFn)] = &[("count", count), ("index", index)];
+    let dict_methods: &[(&str, BuiltinFn)] = &[
+        ("clear", dict_clear),
+        ("copy", dict_copy),
+        ("fromkeys", fromkeys),
+        ("get", get),
+        ("pop", dict_pop),
+        ("popitem", popitem),
+        ("setdefault", setdefault),
+        ("update", update),
+    ];
+    let frozenset_methods: &[(&str, BuiltinFn)] = &[
+        ("copy", set_copy),
+        ("difference", difference),
+        ("intersection", intersection),
+        ("isdisjoint", isdisjoint),
+        ("issubset", issubset),
+        ("issuperset", issuperset),
+        ("symmetric_difference", symmetric_difference),
+        ("union", union),
+    ];
+    let set_methods: &[(&str, BuiltinFn)] = &[
+        ("add", add),
+        ("clear", set_clear),
+        ("difference_update", difference_update),
+        ("discard", discard),
+        ("intersection_update", intersection_update),
+        ("pop", set_pop),
+        ("remove", set_remove),
+        ("symmetric_difference_update", symmetric_difference_update),
+        ("update", set_update),
+    ];
+    let add_all = |class: &Rc<Class>, methods: &[(&str, BuiltinFn)]| {
+        for (name, method) in methods {
+            class.attrs.borrow_mut().insert(String::from(*name), Builtin::build_new(name, *method));
+        }
+    };
+    add_all(list, list_methods);
+    add_all(tuple, tuple_methods);
+    add_all(dict, dict_methods);
+    add_all(set, frozenset_methods);
+    add_all(set, set_methods);
+    add_all(frozenset, frozenset_methods);
+    for kind in [ViewKind::Keys, ViewKind::Values, ViewKind::Items] {
+        let class = view_class(kind).build(object.clone());
+        let name = kind.method();
+        let method: NativeFn = Rc::new(move |_: &mut Interpreter, args: Args| {
+            args.check(name, 1, 1)?;
+            let view = View {
+                dict: this_dict(name, &args)?,
+                kind,
+            };
+            return Ok(Value::Object(Object::build_new(class.clone(), Some(Box::new(view)))));
+        });
+        dict.attrs.borrow_mut().insert(String::from(name), Builtin::build_native(name, method));
+    }
+}
+
+/* list methods; count() and index() are tuple's too
+ */
+
+fn append(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("append", 2, 2)?;
+    let items = this_list("append", &args)?;
+    interpreter.allocate(size_of::<Value>())?;
+    items.borrow_mut().push(args.positional[1].clone());
+    return Ok(Value::NoneT);
+}
+
+fn extend(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("extend", 2, 2)?;
+    let items = this_list("extend", &args)?;
+    let extra = interpreter.iterate(&args.positional[1])?;
+    interpreter.allocate(extra.len() * size_of::<Value>())?;
+    items.borrow_mut().extend(extra);
+    return Ok(Value::NoneT);
+}
+
+fn insert(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("insert", 3, 3)?;
+    let items = this_list("insert", &args)?;
+    let index = int_arg(&args.positional[1])?;
+    interpreter.allocate(size_of::<Value>())?;
+    let mut items = items.borrow_mut();
+    let len = items.len() as i64;
+    let position = if index < 0 { (index + len).max(0) } else { index.min(len) };
+    items.insert(position as usize, args.positional[2].clone());
+    return Ok(Value::NoneT);
+}
+
+/* pop(index=-1)
+ */
+fn pop(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("pop", 1, 2)?;
+    let items = this_list("pop", &args)?;
+    let index = match args.positional.get(1) {
+        Some(index) => int_arg(index)?,
+        None => -1,
+    };
+    let mut items = items.borrow_mut();
+    if items.is_empty() {
+        return Err(Exception::new("IndexError", "pop from empty list"));
+    }
+    let len = items.len() as i64;
+    let position = if index < 0 { index + len } else { index };
+    if position < 0 || position >= len {
+        return Err(Exception::new("IndexError", "pop index out of range"));
+    }
+    return Ok(items.remove(position as usize));
+}
+
+fn remove(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("remove", 2, 2)?;
+    let list = this_list("remove", &args)?;
+    let items = list.borrow().clone();
+    for (position, item) in items.iter().enumerate() {
+        if interpreter.item_matches(item, &args.positional[1])? {
+            list.borrow_mut().remove(position);
+            return Ok(Value::NoneT);
+        }
+    }
+    return Err(value_error("list.remove(x): x not in list"));
+}
+
+/* the items of the list or tuple args[0]
+ */
+fn sequence_items(name: &str, args: &Args) -> Result<Vec<Value>, Exception> {
+    match args.positional.first() {
+        Some(Value::List(items)) => return Ok(items.borrow().clone()),
+        Some(Value::Tuple(items)) => return Ok(items.to_vec()),
+        other => return Err(descriptor_error(name, "list", other)),
+    }
+}
+
+fn count(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("count", 2, 2)?;
+    let mut count = 0;
+    for item in sequence_items("count", &args)? {
+        if interpreter.item_matches(&item, &args.positional[1])? {
+            count += 1;
+        }
+    }
+    return Ok(Value::Int(count));
+}
+
+/* index(value, start=0, stop=len)
+ */
+fn index(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("index", 2, 4)?;
+    let items = sequence_items("index", &args)?;
+    let len = items.len() as i64;
+    let bound = |position: usize, default: i64| -> Result<usize, Exception> {
+        let val = match args.positional.get(position) {
+            Some(value) => int_arg(value)?,
+            None => default,
+        };
+        return Ok((if val < 0 { (val + len).max(0) } else { val.min(len) }) as usize);
+    };
+    let (start, stop) = (bound(2, 0)?, bound(3, len)?);
+    let target = &args.positional[1];
+    for (position, item) in items.iter().enumerate().take(stop).skip(start) {
+        if interpreter.item_matches(item, target)? {
+            return Ok(Value::Int(position as i64));
+        }
+    }
+    match &args.positional[0] {
+        Value::Tuple(_) => return Err(value_error("tuple.index(x): x not in tuple")),
+        _ => return Err(value_error(&format!("{} is not in list", target.repr()))),
+    }
+}
+
+fn reverse(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("reverse", 1, 1)?;
+    this_list("reverse", &args)?.borrow_mut().reverse();
+    return Ok(Value::NoneT);
+}
+
+fn clear(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("clear", 1, 1)?;
+    // the items are dropped after the list is released, as dropping
+    // them may run code that uses it
+    let items = std::mem::take(&mut *this_list("clear", &args)?.borrow_mut());
+    drop(items);
+    return Ok(Value::NoneT);
+}
+
+fn copy(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("copy", 1, 1)?;
+    let items = this_list("copy", &args)?.borrow().clone();
+    interpreter.allocate(items.len() * size_of::<Value>())?;
+    return Ok(new_list(items));
+}
+
+/* sort(*, key=None, reverse=False), which is stable like sorted(). While
+ * it runs the list is empty, so a key function sees no half-sorted list.
+ */
+fn sort(interpreter: &mut Interpreter, mut args: Args) -> EvalResult {
+    let key = args.keyword("key").filter(|key| *key != Value::NoneT);
+    let reverse = args.keyword("reverse").map(|reverse| reverse.is_truthy()).unwrap_or(false);
+    if args.positional.len() > 1 {
+        return Err(type_error(String::from("sort() takes no positional arguments")));
+    }
+    args.check("sort", 1, 1)?;
+    let list = this_list("sort", &args)?;
+    let items = std::mem::take(&mut *list.borrow_mut());
+    let result = sort_values(interpreter, items.clone(), key, reverse);
+    let mut current = list.borrow_mut();
+    if !current.is_empty() {
+        return Err(value_error("list modified during sort"));
+    }
+    match result {
+        Ok(sorted) => *current = sorted,
+        Err(err) => {
+            *current = items;
+            return Err(err);
+        }
+    };
+    return Ok(Value::NoneT);
+}
+
+/* Operators
+ */
+
+/* list + list or tuple + tuple
+ */
+pub fn concat(left: &Value, right: &Value) -> EvalResult {
+    match (left, right) {
+        (Value::List(left), Value::List(right)) => {
+            let mut items = left.borrow().clone();
+            items.extend(right.borrow().iter().cloned());
+            return Ok(new_list(items));
+        }
+        (Value::Tuple(left), Value::Tuple(right)) => {
+            return Ok(new_tuple(left.iter().chain(right.iter()).cloned().collect()));
+        }
+        _ => {
+            let kind = left.type_name();
+            return Err(type_error(format!(
+                "can only concatenate {} (not \"{}\") to {}",
+                kind,
+                right.type_name(),
+                kind
+            )));
+        }
+    }
+}
+
+/* a list or tuple repeated count times
+ */
+pub fn repeat(sequence: &Value, count: i64) -> EvalResult {
+    match sequence {
+        Value::List(items) => return Ok(new_list(repeat_items(&items.borrow(), count)?)),
+        Value::Tuple(items) => return Ok(new_tuple(repeat_items(items, count)?)),
+        _ => unreachable!(),
+    }
+}
+
+/* items repeated count times; MemoryError rather than an abort if that
+ * can't be allocated
+ */
+pub fn repeat_items(items: &[Value], count: i64) -> Result<Vec<Value>, Exception> {
+    let count = count.max(0) as usize;
+    let mut repeated = Vec::new();
+    let len = items.len().checked_mul(count).ok_or_else(|| Exception::new("MemoryError", ""))?;
+    repeated.try_reserve_exact(len).map_err(|_| Exception::new("MemoryError", ""))?;
+    for _ in 0..count {
+        repeated.extend(items.iter().cloned());
+    }
+    return Ok(repeated);
+}
+
+/* the number of values a list, tuple, dict or set operation would create
+ */
+pub fn result_len(op: BinOp, left: &Value, right: &Value) -> Option<usize> {
+    let len = |value: &Value| match value {
+        Value::List(items) => return Some(items.borrow().len()),
+        Value::Tuple(items) => return Some(items.len()),
+        _ => return None,
+    };
+    match (op, left, right) {
+        (BinOp::Add, _, _) => return Some(len(left)? + len(right)?),
+        (BinOp::Mul, Value::List(_) | Value::Tuple(_), count) | (BinOp::Mul, count, Value::List(_) | Value::Tuple(_)) => {
+            let count = as_int(count)?.max(0) as usize;
+            return Some(len(left).or_else(|| len(right))?.saturating_mul(count));
+        }
+        (BinOp::BitOr | BinOp::BitAnd | BinOp::Sub | BinOp::BitXor, _, _) => match (left, right) {
+            // a dict or set entry is a key and a value
+            (Value::Dict(left), Value::Dict(right))
+            | (Value::Set(left) | Value::FrozenSet(left), Value::Set(right) | Value::FrozenSet(right)) => {
+                return Some(2 * (left.borrow().len() + right.borrow().len()));
+            }
+            _ => return None,
+        },
+        _ => return None,
+    }
+}
+
+/* dict | dict
+ */
+pub fn merge(left: &Rc<RefCell<Dict>>, right: &Rc<RefCell<Dict>>) -> EvalResult {
+    let mut merged = left.borrow().clone();
+    for (key, value) in right.borrow().items() {
+        merged.set(key, value)?;
+    }
+    return Ok(new_dict(merged));
+}
+
+/* dict methods
+ */
+
+/* Add the items of source to dict: a dict's, or those of a mapping with
+ * keys() and [], or of an iterable of key-value pairs, as dict() and
+ * update() take them
+ */
+pub fn update_dict(interpreter: &mut Interpreter, dict: &Rc<RefCell<Dict>>, source: &Value) -> Result<(), Exception> {
+    if let Some(other) = source.as_dict() {
+        let items = other.borrow().items();
+        let mut dict = dict.borrow_mut();
+        for (key, value) in items {
+            dict.set(key, value)?;
+        }
+        return Ok(());
+    }
+    if let (Value::Object(_), Ok(keys)) = (source, interpreter.get_attr(source, "keys")) {
+        let keys = interpreter.call(&keys, Args::default())?;
+        for key in interpreter.iterate(&keys)? {
+            let getitem = interpreter.get_attr(source, "__getitem__")?;
+            let value = interpreter.call(&getitem, Args::build_new(vec![key.clone()]))?;
+            dict.borrow_mut().set(key, value)?;
+        }
+        return Ok(());
+    }
+    for (index, item) in interpreter.iterate(source)?.iter().enumerate() {
+        let pair = interpreter.iterate(item).map_err(|err| match err.kind.as_str() {
+            "TypeError" => {
+                let message = format!("cannot convert dictionary update sequence element #{} to a sequence", index);
+                type_error(message)
+            }
+            _ => err,
+        })?;
+        if pair.len() != 2 {
+            let message = format!("dictionary update sequence element #{} has length {}; 2 is required", index, pair.len());
+            return Err(value_error(&message));
+        }
+        dict.borrow_mut().set(pair[0].clone(), pair[1].clone())?;
+    }
+    return Ok(());
+}
+
+/* update([other], **kwargs)
+ */
+fn update(interpreter: &mut Interpreter, mut args: Args) -> EvalResult {
+    let keywords = std::mem::take(&mut args.keywords);
+    args.check("update", 1, 2)?;
+    let dict = this_dict("update", &args)?;
+    if let Some(source) = args.positional.get(1) {
+        update_dict(interpreter, &dict, source)?;
+    }
+    for (key, value) in keywords {
+        dict.borrow_mut().set(Value::Str(key), value)?;
+    }
+    return Ok(Value::NoneT);
+}
+
+/* get(key, default=None)
+ */
+fn get(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("get", 2, 3)?;
+    let dict = this_dict("get", &args)?;
+    let key = interpreter.dict_key(&dict, &args.positional[1])?;
+    let value = dict.borrow().get(&key)?;
+    return Ok(value.or_else(|| args.positional.get(2).cloned()).unwrap_or(Value::NoneT));
+}
+
+/* setdefault(key, default=None)
+ */
+fn setdefault(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("setdefault", 2, 3)?;
+    let dict = this_dict("setdefault", &args)?;
+    let key = &interpreter.dict_key(&dict, &args.positional[1])?;
+    if let Some(value) = dict.borrow().get(key)? {
+        return Ok(value);
+    }
+    let default = args.positional.get(2).cloned().unwrap_or(Value::NoneT);
+    dict.borrow_mut().set(key.clone(), default.clone())?;
+    return Ok(default);
+}
+
+/* pop(key[, default])
+ */
+fn dict_pop(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("pop", 2, 3)?;
+    let dict = this_dict("pop", &args)?;
+    let key = interpreter.dict_key(&dict, &args.positional[1])?;
+    let removed = dict.borrow_mut().remove(&key)?;
+    match (removed, args.positional.get(2)) {
+        (Some(value), _) => return Ok(value),
+        (None, Some(default)) => return Ok(default.clone()),
+        (None, None) => return Err(key_error(&args.positional[1])),
+    }
+}
+
+/* popitem(): the last (key, value) pair added
+ */
+fn popitem(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("popitem", 1, 1)?;
+    let dict = this_dict("popitem", &args)?;
+    let last = dict.borrow_mut().pop_last();
+    match last {
+        Some((key, value)) => return Ok(new_tuple(vec![key, value])),
+        None => return Err(key_error(&Value::Str(String::from("popitem(): dictionary is empty")))),
+    }
+}
+
+fn dict_clear(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("clear", 1, 1)?;
+    let entries = std::mem::take(&mut *this_dict("clear", &args)?.borrow_mut());
+    drop(entries);
+    return Ok(Value::NoneT);
+}
+
+fn dict_copy(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("copy", 1, 1)?;
+    let dict = this_dict("copy", &args)?.borrow().clone();
+    interpreter.allocate(dict.len() * 2 * size_of::<Value>())?;
+    return Ok(new_dict(dict));
+}
+
+/* dict.fromkeys(iterable, value=None)
+ */
+fn fromkeys(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("fromkeys", 1, 2)?;
+    let value = args.positional.get(1).cloned().unwrap_or(Value::NoneT);
+    let mut dict = Dict::build_new();
+    for key in interpreter.iterate(&args.positional[0])? {
+        dict.set(key, value.clone())?;
+    }
+    return Ok(new_dict(dict));
+}
+
+/* Sets
+ */
+
+/* set(iterable=())
+ */
+pub fn set_new(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("set", 0, 1)?;
+    let items = match args.positional.first() {
+        Some(iterable) => set_of(interpreter, iterable)?,
+        None => Dict::build_new(),
+    };
+    return Ok(new_set(items, false));
+}
+
+/* frozenset(iterable=())
+ */
+pub fn frozenset_new(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("frozenset", 0, 1)?;
+    match args.positional.first() {
+        Some(value @ Value::FrozenSet(_)) => return Ok(value.clone()),
+        Some(iterable) => return Ok(new_set(set_of(interpreter, iterable)?, true)),
+        None => return Ok(new_set(Dict::build_new(), true)),
+    }
+}
+
+/* the items of an iterable, as the contents of a set
+ */
+fn set_of(interpreter: &mut Interpreter, iterable: &Value) -> Result<Dict, Exception> {
+    if let Value::Set(items) | Value::FrozenSet(items) = iterable {
+        return Ok(items.borrow().clone());
+    }
+    let mut items = Dict::build_new();
+    for item in interpreter.iterate(iterable)? {
+        interpreter.allocate(2 * size_of::<Value>())?;
+        items.set(item, Value::NoneT)?;
+    }
+    return Ok(items);
+}
+
+/* A set looked for in a set is looked for as the frozenset of its items,
+ * as in CPython, so {1} in {frozenset({1})}
+ */
+pub fn set_key(item: &Value) -> Value {
+    match item {
+        Value::Set(items) => return Value::FrozenSet(items.clone()),
+        _ => return item.clone(),
+    }
+}
+
+/* left op right for the set operators | & - and ^
+ */
+pub fn set_items_op(op: BinOp, left: &Dict, right: &Dict) -> Result<Dict, Exception> {
+    let mut result = Dict::build_new();
+    for item in left.keys() {
+        let found = right.get(&item)?.is_some();
+        let keep = match op {
+            BinOp::BitAnd => found,
+            BinOp::Sub | BinOp::BitXor => !found,
+            _ => true,
+        };
+        if keep {
+            result.set(item, Value::NoneT)?;
+        }
+    }
+    if let BinOp::BitOr | BinOp::BitXor = op {
+        for item in right.keys() {
+            if op == BinOp::BitOr || left.get(&item)?.is_none() {
+                result.set(item, Value::NoneT)?;
+            }
+        }
+    }
+    return Ok(result);
+}
+
+/* set op set, a set or frozenset like the left operand
+ */
+pub fn set_op(op: BinOp, left: &Value, right: &Value) -> EvalResult {
+    match (left, right) {
+        (Value::Set(left_items) | Value::FrozenSet(left_items), Value::Set(right_items) | Value::FrozenSet(right_items)) => {
+            let items = set_items_op(op, &left_items.borrow(), &right_items.borrow())?;
+            return Ok(new_set(items, matches!(left, Value::FrozenSet(_))));
+        }
+        _ => unreachable!(),
+    }
+}
+
+fn is_subset(left: &Dict, right: &Dict) -> bool {
+    return left.len() <= right.len() && left.keys().iter().all(|item| matches!(right.get(item), Ok(Some(_))));
+}
+
+pub fn set_equals(left: &Dict, right: &Dict) -> bool {
+    return left.len() == right.len() && is_subset(left, right);
+}
+
+/* left < right and the like, which for sets are subset tests
+ */
+pub fn set_compare(op: CmpOp, left: &Dict, right: &Dict) -> bool {
+    match op {
+        CmpOp::Lt => return left.len() < right.len() && is_subset(left, right),
+        CmpOp::LtE => return is_subset(left, right),
+        CmpOp::Gt => return right.len() < left.len() && is_subset(right, left),
+        _ => return is_subset(right, left),
+    }
+}
+
+/* the set args[0], which may be a frozenset unless the method changes it
+ */
+fn this_set(name: &str, args: &Args, changes: bool) -> Result<Rc<RefCell<Dict>>, Exception> {
+    match args.positional.first() {
+        Some(Value::Set(items)) => return Ok(items.clone()),
+        Some(Value::FrozenSet(items)) if !changes => return Ok(items.clone()),
+        other => return Err(descriptor_error(name, "set", other)),
+    }
+}
+
+/* a set or frozenset of items, the same type as args[0]
+ */
+fn like_this(args: &Args, items: Dict) -> Value {
+    return new_set(items, matches!(args.positional[0], Value::FrozenSet(_)));
+}
+
+fn add(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("add", 2, 2)?;
+    let items = this_set("add", &args, true)?;
+    interpreter.allocate(2 * size_of::<Value>())?;
+    let item = interpreter.dict_key(&items, &args.positional[1])?;
+    items.borrow_mut().set(item, Value::NoneT)?;
+    return Ok(Value::NoneT);
+}
+
+fn discard(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("discard", 2, 2)?;
+    let items = this_set("discard", &args, true)?;
+    let item = interpreter.dict_key(&items, &args.positional[1])?;
+    let removed = items.borrow_mut().remove(&set_key(&item));
+    removed?;
+    return Ok(Value::NoneT);
+}
+
+fn set_remove(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("remove", 2, 2)?;
+    let items = this_set("remove", &args, true)?;
+    let item = interpreter.dict_key(&items, &args.positional[1])?;
+    let removed = items.borrow_mut().remove(&set_key(&item));
+    match removed? {
+        Some(_) => return Ok(Value::NoneT),
+        None => return Err(key_error(&args.positional[1])),
+    }
+}
+
+/* pop(): an arbitrary item, the last added here
+ */
+fn set_pop(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("pop", 1, 1)?;
+    let last = this_set("pop", &args, true)?.borrow_mut().pop_last();
+    match last {
+        Some((item, _)) => return Ok(item),
+        None => return Err(key_error(&Value::Str(String::from("pop from an empty set")))),
+    }
+}
+
+fn set_clear(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("clear", 1, 1)?;
+    let items = std::mem::take(&mut *this_set("clear", &args, true)?.borrow_mut());
+    drop(items);
+    return Ok(Value::NoneT);
+}
+
+fn set_copy(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("copy", 1, 1)?;
+    if let Value::FrozenSet(_) = args.positional[0] {
+        return Ok(args.positional[0].clone());
+    }
+    let items = this_set("copy", &args, false)?.borrow().clone();
+    interpreter.allocate(2 * items.len() * size_of::<Value>())?;
+    return Ok(new_set(items, false));
+}
+
+/* The methods taking any number of iterables, for each of which the
+ * result is op'd with the items of the iterable: union(*others) and the
+ * like, and update() and the like, which change the set in place
+ */
+fn combine(interpreter: &mut Interpreter, args: &Args, name: &str, op: BinOp, changes: bool) -> Result<Dict, Exception> {
+    if !args.keywords.is_empty() {
+        args.check(name, 1, usize::MAX)?;
+    }
+    let items = this_set(name, args, changes)?;
+    let mut result = items.borrow().clone();
+    for other in &args.positional[1..] {
+        let other = set_of(interpreter, other)?;
+        result = set_items_op(op, &result, &other)?;
+    }
+    interpreter.allocate(2 * result.len() * size_of::<Value>())?;
+    if changes {
+        let old = std::mem::replace(&mut *items.borrow_mut(), result);
+        drop(old);
+        return Ok(Dict::build_new());
+    }
+    return Ok(result);
+}
+
+fn union(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    let items = combine(interpreter, &args, "union", BinOp::BitOr, false)?;
+    return Ok(like_this(&args, items));
+}
+
+fn intersection(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    let items = combine(interpreter, &args, "intersection", BinOp::BitAnd, false)?;
+    return Ok(like_this(&args, items));
+}
+
+fn difference(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    let items = combine(interpreter, &args, "difference", BinOp::Sub, false)?;
+    return Ok(like_this(&args, items));
+}
+
+fn symmetric_difference(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("symmetric_difference", 2, 2)?;
+    let items = combine(interpreter, &args, "symmetric_difference", BinOp::BitXor, false)?;
+    return Ok(like_this(&args, items));
+}
+
+fn set_update(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    combine(interpreter, &args, "update", BinOp::BitOr, true)?;
+    return Ok(Value::NoneT);
+}
+
+fn intersection_update(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    combine(interpreter, &args, "intersection_update", BinOp::BitAnd, true)?;
+    return Ok(Value::NoneT);
+}
+
+fn difference_update(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    combine(interpreter, &args, "difference_update", BinOp::Sub, true)?;
+    return Ok(Value::NoneT);
+}
+
+fn symmetric_difference_update(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("symmetric_difference_update", 2, 2)?;
+    combine(interpreter, &args, "symmetric_difference_update", BinOp::BitXor, true)?;
+    return Ok(Value::NoneT);
+}
+
+/* the set args[0] and the items of the iterable args[1]
+ */
+fn with_other(interpreter: &mut Interpreter, args: &Args, name: &str) -> Result<(Dict, Dict), Exception> {
+    args.check(name, 2, 2)?;
+    let items = this_set(name, args, false)?.borrow().clone();
+    return Ok((items, set_of(interpreter, &args.positional[1])?));
+}
+
+fn issubset(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    let (items, other) = with_other(interpreter, &args, "issubset")?;
+    return Ok(Value::Bool(is_subset(&items, &other)));
+}
+
+fn issuperset(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    let (items, other) = with_other(interpreter, &args, "issuperset")?;
+    return Ok(Value::Bool(is_subset(&other, &items)));
+}
+
+fn isdisjoint(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    let (items, other) = with_other(interpreter, &args, "isdisjoint")?;
+    return Ok(Value::Bool(set_items_op(BinOp::BitAnd, &items, &other)?.is_empty()));
+}
+
+/* Slices
+ */
+
+/* slice(start, stop, step), which a[start:stop:step] gives an instance's
+ * __getitem__ and the like
+ */
+pub struct Slice {
+    bounds: [Value; 3],
+}
+
+pub fn new_slice(class: Rc<Class>, bounds: [Value; 3]) -> Value {
+    return Value::Object(Object::build_new(class, Some(Box::new(Slice { bounds }))));
+}
+
+/* the start, stop and step of a slice object
+ */
+pub fn slice_of(value: &Value) -> Option<[Value; 3]> {
+    return with_native(value, |slice: &Slice| slice.bounds.clone());
+}
+
+pub fn slice_class(object: Rc<Class>) -> Rc<Class> {
+    let mut class = NativeClass::<Slice>::build_new("slice");
+    // slice(stop) or slice(start, stop[, step])
+    class.raw_new(|_, class, args| {
+        args.check("slice", 1, 3)?;
+        let mut bounds = [Value::NoneT, Value::NoneT, Value::NoneT];
+        match &args.positional[..] {
+            [stop] => bounds[1] = stop.clone(),
+            given => bounds[..given.len()].clone_from_slice(given),
+        };
+        return Ok(new_slice(class, bounds));
+    });
+    class.getter("start", |this: &Slice| this.bounds[0].clone());
+    class.getter("stop", |this: &Slice| this.bounds[1].clone());
+    class.getter("step", |this: &Slice| this.bounds[2].clone());
+    // indices(length): the start, stop and step of the indexes the slice
+    // selects from a sequence of that length
+    class.raw_method("indices", |_, args| {
+        args.check("indices", 2, 2)?;
+        let bounds = instance::<Slice>("slice", "indices", &args)?.bounds.clone();
+        let len = int_arg(&args.positional[1])?;
+        if len < 0 {
+            return Err(value_error("length should not be negative"));
+        }
+        let mut ints: Vec<Option<i64>> = Vec::new();
+        for bound in &bounds {
+            match (bound, slice_index(bound)) {
+                (Value::NoneT, _) => ints.push(None),
+                (_, Some(val)) => ints.push(Some(val)),
+                _ => {
+                    let message = "slice indices must be integers or None or have an __index__ method";
+                    return Err(type_error(String::from(message)));
+                }
+            };
+        }
+        let (start, stop, step) = adjust_slice(len, ints[0], ints[1], ints[2])?;
+        return Ok(new_tuple(vec![Value::Int(start), Value::Int(stop), Value::Int(step)]));
+    });
+    class.repr(|this: &Slice| {
+        let reprs: Vec<String> = this.bounds.iter().map(Value::repr).collect();
+        return format!("slice({})", reprs.join(", "));
+    });
+    class.eq(|this: &Slice, other: &Value| match slice_of(other) {
+        Some(bounds) => this.bounds.iter().zip(bounds.iter()).all(|(left, right)| items_equal(left, right)),
+        None => false,
+    });
+    class.hash(|_: &Slice| Err(type_error(String::from("unhashable type: 'slice'"))));
+    return class.build(object);
+}
+
+/* The views of a dict
+ */
+
+#[derive(Clone, Copy)]
+enum ViewKind {
+    Keys,
+    Values,
+    Items,
+}
+
+impl ViewKind {
+    fn method(self) -> &'static str {
+        match self {
+            ViewKind::Keys => return "keys",
+            ViewKind::Values => return "values",
+            ViewKind::Items => return "items",
+        }
+    }
+
+    fn class_name(self) -> &'static str {
+        match self {
+            ViewKind::Keys => return "dict_keys",
+            ViewKind::Values => return "dict_values",
+            ViewKind::Items => return "dict_items",
+        }
+    }
+
+    fn iterator_name(self) -> &'static str {
+        match self {
+            ViewKind::Keys => return "dict_keyiterator",
+            ViewKind::Values => return "dict_valueiterator",
+            ViewKind::Items => return "dict_itemiterator",
+        }
+    }
+}
+
+struct View {
+    dict: Rc<RefCell<Dict>>,
+    kind: ViewKind,
+}
+
+impl View {
+    // what the view shows of the dict now
+    fn items(&self) -> Vec<Value> {
+        let dict = self.dict.borrow();
+        match self.kind {
+            ViewKind::Keys => return dict.keys(),
+            ViewKind::Values => return dict.entries().map(|(_, value)| value.clone()).collect(),
+            ViewKind::Items => {
+                return dict.entries().map(|(key, value)| new_tuple(vec![key.clone(), value.clone()])).collect();
+            }
+        }
+    }
+}
+
+fn view_class(kind: ViewKind) -> NativeClass<View> {
+    let name = kind.class_name();
+    let mut class = NativeClass::<View>::build_new(name);
+    class.raw_method("__len__", move |_, args| {
+        args.check("__len__", 1, 1)?;
+        return Ok(Value::Int(instance::<View>(name, "__len__", &args)?.dict.borrow().len() as i64));
+    });
+    class.raw_method("__iter__", move |_, args| {
+        args.check("__iter__", 1, 1)?;
+        let items = instance::<View>(name, "__iter__", &args)?.items();
+        return Ok(new_iterator(Iter::Items(items, 0, kind.iterator_name())));
+    });
+    class.raw_method("__contains__", move |_, args| {
+        args.check("__contains__", 2, 2)?;
+        let view = instance::<View>(name, "__contains__", &args)?;
+        let item = &args.positional[1];
+        let found = match view.kind {
+            ViewKind::Keys => view.dict.borrow().get(item)?.is_some(),
+            ViewKind::Values => view.items().iter().any(|value| items_equal(value, item)),
+            // a key and value pair is in the items if the key maps to the
+            // value
+            ViewKind::Items => match item {
+                Value::Tuple(pair) if pair.len() == 2 => {
+                    let value = view.dict.borrow().get(&pair[0])?;
+                    value.map(|value| items_equal(&value, &pair[1])).unwrap_or(false)
+                }
+                _ => false,
+            },
+        };
+        return Ok(Value::Bool(found));
+    });
+    class.repr(move |this: &View| {
+        // the dict may be being changed, by the code that made the repr
+        let items = match this.dict.try_borrow() {
+            Ok(_) => this.items(),
+            Err(_) => Vec::new(),
+        };
+        return format!("{}({})", name, new_list(items).repr());
+    });
+    class.truth(|this: &View| !this.dict.borrow().is_empty());
+    return class;
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testing::{error, eval, exec, python};
+
+    #[test]
+    fn test_list_methods() {
+        let mut python = python("x = [3, 1, 2]\nx.append(4)\nx.extend((5, 6))\nx.insert(0, 0)\nx.insert(-1, 9)\nx.insert(100, 7)");
+        assert_eq!(eval(&mut python, "x"), "[0, 3, 1, 2, 4, 5, 9, 6, 7]");
+        assert_eq!(eval(&mut python, "(x.pop(), x.pop(0), x.pop(-2), x)"), "(7, 0, 9, [3, 1, 2, 4, 5, 6])");
+        exec(&mut python, "x.remove(4)\nx.reverse()");
+        assert_eq!(eval(&mut python, "(x, x.index(1), x.index(6, 0, 1), [1, 2, 1].count(1), x.copy() is x)"), "([6, 5, 2, 1, 3], 3, 0, 2, False)");
+        exec(&mut python, "y = x\nx.clear()");
+        assert_eq!(eval(&mut python, "(x, y)"), "([], [])");
+        assert_eq!(error(&mut python, "[].pop()"), "IndexError: pop from empty list");
+        assert_eq!(error(&mut python, "[1].pop(5)"), "IndexError: pop index out of range");
+        assert_eq!(error(&mut python, "[1].remove(2)"), "ValueError: list.remove(x): x not in list");
+        assert_eq!(error(&mut python, "[1].index('a')"), "ValueError: 'a' is not in list");
+        assert_eq!(error(&mut python, "[1, 2].index(1, 1)"), "ValueError: 1 is not in list");
+    }
+
+    #[test]
+    fn test_sort() {
+        let mut python = python("x = [3, 1, 2]\nx.sort()\nwords = ['bb', 'a', 'ccc', 'dd']\nwords.sort(key=len, reverse=True)");
+        assert_eq!(eval(&mut python, "(x, words)"), "([1, 2, 3], ['ccc', 'bb', 'dd', 'a'])");
+        assert_eq!(error(&mut python, "[1, 'a'].sort()"), "TypeError: '<' not supported between instances of 'str' and 'int'");
+        assert_eq!(error(&mut python, "[1].sort(len)"), "TypeError: sort() takes no positional arguments");
+        exec(&mut python, "y = [2, 'a', 1]\ntry:\n    y.sort()\nexcept TypeError:\n    pass");
+        assert_eq!(eval(&mut python, "y"), "[2, 'a', 1]");
+        exec(&mut python, "z = [2, 1]\ndef key(item):\n    z.append(item)\n    return item");
+        assert_eq!(error(&mut python, "z.sort(key=key)"), "ValueError: list modified during sort");
+    }
+
+    #[test]
+    fn test_tuple_methods() {
+        let mut python = python("");
+        assert_eq!(eval(&mut python, "((1, 2, 1).count(1), (1, 2, 1).index(1, 1), (1, 2).index(2, -1))"), "(2, 2, 1)");
+        assert_eq!(error(&mut python, "(1,).index(2)"), "ValueError: tuple.index(x): x not in tuple");
+    }
+
+    #[test]
+    fn test_sequence_operators() {
+        let mut python = python("x = [1]\ny = x\nx += (2,)\nx *= 2\nz = [0] * 3");
+        assert_eq!(eval(&mut python, "([1] + [2], (1,) + (2,), [1, 2] * 2, 2 * (0,), [1] * -1, y, z)"), "([1, 2], (1, 2), [1, 2, 1, 2], (0, 0), [], [1, 2, 1, 2], [0, 0, 0])");
+        assert_eq!(error(&mut python, "[1] + (2,)"), "TypeError: can only concatenate list (not \"tuple\") to list");
+        assert_eq!(error(&mut python, "(1,) + [2]"), "TypeError: can only concatenate tuple (not \"list\") to tuple");
+        assert_eq!(error(&mut python, "[1] * 'a'"), "TypeError: can't multiply sequence by non-int of type 'str'");
+        assert_eq!(error(&mut python, "[1, 2] * 2 ** 62"), "MemoryError");
+    }
+
+    #[test]
+    fn test_dict_methods() {
+        let mut python = python("d = {'a': 1}\nd.update({'b': 2}, c=3)\nd.update([('d', 4)])");
+        assert_eq!(eval(&mut python, "d"), "{'a': 1, 'b': 2, 'c': 3, 'd': 4}");
+        assert_eq!(eval(&mut python, "(d.get('a'), d.get('x'), d.get('x', 0), d.setdefault('a', 5), d.setdefault('e', 5))"), "(1, None, 0, 1, 5)");
+        assert_eq!(eval(&mut python, "(d.pop('e'), d.pop('x', None), d.popitem(), d)"), "(5, None, ('d', 4), {'a': 1, 'b': 2, 'c': 3})");
+        exec(&mut python, "d['d'] = 4");
+        assert_eq!(eval(&mut python, "(d.copy(), d.copy() is d, dict.fromkeys('ab'), dict.fromkeys([1], 0))"), "({'a': 1, 'b': 2, 'c': 3, 'd': 4}, False, {'a': None, 'b': None}, {1: 0})");
+        exec(&mut python, "d.clear()");
+        assert_eq!(eval(&mut python, "d"), "{}");
+        assert_eq!(error(&mut python, "d.pop('x')"), "KeyError: 'x'");
+        assert_eq!(error(&mut python, "d.popitem()"), "KeyError: 'popitem(): dictionary is empty'");
+        assert_eq!(error(&mut python, "d.update([(1, 2, 3)])"), "ValueError: dictionary update sequence element #0 has length 3; 2 is required");
+        assert_eq!(error(&mut python, "d.update([1])"), "TypeError: cannot convert dictionary update sequence element #0 to a sequence");
+    }
+
+    #[test]
+    fn test_dict_operators() {
+        let mut python = python("d = {'a': 1}\ne = d\nd |= [('b', 2)]");
+        assert_eq!(eval(&mut python, "({'a': 1, 'b': 2} | {'b': 3, 'c': 4}, e)"), "({'a': 1, 'b': 3, 'c': 4}, {'a': 1, 'b': 2})");
+        exec(&mut python, "class Mapping:\n    def keys(self):\n        return ['x']\n    def __getitem__(self, key):\n        return key * 2");
+        assert_eq!(eval(&mut python, "dict(Mapping())"), "{'x': 'xx'}");
+    }
+
+    #[test]
+    fn test_views() {
+        let mut python = python("d = {'a': 1, 'b': 2}\nkeys = d.keys()\nvalues = d.values()\nitems = d.items()\nd['c'] = 3");
+        assert_eq!(eval(&mut python, "(keys, values, items)"), "(dict_keys(['a', 'b', 'c']), dict_values([1, 2, 3]), dict_items([('a', 1), ('b', 2), ('c', 3)]))");
+        assert_eq!(eval(&mut python, "(len(keys), list(keys), list(values), list(items), 'a' in keys, 2 in values, ('b', 2) in items, ('b', 3) in items)"), "(3, ['a', 'b', 'c'], [1, 2, 3], [('a', 1), ('b', 2), ('c', 3)], True, True, True, False)");
+        assert_eq!(eval(&mut python, "(bool({}.keys()), [k + str(v) for k, v in d.items()], type(iter(keys)))"), "(False, ['a1', 'b2', 'c3'], <class 'dict_keyiterator'>)");
+    }
+    #[test]
+    fn test_sets() {
+        let mut python = python("s = {1, 2, 3, 2}\nt = set('abca')\nu = frozenset([1, 2])");
+        assert_eq!(eval(&mut python, "(s, len(t), u, set(), frozenset(), {x % 3 for x in range(10)}, type(s), type(u))"), "({1, 2, 3}, 3, frozenset({1, 2}), set(), frozenset(), {0, 1, 2}, <class 'set'>, <class 'frozenset'>)");
+        assert_eq!(eval(&mut python, "({1, 2} | {3}, {1, 2} & {2, 3}, {1, 2} - {2}, {1, 2} ^ {2, 3}, u | {3}, {3} | u)"), "({1, 2, 3}, {2}, {1}, {1, 3}, frozenset({1, 2, 3}), {3, 1, 2})");
+        assert_eq!(eval(&mut python, "({1, 2} == {2, 1}, {1, 2} == u, {1} < {1, 2}, {1, 2} < {1, 2}, {1, 2} <= {1, 2}, {1, 2} > {1}, {1} >= {2})"), "(True, True, True, False, True, True, False)");
+        assert_eq!(eval(&mut python, "(2 in s, 5 in s, {1, 2} in {u}, {u: 1}[frozenset([2, 1])], hash(u) == hash(frozenset([2, 1])), {1, True, 1.0})"), "(True, False, True, 1, True, {1})");
+        assert_eq!(error(&mut python, "{[1]}"), "TypeError: unhashable type: 'list'");
+        assert_eq!(error(&mut python, "hash(s)"), "TypeError: unhashable type: 'set'");
+        assert_eq!(error(&mut python, "{1} + {2}"), "TypeError: unsupported operand type(s) for +: 'set' and 'set'");
+    }
+
+    #[test]
+    fn test_set_methods() {
+        let mut python = python("s = {1, 2}\ns.add(3)\ns.discard(10)\ns.remove(1)\nt = {1, 2, 3}\nt.update([4], (5,))\nt.difference_update({1})\nt.intersection_update(range(5))\nt.symmetric_difference_update({2, 9})");
+        assert_eq!(eval(&mut python, "(sorted(s), s.pop(), s, t)"), "([2, 3], 3, {2}, {3, 4, 9})");
+        assert_eq!(eval(&mut python, "(t.union([0]), t.intersection([3, 4], {4}), t.difference([3]), t.symmetric_difference([3, 7]), t.copy() is t)"), "({3, 4, 9, 0}, {4}, {4, 9}, {4, 9, 7}, False)");
+        assert_eq!(eval(&mut python, "({1}.issubset([1, 2]), {1, 2}.issuperset((1,)), {1}.isdisjoint([2]), frozenset([1]).union([2]))"), "(True, True, True, frozenset({1, 2}))");
+        exec(&mut python, "u = {1}\nv = u\nu |= {2}\nu &= {2, 3}\nu -= {5}\nu ^= {7}\nt.clear()");
+        assert_eq!(eval(&mut python, "(v, t)"), "({2, 7}, set())");
+        assert_eq!(error(&mut python, "set().pop()"), "KeyError: 'pop from an empty set'");
+        assert_eq!(error(&mut python, "{1}.remove(2)"), "KeyError: 2");
+        assert_eq!(error(&mut python, "frozenset().add(1)"), "AttributeError: 'frozenset' object has no attribute 'add'");
+    }
+    #[test]
+    fn test_slices() {
+        let mut python = python("s = slice(1, 5, 2)\na = list(range(10))\nb = [0, 1, 2, 3, 4]\nb[slice(1, 3)] = ['x']\ndel b[slice(None, None, 2)]");
+        assert_eq!(eval(&mut python, "(s, slice(3), s.start, s.stop, s.step, type(s))"), "(slice(1, 5, 2), slice(None, 3, None), 1, 5, 2, <class 'slice'>)");
+        assert_eq!(eval(&mut python, "(a[s], 'hello'[slice(1, 3)], (1, 2, 3)[slice(2)], b)"), "([1, 3], 'el', (1, 2), ['x', 4])");
+        assert_eq!(eval(&mut python, "(slice(None, None, -1).indices(5), slice(-3, None).indices(5), slice(1, 2) == slice(1, 2), slice(1, 2) == slice(1, 3))"), "((4, -1, -1), (2, 5, 1), True, False)");
+        exec(&mut python, "class C:\n    def __getitem__(self, index):\n        return index");
+        assert_eq!(eval(&mut python, "(C()[1:2], C()[::2], C()['a':'b'], C()[1])"), "(slice(1, 2, None), slice(None, None, 2), slice('a', 'b', None), 1)");
+        assert_eq!(error(&mut python, "hash(slice(1))"), "TypeError: unhashable type: 'slice'");
+        assert_eq!(error(&mut python, "a['a':]"), "TypeError: slice indices must be integers or None or have an __index__ method");
+        assert_eq!(error(&mut python, "slice(1, 2, 0).indices(3)"), "ValueError: slice step cannot be zero");
+    }
+}
@@ -0,0 +1,201 @@
+use crate::bytes;
+use crate::dict::{new_dict, Dict};
+use crate::interpreter::int_to_float;
+use crate::value::{new_list, Exception, Value};
+use std::collections::HashMap;
+
+/* Conversions between Rust values and interpreter objects.
+ *
+ * Rust values become objects through From/Into: integers, floats, bools,
+ * strings, byte slices (bytes), () (None), Option (None or the value), Vec
+ * (a list) and HashMap<String, _> (a dict). The other way, FromValue extracts a Rust
+ * value from an object, raising TypeError when it is the wrong type.
+ */
+pub trait FromValue: Sized {
+    fn from_value(value: &Value) -> Result<Self, Exception>;
+}
+
+fn wrong_type(expected: &str, value: &Value) -> Exception {
+    let message = format!("must be {}, not {}", expected, value.type_name());
+    return Exception::new("TypeError", &message);
+}
+
+impl From<i64> for Value {
+    fn from(val: i64) -> Value {
+        return Value::Int(val);
+    }
+}
+
+impl From<i32> for Value {
+    fn from(val: i32) -> Value {
+        return Value::Int(val as i64);
+    }
+}
+
+impl From<f64> for Value {
+    fn from(val: f64) -> Value {
+        return Value::Float(val);
+    }
+}
+
+impl From<bool> for Value {
+    fn from(val: bool) -> Value {
+        return Value::Bool(val);
+    }
+}
+
+impl From<&str> for Value {
+    fn from(val: &str) -> Value {
+        return Value::Str(String::from(val));
+    }
+}
+
+impl From<String> for Value {
+    fn from(val: String) -> Value {
+        return Value::Str(val);
+    }
+}
+
+impl From<&[u8]> for Value {
+    fn from(val: &[u8]) -> Value {
+        return bytes::new_bytes(val.to_vec());
+    }
+}
+
+impl From<()> for Value {
+    fn from(_: ()) -> Value {
+        return Value::NoneT;
+    }
+}
+
+impl<T: Into<Value>> From<Option<T>> for Value {
+    fn from(val: Option<T>) -> Value {
+        match val {
+            Some(val) => return val.into(),
+            None => return Value::NoneT,
+        }
+    }
+}
+
+impl<T: Into<Value>> From<Vec<T>> for Value {
+    fn from(items: Vec<T>) -> Value {
+        return new_list(items.into_iter().map(Into::into).collect());
+    }
+}
+
+impl<T: Into<Value>> From<HashMap<String, T>> for Value {
+    fn from(map: HashMap<String, T>) -> Value {
+        let vars: HashMap<String, Value> = map.into_iter().map(|(key, value)| (key, value.into())).collect();
+        return new_dict(Dict::from_strings(&vars));
+    }
+}
+
+impl From<std::io::Error> for Exception {
+    fn from(err: std::io::Error) -> Exception {
+        return Exception::new("OSError", &err.to_string());
+    }
+}
+
+impl FromValue for Value {
+    fn from_value(value: &Value) -> Result<Value, Exception> {
+        return Ok(value.clone());
+    }
+}
+
+impl FromValue for i64 {
+    fn from_value(value: &Value) -> Result<i64, Exception> {
+        match value {
+            Value::Int(val) => return Ok(*val),
+            Value::Bool(val) => return Ok(*val as i64),
+            Value::BigInt(_) => return Err(Exception::new("OverflowError", "int too big to convert")),
+            _ => return Err(wrong_type("int", value)),
+        }
+    }
+}
+
+impl FromValue for f64 {
+    fn from_value(value: &Value) -> Result<f64, Exception> {
+        match value {
+            Value::Float(val) => return Ok(*val),
+            Value::Int(val) => return Ok(*val as f64),
+            Value::Bool(val) => return Ok(*val as i64 as f64),
+            Value::BigInt(val) => return int_to_float(val),
+            _ => return Err(wrong_type("float", value)),
+        }
+    }
+}
+
+impl FromValue for bool {
+    fn from_value(value: &Value) -> Result<bool, Exception> {
+        match value {
+            Value::Bool(val) => return Ok(*val),
+            _ => return Err(wrong_type("bool", value)),
+        }
+    }
+}
+
+impl FromValue for String {
+    fn from_value(value: &Value) -> Result<String, Exception> {
+        match value {
+            Value::Str(val) => return Ok(val.clone()),
+            _ => return Err(wrong_type("str", value)),
+        }
+    }
+}
+
+impl<T: FromValue> FromValue for Option<T> {
+    fn from_value(value: &Value) -> Result<Option<T>, Exception> {
+        match value {
+            Value::NoneT => return Ok(None),
+            _ => return Ok(Some(T::from_value(value)?)),
+        }
+    }
+}
+
+impl<T: FromValue> FromValue for Vec<T> {
+    fn from_value(value: &Value) -> Result<Vec<T>, Exception> {
+        match value {
+            Value::List(items) => return items.borrow().iter().map(T::from_value).collect(),
+            Value::Tuple(items) => return items.iter().map(T::from_value).collect(),
+            _ => return Err(wrong_type("list", value)),
+        }
+    }
+}
+
+impl<T: FromValue> FromValue for HashMap<String, T> {
+    fn from_value(value: &Value) -> Result<HashMap<String, T>, Exception> {
+        let dict = match value {
+            Value::Dict(dict) => dict,
+            _ => return Err(wrong_type("dict", value)),
+        };
+        let mut map: HashMap<String, T> = HashMap::new();
+        for (key, value) in dict.borrow().items() {
+            map.insert(String::from_value(&key)?, T::from_value(&value)?);
+        }
+        return Ok(map);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        assert_eq!(i64::from_value(&Value::from(3)).unwrap(), 3);
+        assert_eq!(f64::from_value(&Value::from(2)).unwrap(), 2.0);
+        assert_eq!(Option::<String>::from_value(&Value::from(())).unwrap(), None);
+        let items = Value::from(vec!["a", "b"]);
+        assert_eq!(items.repr(), "['a', 'b']");
+        assert_eq!(Vec::<String>::from_value(&items).unwrap(), vec!["a", "b"]);
+        let mut map: HashMap<String, i64> = HashMap::new();
+        map.insert(String::from("y"), 2);
+        map.insert(String::from("x"), 1);
+        let dict = Value::from(map.clone());
+        assert_eq!(dict.repr(), "{'x': 1, 'y': 2}");
+        assert_eq!(HashMap::<String, i64>::from_value(&dict).unwrap(), map);
+
+        let err = bool::from_value(&Value::from(1)).unwrap_err();
+        assert_eq!(err.to_string(), "TypeError: must be bool, not int");
+    }
+}
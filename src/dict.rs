@@ -0,0 +1,241 @@
+use crate::gc::{self, Tracked};
+use crate::modules::{decimal, fractions};
+use crate::value::{Exception, Value};
+use num_bigint::BigInt;
+use num_rational::BigRational;
+use num_traits::ToPrimitive;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::rc::Rc;
+
+/* Hashable view of a value, used as the real key of a Dict. Values that
+ * compare equal in Python map to the same key (1 == 1.0 == True).
+ */
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum HashKey {
+    NoneT,
+    Int(i64),
+    Float(u64),
+    Complex(u64, u64),
+    // a Fraction or Decimal no int or float is equal to
+    Rational(BigInt, BigInt),
+    Str(String),
+    Bytes(Vec<u8>),
+    Tuple(Vec<HashKey>),
+    Range(i64, i64, i64),
+    // the keys of a frozenset's items, sorted so equal sets have equal keys
+    FrozenSet(Vec<HashKey>),
+    // objects hashed by identity
+    Ptr(usize),
+}
+
+impl HashKey {
+    pub fn from_value(value: &Value) -> Result<HashKey, Exception> {
+        match value {
+            Value::NoneT => return Ok(HashKey::NoneT),
+            Value::Bool(val) => return Ok(HashKey::Int(*val as i64)),
+            Value::Int(val) => return Ok(HashKey::Int(*val)),
+            Value::BigInt(val) => return Ok(HashKey::from_rational(&BigRational::from_integer((**val).clone()))),
+            Value::Float(val) => {
+                if val.fract() == 0.0 && val.abs() < 9.2e18 {
+                    return Ok(HashKey::Int(*val as i64));
+                }
+                return Ok(HashKey::Float(val.to_bits()));
+            }
+            // a complex number with no imaginary part is equal to its real part
+            Value::Complex(real, imag) if *imag == 0.0 => return HashKey::from_value(&Value::Float(*real)),
+            Value::Complex(real, imag) => return Ok(HashKey::Complex((real + 0.0).to_bits(), imag.to_bits())),
+            Value::Fraction(val) => return Ok(HashKey::from_rational(val)),
+            Value::Decimal(val) if val.is_special() => {
+                if val.kind == decimal::Kind::SNaN {
+                    return Err(Exception::new("TypeError", "Cannot hash a signaling NaN value"));
+                }
+                // a NaN is only equal to itself
+                if val.is_nan() {
+                    return Ok(HashKey::Ptr(Rc::as_ptr(val) as usize));
+                }
+                return HashKey::from_value(&Value::Float(val.to_f64()));
+            }
+            Value::Decimal(val) => {
+                let (numer, denom) = val.as_integer_ratio();
+                return Ok(HashKey::from_rational(&BigRational::new_raw(numer, denom)));
+            }
+            Value::Str(val) => return Ok(HashKey::Str(val.clone())),
+            Value::Bytes(data) => return Ok(HashKey::Bytes(data.to_vec())),
+            // a read-only view hashes like the bytes it shows
+            Value::MemoryView(view) => {
+                view.check()?;
+                if !view.readonly {
+                    return Err(Exception::new("ValueError", "cannot hash writable memoryview object"));
+                }
+                return Ok(HashKey::Bytes(view.to_vec()));
+            }
+            Value::Tuple(items) => {
+                let keys: Result<Vec<HashKey>, Exception> = items.iter().map(HashKey::from_value).collect();
+                return Ok(HashKey::Tuple(keys?));
+            }
+            Value::Range(start, stop, step) => return Ok(HashKey::Range(*start, *stop, *step)),
+            Value::FrozenSet(items) => {
+                let keys: Result<Vec<HashKey>, Exception> = items.borrow().keys().iter().map(HashKey::from_value).collect();
+                let mut keys = keys?;
+                keys.sort();
+                return Ok(HashKey::FrozenSet(keys));
+            }
+            Value::List(_) | Value::Dict(_) | Value::Set(_) | Value::ByteArray(_) => {
+                let message = format!("unhashable type: '{}'", value.type_name());
+                return Err(Exception::new("TypeError", &message));
+            }
+            Value::Object(object) => match object.native_hash() {
+                Some(key) => return key,
+                None => return Ok(HashKey::Ptr(value.identity())),
+            },
+            _ => return Ok(HashKey::Ptr(value.identity())),
+        }
+    }
+
+    /* the key of an exact number: the key of the int or float equal to it,
+     * if there is one
+     */
+    fn from_rational(val: &BigRational) -> HashKey {
+        if let Some(val) = val.to_integer().to_i64().filter(|_| val.is_integer()) {
+            return HashKey::Int(val);
+        }
+        let float = fractions::to_f64(val);
+        if BigRational::from_float(float).as_ref() == Some(val) {
+            return HashKey::Float(float.to_bits());
+        }
+        return HashKey::Rational(val.numer().clone(), val.denom().clone());
+    }
+
+    /* hash() of the key: ints hash to themselves, like CPython
+     */
+    pub fn hash_value(&self) -> i64 {
+        if let HashKey::Int(val) = self {
+            return if *val == -1 { -2 } else { *val };
+        }
+        let mut hasher = DefaultHasher::new();
+        self.hash(&mut hasher);
+        return hasher.finish() as i64;
+    }
+}
+
+/* Insertion-ordered dictionary. Removed entries leave a hole in `entries`
+ * so indexes stay valid.
+ */
+#[derive(Debug, Clone, Default)]
+pub struct Dict {
+    index: HashMap<HashKey, usize>,
+    entries: Vec<Option<(Value, Value)>>,
+}
+
+impl Dict {
+    pub fn build_new() -> Dict {
+        return Dict::default();
+    }
+
+    pub fn len(&self) -> usize {
+        return self.index.len();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.index.is_empty();
+    }
+
+    pub fn get(&self, key: &Value) -> Result<Option<Value>, Exception> {
+        let key = HashKey::from_value(key)?;
+        return Ok(self.index.get(&key).and_then(|pos| self.entries[*pos].as_ref()).map(|(_, value)| value.clone()));
+    }
+
+    pub fn set(&mut self, key: Value, value: Value) -> Result<(), Exception> {
+        let hash_key = HashKey::from_value(&key)?;
+        match self.index.get(&hash_key) {
+            // keep the original key object, like Python
+            Some(pos) => self.entries[*pos].as_mut().unwrap().1 = value,
+            None => {
+                self.index.insert(hash_key, self.entries.len());
+                self.entries.push(Some((key, value)));
+            }
+        };
+        return Ok(());
+    }
+
+    /* remove a key, returning its value if it was there
+     */
+    pub fn remove(&mut self, key: &Value) -> Result<Option<Value>, Exception> {
+        let key = HashKey::from_value(key)?;
+        return Ok(self.index.remove(&key).and_then(|pos| self.entries[pos].take()).map(|(_, value)| value));
+    }
+
+    /* remove and return the last entry added
+     */
+    pub fn pop_last(&mut self) -> Option<(Value, Value)> {
+        while let Some(entry) = self.entries.pop() {
+            if let Some((key, value)) = entry {
+                if let Ok(hash_key) = HashKey::from_value(&key) {
+                    self.index.remove(&hash_key);
+                }
+                return Some((key, value));
+            }
+        }
+        return None;
+    }
+
+    pub fn items(&self) -> Vec<(Value, Value)> {
+        return self.entries.iter().flatten().cloned().collect();
+    }
+
+    pub fn entries(&self) -> impl Iterator<Item = &(Value, Value)> {
+        return self.entries.iter().flatten();
+    }
+
+    /* empty the dict, returning what it had
+     */
+    pub fn take_entries(&mut self) -> impl Iterator<Item = (Value, Value)> {
+        return std::mem::take(self).entries.into_iter().flatten();
+    }
+
+    pub fn keys(&self) -> Vec<Value> {
+        return self.entries.iter().flatten().map(|(key, _)| key.clone()).collect();
+    }
+
+    pub fn from_strings(vars: &HashMap<String, Value>) -> Dict {
+        let mut dict = Dict::build_new();
+        let mut names: Vec<&String> = vars.keys().collect();
+        names.sort();
+        for name in names {
+            dict.set(Value::Str(name.clone()), vars[name].clone()).unwrap();
+        }
+        return dict;
+    }
+}
+
+impl PartialEq for Dict {
+    fn eq(&self, other: &Dict) -> bool {
+        if self.len() != other.len() {
+            return false;
+        }
+        return self.entries.iter().flatten().all(|(key, value)| {
+            let key = HashKey::from_value(key).unwrap();
+            match other.index.get(&key).and_then(|pos| other.entries[*pos].as_ref()) {
+                Some((_, other_value)) => value == other_value,
+                None => false,
+            }
+        });
+    }
+}
+
+pub fn new_dict(dict: Dict) -> Value {
+    let dict = Rc::new(std::cell::RefCell::new(dict));
+    gc::track(Tracked::Dict(Rc::downgrade(&dict)));
+    return Value::Dict(dict);
+}
+
+/* A new set or frozenset of the keys of items, which the cycle collector
+ * tracks like a dict
+ */
+pub fn new_set(items: Dict, frozen: bool) -> Value {
+    let items = Rc::new(std::cell::RefCell::new(items));
+    gc::track(Tracked::Dict(Rc::downgrade(&items)));
+    return if frozen { Value::FrozenSet(items) } else { Value::Set(items) };
+}
@@ -0,0 +1,216 @@
+use crate::convert::FromValue;
+use crate::interpreter;
+use crate::interrupt::InterruptHandle;
+use crate::limits::Limits;
+use crate::modules;
+use crate::native::{native_function, NativeClass, NativeFunction, NativeModule};
+use crate::parser::Parser;
+use crate::scanner;
+use crate::value::{Args, Exception, Value};
+use std::path::Path;
+
+/* An interpreter for a Rust program to embed: run code in it and exchange
+ * values with it.
+ *
+ *     let mut python = rebar::Interpreter::build_new();
+ *     python.set("limit", 10);
+ *     python.exec("def double(x):\n    return x * 2\n")?;
+ *     let result: i64 = python.eval_as("double(limit)")?;
+ *
+ * Code runs in a __main__ module whose variables get() and set() access.
+ * Errors are the uncaught Python exceptions; format_traceback() gives the
+ * report Python would print.
+ *
+ * Python calls recurse on the Rust stack, so code that recurses deeply
+ * needs a thread with a large stack (the rebar binary uses 512MB).
+ */
+pub struct Interpreter {
+    inner: interpreter::Interpreter,
+}
+
+impl Interpreter {
+    pub fn build_new() -> Interpreter {
+        let mut inner = interpreter::Interpreter::build_new();
+        inner.init_path(None);
+        return Interpreter { inner };
+    }
+
+    /* -O: strip asserts and dead code from what runs from now on
+     */
+    pub fn set_optimize(&mut self, level: u8) {
+        self.inner.optimize = level;
+        modules::sys::set_flags(&self.inner.modules["sys"], level, self.inner.write_cache);
+    }
+
+    /* whether imports write __pycache__ files (on by default)
+     */
+    pub fn set_write_cache(&mut self, write_cache: bool) {
+        self.inner.write_cache = write_cache;
+        modules::sys::set_flags(&self.inner.modules["sys"], self.inner.optimize, write_cache);
+    }
+
+    /* sys.argv: the script and its arguments ([''] until set)
+     */
+    pub fn set_argv(&mut self, argv: &[String]) {
+        modules::sys::set_argv(&self.inner.modules["sys"], argv);
+    }
+
+    /* Limits on what code may do from now on, see Limits
+     */
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.inner.limits = limits;
+    }
+
+    /* A handle that can interrupt running code from another thread, which
+     * then raises KeyboardInterrupt
+     */
+    pub fn interrupt_handle(&self) -> InterruptHandle {
+        return self.inner.interrupt.clone();
+    }
+
+    /* Raise KeyboardInterrupt on Ctrl-C, instead of the process exiting
+     */
+    pub fn catch_sigint(&self) -> std::io::Result<()> {
+        return self.inner.interrupt.catch_sigint();
+    }
+
+    /* Run statements in __main__
+     */
+    pub fn exec(&mut self, source: &str) -> Result<(), Exception> {
+        self.inner.reset_usage();
+        let code = self.inner.compile(source, "<string>")?;
+        let main = self.inner.globals.clone();
+        return self.inner.exec_module(main, &code);
+    }
+
+    /* The value of a single expression, evaluated in __main__
+     */
+    pub fn eval(&mut self, source: &str) -> Result<Value, Exception> {
+        self.inner.reset_usage();
+        let tokens = scanner::tokenize(source).map_err(|err| Parser::syntax_error_at(err, "<string>"))?;
+        let expr = Parser::build_from_tokens(tokens)
+            .parse_eval()
+            .map_err(|err| Parser::syntax_error_at(err, "<string>"))?;
+        return self.inner.eval(&expr);
+    }
+
+    /* eval(), converted to a Rust value
+     */
+    pub fn eval_as<T: FromValue>(&mut self, source: &str) -> Result<T, Exception> {
+        return T::from_value(&self.eval(source)?);
+    }
+
+    /* Run a script as __main__, with its directory at the start of
+     * sys.path like `rebar script.py`
+     */
+    pub fn run_file<P: AsRef<Path>>(&mut self, path: P) -> Result<(), Exception> {
+        let path = path.as_ref();
+        self.inner.init_path(path.parent());
+        self.inner.reset_usage();
+        return self.inner.run_file(path);
+    }
+
+    /* Call a function (or any callable) with positional arguments
+     */
+    pub fn call(&mut self, function: &Value, args: Vec<Value>) -> Result<Value, Exception> {
+        self.inner.reset_usage();
+        return self.inner.call(function, Args::build_new(args));
+    }
+
+    /* A global variable of __main__ (or a builtin), if it exists
+     */
+    pub fn get(&self, name: &str) -> Option<Value> {
+        return self.inner.globals.get(name).or_else(|| self.inner.builtins.get(name));
+    }
+
+    /* get(), converted to a Rust value; NameError if it doesn't exist
+     */
+    pub fn get_as<T: FromValue>(&self, name: &str) -> Result<T, Exception> {
+        match self.get(name) {
+            Some(value) => return T::from_value(&value),
+            None => {
+                let message = format!("name '{}' is not defined", name);
+                return Err(Exception::new("NameError", &message));
+            }
+        }
+    }
+
+    pub fn set<V: Into<Value>>(&mut self, name: &str, value: V) {
+        self.inner.globals.set(name, value.into());
+    }
+
+    /* Make a native function a builtin, callable from any module
+     */
+    pub fn register_function<P, F: NativeFunction<P>>(&mut self, name: &str, function: F) {
+        self.inner.builtins.set(name, native_function(name, function));
+    }
+
+    /* Make a native class a builtin
+     */
+    pub fn register_class<T: 'static>(&mut self, class: NativeClass<T>) {
+        let class = class.build(self.inner.types["object"].clone());
+        self.inner.builtins.set(&class.name.clone(), Value::Class(class));
+    }
+
+    /* Make a native module importable. It replaces any module of the same
+     * name that is already imported.
+     */
+    pub fn register_module(&mut self, module: NativeModule) {
+        let name = module.name.clone();
+        let module = module.build(self.inner.types["object"].clone());
+        self.inner.add_module(&name, module);
+    }
+
+    /* Write the global variables of __main__ to a session file (see
+     * `state save` at the prompt), returning the names of the functions,
+     * classes and builtins left out
+     */
+    pub fn save_session<P: AsRef<Path>>(&mut self, path: P) -> Result<Vec<String>, Exception> {
+        return self.inner.save_session(path.as_ref());
+    }
+
+    /* Set the global variables of __main__ from a session file, returning
+     * how many were set and the errors of the lines that couldn't be read
+     */
+    pub fn load_session<P: AsRef<Path>>(&mut self, path: P) -> Result<(usize, Vec<String>), Exception> {
+        self.inner.reset_usage();
+        return self.inner.load_session(path.as_ref());
+    }
+
+    /* Run the interactive prompt on stdin until it is exited. The error is
+     * the SystemExit of a sys.exit() call.
+     */
+    pub fn repl(&mut self) -> Result<(), Exception> {
+        return Parser::build_new().parse_program(&mut self.inner);
+    }
+}
+
+impl Default for Interpreter {
+    fn default() -> Interpreter {
+        return Interpreter::build_new();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_embedding() {
+        let mut python = Interpreter::build_new();
+        python.set("limit", 10);
+        python.set("names", vec!["a", "b"]);
+        python.exec("def double(x):\n    return x * 2\n\ncount = len(names)\n").unwrap();
+        assert_eq!(python.eval_as::<i64>("double(limit)").unwrap(), 20);
+        assert_eq!(python.get_as::<i64>("count").unwrap(), 2);
+        assert_eq!(python.eval(" 1, 2\n").unwrap().repr(), "(1, 2)");
+
+        let double = python.get("double").unwrap();
+        assert_eq!(python.call(&double, vec![Value::from("ab")]).unwrap(), Value::from("abab"));
+
+        assert_eq!(python.eval("x = 1").unwrap_err().kind, "SyntaxError");
+        assert_eq!(python.get_as::<i64>("missing").unwrap_err().kind, "NameError");
+        let err = python.exec("double()").unwrap_err();
+        assert_eq!(err.to_string(), "TypeError: double() missing 1 required positional argument: 'x'");
+    }
+}
@@ -0,0 +1,326 @@
+use crate::interpreter::Interpreter;
+use crate::modules::json;
+use crate::value::{new_tuple, Args, Builtin, BuiltinFn, Class, Exception, Object, Value};
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/* Exception classes, and the exception objects scripts catch and raise.
+ *
+ * Native code raises an Exception, which names its class (kind) and has
+ * the message; the instance a script sees is only made if it is caught
+ * (or when Python code raises one, which is kept in Exception::value).
+ * The instance's args are its message, or nothing for an empty message.
+ *
+ * Kinds of exceptions that aren't built in, like "decimal.Overflow", are
+ * found in the classes modules register, and any other gets a class of
+ * its own under Exception the first time it is caught.
+ */
+
+// the classes and their bases, bases first
+const CLASSES: &[(&str, &[&str])] = &[
+    ("BaseException", &[]),
+    ("SystemExit", &["BaseException"]),
+    ("KeyboardInterrupt", &["BaseException"]),
+    ("GeneratorExit", &["BaseException"]),
+    // a limit on running code; `except Exception` doesn't catch it
+    ("ResourceLimitError", &["BaseException"]),
+    ("Exception", &["BaseException"]),
+    ("ArithmeticError", &["Exception"]),
+    ("FloatingPointError", &["ArithmeticError"]),
+    ("OverflowError", &["ArithmeticError"]),
+    ("ZeroDivisionError", &["ArithmeticError"]),
+    ("AssertionError", &["Exception"]),
+    ("AttributeError", &["Exception"]),
+    ("BufferError", &["Exception"]),
+    ("EOFError", &["Exception"]),
+    ("ImportError", &["Exception"]),
+    ("ModuleNotFoundError", &["ImportError"]),
+    ("LookupError", &["Exception"]),
+    ("IndexError", &["LookupError"]),
+    ("KeyError", &["LookupError"]),
+    ("MemoryError", &["Exception"]),
+    ("NameError", &["Exception"]),
+    ("UnboundLocalError", &["NameError"]),
+    ("OSError", &["Exception"]),
+    ("ConnectionError", &["OSError"]),
+    ("BrokenPipeError", &["ConnectionError"]),
+    ("FileExistsError", &["OSError"]),
+    ("FileNotFoundError", &["OSError"]),
+    ("InterruptedError", &["OSError"]),
+    ("IsADirectoryError", &["OSError"]),
+    ("NotADirectoryError", &["OSError"]),
+    ("PermissionError", &["OSError"]),
+    ("TimeoutError", &["OSError"]),
+    ("ReferenceError", &["Exception"]),
+    ("RuntimeError", &["Exception"]),
+    ("NotImplementedError", &["RuntimeError"]),
+    ("RecursionError", &["RuntimeError"]),
+    ("StopIteration", &["Exception"]),
+    ("SyntaxError", &["Exception"]),
+    ("IndentationError", &["SyntaxError"]),
+    ("TabError", &["IndentationError"]),
+    ("SystemError", &["Exception"]),
+    ("TypeError", &["Exception"]),
+    ("ValueError", &["Exception"]),
+    ("UnicodeError", &["ValueError"]),
+    ("UnicodeDecodeError", &["UnicodeError"]),
+    ("UnicodeEncodeError", &["UnicodeError"]),
+    // the native modules', which they are set on as attributes
+    ("JSONDecodeError", &["ValueError"]),
+    ("PickleError", &["Exception"]),
+    ("PicklingError", &["PickleError"]),
+    ("UnpicklingError", &["PickleError"]),
+    ("re.error", &["Exception"]),
+    ("io.UnsupportedOperation", &["OSError", "ValueError"]),
+];
+
+/* The exception classes by name, the built-in ones and those of the
+ * native modules
+ */
+pub fn build_classes(object: Rc<Class>) -> HashMap<String, Rc<Class>> {
+    let mut classes: HashMap<String, Rc<Class>> = HashMap::new();
+    for (name, bases) in CLASSES {
+        let bases = match bases {
+            [] => vec![object.clone()],
+            _ => bases.iter().map(|base| classes[*base].clone()).collect(),
+        };
+        classes.insert(String::from(*name), Class::build_new(name, bases, None));
+    }
+    let methods: &[(&str, BuiltinFn)] = &[("__init__", init), ("__str__", str), ("__repr__", repr)];
+    for (name, method) in methods {
+        let method = Builtin::build_new(name, *method);
+        classes["BaseException"].attrs.borrow_mut().insert(String::from(*name), method);
+    }
+    // str(KeyError('a')) is 'a', quoted, being about a key
+    let key_str = Builtin::build_new("__str__", key_error_str);
+    classes["KeyError"].attrs.borrow_mut().insert(String::from("__str__"), key_str);
+    let methods: &[(&str, BuiltinFn)] = &[("__init__", os_error_init), ("__str__", os_error_str)];
+    for (name, method) in methods {
+        let method = Builtin::build_new(name, *method);
+        classes["OSError"].attrs.borrow_mut().insert(String::from(*name), method);
+    }
+    let init = Builtin::build_new("__init__", json::decode_error_init);
+    classes["JSONDecodeError"].attrs.borrow_mut().insert(String::from("__init__"), init);
+    return classes;
+}
+
+/* KeyError(key), whose message is the key's repr
+ */
+pub fn key_error(key: &Value) -> Exception {
+    let mut err = Exception::new("KeyError", &key.repr());
+    err.details_mut().args = Some(vec![key.clone()]);
+    return err;
+}
+
+fn args_of(instance: &Value) -> Vec<Value> {
+    if let Value::Object(object) = instance {
+        if let Some(Value::Tuple(args)) = object.attrs.borrow().get("args") {
+            return args.to_vec();
+        }
+    }
+    return Vec::new();
+}
+
+fn init(_interpreter: &mut Interpreter, args: Args) -> Result<Value, Exception> {
+    let mut positional = args.positional.into_iter();
+    match positional.next() {
+        Some(Value::Object(ref object)) => {
+            object.attrs.borrow_mut().insert(String::from("args"), new_tuple(positional.collect()));
+            return Ok(Value::NoneT);
+        }
+        _ => return Err(Exception::new("TypeError", "descriptor '__init__' requires a 'BaseException' object")),
+    }
+}
+
+/* the message: nothing, the one argument, or the arguments as a tuple
+ */
+fn str(interpreter: &mut Interpreter, args: Args) -> Result<Value, Exception> {
+    args.check("__str__", 1, 1)?;
+    match &args_of(&args.positional[0])[..] {
+        [] => return Ok(Value::Str(String::new())),
+        [arg] => return Ok(Value::Str(interpreter.str(arg)?)),
+        args => return Ok(Value::Str(interpreter.repr(&new_tuple(args.to_vec()))?)),
+    }
+}
+
+fn key_error_str(interpreter: &mut Interpreter, args: Args) -> Result<Value, Exception> {
+    args.check("__str__", 1, 1)?;
+    if let [key] = &args_of(&args.positional[0])[..] {
+        return Ok(Value::Str(interpreter.repr(key)?));
+    }
+    return str(interpreter, args);
+}
+
+/* OSError(errno, strerror[, filename]) has those as attributes, and only
+ * the first two as its args
+ */
+fn os_error_init(interpreter: &mut Interpreter, args: Args) -> Result<Value, Exception> {
+    if args.positional.is_empty() {
+        return init(interpreter, args);
+    }
+    let instance = args.positional[0].clone();
+    let given = args.positional[1..].to_vec();
+    init(interpreter, args)?;
+    if let Value::Object(object) = &instance {
+        let mut attrs = object.attrs.borrow_mut();
+        let mut attr = |name: &str, value: Option<&Value>| {
+            attrs.insert(String::from(name), value.cloned().unwrap_or(Value::NoneT));
+        };
+        let errno = (2..=5).contains(&given.len());
+        attr("errno", given.first().filter(|_| errno));
+        attr("strerror", given.get(1).filter(|_| errno));
+        attr("filename", given.get(2).filter(|_| errno));
+        if errno && given.len() > 2 {
+            attrs.insert(String::from("args"), new_tuple(given[..2].to_vec()));
+        }
+    }
+    return Ok(Value::NoneT);
+}
+
+fn os_error_str(interpreter: &mut Interpreter, args: Args) -> Result<Value, Exception> {
+    args.check("__str__", 1, 1)?;
+    let attr = |name: &str| match &args.positional[0] {
+        Value::Object(object) => return object.attrs.borrow().get(name).cloned().unwrap_or(Value::NoneT),
+        _ => return Value::NoneT,
+    };
+    let (errno, strerror, filename) = (attr("errno"), attr("strerror"), attr("filename"));
+    if let Value::NoneT = errno {
+        return str(interpreter, args);
+    }
+    let message = format!("[Errno {}] {}", interpreter.str(&errno)?, interpreter.str(&strerror)?);
+    match filename {
+        Value::NoneT => return Ok(Value::Str(message)),
+        filename => return Ok(Value::Str(format!("{}: {}", message, interpreter.repr(&filename)?))),
+    }
+}
+
+fn repr(interpreter: &mut Interpreter, args: Args) -> Result<Value, Exception> {
+    args.check("__repr__", 1, 1)?;
+    let instance = &args.positional[0];
+    let mut reprs: Vec<String> = Vec::new();
+    for arg in args_of(instance) {
+        reprs.push(interpreter.repr(&arg)?);
+    }
+    let name = instance.type_name();
+    // a module's exception is shown by its own name, as its class's __name__
+    let name = name.rsplit('.').next().unwrap();
+    return Ok(Value::Str(format!("{}({})", name, reprs.join(", "))));
+}
+
+impl Interpreter {
+    /* The class of exceptions of kind
+     */
+    pub fn exception_class(&mut self, kind: &str) -> Rc<Class> {
+        if let Some(class) = self.exceptions.get(kind) {
+            return class.clone();
+        }
+        let class = Class::build_new(kind, vec![self.exceptions["Exception"].clone()], None);
+        self.exceptions.insert(String::from(kind), class.clone());
+        return class;
+    }
+
+    /* The exception object of err, made the first time it is asked for
+     */
+    pub fn exception_value(&mut self, err: &mut Exception) -> Value {
+        let details = err.details();
+        if let Some(value) = details.and_then(|details| details.value.as_ref()) {
+            return value.clone();
+        }
+        let args = match (err.details().and_then(|details| details.args.as_ref()), err.message.is_empty()) {
+            (Some(args), _) => args.clone(),
+            (None, true) => Vec::new(),
+            (None, false) => vec![Value::Str(err.message.clone())],
+        };
+        let class = self.exception_class(&err.kind);
+        // made as Python code would, OSError(errno, strerror, filename)
+        // setting its attributes
+        let value = match self.call(&Value::Class(class.clone()), Args::build_new(args.clone())) {
+            Ok(value) => value,
+            Err(_) => {
+                let object = Object::build_new(class, None);
+                object.attrs.borrow_mut().insert(String::from("args"), new_tuple(args));
+                Value::Object(object)
+            }
+        };
+        err.details_mut().value = Some(value.clone());
+        return value;
+    }
+
+    /* Set the attributes a caught exception's object has for how it was
+     * raised: __cause__, __context__ and __suppress_context__
+     */
+    pub fn set_exception_attrs(&mut self, err: &mut Exception) {
+        let value = self.exception_value(err);
+        let mut chained = |exception: &mut Option<Exception>| match exception {
+            Some(exception) => {
+                self.set_exception_attrs(exception);
+                return self.exception_value(exception);
+            }
+            None => return Value::NoneT,
+        };
+        let details = err.details_mut();
+        let (cause, context) = (chained(&mut details.cause), chained(&mut details.context));
+        if let Value::Object(object) = &value {
+            let mut attrs = object.attrs.borrow_mut();
+            attrs.insert(String::from("__cause__"), cause);
+            attrs.insert(String::from("__context__"), context);
+            attrs.insert(String::from("__suppress_context__"), Value::Bool(details.suppress_context));
+            attrs.insert(String::from("__traceback__"), Value::NoneT);
+        }
+    }
+
+    /* The exception `raise value` raises: value is an exception object, or
+     * a class to make one of. One being handled is raised again with where
+     * it came from.
+     */
+    pub fn raised(&mut self, value: Value) -> Result<Exception, Exception> {
+        let value = match &value {
+            Value::Class(class) if self.is_exception_class(class) => self.call(&value, Args::default())?,
+            _ => value,
+        };
+        let class = match &value {
+            Value::Object(object) if self.is_exception_class(&object.class) => object.class.clone(),
+            _ => return Err(Exception::new("TypeError", "exceptions must derive from BaseException")),
+        };
+        let handled = self.handling.iter().rev().find(|err| match (err.value(), &value) {
+            (Some(Value::Object(handled)), Value::Object(raised)) => return Rc::ptr_eq(handled, raised),
+            _ => return false,
+        });
+        if let Some(err) = handled {
+            return Ok(err.clone());
+        }
+        let message = self.str(&value)?;
+        let mut err = Exception::new(&class.name, &message);
+        err.details_mut().value = Some(value);
+        return Ok(err);
+    }
+
+    /* Whether err is caught by `except classes`: a class, or a tuple of
+     * them
+     */
+    pub fn exception_matches(&mut self, err: &Exception, classes: &Value) -> Result<bool, Exception> {
+        let class = match err.value() {
+            Some(Value::Object(object)) => object.class.clone(),
+            _ => self.exception_class(&err.kind),
+        };
+        let handled = match classes {
+            Value::Tuple(items) => items.to_vec(),
+            _ => vec![classes.clone()],
+        };
+        let mut matched = false;
+        for handled in handled {
+            match &handled {
+                Value::Class(handled) if self.is_exception_class(handled) => matched |= class.is_subclass(handled),
+                _ => {
+                    let message = "catching classes that do not inherit from BaseException is not allowed";
+                    return Err(Exception::new("TypeError", message));
+                }
+            };
+        }
+        return Ok(matched);
+    }
+
+    pub(crate) fn is_exception_class(&self, class: &Class) -> bool {
+        return class.is_subclass(&self.exceptions["BaseException"]);
+    }
+}
@@ -0,0 +1,943 @@
+use crate::exceptions;
+use crate::interpreter::{as_bigint, as_float, as_int, get_item, int_to_float, EvalResult, Interpreter};
+use crate::limits;
+use crate::modules::{decimal, fractions};
+use crate::value::{format_float, Exception, Value};
+use num_bigint::BigInt;
+use num_traits::{FromPrimitive, Signed, ToPrimitive};
+use std::convert::TryFrom;
+
+/* Formatting values as text: the format-spec mini-language that format()
+ * and str.format() share, str.format()'s replacement fields, and
+ * printf-style formatting with %.
+ *
+ * Only an empty spec formats a Decimal or Fraction, the way str() does;
+ * neither has a mini-language of its own yet. There are no locales, so
+ * 'n' is 'd' for ints and 'g' for floats.
+ *
+ * The width and precision of a field are counted against the memory
+ * limit before it's formatted, and padding that can't be allocated is a
+ * MemoryError.
+ */
+
+fn value_error(message: &str) -> Exception {
+    return Exception::new("ValueError", message);
+}
+
+fn type_error(message: &str) -> Exception {
+    return Exception::new("TypeError", message);
+}
+
+/* a parsed spec: [[fill]align][sign][z][#][0][width][grouping][.precision][type]
+ */
+#[derive(Debug, Clone, Default)]
+struct Spec {
+    fill: Option<char>,
+    align: Option<char>,
+    sign: Option<char>,
+    coerce_zero: bool,
+    alternate: bool,
+    zero: bool,
+    width: usize,
+    grouping: Option<char>,
+    precision: Option<usize>,
+    kind: Option<char>,
+}
+
+/* the number at pos, if there is one, and the position after it
+ */
+fn parse_count(chars: &[char], pos: usize) -> Result<(Option<usize>, usize), Exception> {
+    let end = chars[pos.min(chars.len())..].iter().take_while(|c| c.is_ascii_digit()).count() + pos;
+    if end == pos {
+        return Ok((None, pos));
+    }
+    let digits: String = chars[pos..end].iter().collect();
+    match digits.parse::<usize>() {
+        Ok(count) if count <= i64::MAX as usize => return Ok((Some(count), end)),
+        _ => return Err(value_error("Too many decimal digits in format string")),
+    }
+}
+
+fn parse_spec(text: &str, type_name: &str) -> Result<Spec, Exception> {
+    let chars: Vec<char> = text.chars().collect();
+    let is_align = |c: &char| matches!(c, '<' | '>' | '=' | '^');
+    let mut spec = Spec::default();
+    let mut pos = 0;
+    if chars.get(1).is_some_and(is_align) {
+        spec.fill = Some(chars[0]);
+        spec.align = Some(chars[1]);
+        pos = 2;
+    } else if chars.first().is_some_and(is_align) {
+        spec.align = Some(chars[0]);
+        pos = 1;
+    }
+    if let Some(sign @ ('+' | '-' | ' ')) = chars.get(pos) {
+        spec.sign = Some(*sign);
+        pos += 1;
+    }
+    if chars.get(pos) == Some(&'z') {
+        spec.coerce_zero = true;
+        pos += 1;
+    }
+    if chars.get(pos) == Some(&'#') {
+        spec.alternate = true;
+        pos += 1;
+    }
+    if chars.get(pos) == Some(&'0') {
+        spec.zero = true;
+        pos += 1;
+    }
+    let (width, next) = parse_count(&chars, pos)?;
+    spec.width = width.unwrap_or(0);
+    pos = next;
+    if let Some(grouping @ (',' | '_')) = chars.get(pos) {
+        spec.grouping = Some(*grouping);
+        pos += 1;
+        if let Some(',' | '_') = chars.get(pos) {
+            return Err(value_error("Cannot specify both ',' and '_'."));
+        }
+    }
+    if chars.get(pos) == Some(&'.') {
+        let (precision, next) = parse_count(&chars, pos + 1)?;
+        if precision.is_none() {
+            return Err(value_error("Format specifier missing precision"));
+        }
+        spec.precision = precision;
+        pos = next;
+    }
+    if chars.len() > pos + 1 {
+        return Err(value_error(&format!("Invalid format specifier '{}' for object of type '{}'", text, type_name)));
+    }
+    spec.kind = chars.get(pos).copied();
+    if let (Some(grouping), Some(kind)) = (spec.grouping, spec.kind) {
+        let allowed = match kind {
+            'b' | 'o' | 'x' | 'X' => grouping == '_',
+            'd' | 'e' | 'E' | 'f' | 'F' | 'g' | 'G' | '%' => true,
+            _ => false,
+        };
+        if !allowed {
+            return Err(value_error(&format!("Cannot specify '{}' with '{}'.", grouping, kind)));
+        }
+    }
+    return Ok(spec);
+}
+
+fn unknown_code(kind: char, type_name: &str) -> Exception {
+    return value_error(&format!("Unknown format code '{}' for object of type '{}'", kind, type_name));
+}
+
+/* Pad text to the spec's width. With '=' alignment the padding goes
+ * between the sign (and prefix) and the rest.
+ */
+fn pad(spec: &Spec, head: &str, body: &str, default_align: char) -> Result<String, Exception> {
+    let fill = spec.fill.unwrap_or(if spec.zero { '0' } else { ' ' });
+    let align = spec.align.unwrap_or(if spec.zero && default_align == '>' { '=' } else { default_align });
+    let len = head.chars().count() + body.chars().count();
+    let padding = spec.width.saturating_sub(len);
+    let mut out = limits::new_string(padding.saturating_mul(fill.len_utf8()).saturating_add(head.len() + body.len()))?;
+    let (left, middle) = match align {
+        '<' => (0, 0),
+        '=' => (0, padding),
+        '^' => (padding / 2, 0),
+        _ => (padding, 0),
+    };
+    out.extend(std::iter::repeat_n(fill, left));
+    out.push_str(head);
+    out.extend(std::iter::repeat_n(fill, middle));
+    out.push_str(body);
+    out.extend(std::iter::repeat_n(fill, padding - left - middle));
+    return Ok(out);
+}
+
+/* digits with a separator between each group of size, from the right
+ */
+fn group(digits: &str, separator: char, size: usize) -> String {
+    let count = digits.chars().count();
+    let mut out = String::new();
+    for (index, c) in digits.chars().enumerate() {
+        if index > 0 && (count - index).is_multiple_of(size) {
+            out.push(separator);
+        }
+        out.push(c);
+    }
+    return out;
+}
+
+/* Lay out a number: the sign and prefix, the integer digits, grouped if
+ * the spec asks, and the rest (fraction, exponent, '%')
+ */
+fn format_number(spec: &Spec, negative: bool, prefix: &str, digits: &str, rest: &str) -> Result<String, Exception> {
+    let sign = match (negative, spec.sign) {
+        (true, _) => "-",
+        (false, Some('+')) => "+",
+        (false, Some(' ')) => " ",
+        _ => "",
+    };
+    let head = format!("{}{}", sign, prefix);
+    let separator = match spec.grouping {
+        Some(separator) => separator,
+        None => return pad(spec, &head, &format!("{}{}", digits, rest), '>'),
+    };
+    let size = if matches!(spec.kind, Some('b' | 'o' | 'x' | 'X')) { 4 } else { 3 };
+    // zero padding is grouped too, e.g. format(1234, '08,') is '0,001,234':
+    // n digits grouped take n + (n - 1) / size characters
+    let zero_padded = spec.fill.unwrap_or('0') == '0' && spec.zero && spec.align.unwrap_or('=') == '=';
+    let target = spec.width.saturating_sub(head.len() + rest.chars().count());
+    let count = match zero_padded && target > 0 {
+        true => (target - (target - 1) / (size + 1)).max(digits.len()),
+        false => digits.len(),
+    };
+    let mut padded = limits::new_string(count)?;
+    padded.extend(std::iter::repeat_n('0', count - digits.len()));
+    padded.push_str(digits);
+    return pad(spec, &head, &format!("{}{}", group(&padded, separator, size), rest), '>');
+}
+
+/* the split of formatted digits into the integer digits and the rest
+ */
+fn split_digits(text: &str) -> (&str, &str) {
+    let end = text.find(|c: char| !c.is_ascii_digit()).unwrap_or(text.len());
+    return text.split_at(end);
+}
+
+/* 'e' presentation of a magnitude, e.g. 1.500000e+02
+ */
+fn exponent_form(val: f64, precision: usize, alternate: bool) -> String {
+    let text = format!("{:.*e}", precision, val);
+    let (mantissa, exponent) = text.split_at(text.find('e').unwrap());
+    let exponent: i32 = exponent[1..].parse().unwrap();
+    let point = if alternate && precision == 0 { "." } else { "" };
+    let exp_sign = if exponent < 0 { '-' } else { '+' };
+    return format!("{}{}e{}{:02}", mantissa, point, exp_sign, exponent.abs());
+}
+
+/* 'g' presentation of a magnitude: fixed or exponent form, whichever
+ * precision significant digits suit, without trailing zeros unless
+ * alternate. With no type, fixed form keeps a digit after the point.
+ */
+fn general_form(val: f64, precision: usize, alternate: bool, no_type: bool) -> String {
+    let precision = precision.max(1);
+    let exponent: i64 = match val {
+        0.0 => 0,
+        _ => {
+            let text = format!("{:.*e}", precision - 1, val);
+            text[text.find('e').unwrap() + 1..].parse().unwrap()
+        }
+    };
+    // with no type, a number that needs all its digits before the point
+    // switches to exponent form, since it would get ".0" after them
+    let limit = if no_type { precision as i64 - 1 } else { precision as i64 };
+    let mut text = if (-4..limit).contains(&exponent) {
+        format!("{:.*}", (precision as i64 - 1 - exponent) as usize, val)
+    } else {
+        exponent_form(val, precision - 1, alternate)
+    };
+    let split = text.find('e').unwrap_or(text.len());
+    let (mantissa, exponent) = text.split_at(split);
+    let mut mantissa = String::from(mantissa);
+    if alternate && !mantissa.contains('.') {
+        mantissa.push('.');
+    } else if !alternate && mantissa.contains('.') {
+        mantissa = String::from(mantissa.trim_end_matches('0').trim_end_matches('.'));
+    }
+    if no_type && !mantissa.contains('.') && exponent.is_empty() {
+        mantissa.push_str(".0");
+    }
+    text = format!("{}{}", mantissa, exponent);
+    return text;
+}
+
+fn format_float_spec(val: f64, spec: &Spec, type_name: &str) -> Result<String, Exception> {
+    let precision = spec.precision;
+    if precision.is_some_and(|precision| precision > i32::MAX as usize) {
+        return Err(value_error("precision too big"));
+    }
+    let magnitude = val.abs();
+    let mut body = match spec.kind {
+        _ if val.is_nan() => String::from("nan"),
+        _ if val.is_infinite() => String::from("inf"),
+        Some('f' | 'F') => format!("{:.*}", precision.unwrap_or(6), magnitude),
+        Some('%') => format!("{:.*}", precision.unwrap_or(6), magnitude * 100.0),
+        Some('e' | 'E') => exponent_form(magnitude, precision.unwrap_or(6), spec.alternate),
+        Some('g' | 'G' | 'n') => general_form(magnitude, precision.unwrap_or(6), spec.alternate, false),
+        None => match precision {
+            Some(precision) => general_form(magnitude, precision, spec.alternate, true),
+            None => format_float(magnitude),
+        },
+        Some(kind) => return Err(unknown_code(kind, type_name)),
+    };
+    if spec.alternate && matches!(spec.kind, Some('f' | 'F' | '%')) && precision == Some(0) && val.is_finite() {
+        body.push('.');
+    }
+    if spec.kind == Some('%') {
+        body.push('%');
+    }
+    if matches!(spec.kind, Some('E' | 'F' | 'G')) {
+        body = body.to_uppercase();
+    }
+    let mut negative = val.is_sign_negative() && !val.is_nan();
+    // z makes a zero that was negative, or rounded to zero, positive
+    if spec.coerce_zero && val.is_finite() {
+        let mantissa = &body[..body.find(['e', 'E', '%']).unwrap_or(body.len())];
+        if mantissa.chars().all(|c| c == '0' || c == '.') {
+            negative = false;
+        }
+    }
+    let (digits, rest) = split_digits(&body);
+    return format_number(spec, negative, "", digits, rest);
+}
+
+fn format_int_spec(val: &BigInt, spec: &Spec, type_name: &str) -> Result<String, Exception> {
+    if let Some('e' | 'E' | 'f' | 'F' | 'g' | 'G' | '%') = spec.kind {
+        return format_float_spec(int_to_float(val)?, spec, type_name);
+    }
+    match spec.kind {
+        None | Some('b' | 'd' | 'n' | 'o' | 'x' | 'X') => (),
+        Some('c') if spec.sign.is_some() => return Err(value_error("Sign not allowed with integer format specifier 'c'")),
+        Some('c') if spec.alternate => {
+            return Err(value_error("Alternate form (#) not allowed with integer format specifier 'c'"));
+        }
+        Some('c') => (),
+        Some(kind) => return Err(unknown_code(kind, type_name)),
+    };
+    if spec.precision.is_some() {
+        return Err(value_error("Precision not allowed in integer format specifier"));
+    }
+    if spec.coerce_zero {
+        return Err(value_error("Negative zero coercion (z) not allowed in integer format specifier"));
+    }
+    let magnitude = val.magnitude();
+    let (prefix, digits) = match spec.kind {
+        Some('b') => ("0b", magnitude.to_str_radix(2)),
+        Some('o') => ("0o", magnitude.to_str_radix(8)),
+        Some('x') => ("0x", magnitude.to_str_radix(16)),
+        Some('X') => ("0X", magnitude.to_str_radix(16).to_uppercase()),
+        Some('c') => match val.to_u32().and_then(char::from_u32) {
+            Some(c) => return pad(spec, "", &c.to_string(), '>'),
+            None => return Err(Exception::new("OverflowError", "%c arg not in range(0x110000)")),
+        },
+        _ => ("", magnitude.to_string()),
+    };
+    let prefix = if spec.alternate { prefix } else { "" };
+    return format_number(spec, val.is_negative(), prefix, &digits, "");
+}
+
+fn format_complex_spec(real: f64, imag: f64, spec: &Spec) -> Result<String, Exception> {
+    match spec.kind {
+        None | Some('e' | 'E' | 'f' | 'F' | 'g' | 'G' | 'n') => (),
+        Some(kind) => return Err(unknown_code(kind, "complex")),
+    };
+    if spec.zero {
+        return Err(value_error("Zero padding is not allowed in complex format specifier"));
+    }
+    if spec.align == Some('=') {
+        return Err(value_error("'=' alignment flag is not allowed in complex format specifier"));
+    }
+    // each part formats like a float, with 'g' for no type and a precision,
+    // and like repr() without its ".0" otherwise
+    let part = |val: f64, sign: Option<char>| -> Result<String, Exception> {
+        let kind = match spec.kind {
+            None if spec.precision.is_some() => Some('g'),
+            kind => kind,
+        };
+        let part_spec = Spec {
+            sign,
+            kind,
+            grouping: spec.grouping,
+            precision: spec.precision,
+            alternate: spec.alternate,
+            coerce_zero: spec.coerce_zero,
+            ..Spec::default()
+        };
+        let text = format_float_spec(val, &part_spec, "complex")?;
+        if spec.kind.is_none() && spec.precision.is_none() {
+            return Ok(text.strip_suffix(".0").map(String::from).unwrap_or(text));
+        }
+        return Ok(text);
+    };
+    let body = if spec.kind.is_none() && real == 0.0 && real.is_sign_positive() {
+        format!("{}j", part(imag, spec.sign)?)
+    } else if spec.kind.is_none() {
+        format!("({}{}j)", part(real, spec.sign)?, part(imag, Some('+'))?)
+    } else {
+        format!("{}{}j", part(real, spec.sign)?, part(imag, Some('+'))?)
+    };
+    return pad(spec, "", &body, '>');
+}
+
+fn format_str_spec(text: &str, spec: &Spec) -> Result<String, Exception> {
+    match spec.kind {
+        None | Some('s') => (),
+        Some(kind) => return Err(unknown_code(kind, "str")),
+    };
+    if let Some(grouping) = spec.grouping {
+        return Err(value_error(&format!("Cannot specify '{}' with 's'.", grouping)));
+    }
+    if spec.sign.is_some() {
+        return Err(value_error("Sign not allowed in string format specifier"));
+    }
+    if spec.alternate {
+        return Err(value_error("Alternate form (#) not allowed in string format specifier"));
+    }
+    if spec.align == Some('=') {
+        return Err(value_error("'=' alignment not allowed in string format specifier"));
+    }
+    let text: String = match spec.precision {
+        Some(precision) => text.chars().take(precision).collect(),
+        None => String::from(text),
+    };
+    return pad(spec, "", &text, '<');
+}
+
+/* format(value, spec) for the built-in types
+ */
+pub fn format_value(value: &Value, spec: &str) -> Result<String, Exception> {
+    let type_name = value.type_name();
+    match value {
+        Value::Bool(_) if spec.is_empty() => return Ok(value.to_string()),
+        Value::Bool(_) | Value::Int(_) | Value::BigInt(_) => {
+            return format_int_spec(&as_bigint(value).unwrap(), &parse_spec(spec, type_name)?, type_name);
+        }
+        Value::Float(val) => return format_float_spec(*val, &parse_spec(spec, type_name)?, type_name),
+        Value::Complex(real, imag) => return format_complex_spec(*real, *imag, &parse_spec(spec, type_name)?),
+        Value::Str(text) => return format_str_spec(text, &parse_spec(spec, type_name)?),
+        _ if spec.is_empty() => return Ok(value.to_string()),
+        _ => return Err(type_error(&format!("unsupported format string passed to {}.__format__", type_name))),
+    }
+}
+
+/* format(value, spec): an instance's __format__, if its class has one,
+ * or its str() for an empty spec
+ */
+pub fn format(interpreter: &mut Interpreter, value: &Value, spec: &str) -> Result<String, Exception> {
+    match interpreter.call_special(value, "__format__", vec![Value::Str(String::from(spec))]) {
+        Some(result) => match result? {
+            Value::Str(ref text) => return Ok(text.clone()),
+            other => return Err(type_error(&format!("__format__ must return a str, not {}", other.type_name()))),
+        },
+        None if spec.is_empty() && matches!(value, Value::Object(_) | Value::List(_) | Value::Tuple(_) | Value::Dict(_) | Value::Set(_) | Value::FrozenSet(_)) => {
+            return interpreter.str(value);
+        }
+        None => {
+            // the padding and digits the spec asks for are counted first
+            if let Ok(parsed) = parse_spec(spec, value.type_name()) {
+                let digits = if matches!(value, Value::Str(_)) { 0 } else { parsed.precision.unwrap_or(0) };
+                interpreter.allocate(parsed.width.saturating_add(digits))?;
+            }
+            return format_value(value, spec);
+        }
+    }
+}
+
+/* ascii() of a repr: non-ASCII characters escaped
+ */
+pub fn ascii_escape(text: &str) -> String {
+    let mut out = String::new();
+    for c in text.chars() {
+        match c as u32 {
+            code if code < 0x80 => out.push(c),
+            code if code < 0x100 => out.push_str(&format!("\\x{:02x}", code)),
+            code if code < 0x10000 => out.push_str(&format!("\\u{:04x}", code)),
+            code => out.push_str(&format!("\\U{:08x}", code)),
+        }
+    }
+    return out;
+}
+
+/* object[key], with an instance's __getitem__
+ */
+pub fn subscript(interpreter: &mut Interpreter, object: &Value, key: &Value) -> EvalResult {
+    match interpreter.call_special(object, "__getitem__", vec![key.clone()]) {
+        Some(result) => return result,
+        None => return get_item(object, key),
+    }
+}
+
+/* where str.format() finds named fields: its keyword arguments, or
+ * format_map()'s mapping
+ */
+pub enum Names<'a> {
+    Keywords(&'a [(String, Value)]),
+    Mapping(&'a Value),
+}
+
+/* how a format string numbers its fields: "{}" counts up, "{0}" doesn't,
+ * and one string can't do both
+ */
+enum Numbering {
+    Unknown,
+    Automatic(usize),
+    Manual,
+}
+
+struct Fields<'a> {
+    positional: &'a [Value],
+    names: Names<'a>,
+    numbering: Numbering,
+}
+
+/* str.format(): the string with each replacement field formatted
+ */
+pub fn format_fields(interpreter: &mut Interpreter, text: &str, positional: &[Value], names: Names) -> Result<String, Exception> {
+    let mut fields = Fields {
+        positional,
+        names,
+        numbering: Numbering::Unknown,
+    };
+    return expand(interpreter, text, &mut fields, 2);
+}
+
+/* text with its fields replaced; depth is how deep fields in specs may
+ * nest, e.g. "{:{width}}"
+ */
+fn expand(interpreter: &mut Interpreter, text: &str, fields: &mut Fields, depth: usize) -> Result<String, Exception> {
+    if depth == 0 {
+        return Err(value_error("Max string recursion exceeded"));
+    }
+    let chars: Vec<char> = text.chars().collect();
+    let mut out = String::new();
+    let mut pos = 0;
+    while pos < chars.len() {
+        match (chars[pos], chars.get(pos + 1)) {
+            ('{', Some('{')) | ('}', Some('}')) => {
+                out.push(chars[pos]);
+                pos += 2;
+            }
+            ('}', _) => return Err(value_error("Single '}' encountered in format string")),
+            ('{', None) => return Err(value_error("Single '{' encountered in format string")),
+            ('{', _) => {
+                // the field ends at its matching brace, past any in its spec
+                let mut nesting = 1;
+                let mut end = pos + 1;
+                while end < chars.len() {
+                    match chars[end] {
+                        '{' => nesting += 1,
+                        '}' if nesting == 1 => break,
+                        '}' => nesting -= 1,
+                        _ => (),
+                    };
+                    end += 1;
+                }
+                if end == chars.len() {
+                    return Err(value_error("expected '}' before end of string"));
+                }
+                let field: String = chars[pos + 1..end].iter().collect();
+                out.push_str(&replace_field(interpreter, &field, fields, depth)?);
+                pos = end + 1;
+            }
+            (c, _) => {
+                out.push(c);
+                pos += 1;
+            }
+        }
+    }
+    return Ok(out);
+}
+
+/* a field: name, then !conversion, then :spec
+ */
+fn replace_field(interpreter: &mut Interpreter, field: &str, fields: &mut Fields, depth: usize) -> Result<String, Exception> {
+    let mut in_brackets = false;
+    let mut name_end = field.len();
+    for (index, c) in field.char_indices() {
+        match c {
+            '[' => in_brackets = true,
+            ']' => in_brackets = false,
+            '!' | ':' if !in_brackets => {
+                name_end = index;
+                break;
+            }
+            _ => (),
+        };
+    }
+    let (name, rest) = field.split_at(name_end);
+    let (conversion, spec) = match rest.strip_prefix('!') {
+        Some(rest) => {
+            let mut chars = rest.chars();
+            let conversion = chars.next();
+            let after = chars.as_str();
+            match after.strip_prefix(':') {
+                Some(spec) => (conversion, spec),
+                None if after.is_empty() => (conversion, ""),
+                None => return Err(value_error("expected ':' after conversion specifier")),
+            }
+        }
+        None => (None, rest.strip_prefix(':').unwrap_or(rest)),
+    };
+    let value = lookup_field(interpreter, name, fields)?;
+    let value = match conversion {
+        None => value,
+        Some('r') => Value::Str(interpreter.repr(&value)?),
+        Some('s') => Value::Str(interpreter.str(&value)?),
+        Some('a') => Value::Str(ascii_escape(&interpreter.repr(&value)?)),
+        Some(other) => return Err(value_error(&format!("Unknown conversion specifier {}", other))),
+    };
+    let spec = match spec.contains('{') {
+        true => expand(interpreter, spec, fields, depth - 1)?,
+        false => String::from(spec),
+    };
+    return format(interpreter, &value, &spec);
+}
+
+/* the value a field names: an argument, then any .attribute and [key]
+ * parts
+ */
+fn lookup_field(interpreter: &mut Interpreter, name: &str, fields: &mut Fields) -> EvalResult {
+    let (arg, mut rest) = name.split_at(name.find(['.', '[']).unwrap_or(name.len()));
+    let index = match (arg.parse::<usize>(), &fields.numbering) {
+        (Ok(_), Numbering::Automatic(_)) => {
+            return Err(value_error("cannot switch from automatic field numbering to manual field specification"));
+        }
+        (Ok(index), _) => {
+            fields.numbering = Numbering::Manual;
+            Some(index)
+        }
+        (Err(_), Numbering::Manual) if arg.is_empty() => {
+            return Err(value_error("cannot switch from manual field specification to automatic field numbering"));
+        }
+        (Err(_), Numbering::Automatic(next)) if arg.is_empty() => {
+            let index = *next;
+            fields.numbering = Numbering::Automatic(index + 1);
+            Some(index)
+        }
+        (Err(_), Numbering::Unknown) if arg.is_empty() => {
+            fields.numbering = Numbering::Automatic(1);
+            Some(0)
+        }
+        (Err(_), _) => None,
+    };
+    let mut value = match index {
+        Some(index) => match fields.positional.get(index) {
+            Some(value) => value.clone(),
+            None => {
+                let message = format!("Replacement index {} out of range for positional args tuple", index);
+                return Err(Exception::new("IndexError", &message));
+            }
+        },
+        None => match &fields.names {
+            Names::Keywords(keywords) => match keywords.iter().find(|(key, _)| key == arg) {
+                Some((_, value)) => value.clone(),
+                None => return Err(exceptions::key_error(&Value::Str(String::from(arg)))),
+            },
+            Names::Mapping(mapping) => subscript(interpreter, mapping, &Value::Str(String::from(arg)))?,
+        },
+    };
+    while !rest.is_empty() {
+        if let Some(after) = rest.strip_prefix('.') {
+            let end = after.find(['.', '[']).unwrap_or(after.len());
+            if end == 0 {
+                return Err(value_error("Empty attribute in format string"));
+            }
+            value = interpreter.get_attr(&value, &after[..end])?;
+            rest = &after[end..];
+        } else {
+            let after = &rest[1..];
+            let end = match after.find(']') {
+                Some(end) => end,
+                None => return Err(value_error("Missing ']' in format string")),
+            };
+            let key = match &after[..end] {
+                "" => return Err(value_error("Empty attribute in format string")),
+                key => match key.parse::<i64>() {
+                    Ok(index) => Value::Int(index),
+                    Err(_) => Value::Str(String::from(key)),
+                },
+            };
+            value = subscript(interpreter, &value, &key)?;
+            rest = &after[end + 1..];
+            if !rest.is_empty() && !rest.starts_with(['.', '[']) {
+                return Err(value_error("Only '.' or '[' may follow ']' in format field specifier"));
+            }
+        }
+    }
+    return Ok(value);
+}
+
+/* the int %d formats: ints as they are, and other real numbers truncated
+ */
+fn percent_int(conversion: char, value: &Value) -> Result<BigInt, Exception> {
+    let message = format!("%{} format: a real number is required, not {}", conversion, value.type_name());
+    match value {
+        Value::Float(val) if val.is_nan() => return Err(value_error("cannot convert float NaN to integer")),
+        Value::Float(val) if val.is_infinite() => {
+            return Err(Exception::new("OverflowError", "cannot convert float infinity to integer"));
+        }
+        Value::Float(val) => return Ok(BigInt::from_f64(val.trunc()).unwrap()),
+        Value::Fraction(val) => return Ok(val.to_integer()),
+        Value::Decimal(val) => return as_bigint(&decimal::to_int(val)?).ok_or_else(|| type_error(&message)),
+        _ => return as_bigint(value).ok_or_else(|| type_error(&message)),
+    }
+}
+
+fn percent_float(value: &Value) -> Result<f64, Exception> {
+    match value {
+        Value::Fraction(val) => return Ok(fractions::to_f64(val)),
+        Value::Decimal(val) => return decimal::to_float(val),
+        _ => return as_float(value).ok_or_else(|| type_error(&format!("must be real number, not {}", value.type_name()))),
+    }
+}
+
+/* The text %s or %r (the conversion) gives for a value, without
+ * calling __str__ or __repr__, for constant folding
+ */
+fn plain_text(conversion: char, value: &Value) -> Result<String, Exception> {
+    match conversion {
+        's' => return Ok(value.to_string()),
+        _ => return Ok(value.repr()),
+    }
+}
+
+/* one %-conversion of a value; index is where the conversion character
+ * is, for errors
+ */
+fn percent_field(
+    conversion: char,
+    value: &Value,
+    mut spec: Spec,
+    index: usize,
+    text_of: TextOf,
+) -> Result<String, Exception> {
+    match conversion {
+        's' | 'r' | 'a' | 'c' => {
+            let text = match conversion {
+                's' | 'r' => text_of(conversion, value)?,
+                'a' => ascii_escape(&text_of('r', value)?),
+                _ => match value {
+                    Value::Str(text) if text.chars().count() == 1 => text.clone(),
+                    Value::Int(_) | Value::Bool(_) | Value::BigInt(_) => {
+                        match as_int(value).and_then(|code| u32::try_from(code).ok()).and_then(char::from_u32) {
+                            Some(c) => c.to_string(),
+                            None => return Err(Exception::new("OverflowError", "%c arg not in range(0x110000)")),
+                        }
+                    }
+                    _ => return Err(type_error("%c requires int or char")),
+                },
+            };
+            let text: String = match spec.precision.filter(|_| conversion != 'c') {
+                Some(precision) => text.chars().take(precision).collect(),
+                None => text,
+            };
+            spec.zero = false;
+            return pad(&spec, "", &text, '>');
+        }
+        'd' | 'i' | 'u' | 'o' | 'x' | 'X' => {
+            let val = match conversion {
+                'd' | 'i' | 'u' => percent_int(conversion, value)?,
+                _ => match value {
+                    Value::Int(_) | Value::Bool(_) | Value::BigInt(_) => as_bigint(value).unwrap(),
+                    _ => {
+                        let message = format!("%{} format: an integer is required, not {}", conversion, value.type_name());
+                        return Err(type_error(&message));
+                    }
+                },
+            };
+            let magnitude = val.magnitude();
+            let (prefix, digits) = match conversion {
+                'o' => ("0o", magnitude.to_str_radix(8)),
+                'x' => ("0x", magnitude.to_str_radix(16)),
+                'X' => ("0X", magnitude.to_str_radix(16).to_uppercase()),
+                _ => ("", magnitude.to_string()),
+            };
+            // the precision is a minimum number of digits
+            let precision = spec.precision.unwrap_or(0);
+            if precision > i32::MAX as usize {
+                return Err(value_error("precision too big"));
+            }
+            let mut padded = limits::new_string(precision.max(digits.len()))?;
+            padded.extend(std::iter::repeat_n('0', precision.saturating_sub(digits.len())));
+            padded.push_str(&digits);
+            let prefix = if spec.alternate { prefix } else { "" };
+            return format_number(&spec, val.is_negative(), prefix, &padded, "");
+        }
+        'e' | 'E' | 'f' | 'F' | 'g' | 'G' => {
+            spec.kind = Some(conversion);
+            spec.precision = Some(spec.precision.unwrap_or(6));
+            return format_float_spec(percent_float(value)?, &spec, value.type_name());
+        }
+        _ => {
+            let message = format!("unsupported format character '{}' (0x{:x}) at index {}", conversion, conversion as u32, index);
+            return Err(value_error(&message));
+        }
+    }
+}
+
+/* A count in a conversion: digits, or '*' for the next argument. A
+ * negative '*' width left-aligns.
+ */
+fn percent_count(
+    chars: &[char],
+    pos: &mut usize,
+    next_arg: &mut dyn FnMut() -> Result<Value, Exception>,
+) -> Result<Option<i64>, Exception> {
+    if chars.get(*pos) == Some(&'*') {
+        *pos += 1;
+        match as_int(&next_arg()?) {
+            Some(count) => return Ok(Some(count)),
+            None => return Err(type_error("* wants int")),
+        }
+    }
+    let (count, next) = parse_count(chars, *pos)?;
+    *pos = next;
+    return Ok(count.map(|count| count as i64));
+}
+
+type TextOf<'a> = &'a mut dyn FnMut(char, &Value) -> Result<String, Exception>;
+
+/* format % args: a tuple is the arguments, and anything else the one
+ * argument, or with %(key)s, the mapping keys are looked up in. The
+ * interpreter, when there is one, gives the text of instances and counts
+ * the memory of each field; constant folding has none.
+ */
+pub fn percent_format(mut interpreter: Option<&mut Interpreter>, format: &str, args: &Value) -> EvalResult {
+    let items = match args {
+        Value::Tuple(items) => items.to_vec(),
+        _ => vec![args.clone()],
+    };
+    let mapping = match args {
+        Value::Dict(_) => Some(args),
+        Value::Object(_) if args.as_dict().is_some() => Some(args),
+        _ => None,
+    };
+    let chars: Vec<char> = format.chars().collect();
+    let mut next = 0;
+    let mut next_arg = || match items.get(next) {
+        Some(value) => {
+            next += 1;
+            return Ok(value.clone());
+        }
+        None => return Err(type_error("not enough arguments for format string")),
+    };
+    let mut out = String::new();
+    let mut pos = 0;
+    while pos < chars.len() {
+        if chars[pos] != '%' {
+            out.push(chars[pos]);
+            pos += 1;
+            continue;
+        }
+        pos += 1;
+        let mut key = None;
+        if chars.get(pos) == Some(&'(') {
+            let mut nesting = 1;
+            let start = pos + 1;
+            while nesting > 0 {
+                pos += 1;
+                match chars.get(pos) {
+                    Some('(') => nesting += 1,
+                    Some(')') => nesting -= 1,
+                    Some(_) => (),
+                    None => return Err(value_error("incomplete format key")),
+                };
+            }
+            key = Some(chars[start..pos].iter().collect::<String>());
+            pos += 1;
+        }
+        let mut spec = Spec::default();
+        while let Some(flag @ ('-' | '+' | ' ' | '#' | '0')) = chars.get(pos) {
+            match flag {
+                '-' => spec.align = Some('<'),
+                '+' => spec.sign = Some('+'),
+                ' ' if spec.sign.is_none() => spec.sign = Some(' '),
+                '#' => spec.alternate = true,
+                '0' => spec.zero = true,
+                _ => (),
+            };
+            pos += 1;
+        }
+        if let Some(width) = percent_count(&chars, &mut pos, &mut next_arg)? {
+            if width < 0 {
+                spec.align = Some('<');
+            }
+            spec.width = width.unsigned_abs() as usize;
+        }
+        if chars.get(pos) == Some(&'.') {
+            pos += 1;
+            let precision = percent_count(&chars, &mut pos, &mut next_arg)?;
+            spec.precision = Some(precision.unwrap_or(0).max(0) as usize);
+        }
+        if spec.align.is_some() {
+            spec.zero = false;
+        }
+        while let Some('h' | 'l' | 'L') = chars.get(pos) {
+            pos += 1;
+        }
+        let conversion = match chars.get(pos) {
+            Some(conversion) => *conversion,
+            None => return Err(value_error("incomplete format")),
+        };
+        if conversion == '%' {
+            out.push('%');
+            pos += 1;
+            continue;
+        }
+        let value = match &key {
+            Some(key) => match (mapping, interpreter.as_deref_mut()) {
+                (Some(mapping), Some(interpreter)) => subscript(interpreter, mapping, &Value::Str(key.clone()))?,
+                (Some(mapping), None) => get_item(mapping, &Value::Str(key.clone()))?,
+                (None, _) => return Err(type_error("format requires a mapping")),
+            },
+            None => next_arg()?,
+        };
+        let text = match interpreter.as_deref_mut() {
+            Some(interpreter) => {
+                let digits = if matches!(conversion, 's' | 'r' | 'a') { 0 } else { spec.precision.unwrap_or(0) };
+                interpreter.allocate(spec.width.saturating_add(digits))?;
+                let mut text_of = |conversion, value: &Value| match conversion {
+                    's' => return interpreter.str(value),
+                    _ => return interpreter.repr(value),
+                };
+                percent_field(conversion, &value, spec, pos, &mut text_of)?
+            }
+            None => percent_field(conversion, &value, spec, pos, &mut plain_text)?,
+        };
+        out.push_str(&text);
+        pos += 1;
+    }
+    if mapping.is_none() && next < items.len() {
+        return Err(type_error("not all arguments converted during string formatting"));
+    }
+    return Ok(Value::Str(out));
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testing::{eval, python};
+
+    #[test]
+    fn test_format_spec() {
+        let mut python = python("");
+        assert_eq!(eval(&mut python, "(format(1234567.891, ',.2f'), format(255, '#_x'), format(255, '_b'), format(1234, '08,'), format(-5, '08'), format(-5, '=+8'), format('ab', '05'))"), "('1,234,567.89', '0xff', '1111_1111', '0,001,234', '-0000005', '-      5', 'ab000')");
+        assert_eq!(eval(&mut python, "(format(3.0, ''), format(1e16, ''), format(123.456, '.3'), format(3.0, '.3'), format(1234.0, '.4'), format(0.0001, '.3'), format(1.0, '#g'), format(123.0, '#.3g'), format(1e-5, 'g'))"), "('3.0', '1e+16', '1.23e+02', '3.0', '1.234e+03', '0.0001', '1.00000', '123.', '1e-05')");
+        assert_eq!(eval(&mut python, "(format(1.0, 'e'), format(123456789, 'e'), format(2.5, '.0f'), format(1.5, '%'), format(-0.0, 'z.1f'), format(float('nan'), '08.2f'), format(float('inf'), 'F'))"), "('1.000000e+00', '1.234568e+08', '2', '150.000000%', '0.0', '00000nan', 'INF')");
+        assert_eq!(eval(&mut python, "(format(True, ''), format(True, '>5'), format(65, 'c'), format(1+2j, ''), format(1+2j, '.2f'), format(1+2j, '10'), format(1j, 'g'), format(None, ''))"), "('True', '    1', 'A', '(1+2j)', '1.00+2.00j', '    (1+2j)', '0+1j', 'None')");
+    }
+
+    #[test]
+    fn test_str_format() {
+        let mut python = python("");
+        assert_eq!(eval(&mut python, "('{0}{1}{0}'.format('a', 'b'), '{:>{w}}'.format('x', w=4), '{!r:^7}'.format('a'), '{0[1]}{x[k]}'.format([5, 6], x={'k': 7}), '{{}}{}'.format(1), '{x[0]}'.format_map({'x': [9]}))"), "('aba', '   x', \"  'a'  \", '67', '{}1', '9')");
+    }
+
+    #[test]
+    fn test_percent() {
+        let mut python = python("");
+        assert_eq!(eval(&mut python, "'%5.1f|%-4d|%x|%#o|%r|%c|%%|%+d' % (3.14159, 7, 255, 8, 'a', 65, 3)"), "\"  3.1|7   |ff|0o10|'a'|A|%|+3\"");
+        assert_eq!(eval(&mut python, "('%(a)s-%(b)03d' % {'a': 1, 'b': 2}, '%*d' % (5, 3), '%d' % 3.9, '%s' % (1,), '%.3d' % 5, '%a' % 'é', ascii('é\\u1234'))"), "('1-002', '    3', '3', '1', '005', \"'\\\\xe9'\", \"'\\\\xe9\\\\u1234'\")");
+    }
+
+    #[test]
+    fn test_errors() {
+        let mut python = python("");
+        assert_eq!(eval(&mut python, "'%d' % 'a'"), "TypeError: %d format: a real number is required, not str");
+        assert_eq!(eval(&mut python, "'%d %d' % (1,)"), "TypeError: not enough arguments for format string");
+        assert_eq!(eval(&mut python, "'%d' % (1, 2)"), "TypeError: not all arguments converted during string formatting");
+        assert_eq!(eval(&mut python, "'%y' % 1"), "ValueError: unsupported format character 'y' (0x79) at index 1");
+        assert_eq!(eval(&mut python, "'{0}{}'.format(1, 2)"), "ValueError: cannot switch from manual field specification to automatic field numbering");
+        assert_eq!(eval(&mut python, "'{}'.format()"), "IndexError: Replacement index 0 out of range for positional args tuple");
+        assert_eq!(eval(&mut python, "format('a', 'd')"), "ValueError: Unknown format code 'd' for object of type 'str'");
+        assert_eq!(eval(&mut python, "format(1, 'z')"), "ValueError: Negative zero coercion (z) not allowed in integer format specifier");
+        assert_eq!(eval(&mut python, "format('a', '+')"), "ValueError: Sign not allowed in string format specifier");
+        assert_eq!(eval(&mut python, "format(1, ',_')"), "ValueError: Cannot specify both ',' and '_'.");
+        assert_eq!(eval(&mut python, "'}'.format()"), "ValueError: Single '}' encountered in format string");
+    }
+}
@@ -0,0 +1,496 @@
+use crate::dict::Dict;
+use crate::interpreter::Interpreter;
+use crate::value::{Args, Class, Function, Method, Object, Scope, Value};
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::mem;
+use std::rc::{Rc, Weak};
+
+/* The cycle collector.
+ *
+ * Values are reference counted, so an object is freed (and its __del__
+ * run) as soon as the last reference to it goes. What reference counting
+ * can't free is objects that refer to each other: a list that contains
+ * itself, or a function stored in its own closure.
+ *
+ * Containers that could be part of a cycle are tracked, weakly, from when
+ * they are created. A collection subtracts the references tracked
+ * containers hold to each other from their reference counts; what is left
+ * over are references from elsewhere (variables, the Rust stack), and
+ * anything not reachable from a container with such references is garbage.
+ * Garbage objects get their __del__ called first, then the garbage is
+ * emptied, which breaks the cycles so reference counting frees it.
+ * References the collector can't see (from iterators or native objects)
+ * only make it keep more, never free something in use.
+ *
+ * The collector runs automatically once enough containers have been
+ * created since the last collection, at the next statement.
+ */
+
+// containers created before the first automatic collection
+const THRESHOLD: usize = 700;
+
+pub enum Tracked {
+    List(Weak<RefCell<Vec<Value>>>),
+    Tuple(Weak<Vec<Value>>),
+    Dict(Weak<RefCell<Dict>>),
+    Scope(Weak<Scope>),
+    Function(Weak<Function>),
+    Object(Weak<Object>),
+    Method(Weak<Method>),
+}
+
+/* A tracked container, kept alive while a collection looks at it
+ */
+enum Node {
+    List(Rc<RefCell<Vec<Value>>>),
+    Tuple(Rc<Vec<Value>>),
+    Dict(Rc<RefCell<Dict>>),
+    Scope(Rc<Scope>),
+    Function(Rc<Function>),
+    Object(Rc<Object>),
+    Method(Rc<Method>),
+}
+
+struct State {
+    tracked: Vec<Tracked>,
+    // containers created since the last collection, gc.get_count()
+    count: usize,
+    threshold: usize,
+    enabled: bool,
+    collecting: bool,
+    // calls waiting for the next statement: __del__ methods, with their
+    // arguments
+    pending: Vec<(Value, Vec<Value>)>,
+}
+
+thread_local! {
+    static STATE: RefCell<State> = const {
+        RefCell::new(State {
+            tracked: Vec::new(),
+            count: 0,
+            threshold: THRESHOLD,
+            enabled: true,
+            collecting: false,
+            pending: Vec::new(),
+        })
+    };
+    // a collection or finalizers are due; checked at every statement
+    static DUE: Cell<bool> = const { Cell::new(false) };
+    static WATCHERS: RefCell<HashMap<usize, Vec<Watcher>>> = RefCell::new(HashMap::new());
+}
+
+/* Told when an object is freed, for weak references. It can return a call
+ * to make at the next statement, like a __del__.
+ */
+pub type Watcher = Box<dyn FnOnce() -> Option<(Value, Vec<Value>)>>;
+
+pub fn track(container: Tracked) {
+    STATE.with(|state| {
+        let mut state = state.borrow_mut();
+        state.tracked.push(container);
+        state.count += 1;
+        if state.count >= state.threshold && state.enabled {
+            DUE.with(|due| due.set(true));
+        }
+    });
+}
+
+/* Call function(args) at the next statement
+ */
+pub fn schedule(function: Value, args: Vec<Value>) {
+    // objects can be dropped while the thread exits, when there is no
+    // state left and nothing more will run anyway
+    let _ = STATE.try_with(|state| state.borrow_mut().pending.push((function, args)));
+    let _ = DUE.try_with(|due| due.set(true));
+}
+
+pub fn due() -> bool {
+    return DUE.with(Cell::get);
+}
+
+/* gc.get_count(): containers created since the last collection
+ */
+pub fn count() -> usize {
+    return STATE.with(|state| state.borrow().count);
+}
+
+pub fn set_enabled(enabled: bool) {
+    STATE.with(|state| state.borrow_mut().enabled = enabled);
+    DUE.with(|due| due.set(true));
+}
+
+pub fn is_enabled() -> bool {
+    return STATE.with(|state| state.borrow().enabled);
+}
+
+/* Call watcher when the instance, function, class or module with this
+ * id() is freed. The id can't be reused while there are weak references
+ * to the object.
+ */
+pub fn watch(identity: usize, watcher: Watcher) {
+    WATCHERS.with(|watchers| watchers.borrow_mut().entry(identity).or_default().push(watcher));
+}
+
+/* The object with this id() is being freed: run its watchers
+ */
+pub fn reclaimed(identity: usize) {
+    let watchers = WATCHERS.try_with(|watchers| match watchers.try_borrow_mut() {
+        Ok(mut watchers) if !watchers.is_empty() => return watchers.remove(&identity),
+        _ => return None,
+    });
+    for watcher in watchers.ok().flatten().unwrap_or_default() {
+        if let Some((function, args)) = watcher() {
+            schedule(function, args);
+        }
+    }
+}
+
+/* whether a value can refer to a tracked container
+ */
+pub fn is_container(value: &Value) -> bool {
+    return matches!(
+        value,
+        Value::List(_)
+            | Value::Tuple(_)
+            | Value::Dict(_)
+            | Value::Set(_)
+            | Value::FrozenSet(_)
+            | Value::Function(_)
+            | Value::Object(_)
+            | Value::Method(_)
+    );
+}
+
+/* __del__ of an instance's class. Attributes being changed at the moment
+ * can't be looked at, so don't count.
+ */
+pub fn finalizer(class: &Class) -> Option<Value> {
+    if let Some(del) = class.attrs.try_borrow().ok()?.get("__del__") {
+        return Some(del.clone());
+    }
+    return class.bases.iter().find_map(|base| finalizer(base));
+}
+
+impl Tracked {
+    fn upgrade(&self) -> Option<Node> {
+        match self {
+            Tracked::List(items) => return items.upgrade().map(Node::List),
+            Tracked::Tuple(items) => return items.upgrade().map(Node::Tuple),
+            Tracked::Dict(dict) => return dict.upgrade().map(Node::Dict),
+            Tracked::Scope(scope) => return scope.upgrade().map(Node::Scope),
+            Tracked::Function(function) => return function.upgrade().map(Node::Function),
+            Tracked::Object(object) => return object.upgrade().map(Node::Object),
+            Tracked::Method(method) => return method.upgrade().map(Node::Method),
+        }
+    }
+}
+
+impl Node {
+    fn downgrade(&self) -> Tracked {
+        match self {
+            Node::List(items) => return Tracked::List(Rc::downgrade(items)),
+            Node::Tuple(items) => return Tracked::Tuple(Rc::downgrade(items)),
+            Node::Dict(dict) => return Tracked::Dict(Rc::downgrade(dict)),
+            Node::Scope(scope) => return Tracked::Scope(Rc::downgrade(scope)),
+            Node::Function(function) => return Tracked::Function(Rc::downgrade(function)),
+            Node::Object(object) => return Tracked::Object(Rc::downgrade(object)),
+            Node::Method(method) => return Tracked::Method(Rc::downgrade(method)),
+        }
+    }
+
+    // the same as Value::identity() of the container
+    fn identity(&self) -> usize {
+        match self {
+            Node::List(items) => return Rc::as_ptr(items) as *const u8 as usize,
+            Node::Tuple(items) => return Rc::as_ptr(items) as *const u8 as usize,
+            Node::Dict(dict) => return Rc::as_ptr(dict) as *const u8 as usize,
+            Node::Scope(scope) => return Rc::as_ptr(scope) as *const u8 as usize,
+            Node::Function(function) => return Rc::as_ptr(function) as *const u8 as usize,
+            Node::Object(object) => return Rc::as_ptr(object) as *const u8 as usize,
+            Node::Method(method) => return Rc::as_ptr(method) as *const u8 as usize,
+        }
+    }
+
+    fn strong_count(&self) -> usize {
+        match self {
+            Node::List(items) => return Rc::strong_count(items),
+            Node::Tuple(items) => return Rc::strong_count(items),
+            Node::Dict(dict) => return Rc::strong_count(dict),
+            Node::Scope(scope) => return Rc::strong_count(scope),
+            Node::Function(function) => return Rc::strong_count(function),
+            Node::Object(object) => return Rc::strong_count(object),
+            Node::Method(method) => return Rc::strong_count(method),
+        }
+    }
+
+    /* Call visit with the identity of each container this one refers to.
+     * Returns false if the contents are being changed and can't be looked
+     * at.
+     */
+    fn visit(&self, visit: &mut dyn FnMut(usize)) -> bool {
+        let mut values = |values: &mut dyn Iterator<Item = &Value>| {
+            values.filter(|value| is_container(value)).for_each(|value| visit(value.identity()));
+        };
+        match self {
+            Node::List(items) => match items.try_borrow() {
+                Ok(items) => values(&mut items.iter()),
+                Err(_) => return false,
+            },
+            Node::Tuple(items) => values(&mut items.iter()),
+            Node::Dict(dict) => match dict.try_borrow() {
+                Ok(dict) => values(&mut dict.entries().flat_map(|(key, value)| [key, value])),
+                Err(_) => return false,
+            },
+            Node::Scope(scope) => match scope.vars.try_borrow() {
+                Ok(vars) => {
+                    values(&mut vars.values());
+                    if let Some(parent) = &scope.parent {
+                        visit(Rc::as_ptr(parent) as *const u8 as usize);
+                    }
+                }
+                Err(_) => return false,
+            },
+            Node::Function(function) => {
+                values(&mut function.defaults.values().chain(&function.wrapped));
+                if let Some(closure) = &function.closure {
+                    visit(Rc::as_ptr(closure) as *const u8 as usize);
+                }
+            }
+            Node::Object(object) => match object.attrs.try_borrow() {
+                Ok(attrs) => values(&mut attrs.values()),
+                Err(_) => return false,
+            },
+            Node::Method(method) => values(&mut [&method.object, &method.function].iter().copied()),
+        }
+        return true;
+    }
+
+    /* Empty the container, moving what it held into held. Tuples, functions
+     * and methods can't be changed, but can't form a cycle without a
+     * container that can.
+     */
+    fn clear(&self, held: &mut Vec<Value>) {
+        match self {
+            Node::List(items) => held.append(&mut items.borrow_mut()),
+            Node::Dict(dict) => {
+                let dict = mem::take(&mut *dict.borrow_mut());
+                held.extend(dict.entries().flat_map(|(key, value)| [key.clone(), value.clone()]));
+            }
+            Node::Scope(scope) => held.extend(scope.vars.borrow_mut().drain().map(|(_, value)| value)),
+            Node::Object(object) => held.extend(object.attrs.borrow_mut().drain().map(|(_, value)| value)),
+            Node::Tuple(_) | Node::Function(_) | Node::Method(_) => (),
+        }
+    }
+}
+
+/* Which of the nodes are garbage: not reachable from a node with references
+ * from outside the tracked containers
+ */
+fn unreachable(nodes: &[Node]) -> Vec<bool> {
+    let index: HashMap<usize, usize> = nodes.iter().enumerate().map(|(pos, node)| (node.identity(), pos)).collect();
+    // each node's references, less the one held by nodes itself
+    let mut refs: Vec<usize> = nodes.iter().map(|node| node.strong_count() - 1).collect();
+    let mut roots: Vec<usize> = Vec::new();
+    for (pos, node) in nodes.iter().enumerate() {
+        let visited = node.visit(&mut |child| {
+            if let Some(child) = index.get(&child) {
+                refs[*child] = refs[*child].saturating_sub(1);
+            }
+        });
+        if !visited {
+            roots.push(pos);
+        }
+    }
+    roots.extend((0..nodes.len()).filter(|pos| refs[*pos] > 0));
+
+    let mut garbage = vec![true; nodes.len()];
+    while let Some(pos) = roots.pop() {
+        if !mem::replace(&mut garbage[pos], false) {
+            continue;
+        }
+        nodes[pos].visit(&mut |child| {
+            if let Some(child) = index.get(&child) {
+                if garbage[*child] {
+                    roots.push(*child);
+                }
+            }
+        });
+    }
+    return garbage;
+}
+
+impl Interpreter {
+    /* Collect now if a collection is due, then run waiting finalizers
+     */
+    pub fn run_gc(&mut self) {
+        DUE.with(|due| due.set(false));
+        let collect = STATE.with(|state| {
+            let state = state.borrow();
+            return state.enabled && !state.collecting && state.count >= state.threshold;
+        });
+        if collect {
+            self.collect_garbage();
+        }
+        self.run_finalizers();
+    }
+
+    pub fn run_finalizers(&mut self) {
+        loop {
+            let pending = STATE.with(|state| mem::take(&mut state.borrow_mut().pending));
+            if pending.is_empty() {
+                return;
+            }
+            for (function, args) in pending {
+                self.call_finalizer(&function, args);
+            }
+        }
+    }
+
+    /* Errors in finalizers can't go anywhere, so are reported and ignored
+     */
+    fn call_finalizer(&mut self, function: &Value, args: Vec<Value>) {
+        if let Err(err) = self.call(function, Args::build_new(args)) {
+            eprintln!("Exception ignored in: {}", function.repr());
+            eprintln!("{}", err.format_traceback());
+        }
+    }
+
+    /* gc.collect(): free unreachable cycles, returning how many containers
+     * they were made of
+     */
+    pub fn collect_garbage(&mut self) -> usize {
+        let tracked = STATE.with(|state| {
+            let mut state = state.borrow_mut();
+            state.collecting = true;
+            return mem::take(&mut state.tracked);
+        });
+        let mut nodes: Vec<Node> = Vec::new();
+        let mut seen: HashMap<usize, ()> = HashMap::new();
+        for node in tracked.iter().filter_map(Tracked::upgrade) {
+            if seen.insert(node.identity(), ()).is_none() {
+                nodes.push(node);
+            }
+        }
+        drop(tracked);
+
+        // finalizers see the objects whole, and may make them reachable
+        // again, so reachability is worked out again after them
+        let mut garbage = unreachable(&nodes);
+        let mut finalized = false;
+        for (node, is_garbage) in nodes.iter().zip(&garbage) {
+            if let (Node::Object(object), true) = (node, is_garbage) {
+                if object.finalized.replace(true) {
+                    continue;
+                }
+                if let Some(del) = finalizer(&object.class) {
+                    self.call_finalizer(&del, vec![Value::Object(object.clone())]);
+                    finalized = true;
+                }
+            }
+        }
+        if finalized {
+            garbage = unreachable(&nodes);
+        }
+
+        let mut held: Vec<Value> = Vec::new();
+        let mut survivors: Vec<Tracked> = Vec::new();
+        let mut collected = 0;
+        for (node, is_garbage) in nodes.into_iter().zip(garbage) {
+            if is_garbage {
+                node.clear(&mut held);
+                collected += 1;
+            } else {
+                survivors.push(node.downgrade());
+            }
+        }
+        STATE.with(|state| {
+            let mut state = state.borrow_mut();
+            state.tracked.append(&mut survivors);
+            state.count = 0;
+            // a collection looks at everything, so they get rarer as more
+            // is kept
+            state.threshold = THRESHOLD.max(state.tracked.len() / 4);
+            state.collecting = false;
+        });
+        // frees the garbage, which may schedule finalizers
+        drop(held);
+        return collected;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::embed;
+    use crate::native::NativeClass;
+    use crate::testing::exec;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    struct Resource {
+        name: String,
+    }
+
+    /* An interpreter with automatic collection off and a Resource class
+     * whose finalizer records its name in the log returned with it
+     */
+    fn setup() -> (embed::Interpreter, Rc<RefCell<Vec<String>>>) {
+        let mut python = embed::Interpreter::build_new();
+        let log: Rc<RefCell<Vec<String>>> = Rc::default();
+        let mut resource: NativeClass<Resource> = NativeClass::build_new("Resource");
+        resource.constructor(|name: String| Resource { name });
+        let deleted = log.clone();
+        resource.method("__del__", move |this: &mut Resource| deleted.borrow_mut().push(this.name.clone()));
+        python.register_class(resource);
+        exec(&mut python, "import gc\ngc.disable()\n");
+        return (python, log);
+    }
+
+    fn collect(python: &mut embed::Interpreter) -> i64 {
+        return python.eval_as::<i64>("gc.collect()").unwrap();
+    }
+
+    #[test]
+    fn test_reference_counting() {
+        // reference counting frees what isn't in a cycle straight away
+        let (mut python, log) = setup();
+        exec(&mut python, "r = Resource('a')\nr = None\nx = [1, 2]\n");
+        assert_eq!(*log.borrow(), vec!["a"]);
+        exec(&mut python, "x = None\n");
+        assert_eq!(collect(&mut python), 0);
+    }
+
+    #[test]
+    fn test_cycles() {
+        let (mut python, log) = setup();
+        exec(&mut python, "x = [Resource('b')]\nx[0] = (x, x[0])\nd = {}\nd['d'] = d\nx = None\nd = None\n");
+        assert!(log.borrow().is_empty());
+        assert_eq!(collect(&mut python), 4);
+        assert_eq!(*log.borrow(), vec!["b"]);
+
+        // a function in its own closure
+        exec(&mut python, "def outer():\n    def inner():\n        return inner\n    return inner\n\nf = outer()\n");
+        assert_eq!(collect(&mut python), 0);
+        exec(&mut python, "f = None\n");
+        assert_eq!(collect(&mut python), 2);
+    }
+
+    #[test]
+    fn test_reachable_cycles() {
+        // cycles still referenced from outside are kept
+        let (mut python, _) = setup();
+        exec(&mut python, "x = [0]\nx[0] = [x]\ny = x[0]\nx = None\n");
+        assert_eq!(collect(&mut python), 0);
+        assert!(python.eval_as::<bool>("y[0][0] is y").unwrap());
+    }
+
+    #[test]
+    fn test_counts() {
+        let (mut python, _) = setup();
+        exec(&mut python, "count = gc.get_count()[0]\nx = [[], {}]\n");
+        assert_eq!(python.eval_as::<i64>("gc.get_count()[0] - count").unwrap(), 3);
+        assert!(!python.eval_as::<bool>("gc.isenabled()").unwrap());
+        exec(&mut python, "gc.enable()\n");
+        assert!(python.eval_as::<bool>("gc.isenabled()").unwrap());
+    }
+}
@@ -0,0 +1,406 @@
+use crate::ast::Stmt;
+use crate::cache;
+use crate::interpreter::Interpreter;
+use crate::optimizer;
+use crate::parser::Parser;
+use crate::scanner;
+use crate::scanner::Token;
+use crate::value::{new_list, Exception, Module, Value};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+/* The import system.
+ *
+ * Modules are found by searching sys.path (the script's directory followed
+ * by the entries of REBARPATH) for either <name>.py or a package directory
+ * <name>/__init__.py. Submodules of a package are searched for in the
+ * package's __path__ instead. Loaded modules are cached in
+ * Interpreter::modules (sys.modules), and a module is cached before its code
+ * runs, so circular imports see the partially initialized module like they
+ * do in CPython.
+ */
+
+fn import_error(kind: &str, message: String) -> Exception {
+    return Exception::new(kind, &message);
+}
+
+fn path_list(paths: &[PathBuf]) -> Value {
+    let items = paths
+        .iter()
+        .map(|path| Value::Str(path.to_string_lossy().into_owned()))
+        .collect();
+    return new_list(items);
+}
+
+impl Interpreter {
+    /* Build sys.path: the script's directory ("" - the current directory -
+     * at the prompt), then REBARPATH.
+     */
+    pub fn init_path(&mut self, script_dir: Option<&Path>) {
+        let mut paths: Vec<PathBuf> = vec![script_dir.map(Path::to_path_buf).unwrap_or_default()];
+        if let Some(rebar_path) = env::var_os("REBARPATH") {
+            paths.extend(env::split_paths(&rebar_path).filter(|path| !path.as_os_str().is_empty()));
+        }
+        let sys = self.modules.get("sys").cloned().unwrap();
+        sys.set("path", path_list(&paths));
+    }
+
+    /* Cache a module for imports, and list it in sys.modules. sys.modules
+     * mirrors the cache: changing it doesn't change what imports find.
+     */
+    pub fn add_module(&mut self, name: &str, module: Rc<Module>) {
+        if let Some(Value::Dict(ref modules)) = self.modules.get("sys").and_then(|sys| sys.get("modules")) {
+            let key = Value::Str(String::from(name));
+            modules.borrow_mut().set(key, Value::Module(module.clone())).unwrap();
+        }
+        self.modules.insert(String::from(name), module);
+    }
+
+    fn remove_module(&mut self, name: &str) {
+        if let Some(Value::Dict(ref modules)) = self.modules.get("sys").and_then(|sys| sys.get("modules")) {
+            modules.borrow_mut().remove(&Value::Str(String::from(name))).unwrap();
+        }
+        self.modules.remove(name);
+    }
+
+    /* current sys.path, ignoring entries that aren't strings
+     */
+    fn search_path(&mut self) -> Vec<PathBuf> {
+        let path = self.modules.get("sys").and_then(|sys| sys.get("path"));
+        let entries = path.map(|path| self.iterate(&path).unwrap_or_default()).unwrap_or_default();
+        return Interpreter::to_paths(&entries);
+    }
+
+    fn to_paths(entries: &[Value]) -> Vec<PathBuf> {
+        let mut paths: Vec<PathBuf> = Vec::new();
+        for entry in entries {
+            if let Value::Str(entry) = entry {
+                paths.push(if entry.is_empty() { PathBuf::from(".") } else { PathBuf::from(entry) });
+            }
+        }
+        return paths;
+    }
+
+    /* Compile a source file, or load it already compiled from the
+     * __pycache__ cache
+     */
+    fn load_code(&self, path: &Path) -> Result<Vec<Stmt>, Exception> {
+        if let Some(code) = cache::load(path, self.optimize) {
+            return Ok(code);
+        }
+        let filename = path.display().to_string();
+        let source = fs::read_to_string(path)
+            .map_err(|err| import_error("OSError", format!("can't open file '{}': {}", filename, err)))?;
+        let code = self.compile(&source, &filename)?;
+        if self.write_cache {
+            cache::store(path, self.optimize, &code);
+        }
+        return Ok(code);
+    }
+
+    /* Lex, parse and optimize source code that isn't in a file. filename
+     * is only used in error messages.
+     */
+    pub fn compile(&self, source: &str, filename: &str) -> Result<Vec<Stmt>, Exception> {
+        let tokens = Interpreter::tokenize(source, filename)?;
+        return self.parse_tokens(tokens, filename);
+    }
+
+    /* compile() for part of a file that starts after its first offset
+     * lines, so statements and syntax errors have the file's line numbers
+     */
+    pub fn compile_at(&self, source: &str, filename: &str, offset: usize) -> Result<Vec<Stmt>, Exception> {
+        let mut tokens = scanner::tokenize(source).map_err(|mut err| {
+            err.line = err.line.map(|line| line + offset);
+            return Parser::syntax_error_at(err, filename);
+        })?;
+        for (_, line) in tokens.iter_mut() {
+            *line += offset;
+        }
+        return self.parse_tokens(tokens, filename);
+    }
+
+    fn tokenize(source: &str, filename: &str) -> Result<Vec<(Token, usize)>, Exception> {
+        return scanner::tokenize(source).map_err(|err| Parser::syntax_error_at(err, filename));
+    }
+
+    fn parse_tokens(&self, tokens: Vec<(Token, usize)>, filename: &str) -> Result<Vec<Stmt>, Exception> {
+        let code = Parser::build_from_tokens(tokens)
+            .parse_module()
+            .map_err(|err| Parser::syntax_error_at(err, filename))?;
+        return Ok(optimizer::optimize(code, self.optimize));
+    }
+
+    /* Run code with module as the global namespace
+     */
+    pub fn exec_module(&mut self, module: Rc<Module>, code: &[Stmt]) -> Result<(), Exception> {
+        let saved = std::mem::replace(&mut self.globals, module.clone());
+        let saved_scope = self.scope.take();
+        module.initializing.set(true);
+        let result = self.exec_block(code);
+        // finalizers of what the last statement freed
+        self.run_gc();
+        module.initializing.set(false);
+        self.globals = saved;
+        self.scope = saved_scope;
+        if let Err(mut err) = result {
+            err.add_frame(&module, "<module>");
+            return Err(err);
+        }
+        return Ok(());
+    }
+
+    /* Run a script as __main__
+     */
+    pub fn run_file(&mut self, path: &Path) -> Result<(), Exception> {
+        let code = self.load_code(path)?;
+        let main = self.globals.clone();
+        main.set("__file__", Value::Str(path.to_string_lossy().into_owned()));
+        return self.exec_module(main, &code);
+    }
+
+    /* Locate a module called part in the given directories. Returns the file
+     * to run, and the package directory if it is a package.
+     */
+    fn find_module(part: &str, search: &[PathBuf]) -> Option<(PathBuf, Option<PathBuf>)> {
+        for dir in search {
+            let package = dir.join(part);
+            let init = package.join("__init__.py");
+            if init.is_file() {
+                return Some((init, Some(package)));
+            }
+            let file = dir.join(format!("{}.py", part));
+            if file.is_file() {
+                return Some((file, None));
+            }
+        }
+        return None;
+    }
+
+    /* Import a module by its full dotted name, importing parent packages
+     * first, and return it.
+     */
+    pub fn import_module(&mut self, name: &str) -> Result<Rc<Module>, Exception> {
+        if let Some(module) = self.modules.get(name) {
+            return Ok(module.clone());
+        }
+        self.check_access("ImportError", &format!("import of '{}'", name))?;
+
+        let (parent, part) = match name.rfind('.') {
+            Some(dot) => (Some(self.import_module(&name[..dot])?), &name[dot + 1..]),
+            None => (None, name),
+        };
+        let search = match &parent {
+            Some(parent) => {
+                // importing the parent package may have imported this module
+                if let Some(module) = self.modules.get(name) {
+                    return Ok(module.clone());
+                }
+                match parent.get("__path__") {
+                    Some(path) => Interpreter::to_paths(&self.iterate(&path)?),
+                    None => {
+                        return Err(import_error(
+                            "ModuleNotFoundError",
+                            format!("No module named '{}'; '{}' is not a package", name, parent.name),
+                        ));
+                    }
+                }
+            }
+            None => self.search_path(),
+        };
+
+        let (file, package_dir) = match Interpreter::find_module(part, &search) {
+            Some(found) => found,
+            None => {
+                return Err(import_error("ModuleNotFoundError", format!("No module named '{}'", name)));
+            }
+        };
+        let code = self.load_code(&file)?;
+
+        let module = Rc::new(Module::build_new(name, Some(file.clone())));
+        module.set("__file__", Value::Str(file.to_string_lossy().into_owned()));
+        match package_dir {
+            Some(dir) => {
+                module.set("__path__", path_list(&[dir]));
+                module.set("__package__", Value::Str(String::from(name)));
+            }
+            None => {
+                let package = parent.as_ref().map(|parent| parent.name.clone()).unwrap_or_default();
+                module.set("__package__", Value::Str(package));
+            }
+        };
+
+        self.add_module(name, module.clone());
+        if let Err(err) = self.exec_module(module.clone(), &code) {
+            self.remove_module(name);
+            return Err(err);
+        }
+        if let Some(parent) = parent {
+            parent.set(part, Value::Module(module.clone()));
+        }
+        return Ok(module);
+    }
+
+    /* Turn a relative module name (level leading dots) into an absolute one,
+     * relative to the current module's __package__
+     */
+    fn resolve_name(&self, name: &str, level: usize) -> Result<String, Exception> {
+        if level == 0 {
+            return Ok(String::from(name));
+        }
+        let package = match self.globals.get("__package__") {
+            Some(Value::Str(ref package)) if !package.is_empty() => package.clone(),
+            _ => {
+                return Err(import_error(
+                    "ImportError",
+                    String::from("attempted relative import with no known parent package"),
+                ));
+            }
+        };
+        let bits: Vec<&str> = package.rsplitn(level, '.').collect();
+        if bits.len() < level {
+            return Err(import_error(
+                "ImportError",
+                String::from("attempted relative import beyond top-level package"),
+            ));
+        }
+        let base = bits[bits.len() - 1];
+        if name.is_empty() {
+            return Ok(String::from(base));
+        }
+        return Ok(format!("{}.{}", base, name));
+    }
+
+    /* import a.b.c binds a; import a.b.c as d binds d to a.b.c
+     */
+    pub fn exec_import(&mut self, names: &[(String, Option<String>)]) -> Result<(), Exception> {
+        for (name, alias) in names {
+            let module = self.import_module(name)?;
+            match alias {
+                Some(alias) => self.assign_name(alias, Value::Module(module))?,
+                None => {
+                    let top = name.split('.').next().unwrap();
+                    let top_module = self.modules.get(top).cloned().unwrap();
+                    self.assign_name(top, Value::Module(top_module))?;
+                }
+            };
+        }
+        return Ok(());
+    }
+
+    pub fn exec_import_from(
+        &mut self,
+        level: usize,
+        module_name: &str,
+        names: &[(String, Option<String>)],
+    ) -> Result<(), Exception> {
+        let full_name = self.resolve_name(module_name, level)?;
+        let module = self.import_module(&full_name)?;
+
+        if names.len() == 1 && names[0].0 == "*" {
+            return self.import_star(&module);
+        }
+        for (name, alias) in names {
+            let value = match module.get(name) {
+                Some(value) => value,
+                // not an attribute (yet): it may be a submodule
+                None => match self.import_module(&format!("{}.{}", full_name, name)) {
+                    Ok(submodule) => Value::Module(submodule),
+                    Err(err) if err.kind == "ModuleNotFoundError" => {
+                        let location = match &module.file {
+                            Some(file) => file.display().to_string(),
+                            None => String::from("unknown location"),
+                        };
+                        let message = if module.initializing.get() {
+                            format!(
+                                "cannot import name '{}' from partially initialized module '{}' \
+                                 (most likely due to a circular import) ({})",
+                                name, full_name, location
+                            )
+                        } else {
+                            format!("cannot import name '{}' from '{}' ({})", name, full_name, location)
+                        };
+                        return Err(import_error("ImportError", message));
+                    }
+                    Err(err) => return Err(err),
+                },
+            };
+            self.assign_name(alias.as_ref().unwrap_or(name), value)?;
+        }
+        return Ok(());
+    }
+
+    /* from x import * - the names in x.__all__, or every name not starting
+     * with an underscore
+     */
+    fn import_star(&mut self, module: &Rc<Module>) -> Result<(), Exception> {
+        let names: Vec<String> = match module.get("__all__") {
+            Some(all) => {
+                let mut names: Vec<String> = Vec::new();
+                for item in self.iterate(&all)? {
+                    match item {
+                        Value::Str(ref name) => names.push(name.clone()),
+                        other => {
+                            return Err(import_error(
+                                "TypeError",
+                                format!(
+                                    "Item in {}.__all__ must be str, not {}",
+                                    module.name,
+                                    other.type_name()
+                                ),
+                            ));
+                        }
+                    }
+                }
+                names
+            }
+            None => {
+                let vars = module.vars.borrow();
+                vars.keys().filter(|name| !name.starts_with('_')).cloned().collect()
+            }
+        };
+        for name in names {
+            match module.get(&name) {
+                Some(value) => self.globals.set(&name, value),
+                None => {
+                    return Err(import_error(
+                        "AttributeError",
+                        format!("module '{}' has no attribute '{}'", module.name, name),
+                    ));
+                }
+            };
+        }
+        return Ok(());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_import_package() {
+        let dir = env::temp_dir().join(format!("rebar-import-{}", std::process::id()));
+        fs::create_dir_all(dir.join("pkg")).unwrap();
+        fs::write(dir.join("pkg").join("__init__.py"), "from .mod import x\n").unwrap();
+        fs::write(dir.join("pkg").join("mod.py"), "import pkg\nx = 1\n").unwrap();
+        fs::write(dir.join("loop.py"), "from loop import y\ny = 2\n").unwrap();
+
+        let mut interpreter = Interpreter::build_new();
+        interpreter.write_cache = false;
+        interpreter.init_path(Some(&dir));
+        let module = interpreter.import_module("pkg.mod").unwrap();
+        assert_eq!(module.get("x"), Some(Value::Int(1)));
+        let package = interpreter.modules.get("pkg").unwrap();
+        assert_eq!(package.get("mod"), Some(Value::Module(module)));
+
+        let err = interpreter.import_module("loop").unwrap_err();
+        assert_eq!(err.kind, "ImportError");
+        assert!(err.message.contains("partially initialized module 'loop'"));
+        assert!(!interpreter.modules.contains_key("loop"));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,3115 @@
+use crate::ast::{
+    Arg, BinOp, BoolOp, CmpOp, Comprehension, ComprehensionKind, Expr, FunctionDef, Handler, Stmt, StmtKind, UnaryOp,
+};
+use crate::bytes;
+use crate::containers;
+use crate::dict::{new_dict, new_set, Dict};
+use crate::gc::{self, Tracked};
+use crate::format;
+use crate::exceptions;
+use crate::interrupt::InterruptHandle;
+use crate::iterator;
+use crate::limits::{self, Limits, Usage};
+use crate::modules;
+use crate::modules::cmath::{quotient, Complex};
+use crate::modules::{decimal, fractions};
+use crate::strings;
+use crate::value::{new_list, new_tuple, range_len, Args, Class, Exception, Function, Method, Module, Scope, Value};
+use num_bigint::BigInt;
+use num_integer::Integer;
+use num_rational::BigRational;
+use num_traits::{One, Pow, Signed, ToPrimitive, Zero};
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::mem::size_of;
+use std::rc::Rc;
+
+// start, stop and step of a slice, None where omitted
+type SliceBounds = (Option<i64>, Option<i64>, Option<i64>);
+
+/* The index of a subscript, evaluated: a[i] or a[lower:upper:step]
+ */
+enum Index {
+    Item(Value),
+    // lower, upper and step, None where left out
+    Slice([Value; 3]),
+}
+
+pub type EvalResult = Result<Value, Exception>;
+
+/* How a statement finished - loops use this to implement break/continue
+ */
+#[derive(Debug, PartialEq)]
+pub enum Flow {
+    Normal,
+    Break,
+    Continue,
+    Return(Value),
+}
+
+/* Tree-walking evaluator for the AST
+ */
+pub struct Interpreter {
+    // namespace of the module whose code is running
+    pub globals: Rc<Module>,
+    // every module imported so far, by full dotted name (sys.modules)
+    pub modules: HashMap<String, Rc<Module>>,
+    // -O level, passed on to the optimizer for imported modules
+    pub optimize: u8,
+    // whether imports write __pycache__ files (-B turns this off)
+    pub write_cache: bool,
+    // names looked up after globals: print, len, int, ...
+    pub builtins: Rc<Module>,
+    // the built-in types by name, so type() of a value is always the same
+    // Class object
+    pub types: HashMap<String, Rc<Class>>,
+    // the exception classes by name (see exceptions.rs)
+    pub exceptions: HashMap<String, Rc<Class>>,
+    // the exceptions being handled by except blocks, innermost last
+    pub handling: Vec<Exception>,
+    // local variables of the running function; None at module level
+    pub scope: Option<Rc<Scope>>,
+    pub call_depth: usize,
+    // what running code may do, and what it has used so far
+    pub limits: Limits,
+    pub usage: Usage,
+    // set to raise KeyboardInterrupt at the next statement
+    pub interrupt: InterruptHandle,
+}
+
+impl Interpreter {
+    pub fn build_new() -> Interpreter {
+        let main = Rc::new(Module::build_new("__main__", None));
+        let types = modules::builtins::build_types();
+        let exceptions = exceptions::build_classes(types["object"].clone());
+        let builtins = modules::builtins::build_module(&types, &exceptions);
+        let mut interpreter = Interpreter {
+            globals: main.clone(),
+            modules: HashMap::new(),
+            optimize: 0,
+            write_cache: true,
+            builtins: builtins.clone(),
+            types,
+            exceptions,
+            handling: Vec::new(),
+            scope: None,
+            call_depth: 0,
+            limits: Limits::default(),
+            usage: Usage::build_new(),
+            interrupt: InterruptHandle::build_new(),
+        };
+        interpreter.modules.insert(String::from("__main__"), main);
+        interpreter.modules.insert(String::from("builtins"), builtins);
+        // open() and the standard streams are io's file objects
+        let io = modules::io::build_module(interpreter.types["object"].clone());
+        interpreter.builtins.set("open", io.get("open").unwrap());
+        let sys = modules::sys::build_module(&io, interpreter.types["object"].clone());
+        interpreter.modules.insert(String::from("sys"), sys);
+        interpreter.modules.insert(String::from("io"), io);
+        interpreter.modules.insert(String::from("gc"), modules::gc::build_module());
+        interpreter.modules.insert(String::from("pickle"), modules::pickle::build_module());
+        interpreter.modules.insert(String::from("json"), modules::json::build_module());
+        interpreter.modules.insert(String::from("math"), modules::math::build_module());
+        interpreter.modules.insert(String::from("cmath"), modules::cmath::build_module());
+        let re = modules::re::build_module(interpreter.types["object"].clone());
+        interpreter.modules.insert(String::from("re"), re);
+        let weakref = modules::weakref::build_module(interpreter.types["object"].clone());
+        interpreter.modules.insert(String::from("weakref"), weakref);
+        // Fractions and Decimals are values of their own, so their classes
+        // are types like int's
+        let fraction = fractions::build_class(interpreter.types["object"].clone());
+        interpreter.types.insert(fraction.name.clone(), fraction.clone());
+        interpreter.modules.insert(String::from("fractions"), fractions::build_module(fraction));
+        let decimal = decimal::build_class(interpreter.types["object"].clone());
+        interpreter.types.insert(decimal.name.clone(), decimal.clone());
+        let decimal = decimal::build_module(decimal, interpreter.types["object"].clone(), &interpreter.exceptions);
+        // its signals are raised by kind, like "decimal.Overflow"
+        for value in decimal.vars.borrow().values() {
+            if let Value::Class(class) = value {
+                if class.name.starts_with("decimal.") {
+                    interpreter.exceptions.insert(class.name.clone(), class.clone());
+                }
+            }
+        }
+        interpreter.modules.insert(String::from("decimal"), decimal);
+        let os = modules::os::build_module(interpreter.types["object"].clone());
+        interpreter.modules.insert(String::from("os.path"), modules::os::path_module(&os));
+        interpreter.modules.insert(String::from("os"), os);
+        let collections = modules::collections::build_module(interpreter.types["object"].clone(), interpreter.types["dict"].clone());
+        interpreter.modules.insert(String::from("collections"), collections);
+        let itertools = modules::itertools::build_module(interpreter.types["object"].clone());
+        interpreter.modules.insert(String::from("itertools"), itertools);
+        let functools = modules::functools::build_module(interpreter.types["object"].clone());
+        interpreter.modules.insert(String::from("functools"), functools);
+        let module_exceptions = [
+            ("json", "JSONDecodeError", "JSONDecodeError"),
+            ("pickle", "PickleError", "PickleError"),
+            ("pickle", "PicklingError", "PicklingError"),
+            ("pickle", "UnpicklingError", "UnpicklingError"),
+            ("re", "error", "re.error"),
+            ("io", "UnsupportedOperation", "io.UnsupportedOperation"),
+        ];
+        for (module, name, kind) in module_exceptions {
+            interpreter.modules[module].set(name, Value::Class(interpreter.exceptions[kind].clone()));
+        }
+        // from here on the importer keeps sys.modules up to date
+        modules::sys::set_modules(&interpreter.modules["sys"], &interpreter.modules);
+        return interpreter;
+    }
+
+    pub fn exec_block(&mut self, block: &[Stmt]) -> Result<Flow, Exception> {
+        for stmt in block {
+            let flow = self.exec_statement(stmt)?;
+            if flow != Flow::Normal {
+                return Ok(flow);
+            }
+        }
+        return Ok(Flow::Normal);
+    }
+
+    pub fn exec_statement(&mut self, stmt: &Stmt) -> Result<Flow, Exception> {
+        return limits::with_stack(|| self.exec_statement_kind(&stmt.kind)).map_err(|mut err| {
+            // the innermost statement an exception passes through is where
+            // its frame's traceback entry points
+            err.line.get_or_insert(stmt.line);
+            err
+        });
+    }
+
+    fn exec_statement_kind(&mut self, stmt: &StmtKind) -> Result<Flow, Exception> {
+        self.tick()?;
+        match stmt {
+            StmtKind::Expr(expr) => {
+                self.eval(expr)?;
+            }
+            StmtKind::Assign(targets, expr) => {
+                let value = self.eval(expr)?;
+                for target in targets {
+                    self.assign(target, value.clone())?;
+                }
+            }
+            StmtKind::AugAssign(target, op, expr) => self.exec_aug_assign(target, *op, expr)?,
+            StmtKind::Delete(targets) => {
+                for target in targets {
+                    self.delete(target)?;
+                }
+            }
+            StmtKind::If(test, body, orelse) => {
+                let test = self.eval(test)?;
+                if self.truthy(&test)? {
+                    return self.exec_block(body);
+                }
+                return self.exec_block(orelse);
+            }
+            StmtKind::While(test, body, orelse) => {
+                loop {
+                    let test = self.eval(test)?;
+                    if !self.truthy(&test)? {
+                        break;
+                    }
+                    match self.exec_block(body)? {
+                        Flow::Break => return Ok(Flow::Normal),
+                        Flow::Return(value) => return Ok(Flow::Return(value)),
+                        _ => (),
+                    };
+                }
+                return self.exec_block(orelse);
+            }
+            StmtKind::For(target, iter, body, orelse) => {
+                let iterable = self.eval(iter)?;
+                let iterator = self.get_iter(&iterable)?;
+                while let Some(item) = self.next_item(&iterator)? {
+                    self.assign(target, item)?;
+                    match self.exec_block(body)? {
+                        Flow::Break => return Ok(Flow::Normal),
+                        Flow::Return(value) => return Ok(Flow::Return(value)),
+                        _ => (),
+                    };
+                }
+                return self.exec_block(orelse);
+            }
+            StmtKind::With(items, body) => return self.exec_with(items, body),
+            StmtKind::Try(body, handlers, orelse, finalbody) => return self.exec_try(body, handlers, orelse, finalbody),
+            StmtKind::Raise(exc, cause) => return Err(self.exec_raise(exc.as_ref(), cause.as_ref())?),
+            StmtKind::FunctionDef(def) => {
+                let function = self.decorate(&def.decorators, |interpreter| interpreter.make_function(def))?;
+                self.assign_name(&def.name, function)?;
+            }
+            StmtKind::ClassDef(def) => {
+                let class = self.decorate(&def.decorators, |interpreter| interpreter.make_class(def))?;
+                self.assign_name(&def.name, class)?;
+            }
+            StmtKind::Return(value) => {
+                let value = match value {
+                    Some(value) => self.eval(value)?,
+                    None => Value::NoneT,
+                };
+                return Ok(Flow::Return(value));
+            }
+            StmtKind::Global(names) => {
+                if let Some(scope) = &self.scope {
+                    scope.globals.borrow_mut().extend(names.iter().cloned());
+                }
+            }
+            StmtKind::Nonlocal(names) => self.declare_nonlocal(names)?,
+            StmtKind::Assert(test, msg) => {
+                let test = self.eval(test)?;
+                if !self.truthy(&test)? {
+                    let msg = match msg {
+                        Some(msg) => self.eval(msg)?,
+                        None => return Err(Exception::new("AssertionError", "")),
+                    };
+                    // the message is the exception's argument, whatever it is
+                    let mut err = Exception::new("AssertionError", &self.str(&msg)?);
+                    err.details_mut().args = Some(vec![msg]);
+                    return Err(err);
+                }
+            }
+            StmtKind::Import(names) => self.exec_import(names)?,
+            StmtKind::ImportFrom(level, module, names) => self.exec_import_from(*level, module, names)?,
+            StmtKind::Pass => (),
+            StmtKind::Break => return Ok(Flow::Break),
+            StmtKind::Continue => return Ok(Flow::Continue),
+        };
+        return Ok(Flow::Normal);
+    }
+
+    /* with statements: each item's __exit__ is called however the rest
+     * finishes, with the exception's class and value (and None for the
+     * traceback); a true result suppresses the exception.
+     */
+    fn exec_with(&mut self, items: &[(Expr, Option<Expr>)], body: &[Stmt]) -> Result<Flow, Exception> {
+        let ((context, target), rest) = match items.split_first() {
+            Some(first) => first,
+            None => return self.exec_block(body),
+        };
+        let manager = self.eval(context)?;
+        let protocol = |result: EvalResult| {
+            return result.map_err(|err| match err.kind.as_str() {
+                "AttributeError" => {
+                    let message = "object does not support the context manager protocol";
+                    Exception::new("TypeError", &format!("'{}' {}", manager.type_name(), message))
+                }
+                _ => err,
+            });
+        };
+        let enter = protocol(self.get_attr(&manager, "__enter__"))?;
+        let exit = protocol(self.get_attr(&manager, "__exit__"))?;
+        let value = self.call(&enter, Args::default())?;
+        let result = match target {
+            Some(target) => self.assign(target, value).and_then(|_| self.exec_with(rest, body)),
+            None => self.exec_with(rest, body),
+        };
+        match result {
+            Ok(flow) => {
+                self.call(&exit, Args::build_new(vec![Value::NoneT, Value::NoneT, Value::NoneT]))?;
+                return Ok(flow);
+            }
+            Err(mut err) => {
+                let value = self.exception_value(&mut err);
+                let class = Value::Class(self.type_of(&value));
+                let exited = self.call(&exit, Args::build_new(vec![class, value, Value::NoneT]));
+                let exited = exited.map_err(|new| chain(new, err.clone()))?;
+                // an exceeded limit can't be suppressed
+                if self.truthy(&exited)? && self.usage.exceeded.is_none() {
+                    return Ok(Flow::Normal);
+                }
+                return Err(err);
+            }
+        }
+    }
+
+    /* try statements. The else block runs if the body finishes without an
+     * exception, break or return, and the finally block however the rest
+     * finishes; a break, continue or return in it drops any exception.
+     */
+    fn exec_try(&mut self, body: &[Stmt], handlers: &[Handler], orelse: &[Stmt], finalbody: &[Stmt]) -> Result<Flow, Exception> {
+        let result = match self.exec_block(body) {
+            Ok(Flow::Normal) => self.exec_block(orelse),
+            Ok(flow) => Ok(flow),
+            Err(err) if handlers.is_empty() => Err(err),
+            Err(err) => self.exec_handlers(err, handlers),
+        };
+        if finalbody.is_empty() {
+            return result;
+        }
+        // the exception on its way out is being handled, as far as a
+        // bare raise and chaining are concerned
+        let pending = match result {
+            Err(mut err) => {
+                self.exception_value(&mut err);
+                self.handling.push(err.clone());
+                Err(err)
+            }
+            ok => ok,
+        };
+        let finished = self.exec_block(finalbody);
+        if pending.is_err() {
+            self.handling.pop();
+        }
+        match (finished, pending) {
+            (Ok(Flow::Normal), pending) => return pending,
+            (Ok(flow), _) => return Ok(flow),
+            (Err(new), Err(err)) => return Err(chain(new, err)),
+            (Err(new), Ok(_)) => return Err(new),
+        }
+    }
+
+    /* Run the first handler that catches err, or pass err on
+     */
+    fn exec_handlers(&mut self, mut err: Exception, handlers: &[Handler]) -> Result<Flow, Exception> {
+        // nothing is handled once a limit is exceeded, so the error gets
+        // back to the host
+        if self.usage.exceeded.is_some() {
+            return Err(err);
+        }
+        let value = self.exception_value(&mut err);
+        for handler in handlers {
+            if let Some(kind) = &handler.kind {
+                let matched = self.eval(kind).and_then(|classes| self.exception_matches(&err, &classes));
+                let matched = matched.map_err(|mut new| {
+                    new.line.get_or_insert(handler.line);
+                    return chain(new, err.clone());
+                });
+                if !matched? {
+                    continue;
+                }
+            }
+            self.set_exception_attrs(&mut err);
+            if let Some(name) = &handler.name {
+                self.assign_name(name, value.clone())?;
+            }
+            self.handling.push(err);
+            let result = self.exec_block(&handler.body);
+            let err = self.handling.pop().unwrap();
+            // like CPython, the name is deleted so the exception (and all it
+            // refers to) isn't kept alive by the frame
+            if let Some(name) = &handler.name {
+                let _ = self.delete_name(name);
+            }
+            return result.map_err(|new| chain(new, err));
+        }
+        return Err(err);
+    }
+
+    /* The exception a raise statement raises: the one being handled for a
+     * bare raise
+     */
+    fn exec_raise(&mut self, exc: Option<&Expr>, cause: Option<&Expr>) -> Result<Exception, Exception> {
+        let exc = match exc {
+            Some(exc) => self.eval(exc)?,
+            None => match self.handling.last() {
+                Some(err) => return Ok(err.clone()),
+                None => return Err(Exception::new("RuntimeError", "No active exception to reraise")),
+            },
+        };
+        let mut err = self.raised(exc)?;
+        if let Some(cause) = cause {
+            let cause = match self.eval(cause)? {
+                Value::NoneT => None,
+                cause => Some(self.raised(cause).map_err(|err| match err.kind.as_str() {
+                    "TypeError" => Exception::new("TypeError", "exception causes must derive from BaseException"),
+                    _ => err,
+                })?),
+            };
+            let details = err.details_mut();
+            details.cause = cause;
+            details.suppress_context = true;
+        }
+        return Ok(err);
+    }
+
+    fn assign(&mut self, target: &Expr, value: Value) -> Result<(), Exception> {
+        match target {
+            Expr::Name(name) => self.assign_name(name, value)?,
+            Expr::Attribute(object, attr) => {
+                let object = self.eval(object)?;
+                self.set_attr(&object, attr, value)?;
+            }
+            Expr::Subscript(object, index) => {
+                let object = self.eval(object)?;
+                let index = self.eval_index(index)?;
+                self.set_index(&object, index, value)?;
+            }
+            Expr::Tuple(targets) | Expr::List(targets) => {
+                let items = self.iterate(&value)?;
+                if items.len() != targets.len() {
+                    let message = if items.len() > targets.len() {
+                        format!("too many values to unpack (expected {})", targets.len())
+                    } else {
+                        format!(
+                            "not enough values to unpack (expected {}, got {})",
+                            targets.len(),
+                            items.len()
+                        )
+                    };
+                    return Err(Exception::new("ValueError", &message));
+                }
+                for (target, item) in targets.iter().zip(items) {
+                    self.assign(target, item)?;
+                }
+            }
+            _ => return Err(Exception::new("SyntaxError", "cannot assign to expression")),
+        };
+        return Ok(());
+    }
+
+    /* target op= value. The target's object and index are evaluated once,
+     * and lists and bytearrays are extended in place by +=, so everything
+     * referring to them sees the change.
+     */
+    fn exec_aug_assign(&mut self, target: &Expr, op: BinOp, value: &Expr) -> Result<(), Exception> {
+        match target {
+            Expr::Name(name) => {
+                let current = self.eval(target)?;
+                let value = self.eval(value)?;
+                let result = self.inplace_op(op, current, &value)?;
+                return self.assign_name(name, result);
+            }
+            Expr::Attribute(object, attr) => {
+                let object = self.eval(object)?;
+                let current = self.get_attr(&object, attr)?;
+                let value = self.eval(value)?;
+                let result = self.inplace_op(op, current, &value)?;
+                return self.set_attr(&object, attr, result);
+            }
+            Expr::Subscript(object, index) => {
+                let object = self.eval(object)?;
+                let index = self.eval_index(index)?;
+                let current = self.get_index(&object, &index)?;
+                let value = self.eval(value)?;
+                let result = self.inplace_op(op, current, &value)?;
+                return self.set_index(&object, index, result);
+            }
+            _ => return Err(Exception::new("SyntaxError", "illegal expression for augmented assignment")),
+        }
+    }
+
+    fn inplace_op(&mut self, op: BinOp, left: Value, right: &Value) -> EvalResult {
+        if let Some(result) = self.inplace_special(op, &left, right) {
+            return result;
+        }
+        match (op, &left) {
+            (BinOp::Add, Value::List(items)) => {
+                let extra = self.iterate(right)?;
+                self.allocate(extra.len() * size_of::<Value>())?;
+                items.borrow_mut().extend(extra);
+                return Ok(left);
+            }
+            (BinOp::Mul, Value::List(items)) => {
+                if let Some(count) = as_int(right) {
+                    self.allocate(items.borrow().len().saturating_mul(count.max(0) as usize).saturating_mul(size_of::<Value>()))?;
+                    let repeated = containers::repeat_items(&items.borrow(), count)?;
+                    // the old items are dropped after the list is released
+                    let old = std::mem::replace(&mut *items.borrow_mut(), repeated);
+                    drop(old);
+                    return Ok(left);
+                }
+                return self.binary_op(op, &left, right);
+            }
+            (BinOp::BitOr | BinOp::BitAnd | BinOp::Sub | BinOp::BitXor, Value::Set(items)) => {
+                if let Value::Set(other) | Value::FrozenSet(other) = right {
+                    let result = containers::set_items_op(op, &items.borrow(), &other.borrow())?;
+                    self.allocate(2 * result.len() * size_of::<Value>())?;
+                    let old = std::mem::replace(&mut *items.borrow_mut(), result);
+                    drop(old);
+                    return Ok(left);
+                }
+                return self.binary_op(op, &left, right);
+            }
+            (BinOp::BitOr, Value::Dict(dict)) => {
+                containers::update_dict(self, dict, right)?;
+                return Ok(left);
+            }
+            (BinOp::Add, Value::ByteArray(array)) => {
+                if let Some(len) = str_result_len(op, &left, right) {
+                    self.allocate(len)?;
+                }
+                let joined = bytes::contents(&bytes::concat(&left, right)?).unwrap();
+                bytes::check_resizable(array)?;
+                *array.data.borrow_mut() = joined;
+                return Ok(left);
+            }
+            _ => return self.binary_op(op, &left, right),
+        }
+    }
+
+    /* left op right, with the memory a string or bytes result takes
+     * counted first
+     */
+    fn binary_op(&mut self, op: BinOp, left: &Value, right: &Value) -> EvalResult {
+        if let Some(result) = self.binary_special(op, left, right) {
+            return result;
+        }
+        if let Some(len) = str_result_len(op, left, right) {
+            self.allocate(len)?;
+        }
+        if let Some(len) = containers::result_len(op, left, right) {
+            self.allocate(len.saturating_mul(size_of::<Value>()))?;
+        }
+        if let Some(len) = int_result_len(op, left, right) {
+            self.allocate(len)?;
+            limits::new_buffer(len)?;
+        }
+        if let (BinOp::Mod, Value::Str(format)) = (op, left) {
+            return format::percent_format(Some(self), format, right);
+        }
+        return binary_op(op, left, right);
+    }
+
+    /* del target
+     */
+    fn delete(&mut self, target: &Expr) -> Result<(), Exception> {
+        match target {
+            Expr::Name(name) => return self.delete_name(name),
+            Expr::Attribute(object, attr) => {
+                let object = self.eval(object)?;
+                let name = Value::Str(attr.clone());
+                if let Some(result) = self.call_special(&object, "__delattr__", vec![name]) {
+                    result?;
+                    return Ok(());
+                }
+                return delete_attribute(&object, attr);
+            }
+            Expr::Subscript(object, index) => {
+                let object = self.eval(object)?;
+                let index = self.eval_index(index)?;
+                return self.delete_index(&object, index);
+            }
+            Expr::Tuple(targets) | Expr::List(targets) => {
+                for target in targets {
+                    self.delete(target)?;
+                }
+                return Ok(());
+            }
+            _ => return Err(Exception::new("SyntaxError", "cannot delete expression")),
+        }
+    }
+
+    /* Unbind a name in the current scope, honoring global/nonlocal
+     */
+    fn delete_name(&mut self, name: &str) -> Result<(), Exception> {
+        let removed = match &self.scope {
+            Some(scope) if !scope.globals.borrow().contains(name) => {
+                let mut current = Some(scope);
+                if scope.nonlocals.borrow().contains(name) {
+                    current = scope.parent.as_ref();
+                    while let Some(outer) = current.filter(|outer| !outer.vars.borrow().contains_key(name)) {
+                        current = outer.parent.as_ref();
+                    }
+                }
+                current.is_some_and(|scope| scope.vars.borrow_mut().remove(name).is_some())
+            }
+            _ => self.globals.vars.borrow_mut().remove(name).is_some(),
+        };
+        if !removed {
+            return Err(self.name_error(name));
+        }
+        return Ok(());
+    }
+
+    /* The error for a name that isn't bound: an UnboundLocalError if it's
+     * a local of the function running, a NameError otherwise
+     */
+    fn name_error(&self, name: &str) -> Exception {
+        let mut scope = self.scope.as_ref();
+        while let Some(current) = scope {
+            if current.function.as_ref().is_some_and(|def| def.is_local(name)) {
+                if self.scope.as_ref().is_some_and(|innermost| Rc::ptr_eq(innermost, current)) {
+                    let message = format!("cannot access local variable '{}' where it is not associated with a value", name);
+                    return Exception::new("UnboundLocalError", &message);
+                }
+                let message = format!(
+                    "cannot access free variable '{}' where it is not associated with a value in enclosing scope",
+                    name
+                );
+                return Exception::new("NameError", &message);
+            }
+            scope = current.parent.as_ref();
+        }
+        return Exception::new("NameError", &format!("name '{}' is not defined", name));
+    }
+
+    pub fn eval(&mut self, expr: &Expr) -> EvalResult {
+        return limits::with_stack(|| self.eval_expr(expr));
+    }
+
+    fn eval_expr(&mut self, expr: &Expr) -> EvalResult {
+        match expr {
+            Expr::NoneT => return Ok(Value::NoneT),
+            Expr::Bool(val) => return Ok(Value::Bool(*val)),
+            Expr::Int(val) => return Ok(Value::Int(*val)),
+            Expr::BigInt(val) => return Ok(Value::BigInt(val.clone())),
+            Expr::Float(val) => return Ok(Value::Float(*val)),
+            Expr::Imaginary(val) => return Ok(Value::Complex(0.0, *val)),
+            Expr::Str(val) => return Ok(Value::Str(val.clone())),
+            Expr::Bytes(val) => return Ok(bytes::new_bytes(val.clone())),
+            Expr::Name(name) => match self.lookup(name) {
+                Some(value) => return Ok(value),
+                None => return Err(self.name_error(name)),
+            },
+            Expr::Attribute(object, attr) => {
+                let object = self.eval(object)?;
+                return self.get_attr(&object, attr);
+            }
+            Expr::List(items) => {
+                self.allocate(items.len() * size_of::<Value>())?;
+                let items = self.eval_all(items)?;
+                return Ok(new_list(items));
+            }
+            Expr::Tuple(items) => {
+                self.allocate(items.len() * size_of::<Value>())?;
+                let items = self.eval_all(items)?;
+                return Ok(new_tuple(items));
+            }
+            Expr::Set(items) => {
+                self.allocate(2 * items.len() * size_of::<Value>())?;
+                let set = RefCell::new(Dict::build_new());
+                for item in self.eval_all(items)? {
+                    let item = self.dict_key(&set, &item)?;
+                    set.borrow_mut().set(item, Value::NoneT)?;
+                }
+                return Ok(new_set(set.into_inner(), false));
+            }
+            Expr::UnaryOp(op, operand) => {
+                let operand = self.eval(operand)?;
+                if let UnaryOp::Not = op {
+                    return Ok(Value::Bool(!self.truthy(&operand)?));
+                }
+                if let Some(result) = self.unary_special(*op, &operand) {
+                    return result;
+                }
+                return unary_op(*op, &operand);
+            }
+            Expr::BinOp(left, op, right) => {
+                let left = self.eval(left)?;
+                let right = self.eval(right)?;
+                return self.binary_op(*op, &left, &right);
+            }
+            Expr::BoolOp(left, op, right) => {
+                // short-circuits, and returns the deciding operand itself
+                let left = self.eval(left)?;
+                match (op, self.truthy(&left)?) {
+                    (BoolOp::And, false) | (BoolOp::Or, true) => return Ok(left),
+                    _ => return self.eval(right),
+                }
+            }
+            Expr::Compare(first, rest) => {
+                let mut left = self.eval(first)?;
+                for (op, right) in rest {
+                    let right = self.eval(right)?;
+                    let result = match (op, &right) {
+                        (CmpOp::In | CmpOp::NotIn, Value::Iterator(_)) => {
+                            self.iterator_contains(&right, &left)? == (*op == CmpOp::In)
+                        }
+                        (CmpOp::In | CmpOp::NotIn, Value::Object(_)) => {
+                            match self.call_special(&right, "__contains__", vec![left.clone()]) {
+                                Some(found) => self.truthy(&found?)? == (*op == CmpOp::In),
+                                None => compare(*op, &left, &right)?,
+                            }
+                        }
+                        (CmpOp::In | CmpOp::NotIn, _) => self.contains_item(&right, &left)? == (*op == CmpOp::In),
+                        _ => self.rich_compare(*op, &left, &right)?,
+                    };
+                    if !result {
+                        return Ok(Value::Bool(false));
+                    }
+                    left = right;
+                }
+                return Ok(Value::Bool(true));
+            }
+            Expr::Call(func, args) => {
+                let func = self.eval(func)?;
+                let args = self.eval_args(args)?;
+                return self.call(&func, args);
+            }
+            Expr::Subscript(object, index) => {
+                let object = self.eval(object)?;
+                let index = self.eval_index(index)?;
+                return self.get_index(&object, &index);
+            }
+            Expr::Slice(..) => return Err(Exception::new("SyntaxError", "invalid syntax")),
+            Expr::Dict(items) => {
+                self.allocate(items.len() * 2 * size_of::<Value>())?;
+                let dict = RefCell::new(Dict::build_new());
+                for (key, value) in items {
+                    let key = self.eval(key)?;
+                    let value = self.eval(value)?;
+                    let key = self.dict_key(&dict, &key)?;
+                    dict.borrow_mut().set(key, value)?;
+                }
+                return Ok(new_dict(dict.into_inner()));
+            }
+            Expr::Lambda(def) => return self.make_function(def),
+            Expr::Comprehension(comp) => return self.eval_comprehension(comp),
+            Expr::IfExp(test, body, orelse) => {
+                let test = self.eval(test)?;
+                if self.truthy(&test)? {
+                    return self.eval(body);
+                }
+                return self.eval(orelse);
+            }
+        }
+    }
+}
+
+impl Interpreter {
+    fn eval_all(&mut self, exprs: &[Expr]) -> Result<Vec<Value>, Exception> {
+        let mut values: Vec<Value> = Vec::new();
+        for expr in exprs {
+            values.push(self.eval(expr)?);
+        }
+        return Ok(values);
+    }
+
+    /* Resolve a name: the running function's scopes (innermost first),
+     * then globals, then builtins
+     */
+    pub fn lookup(&self, name: &str) -> Option<Value> {
+        if let Some(scope) = &self.scope {
+            if !scope.globals.borrow().contains(name) {
+                let mut scope = Some(scope);
+                while let Some(current) = scope {
+                    if let Some(value) = current.vars.borrow().get(name) {
+                        return Some(value.clone());
+                    }
+                    // a function's local that isn't bound yet hides the
+                    // names outside it
+                    if current.function.as_ref().is_some_and(|def| def.is_local(name)) {
+                        return None;
+                    }
+                    scope = current.parent.as_ref();
+                }
+            }
+        }
+        return self.globals.get(name).or_else(|| self.builtins.get(name));
+    }
+
+    /* Bind a name in the current scope, honoring global/nonlocal
+     */
+    pub fn assign_name(&mut self, name: &str, value: Value) -> Result<(), Exception> {
+        let scope = match &self.scope {
+            Some(scope) => scope,
+            None => {
+                self.globals.set(name, value);
+                return Ok(());
+            }
+        };
+        if scope.globals.borrow().contains(name) {
+            self.globals.set(name, value);
+        } else if scope.nonlocals.borrow().contains(name) {
+            let mut outer = scope.parent.as_ref();
+            while let Some(current) = outer {
+                if current.vars.borrow().contains_key(name) {
+                    current.vars.borrow_mut().insert(String::from(name), value);
+                    return Ok(());
+                }
+                outer = current.parent.as_ref();
+            }
+        } else {
+            scope.vars.borrow_mut().insert(String::from(name), value);
+        }
+        return Ok(());
+    }
+
+    fn declare_nonlocal(&mut self, names: &[String]) -> Result<(), Exception> {
+        let scope = match &self.scope {
+            Some(scope) => scope,
+            None => {
+                return Err(Exception::new(
+                    "SyntaxError",
+                    "nonlocal declaration not allowed at module level",
+                ));
+            }
+        };
+        for name in names {
+            let mut outer = scope.parent.as_ref();
+            while let Some(current) = outer {
+                if current.vars.borrow().contains_key(name) {
+                    break;
+                }
+                outer = current.parent.as_ref();
+            }
+            if outer.is_none() {
+                let message = format!("no binding for nonlocal '{}' found", name);
+                return Err(Exception::new("SyntaxError", &message));
+            }
+            scope.nonlocals.borrow_mut().insert(name.clone());
+        }
+        return Ok(());
+    }
+
+    /* What a def or class statement binds its name to: its decorators are
+     * evaluated first, top to bottom, then called on what make() builds,
+     * bottom to top
+     */
+    fn decorate(&mut self, decorators: &[Expr], make: impl FnOnce(&mut Interpreter) -> EvalResult) -> EvalResult {
+        let mut funcs: Vec<Value> = Vec::with_capacity(decorators.len());
+        for decorator in decorators {
+            funcs.push(self.eval(decorator)?);
+        }
+        let mut value = make(self)?;
+        for func in funcs.iter().rev() {
+            value = self.call(func, Args::build_new(vec![value]))?;
+        }
+        return Ok(value);
+    }
+
+    /* Create a function object for a def or lambda, evaluating its default
+     * values now
+     */
+    fn make_function(&mut self, def: &Rc<FunctionDef>) -> EvalResult {
+        let mut defaults: HashMap<String, Value> = HashMap::new();
+        for (name, default) in def.params.args.iter().chain(&def.params.kwonly) {
+            if let Some(default) = default {
+                defaults.insert(name.clone(), self.eval(default)?);
+            }
+        }
+        let function = Rc::new(Function {
+            def: def.clone(),
+            defaults,
+            globals: self.globals.clone(),
+            closure: match &self.scope {
+                // methods see what encloses the class instead
+                Some(scope) if scope.class_body => scope.parent.clone(),
+                scope => scope.clone(),
+            },
+            wrapped: None,
+        });
+        gc::track(Tracked::Function(Rc::downgrade(&function)));
+        // a scope can only be part of a cycle once a function refers to it
+        if let Some(closure) = &function.closure {
+            gc::track(Tracked::Scope(Rc::downgrade(closure)));
+        }
+        return Ok(Value::Function(function));
+    }
+
+    /* A comprehension runs in a scope of its own, nested in the current
+     * one like a function's. A generator expression is an iterator that
+     * runs it an item at a time, as the items are asked for.
+     */
+    fn eval_comprehension(&mut self, comp: &Rc<Comprehension>) -> EvalResult {
+        let iterable = self.eval(&comp.clauses[0].iter)?;
+        let mut iterators = vec![self.get_iter(&iterable)?];
+        let scope = Rc::new(Scope::build_new(self.scope.clone()));
+        let globals = self.globals.clone();
+        match comp.kind {
+            ComprehensionKind::List => {
+                return self.in_comprehension(comp, &globals, &scope, |interpreter| {
+                    let mut items: Vec<Value> = Vec::new();
+                    while interpreter.next_comprehension(comp, &mut iterators)? {
+                        interpreter.allocate(size_of::<Value>())?;
+                        items.push(interpreter.eval(&comp.element)?);
+                    }
+                    return Ok(new_list(items));
+                });
+            }
+            ComprehensionKind::Dict => {
+                return self.in_comprehension(comp, &globals, &scope, |interpreter| {
+                    let dict = RefCell::new(Dict::build_new());
+                    while interpreter.next_comprehension(comp, &mut iterators)? {
+                        interpreter.allocate(2 * size_of::<Value>())?;
+                        let key = interpreter.eval(&comp.element)?;
+                        let value = interpreter.eval(comp.value.as_ref().unwrap())?;
+                        let key = interpreter.dict_key(&dict, &key)?;
+                        dict.borrow_mut().set(key, value)?;
+                    }
+                    return Ok(new_dict(dict.into_inner()));
+                });
+            }
+            ComprehensionKind::Set => {
+                return self.in_comprehension(comp, &globals, &scope, |interpreter| {
+                    let set = RefCell::new(Dict::build_new());
+                    while interpreter.next_comprehension(comp, &mut iterators)? {
+                        interpreter.allocate(2 * size_of::<Value>())?;
+                        let item = interpreter.eval(&comp.element)?;
+                        let item = interpreter.dict_key(&set, &item)?;
+                        set.borrow_mut().set(item, Value::NoneT)?;
+                    }
+                    return Ok(new_set(set.into_inner(), false));
+                });
+            }
+            ComprehensionKind::Generator => {
+                let comp = comp.clone();
+                return Ok(iterator::native_iterator("generator", move |interpreter| {
+                    return interpreter.in_comprehension(&comp, &globals, &scope, |interpreter| {
+                        if !interpreter.next_comprehension(&comp, &mut iterators)? {
+                            return Ok(None);
+                        }
+                        return Ok(Some(interpreter.eval(&comp.element)?));
+                    });
+                }));
+            }
+        }
+    }
+
+    /* Run part of a comprehension with its scope and module as the
+     * current ones. Exceptions leaving it get a frame for it, as in CPython.
+     */
+    fn in_comprehension<T, F>(
+        &mut self,
+        comp: &Comprehension,
+        globals: &Rc<Module>,
+        scope: &Rc<Scope>,
+        run: F,
+    ) -> Result<T, Exception>
+    where
+        F: FnOnce(&mut Interpreter) -> Result<T, Exception>,
+    {
+        let saved_globals = std::mem::replace(&mut self.globals, globals.clone());
+        let saved_scope = self.scope.replace(scope.clone());
+        let result = run(self);
+        self.globals = saved_globals;
+        self.scope = saved_scope;
+        return result.map_err(|mut err| {
+            let name = match comp.kind {
+                ComprehensionKind::List => "<listcomp>",
+                ComprehensionKind::Dict => "<dictcomp>",
+                ComprehensionKind::Set => "<setcomp>",
+                ComprehensionKind::Generator => "<genexpr>",
+            };
+            err.line.get_or_insert(comp.line);
+            err.add_frame(globals, name);
+            err
+        });
+    }
+
+    /* Bind the targets of a comprehension's clauses to their next items
+     * that pass the conditions; false once they run out. iterators holds
+     * the iterator of each clause that is running, innermost last.
+     */
+    fn next_comprehension(&mut self, comp: &Comprehension, iterators: &mut Vec<Value>) -> Result<bool, Exception> {
+        while let Some(iterator) = iterators.last() {
+            let clause = &comp.clauses[iterators.len() - 1];
+            let item = match self.next_item(iterator)? {
+                Some(item) => item,
+                None => {
+                    iterators.pop();
+                    continue;
+                }
+            };
+            self.assign(&clause.target, item)?;
+            let mut passed = true;
+            for condition in &clause.conditions {
+                let condition = self.eval(condition)?;
+                if !self.truthy(&condition)? {
+                    passed = false;
+                    break;
+                }
+            }
+            if !passed {
+                continue;
+            }
+            match comp.clauses.get(iterators.len()) {
+                Some(inner) => {
+                    let iterable = self.eval(&inner.iter)?;
+                    iterators.push(self.get_iter(&iterable)?);
+                }
+                None => return Ok(true),
+            };
+        }
+        return Ok(false);
+    }
+
+    pub(crate) fn eval_args(&mut self, args: &[Arg]) -> Result<Args, Exception> {
+        let mut result = Args::default();
+        for arg in args {
+            match arg {
+                Arg::Positional(expr) => result.positional.push(self.eval(expr)?),
+                Arg::Keyword(name, expr) => {
+                    let value = self.eval(expr)?;
+                    result.keywords.push((name.clone(), value));
+                }
+                Arg::Star(expr) => {
+                    let value = self.eval(expr)?;
+                    result.positional.extend(self.iterate(&value)?);
+                }
+                Arg::DoubleStar(expr) => match self.eval(expr)? {
+                    ref mapping if mapping.as_dict().is_some() => {
+                        for (key, value) in mapping.as_dict().unwrap().borrow().items() {
+                            match key {
+                                Value::Str(ref key) => result.keywords.push((key.clone(), value)),
+                                _ => return Err(type_error(String::from("keywords must be strings"))),
+                            };
+                        }
+                    }
+                    other => {
+                        return Err(type_error(format!(
+                            "argument after ** must be a mapping, not {}",
+                            other.type_name()
+                        )));
+                    }
+                },
+            };
+        }
+        return Ok(result);
+    }
+
+    /* Call a function, builtin or class
+     */
+    pub fn call(&mut self, func: &Value, args: Args) -> EvalResult {
+        match func {
+            Value::Builtin(builtin) => return (builtin.func)(self, args),
+            Value::Function(function) => return self.call_function(function, args),
+            Value::Class(class) => match &class.constructor {
+                Some(constructor) => return constructor(self, args),
+                // an instance with no native value, which __init__ sets up
+                None if self.is_plain_class(class) => return self.instantiate(class, args),
+                None => return Err(type_error(format!("cannot create '{}' instances", class.name))),
+            },
+            Value::Method(method) => {
+                let mut args = args;
+                args.positional.insert(0, method.object.clone());
+                return self.call(&method.function, args);
+            }
+            Value::Object(object) => match object.class.lookup("__call__") {
+                Some(method) => {
+                    let mut args = args;
+                    args.positional.insert(0, func.clone());
+                    return self.call(&method, args);
+                }
+                None => return Err(type_error(format!("'{}' object is not callable", object.class.name))),
+            },
+            _ => return Err(type_error(format!("'{}' object is not callable", func.type_name()))),
+        }
+    }
+
+    /* Call a special method (__len__, __getitem__, ...) the class of an
+     * instance defines, with the instance first. None if it isn't an
+     * instance, or its class doesn't define one.
+     */
+    pub fn call_special(&mut self, object: &Value, name: &str, args: Vec<Value>) -> Option<EvalResult> {
+        if let Value::Object(instance) = object {
+            let method = instance.class.lookup(name)?;
+            let mut positional = vec![object.clone()];
+            positional.extend(args);
+            return Some(self.call(&method, Args::build_new(positional)));
+        }
+        return None;
+    }
+
+    fn call_function(&mut self, function: &Rc<Function>, args: Args) -> EvalResult {
+        if self.call_depth >= self.limits.max_recursion {
+            return Err(Exception::new("RecursionError", "maximum recursion depth exceeded"));
+        }
+        let mut scope = Scope::build_new(function.closure.clone());
+        scope.function = Some(function.def.clone());
+        let scope = Rc::new(scope);
+        bind_arguments(function, args, &mut scope.vars.borrow_mut())?;
+
+        let saved_globals = std::mem::replace(&mut self.globals, function.globals.clone());
+        let saved_scope = self.scope.replace(scope);
+        self.call_depth += 1;
+        let result = limits::with_stack(|| self.exec_block(&function.def.body));
+        self.call_depth -= 1;
+        self.globals = saved_globals;
+        self.scope = saved_scope;
+        match result {
+            Ok(Flow::Return(value)) => return Ok(value),
+            Ok(_) => return Ok(Value::NoneT),
+            Err(mut err) => {
+                err.add_frame(&function.globals, &function.def.name);
+                return Err(err);
+            }
+        }
+    }
+
+    /* object.attr, including properties, which need calling
+     */
+    pub fn get_attr(&mut self, object: &Value, attr: &str) -> EvalResult {
+        if let Some(result) = self.super_attr(object, attr) {
+            return result;
+        }
+        if let Value::Object(instance) = object {
+            if let Some(Value::Property(ref property)) = instance.class.lookup(attr) {
+                return self.call(&property.getter, Args::build_new(vec![object.clone()]));
+            }
+        }
+        match get_attribute(object, attr) {
+            // __getattr__ is only asked for what isn't found otherwise
+            Err(err) if err.kind == "AttributeError" => {
+                // methods of built-in types are functions on their classes
+                if !matches!(object, Value::Object(_) | Value::Class(_) | Value::Module(_)) {
+                    if let Some(function @ (Value::Function(_) | Value::Builtin(_))) = self.type_of(object).lookup(attr) {
+                        return Ok(Method::build_new(object.clone(), function));
+                    }
+                }
+                match self.call_special(object, "__getattr__", vec![Value::Str(String::from(attr))]) {
+                    Some(result) => return result,
+                    None => return Err(err),
+                }
+            }
+            result => return result,
+        }
+    }
+
+    /* object.attr = value
+     */
+    pub fn set_attr(&mut self, object: &Value, attr: &str, value: Value) -> Result<(), Exception> {
+        if let Value::Object(instance) = object {
+            if let Some(Value::Property(ref property)) = instance.class.lookup(attr) {
+                let setter = match &property.setter {
+                    Some(setter) => setter,
+                    None => {
+                        let message = format!("property '{}' of '{}' object has no setter", attr, instance.class.name);
+                        return Err(Exception::new("AttributeError", &message));
+                    }
+                };
+                self.call(setter, Args::build_new(vec![object.clone(), value]))?;
+                return Ok(());
+            }
+        }
+        let name = Value::Str(String::from(attr));
+        if let Some(result) = self.call_special(object, "__setattr__", vec![name, value.clone()]) {
+            result?;
+            return Ok(());
+        }
+        return set_attribute(object, attr, value);
+    }
+
+    /* repr(value), calling __repr__ for instances of classes that define
+     * it, in containers too
+     */
+    pub fn repr(&mut self, value: &Value) -> Result<String, Exception> {
+        return value.repr_with(&mut |object| match self.call_special(object, "__repr__", vec![]) {
+            Some(result) => return expect_str(result?, "__repr__").map(Some),
+            None => return Ok(None),
+        });
+    }
+
+    /* str(value): __str__ if the instance's class defines it, otherwise
+     * the repr
+     */
+    pub fn str(&mut self, value: &Value) -> Result<String, Exception> {
+        match value {
+            Value::Object(_) => {
+                if let Some(result) = self.call_special(value, "__str__", vec![]) {
+                    return expect_str(result?, "__str__");
+                }
+                return self.repr(value);
+            }
+            Value::List(_) | Value::Tuple(_) | Value::Dict(_) | Value::Set(_) | Value::FrozenSet(_) => return self.repr(value),
+            _ => return Ok(value.to_string()),
+        }
+    }
+
+    /* type(value)
+     */
+    pub fn type_of(&mut self, value: &Value) -> Rc<Class> {
+        match value {
+            Value::Bool(_) => return self.types["bool"].clone(),
+            Value::Object(object) => return object.class.clone(),
+            _ => (),
+        };
+        let name = value.type_name();
+        if let Some(class) = self.types.get(name) {
+            return class.clone();
+        }
+        // internal types (iterators etc.) are created on first use
+        let class = Class::build_new(name, vec![self.types["object"].clone()], None);
+        self.types.insert(String::from(name), class.clone());
+        return class;
+    }
+
+    /* the bounds of a slice as indexes, None where they are left out
+     */
+    fn slice_bounds(&self, bounds: &[Value; 3]) -> Result<SliceBounds, Exception> {
+        let mut ints: Vec<Option<i64>> = Vec::new();
+        for bound in bounds {
+            match (bound, slice_index(bound)) {
+                (Value::NoneT, _) => ints.push(None),
+                (_, Some(val)) => ints.push(Some(val)),
+                _ => {
+                    return Err(type_error(String::from(
+                        "slice indices must be integers or None or have an __index__ method",
+                    )));
+                }
+            };
+        }
+        return Ok((ints[0], ints[1], ints[2]));
+    }
+
+    /* a[lower:upper:step] or a[slice(...)] is a slice; the bounds are
+     * kept as they are for instances, which are given a slice object
+     */
+    fn eval_index(&mut self, index: &Expr) -> Result<Index, Exception> {
+        if let Expr::Slice(lower, upper, step) = index {
+            let mut bounds = [Value::NoneT, Value::NoneT, Value::NoneT];
+            for (bound, expr) in bounds.iter_mut().zip([lower, upper, step]) {
+                if let Some(expr) = expr {
+                    *bound = self.eval(expr)?;
+                }
+            }
+            return Ok(Index::Slice(bounds));
+        }
+        let index = self.eval(index)?;
+        if let Some(bounds) = containers::slice_of(&index) {
+            return Ok(Index::Slice(bounds));
+        }
+        return Ok(Index::Item(index));
+    }
+
+    /* what an instance's __getitem__ and the like are given for index
+     */
+    fn index_value(&mut self, index: &Index) -> Value {
+        match index {
+            Index::Slice(bounds) => return containers::new_slice(self.types["slice"].clone(), bounds.clone()),
+            Index::Item(index) => return index.clone(),
+        }
+    }
+
+    /* object[index]
+     */
+    fn get_index(&mut self, object: &Value, index: &Index) -> EvalResult {
+        if let Value::Object(_) = object {
+            let index = self.index_value(index);
+            if let Some(result) = self.call_special(object, "__getitem__", vec![index.clone()]) {
+                return result;
+            }
+        }
+        match index {
+            Index::Slice(bounds) => {
+                let (lower, upper, step) = self.slice_bounds(bounds)?;
+                return get_slice(object, lower, upper, step);
+            }
+            Index::Item(index) => return get_item(object, &self.container_key(object, index)?),
+        }
+    }
+
+    /* object[index] = value
+     */
+    fn set_index(&mut self, object: &Value, index: Index, value: Value) -> Result<(), Exception> {
+        if let Value::Object(_) = object {
+            let index = self.index_value(&index);
+            if let Some(result) = self.call_special(object, "__setitem__", vec![index.clone(), value.clone()]) {
+                result?;
+                return Ok(());
+            }
+        }
+        match index {
+            Index::Slice(bounds) => {
+                let (lower, upper, step) = self.slice_bounds(&bounds)?;
+                if let Value::ByteArray(_) | Value::MemoryView(_) = object {
+                    let data = bytes::assigned_bytes(self, object, &value)?;
+                    return set_bytes_slice(object, lower, upper, step, data);
+                }
+                let items = self.iterate(&value)?;
+                return set_slice(object, lower, upper, step, items);
+            }
+            Index::Item(index) => return set_item(object, &self.container_key(object, &index)?, value),
+        }
+    }
+
+    /* del object[index]
+     */
+    fn delete_index(&mut self, object: &Value, index: Index) -> Result<(), Exception> {
+        if let Value::Object(_) = object {
+            let index = self.index_value(&index);
+            if let Some(result) = self.call_special(object, "__delitem__", vec![index.clone()]) {
+                result?;
+                return Ok(());
+            }
+        }
+        match index {
+            Index::Slice(bounds) => {
+                let (lower, upper, step) = self.slice_bounds(&bounds)?;
+                return delete_slice(object, lower, upper, step);
+            }
+            Index::Item(index) => return delete_item(object, &self.container_key(object, &index)?),
+        }
+    }
+
+    /* `item in iterator` consumes the iterator up to the item
+     */
+    fn iterator_contains(&mut self, iterator: &Value, item: &Value) -> Result<bool, Exception> {
+        while let Some(next) = self.next_item(iterator)? {
+            if equals(&next, item) {
+                return Ok(true);
+            }
+        }
+        return Ok(false);
+    }
+}
+
+/* 'a'; 'a' and 'b'; 'a', 'b', and 'c' - for argument error messages
+ */
+pub(crate) fn format_names(names: &[&String]) -> String {
+    let quoted: Vec<String> = names.iter().map(|name| format!("'{}'", name)).collect();
+    match quoted.len() {
+        1 => return quoted[0].clone(),
+        2 => return format!("{} and {}", quoted[0], quoted[1]),
+        len => return format!("{}, and {}", quoted[..len - 1].join(", "), quoted[len - 1]),
+    }
+}
+
+/* Match call arguments to a function's parameters, with CPython's error
+ * messages
+ */
+fn bind_arguments(function: &Function, args: Args, vars: &mut HashMap<String, Value>) -> Result<(), Exception> {
+    let params = &function.def.params;
+    let name = &function.def.name;
+
+    let given = args.positional.len();
+    let mut positional = args.positional.into_iter();
+    for (param, _) in &params.args {
+        if let Some(value) = positional.next() {
+            vars.insert(param.clone(), value);
+        }
+    }
+    let extra: Vec<Value> = positional.collect();
+    match &params.vararg {
+        Some(vararg) => {
+            vars.insert(vararg.clone(), new_tuple(extra));
+        }
+        None if !extra.is_empty() => {
+            let max = params.args.len();
+            let required = params.args.iter().filter(|(param, _)| !function.defaults.contains_key(param)).count();
+            let takes = if required == max { max.to_string() } else { format!("from {} to {}", required, max) };
+            return Err(type_error(format!(
+                "{}() takes {} positional argument{} but {} {} given",
+                name,
+                takes,
+                if max == 1 { "" } else { "s" },
+                given,
+                if given == 1 { "was" } else { "were" }
+            )));
+        }
+        None => (),
+    };
+
+    let mut kwargs = Dict::build_new();
+    for (key, value) in args.keywords {
+        let named = params.args.iter().chain(&params.kwonly).any(|(param, _)| *param == key);
+        if named {
+            if vars.contains_key(&key) {
+                return Err(type_error(format!("{}() got multiple values for argument '{}'", name, key)));
+            }
+            vars.insert(key, value);
+        } else if params.kwarg.is_some() {
+            let key = Value::Str(key);
+            if kwargs.get(&key)?.is_some() {
+                return Err(type_error(format!(
+                    "{}() got multiple values for keyword argument {}",
+                    name,
+                    key.repr()
+                )));
+            }
+            kwargs.set(key, value)?;
+        } else {
+            return Err(type_error(format!("{}() got an unexpected keyword argument '{}'", name, key)));
+        }
+    }
+
+    for (params, kind) in [(&params.args, "positional"), (&params.kwonly, "keyword-only")] {
+        let mut missing: Vec<&String> = Vec::new();
+        for (param, _) in params {
+            if !vars.contains_key(param) {
+                match function.defaults.get(param) {
+                    Some(default) => {
+                        vars.insert(param.clone(), default.clone());
+                    }
+                    None => missing.push(param),
+                };
+            }
+        }
+        if !missing.is_empty() {
+            return Err(type_error(format!(
+                "{}() missing {} required {} argument{}: {}",
+                name,
+                missing.len(),
+                kind,
+                if missing.len() == 1 { "" } else { "s" },
+                format_names(&missing)
+            )));
+        }
+    }
+    if let Some(kwarg) = &params.kwarg {
+        vars.insert(kwarg.clone(), new_dict(kwargs));
+    }
+    return Ok(());
+}
+
+pub fn get_attribute(object: &Value, attr: &str) -> EvalResult {
+    match object {
+        Value::Module(module) => {
+            if let Some(value) = module.get(attr) {
+                return Ok(value);
+            }
+            let message = format!("module '{}' has no attribute '{}'", module.name, attr);
+            return Err(Exception::new("AttributeError", &message));
+        }
+        Value::Class(class) => {
+            match attr {
+                "__name__" => return Ok(Value::Str(String::from(class.bare_name()))),
+                "__module__" => return Ok(class.module()),
+                "__bases__" => return Ok(new_tuple(class.bases.iter().cloned().map(Value::Class).collect())),
+                "__mro__" => {
+                    let mro = std::iter::once(class.clone()).chain(class.mro.iter().cloned());
+                    return Ok(new_tuple(mro.map(Value::Class).collect()));
+                }
+                _ => (),
+            };
+            if let Some(value) = class.lookup(attr) {
+                return Ok(value);
+            }
+            let message = format!("type object '{}' has no attribute '{}'", class.name, attr);
+            return Err(Exception::new("AttributeError", &message));
+        }
+        Value::Object(instance) => {
+            if let Some(value) = instance.attrs.borrow().get(attr) {
+                return Ok(value.clone());
+            }
+            if attr == "__class__" {
+                return Ok(Value::Class(instance.class.clone()));
+            }
+            match instance.class.lookup(attr) {
+                // functions on the class are methods of the instance
+                Some(function @ (Value::Function(_) | Value::Builtin(_))) => {
+                    return Ok(Method::build_new(object.clone(), function));
+                }
+                Some(value) => return Ok(value),
+                None => {
+                    let message = format!("'{}' object has no attribute '{}'", instance.class.name, attr);
+                    return Err(Exception::new("AttributeError", &message));
+                }
+            }
+        }
+        Value::Complex(real, _) if attr == "real" => return Ok(Value::Float(*real)),
+        Value::Complex(_, imag) if attr == "imag" => return Ok(Value::Float(*imag)),
+        Value::Fraction(val) if attr == "numerator" => return fractions::int_value(val.numer()),
+        Value::Fraction(val) if attr == "denominator" => return fractions::int_value(val.denom()),
+        Value::Fraction(_) | Value::Decimal(_) if attr == "real" => return Ok(object.clone()),
+        Value::Fraction(_) if attr == "imag" => return Ok(Value::Int(0)),
+        Value::Decimal(_) if attr == "imag" => return Ok(decimal::new_decimal(decimal::Decimal::from_i64(0))),
+        Value::MemoryView(view) if bytes::VIEW_ATTRIBUTES.contains(&attr) => return bytes::view_attribute(view, attr),
+        Value::Function(function) if attr == "__name__" => return Ok(Value::Str(function.def.name.clone())),
+        Value::Function(function) if attr == "__doc__" => {
+            return Ok(function.def.doc.clone().map_or(Value::NoneT, Value::Str));
+        }
+        Value::Function(function) if attr == "__wrapped__" && function.wrapped.is_some() => {
+            return Ok(function.wrapped.clone().unwrap());
+        }
+        Value::Builtin(builtin) if attr == "__name__" => return Ok(Value::Str(builtin.name.clone())),
+        _ => {
+            let message = format!("'{}' object has no attribute '{}'", object.type_name(), attr);
+            return Err(Exception::new("AttributeError", &message));
+        }
+    }
+}
+
+pub fn set_attribute(object: &Value, attr: &str, value: Value) -> Result<(), Exception> {
+    match object {
+        Value::Module(module) => {
+            module.set(attr, value);
+            return Ok(());
+        }
+        Value::Class(class) if class.user_defined => {
+            class.attrs.borrow_mut().insert(String::from(attr), value);
+            return Ok(());
+        }
+        Value::Class(class) => {
+            let message = format!("cannot set '{}' attribute of immutable type '{}'", attr, class.name);
+            return Err(type_error(message));
+        }
+        // instances of native classes have no attributes of their own
+        Value::Object(instance) if instance.native.is_none() => {
+            instance.attrs.borrow_mut().insert(String::from(attr), value);
+            return Ok(());
+        }
+        _ => {
+            let message = format!("'{}' object has no attribute '{}'", object.type_name(), attr);
+            return Err(Exception::new("AttributeError", &message));
+        }
+    }
+}
+
+/* del object.attr
+ */
+pub fn delete_attribute(object: &Value, attr: &str) -> Result<(), Exception> {
+    let removed = match object {
+        Value::Module(module) => module.vars.borrow_mut().remove(attr).is_some(),
+        Value::Class(class) if class.user_defined => class.attrs.borrow_mut().remove(attr).is_some(),
+        Value::Class(class) => {
+            let message = format!("cannot set '{}' attribute of immutable type '{}'", attr, class.name);
+            return Err(type_error(message));
+        }
+        Value::Object(instance) => instance.attrs.borrow_mut().remove(attr).is_some(),
+        _ => false,
+    };
+    if !removed {
+        let message = format!("'{}' object has no attribute '{}'", object.type_name(), attr);
+        return Err(Exception::new("AttributeError", &message));
+    }
+    return Ok(());
+}
+
+/* Position in a sequence of length len for a (possibly negative) index
+ */
+fn sequence_index(kind: &str, index: &Value, len: usize) -> Result<usize, Exception> {
+    let index = match as_int(index) {
+        Some(index) => index,
+        None if is_big(index) => {
+            return Err(Exception::new("IndexError", "cannot fit 'int' into an index-sized integer"));
+        }
+        None => {
+            return Err(type_error(format!(
+                "{} indices must be integers or slices, not {}",
+                kind,
+                index.type_name()
+            )));
+        }
+    };
+    let len = len as i64;
+    let position = if index < 0 { index + len } else { index };
+    if position < 0 || position >= len {
+        return Err(Exception::new("IndexError", &format!("{} index out of range", kind)));
+    }
+    return Ok(position as usize);
+}
+
+/* object[index]
+ */
+pub fn get_item(object: &Value, index: &Value) -> EvalResult {
+    match object {
+        Value::List(items) => {
+            let items = items.borrow();
+            return Ok(items[sequence_index("list", index, items.len())?].clone());
+        }
+        Value::Tuple(items) => return Ok(items[sequence_index("tuple", index, items.len())?].clone()),
+        Value::Str(val) => {
+            let chars: Vec<char> = val.chars().collect();
+            return Ok(Value::Str(chars[sequence_index("string", index, chars.len())?].to_string()));
+        }
+        // a range's items are ints of any size, so no index is too big for it
+        Value::Range(..) if is_big(index) => return Err(Exception::new("IndexError", "range object index out of range")),
+        Value::Range(start, stop, step) => {
+            let position = sequence_index("range object", index, range_len(*start, *stop, *step) as usize)?;
+            return Ok(Value::Int(start + position as i64 * step));
+        }
+        Value::Bytes(data) => {
+            let position = sequence_index("byte", index, data.len()).map_err(|err| match err.kind.as_str() {
+                "IndexError" if !is_big(index) => Exception::new("IndexError", "index out of range"),
+                _ => err,
+            })?;
+            return Ok(Value::Int(data[position] as i64));
+        }
+        Value::ByteArray(array) => {
+            let data = array.data.borrow();
+            return Ok(Value::Int(data[sequence_index("bytearray", index, data.len())?] as i64));
+        }
+        Value::MemoryView(view) => return Ok(Value::Int(view.get(bytes::view_index(view, index)?) as i64)),
+        Value::Dict(dict) => match dict.borrow().get(index)? {
+            Some(value) => return Ok(value),
+            None => return Err(exceptions::key_error(index)),
+        },
+        _ => return Err(type_error(format!("'{}' object is not subscriptable", object.type_name()))),
+    }
+}
+
+/* object[index] = value
+ */
+pub fn set_item(object: &Value, index: &Value, value: Value) -> Result<(), Exception> {
+    match object {
+        Value::List(items) => {
+            let mut items = items.borrow_mut();
+            let position = sequence_index("list assignment", index, items.len())?;
+            items[position] = value;
+            return Ok(());
+        }
+        Value::Dict(dict) => return dict.borrow_mut().set(index.clone(), value),
+        Value::ByteArray(array) => {
+            let byte = bytes::byte_value(&value)?;
+            let mut data = array.data.borrow_mut();
+            let position = sequence_index("bytearray", index, data.len())?;
+            data[position] = byte;
+            return Ok(());
+        }
+        Value::MemoryView(view) => return bytes::set_view_item(view, index, &value),
+        _ => {
+            return Err(type_error(format!(
+                "'{}' object does not support item assignment",
+                object.type_name()
+            )));
+        }
+    }
+}
+
+/* del object[index]
+ */
+pub fn delete_item(object: &Value, index: &Value) -> Result<(), Exception> {
+    match object {
+        Value::List(items) => {
+            let mut items = items.borrow_mut();
+            let position = sequence_index("list assignment", index, items.len())?;
+            items.remove(position);
+            return Ok(());
+        }
+        Value::Dict(dict) => match dict.borrow_mut().remove(index)? {
+            Some(_) => return Ok(()),
+            None => return Err(exceptions::key_error(index)),
+        },
+        Value::ByteArray(array) => {
+            let position = sequence_index("bytearray", index, array.data.borrow().len())?;
+            bytes::check_resizable(array)?;
+            array.data.borrow_mut().remove(position);
+            return Ok(());
+        }
+        _ => {
+            return Err(type_error(format!(
+                "'{}' object doesn't support item deletion",
+                object.type_name()
+            )));
+        }
+    }
+}
+
+/* Clamp slice bounds to a sequence of length len, giving the start, stop
+ * and step of the selected indexes (CPython's PySlice_AdjustIndices)
+ */
+pub fn adjust_slice(
+    len: i64,
+    lower: Option<i64>,
+    upper: Option<i64>,
+    step: Option<i64>,
+) -> Result<(i64, i64, i64), Exception> {
+    let step = step.unwrap_or(1);
+    if step == 0 {
+        return Err(Exception::new("ValueError", "slice step cannot be zero"));
+    }
+    let (low, high) = if step > 0 { (0, len) } else { (-1, len - 1) };
+    let clamp = |bound: Option<i64>, default: i64| match bound {
+        None => default,
+        Some(bound) if bound < 0 => (bound + len).max(low),
+        Some(bound) => bound.min(high),
+    };
+    let start = clamp(lower, if step > 0 { low } else { high });
+    let stop = clamp(upper, if step > 0 { high } else { low });
+    return Ok((start, stop, step));
+}
+
+fn slice_positions(len: usize, lower: Option<i64>, upper: Option<i64>, step: Option<i64>) -> Result<Vec<usize>, Exception> {
+    let (start, stop, step) = adjust_slice(len as i64, lower, upper, step)?;
+    let count = range_len(start, stop, step);
+    return Ok((0..count).map(|index| (start + index * step) as usize).collect());
+}
+
+/* object[lower:upper:step]
+ */
+pub fn get_slice(object: &Value, lower: Option<i64>, upper: Option<i64>, step: Option<i64>) -> EvalResult {
+    let pick = |items: &[Value]| -> Result<Vec<Value>, Exception> {
+        let positions = slice_positions(items.len(), lower, upper, step)?;
+        return Ok(positions.into_iter().map(|position| items[position].clone()).collect());
+    };
+    match object {
+        Value::List(items) => return Ok(new_list(pick(&items.borrow())?)),
+        Value::Tuple(items) => return Ok(new_tuple(pick(items)?)),
+        Value::Str(val) => {
+            let chars: Vec<char> = val.chars().collect();
+            let positions = slice_positions(chars.len(), lower, upper, step)?;
+            return Ok(Value::Str(positions.into_iter().map(|position| chars[position]).collect()));
+        }
+        Value::Bytes(data) => {
+            let positions = slice_positions(data.len(), lower, upper, step)?;
+            return Ok(bytes::new_bytes(positions.into_iter().map(|position| data[position]).collect()));
+        }
+        Value::ByteArray(array) => {
+            let data = array.data.borrow();
+            let positions = slice_positions(data.len(), lower, upper, step)?;
+            return Ok(bytes::new_bytearray(positions.into_iter().map(|position| data[position]).collect()));
+        }
+        // a slice of a view is a view of the same bytes
+        Value::MemoryView(view) => {
+            view.check()?;
+            let (start, stop, step) = adjust_slice(view.len as i64, lower, upper, step)?;
+            return Ok(bytes::view_slice(view, start, range_len(start, stop, step), step));
+        }
+        Value::Range(range_start, range_stop, range_step) => {
+            let len = range_len(*range_start, *range_stop, *range_step);
+            let (start, stop, step) = adjust_slice(len, lower, upper, step)?;
+            return Ok(Value::Range(
+                range_start + start * range_step,
+                range_start + stop * range_step,
+                range_step * step,
+            ));
+        }
+        _ => return Err(type_error(format!("'{}' object is not subscriptable", object.type_name()))),
+    }
+}
+
+/* list[lower:upper:step] = items
+ */
+fn set_slice(
+    object: &Value,
+    lower: Option<i64>,
+    upper: Option<i64>,
+    step: Option<i64>,
+    values: Vec<Value>,
+) -> Result<(), Exception> {
+    let items = match object {
+        Value::List(items) => items,
+        _ => {
+            return Err(type_error(format!(
+                "'{}' object does not support item assignment",
+                object.type_name()
+            )));
+        }
+    };
+    let mut items = items.borrow_mut();
+    if step.unwrap_or(1) == 1 {
+        let (start, stop, _) = adjust_slice(items.len() as i64, lower, upper, step)?;
+        let (start, stop) = (start as usize, stop.max(start) as usize);
+        items.splice(start..stop, values);
+        return Ok(());
+    }
+    let positions = slice_positions(items.len(), lower, upper, step)?;
+    if positions.len() != values.len() {
+        let message = format!(
+            "attempt to assign sequence of size {} to extended slice of size {}",
+            values.len(),
+            positions.len()
+        );
+        return Err(Exception::new("ValueError", &message));
+    }
+    for (position, value) in positions.into_iter().zip(values) {
+        items[position] = value;
+    }
+    return Ok(());
+}
+
+/* del list[lower:upper:step], or the same on a bytearray
+ */
+fn delete_slice(object: &Value, lower: Option<i64>, upper: Option<i64>, step: Option<i64>) -> Result<(), Exception> {
+    fn remove<T>(items: &mut Vec<T>, positions: Vec<usize>) {
+        let mut keep = vec![true; items.len()];
+        for position in positions {
+            keep[position] = false;
+        }
+        let mut keep = keep.into_iter();
+        items.retain(|_| keep.next().unwrap());
+    }
+    match object {
+        Value::List(items) => {
+            let mut items = items.borrow_mut();
+            let positions = slice_positions(items.len(), lower, upper, step)?;
+            remove(&mut items, positions);
+            return Ok(());
+        }
+        Value::ByteArray(array) => {
+            let positions = slice_positions(array.data.borrow().len(), lower, upper, step)?;
+            if !positions.is_empty() {
+                bytes::check_resizable(array)?;
+            }
+            remove(&mut array.data.borrow_mut(), positions);
+            return Ok(());
+        }
+        _ => {
+            return Err(type_error(format!(
+                "'{}' object does not support item deletion",
+                object.type_name()
+            )));
+        }
+    }
+}
+
+/* bytearray[lower:upper:step] = data, or the same on a memoryview, which
+ * can't change size
+ */
+fn set_bytes_slice(
+    object: &Value,
+    lower: Option<i64>,
+    upper: Option<i64>,
+    step: Option<i64>,
+    data: Vec<u8>,
+) -> Result<(), Exception> {
+    match object {
+        Value::ByteArray(array) => {
+            let len = array.data.borrow().len();
+            if step.unwrap_or(1) == 1 {
+                let (start, stop, _) = adjust_slice(len as i64, lower, upper, step)?;
+                let (start, stop) = (start as usize, stop.max(start) as usize);
+                if stop - start != data.len() {
+                    bytes::check_resizable(array)?;
+                }
+                array.data.borrow_mut().splice(start..stop, data);
+                return Ok(());
+            }
+            let positions = slice_positions(len, lower, upper, step)?;
+            if positions.len() != data.len() {
+                let message = format!(
+                    "attempt to assign bytes of size {} to extended slice of size {}",
+                    data.len(),
+                    positions.len()
+                );
+                return Err(Exception::new("ValueError", &message));
+            }
+            let mut items = array.data.borrow_mut();
+            for (position, byte) in positions.into_iter().zip(data) {
+                items[position] = byte;
+            }
+            return Ok(());
+        }
+        Value::MemoryView(view) => {
+            view.check()?;
+            if view.readonly {
+                return Err(type_error(String::from("cannot modify read-only memory")));
+            }
+            let positions = slice_positions(view.len, lower, upper, step)?;
+            if positions.len() != data.len() {
+                let message = "memoryview assignment: lvalue and rvalue have different structures";
+                return Err(Exception::new("ValueError", message));
+            }
+            for (position, byte) in positions.into_iter().zip(data) {
+                view.set(position, byte);
+            }
+            return Ok(());
+        }
+        _ => unreachable!(),
+    }
+}
+
+/* An exception raised while err was being handled, with err as its
+ * context; not if it is err again, or already has a context from handling
+ * something else
+ */
+fn chain(mut new: Exception, err: Exception) -> Exception {
+    let same = match (new.value(), err.value()) {
+        (Some(Value::Object(raised)), Some(Value::Object(handled))) => Rc::ptr_eq(raised, handled),
+        _ => false,
+    };
+    let details = new.details_mut();
+    if !same && details.context.is_none() {
+        details.context = Some(err);
+    }
+    return new;
+}
+
+// what __str__ or __repr__ returned, which has to be a str
+fn expect_str(value: Value, method: &str) -> Result<String, Exception> {
+    match value {
+        Value::Str(ref val) => return Ok(val.clone()),
+        other => return Err(type_error(format!("{} returned non-string (type {})", method, other.type_name()))),
+    }
+}
+
+fn type_error(message: String) -> Exception {
+    return Exception::new("TypeError", &message);
+}
+
+/* int-like view of a value (bool is a subclass of int)
+ */
+pub fn as_int(value: &Value) -> Option<i64> {
+    match value {
+        Value::Int(val) => return Some(*val),
+        Value::Bool(val) => return Some(*val as i64),
+        _ => return None,
+    }
+}
+
+/* an int as a slice bound, with ints too big for 64 bits at the ends,
+ * past any sequence
+ */
+pub fn slice_index(value: &Value) -> Option<i64> {
+    match value {
+        Value::BigInt(val) => return Some(if val.is_negative() { i64::MIN } else { i64::MAX }),
+        _ => return as_int(value),
+    }
+}
+
+/* The error for a value that isn't an int where an index or count is
+ * needed. An int too big for 64 bits is an int, but doesn't fit.
+ */
+pub fn not_an_int(value: &Value) -> Exception {
+    match value {
+        Value::BigInt(_) => return Exception::new("OverflowError", "cannot fit 'int' into an index-sized integer"),
+        _ => {
+            let message = format!("'{}' object cannot be interpreted as an integer", value.type_name());
+            return Exception::new("TypeError", &message);
+        }
+    }
+}
+
+/* an int of any size
+ */
+pub fn as_bigint(value: &Value) -> Option<BigInt> {
+    match value {
+        Value::BigInt(val) => return Some((**val).clone()),
+        _ => return as_int(value).map(BigInt::from),
+    }
+}
+
+/* An int result, which is a BigInt only if it doesn't fit in 64 bits
+ */
+pub fn new_int(val: BigInt) -> Value {
+    match val.to_i64() {
+        Some(val) => return Value::Int(val),
+        None => return Value::BigInt(Rc::new(val)),
+    }
+}
+
+/* float(val), which is an error past the largest float
+ */
+pub fn int_to_float(val: &BigInt) -> Result<f64, Exception> {
+    match val.to_f64().filter(|float| float.is_finite()) {
+        Some(float) => return Ok(float),
+        None => return Err(Exception::new("OverflowError", "int too large to convert to float")),
+    }
+}
+
+/* float view of a number; an int too big for a float is an infinity
+ */
+pub fn as_float(value: &Value) -> Option<f64> {
+    match value {
+        Value::Float(val) => return Some(*val),
+        Value::BigInt(val) => {
+            let infinity = if val.is_negative() { f64::NEG_INFINITY } else { f64::INFINITY };
+            return Some(val.to_f64().unwrap_or(infinity));
+        }
+        _ => return as_int(value).map(|val| val as f64),
+    }
+}
+
+/* complex view of a number, as (real, imag)
+ */
+pub fn as_complex(value: &Value) -> Option<(f64, f64)> {
+    match value {
+        Value::Complex(real, imag) => return Some((*real, *imag)),
+        _ => return as_float(value).map(|val| (val, 0.0)),
+    }
+}
+
+/* whether a value is a Fraction or Decimal, which work out their own
+ * arithmetic and comparisons with other numbers
+ */
+fn is_exact(value: &Value) -> bool {
+    return matches!(value, Value::Fraction(_) | Value::Decimal(_));
+}
+
+// a BigInt, which is compared exactly, as a Fraction is
+fn is_big(value: &Value) -> bool {
+    return matches!(value, Value::BigInt(_));
+}
+
+fn op_symbol(op: BinOp) -> &'static str {
+    match op {
+        BinOp::Add => return "+",
+        BinOp::Sub => return "-",
+        BinOp::Mul => return "*",
+        BinOp::Div => return "/",
+        BinOp::FloorDiv => return "//",
+        BinOp::Mod => return "%",
+        BinOp::Pow => return "** or pow()",
+        BinOp::BitAnd => return "&",
+        BinOp::BitOr => return "|",
+        BinOp::BitXor => return "^",
+        BinOp::LShift => return "<<",
+        BinOp::RShift => return ">>",
+    }
+}
+
+/* Python's floored integer division and modulo
+ */
+fn int_floor_div(left: i64, right: i64) -> Option<i64> {
+    let quotient = left.checked_div(right)?;
+    if left % right != 0 && (left < 0) != (right < 0) {
+        return Some(quotient - 1);
+    }
+    return Some(quotient);
+}
+
+fn int_mod(left: i64, right: i64) -> i64 {
+    let remainder = left.wrapping_rem(right);
+    if remainder != 0 && (remainder < 0) != (right < 0) {
+        return remainder + right;
+    }
+    return remainder;
+}
+
+fn float_mod(left: f64, right: f64) -> f64 {
+    let remainder = left % right;
+    if remainder != 0.0 && (remainder < 0.0) != (right < 0.0) {
+        return remainder + right;
+    }
+    return remainder;
+}
+
+/* Arithmetic on ints, which go on as BigInts where the result doesn't fit
+ * in 64 bits
+ */
+fn int_binary_op(op: BinOp, left: i64, right: i64) -> EvalResult {
+    let promoted = || big_binary_op(op, &BigInt::from(left), &BigInt::from(right));
+    match op {
+        BinOp::Add => return left.checked_add(right).map(Value::Int).map_or_else(promoted, Ok),
+        BinOp::Sub => return left.checked_sub(right).map(Value::Int).map_or_else(promoted, Ok),
+        BinOp::Mul => return left.checked_mul(right).map(Value::Int).map_or_else(promoted, Ok),
+        BinOp::Div => {
+            if right == 0 {
+                return Err(Exception::new("ZeroDivisionError", "division by zero"));
+            }
+            return Ok(Value::Float(left as f64 / right as f64));
+        }
+        BinOp::FloorDiv | BinOp::Mod if right == 0 => {
+            return Err(Exception::new(
+                "ZeroDivisionError",
+                "integer division or modulo by zero",
+            ));
+        }
+        BinOp::FloorDiv => return int_floor_div(left, right).map(Value::Int).map_or_else(promoted, Ok),
+        BinOp::Mod => return Ok(Value::Int(int_mod(left, right))),
+        BinOp::Pow => {
+            if right < 0 {
+                if left == 0 {
+                    return Err(Exception::new(
+                        "ZeroDivisionError",
+                        "0.0 cannot be raised to a negative power",
+                    ));
+                }
+                return Ok(Value::Float((left as f64).powf(right as f64)));
+            }
+            let power = u32::try_from(right).ok().and_then(|exponent| left.checked_pow(exponent));
+            return power.map(Value::Int).map_or_else(promoted, Ok);
+        }
+        BinOp::BitAnd => return Ok(Value::Int(left & right)),
+        BinOp::BitOr => return Ok(Value::Int(left | right)),
+        BinOp::BitXor => return Ok(Value::Int(left ^ right)),
+        BinOp::LShift | BinOp::RShift if right < 0 => {
+            return Err(Exception::new("ValueError", "negative shift count"));
+        }
+        BinOp::LShift => {
+            // shifting 0 never overflows; anything else must shift back
+            if left == 0 {
+                return Ok(Value::Int(0));
+            }
+            let shifted = (right < 64).then(|| left << right).filter(|val| val >> right == left);
+            return shifted.map(Value::Int).map_or_else(promoted, Ok);
+        }
+        BinOp::RShift => return Ok(Value::Int(left >> right.min(63))),
+    }
+}
+
+fn big_binary_op(op: BinOp, left: &BigInt, right: &BigInt) -> EvalResult {
+    match op {
+        BinOp::Add => return Ok(new_int(left + right)),
+        BinOp::Sub => return Ok(new_int(left - right)),
+        BinOp::Mul => return Ok(new_int(left * right)),
+        BinOp::Div => {
+            if right.is_zero() {
+                return Err(Exception::new("ZeroDivisionError", "division by zero"));
+            }
+            // the quotient is rounded once, from its exact value
+            let quotient = fractions::to_f64(&BigRational::new(left.clone(), right.clone()));
+            if quotient.is_infinite() {
+                return Err(Exception::new("OverflowError", "integer division result too large for a float"));
+            }
+            return Ok(Value::Float(quotient));
+        }
+        BinOp::FloorDiv | BinOp::Mod if right.is_zero() => {
+            return Err(Exception::new(
+                "ZeroDivisionError",
+                "integer division or modulo by zero",
+            ));
+        }
+        BinOp::FloorDiv => return Ok(new_int(left.div_floor(right))),
+        BinOp::Mod => return Ok(new_int(left.mod_floor(right))),
+        BinOp::Pow => {
+            if right.is_negative() {
+                if left.is_zero() {
+                    return Err(Exception::new(
+                        "ZeroDivisionError",
+                        "0.0 cannot be raised to a negative power",
+                    ));
+                }
+                return float_binary_op(op, int_to_float(left)?, right.to_f64().unwrap_or(f64::NEG_INFINITY));
+            }
+            match right.to_u32() {
+                Some(exponent) => return Ok(new_int(Pow::pow(left, exponent))),
+                // only 0, 1 and -1 have powers that big that fit in memory
+                None if left.is_zero() || left.is_one() => return Ok(new_int(left.clone())),
+                None if *left == -BigInt::one() => return Ok(Value::Int(if right.is_even() { 1 } else { -1 })),
+                None => return Err(Exception::new("MemoryError", "")),
+            }
+        }
+        BinOp::BitAnd => return Ok(new_int(left & right)),
+        BinOp::BitOr => return Ok(new_int(left | right)),
+        BinOp::BitXor => return Ok(new_int(left ^ right)),
+        BinOp::LShift | BinOp::RShift if right.is_negative() => {
+            return Err(Exception::new("ValueError", "negative shift count"));
+        }
+        BinOp::LShift => match right.to_usize() {
+            _ if left.is_zero() => return Ok(Value::Int(0)),
+            Some(shift) => return Ok(new_int(left << shift)),
+            None => return Err(Exception::new("OverflowError", "too many digits in integer")),
+        },
+        // shifting right rounds down, so a negative int ends up at -1
+        BinOp::RShift => match right.to_usize() {
+            Some(shift) => return Ok(new_int(left >> shift)),
+            None => return Ok(Value::Int(if left.is_negative() { -1 } else { 0 })),
+        },
+    }
+}
+
+fn float_binary_op(op: BinOp, left: f64, right: f64) -> EvalResult {
+    match op {
+        BinOp::Add => return Ok(Value::Float(left + right)),
+        BinOp::Sub => return Ok(Value::Float(left - right)),
+        BinOp::Mul => return Ok(Value::Float(left * right)),
+        BinOp::Div | BinOp::FloorDiv | BinOp::Mod if right == 0.0 => {
+            let message = match op {
+                BinOp::Div => "float division by zero",
+                BinOp::FloorDiv => "float floor division by zero",
+                _ => "float modulo",
+            };
+            return Err(Exception::new("ZeroDivisionError", message));
+        }
+        BinOp::Div => return Ok(Value::Float(left / right)),
+        BinOp::FloorDiv => return Ok(Value::Float((left / right).floor())),
+        BinOp::Mod => return Ok(Value::Float(float_mod(left, right))),
+        BinOp::Pow => {
+            if left == 0.0 && right < 0.0 {
+                return Err(Exception::new(
+                    "ZeroDivisionError",
+                    "0.0 cannot be raised to a negative power",
+                ));
+            }
+            if left < 0.0 && right.fract() != 0.0 {
+                // the result is complex
+                return complex_binary_op(op, (left, 0.0), (right, 0.0));
+            }
+            let result = left.powf(right);
+            if result.is_infinite() && left.is_finite() && right.is_finite() {
+                return Err(Exception::new("OverflowError", "(34, 'Numerical result out of range')"));
+            }
+            return Ok(Value::Float(result));
+        }
+        BinOp::BitAnd | BinOp::BitOr | BinOp::BitXor | BinOp::LShift | BinOp::RShift => unreachable!(),
+    }
+}
+
+/* Arithmetic on complex numbers, with ints and floats converted to complex
+ * first. Floor division and modulo aren't defined for complex numbers.
+ */
+fn complex_binary_op(op: BinOp, left: Complex, right: Complex) -> EvalResult {
+    let (a, b) = (left, right);
+    match op {
+        BinOp::Add => return Ok(Value::Complex(a.0 + b.0, a.1 + b.1)),
+        BinOp::Sub => return Ok(Value::Complex(a.0 - b.0, a.1 - b.1)),
+        BinOp::Mul => {
+            let (real, imag) = complex_mul(a, b);
+            return Ok(Value::Complex(real, imag));
+        }
+        BinOp::Div => match quotient(a, b) {
+            Some((real, imag)) => return Ok(Value::Complex(real, imag)),
+            None => return Err(Exception::new("ZeroDivisionError", "complex division by zero")),
+        },
+        BinOp::Pow => {
+            // small integer exponents are done by repeated multiplication,
+            // which is faster and more accurate
+            let result = if b.1 == 0.0 && b.0 == b.0.floor() && b.0.abs() <= 100.0 {
+                complex_powi(a, b.0 as i64)
+            } else {
+                complex_pow(a, b)
+            };
+            let (real, imag) = result.ok_or_else(|| {
+                return Exception::new("ZeroDivisionError", "0.0 to a negative or complex power");
+            })?;
+            if real.is_infinite() || imag.is_infinite() {
+                return Err(Exception::new("OverflowError", "complex exponentiation"));
+            }
+            return Ok(Value::Complex(real, imag));
+        }
+        BinOp::FloorDiv | BinOp::Mod => unreachable!(),
+        BinOp::BitAnd | BinOp::BitOr | BinOp::BitXor | BinOp::LShift | BinOp::RShift => unreachable!(),
+    }
+}
+
+fn complex_mul(a: Complex, b: Complex) -> Complex {
+    return (a.0 * b.0 - a.1 * b.1, a.0 * b.1 + a.1 * b.0);
+}
+
+/* a ** n by squaring; None for zero to a negative power
+ */
+fn complex_powi(a: Complex, n: i64) -> Option<Complex> {
+    let mut result = (1.0, 0.0);
+    let mut square = a;
+    let mut remaining = n.unsigned_abs();
+    while remaining > 0 {
+        if remaining & 1 == 1 {
+            result = complex_mul(result, square);
+        }
+        remaining >>= 1;
+        square = complex_mul(square, square);
+    }
+    if n < 0 {
+        return quotient((1.0, 0.0), result);
+    }
+    return Some(result);
+}
+
+/* a ** b in polar form; None for zero to a negative or complex power
+ */
+fn complex_pow(a: Complex, b: Complex) -> Option<Complex> {
+    if b == (0.0, 0.0) {
+        return Some((1.0, 0.0));
+    }
+    if a == (0.0, 0.0) {
+        if b.1 != 0.0 || b.0 < 0.0 {
+            return None;
+        }
+        return Some((0.0, 0.0));
+    }
+    let magnitude = a.0.hypot(a.1);
+    let mut len = magnitude.powf(b.0);
+    let angle = a.1.atan2(a.0);
+    let mut phase = angle * b.0;
+    if b.1 != 0.0 {
+        len /= (angle * b.1).exp();
+        phase += b.1 * magnitude.ln();
+    }
+    return Some((len * phase.cos(), len * phase.sin()));
+}
+
+/* The length of the string or bytes a binary operation would create,
+ * worked out without creating it
+ */
+pub fn str_result_len(op: BinOp, left: &Value, right: &Value) -> Option<usize> {
+    match (op, left, right) {
+        (BinOp::Add, Value::Str(left), Value::Str(right)) => return Some(left.len() + right.len()),
+        (BinOp::Mul, Value::Str(val), count) | (BinOp::Mul, count, Value::Str(val)) => {
+            let count = as_int(count)?.max(0) as usize;
+            return Some(val.len().saturating_mul(count));
+        }
+        (BinOp::Add, Value::Bytes(_) | Value::ByteArray(_), _) => {
+            return Some(bytes::byte_len(left)? + bytes::byte_len(right)?);
+        }
+        (BinOp::Mul, data @ (Value::Bytes(_) | Value::ByteArray(_)), count)
+        | (BinOp::Mul, count, data @ (Value::Bytes(_) | Value::ByteArray(_))) => {
+            let count = as_int(count)?.max(0) as usize;
+            return Some(bytes::byte_len(data)?.saturating_mul(count));
+        }
+        _ => return None,
+    }
+}
+
+/* The bytes an int result of left op right may take, where it could be
+ * bigger than 64 bits, worked out before building it
+ */
+pub fn int_result_len(op: BinOp, left: &Value, right: &Value) -> Option<usize> {
+    let bits = |value: &Value| match value {
+        Value::BigInt(val) => return Some(val.bits()),
+        _ => return as_int(value).map(|val| 64 - val.unsigned_abs().leading_zeros() as u64),
+    };
+    // a count too big for u64 is past any memory there is anyway
+    let count = || match right {
+        Value::BigInt(val) if val.is_positive() => return Some(u64::MAX),
+        _ => return u64::try_from(as_int(right)?).ok(),
+    };
+    let left_bits = bits(left)?;
+    let result_bits = match op {
+        BinOp::Mul => left_bits + bits(right)?,
+        // 0, 1 and -1 stay the same size however many times they're multiplied
+        BinOp::Pow if left_bits > 1 => left_bits.saturating_mul(count()?),
+        BinOp::LShift if left_bits > 0 => left_bits.saturating_add(count()?),
+        _ => return None,
+    };
+    if result_bits <= 64 {
+        return None;
+    }
+    return Some(usize::try_from(result_bits / 8 + 1).unwrap_or(usize::MAX));
+}
+
+/* Shared by the interpreter and the optimizer's constant folding, so folded
+ * results are exactly what evaluating at runtime would give.
+ */
+pub fn binary_op(op: BinOp, left: &Value, right: &Value) -> EvalResult {
+    if let (BinOp::BitAnd | BinOp::BitOr | BinOp::BitXor, Value::Bool(a), Value::Bool(b)) = (op, left, right) {
+        // bool & bool stays a bool
+        return Ok(Value::Bool(match op {
+            BinOp::BitAnd => a & b,
+            BinOp::BitOr => a | b,
+            _ => a ^ b,
+        }));
+    }
+    if let (Some(left), Some(right)) = (as_int(left), as_int(right)) {
+        return int_binary_op(op, left, right);
+    }
+    if is_big(left) || is_big(right) {
+        if let (Some(left), Some(right)) = (as_bigint(left), as_bigint(right)) {
+            return big_binary_op(op, &left, &right);
+        }
+    }
+    // with a float or complex, an int becomes a float, which it may be too
+    // big for
+    if let (Value::BigInt(val), Value::Float(_) | Value::Complex(..))
+    | (Value::Float(_) | Value::Complex(..), Value::BigInt(val)) = (left, right)
+    {
+        int_to_float(val)?;
+    }
+    if let (Some(left), Some(right)) = (as_float(left), as_float(right)) {
+        if !op.is_bitwise() {
+            return float_binary_op(op, left, right);
+        }
+    }
+    if let (Some(left), Some(right)) = (as_complex(left), as_complex(right)) {
+        if !matches!(op, BinOp::FloorDiv | BinOp::Mod) && !op.is_bitwise() {
+            return complex_binary_op(op, left, right);
+        }
+    }
+    if is_exact(left) || is_exact(right) {
+        if let Some(result) = decimal::binary_op(op, left, right).or_else(|| fractions::binary_op(op, left, right)) {
+            return result;
+        }
+    }
+    match (op, left, right) {
+        (BinOp::Add, Value::Str(left), Value::Str(right)) => {
+            return Ok(Value::Str(format!("{}{}", left, right)));
+        }
+        // too many repeats to fit in memory, or none
+        (
+            BinOp::Mul,
+            items @ (Value::Str(_) | Value::Bytes(_) | Value::ByteArray(_) | Value::List(_) | Value::Tuple(_)),
+            count @ Value::BigInt(val),
+        )
+        | (
+            BinOp::Mul,
+            count @ Value::BigInt(val),
+            items @ (Value::Str(_) | Value::Bytes(_) | Value::ByteArray(_) | Value::List(_) | Value::Tuple(_)),
+        ) => {
+            if val.is_negative() {
+                return binary_op(op, items, &Value::Int(0));
+            }
+            return Err(not_an_int(count));
+        }
+        (BinOp::Mul, Value::Str(val), count) | (BinOp::Mul, count, Value::Str(val))
+            if as_int(count).is_some() =>
+        {
+            return strings::repeat(val, as_int(count).unwrap());
+        }
+        (BinOp::Mod, Value::Str(format), args) => return format::percent_format(None, format, args),
+        (BinOp::Add, Value::Bytes(_) | Value::ByteArray(_), _) => return bytes::concat(left, right),
+        (BinOp::Mul, data @ (Value::Bytes(_) | Value::ByteArray(_)), count)
+        | (BinOp::Mul, count, data @ (Value::Bytes(_) | Value::ByteArray(_)))
+            if as_int(count).is_some() =>
+        {
+            return bytes::repeat(data, as_int(count).unwrap());
+        }
+        (BinOp::Add, Value::List(_), _) | (BinOp::Add, Value::Tuple(_), _) => return containers::concat(left, right),
+        (BinOp::Mul, items @ (Value::List(_) | Value::Tuple(_)), count)
+        | (BinOp::Mul, count, items @ (Value::List(_) | Value::Tuple(_))) => match as_int(count) {
+            Some(count) => return containers::repeat(items, count),
+            None => {
+                let message = format!("can't multiply sequence by non-int of type '{}'", count.type_name());
+                return Err(type_error(message));
+            }
+        },
+        (BinOp::BitOr, Value::Dict(left), Value::Dict(right)) => return containers::merge(left, right),
+        (
+            BinOp::BitOr | BinOp::BitAnd | BinOp::Sub | BinOp::BitXor,
+            Value::Set(_) | Value::FrozenSet(_),
+            Value::Set(_) | Value::FrozenSet(_),
+        ) => return containers::set_op(op, left, right),
+        _ => {
+            return Err(type_error(format!(
+                "unsupported operand type(s) for {}: '{}' and '{}'",
+                op_symbol(op),
+                left.type_name(),
+                right.type_name()
+            )));
+        }
+    }
+}
+
+pub fn unary_op(op: UnaryOp, operand: &Value) -> EvalResult {
+    match (op, operand) {
+        (UnaryOp::Not, _) => return Ok(Value::Bool(!operand.is_truthy())),
+        (UnaryOp::Neg, Value::Float(val)) => return Ok(Value::Float(-val)),
+        (UnaryOp::Pos, Value::Float(val)) => return Ok(Value::Float(*val)),
+        (UnaryOp::Neg, Value::Complex(real, imag)) => return Ok(Value::Complex(-real, -imag)),
+        (UnaryOp::Pos, Value::Complex(real, imag)) => return Ok(Value::Complex(*real, *imag)),
+        (UnaryOp::Neg, Value::Fraction(val)) => return Ok(fractions::new_fraction(-&**val)),
+        (UnaryOp::Pos, Value::Fraction(_)) => return Ok(operand.clone()),
+        (UnaryOp::Neg | UnaryOp::Pos, Value::Decimal(_)) => return decimal::unary_op(op, operand),
+        _ => (),
+    };
+    if let Value::BigInt(val) = operand {
+        match op {
+            UnaryOp::Neg => return Ok(new_int(-&**val)),
+            UnaryOp::Pos => return Ok(operand.clone()),
+            _ => return Ok(new_int(!&**val)),
+        }
+    }
+    match (op, as_int(operand)) {
+        (UnaryOp::Neg, Some(val)) => {
+            return Ok(val.checked_neg().map_or_else(|| new_int(-BigInt::from(val)), Value::Int));
+        }
+        (UnaryOp::Pos, Some(val)) => return Ok(Value::Int(val)),
+        (UnaryOp::Invert, Some(val)) => return Ok(Value::Int(!val)),
+        _ => {
+            let symbol = match op {
+                UnaryOp::Neg => "-",
+                UnaryOp::Invert => "~",
+                _ => "+",
+            };
+            return Err(type_error(format!(
+                "bad operand type for unary {}: '{}'",
+                symbol,
+                operand.type_name()
+            )));
+        }
+    }
+}
+
+// how deeply containers may be nested in a comparison, like CPython's
+// default recursion limit
+const MAX_COMPARE_DEPTH: usize = 1000;
+
+thread_local! {
+    // how deeply the containers being compared are nested, and whether a
+    // comparison went deeper than MAX_COMPARE_DEPTH
+    static COMPARE_DEPTH: Cell<usize> = const { Cell::new(0) };
+    static COMPARE_TOO_DEEP: Cell<bool> = const { Cell::new(false) };
+}
+
+fn compare_recursion_error() -> Exception {
+    return Exception::new("RecursionError", "maximum recursion depth exceeded in comparison");
+}
+
+/* Compare the items of two containers, one level deeper. Past
+ * MAX_COMPARE_DEPTH (e.g. lists that contain themselves) it gives
+ * too_deep instead, and compare() raises RecursionError once the
+ * outermost comparison is done.
+ */
+fn compare_items<R>(too_deep: R, compare: impl FnOnce() -> R) -> R {
+    let depth = COMPARE_DEPTH.with(Cell::get);
+    if depth >= MAX_COMPARE_DEPTH {
+        COMPARE_TOO_DEEP.with(|too_deep| too_deep.set(true));
+        return too_deep;
+    }
+    COMPARE_DEPTH.with(|nesting| nesting.set(depth + 1));
+    let result = limits::with_stack(compare);
+    COMPARE_DEPTH.with(|nesting| nesting.set(depth));
+    return result;
+}
+
+/* RecursionError if a comparison that has finished went too deep
+ */
+pub(crate) fn check_compare_depth() -> Result<(), Exception> {
+    if COMPARE_DEPTH.with(Cell::get) == 0 && COMPARE_TOO_DEEP.with(|too_deep| too_deep.replace(false)) {
+        return Err(compare_recursion_error());
+    }
+    return Ok(());
+}
+
+/* == between items of containers: the same object is always equal to
+ * itself, as in CPython
+ */
+pub fn items_equal(left: &Value, right: &Value) -> bool {
+    return (left.identity() != 0 && left.identity() == right.identity()) || equals(left, right);
+}
+
+pub fn equals(left: &Value, right: &Value) -> bool {
+    // a float can't hold every int this big, so they compare exactly
+    if is_big(left) || is_big(right) {
+        if let Some(result) = fractions::equals(left, right) {
+            return result;
+        }
+    }
+    if let (Some(left), Some(right)) = (as_float(left), as_float(right)) {
+        return left == right;
+    }
+    if let (Some(left), Some(right)) = (as_complex(left), as_complex(right)) {
+        return left == right;
+    }
+    if is_exact(left) || is_exact(right) {
+        if let Some(result) = decimal::equals(left, right).or_else(|| fractions::equals(left, right)) {
+            return result;
+        }
+    }
+    if let Some(result) = bytes::equals(left, right) {
+        return result;
+    }
+    // native instances may compare by value, with either on the left
+    let native = || native_equals(left, right).or_else(|| native_equals(right, left));
+    if let Some(result) = compare_items(Some(false), native) {
+        return result;
+    }
+    match (left, right) {
+        (Value::List(left), Value::List(right)) => {
+            return compare_items(false, || sequence_equals(&left.borrow(), &right.borrow()));
+        }
+        (Value::Tuple(left), Value::Tuple(right)) => return compare_items(false, || sequence_equals(left, right)),
+        (Value::Dict(left), Value::Dict(right)) => {
+            let (left, right) = (left.borrow(), right.borrow());
+            return left.len() == right.len()
+                && compare_items(false, || {
+                    left.items().iter().all(|(key, value)| match right.get(key) {
+                        Ok(Some(other)) => items_equal(value, &other),
+                        _ => false,
+                    })
+                });
+        }
+        (Value::Set(left) | Value::FrozenSet(left), Value::Set(right) | Value::FrozenSet(right)) => {
+            return containers::set_equals(&left.borrow(), &right.borrow());
+        }
+        (Value::Range(..), Value::Range(..)) => {
+            // ranges are equal if they give the same sequence
+            let items = |value: &Value| match value {
+                Value::Range(start, stop, step) => {
+                    let len = range_len(*start, *stop, *step);
+                    (len, if len > 0 { *start } else { 0 }, if len > 1 { *step } else { 0 })
+                }
+                _ => unreachable!(),
+            };
+            return items(left) == items(right);
+        }
+        _ => return left == right,
+    }
+}
+
+fn native_equals(left: &Value, right: &Value) -> Option<bool> {
+    match left {
+        Value::Object(object) => return object.native_eq(right),
+        _ => return None,
+    }
+}
+
+fn sequence_equals(left: &[Value], right: &[Value]) -> bool {
+    return left.len() == right.len() && left.iter().zip(right).all(|(left, right)| items_equal(left, right));
+}
+
+fn identical(left: &Value, right: &Value) -> bool {
+    if left.identity() != 0 || right.identity() != 0 {
+        return left.identity() == right.identity();
+    }
+    // immutable scalars have no identity yet: same type and equal
+    return left.type_name() == right.type_name() && left == right;
+}
+
+/* `item in container`, for containers that can be searched without running
+ * code
+ */
+pub fn contains(container: &Value, item: &Value) -> Result<bool, Exception> {
+    match container {
+        Value::Str(val) => match item {
+            Value::Str(item) => return Ok(val.contains(item.as_str())),
+            _ => {
+                return Err(type_error(format!(
+                    "'in <string>' requires string as left operand, not {}",
+                    item.type_name()
+                )));
+            }
+        },
+        Value::List(_) | Value::Tuple(_) => {
+            let found = match container {
+                Value::List(items) => items.borrow().iter().any(|other| items_equal(other, item)),
+                Value::Tuple(items) => items.iter().any(|other| items_equal(other, item)),
+                _ => unreachable!(),
+            };
+            check_compare_depth()?;
+            return Ok(found);
+        }
+        Value::Dict(dict) => return Ok(dict.borrow().get(item)?.is_some()),
+        Value::Set(items) | Value::FrozenSet(items) => return Ok(items.borrow().get(&containers::set_key(item))?.is_some()),
+        Value::Bytes(_) | Value::ByteArray(_) => return bytes::contains(&bytes::contents(container).unwrap(), item),
+        Value::MemoryView(_) => {
+            let data = bytes::buffer(container)?;
+            return Ok(as_int(item).is_some_and(|val| data.iter().any(|byte| *byte as i64 == val)));
+        }
+        Value::Range(start, stop, step) => {
+            let value = match (as_int(item), item) {
+                (Some(value), _) => value,
+                (None, Value::Float(val)) if val.fract() == 0.0 => *val as i64,
+                _ => return Ok(false),
+            };
+            let in_bounds = if *step > 0 { *start <= value && value < *stop } else { *stop < value && value <= *start };
+            return Ok(in_bounds && (value - start) % step == 0);
+        }
+        _ => {
+            return Err(type_error(format!(
+                "argument of type '{}' is not iterable",
+                container.type_name()
+            )));
+        }
+    }
+}
+
+/* lexicographic ordering of lists and tuples
+ */
+fn compare_sequences(op: CmpOp, left: &[Value], right: &[Value]) -> Result<bool, Exception> {
+    return compare_items(Err(compare_recursion_error()), || {
+        for (left, right) in left.iter().zip(right) {
+            if !items_equal(left, right) {
+                return compare_values(op, left, right);
+            }
+        }
+        return Ok(apply_ordering(op, left.len().cmp(&right.len())));
+    });
+}
+
+fn apply_ordering(op: CmpOp, ordering: std::cmp::Ordering) -> bool {
+    match op {
+        CmpOp::Lt => return ordering.is_lt(),
+        CmpOp::LtE => return ordering.is_le(),
+        CmpOp::Gt => return ordering.is_gt(),
+        _ => return ordering.is_ge(),
+    }
+}
+
+fn unorderable(op: CmpOp, left: &Value, right: &Value) -> Exception {
+    let symbol = match op {
+        CmpOp::Lt => "<",
+        CmpOp::LtE => "<=",
+        CmpOp::Gt => ">",
+        _ => ">=",
+    };
+    return type_error(format!(
+        "'{}' not supported between instances of '{}' and '{}'",
+        symbol,
+        left.type_name(),
+        right.type_name()
+    ));
+}
+
+pub fn compare(op: CmpOp, left: &Value, right: &Value) -> Result<bool, Exception> {
+    let result = compare_values(op, left, right);
+    check_compare_depth()?;
+    return result;
+}
+
+fn compare_values(op: CmpOp, left: &Value, right: &Value) -> Result<bool, Exception> {
+    match op {
+        CmpOp::Eq => return Ok(equals(left, right)),
+        CmpOp::NotEq => return Ok(!equals(left, right)),
+        CmpOp::Is => return Ok(identical(left, right)),
+        CmpOp::IsNot => return Ok(!identical(left, right)),
+        CmpOp::In => return contains(right, left),
+        CmpOp::NotIn => return Ok(!contains(right, left)?),
+        _ => (),
+    };
+    let ordering = match (left, right) {
+        (Value::Str(left), Value::Str(right)) => left.partial_cmp(right),
+        (Value::Bytes(_) | Value::ByteArray(_), Value::Bytes(_) | Value::ByteArray(_)) => {
+            bytes::contents(left).partial_cmp(&bytes::contents(right))
+        }
+        (Value::List(left), Value::List(right)) => return compare_sequences(op, &left.borrow(), &right.borrow()),
+        (Value::Tuple(left), Value::Tuple(right)) => return compare_sequences(op, left, right),
+        (Value::Set(left) | Value::FrozenSet(left), Value::Set(right) | Value::FrozenSet(right)) => {
+            return Ok(containers::set_compare(op, &left.borrow(), &right.borrow()));
+        }
+        _ if is_exact(left) || is_exact(right) || is_big(left) || is_big(right) => {
+            let ordering = match decimal::partial_cmp(left, right) {
+                Some(ordering) => Some(ordering?),
+                None => fractions::partial_cmp(left, right),
+            };
+            ordering.ok_or_else(|| unorderable(op, left, right))?
+        }
+        _ => match (as_float(left), as_float(right)) {
+            (Some(left), Some(right)) => left.partial_cmp(&right),
+            _ => return Err(unorderable(op, left, right)),
+        },
+    };
+    // NaN compares false with everything
+    match ordering {
+        Some(ordering) => return Ok(apply_ordering(op, ordering)),
+        None => return Ok(false),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testing::{error, eval, eval_str, exec, python, traceback};
+
+    // the last line of a traceback, "Kind: message"
+    fn raises(source: &str) -> String {
+        let mut python = python("");
+        return traceback(&mut python, source).lines().last().unwrap().to_string();
+    }
+
+    #[test]
+    fn test_assign() {
+        let mut python = python("x = y = [1]; a, (b, c) = 1, (2, 3)");
+        assert_eq!(eval(&mut python, "x is y"), "True");
+        assert_eq!(eval(&mut python, "(a, b, c)"), "(1, 2, 3)");
+        exec(&mut python, "x[0] = x[1:] = [4, 5]");
+        assert_eq!(eval(&mut python, "y"), "[[4, 5], 4, 5]");
+    }
+
+    #[test]
+    fn test_aug_assign() {
+        let mut python = python("x = y = [1]; x += [2]; s = t = 'a'; s *= 3");
+        assert_eq!(eval(&mut python, "y"), "[1, 2]");
+        assert_eq!(eval(&mut python, "(s, t)"), "('aaa', 'a')");
+        exec(&mut python, "b = bytearray(b'ab'); c = b; b += b'c'; n = 7; n //= 2; n <<= 3");
+        assert_eq!(eval(&mut python, "(c, n)"), "(bytearray(b'abc'), 24)");
+        exec(&mut python, "d = {'k': [0]}; d['k'][0] -= 5; d['k'] += [1]");
+        assert_eq!(eval(&mut python, "d"), "{'k': [-5, 1]}");
+        assert_eq!(raises("z += 1"), "NameError: name 'z' is not defined");
+    }
+
+    #[test]
+    fn test_try() {
+        let mut python = python(
+            "
+log = []
+def f(x):
+    global log
+    try:
+        log += ['try']
+        return 10 // x
+    except ZeroDivisionError as e:
+        log += [repr(e)]
+        return -1
+    else:
+        log += ['else']
+    finally:
+        log += ['finally']
+def g():
+    global log
+    for i in range(3):
+        try:
+            if i == 1:
+                break
+        finally:
+            log += [i]
+    try:
+        raise ValueError
+    finally:
+        return 'dropped'
+",
+        );
+        assert_eq!(eval(&mut python, "(f(2), f(0), log)"), "(5, -1, ['try', 'finally', 'try', \"ZeroDivisionError('integer division or modulo by zero')\", 'finally'])");
+        exec(&mut python, "log = []");
+        assert_eq!(eval(&mut python, "(g(), log)"), "('dropped', [0, 1])");
+        exec(&mut python, "try:\n    x = 1\nexcept:\n    x = 2\nelse:\n    x += 10");
+        assert_eq!(eval(&mut python, "x"), "11");
+        // the name is unbound after the handler
+        exec(&mut python, "try:\n    1 / 0\nexcept Exception as e:\n    x = str(e)");
+        assert_eq!(eval(&mut python, "x"), "'division by zero'");
+        assert_eq!(error(&mut python, "e"), "NameError: name 'e' is not defined");
+        assert_eq!(raises("try:\n    int('a')\nexcept (KeyError, IndexError):\n    pass"), "ValueError: invalid literal for int() with base 10: 'a'");
+        assert_eq!(raises("try:\n    int('a')\nexcept 5:\n    pass"), "TypeError: catching classes that do not inherit from BaseException is not allowed");
+    }
+
+    #[test]
+    fn test_raise() {
+        let mut python = python("");
+        exec(&mut python, "try:\n    {}['k']\nexcept (IndexError, KeyError) as e:\n    caught = e");
+        assert_eq!(eval(&mut python, "(repr(caught), str(caught), caught.args, isinstance(caught, LookupError))"), "(\"KeyError('k')\", \"'k'\", ('k',), True)");
+        assert_eq!(raises("raise ValueError"), "ValueError");
+        assert_eq!(raises("raise ValueError('bad', 1)"), "ValueError: ('bad', 1)");
+        assert_eq!(raises("raise KeyError('k')"), "KeyError: 'k'");
+        assert!(traceback(&mut python, "raise caught").ends_with("\nKeyError: 'k'"));
+        assert_eq!(raises("raise 5"), "TypeError: exceptions must derive from BaseException");
+        assert_eq!(raises("raise ValueError from 5"), "TypeError: exception causes must derive from BaseException");
+        assert_eq!(raises("raise"), "RuntimeError: No active exception to reraise");
+        assert_eq!(raises("try:\n    1 / 0\nexcept ZeroDivisionError:\n    raise"), "ZeroDivisionError: division by zero");
+        assert_eq!(eval(&mut python, "(str(OSError(2, 'gone', 'f')), OSError(2, 'gone', 'f').args, repr(OSError()))"), "(\"[Errno 2] gone: 'f'\", (2, 'gone'), 'OSError()')");
+    }
+
+    #[test]
+    fn test_exception_chaining() {
+        let mut python = python("");
+        exec(&mut python, "try:\n    try:\n        int('x')\n    except ValueError as e:\n        raise KeyError(1) from e\nexcept KeyError as e:\n    chained = e");
+        assert_eq!(eval(&mut python, "(repr(chained.__cause__), chained.__suppress_context__)"), "('ValueError(\"invalid literal for int() with base 10: \\'x\\'\")', True)");
+        exec(&mut python, "try:\n    try:\n        1 / 0\n    except ZeroDivisionError:\n        [][0]\nexcept IndexError as e:\n    chained = e");
+        assert_eq!(eval(&mut python, "(repr(chained.__context__), chained.__cause__)"), "(\"ZeroDivisionError('division by zero')\", None)");
+        let source = "
+def inner():
+    return {}['x']
+def outer():
+    try:
+        inner()
+    except KeyError as e:
+        raise ValueError('converted') from e
+outer()
+";
+        let expected = "\
+Traceback (most recent call last):
+  File \"<stdin>\", line 6, in outer
+  File \"<stdin>\", line 3, in inner
+KeyError: 'x'
+
+The above exception was the direct cause of the following exception:
+
+Traceback (most recent call last):
+  File \"<stdin>\", line 9, in <module>
+  File \"<stdin>\", line 8, in outer
+ValueError: converted";
+        assert_eq!(traceback(&mut python, source), expected);
+        let source = "try:\n    raise KeyError('a')\nfinally:\n    raise TypeError('t')\n";
+        let expected = "\
+Traceback (most recent call last):
+  File \"<stdin>\", line 2, in <module>
+KeyError: 'a'
+
+During handling of the above exception, another exception occurred:
+
+Traceback (most recent call last):
+  File \"<stdin>\", line 4, in <module>
+TypeError: t";
+        assert_eq!(traceback(&mut python, source), expected);
+    }
+
+    #[test]
+    fn test_exception_classes() {
+        let mut python = python("import decimal, json");
+        assert_eq!(eval(&mut python, "(issubclass(KeyError, LookupError), issubclass(KeyboardInterrupt, Exception), IOError)"), "(True, False, <class 'OSError'>)");
+        assert_eq!(eval(&mut python, "(issubclass(decimal.DivisionByZero, ZeroDivisionError), issubclass(json.JSONDecodeError, ValueError))"), "(True, True)");
+        exec(&mut python, "try:\n    decimal.Decimal(1) / 0\nexcept ArithmeticError as e:\n    caught = e");
+        assert_eq!(eval(&mut python, "type(caught) is decimal.DivisionByZero"), "True");
+        exec(&mut python, "try:\n    open('/nonexistent/file')\nexcept OSError as e:\n    caught = e");
+        assert_eq!(eval(&mut python, "(type(caught), caught.errno, caught.filename)"), "(<class 'FileNotFoundError'>, 2, '/nonexistent/file')");
+    }
+
+    #[test]
+    fn test_if() {
+        let mut python = python("out = ''\nfor x in [1, 5, 10]:\n    if x < 3:\n        out += 's'\n    elif x < 7:\n        out += 'm'\n    else:\n        out += 'b'");
+        assert_eq!(eval(&mut python, "out"), "'smb'");
+    }
+
+    #[test]
+    fn test_loops() {
+        let mut python = python("i, seen = 0, ''\nwhile True:\n    i += 1\n    if i % 2:\n        continue\n    if i > 6:\n        break\n    seen += str(i)\nelse:\n    seen += 'no'");
+        assert_eq!(eval(&mut python, "seen"), "'246'");
+        exec(&mut python, "n = 0\nwhile n < 3:\n    n += 1\nelse:\n    n = 'done'\nfor x in range(3):\n    if x == 5:\n        break\nelse:\n    x = 'finished'");
+        assert_eq!(eval(&mut python, "(n, x)"), "('done', 'finished')");
+        exec(&mut python, "for a, (b, c) in [(1, (2, 3))]:\n    pass");
+        assert_eq!(eval(&mut python, "(a, b, c)"), "(1, 2, 3)");
+    }
+
+    #[test]
+    fn test_functions() {
+        let mut python = python("def f(a, b=2, *args, c, d=4, **kw):\n    return a, b, args, c, d, kw\ndef g():\n    pass\nsq = lambda x, y=1: x * y");
+        assert_eq!(eval(&mut python, "(f(1, c=3), f(1, 2, 3, 4, c=5, e=6))"), "((1, 2, (), 3, 4, {}), (1, 2, (3, 4), 5, 4, {'e': 6}))");
+        assert_eq!(eval(&mut python, "(g(), sq(3), sq(3, 3))"), "(None, 3, 9)");
+        assert_eq!(error(&mut python, "f(1)"), "TypeError: f() missing 1 required keyword-only argument: 'c'");
+        assert_eq!(error(&mut python, "g(1)"), "TypeError: g() takes 0 positional arguments but 1 was given");
+        assert_eq!(error(&mut python, "sq(1, z=2)"), "TypeError: <lambda>() got an unexpected keyword argument 'z'");
+        assert_eq!(raises("def f():\n    return x\nf()"), "NameError: name 'x' is not defined");
+    }
+
+    #[test]
+    fn test_global_nonlocal() {
+        let mut python = python("count = 0\ndef inc():\n    global count\n    count += 1\ndef counter():\n    n = 0\n    def step():\n        nonlocal n\n        n += 1\n        return n\n    return step\ninc(); inc()\nstep = counter(); step()");
+        assert_eq!(eval(&mut python, "(count, step())"), "(2, 2)");
+    }
+
+    #[test]
+    fn test_unbound_local() {
+        let mut python = python("x = 1\ndef f():\n    print(x)\n    x = 2\ndef g():\n    if False:\n        y = 1\n    return y\ndef h():\n    def inner():\n        return z\n    inner()\n    z = 1\ndef k():\n    del w\n    w = 1\ndef ok(a):\n    for i in range(2):\n        pass\n    return [a * d for d in (x, i)]");
+        assert_eq!(error(&mut python, "f()"), "UnboundLocalError: cannot access local variable 'x' where it is not associated with a value");
+        assert_eq!(error(&mut python, "g()"), "UnboundLocalError: cannot access local variable 'y' where it is not associated with a value");
+        assert_eq!(error(&mut python, "h()"), "NameError: cannot access free variable 'z' where it is not associated with a value in enclosing scope");
+        assert_eq!(error(&mut python, "k()"), "UnboundLocalError: cannot access local variable 'w' where it is not associated with a value");
+        assert_eq!(eval(&mut python, "(ok(3), x)"), "([3, 3], 1)");
+    }
+
+    #[test]
+    fn test_assert() {
+        let mut python = python("assert 1 == 1, 'unused'");
+        assert_eq!(raises("assert 1 == 2, 'nope'"), "AssertionError: nope");
+        assert_eq!(raises("assert []"), "AssertionError");
+        exec(&mut python, "try:\n    assert 0, ('a', 1)\nexcept AssertionError as e:\n    caught = e");
+        assert_eq!(eval(&mut python, "caught.args"), "(('a', 1),)");
+    }
+
+    #[test]
+    fn test_import() {
+        let mut python = python("import math, json as j\nfrom os import path as p, sep");
+        assert_eq!(eval(&mut python, "(math.pi > 3, j.dumps([1]), p.join('a', 'b'), sep)"), "(True, '[1]', 'a/b', '/')");
+        assert_eq!(raises("import nosuchmod"), "ModuleNotFoundError: No module named 'nosuchmod'");
+        assert_eq!(raises("from math import nosuch"), "ImportError: cannot import name 'nosuch' from 'math' (unknown location)");
+    }
+
+    #[test]
+    fn test_with() {
+        let mut python = python("out = ''\nclass CM:\n    def __enter__(self):\n        global out\n        out += '<'\n        return 42\n    def __exit__(self, t, v, tb):\n        global out\n        out += '>' + (t.__name__ if t else '')\n        return True\nwith CM() as v, CM():\n    out += str(v)\n    raise ValueError");
+        assert_eq!(eval(&mut python, "out"), "'<<42>ValueError>'");
+        assert_eq!(raises("with 1:\n    pass"), "TypeError: 'int' object does not support the context manager protocol");
+    }
+
+    #[test]
+    fn test_class() {
+        let mut python = python("class Base:\n    kind = 'base'\n    def __init__(self, x):\n        self.x = x\n    def describe(self):\n        return self.kind + ':' + str(self.x)\nclass Child(Base):\n    kind = 'child'\nc = Child(1)");
+        assert_eq!(eval(&mut python, "(c.describe(), c.x, Base.kind, isinstance(c, Base), issubclass(Child, Base))"), "('child:1', 1, 'base', True, True)");
+        assert_eq!(eval(&mut python, "(type(c), c.__class__ is Child, Child.__bases__, Child.__mro__)"), "(<class '__main__.Child'>, True, (<class '__main__.Base'>,), (<class '__main__.Child'>, <class '__main__.Base'>, <class 'object'>))");
+        exec(&mut python, "c.y = 2\nBase.extra = 3\nT = type('T', (Base,), {'kind': 't'})");
+        assert_eq!(eval(&mut python, "(c.y, c.extra, T(4).describe(), Child.__qualname__, Child.__module__)"), "(2, 3, 't:4', 'Child', '__main__')");
+        assert_eq!(error(&mut python, "Base()"), "TypeError: __init__() missing 1 required positional argument: 'x'");
+        exec(&mut python, "class Plain:\n    pass");
+        assert_eq!(error(&mut python, "Plain(1)"), "TypeError: Plain() takes no arguments");
+        assert_eq!(raises("class A(int):\n    pass"), "TypeError: subclassing the built-in type 'int' is not supported");
+        assert_eq!(raises("class A:\n    pass\nclass B(A, A):\n    pass"), "TypeError: duplicate base class A");
+        assert_eq!(raises("class A:\n    pass\nclass B(A):\n    pass\nclass C(A, B):\n    pass"), "TypeError: Cannot create a consistent method resolution order (MRO) for bases A, B");
+        assert_eq!(raises("class A:\n    def __init__(self):\n        return 1\nA()"), "TypeError: __init__() should return None, not 'int'");
+        assert!(traceback(&mut python, "class K:\n    x = 1 / 0").contains("line 2, in K"));
+    }
+
+    #[test]
+    fn test_decorators() {
+        let mut python = python("calls = []\ndef trace(name):\n    calls.append('eval ' + name)\n    def decorate(f):\n        calls.append('call ' + name)\n        return f\n    return decorate\ndef twice(f):\n    return lambda x: f(f(x))\n");
+        exec(&mut python, "@trace('a')\n@trace('b')\n@twice\ndef inc(x):\n    'add one'\n    return x + 1\n");
+        assert_eq!(eval(&mut python, "(calls, inc(1), inc.__name__)"), "(['eval a', 'eval b', 'call b', 'call a'], 3, '<lambda>')");
+        exec(&mut python, "def named(cls):\n    cls.tag = cls.__name__.lower()\n    return cls\n@named\nclass Thing:\n    pass\n");
+        assert_eq!(eval(&mut python, "(Thing.tag, type(Thing()) is Thing)"), "('thing', True)");
+        exec(&mut python, "def outer():\n    @twice\n    def inner(x):\n        return x * 3\n    return inner(2)\n");
+        assert_eq!(eval(&mut python, "outer()"), "18");
+        exec(&mut python, "def doc():\n    '''Docs.'''\ndef plain():\n    x = 'not a docstring'\n");
+        assert_eq!(eval(&mut python, "(doc.__doc__, plain.__doc__, (lambda: 'x').__doc__)"), "('Docs.', None, None)");
+        assert!(traceback(&mut python, "@undefined\ndef f():\n    pass").contains("NameError: name 'undefined' is not defined"));
+    }
+
+    #[test]
+    fn test_class_scope() {
+        let mut python = python("x = 'global'\ndef outer():\n    y = 'local'\n    class Inner:\n        x = 'class'\n        z = y\n        def get(self):\n            return x, y\n    return Inner\nInner = outer()");
+        assert_eq!(eval(&mut python, "(Inner().get(), Inner.x, Inner.z)"), "(('global', 'local'), 'class', 'local')");
+    }
+
+    #[test]
+    fn test_super() {
+        let mut python = python("class Root:\n    def hello(self):\n        return 'root'\nclass Mixin:\n    def hello(self):\n        return 'mixin ' + super().hello()\nclass Both(Mixin, Root):\n    def hello(self):\n        return 'both ' + super(Both, self).hello()");
+        assert_eq!(eval(&mut python, "(Both().hello(), [c.__name__ for c in Both.__mro__])"), "('both mixin root', ['Both', 'Mixin', 'Root', 'object'])");
+        assert_eq!(error(&mut python, "super()"), "RuntimeError: super(): no arguments");
+        assert_eq!(error(&mut python, "super(Both, Root())"), "TypeError: super(type, obj): obj must be an instance or subtype of type");
+        exec(&mut python, "def f(self):\n    return super().hello()");
+        assert_eq!(error(&mut python, "f(Both())"), "RuntimeError: super(): __class__ cell not found");
+    }
+
+    #[test]
+    fn test_special_methods() {
+        let mut python = python("class V:\n    def __init__(self, x, y):\n        self.x, self.y = x, y\n    def __add__(self, other):\n        if not isinstance(other, V):\n            return NotImplemented\n        return V(self.x + other.x, self.y + other.y)\n    def __rmul__(self, k):\n        return V(k * self.x, k * self.y)\n    def __neg__(self):\n        return V(-self.x, -self.y)\n    def __eq__(self, other):\n        return isinstance(other, V) and (self.x, self.y) == (other.x, other.y)\n    def __lt__(self, other):\n        return (self.x, self.y) < (other.x, other.y)\n    def __bool__(self):\n        return bool(self.x or self.y)\n    def __len__(self):\n        return 2\n    def __repr__(self):\n        return 'V(%d, %d)' % (self.x, self.y)\n    def __iter__(self):\n        return iter((self.x, self.y))\na = V(1, 2)");
+        assert_eq!(eval_str(&mut python, "repr((a + V(3, 4), 3 * a, -a, len(a), list(a)))"), "(V(4, 6), V(3, 6), V(-1, -2), 2, [1, 2])");
+        assert_eq!(eval(&mut python, "(a == V(1, 2), a != V(1, 2), a < V(2, 0), V(2, 0) > a, bool(V(0, 0)), not a)"), "(True, False, True, True, False, False)");
+        assert_eq!(error(&mut python, "a + 1"), "TypeError: unsupported operand type(s) for +: 'V' and 'int'");
+        exec(&mut python, "a += V(1, 1)\nb = 'yes' if V(0, 0) else 'no'");
+        assert_eq!(eval_str(&mut python, "'%s %r' % (a, [b])"), "V(2, 3) ['no']");
+        assert_eq!(eval_str(&mut python, "'{!r} {}'.format(a, [a])"), "V(2, 3) [V(2, 3)]");
+        exec(&mut python, "class Count:\n    def __init__(self, n):\n        self.i, self.n = 0, n\n    def __iter__(self):\n        return self\n    def __next__(self):\n        if self.i >= self.n:\n            raise StopIteration\n        self.i += 1\n        return self.i");
+        assert_eq!(eval(&mut python, "(list(Count(3)), [x * 2 for x in Count(2)], next(Count(5)))"), "([1, 2, 3], [2, 4], 1)");
+    }
+
+    #[test]
+    fn test_special_methods_in_builtins() {
+        let mut python = python("class P:\n    def __init__(self, x):\n        self.x = x\n    def __eq__(self, other):\n        return isinstance(other, P) and other.x == self.x\n    def __hash__(self):\n        return hash(self.x)\n    def __lt__(self, other):\n        return self.x < other.x\n    def __repr__(self):\n        return 'P(%d)' % self.x\nd = {P(1): 'a', P(1): 'b'}\nd[P(2)] = 'c'\ndel d[P(2)]\ns = {P(1), P(1)}\ns.add(P(1))");
+        assert_eq!(eval_str(&mut python, "repr((d, d[P(1)], d.get(P(1)), P(1) in d, s, P(1) in s, hash(P(3))))"), "({P(1): 'b'}, 'b', 'b', True, {P(1)}, True, 3)");
+        assert_eq!(eval_str(&mut python, "repr((sorted([P(3), P(1), P(2)]), min([P(3), P(1)]), max(P(3), P(5))))"), "([P(1), P(2), P(3)], P(1), P(5))");
+        assert_eq!(eval(&mut python, "(P(1) in [P(1)], [P(1)].index(P(1)), [P(1), P(1)].count(P(1)))"), "(True, 0, 2)");
+        exec(&mut python, "class Bad:\n    def __hash__(self):\n        return 'x'");
+        assert_eq!(error(&mut python, "hash(Bad())"), "TypeError: __hash__ method should return an integer");
+    }
+
+    #[test]
+    fn test_exception_subclass() {
+        let mut python = python("class AppError(Exception):\n    def __init__(self, code):\n        self.code = code\nclass NotFound(AppError):\n    pass\ntry:\n    raise NotFound(404)\nexcept AppError as e:\n    caught = e");
+        assert_eq!(eval_str(&mut python, "repr((caught, str(caught), caught.code, caught.args, type(caught).__name__))"), "(NotFound(404), '404', 404, (404,), 'NotFound')");
+        assert_eq!(raises("class E(ValueError):\n    pass\nraise E('bad')"), "E: bad");
+    }
+
+    #[test]
+    fn test_delete() {
+        let mut python = python("l = list(range(6)); d = {'a': 1, 'b': 2}; n = 1");
+        exec(&mut python, "del l[0], l[::2], d['a'], n");
+        assert_eq!(eval(&mut python, "(l, d)"), "([2, 4], {'b': 2})");
+        assert_eq!(error(&mut python, "n"), "NameError: name 'n' is not defined");
+        assert_eq!(raises("del n"), "NameError: name 'n' is not defined");
+        assert_eq!(raises("del (1, 2)[0]"), "TypeError: 'tuple' object doesn't support item deletion");
+        assert_eq!(raises("del [][0]"), "IndexError: list assignment index out of range");
+        assert_eq!(raises("del {}['k']"), "KeyError: 'k'");
+        assert_eq!(raises("del int.x"), "TypeError: cannot set 'x' attribute of immutable type 'int'");
+    }
+
+    #[test]
+    fn test_delete_global() {
+        let mut python = python("g = 1\ndef f():\n    global g\n    del g\nf()");
+        assert_eq!(error(&mut python, "g"), "NameError: name 'g' is not defined");
+    }
+
+    #[test]
+    fn test_recursive_containers() {
+        let mut python = python("x = [1]; x[0] = x; y = [1]; y[0] = y; d = {}; d['a'] = d; t = ([0],); t[0][0] = t");
+        assert_eq!(eval_str(&mut python, "(x, d, t)"), "([[...]], {'a': {...}}, ([(...)],))");
+        assert_eq!(eval_str(&mut python, "'%s %r {}'.format(x) % (x, d)"), "[[...]] {'a': {...}} [[...]]");
+        assert_eq!(eval(&mut python, "(x == x, x != x, x in x, [x] == [x], d == d)"), "(True, False, True, True, True)");
+        let message = "RecursionError: maximum recursion depth exceeded in comparison";
+        assert_eq!(error(&mut python, "x == y"), message);
+        assert_eq!(error(&mut python, "x < y"), message);
+        assert_eq!(error(&mut python, "sorted([x, y])"), message);
+        assert_eq!(error(&mut python, "x in [y]"), message);
+        // the error doesn't linger into the next comparison
+        assert_eq!(eval(&mut python, "[1] == [1]"), "True");
+        exec(&mut python, "n = []\nfor i in range(100000):\n    n = [n]");
+        assert_eq!(eval(&mut python, "(len(repr(n)), n == n)"), "(200002, True)");
+    }
+
+    #[test]
+    fn test_bitwise_ops() {
+        let mut python = python("");
+        assert_eq!(eval(&mut python, "(6 & 3, 6 | 3, 6 ^ 3, ~5, 1 << 62, -7 >> 100)"), "(2, 7, 5, -6, 4611686018427387904, -1)");
+        assert_eq!(eval(&mut python, "(True & False, True | 0, 1 + 2 << 1 & 7)"), "(False, 1, 6)");
+        assert_eq!(error(&mut python, "1 << -1"), "ValueError: negative shift count");
+        assert_eq!(eval(&mut python, "1 << 63"), "9223372036854775808");
+        assert_eq!(error(&mut python, "3 & 1.0"), "TypeError: unsupported operand type(s) for &: 'int' and 'float'");
+        assert_eq!(error(&mut python, "~1.5"), "TypeError: bad operand type for unary ~: 'float'");
+    }
+
+    #[test]
+    fn test_int_literals() {
+        let mut python = python("");
+        assert_eq!(eval(&mut python, "(0x1F, 0o17, 0b1010_1010, 1_000, 1_0.5, 2_0j)"), "(31, 15, 170, 1000, 10.5, 20j)");
+        assert_eq!(eval(&mut python, "(99999999999999999999, 0x1_0000_0000_0000_0000)"), "(99999999999999999999, 18446744073709551616)");
+    }
+
+    #[test]
+    fn test_big_ints() {
+        // ints that overflow 64 bits carry on exactly, as CPython's do
+        let mut python = python("a = 2 ** 100
+");
+        assert_eq!(eval(&mut python, "a"), "1267650600228229401496703205376");
+        assert_eq!(eval(&mut python, "(a - a, type(a // a), -a // 3, -a % 7)"), "(0, <class 'int'>, -422550200076076467165567735126, 5)");
+        assert_eq!(eval(&mut python, "(a * a == 2 ** 200, a >> 98, -a >> 200, a & 0xff, ~a == -a - 1)"), "(True, 4, -1, 0, True)");
+        assert_eq!(eval(&mut python, "(a / 3, 2 ** -a, 9223372036854775807 + 1, -(-9223372036854775807 - 1))"), "(4.2255020007607644e+29, 0.0, 9223372036854775808, 9223372036854775808)");
+        assert_eq!(eval(&mut python, "(a > 1e30, a == 2.0 ** 100, a < 1e300, a == 2 ** 100 + 0j, 3 < a)"), "(True, True, True, True, True)");
+        assert_eq!(eval(&mut python, "(hash(a) == hash(2.0 ** 100), {a: 1}[2.0 ** 100], a in [2 ** 100])"), "(True, 1, True)");
+        assert_eq!(eval(&mut python, "(abs(-a), divmod(a, -7), pow(3, a, 10 ** 30), round(a, -29))"), "(1267650600228229401496703205376, (-181092942889747057356671886483, -5), 961644755576270127791781969921, 1300000000000000000000000000000)");
+        assert_eq!(eval(&mut python, "(int('9' * 25), int(1e25), float(a), hex(-a), '%x' % a, format(a, ','))"), "(9999999999999999999999999, 10000000000000000905969664, 1.2676506002282294e+30, '-0x10000000000000000000000000', '10000000000000000000000000', '1,267,650,600,228,229,401,496,703,205,376')");
+        assert_eq!(error(&mut python, "float(10 ** 400)"), "OverflowError: int too large to convert to float");
+        assert_eq!(error(&mut python, "10 ** 400 / 3"), "OverflowError: integer division result too large for a float");
+        assert_eq!(error(&mut python, "[1][a]"), "IndexError: cannot fit 'int' into an index-sized integer");
+        assert_eq!(error(&mut python, "'a' * a"), "OverflowError: cannot fit 'int' into an index-sized integer");
+    }
+
+    #[test]
+    fn test_comprehensions() {
+        let mut python = python("x = 'outer'");
+        assert_eq!(eval(&mut python, "[x * 2 for x in range(6) if x % 2 if x > 1]"), "[6, 10]");
+        assert_eq!(eval(&mut python, "[(i, j) for i in range(3) for j in range(i)]"), "[(1, 0), (2, 0), (2, 1)]");
+        assert_eq!(eval(&mut python, "{k: v for k, v in zip('ab', range(2))}"), "{'a': 0, 'b': 1}");
+        // the loop variable doesn't leak
+        assert_eq!(eval(&mut python, "x"), "'outer'");
+        exec(&mut python, "def f(n):\n    return [lambda: i * n for i in range(3)]");
+        assert_eq!(eval(&mut python, "[g() for g in f(10)]"), "[20, 20, 20]");
+    }
+
+    #[test]
+    fn test_generator_expressions() {
+        let mut python = python("import itertools\ng = (x * x for x in range(4))");
+        assert_eq!(eval(&mut python, "(next(g), list(g), list(g))"), "(0, [1, 4, 9], [])");
+        assert_eq!(eval(&mut python, "sum(x for x in range(10))"), "45");
+        // items are made as they are asked for
+        assert_eq!(eval(&mut python, "next(x for x in itertools.count() if x > 5)"), "6");
+        assert_eq!(eval(&mut python, "type(g).__name__"), "'generator'");
+    }
+
+    #[test]
+    fn test_comprehension_frames() {
+        let mut python = python("g = (1 / x for x in [1, 0])");
+        let expected = "Traceback (most recent call last):\n  File \"<stdin>\", line 2, in <module>\n  File \"<stdin>\", line 1, in <genexpr>\nZeroDivisionError: division by zero";
+        assert_eq!(traceback(&mut python, "\nlist(g)"), expected);
+        assert_eq!(raises("{x: 1 for x in [[]]}"), "TypeError: unhashable type: 'list'");
+    }
+
+    #[test]
+    fn test_semicolons() {
+        let mut python = python("a = 1; b = 2;\nif a: c = 3; d = 4");
+        assert_eq!(eval(&mut python, "(a, b, c, d)"), "(1, 2, 3, 4)");
+    }
+}
@@ -0,0 +1,84 @@
+use crate::interpreter::Interpreter;
+use crate::value::Exception;
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/* Cancels running code from outside it: from another thread, or from the
+ * SIGINT handler. interrupt() only sets a flag; the interpreter checks it
+ * at the next safe point (each statement, and each item an iterator
+ * produces) and raises KeyboardInterrupt there, so nothing is left half
+ * done. An interrupt that arrives while no code is running is raised when
+ * code next runs.
+ */
+#[derive(Clone, Debug, Default)]
+pub struct InterruptHandle {
+    flag: Arc<AtomicBool>,
+}
+
+impl InterruptHandle {
+    pub fn build_new() -> InterruptHandle {
+        return InterruptHandle::default();
+    }
+
+    pub fn interrupt(&self) {
+        self.flag.store(true, Ordering::SeqCst);
+    }
+
+    /* Forget an interrupt that hasn't been raised yet
+     */
+    pub fn clear(&self) {
+        self.flag.store(false, Ordering::SeqCst);
+    }
+
+    /* Whether there is an interrupt to raise, clearing it if so
+     */
+    pub fn take(&self) -> bool {
+        // a load first, as this runs for every statement
+        return self.flag.load(Ordering::Relaxed) && self.flag.swap(false, Ordering::SeqCst);
+    }
+
+    /* Interrupt whenever the process gets SIGINT (Ctrl-C), instead of
+     * being killed by it
+     */
+    pub fn catch_sigint(&self) -> io::Result<()> {
+        signal_hook::flag::register(signal_hook::consts::SIGINT, self.flag.clone())?;
+        return Ok(());
+    }
+}
+
+impl Interpreter {
+    /* Raise KeyboardInterrupt if code has been interrupted
+     */
+    pub fn check_interrupt(&self) -> Result<(), Exception> {
+        if self.interrupt.take() {
+            return Err(Exception::new("KeyboardInterrupt", ""));
+        }
+        return Ok(());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::embed;
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn test_interrupt() {
+        let mut python = embed::Interpreter::build_new();
+        let handle = python.interrupt_handle();
+        let canceller = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(20));
+            handle.interrupt();
+        });
+        let err = python.exec("x = 0\nwhile True:\n    x = x + 1\n").unwrap_err();
+        canceller.join().unwrap();
+        assert_eq!(err.to_string(), "KeyboardInterrupt");
+        assert!(python.get_as::<i64>("x").unwrap() > 0);
+
+        // the interpreter carries on afterwards
+        python.exec("x = 'done'\n").unwrap();
+        assert_eq!(python.get_as::<String>("x").unwrap(), "done");
+    }
+}
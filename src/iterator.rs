@@ -0,0 +1,264 @@
+use crate::bytes;
+use crate::interpreter::{equals, Interpreter};
+use crate::value::{new_tuple, range_len, Args, Exception, Value};
+use std::cell::RefCell;
+use std::mem::size_of;
+use std::rc::Rc;
+
+/* Iterator objects, and the iteration protocol used by for loops,
+ * unpacking and the builtins.
+ *
+ * List iterators read the list as they go, so appending during a loop is
+ * seen like in CPython. map, filter, zip and enumerate are lazy and pull
+ * from the iterators they wrap one item at a time.
+ */
+pub enum Iter {
+    List(Rc<RefCell<Vec<Value>>>, usize),
+    Tuple(Rc<Vec<Value>>, usize),
+    // a snapshot of items (str characters, dict keys, reversed()), with the
+    // iterator's type name
+    Items(Vec<Value>, usize, &'static str),
+    // a bytes, bytearray or memoryview, read as it goes like a list
+    Bytes(Value, usize),
+    // next value, items left, step
+    Range(i64, i64, i64),
+    Enumerate(Value, i64),
+    Zip(Vec<Value>),
+    Map(Value, Vec<Value>),
+    Filter(Value, Value),
+    // iter(callable, sentinel): the callable and sentinel, None once the
+    // sentinel has come up
+    Callable(Option<(Value, Value)>),
+    // an iterator of a native module (itertools'), with its type name
+    Native(&'static str, NextFn),
+}
+
+/* What makes the items of a native iterator: the next item, or None once
+ * there are no more (and on every call after that)
+ */
+pub type NextFn = Rc<RefCell<dyn FnMut(&mut Interpreter) -> Result<Option<Value>, Exception>>>;
+
+impl Iter {
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Iter::List(..) => return "list_iterator",
+            Iter::Tuple(..) => return "tuple_iterator",
+            Iter::Items(_, _, name) => return name,
+            Iter::Bytes(Value::ByteArray(_), _) => return "bytearray_iterator",
+            Iter::Bytes(Value::MemoryView(_), _) => return "memory_iterator",
+            Iter::Bytes(..) => return "bytes_iterator",
+            Iter::Range(..) => return "range_iterator",
+            Iter::Enumerate(..) => return "enumerate",
+            Iter::Zip(_) => return "zip",
+            Iter::Map(..) => return "map",
+            Iter::Filter(..) => return "filter",
+            Iter::Callable(_) => return "callable_iterator",
+            Iter::Native(name, _) => return name,
+        }
+    }
+}
+
+enum Wrapped {
+    Enumerate(Value, i64),
+    Zip(Vec<Value>),
+    Map(Value, Vec<Value>),
+    Filter(Value, Value),
+    Callable(Value, Value),
+    Native(NextFn),
+}
+
+pub fn new_iterator(iter: Iter) -> Value {
+    return Value::Iterator(Rc::new(RefCell::new(iter)));
+}
+
+/* A native iterator of type name, whose items next makes
+ */
+pub fn native_iterator<F>(name: &'static str, next: F) -> Value
+where
+    F: FnMut(&mut Interpreter) -> Result<Option<Value>, Exception> + 'static,
+{
+    return new_iterator(Iter::Native(name, Rc::new(RefCell::new(next))));
+}
+
+impl Interpreter {
+    /* iter(value)
+     */
+    pub fn get_iter(&mut self, value: &Value) -> Result<Value, Exception> {
+        if let Some(iterator) = self.call_special(value, "__iter__", vec![]) {
+            // an instance with __next__ is its own kind of iterator;
+            // anything else __iter__ gives has to be a built-in iterable
+            let iterator = iterator?;
+            if let Value::Object(object) = &iterator {
+                if object.class.lookup("__next__").is_some() {
+                    return Ok(iterator);
+                }
+                let message = format!("iter() returned non-iterator of type '{}'", object.class.name);
+                return Err(Exception::new("TypeError", &message));
+            }
+            return self.get_iter(&iterator);
+        }
+        let iter = match value {
+            Value::Iterator(_) => return Ok(value.clone()),
+            Value::List(items) => Iter::List(items.clone(), 0),
+            Value::Tuple(items) => Iter::Tuple(items.clone(), 0),
+            Value::Str(val) => {
+                self.allocate(val.len() * size_of::<Value>())?;
+                let chars = val.chars().map(|c| Value::Str(c.to_string())).collect();
+                Iter::Items(chars, 0, "str_iterator")
+            }
+            Value::Dict(dict) => Iter::Items(dict.borrow().keys(), 0, "dict_keyiterator"),
+            Value::Set(items) | Value::FrozenSet(items) => Iter::Items(items.borrow().keys(), 0, "set_iterator"),
+            Value::Bytes(_) | Value::ByteArray(_) => Iter::Bytes(value.clone(), 0),
+            Value::MemoryView(view) => {
+                view.check()?;
+                Iter::Bytes(value.clone(), 0)
+            }
+            Value::Range(start, stop, step) => Iter::Range(*start, range_len(*start, *stop, *step), *step),
+            _ => {
+                let message = format!("'{}' object is not iterable", value.type_name());
+                return Err(Exception::new("TypeError", &message));
+            }
+        };
+        return Ok(new_iterator(iter));
+    }
+
+    /* next(iterator), or None when it is exhausted
+     */
+    pub fn next_item(&mut self, iterator: &Value) -> Result<Option<Value>, Exception> {
+        let iter = match iterator {
+            Value::Iterator(iter) => iter,
+            Value::Object(_) => {
+                self.tick()?;
+                match self.call_special(iterator, "__next__", vec![]) {
+                    Some(Ok(item)) => return Ok(Some(item)),
+                    Some(Err(err)) if err.kind == "StopIteration" => return Ok(None),
+                    Some(Err(err)) => return Err(err),
+                    None => {
+                        let message = format!("'{}' object is not an iterator", iterator.type_name());
+                        return Err(Exception::new("TypeError", &message));
+                    }
+                }
+            }
+            _ => {
+                let message = format!("'{}' object is not an iterator", iterator.type_name());
+                return Err(Exception::new("TypeError", &message));
+            }
+        };
+        self.tick()?;
+        // wrapping iterators are advanced without the iterator borrowed, as
+        // the wrapped iterators and functions may use it again
+        let wrapped = match &mut *iter.borrow_mut() {
+            Iter::List(items, pos) => {
+                let item = items.borrow().get(*pos).cloned();
+                *pos += 1;
+                return Ok(item);
+            }
+            Iter::Tuple(items, pos) => {
+                let item = items.get(*pos).cloned();
+                *pos += 1;
+                return Ok(item);
+            }
+            Iter::Items(items, pos, _) => {
+                let item = items.get(*pos).cloned();
+                *pos += 1;
+                return Ok(item);
+            }
+            Iter::Bytes(data, pos) => {
+                let item = bytes::byte_at(data, *pos).map(|byte| Value::Int(byte as i64));
+                *pos += 1;
+                return Ok(item);
+            }
+            Iter::Range(next, left, step) => {
+                if *left <= 0 {
+                    return Ok(None);
+                }
+                let item = *next;
+                *left -= 1;
+                *next = next.wrapping_add(*step);
+                return Ok(Some(Value::Int(item)));
+            }
+            Iter::Enumerate(inner, count) => {
+                let count = std::mem::replace(count, count.wrapping_add(1));
+                Wrapped::Enumerate(inner.clone(), count)
+            }
+            Iter::Zip(inners) => Wrapped::Zip(inners.clone()),
+            Iter::Map(func, inners) => Wrapped::Map(func.clone(), inners.clone()),
+            Iter::Filter(func, inner) => Wrapped::Filter(func.clone(), inner.clone()),
+            Iter::Callable(None) => return Ok(None),
+            Iter::Callable(Some((func, sentinel))) => Wrapped::Callable(func.clone(), sentinel.clone()),
+            Iter::Native(_, next) => Wrapped::Native(next.clone()),
+        };
+
+        match wrapped {
+            Wrapped::Enumerate(inner, count) => match self.next_item(&inner)? {
+                Some(item) => return Ok(Some(new_tuple(vec![Value::Int(count), item]))),
+                None => return Ok(None),
+            },
+            Wrapped::Zip(inners) => match self.next_all(&inners)? {
+                // zip() with no arguments is empty
+                Some(items) if !items.is_empty() => return Ok(Some(new_tuple(items))),
+                _ => return Ok(None),
+            },
+            Wrapped::Map(func, inners) => match self.next_all(&inners)? {
+                Some(items) => return Ok(Some(self.call(&func, Args::build_new(items))?)),
+                None => return Ok(None),
+            },
+            Wrapped::Filter(func, inner) => {
+                while let Some(item) = self.next_item(&inner)? {
+                    let keep = match &func {
+                        Value::NoneT => item.is_truthy(),
+                        _ => self.call(&func, Args::build_new(vec![item.clone()]))?.is_truthy(),
+                    };
+                    if keep {
+                        return Ok(Some(item));
+                    }
+                }
+                return Ok(None);
+            }
+            Wrapped::Callable(func, sentinel) => {
+                let item = self.call(&func, Args::default())?;
+                if equals(&item, &sentinel) {
+                    *iter.borrow_mut() = Iter::Callable(None);
+                    return Ok(None);
+                }
+                return Ok(Some(item));
+            }
+            // an iterator asked for its next item while making one, like a
+            // generator resumed from inside itself
+            Wrapped::Native(next) => match next.try_borrow_mut() {
+                Ok(mut next) => return (*next)(self),
+                Err(_) => return Err(Exception::new("ValueError", "generator already executing")),
+            },
+        }
+    }
+
+    /* the next item of each iterator, or None once any is exhausted
+     */
+    fn next_all(&mut self, iterators: &[Value]) -> Result<Option<Vec<Value>>, Exception> {
+        let mut items: Vec<Value> = Vec::new();
+        for iterator in iterators {
+            match self.next_item(iterator)? {
+                Some(item) => items.push(item),
+                None => return Ok(None),
+            }
+        }
+        return Ok(Some(items));
+    }
+
+    /* all the items of an iterable
+     */
+    pub fn iterate(&mut self, value: &Value) -> Result<Vec<Value>, Exception> {
+        match value {
+            Value::List(items) => return Ok(items.borrow().clone()),
+            Value::Tuple(items) => return Ok(items.to_vec()),
+            _ => (),
+        };
+        let iterator = self.get_iter(value)?;
+        let mut items: Vec<Value> = Vec::new();
+        while let Some(item) = self.next_item(&iterator)? {
+            self.allocate(size_of::<Value>())?;
+            items.push(item);
+        }
+        return Ok(items);
+    }
+}
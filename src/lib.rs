@@ -0,0 +1,44 @@
+#![allow(clippy::needless_return)]
+
+/* rebar: a Python interpreter, written in Rust.
+ *
+ * The library embeds the interpreter in a Rust program (see
+ * embed::Interpreter); the rebar binary is a thin command line over it.
+ */
+
+mod ast;
+mod bytes;
+mod cache;
+mod classes;
+mod containers;
+mod convert;
+mod dict;
+mod embed;
+mod exceptions;
+mod format;
+mod gc;
+mod importer;
+mod interpreter;
+mod interrupt;
+mod iterator;
+mod limits;
+mod modules;
+mod native;
+mod optimizer;
+mod parser;
+mod readline;
+mod scanner;
+mod session;
+mod strings;
+#[cfg(test)]
+mod testing;
+mod value;
+
+pub use convert::FromValue;
+pub use embed::Interpreter;
+pub use interrupt::InterruptHandle;
+pub use limits::Limits;
+pub use native::{
+    IntoInstance, IntoResult, NativeClass, NativeConstructor, NativeFunction, NativeMethod, NativeModule,
+};
+pub use value::{Exception, Value};
@@ -0,0 +1,427 @@
+use crate::gc;
+use crate::interpreter::Interpreter;
+use crate::value::Exception;
+use std::time::{Duration, Instant};
+
+// the clock is only read every so many instructions
+const TIME_CHECK_INTERVAL: u64 = 1024;
+// the stack left when with_stack() grows it, and how much it adds
+const STACK_RED_ZONE: usize = 256 * 1024;
+const STACK_SEGMENT: usize = 4 * 1024 * 1024;
+// native loops over long strings and bytes tick once per this many bytes,
+// so the time limit and interrupts reach them
+pub const TICK_BYTES: usize = 64 * 1024;
+
+/* Limits on what running code may do, for scripts that aren't trusted.
+ *
+ * Going over a limit raises an exception, which ends the script and is
+ * returned to the host:
+ * - max_instructions: statements executed plus items iterated over, and
+ *   each 64KB a native loop over a long string goes through
+ *   (ResourceLimitError)
+ * - max_time: wall-clock time (TimeoutError)
+ * - max_recursion: nesting of function calls (RecursionError)
+ * - max_memory: bytes of the strings, lists, tuples, dicts and big ints
+ *   created (MemoryError)
+ * - max_output: bytes written to stdout and stderr (ResourceLimitError)
+ *
+ * Once one of the limits but the recursion limit is exceeded, its error
+ * is raised again at every instruction, and except clauses and __exit__
+ * methods can't catch or suppress it, so the script can't carry on past
+ * it. RecursionError can be caught, as in CPython: the calls it unwound
+ * are over.
+ *
+ * Usage is counted from the start of each exec(), eval(), call() or
+ * run_file() of the embedding API. sandboxed turns off access to files,
+ * the environment and imports of modules that aren't loaded already;
+ * builtins, sys and modules the host registered can still be imported.
+ *
+ * Only the recursion limit is on by default.
+ */
+#[derive(Clone, Debug)]
+pub struct Limits {
+    pub max_instructions: Option<u64>,
+    pub max_time: Option<Duration>,
+    pub max_recursion: usize,
+    pub max_memory: Option<usize>,
+    pub max_output: Option<usize>,
+    pub sandboxed: bool,
+}
+
+impl Default for Limits {
+    fn default() -> Limits {
+        return Limits {
+            max_instructions: None,
+            max_time: None,
+            // like sys.getrecursionlimit()
+            max_recursion: 1000,
+            max_memory: None,
+            max_output: None,
+            sandboxed: false,
+        };
+    }
+}
+
+/* What the running code has used so far
+ */
+#[derive(Debug)]
+pub struct Usage {
+    pub instructions: u64,
+    pub started: Instant,
+    pub memory: usize,
+    pub output: usize,
+    // the error of the limit that has been exceeded, if one has
+    pub exceeded: Option<Exception>,
+}
+
+impl Usage {
+    pub fn build_new() -> Usage {
+        return Usage {
+            instructions: 0,
+            started: Instant::now(),
+            memory: 0,
+            output: 0,
+            exceeded: None,
+        };
+    }
+}
+
+/* Run a step of the interpreter or parser that recurses, on a new stack
+ * segment from the heap if the thread's stack is nearly used up. The
+ * depth of recursion is up to max_recursion (or the nesting of the code),
+ * not the size of the stack of whichever thread the host runs it on.
+ */
+pub fn with_stack<R>(run: impl FnOnce() -> R) -> R {
+    return stacker::maybe_grow(STACK_RED_ZONE, STACK_SEGMENT, run);
+}
+
+fn memory_error() -> Exception {
+    return Exception::new("MemoryError", "");
+}
+
+/* An empty string with room for len bytes, or MemoryError if there's no
+ * such room, where allocating it as the string grows would abort
+ */
+pub fn new_string(len: usize) -> Result<String, Exception> {
+    let mut text = String::new();
+    text.try_reserve_exact(len).map_err(|_| memory_error())?;
+    return Ok(text);
+}
+
+/* new_string() for bytes
+ */
+pub fn new_buffer(len: usize) -> Result<Vec<u8>, Exception> {
+    let mut data = Vec::new();
+    data.try_reserve_exact(len).map_err(|_| memory_error())?;
+    return Ok(data);
+}
+
+impl Interpreter {
+    /* Start counting usage again from nothing
+     */
+    pub fn reset_usage(&mut self) {
+        self.usage = Usage::build_new();
+    }
+
+    /* Count an instruction: called for each statement executed and each
+     * item an iterator produces. These are also the points where
+     * interrupts are raised and the garbage collector runs.
+     */
+    pub fn tick(&mut self) -> Result<(), Exception> {
+        if let Some(err) = &self.usage.exceeded {
+            return Err(err.clone());
+        }
+        self.check_interrupt()?;
+        if gc::due() {
+            self.run_gc();
+        }
+        self.usage.instructions += 1;
+        if let Some(max) = self.limits.max_instructions {
+            if self.usage.instructions > max {
+                let message = format!("instruction limit of {} exceeded", max);
+                return Err(self.exceed(Exception::new("ResourceLimitError", &message)));
+            }
+        }
+        if let Some(max) = self.limits.max_time {
+            if self.usage.instructions.is_multiple_of(TIME_CHECK_INTERVAL) && self.usage.started.elapsed() > max {
+                let message = format!("time limit of {:?} exceeded", max);
+                return Err(self.exceed(Exception::new("TimeoutError", &message)));
+            }
+        }
+        return Ok(());
+    }
+
+    /* Note that the limit err is for has been exceeded, so that it is
+     * raised from now on
+     */
+    fn exceed(&mut self, err: Exception) -> Exception {
+        self.usage.exceeded = Some(err.clone());
+        return err;
+    }
+
+    /* Count memory about to be allocated, before allocating it
+     */
+    pub fn allocate(&mut self, bytes: usize) -> Result<(), Exception> {
+        self.usage.memory = self.usage.memory.saturating_add(bytes);
+        match self.limits.max_memory {
+            Some(max) if self.usage.memory > max => {
+                let message = format!("memory limit of {} bytes exceeded", max);
+                return Err(self.exceed(Exception::new("MemoryError", &message)));
+            }
+            _ => return Ok(()),
+        }
+    }
+
+    /* Count a str result of len bytes and make room for it, before it's
+     * built. len is None where working it out overflowed, which raises
+     * OverflowError.
+     */
+    pub fn allocate_string(&mut self, len: Option<usize>) -> Result<String, Exception> {
+        let len = len.ok_or_else(|| Exception::new("OverflowError", "result is too long"))?;
+        self.allocate(len)?;
+        return new_string(len);
+    }
+
+    /* allocate_string() for a bytes or bytearray result
+     */
+    pub fn allocate_buffer(&mut self, len: Option<usize>) -> Result<Vec<u8>, Exception> {
+        let len = len.ok_or_else(|| Exception::new("OverflowError", "result is too long"))?;
+        self.allocate(len)?;
+        return new_buffer(len);
+    }
+
+    /* Count output about to be written to stdout or stderr
+     */
+    pub fn output(&mut self, bytes: usize) -> Result<(), Exception> {
+        self.usage.output = self.usage.output.saturating_add(bytes);
+        match self.limits.max_output {
+            Some(max) if self.usage.output > max => {
+                let message = format!("output limit of {} bytes exceeded", max);
+                return Err(self.exceed(Exception::new("ResourceLimitError", &message)));
+            }
+            _ => return Ok(()),
+        }
+    }
+
+    /* Check that file, environment or import access is allowed. what
+     * describes the access and kind is the exception raised if it isn't:
+     * check_access("PermissionError", "file access")
+     */
+    pub fn check_access(&self, kind: &str, what: &str) -> Result<(), Exception> {
+        if self.limits.sandboxed {
+            let message = format!("{} is not allowed in the sandbox", what);
+            return Err(Exception::new(kind, &message));
+        }
+        return Ok(());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::embed;
+    use crate::limits::Limits;
+    use std::time::Duration;
+
+    fn run(limits: Limits, source: &str) -> String {
+        let mut python = embed::Interpreter::build_new();
+        python.set_limits(limits);
+        match python.exec(source) {
+            Ok(()) => return String::from("ok"),
+            Err(err) => return err.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_instructions() {
+        let limits = Limits {
+            max_instructions: Some(1000),
+            ..Limits::default()
+        };
+        assert_eq!(run(limits.clone(), "x = 0\nwhile x < 10:\n    x = x + 1\n"), "ok");
+        let message = "ResourceLimitError: instruction limit of 1000 exceeded";
+        assert_eq!(run(limits.clone(), "while True:\n    pass\n"), message);
+        assert_eq!(run(limits, "sum(range(10 ** 9))"), message);
+    }
+
+    #[test]
+    fn test_time() {
+        let limits = Limits {
+            max_time: Some(Duration::from_millis(10)),
+            ..Limits::default()
+        };
+        assert_eq!(run(limits, "while True:\n    pass\n"), "TimeoutError: time limit of 10ms exceeded");
+    }
+
+    #[test]
+    fn test_recursion() {
+        let limits = Limits {
+            max_recursion: 50,
+            ..Limits::default()
+        };
+        let source = "def f(n):\n    if n:\n        f(n - 1)\n\nf(40)\nf(60)\n";
+        assert_eq!(run(limits, source), "RecursionError: maximum recursion depth exceeded");
+    }
+
+    #[test]
+    fn test_stack() {
+        // a thread of the default size has a smaller stack than the main
+        // thread, and the recursion limit still comes first
+        let thread = std::thread::Builder::new().spawn(|| {
+            let recursion = run(Limits::default(), "def f():\n    f()\n\nf()\n");
+            let nesting = run(Limits::default(), &format!("x = {}1\n", "-".repeat(1500)));
+            let items = run(Limits::default(), "a = []\nfor i in range(100000):\n    a = [a, (a,), {1: a}]\ndel a\n");
+            return (recursion, nesting, items);
+        });
+        let message = "RecursionError: maximum recursion depth exceeded";
+        assert_eq!(thread.unwrap().join().unwrap(), (String::from(message), String::from("ok"), String::from("ok")));
+    }
+
+    #[test]
+    fn test_memory() {
+        let limits = Limits {
+            max_memory: Some(1 << 20),
+            ..Limits::default()
+        };
+        assert_eq!(run(limits.clone(), "s = 'ab' * 1000\n"), "ok");
+        let message = "MemoryError: memory limit of 1048576 bytes exceeded";
+        assert_eq!(run(limits.clone(), "s = 'ab' * 10 ** 12\n"), message);
+        assert_eq!(run(limits.clone(), "s = list(range(10 ** 9))\n"), message);
+        assert_eq!(run(limits.clone(), "n = 3 ** 10 ** 8\n"), message);
+        assert_eq!(run(limits.clone(), "n = 1 << 10 ** 8\n"), message);
+        for source in [
+            "'{:>9999999999999}'.format(1)",
+            "format(1.5, '.999999999f')",
+            "'%9999999999999d' % 1",
+            "'%.999999999d' % 1",
+            "'a'.zfill(9999999999999)",
+            "'a'.center(9999999999999)",
+            "'a'.ljust(9999999999999, 'é')",
+            "'\\t'.expandtabs(9999999999999)",
+            "('ab' * 1000).replace('', 'x' * 1000)",
+            "''.join(['x' * 1000] * 10 ** 4)",
+            "'a'.translate({97: 'x' * 10 ** 7})",
+            "b'a'.ljust(9999999999999)",
+            "bytearray(b'a').zfill(9999999999999)",
+            "b'a'.center(9999999999999, b'-')",
+            "b'\\t'.expandtabs(9999999999999)",
+            "b'a' * 2 ** 62",
+            "bytes(10 ** 7)",
+            "(b'ab' * 1000).replace(b'', b'x' * 1000)",
+            "b''.join([b'x' * 1000] * 10 ** 4)",
+        ] {
+            assert_eq!(run(limits.clone(), source), message, "{}", source);
+        }
+    }
+
+    #[test]
+    fn test_huge_strings() {
+        // no limit: results too big to allocate raise instead of aborting
+        let limits = Limits::default();
+        assert_eq!(run(limits.clone(), "'a' * 2 ** 62"), "MemoryError");
+        assert_eq!(run(limits.clone(), "'ab'.ljust(2 ** 62)"), "MemoryError");
+        assert_eq!(run(limits.clone(), "'{:>4611686018427387904}'.format(1)"), "MemoryError");
+        assert_eq!(run(limits.clone(), "'ab' * 2 ** 62"), "OverflowError: repeated string is too long");
+        assert_eq!(run(limits.clone(), "b'a' * 2 ** 62"), "MemoryError");
+        assert_eq!(run(limits.clone(), "bytearray(b'ab').ljust(2 ** 62)"), "MemoryError");
+        assert_eq!(run(limits.clone(), "bytes(2 ** 62)"), "MemoryError");
+        assert_eq!(run(limits.clone(), "b'ab' * 2 ** 62"), "OverflowError: repeated bytes are too long");
+        assert_eq!(run(limits.clone(), "3 ** 2 ** 62"), "MemoryError");
+        assert_eq!(run(limits.clone(), "1 << 2 ** 62"), "MemoryError");
+        assert_eq!(run(limits.clone(), "'{:.3000000000f}'.format(1.0)"), "ValueError: precision too big");
+        assert_eq!(run(limits, "'%.3000000000f' % 1.0"), "ValueError: precision too big");
+    }
+
+    #[test]
+    fn test_long_native_loops() {
+        // searches of a long string tick as they go, so the instruction and
+        // time limits reach them; the loop alone is a handful of ticks
+        let limits = Limits {
+            max_instructions: Some(100),
+            ..Limits::default()
+        };
+        let message = "ResourceLimitError: instruction limit of 100 exceeded";
+        for call in ["s.find('x')", "s.rfind('x')", "s.count('abcd')", "s.replace('abcd', 'x')", "s.expandtabs()"] {
+            let source = format!("s = 'abcd' * 10 ** 6\nfor i in range(3):\n    {}\n", call);
+            assert_eq!(run(limits.clone(), &source), message, "{}", call);
+        }
+        assert_eq!(run(limits, "s = 'abcd' * 10 ** 4\nfor i in range(3):\n    s.count('abcd')\n"), "ok");
+    }
+
+    #[test]
+    fn test_output() {
+        let limits = Limits {
+            max_output: Some(3),
+            ..Limits::default()
+        };
+        assert_eq!(run(limits.clone(), "print('ab')\n"), "ok");
+        let message = "ResourceLimitError: output limit of 3 bytes exceeded";
+        assert_eq!(run(limits.clone(), "print('abc')\n"), message);
+        assert_eq!(run(limits, "import sys\nprint('a')\nsys.stderr.write('bc')\n"), message);
+    }
+
+    #[test]
+    fn test_swallowing() {
+        // an exceeded limit can't be caught, suppressed or outlived
+        let swallow = |body: &str| {
+            let suppress = "class Suppress:\n    def __enter__(self):\n        pass\n    def __exit__(self, *exc):\n        return True\n";
+            let caught = format!("while True:\n    try:\n        {}\n    except BaseException:\n        pass\n", body);
+            let finally = format!("def f():\n    try:\n        {}\n    finally:\n        return 1\n\nwhile True:\n    f()\n", body);
+            let suppressed = format!("{}while True:\n    with Suppress():\n        {}\n", suppress, body);
+            return [caught, finally, suppressed];
+        };
+        let limits = Limits {
+            max_instructions: Some(1000),
+            ..Limits::default()
+        };
+        for source in swallow("while True: pass") {
+            assert_eq!(run(limits.clone(), &source), "ResourceLimitError: instruction limit of 1000 exceeded", "{}", source);
+        }
+        let limits = Limits {
+            max_time: Some(Duration::from_millis(200)),
+            ..Limits::default()
+        };
+        for source in swallow("while True: pass") {
+            assert_eq!(run(limits.clone(), &source), "TimeoutError: time limit of 200ms exceeded", "{}", source);
+        }
+        let limits = Limits {
+            max_memory: Some(1 << 20),
+            max_time: Some(Duration::from_secs(10)),
+            ..Limits::default()
+        };
+        for source in swallow("s = 'a' * 10 ** 7") {
+            assert_eq!(run(limits.clone(), &source), "MemoryError: memory limit of 1048576 bytes exceeded", "{}", source);
+        }
+        let limits = Limits {
+            max_output: Some(10),
+            max_time: Some(Duration::from_secs(10)),
+            ..Limits::default()
+        };
+        for source in swallow("print('x' * 100)") {
+            assert_eq!(run(limits.clone(), &source), "ResourceLimitError: output limit of 10 bytes exceeded", "{}", source);
+        }
+        // RecursionError is caught as in CPython
+        let source = "def f():\n    f()\n\ntry:\n    f()\nexcept RecursionError:\n    pass\n";
+        assert_eq!(run(Limits::default(), source), "ok");
+    }
+
+    #[test]
+    fn test_sandbox() {
+        let limits = Limits {
+            sandboxed: true,
+            ..Limits::default()
+        };
+        assert_eq!(run(limits.clone(), "import sys\n"), "ok");
+        let message = "ImportError: import of 'nonexistent' is not allowed in the sandbox";
+        assert_eq!(run(limits.clone(), "import nonexistent\n"), message);
+        let message = "PermissionError: file access is not allowed in the sandbox";
+        assert_eq!(run(limits.clone(), "open('data.txt')\n"), message);
+        assert_eq!(run(limits.clone(), "import io\nprint('ok', file=io.StringIO())\n"), "ok");
+        assert_eq!(run(limits.clone(), "import os\nos.listdir()\n"), message);
+        assert_eq!(run(limits.clone(), "import os\nos.path.join('a', os.path.basename('b/c'))\n"), "ok");
+        let message = "PermissionError: environment access is not allowed in the sandbox";
+        assert_eq!(run(limits.clone(), "import os\nos.environ['HOME']\n"), message);
+        assert_eq!(run(limits.clone(), "import sys\nsys.setrecursionlimit(500)\n"), "ok");
+        let message = "PermissionError: raising the recursion limit is not allowed in the sandbox";
+        assert_eq!(run(limits, "import sys\nsys.setrecursionlimit(5000)\n"), message);
+    }
+}
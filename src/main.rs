@@ -1,9 +1,93 @@
-mod scanner;
-mod parser;
+#![allow(clippy::needless_return)]
+
+use rebar::{Exception, Interpreter};
+use std::env;
+use std::io::{self, Write};
+use std::path::Path;
+use std::process;
+use std::thread;
+
+// Python frames recurse on the Rust stack, so the interpreter runs on a
+// thread with room for the full recursion limit
+const STACK_SIZE: usize = 512 * 1024 * 1024;
 
 fn main() {
-    let mut parser = parser::Parser::build_new();
+    let interpreter = thread::Builder::new().stack_size(STACK_SIZE).spawn(run).unwrap();
+    if interpreter.join().is_err() {
+        process::exit(101);
+    }
+}
+
+fn run() {
+    // usage: rebar [-B] [-O] [script [arg ...]]
+    let mut write_cache = true;
+    let mut optimize: u8 = 0;
+    let mut script: Option<String> = None;
+    let mut args = env::args().skip(1);
+    for arg in &mut args {
+        match arg.as_str() {
+            "-B" => write_cache = false,
+            "-O" => optimize = 1,
+            _ => {
+                script = Some(arg);
+                break;
+            }
+        }
+    }
+    if env::var_os("REBARDONTWRITEBYTECODE").is_some() {
+        write_cache = false;
+    }
+
+    let mut interpreter = Interpreter::build_new();
+    interpreter.set_write_cache(write_cache);
+    interpreter.set_optimize(optimize);
+    if let Err(err) = interpreter.catch_sigint() {
+        eprintln!("rebar: can't handle Ctrl-C: {}", err);
+    }
+
+    let result = match script {
+        Some(script) => {
+            // sys.argv is the script and the arguments after it
+            let argv: Vec<String> = std::iter::once(script.clone()).chain(args).collect();
+            interpreter.set_argv(&argv);
+            let path = Path::new(&script);
+            if let Err(err) = std::fs::metadata(path) {
+                eprintln!("rebar: can't open file '{}': {}", path.display(), err);
+                process::exit(2);
+            }
+            interpreter.run_file(path)
+        }
+        None => interpreter.repl(),
+    };
+    if let Err(err) = result {
+        let status = exit_status(&err);
+        let _ = io::stdout().flush();
+        process::exit(status);
+    }
+}
 
-    // recursive descent parse
-    parser.parse_program();
+/* The status to exit with for an uncaught exception, after reporting it.
+ * SystemExit is how sys.exit() ends the program: sys.exit() is status 0
+ * and sys.exit(n) is n, while anything else is printed and is 1.
+ */
+fn exit_status(err: &Exception) -> i32 {
+    match err.kind.as_str() {
+        "SystemExit" => {
+            if err.message.is_empty() {
+                return 0;
+            }
+            match err.message.parse() {
+                Ok(status) => return status,
+                Err(_) => {
+                    eprintln!("{}", err.message);
+                    return 1;
+                }
+            }
+        }
+        kind => {
+            eprintln!("{}", err.format_traceback());
+            // the status of being killed by SIGINT, like CPython
+            return if kind == "KeyboardInterrupt" { 130 } else { 1 };
+        }
+    }
 }
@@ -0,0 +1,1357 @@
+use crate::ast::{BinOp, CmpOp};
+use crate::bytes;
+use crate::classes;
+use crate::containers;
+use crate::dict::{new_dict, Dict, HashKey};
+use crate::format;
+use crate::interpreter::{
+    as_bigint, as_complex, as_float, as_int, binary_op, int_to_float, new_int, not_an_int, EvalResult, Interpreter,
+};
+use crate::iterator::{new_iterator, Iter};
+use crate::modules::{cmath, decimal, fractions};
+use crate::native::NativeClass;
+use crate::strings;
+use crate::value::{new_list, new_tuple, range_len, Args, Builtin, BuiltinFn, Class, Exception, Module, Object, Value};
+use num_bigint::BigInt;
+use num_integer::Integer;
+use num_traits::{FromPrimitive, One, Pow, Signed, Zero};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::rc::Rc;
+
+/* builtins - the names every module can use without importing them
+ *
+ * The built-in types (int, str, list, ...) are Class objects whose
+ * constructor converts its argument, so they work both as conversion
+ * functions and with isinstance().
+ */
+
+fn type_error(message: String) -> Exception {
+    return Exception::new("TypeError", &message);
+}
+
+fn value_error(message: String) -> Exception {
+    return Exception::new("ValueError", &message);
+}
+
+/* an argument that must be an int (or bool)
+ */
+fn expect_int(value: &Value) -> Result<i64, Exception> {
+    match as_int(value) {
+        Some(val) => return Ok(val),
+        None => return Err(not_an_int(value)),
+    }
+}
+
+/* positional argument index, or the keyword argument name, or None
+ */
+fn optional_arg(args: &mut Args, index: usize, name: &str) -> Option<Value> {
+    let keyword = args.keyword(name);
+    return args.positional.get(index).cloned().or(keyword);
+}
+
+pub fn build_types() -> HashMap<String, Rc<Class>> {
+    let object = Class::build_new("object", Vec::new(), None);
+    let int = Class::build_new("int", vec![object.clone()], Some(int_new));
+    let table: &[(&'static str, BuiltinFn)] = &[
+        ("type", type_new),
+        ("float", float_new),
+        ("complex", complex_new),
+        ("str", str_new),
+        ("list", list_new),
+        ("tuple", tuple_new),
+        ("dict", dict_new),
+        ("set", containers::set_new),
+        ("frozenset", containers::frozenset_new),
+        ("range", range_new),
+        ("enumerate", enumerate_new),
+        ("zip", zip_new),
+        ("map", map_new),
+        ("filter", filter_new),
+        ("reversed", reversed_new),
+        ("bytes", bytes::bytes_new),
+        ("bytearray", bytes::bytearray_new),
+        ("memoryview", bytes::memoryview_new),
+        ("super", super_new),
+    ];
+    let mut types: HashMap<String, Rc<Class>> = HashMap::new();
+    for (name, constructor) in table {
+        types.insert(String::from(*name), Class::build_new(name, vec![object.clone()], Some(*constructor)));
+    }
+    let init = Builtin::build_new("__init__", classes::object_init);
+    object.attrs.borrow_mut().insert(String::from("__init__"), init);
+    let conjugate = Builtin::build_new("conjugate", complex_conjugate);
+    types["complex"].attrs.borrow_mut().insert(String::from("conjugate"), conjugate);
+    strings::add_methods(&types["str"]);
+    containers::add_methods(&types["list"], &types["tuple"], &types["dict"], &types["set"], &types["frozenset"], &object);
+    bytes::add_methods(&types["bytes"], &types["bytearray"], &types["memoryview"]);
+    types.insert(String::from("slice"), containers::slice_class(object.clone()));
+    types.insert(String::from("bool"), Class::build_new("bool", vec![int.clone()], Some(bool_new)));
+    types.insert(String::from("int"), int);
+    types.insert(String::from("object"), object);
+    return types;
+}
+
+pub fn build_module(types: &HashMap<String, Rc<Class>>, exceptions: &HashMap<String, Rc<Class>>) -> Rc<Module> {
+    let module = Module::build_new("builtins", None);
+    let functions: &[(&str, BuiltinFn)] = &[
+        ("print", print),
+        ("input", input),
+        ("len", len),
+        ("isinstance", isinstance),
+        ("issubclass", issubclass),
+        ("repr", repr),
+        ("ascii", ascii),
+        ("format", format),
+        ("abs", abs),
+        ("min", min),
+        ("max", max),
+        ("sum", sum),
+        ("sorted", sorted),
+        ("any", any),
+        ("all", all),
+        ("round", round),
+        ("divmod", divmod),
+        ("pow", pow),
+        ("hash", hash),
+        ("id", id),
+        ("iter", iter),
+        ("next", next),
+        ("chr", chr),
+        ("ord", ord),
+        ("hex", hex),
+        ("bin", bin),
+        ("oct", oct),
+        ("getattr", getattr),
+        ("setattr", setattr),
+        ("hasattr", hasattr),
+        ("callable", callable),
+        ("vars", vars),
+    ];
+    for (name, func) in functions {
+        module.set(name, Builtin::build_new(name, *func));
+    }
+    for (name, class) in types {
+        module.set(name, Value::Class(class.clone()));
+    }
+    // the modules' exceptions have dotted names
+    for (name, class) in exceptions.iter().filter(|(name, _)| !name.contains('.')) {
+        module.set(name, Value::Class(class.clone()));
+    }
+    for alias in ["EnvironmentError", "IOError"] {
+        module.set(alias, Value::Class(exceptions["OSError"].clone()));
+    }
+    // what special methods return for operands they don't support
+    let mut not_implemented = NativeClass::<()>::build_new("NotImplementedType");
+    not_implemented.repr(|_| String::from("NotImplemented"));
+    let not_implemented = not_implemented.build(types["object"].clone());
+    module.set("NotImplemented", Value::Object(Object::build_new(not_implemented, Some(Box::new(())))));
+    return Rc::new(module);
+}
+
+/* sep/end arguments of print()
+ */
+fn string_option(value: Option<Value>, name: &str, default: &str) -> Result<String, Exception> {
+    match value {
+        None | Some(Value::NoneT) => return Ok(String::from(default)),
+        Some(Value::Str(ref val)) => return Ok(val.clone()),
+        Some(other) => {
+            return Err(type_error(format!("{} must be None or a string, not {}", name, other.type_name())));
+        }
+    }
+}
+
+/* sys.stdin, sys.stdout or sys.stderr, whatever scripts have set it to
+ */
+fn sys_stream(interpreter: &Interpreter, name: &str) -> Value {
+    let stream = interpreter.modules.get("sys").and_then(|sys| sys.get(name));
+    return stream.unwrap_or(Value::NoneT);
+}
+
+fn print(interpreter: &mut Interpreter, mut args: Args) -> EvalResult {
+    let sep = string_option(args.keyword("sep"), "sep", " ")?;
+    let end = string_option(args.keyword("end"), "end", "\n")?;
+    let file = args.keyword("file").unwrap_or(Value::NoneT);
+    let flush = args.keyword("flush").map(|flush| flush.is_truthy()).unwrap_or(false);
+    args.check("print", 0, usize::MAX)?;
+
+    let file = match file {
+        Value::NoneT => sys_stream(interpreter, "stdout"),
+        file => file,
+    };
+    // like CPython, printing without a sys.stdout does nothing
+    if let Value::NoneT = file {
+        return Ok(Value::NoneT);
+    }
+    let mut items: Vec<String> = Vec::new();
+    for item in &args.positional {
+        items.push(interpreter.str(item)?);
+    }
+    let text = format!("{}{}", items.join(&sep), end);
+    let write = interpreter.get_attr(&file, "write")?;
+    interpreter.call(&write, Args::build_new(vec![Value::Str(text)]))?;
+    if flush {
+        let flush = interpreter.get_attr(&file, "flush")?;
+        interpreter.call(&flush, Args::default())?;
+    }
+    return Ok(Value::NoneT);
+}
+
+/* input([prompt]): the prompt is written to sys.stdout and the line read
+ * from sys.stdin
+ */
+fn input(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("input", 0, 1)?;
+    let (stdin, stdout) = (sys_stream(interpreter, "stdin"), sys_stream(interpreter, "stdout"));
+    for (stream, name) in [(&stdin, "stdin"), (&stdout, "stdout")] {
+        if let Value::NoneT = stream {
+            return Err(Exception::new("RuntimeError", &format!("input(): lost sys.{}", name)));
+        }
+    }
+    if let Some(prompt) = args.positional.first() {
+        let write = interpreter.get_attr(&stdout, "write")?;
+        interpreter.call(&write, Args::build_new(vec![Value::Str(prompt.to_string())]))?;
+    }
+    let flush = interpreter.get_attr(&stdout, "flush")?;
+    interpreter.call(&flush, Args::default())?;
+    let readline = interpreter.get_attr(&stdin, "readline")?;
+    match interpreter.call(&readline, Args::default())? {
+        Value::Str(ref line) if line.is_empty() => return Err(Exception::new("EOFError", "EOF when reading a line")),
+        Value::Str(ref mut line) => {
+            if line.ends_with('\n') {
+                line.pop();
+            }
+            return Ok(Value::Str(std::mem::take(line)));
+        }
+        other => {
+            let message = format!("object.readline() returned non-string of type '{}'", other.type_name());
+            return Err(type_error(message));
+        }
+    }
+}
+
+fn len(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("len", 1, 1)?;
+    if let Some(len) = interpreter.call_special(&args.positional[0], "__len__", vec![]) {
+        return len;
+    }
+    let len = match &args.positional[0] {
+        Value::Str(val) => val.chars().count(),
+        Value::List(items) => items.borrow().len(),
+        Value::Tuple(items) => items.len(),
+        Value::Dict(dict) | Value::Set(dict) | Value::FrozenSet(dict) => dict.borrow().len(),
+        Value::Range(start, stop, step) => range_len(*start, *stop, *step) as usize,
+        Value::MemoryView(view) => {
+            view.check()?;
+            view.len
+        }
+        value @ (Value::Bytes(_) | Value::ByteArray(_)) => bytes::byte_len(value).unwrap(),
+        other => return Err(type_error(format!("object of type '{}' has no len()", other.type_name()))),
+    };
+    return Ok(Value::Int(len as i64));
+}
+
+fn type_new(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    match (args.positional.as_slice(), args.keywords.is_empty()) {
+        ([value], true) => return Ok(Value::Class(interpreter.type_of(value))),
+        ([name, bases, dict], true) => return interpreter.new_type(name, bases, dict),
+        _ => return Err(type_error(String::from("type() takes 1 or 3 arguments"))),
+    }
+}
+
+fn super_new(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    return interpreter.make_super(args);
+}
+
+/* whether class is classinfo, or a subclass of it (or of one of a tuple
+ * of them)
+ */
+fn class_matches(function: &str, class: &Class, classinfo: &Value) -> Result<bool, Exception> {
+    match classinfo {
+        Value::Class(info) => return Ok(class.is_subclass(info)),
+        Value::Tuple(infos) => {
+            for info in infos.iter() {
+                if class_matches(function, class, info)? {
+                    return Ok(true);
+                }
+            }
+            return Ok(false);
+        }
+        _ => {
+            let message = if function == "isinstance" {
+                "isinstance() arg 2 must be a type, a tuple of types, or a union"
+            } else {
+                "issubclass() arg 2 must be a class, a tuple of classes, or a union"
+            };
+            return Err(type_error(String::from(message)));
+        }
+    }
+}
+
+fn isinstance(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("isinstance", 2, 2)?;
+    let class = interpreter.type_of(&args.positional[0]);
+    return Ok(Value::Bool(class_matches("isinstance", &class, &args.positional[1])?));
+}
+
+fn issubclass(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("issubclass", 2, 2)?;
+    match &args.positional[0] {
+        Value::Class(class) => return Ok(Value::Bool(class_matches("issubclass", class, &args.positional[1])?)),
+        _ => return Err(type_error(String::from("issubclass() arg 1 must be a class"))),
+    }
+}
+
+/* float -> int, as int() and round() do it
+ */
+pub(crate) fn float_to_int(val: f64) -> EvalResult {
+    if val.is_nan() {
+        return Err(value_error(String::from("cannot convert float NaN to integer")));
+    }
+    if val.is_infinite() {
+        return Err(Exception::new("OverflowError", "cannot convert float infinity to integer"));
+    }
+    return Ok(new_int(BigInt::from_f64(val.trunc()).unwrap()));
+}
+
+/* Python's int() string syntax: surrounding whitespace, a sign, an optional
+ * base prefix (required to match base, or choosing it if base is 0) and
+ * single underscores between digits
+ */
+fn parse_int(text: &str, base: u32) -> EvalResult {
+    let invalid = || {
+        let shown = if base == 0 { 0 } else { base };
+        return value_error(format!(
+            "invalid literal for int() with base {}: {}",
+            shown,
+            Value::Str(String::from(text)).repr()
+        ));
+    };
+    let trimmed = text.trim().to_ascii_lowercase();
+    let (sign, mut digits) = match trimmed.strip_prefix('-') {
+        Some(rest) => ("-", rest),
+        None => ("", trimmed.strip_prefix('+').unwrap_or(&trimmed)),
+    };
+    let mut base = base;
+    for (prefix, prefix_base) in [("0x", 16), ("0o", 8), ("0b", 2)] {
+        if (base == 0 || base == prefix_base) && digits.starts_with(prefix) {
+            digits = digits[2..].strip_prefix('_').unwrap_or(&digits[2..]);
+            base = prefix_base;
+            break;
+        }
+    }
+    if base == 0 {
+        base = 10;
+        // no leading zeros in decimal literals
+        if digits.starts_with('0') && digits.chars().any(|c| c != '0' && c != '_') {
+            return Err(invalid());
+        }
+    }
+    if digits.is_empty() || digits.starts_with(['_', '+', '-']) || digits.ends_with('_') || digits.contains("__") {
+        return Err(invalid());
+    }
+    let digits: String = digits.chars().filter(|c| *c != '_').collect();
+    match BigInt::parse_bytes(format!("{}{}", sign, digits).as_bytes(), base) {
+        Some(val) => return Ok(new_int(val)),
+        None => return Err(invalid()),
+    }
+}
+
+fn int_new(_interpreter: &mut Interpreter, mut args: Args) -> EvalResult {
+    let base = args.keyword("base");
+    args.check("int", 0, 2)?;
+    let base = args.positional.get(1).cloned().or(base);
+    let value = match args.positional.first() {
+        Some(value) => value,
+        None if base.is_some() => return Err(type_error(String::from("int() missing string argument"))),
+        None => return Ok(Value::Int(0)),
+    };
+    if let Some(base) = base {
+        let base = expect_int(&base)?;
+        if base != 0 && !(2..=36).contains(&base) {
+            return Err(value_error(String::from("int() base must be >= 2 and <= 36, or 0")));
+        }
+        match value {
+            Value::Str(text) => return parse_int(text, base as u32),
+            _ => return Err(type_error(String::from("int() can't convert non-string with explicit base"))),
+        }
+    }
+    match value {
+        Value::Str(text) => return parse_int(text, 10),
+        Value::Float(val) => return float_to_int(*val),
+        Value::Fraction(val) => return fractions::int_value(&val.to_integer()),
+        Value::Decimal(val) => return decimal::to_int(val),
+        _ => match as_bigint(value) {
+            Some(val) => return Ok(new_int(val)),
+            None => {
+                return Err(type_error(format!(
+                    "int() argument must be a string, a bytes-like object or a real number, not '{}'",
+                    value.type_name()
+                )));
+            }
+        },
+    }
+}
+
+/* float() string syntax: Rust's, plus underscores between digits
+ */
+/* underscores in a number are only allowed between digits
+ */
+fn valid_underscores(text: &str) -> bool {
+    let chars: Vec<char> = text.chars().collect();
+    for (index, c) in chars.iter().enumerate() {
+        if *c == '_' {
+            let digit_before = index > 0 && chars[index - 1].is_ascii_digit();
+            let digit_after = chars.get(index + 1).map(char::is_ascii_digit).unwrap_or(false);
+            if !digit_before || !digit_after {
+                return false;
+            }
+        }
+    }
+    return true;
+}
+
+fn parse_float(text: &str) -> Option<f64> {
+    let text = text.trim();
+    if !valid_underscores(text) {
+        return None;
+    }
+    return text.replace('_', "").parse::<f64>().ok();
+}
+
+fn float_new(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("float", 0, 1)?;
+    let value = match args.positional.first() {
+        Some(value) => value,
+        None => return Ok(Value::Float(0.0)),
+    };
+    if let Value::Str(text) = value {
+        match parse_float(text) {
+            Some(val) => return Ok(Value::Float(val)),
+            None => return Err(value_error(format!("could not convert string to float: {}", value.repr()))),
+        }
+    }
+    match value {
+        Value::Fraction(val) => return Ok(Value::Float(fractions::to_f64(val))),
+        Value::Decimal(val) => return Ok(Value::Float(decimal::to_float(val)?)),
+        Value::BigInt(val) => return Ok(Value::Float(int_to_float(val)?)),
+        _ => (),
+    };
+    match as_float(value) {
+        Some(val) => return Ok(Value::Float(val)),
+        None => {
+            return Err(type_error(format!(
+                "float() argument must be a string or a real number, not '{}'",
+                value.type_name()
+            )));
+        }
+    }
+}
+
+/* The longest prefix of text that's a float, as float() reads it (but
+ * without underscores), with its length
+ */
+fn float_prefix(text: &str) -> Option<(f64, usize)> {
+    let bytes = text.as_bytes();
+    let mut end = 0;
+    if matches!(bytes.first(), Some(b'+' | b'-')) {
+        end += 1;
+    }
+    let sign = if bytes.first() == Some(&b'-') { -1.0 } else { 1.0 };
+    let rest = text[end..].to_ascii_lowercase();
+    for (word, val) in [("infinity", f64::INFINITY), ("inf", f64::INFINITY), ("nan", f64::NAN)] {
+        if rest.starts_with(word) {
+            return Some((sign * val, end + word.len()));
+        }
+    }
+    let digits = |from: usize| bytes[from..].iter().take_while(|c| c.is_ascii_digit()).count();
+    let mut mantissa = digits(end);
+    end += mantissa;
+    if bytes.get(end) == Some(&b'.') {
+        let fraction = digits(end + 1);
+        mantissa += fraction;
+        end += 1 + fraction;
+    }
+    if mantissa == 0 {
+        return None;
+    }
+    if matches!(bytes.get(end), Some(b'e' | b'E')) {
+        let signed = matches!(bytes.get(end + 1), Some(b'+' | b'-')) as usize;
+        let exponent = digits(end + 1 + signed);
+        if exponent > 0 {
+            end += 1 + signed + exponent;
+        }
+    }
+    return Some((text[..end].parse().ok()?, end));
+}
+
+/* complex("1+2j"): a real part, an imaginary part ending in j, or both, in
+ * optional parentheses. "j", "-j" and "1+j" mean an imaginary part of 1.
+ */
+fn parse_complex(text: &str) -> Option<(f64, f64)> {
+    let mut rest = text.trim();
+    let bracketed = rest.starts_with('(');
+    if bracketed {
+        rest = rest[1..].trim_start();
+        rest = rest.strip_suffix(')')?.trim_end();
+    }
+    let (real, imag);
+    match float_prefix(rest) {
+        Some((first, len)) => {
+            rest = &rest[len..];
+            if rest.starts_with('+') || rest.starts_with('-') {
+                real = first;
+                match float_prefix(rest) {
+                    Some((second, len)) => {
+                        imag = second;
+                        rest = &rest[len..];
+                    }
+                    None => {
+                        imag = if rest.starts_with('+') { 1.0 } else { -1.0 };
+                        rest = &rest[1..];
+                    }
+                };
+                rest = rest.strip_prefix(['j', 'J'])?;
+            } else if let Some(after) = rest.strip_prefix(['j', 'J']) {
+                real = 0.0;
+                imag = first;
+                rest = after;
+            } else {
+                real = first;
+                imag = 0.0;
+            }
+        }
+        None => {
+            real = 0.0;
+            imag = if rest.starts_with('-') { -1.0 } else { 1.0 };
+            rest = rest.strip_prefix(['+', '-']).unwrap_or(rest);
+            rest = rest.strip_prefix(['j', 'J'])?;
+        }
+    };
+    if !rest.is_empty() {
+        return None;
+    }
+    return Some((real, imag));
+}
+
+/* an argument complex() can convert: a number, or an object with
+ * __complex__, __float__ or __index__
+ */
+fn is_number(value: &Value) -> bool {
+    match value {
+        Value::Object(object) => {
+            return ["__complex__", "__float__", "__index__"].iter().any(|name| object.class.lookup(name).is_some());
+        }
+        Value::Fraction(_) | Value::Decimal(_) => return true,
+        _ => return as_complex(value).is_some(),
+    }
+}
+
+fn complex_new(interpreter: &mut Interpreter, mut args: Args) -> EvalResult {
+    let real = optional_arg(&mut args, 0, "real").unwrap_or(Value::Int(0));
+    let imag = optional_arg(&mut args, 1, "imag");
+    args.check("complex", 0, 2)?;
+    if let Value::Str(text) = &real {
+        if imag.is_some() {
+            return Err(type_error(String::from("complex() can't take second arg if first is a string")));
+        }
+        if !valid_underscores(text) {
+            return Err(value_error(format!("could not convert string to complex: {}", real.repr())));
+        }
+        match parse_complex(&text.replace('_', "")) {
+            Some((real, imag)) => return Ok(Value::Complex(real, imag)),
+            None => return Err(value_error(String::from("complex() arg is a malformed string"))),
+        }
+    }
+    if let Some(Value::Str(_)) = imag {
+        return Err(type_error(String::from("complex() second arg can't be a string")));
+    }
+    if !is_number(&real) {
+        return Err(type_error(format!(
+            "complex() first argument must be a string or a number, not '{}'",
+            real.type_name()
+        )));
+    }
+    if let Some(imag) = imag.as_ref().filter(|imag| !is_number(imag)) {
+        return Err(type_error(format!(
+            "complex() second argument must be a number, not '{}'",
+            imag.type_name()
+        )));
+    }
+    // (a + bj) + (c + dj) j, adding only the parts that are there so the
+    // signs of zeros are kept
+    let is_complex = |value: &Value| match value {
+        Value::Complex(..) => true,
+        Value::Object(object) => object.class.lookup("__complex__").is_some(),
+        _ => false,
+    };
+    let (mut result_real, real_imag) = cmath::complex(interpreter, &real)?;
+    let mut result_imag = real_imag;
+    if let Some(imag) = imag {
+        let (imag_real, imag_imag) = cmath::complex(interpreter, &imag)?;
+        result_imag = imag_real;
+        if is_complex(&imag) {
+            result_real -= imag_imag;
+        }
+        if is_complex(&real) {
+            result_imag += real_imag;
+        }
+    }
+    return Ok(Value::Complex(result_real, result_imag));
+}
+
+fn complex_conjugate(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("conjugate", 1, 1)?;
+    match &args.positional[0] {
+        Value::Complex(real, imag) => return Ok(Value::Complex(*real, -imag)),
+        other => {
+            return Err(type_error(format!(
+                "descriptor 'conjugate' for 'complex' objects doesn't apply to a '{}' object",
+                other.type_name()
+            )));
+        }
+    }
+}
+
+/* str(object=''), or str(object, encoding='utf-8', errors='strict'),
+ * which decodes a bytes-like object
+ */
+fn str_new(interpreter: &mut Interpreter, mut args: Args) -> EvalResult {
+    let decoding = args.positional.len() > 1 || args.keywords.iter().any(|(key, _)| key == "encoding" || key == "errors");
+    let (encoding, errors) = bytes::codec_args("str", &mut args, 1)?;
+    args.check("str", 0, 3)?;
+    let object = match args.positional.first() {
+        Some(object) => object,
+        None => return Ok(Value::Str(String::new())),
+    };
+    if !decoding {
+        return Ok(Value::Str(interpreter.str(object)?));
+    }
+    match bytes::contents(object) {
+        Some(data) => return Ok(Value::Str(bytes::decode(&data, &encoding, &errors)?)),
+        None if matches!(object, Value::Str(_)) => return Err(type_error(String::from("decoding str is not supported"))),
+        None => {
+            return Err(type_error(format!(
+                "decoding to str: need a bytes-like object, {} found",
+                object.type_name()
+            )));
+        }
+    }
+}
+
+fn bool_new(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("bool", 0, 1)?;
+    match args.positional.first() {
+        Some(value) => return Ok(Value::Bool(interpreter.truthy(value)?)),
+        None => return Ok(Value::Bool(false)),
+    }
+}
+
+fn repr(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("repr", 1, 1)?;
+    return Ok(Value::Str(interpreter.repr(&args.positional[0])?));
+}
+
+fn ascii(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("ascii", 1, 1)?;
+    return Ok(Value::Str(format::ascii_escape(&interpreter.repr(&args.positional[0])?)));
+}
+
+/* format(value, format_spec='')
+ */
+fn format(interpreter: &mut Interpreter, mut args: Args) -> EvalResult {
+    let spec = optional_arg(&mut args, 1, "format_spec");
+    args.check("format", 1, 2)?;
+    let spec = match spec {
+        None => String::new(),
+        Some(Value::Str(ref spec)) => spec.clone(),
+        Some(other) => return Err(type_error(format!("format() argument 2 must be str, not {}", other.type_name()))),
+    };
+    return Ok(Value::Str(format::format(interpreter, &args.positional[0], &spec)?));
+}
+
+fn list_new(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("list", 0, 1)?;
+    let items = match args.positional.first() {
+        Some(iterable) => interpreter.iterate(iterable)?,
+        None => Vec::new(),
+    };
+    return Ok(new_list(items));
+}
+
+fn tuple_new(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("tuple", 0, 1)?;
+    match args.positional.first() {
+        Some(Value::Tuple(items)) => return Ok(Value::Tuple(items.clone())),
+        Some(iterable) => return Ok(new_tuple(interpreter.iterate(iterable)?)),
+        None => return Ok(new_tuple(Vec::new())),
+    }
+}
+
+/* dict(mapping or iterable of pairs, **kwargs)
+ */
+fn dict_new(interpreter: &mut Interpreter, mut args: Args) -> EvalResult {
+    let keywords = std::mem::take(&mut args.keywords);
+    args.check("dict", 0, 1)?;
+    let dict = Rc::new(RefCell::new(Dict::build_new()));
+    if let Some(source) = args.positional.first() {
+        containers::update_dict(interpreter, &dict, source)?;
+    }
+    for (key, value) in keywords {
+        dict.borrow_mut().set(Value::Str(key), value)?;
+    }
+    let dict = dict.replace(Dict::build_new());
+    return Ok(new_dict(dict));
+}
+
+fn range_new(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("range", 1, 3)?;
+    let bounds: Vec<i64> = args.positional.iter().map(expect_int).collect::<Result<_, _>>()?;
+    let (start, stop, step) = match bounds[..] {
+        [stop] => (0, stop, 1),
+        [start, stop] => (start, stop, 1),
+        [start, stop, step] => (start, stop, step),
+        _ => unreachable!(),
+    };
+    if step == 0 {
+        return Err(value_error(String::from("range() arg 3 must not be zero")));
+    }
+    return Ok(Value::Range(start, stop, step));
+}
+
+fn enumerate_new(interpreter: &mut Interpreter, mut args: Args) -> EvalResult {
+    let start = optional_arg(&mut args, 1, "start");
+    args.check("enumerate", 1, 2)?;
+    let start = match start {
+        Some(start) => expect_int(&start)?,
+        None => 0,
+    };
+    let iterator = interpreter.get_iter(&args.positional[0])?;
+    return Ok(new_iterator(Iter::Enumerate(iterator, start)));
+}
+
+fn zip_new(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("zip", 0, usize::MAX)?;
+    let mut iterators: Vec<Value> = Vec::new();
+    for iterable in &args.positional {
+        iterators.push(interpreter.get_iter(iterable)?);
+    }
+    return Ok(new_iterator(Iter::Zip(iterators)));
+}
+
+fn map_new(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    if args.positional.len() < 2 {
+        return Err(type_error(String::from("map() must have at least two arguments.")));
+    }
+    args.check("map", 2, usize::MAX)?;
+    let mut iterators: Vec<Value> = Vec::new();
+    for iterable in &args.positional[1..] {
+        iterators.push(interpreter.get_iter(iterable)?);
+    }
+    return Ok(new_iterator(Iter::Map(args.positional[0].clone(), iterators)));
+}
+
+fn filter_new(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("filter", 2, 2)?;
+    let iterator = interpreter.get_iter(&args.positional[1])?;
+    return Ok(new_iterator(Iter::Filter(args.positional[0].clone(), iterator)));
+}
+
+fn reversed_new(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("reversed", 1, 1)?;
+    if let Some(result) = interpreter.call_special(&args.positional[0], "__reversed__", vec![]) {
+        return result;
+    }
+    let mut items = match &args.positional[0] {
+        Value::Range(start, stop, step) => {
+            let len = range_len(*start, *stop, *step);
+            let last = start.wrapping_add((len - 1).wrapping_mul(*step));
+            return Ok(new_iterator(Iter::Range(last, len, -step)));
+        }
+        Value::List(items) => items.borrow().clone(),
+        Value::Tuple(items) => items.to_vec(),
+        Value::Str(val) => val.chars().map(|c| Value::Str(c.to_string())).collect(),
+        value if value.as_dict().is_some() => value.as_dict().unwrap().borrow().keys(),
+        value @ (Value::Bytes(_) | Value::ByteArray(_) | Value::MemoryView(_)) => {
+            bytes::buffer(value)?.into_iter().map(|byte| Value::Int(byte as i64)).collect()
+        }
+        other => return Err(type_error(format!("'{}' object is not reversible", other.type_name()))),
+    };
+    items.reverse();
+    return Ok(new_iterator(Iter::Items(items, 0, "reversed")));
+}
+
+fn abs(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("abs", 1, 1)?;
+    let value = &args.positional[0];
+    if let Value::Float(val) = value {
+        return Ok(Value::Float(val.abs()));
+    }
+    if let Value::Complex(real, imag) = value {
+        let result = cmath::magnitude((*real, *imag));
+        if result.is_infinite() && real.is_finite() && imag.is_finite() {
+            return Err(Exception::new("OverflowError", "absolute value too large"));
+        }
+        return Ok(Value::Float(result));
+    }
+    match value {
+        Value::Fraction(val) => return Ok(fractions::new_fraction(num_traits::Signed::abs(&**val))),
+        Value::Decimal(val) => return decimal::abs(val),
+        _ => (),
+    };
+    match as_bigint(value) {
+        Some(val) => return Ok(new_int(val.abs())),
+        None => return Err(type_error(format!("bad operand type for abs(): '{}'", value.type_name()))),
+    }
+}
+
+/* min() and max(): op is the comparison an item must pass against the best
+ * so far to replace it, so the first of equal items wins
+ */
+fn min_max(interpreter: &mut Interpreter, mut args: Args, name: &str, op: CmpOp) -> EvalResult {
+    let key = args.keyword("key").filter(|key| *key != Value::NoneT);
+    let default = args.keyword("default");
+    args.check(name, 1, usize::MAX)?;
+    let items = if args.positional.len() == 1 {
+        interpreter.iterate(&args.positional[0])?
+    } else {
+        if default.is_some() {
+            return Err(type_error(format!(
+                "Cannot specify a default for {}() with multiple positional arguments",
+                name
+            )));
+        }
+        args.positional
+    };
+    let mut best: Option<(Value, Value)> = None;
+    for item in items {
+        let item_key = match &key {
+            Some(key) => interpreter.call(key, Args::build_new(vec![item.clone()]))?,
+            None => item.clone(),
+        };
+        let replace = match &best {
+            Some((best_key, _)) => interpreter.rich_compare(op, &item_key, best_key)?,
+            None => true,
+        };
+        if replace {
+            best = Some((item_key, item));
+        }
+    }
+    match (best, default) {
+        (Some((_, item)), _) => return Ok(item),
+        (None, Some(default)) => return Ok(default),
+        (None, None) => return Err(value_error(format!("{}() iterable argument is empty", name))),
+    }
+}
+
+fn min(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    return min_max(interpreter, args, "min", CmpOp::Lt);
+}
+
+fn max(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    return min_max(interpreter, args, "max", CmpOp::Gt);
+}
+
+fn sum(interpreter: &mut Interpreter, mut args: Args) -> EvalResult {
+    let start = optional_arg(&mut args, 1, "start");
+    args.check("sum", 1, 2)?;
+    let mut total = start.unwrap_or(Value::Int(0));
+    match total {
+        Value::Str(_) => return Err(type_error(String::from("sum() can't sum strings [use ''.join(seq) instead]"))),
+        Value::Bytes(_) => return Err(type_error(String::from("sum() can't sum bytes [use b''.join(seq) instead]"))),
+        Value::ByteArray(_) => {
+            return Err(type_error(String::from("sum() can't sum bytearray [use b''.join(seq) instead]")));
+        }
+        _ => (),
+    };
+    let iterator = interpreter.get_iter(&args.positional[0])?;
+    while let Some(item) = interpreter.next_item(&iterator)? {
+        total = binary_op(BinOp::Add, &total, &item)?;
+    }
+    return Ok(total);
+}
+
+/* Stable merge sort of (key, item) pairs using only less, a `<` which may
+ * fail
+ */
+pub(crate) fn merge_sort(
+    mut items: Vec<(Value, Value)>,
+    reverse: bool,
+    less: &mut impl FnMut(&Value, &Value) -> Result<bool, Exception>,
+) -> Result<Vec<(Value, Value)>, Exception> {
+    if items.len() <= 1 {
+        return Ok(items);
+    }
+    let right = items.split_off(items.len() / 2);
+    let mut left = merge_sort(items, reverse, less)?.into_iter().peekable();
+    let mut right = merge_sort(right, reverse, less)?.into_iter().peekable();
+    let mut merged: Vec<(Value, Value)> = Vec::new();
+    while let (Some((left_key, _)), Some((right_key, _))) = (left.peek(), right.peek()) {
+        // an item from the right run only goes first if strictly before
+        let take_right = if reverse {
+            less(left_key, right_key)?
+        } else {
+            less(right_key, left_key)?
+        };
+        merged.push(if take_right { right.next() } else { left.next() }.unwrap());
+    }
+    merged.extend(left);
+    merged.extend(right);
+    return Ok(merged);
+}
+
+fn sorted(interpreter: &mut Interpreter, mut args: Args) -> EvalResult {
+    let key = args.keyword("key").filter(|key| *key != Value::NoneT);
+    let reverse = args.keyword("reverse").map(|reverse| reverse.is_truthy()).unwrap_or(false);
+    args.check("sorted", 1, 1)?;
+    let items = interpreter.iterate(&args.positional[0])?;
+    return Ok(new_list(sort_values(interpreter, items, key, reverse)?));
+}
+
+/* items sorted by key(item), or by the items themselves, as sorted() and
+ * list.sort() do it
+ */
+pub(crate) fn sort_values(interpreter: &mut Interpreter, items: Vec<Value>, key: Option<Value>, reverse: bool) -> Result<Vec<Value>, Exception> {
+    let mut pairs: Vec<(Value, Value)> = Vec::new();
+    for item in items {
+        let item_key = match &key {
+            Some(key) => interpreter.call(key, Args::build_new(vec![item.clone()]))?,
+            None => item.clone(),
+        };
+        pairs.push((item_key, item));
+    }
+    let mut less = |left: &Value, right: &Value| interpreter.rich_compare(CmpOp::Lt, left, right);
+    return Ok(merge_sort(pairs, reverse, &mut less)?.into_iter().map(|(_, item)| item).collect());
+}
+
+fn any(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("any", 1, 1)?;
+    let iterator = interpreter.get_iter(&args.positional[0])?;
+    while let Some(item) = interpreter.next_item(&iterator)? {
+        if item.is_truthy() {
+            return Ok(Value::Bool(true));
+        }
+    }
+    return Ok(Value::Bool(false));
+}
+
+fn all(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("all", 1, 1)?;
+    let iterator = interpreter.get_iter(&args.positional[0])?;
+    while let Some(item) = interpreter.next_item(&iterator)? {
+        if !item.is_truthy() {
+            return Ok(Value::Bool(false));
+        }
+    }
+    return Ok(Value::Bool(true));
+}
+
+/* round an int to a negative number of digits, halves to even
+ */
+fn round_int(val: &BigInt, ndigits: i64) -> BigInt {
+    if ndigits >= 0 {
+        return val.clone();
+    }
+    // a unit with more digits than val rounds it to 0
+    let digits = ndigits.unsigned_abs();
+    if digits > val.bits() {
+        return BigInt::zero();
+    }
+    let unit = BigInt::from(10).pow(digits as u32);
+    let (quotient, remainder) = val.div_mod_floor(&unit);
+    let rounded = match (remainder * BigInt::from(2)).cmp(&unit) {
+        std::cmp::Ordering::Greater => quotient + 1,
+        std::cmp::Ordering::Equal if quotient.is_odd() => quotient + 1,
+        _ => quotient,
+    };
+    return rounded * unit;
+}
+
+/* round a float to ndigits decimal places, halves to even. Formatting
+ * rounds the exact binary value, so 2.675 (really 2.67499...) gives 2.67
+ * like CPython.
+ */
+fn round_float(val: f64, ndigits: i64) -> f64 {
+    if !val.is_finite() || ndigits > 308 {
+        return val;
+    }
+    if ndigits >= 0 {
+        return format!("{:.*}", ndigits as usize, val).parse().unwrap();
+    }
+    if ndigits < -308 {
+        return 0.0 * val;
+    }
+    let unit = 10f64.powi(-ndigits as i32);
+    return (val / unit).round_ties_even() * unit;
+}
+
+fn round(_interpreter: &mut Interpreter, mut args: Args) -> EvalResult {
+    let ndigits = optional_arg(&mut args, 1, "ndigits").filter(|ndigits| *ndigits != Value::NoneT);
+    args.check("round", 1, 2)?;
+    let number = &args.positional[0];
+    let ndigits = match ndigits {
+        Some(ndigits) => Some(expect_int(&ndigits)?),
+        None => None,
+    };
+    match (number, ndigits) {
+        (Value::Float(val), None) => return float_to_int(val.round_ties_even()),
+        (Value::Float(val), Some(ndigits)) => return Ok(Value::Float(round_float(*val, ndigits))),
+        (Value::Fraction(val), _) => return fractions::round(val, ndigits),
+        (Value::Decimal(val), _) => return decimal::round(val, ndigits),
+        _ => match as_bigint(number) {
+            Some(val) => return Ok(new_int(round_int(&val, ndigits.unwrap_or(0)))),
+            None => {
+                return Err(type_error(format!("type {} doesn't define __round__ method", number.type_name())));
+            }
+        },
+    }
+}
+
+fn divmod(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("divmod", 2, 2)?;
+    let (left, right) = (&args.positional[0], &args.positional[1]);
+    let quotient = binary_op(BinOp::FloorDiv, left, right)?;
+    let remainder = binary_op(BinOp::Mod, left, right)?;
+    return Ok(new_tuple(vec![quotient, remainder]));
+}
+
+/* modular inverse by the extended Euclidean algorithm
+ */
+fn mod_inverse(val: &BigInt, modulus: &BigInt) -> Option<BigInt> {
+    let (mut old_r, mut r) = (val.mod_floor(modulus), modulus.clone());
+    let (mut old_s, mut s) = (BigInt::one(), BigInt::zero());
+    while !r.is_zero() {
+        let quotient = &old_r / &r;
+        let next_r = &old_r - &quotient * &r;
+        old_r = std::mem::replace(&mut r, next_r);
+        let next_s = &old_s - &quotient * &s;
+        old_s = std::mem::replace(&mut s, next_s);
+    }
+    if !old_r.is_one() {
+        return None;
+    }
+    return Some(old_s.mod_floor(modulus));
+}
+
+fn pow(_interpreter: &mut Interpreter, mut args: Args) -> EvalResult {
+    let modulus = optional_arg(&mut args, 2, "mod").filter(|modulus| *modulus != Value::NoneT);
+    args.check("pow", 2, 3)?;
+    let (base, exp) = (&args.positional[0], &args.positional[1]);
+    let modulus = match modulus {
+        Some(modulus) => modulus,
+        None => return binary_op(BinOp::Pow, base, exp),
+    };
+    let (base, exp, modulus) = match (as_bigint(base), as_bigint(exp), as_bigint(&modulus)) {
+        (Some(base), Some(exp), Some(modulus)) => (base, exp, modulus),
+        _ => {
+            return Err(type_error(String::from(
+                "pow() 3rd argument not allowed unless all arguments are integers",
+            )));
+        }
+    };
+    if modulus.is_zero() {
+        return Err(value_error(String::from("pow() 3rd argument cannot be 0")));
+    }
+    let size = modulus.abs();
+    let mut base = base.mod_floor(&size);
+    if exp.is_negative() {
+        base = match mod_inverse(&base, &size) {
+            Some(inverse) => inverse,
+            None => return Err(value_error(String::from("base is not invertible for the given modulus"))),
+        };
+    }
+    let mut result = base.modpow(&exp.abs(), &size);
+    // the result takes the sign of the modulus, like %
+    if modulus.is_negative() && !result.is_zero() {
+        result += modulus;
+    }
+    return Ok(new_int(result));
+}
+
+fn hash(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("hash", 1, 1)?;
+    return Ok(Value::Int(interpreter.hash_value(&args.positional[0])?));
+}
+
+fn id(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("id", 1, 1)?;
+    let value = &args.positional[0];
+    let identity = match value.identity() {
+        // plain values have no address: equal values share an id
+        0 => HashKey::from_value(value).map(|key| key.hash_value()).unwrap_or(0),
+        identity => identity as i64,
+    };
+    return Ok(Value::Int(identity));
+}
+
+fn iter(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("iter", 1, 2)?;
+    if let [func, sentinel] = &args.positional[..] {
+        if !is_callable(func) {
+            return Err(type_error(String::from("iter(v, w): v must be callable")));
+        }
+        return Ok(new_iterator(Iter::Callable(Some((func.clone(), sentinel.clone())))));
+    }
+    return interpreter.get_iter(&args.positional[0]);
+}
+
+fn next(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("next", 1, 2)?;
+    match (interpreter.next_item(&args.positional[0])?, args.positional.get(1)) {
+        (Some(item), _) => return Ok(item),
+        (None, Some(default)) => return Ok(default.clone()),
+        (None, None) => return Err(Exception::new("StopIteration", "")),
+    }
+}
+
+fn chr(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("chr", 1, 1)?;
+    let code = expect_int(&args.positional[0])?;
+    match u32::try_from(code).ok().and_then(char::from_u32) {
+        Some(c) => return Ok(Value::Str(c.to_string())),
+        None => return Err(value_error(String::from("chr() arg not in range(0x110000)"))),
+    }
+}
+
+fn ord(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("ord", 1, 1)?;
+    match &args.positional[0] {
+        Value::Str(val) => {
+            let mut chars = val.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => return Ok(Value::Int(c as i64)),
+                _ => {
+                    return Err(type_error(format!(
+                        "ord() expected a character, but string of length {} found",
+                        val.chars().count()
+                    )));
+                }
+            }
+        }
+        value @ (Value::Bytes(_) | Value::ByteArray(_)) => {
+            let data = bytes::contents(value).unwrap();
+            if data.len() != 1 {
+                return Err(type_error(format!(
+                    "ord() expected a character, but string of length {} found",
+                    data.len()
+                )));
+            }
+            return Ok(Value::Int(data[0] as i64));
+        }
+        other => {
+            return Err(type_error(format!(
+                "ord() expected string of length 1, but {} found",
+                other.type_name()
+            )));
+        }
+    }
+}
+
+/* hex(), oct() and bin(): prefix and digits, with the sign in front
+ */
+fn format_radix(name: &str, args: Args, prefix: &str, radix: u32) -> EvalResult {
+    args.check(name, 1, 1)?;
+    let val = as_bigint(&args.positional[0]).ok_or_else(|| not_an_int(&args.positional[0]))?;
+    let digits = val.magnitude().to_str_radix(radix);
+    let sign = if val.is_negative() { "-" } else { "" };
+    return Ok(Value::Str(format!("{}{}{}", sign, prefix, digits)));
+}
+
+fn hex(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    return format_radix("hex", args, "0x", 16);
+}
+
+fn oct(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    return format_radix("oct", args, "0o", 8);
+}
+
+fn bin(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    return format_radix("bin", args, "0b", 2);
+}
+
+fn attribute_name(value: &Value) -> Result<&str, Exception> {
+    match value {
+        Value::Str(name) => return Ok(name),
+        other => return Err(type_error(format!("attribute name must be string, not '{}'", other.type_name()))),
+    }
+}
+
+fn getattr(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("getattr", 2, 3)?;
+    let name = attribute_name(&args.positional[1])?;
+    match (interpreter.get_attr(&args.positional[0], name), args.positional.get(2)) {
+        (Err(err), Some(default)) if err.kind == "AttributeError" => return Ok(default.clone()),
+        (result, _) => return result,
+    }
+}
+
+fn setattr(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("setattr", 3, 3)?;
+    let name = attribute_name(&args.positional[1])?;
+    interpreter.set_attr(&args.positional[0], name, args.positional[2].clone())?;
+    return Ok(Value::NoneT);
+}
+
+fn hasattr(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("hasattr", 2, 2)?;
+    let name = attribute_name(&args.positional[1])?;
+    match interpreter.get_attr(&args.positional[0], name) {
+        Ok(_) => return Ok(Value::Bool(true)),
+        Err(err) if err.kind == "AttributeError" => return Ok(Value::Bool(false)),
+        Err(err) => return Err(err),
+    }
+}
+
+pub(crate) fn is_callable(value: &Value) -> bool {
+    match value {
+        Value::Function(_) | Value::Builtin(_) | Value::Class(_) | Value::Method(_) => return true,
+        Value::Object(object) => return object.class.lookup("__call__").is_some(),
+        _ => return false,
+    }
+}
+
+fn callable(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("callable", 1, 1)?;
+    return Ok(Value::Bool(is_callable(&args.positional[0])));
+}
+
+/* vars(object), or the current local namespace. Returns a snapshot rather
+ * than the live namespace.
+ */
+fn vars(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("vars", 0, 1)?;
+    let dict = match args.positional.first() {
+        None => match &interpreter.scope {
+            Some(scope) => Dict::from_strings(&scope.vars.borrow()),
+            None => Dict::from_strings(&interpreter.globals.vars.borrow()),
+        },
+        Some(Value::Module(module)) => Dict::from_strings(&module.vars.borrow()),
+        Some(Value::Class(class)) => Dict::from_strings(&class.attrs.borrow()),
+        Some(Value::Object(instance)) if instance.native.is_none() => Dict::from_strings(&instance.attrs.borrow()),
+        Some(_) => return Err(type_error(String::from("vars() argument must have __dict__ attribute"))),
+    };
+    return Ok(new_dict(dict));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{error, eval, eval_str, exec, python};
+
+    #[test]
+    fn test_parse_numbers() {
+        assert_eq!(parse_int(" -0x_ff ", 0), Ok(Value::Int(-255)));
+        assert_eq!(parse_int("1_000", 10), Ok(Value::Int(1000)));
+        assert!(parse_int("010", 0).is_err());
+        assert!(parse_int("1__0", 10).is_err());
+        assert_eq!(parse_float(" 1_0.5 "), Some(10.5));
+        assert_eq!(parse_float("-inf"), Some(f64::NEG_INFINITY));
+        assert_eq!(parse_float("_1"), None);
+        assert_eq!(parse_complex(" (1.5-2e1J) "), Some((1.5, -20.0)));
+        assert_eq!(parse_complex("-j"), Some((0.0, -1.0)));
+        assert_eq!(parse_complex("1+j"), Some((1.0, 1.0)));
+        assert_eq!(parse_complex("1+2"), None);
+    }
+
+    #[test]
+    fn test_complex() {
+        let mut python = python("");
+        assert_eq!(eval_str(&mut python, "(1+2j) * (3-4j)"), "(11+2j)");
+        assert_eq!(eval_str(&mut python, "(1+2j) / 2 - 0.5j"), "(0.5+0.5j)");
+        assert_eq!(eval_str(&mut python, "(complex(1, -0.0), -0j, (1j) ** 2)"), "((1-0j), (-0-0j), (-1+0j))");
+        assert_eq!(eval_str(&mut python, "(abs(3+4j), (1+2j).conjugate(), (2-1j).imag)"), "(5.0, (1-2j), -1.0)");
+        assert_eq!(eval_str(&mut python, "(complex('1e3+j'), 1+0j == 1)"), "((1000+1j), True)");
+    }
+
+    #[test]
+    fn test_rounding() {
+        assert_eq!(round_int(&BigInt::from(25), -1), BigInt::from(20));
+        assert_eq!(round_int(&BigInt::from(35), -1), BigInt::from(40));
+        assert_eq!(round_int(&BigInt::from(-25), -1), BigInt::from(-20));
+        assert_eq!(round_int(&BigInt::from(12), i64::MIN), BigInt::zero());
+        assert_eq!(round_float(2.675, 2), 2.67);
+        assert_eq!(round_float(0.5, 0), 0.0);
+        assert_eq!(round_float(0.125, 2), 0.12);
+        assert_eq!(round_float(1250.0, -2), 1200.0);
+    }
+    #[test]
+    fn test_print_and_input() {
+        let mut python = python("import io, sys\nout = io.StringIO()\nsys.stdin = io.StringIO('one\\ntwo\\n')");
+        exec(&mut python, "saved = sys.stdout\nsys.stdout = out\nprint(1, 'a', None, sep='-', end='!\\n')\nprint()\nprint('x', file=out, flush=True)\nfirst = input()\nsecond = input('> ')\nsys.stdout = saved");
+        assert_eq!(eval(&mut python, "(out.getvalue(), first, second)"), "('1-a-None!\\n\\nx\\n> ', 'one', 'two')");
+        assert_eq!(error(&mut python, "print(sep=1)"), "TypeError: sep must be None or a string, not int");
+        assert_eq!(error(&mut python, "input(1, 2)"), "TypeError: input expected at most 1 argument, got 2");
+    }
+
+    #[test]
+    fn test_type_builtins() {
+        let mut python = python("class A:\n    pass\nclass B(A):\n    pass");
+        exec(&mut python, "checks = (isinstance(B(), A), isinstance(1, (str, int)), isinstance(True, int), issubclass(B, A), issubclass(A, (B, object)))");
+        assert_eq!(eval(&mut python, "checks"), "(True, True, True, True, True)");
+        assert_eq!(eval(&mut python, "(len('héllo'), len([1, 2]), len({1: 2}), len(range(0, 10, 3)), len(b'ab'), callable(len), callable(A), callable(1))"), "(5, 2, 1, 4, 2, True, True, False)");
+        assert_eq!(eval(&mut python, "(hash(1) == hash(1.0), hash('a') == hash('a'), id(A) == id(A), id(A()) != id(B), type(1), type(A()))"), "(True, True, True, True, <class 'int'>, <class '__main__.A'>)");
+        assert_eq!(error(&mut python, "len(1)"), "TypeError: object of type 'int' has no len()");
+        assert_eq!(error(&mut python, "hash([])"), "TypeError: unhashable type: 'list'");
+        assert_eq!(error(&mut python, "isinstance(1, 1)"), "TypeError: isinstance() arg 2 must be a type, a tuple of types, or a union");
+    }
+
+    #[test]
+    fn test_text_builtins() {
+        let mut python = python("");
+        exec(&mut python, "texts = (repr('a\\n'), ascii('héllo☃'), format(3.14159, '.2f'), format(42, '>5'), format('x'))");
+        assert_eq!(eval(&mut python, "texts"), "(\"'a\\\\n'\", \"'h\\\\xe9llo\\\\u2603'\", '3.14', '   42', 'x')");
+        assert_eq!(eval(&mut python, "(chr(65), ord('€'), hex(255), bin(-5), oct(8))"), "('A', 8364, '0xff', '-0b101', '0o10')");
+        assert_eq!(error(&mut python, "chr(-1)"), "ValueError: chr() arg not in range(0x110000)");
+        assert_eq!(error(&mut python, "ord('ab')"), "TypeError: ord() expected a character, but string of length 2 found");
+        assert_eq!(error(&mut python, "hex(1.5)"), "TypeError: 'float' object cannot be interpreted as an integer");
+        assert_eq!(error(&mut python, "format(1, 'q')"), "ValueError: Unknown format code 'q' for object of type 'int'");
+    }
+
+    #[test]
+    fn test_number_builtins() {
+        let mut python = python("");
+        exec(&mut python, "least = (min(3, 1, 2), min([4, 5]), min([3, 1], key=lambda v: -v))\nmost = (max('abc'), max([], default=0))");
+        assert_eq!(eval(&mut python, "(abs(-3), abs(-2.5), least, most)"), "(3, 2.5, (1, 4, 3), ('c', 0))");
+        assert_eq!(eval(&mut python, "(sum([1, 2, 3]), sum([0.1] * 3), sum([[1], [2]], []), sum(range(4), 10))"), "(6, 0.30000000000000004, [1, 2], 16)");
+        assert_eq!(eval(&mut python, "(round(2.5), round(3.5), round(2.675, 2), round(1234, -2), round(-0.5))"), "(2, 4, 2.67, 1200, 0)");
+        assert_eq!(eval(&mut python, "(divmod(7, -2), divmod(7.5, 2), pow(2, 10), pow(2, 10, 1000), pow(2, -1))"), "((-4, -1), (3.0, 1.5), 1024, 24, 0.5)");
+        assert_eq!(error(&mut python, "abs('a')"), "TypeError: bad operand type for abs(): 'str'");
+        assert_eq!(error(&mut python, "divmod(1, 0)"), "ZeroDivisionError: integer division or modulo by zero");
+        assert_eq!(error(&mut python, "pow(0, -1)"), "ZeroDivisionError: 0.0 cannot be raised to a negative power");
+        assert_eq!(error(&mut python, "sum(['a'], '')"), "TypeError: sum() can't sum strings [use ''.join(seq) instead]");
+        assert_eq!(error(&mut python, "round('a')"), "TypeError: type str doesn't define __round__ method");
+        assert_eq!(eval(&mut python, "round(12, -2**62 * 2)"), "0");
+        assert_eq!(eval(&mut python, "round(9223372036854775807, -1)"), "9223372036854775810");
+    }
+
+    #[test]
+    fn test_iteration_builtins() {
+        let mut python = python("");
+        exec(&mut python, "it = iter([7, 8])\nfirst = next(it)\nsecond = next(it)\ndone = next(it, 'done')");
+        assert_eq!(eval(&mut python, "(first, second, done, type(it), sorted([3, 1, 2], reverse=True), any([0, 1]), all([]), all([1, 0]))"), "(7, 8, 'done', <class 'list_iterator'>, [3, 2, 1], True, True, False)");
+        assert_eq!(eval(&mut python, "(list(enumerate('ab', 1)), list(zip('ab', [1, 2, 3])), list(map(abs, [-1, 2])), list(filter(None, [0, 1, 2])), list(reversed([1, 2])))"), "([(1, 'a'), (2, 'b')], [('a', 1), ('b', 2)], [1, 2], [1, 2], [2, 1])");
+        assert_eq!(error(&mut python, "next(iter([]))"), "StopIteration");
+        assert_eq!(error(&mut python, "iter(1)"), "TypeError: 'int' object is not iterable");
+        assert_eq!(error(&mut python, "sorted(1)"), "TypeError: 'int' object is not iterable");
+    }
+
+    #[test]
+    fn test_attribute_builtins() {
+        let mut python = python("import math\nclass A:\n    pass\na = A()\nsetattr(a, 'x', 1)");
+        assert_eq!(eval(&mut python, "(getattr(a, 'x'), getattr(a, 'y', 'default'), hasattr(a, 'x'), hasattr(a, 'y'), vars(a))"), "(1, 'default', True, False, {'x': 1})");
+        exec(&mut python, "def f():\n    local = 2\n    return vars()\nnames = f()");
+        assert_eq!(eval(&mut python, "(names, 'A' in vars(), 'pi' in vars(math))"), "({'local': 2}, True, True)");
+        assert_eq!(error(&mut python, "getattr(a, 'zz')"), "AttributeError: 'A' object has no attribute 'zz'");
+        assert_eq!(error(&mut python, "setattr(1, 'a', 2)"), "AttributeError: 'int' object has no attribute 'a'");
+        assert_eq!(error(&mut python, "vars(1)"), "TypeError: vars() argument must have __dict__ attribute");
+    }
+
+    #[test]
+    fn test_type_constructors() {
+        let mut python = python("");
+        assert_eq!(eval(&mut python, "(int('12'), float('1.5'), complex(1, 2), str(12), bool([]), list('ab'), tuple([1]), dict(a=1))"), "(12, 1.5, (1+2j), '12', False, ['a', 'b'], (1,), {'a': 1})");
+        assert_eq!(eval(&mut python, "(set([1]), frozenset(), range(3), bytes([65]), bytearray(b'x'), memoryview(b'ab')[0], type('x'), type(object()))"), "({1}, frozenset(), range(0, 3), b'A', bytearray(b'x'), 97, <class 'str'>, <class 'object'>)");
+        assert_eq!(error(&mut python, "int('x')"), "ValueError: invalid literal for int() with base 10: 'x'");
+    }
+}
@@ -0,0 +1,451 @@
+use crate::interpreter::{EvalResult, Interpreter};
+use crate::modules::math::{asinh, domain_error, range_error, real, scale, tolerances};
+use crate::value::{new_tuple, Args, Builtin, BuiltinFn, Exception, Module, Value};
+use std::f64::consts::{E, LN_10, LN_2, PI, TAU};
+use std::rc::Rc;
+
+/* cmath - functions of complex numbers, like CPython's cmath module
+ *
+ * Functions take complex numbers, ints, floats and bools, and instances
+ * whose class defines __complex__ or __float__, and return complex
+ * numbers. The algorithms for finite arguments are CPython's, as are the
+ * errors: ValueError "math domain error" e.g. for log(0), and
+ * OverflowError "math range error" for a result too large to represent.
+ * Arguments with infinite or NaN parts aren't looked up in C99's tables
+ * of special values the way CPython does; the same formulas are used,
+ * and a NaN they make from numbers that aren't NaN is a domain error.
+ */
+pub fn build_module() -> Rc<Module> {
+    let module = Module::build_new("cmath", None);
+    // (name, function, whether an infinite result for a finite argument is
+    // an overflow rather than a domain error)
+    let unary: &[Unary] = &[
+        ("acos", acos, false),
+        ("acosh", acosh, false),
+        ("asin", asin, false),
+        ("asinh", complex_asinh, false),
+        ("atan", atan, false),
+        ("atanh", atanh, false),
+        ("cos", cos, true),
+        ("cosh", cosh, true),
+        ("exp", exp, true),
+        ("log10", log10, false),
+        ("sin", sin, true),
+        ("sinh", sinh, true),
+        ("sqrt", sqrt, false),
+        ("tan", tan, false),
+        ("tanh", tanh, false),
+    ];
+    for (name, func, can_overflow) in unary {
+        let (name, func, can_overflow) = (*name, *func, *can_overflow);
+        let function = move |interpreter: &mut Interpreter, args: Args| {
+            args.check(name, 1, 1)?;
+            let z = complex(interpreter, &args.positional[0])?;
+            let (real, imag) = cmath_1(z, func(z), can_overflow)?;
+            return Ok(Value::Complex(real, imag));
+        };
+        module.set(name, Builtin::build_native(name, Rc::new(function)));
+    }
+    let functions: &[(&str, BuiltinFn)] = &[
+        ("isclose", isclose),
+        ("isfinite", isfinite),
+        ("isinf", isinf),
+        ("isnan", isnan),
+        ("log", log),
+        ("phase", phase),
+        ("polar", polar),
+        ("rect", rect),
+    ];
+    for (name, func) in functions {
+        module.set(name, Builtin::build_new(name, *func));
+    }
+    module.set("pi", Value::Float(PI));
+    module.set("e", Value::Float(E));
+    module.set("tau", Value::Float(TAU));
+    module.set("inf", Value::Float(f64::INFINITY));
+    module.set("infj", Value::Complex(0.0, f64::INFINITY));
+    module.set("nan", Value::Float(f64::NAN));
+    module.set("nanj", Value::Complex(0.0, f64::NAN));
+    return Rc::new(module);
+}
+
+// (real, imag)
+pub(crate) type Complex = (f64, f64);
+
+type Unary = (&'static str, fn(Complex) -> Complex, bool);
+
+// a number this large can be doubled without overflowing
+const LARGE_DOUBLE: f64 = f64::MAX / 4.0;
+
+/* complex value of a number argument
+ */
+pub(crate) fn complex(interpreter: &mut Interpreter, value: &Value) -> Result<Complex, Exception> {
+    if let Value::Complex(real, imag) = value {
+        return Ok((*real, *imag));
+    }
+    if let Some(result) = interpreter.call_special(value, "__complex__", Vec::new()) {
+        match result? {
+            Value::Complex(real, imag) => return Ok((real, imag)),
+            other => {
+                let message = format!("__complex__ returned non-complex (type {})", other.type_name());
+                return Err(Exception::new("TypeError", &message));
+            }
+        }
+    }
+    return Ok((real(interpreter, value)?, 0.0));
+}
+
+/* The errors of a function, as CPython works them out: an infinite
+ * result for a finite argument is an overflow or (e.g. log(0)) a domain
+ * error, and NaN from numbers that aren't NaN is a domain error
+ */
+fn cmath_1(z: Complex, result: Complex, can_overflow: bool) -> Result<Complex, Exception> {
+    let finite = |z: Complex| z.0.is_finite() && z.1.is_finite();
+    let nan = |z: Complex| z.0.is_nan() || z.1.is_nan();
+    if finite(z) && !finite(result) {
+        // e.g. cosh(1000) is inf * cos(0) + inf * sin(0) j, (inf, NaN)
+        if can_overflow && (result.0.is_infinite() || result.1.is_infinite()) {
+            return Err(range_error());
+        }
+        return Err(domain_error());
+    }
+    if nan(result) && !nan(z) {
+        return Err(domain_error());
+    }
+    return Ok(result);
+}
+
+/* a / b, by Smith's method to keep the intermediate results in range;
+ * None when b is 0
+ */
+pub(crate) fn quotient(a: Complex, b: Complex) -> Option<Complex> {
+    let (abs_real, abs_imag) = (b.0.abs(), b.1.abs());
+    if abs_real >= abs_imag {
+        if abs_real == 0.0 {
+            return None;
+        }
+        let ratio = b.1 / b.0;
+        let denom = b.0 + b.1 * ratio;
+        return Some(((a.0 + a.1 * ratio) / denom, (a.1 - a.0 * ratio) / denom));
+    }
+    if abs_imag >= abs_real {
+        let ratio = b.0 / b.1;
+        let denom = b.0 * ratio + b.1;
+        return Some(((a.0 * ratio + a.1) / denom, (a.1 * ratio - a.0) / denom));
+    }
+    // a part of b is NaN
+    return Some((f64::NAN, f64::NAN));
+}
+
+/* abs(z), which is infinite if either part is, even if the other is NaN
+ */
+pub(crate) fn magnitude(z: Complex) -> f64 {
+    if z.0.is_infinite() || z.1.is_infinite() {
+        return f64::INFINITY;
+    }
+    return z.0.hypot(z.1);
+}
+
+fn sqrt(z: Complex) -> Complex {
+    let (x, y) = z;
+    if x == 0.0 && y == 0.0 {
+        return (0.0, y);
+    }
+    let (ax, ay) = (x.abs(), y.abs());
+    let s = if ax < f64::MIN_POSITIVE && ay < f64::MIN_POSITIVE {
+        // hypot(ax, ay) would be subnormal, so scale up first
+        let ax = scale(ax, 53);
+        scale((ax + ax.hypot(scale(ay, 53))).sqrt(), -27)
+    } else {
+        let ax = ax / 8.0;
+        2.0 * (ax + ax.hypot(ay / 8.0)).sqrt()
+    };
+    let d = ay / (2.0 * s);
+    if x >= 0.0 {
+        return (s, d.copysign(y));
+    }
+    return (d, s.copysign(y));
+}
+
+fn exp(z: Complex) -> Complex {
+    let (x, y) = z;
+    // exp(x) may overflow when exp(z) doesn't
+    let (l, factor) = if x > LARGE_DOUBLE.ln() { ((x - 1.0).exp(), E) } else { (x.exp(), 1.0) };
+    if y == 0.0 {
+        return (l * factor, y);
+    }
+    return (l * y.cos() * factor, l * y.sin() * factor);
+}
+
+fn complex_log(z: Complex) -> Complex {
+    let (x, y) = z;
+    let (ax, ay) = (x.abs(), y.abs());
+    let real = if ax > LARGE_DOUBLE || ay > LARGE_DOUBLE {
+        (ax / 2.0).hypot(ay / 2.0).ln() + LN_2
+    } else if ax < f64::MIN_POSITIVE && ay < f64::MIN_POSITIVE {
+        if ax > 0.0 || ay > 0.0 {
+            // hypot(ax, ay) would be subnormal
+            scale(ax, 53).hypot(scale(ay, 53)).ln() - 53.0 * LN_2
+        } else {
+            f64::NEG_INFINITY
+        }
+    } else {
+        let h = ax.hypot(ay);
+        if (0.71..=1.73).contains(&h) {
+            // near 1, where log(h) loses precision
+            let (am, an) = if ax > ay { (ax, ay) } else { (ay, ax) };
+            ((am - 1.0) * (am + 1.0) + an * an).ln_1p() / 2.0
+        } else {
+            h.ln()
+        }
+    };
+    return (real, y.atan2(x));
+}
+
+fn log10(z: Complex) -> Complex {
+    let (real, imag) = complex_log(z);
+    return (real / LN_10, imag / LN_10);
+}
+
+fn acos(z: Complex) -> Complex {
+    let (x, y) = z;
+    if x.abs() > LARGE_DOUBLE || y.abs() > LARGE_DOUBLE {
+        // avoid overflow for large arguments
+        let real = y.abs().atan2(x);
+        let l = (x / 2.0).hypot(y / 2.0).ln() + LN_2 * 2.0;
+        if x < 0.0 {
+            return (real, -l.copysign(y));
+        }
+        return (real, l.copysign(-y));
+    }
+    let s1 = sqrt((1.0 - x, -y));
+    let s2 = sqrt((1.0 + x, y));
+    return (2.0 * s1.0.atan2(s2.0), asinh(s2.0 * s1.1 - s2.1 * s1.0));
+}
+
+fn acosh(z: Complex) -> Complex {
+    let (x, y) = z;
+    if x.abs() > LARGE_DOUBLE || y.abs() > LARGE_DOUBLE {
+        return ((x / 2.0).hypot(y / 2.0).ln() + LN_2 * 2.0, y.atan2(x));
+    }
+    let s1 = sqrt((x - 1.0, y));
+    let s2 = sqrt((x + 1.0, y));
+    return (asinh(s1.0 * s2.0 + s1.1 * s2.1), 2.0 * s1.1.atan2(s2.0));
+}
+
+/* asin(z) = -i asinh(iz)
+ */
+fn asin(z: Complex) -> Complex {
+    let s = complex_asinh((-z.1, z.0));
+    return (s.1, -s.0);
+}
+
+fn complex_asinh(z: Complex) -> Complex {
+    let (x, y) = z;
+    if x.abs() > LARGE_DOUBLE || y.abs() > LARGE_DOUBLE {
+        let l = (x / 2.0).hypot(y / 2.0).ln() + LN_2 * 2.0;
+        let real = if y >= 0.0 { l.copysign(x) } else { -l.copysign(-x) };
+        return (real, y.atan2(x.abs()));
+    }
+    let s1 = sqrt((1.0 + y, -x));
+    let s2 = sqrt((1.0 - y, x));
+    return (asinh(s1.0 * s2.1 - s2.0 * s1.1), y.atan2(s1.0 * s2.0 - s1.1 * s2.1));
+}
+
+/* atan(z) = -i atanh(iz)
+ */
+fn atan(z: Complex) -> Complex {
+    let s = atanh((-z.1, z.0));
+    return (s.1, -s.0);
+}
+
+fn atanh(z: Complex) -> Complex {
+    let (x, y) = z;
+    // atanh(z) = -atanh(-z)
+    if x < 0.0 {
+        let (real, imag) = atanh((-x, -y));
+        return (-real, -imag);
+    }
+    let ay = y.abs();
+    if x > LARGE_DOUBLE.sqrt() || ay > LARGE_DOUBLE.sqrt() {
+        let h = (x / 2.0).hypot(y / 2.0);
+        return (x / 4.0 / h / h, -(PI / 2.0).copysign(-y));
+    }
+    if x == 1.0 && ay < f64::MIN_POSITIVE.sqrt() {
+        if ay == 0.0 {
+            return (f64::INFINITY, y);
+        }
+        return (-(ay.sqrt() / ay.hypot(2.0).sqrt()).ln(), (2.0_f64.atan2(-ay) / 2.0).copysign(y));
+    }
+    let real = (4.0 * x / ((1.0 - x) * (1.0 - x) + ay * ay)).ln_1p() / 4.0;
+    let imag = -(-2.0 * y).atan2((1.0 - x) * (1.0 + x) - ay * ay) / 2.0;
+    return (real, imag);
+}
+
+/* cos(z) = cosh(iz)
+ */
+fn cos(z: Complex) -> Complex {
+    return cosh((-z.1, z.0));
+}
+
+fn cosh(z: Complex) -> Complex {
+    let (x, y) = z;
+    if x.abs() > LARGE_DOUBLE.ln() {
+        // cosh(x) may overflow when cosh(z) doesn't
+        let x_minus_one = x - 1.0_f64.copysign(x);
+        return (y.cos() * x_minus_one.cosh() * E, y.sin() * x_minus_one.sinh() * E);
+    }
+    return (y.cos() * x.cosh(), y.sin() * x.sinh());
+}
+
+/* sin(z) = -i sinh(iz)
+ */
+fn sin(z: Complex) -> Complex {
+    let s = sinh((-z.1, z.0));
+    return (s.1, -s.0);
+}
+
+fn sinh(z: Complex) -> Complex {
+    let (x, y) = z;
+    if x.abs() > LARGE_DOUBLE.ln() {
+        let x_minus_one = x - 1.0_f64.copysign(x);
+        return (y.cos() * x_minus_one.sinh() * E, y.sin() * x_minus_one.cosh() * E);
+    }
+    return (y.cos() * x.sinh(), y.sin() * x.cosh());
+}
+
+/* tan(z) = -i tanh(iz)
+ */
+fn tan(z: Complex) -> Complex {
+    let s = tanh((-z.1, z.0));
+    return (s.1, -s.0);
+}
+
+fn tanh(z: Complex) -> Complex {
+    let (x, y) = z;
+    if x.abs() > LARGE_DOUBLE.ln() {
+        return (1.0_f64.copysign(x), 4.0 * y.sin() * y.cos() * (-2.0 * x.abs()).exp());
+    }
+    let (tx, ty) = (x.tanh(), y.tan());
+    let cx = 1.0 / x.cosh();
+    let txty = tx * ty;
+    let denom = 1.0 + txty * txty;
+    return (tx * (1.0 + ty * ty) / denom, ((ty / denom) * cx) * cx);
+}
+
+/* log(z[, base])
+ */
+fn log(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("log", 1, 2)?;
+    let z = complex(interpreter, &args.positional[0])?;
+    let mut result = cmath_1(z, complex_log(z), false)?;
+    if let Some(base) = args.positional.get(1) {
+        let base = complex(interpreter, base)?;
+        let base = cmath_1(base, complex_log(base), false)?;
+        result = quotient(result, base).ok_or_else(domain_error)?;
+    }
+    return Ok(Value::Complex(result.0, result.1));
+}
+
+fn phase(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("phase", 1, 1)?;
+    let (x, y) = complex(interpreter, &args.positional[0])?;
+    return Ok(Value::Float(y.atan2(x)));
+}
+
+/* polar(z) = (abs(z), phase(z))
+ */
+fn polar(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("polar", 1, 1)?;
+    let z = complex(interpreter, &args.positional[0])?;
+    let r = magnitude(z);
+    if r.is_infinite() && z.0.is_finite() && z.1.is_finite() {
+        return Err(range_error());
+    }
+    return Ok(new_tuple(vec![Value::Float(r), Value::Float(z.1.atan2(z.0))]));
+}
+
+/* rect(r, phi): the complex number with modulus r and phase phi
+ */
+fn rect(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("rect", 2, 2)?;
+    let r = real(interpreter, &args.positional[0])?;
+    let phi = real(interpreter, &args.positional[1])?;
+    if r != 0.0 && !r.is_nan() && phi.is_infinite() {
+        return Err(domain_error());
+    }
+    if r == 0.0 && !phi.is_finite() {
+        return Ok(Value::Complex(r, r));
+    }
+    if r.is_infinite() && phi.is_finite() && phi != 0.0 {
+        // cos(phi) or sin(phi) may be 0, and inf * 0 is NaN
+        let sign = 1.0_f64.copysign(r);
+        return Ok(Value::Complex(sign * f64::INFINITY.copysign(phi.cos()), sign * f64::INFINITY.copysign(phi.sin())));
+    }
+    if phi == 0.0 {
+        return Ok(Value::Complex(r, r * phi));
+    }
+    return Ok(Value::Complex(r * phi.cos(), r * phi.sin()));
+}
+
+fn isfinite(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("isfinite", 1, 1)?;
+    let (x, y) = complex(interpreter, &args.positional[0])?;
+    return Ok(Value::Bool(x.is_finite() && y.is_finite()));
+}
+
+fn isinf(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("isinf", 1, 1)?;
+    let (x, y) = complex(interpreter, &args.positional[0])?;
+    return Ok(Value::Bool(x.is_infinite() || y.is_infinite()));
+}
+
+fn isnan(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("isnan", 1, 1)?;
+    let (x, y) = complex(interpreter, &args.positional[0])?;
+    return Ok(Value::Bool(x.is_nan() || y.is_nan()));
+}
+
+/* isclose(a, b, *, rel_tol=1e-09, abs_tol=0.0)
+ */
+fn isclose(interpreter: &mut Interpreter, mut args: Args) -> EvalResult {
+    let (rel_tol, abs_tol) = tolerances(interpreter, &mut args)?;
+    args.check("isclose", 2, 2)?;
+    let a = complex(interpreter, &args.positional[0])?;
+    let b = complex(interpreter, &args.positional[1])?;
+    if a == b {
+        return Ok(Value::Bool(true));
+    }
+    if [a.0, a.1, b.0, b.1].iter().any(|part| part.is_infinite()) {
+        return Ok(Value::Bool(false));
+    }
+    let diff = magnitude((a.0 - b.0, a.1 - b.1));
+    let close = diff <= rel_tol * magnitude(b) || diff <= rel_tol * magnitude(a) || diff <= abs_tol;
+    return Ok(Value::Bool(close));
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testing::{error, eval, python};
+
+    #[test]
+    fn test_functions() {
+        let mut python = python("import cmath\n");
+        assert_eq!(eval(&mut python, "cmath.sqrt(-1)"), "1j");
+        assert_eq!(eval(&mut python, "cmath.sqrt(-4).imag"), "2.0");
+        assert_eq!(eval(&mut python, "cmath.exp(cmath.log(-2))"), "(-2+2.4492935982947064e-16j)");
+        assert_eq!(eval(&mut python, "cmath.polar(cmath.rect(2, 0.5))"), "(2.0, 0.5)");
+        assert_eq!(eval(&mut python, "cmath.phase(-1)"), "3.141592653589793");
+        assert_eq!(eval(&mut python, "cmath.acos(2)"), "-1.3169578969248166j");
+        assert_eq!(eval(&mut python, "cmath.sqrt(-1) == cmath.sqrt(-1)"), "True");
+        assert_eq!(eval(&mut python, "(cmath.infj, cmath.isinf(cmath.infj))"), "(infj, True)");
+    }
+
+    #[test]
+    fn test_errors() {
+        let mut python = python("import cmath\n");
+        assert_eq!(error(&mut python, "cmath.log(0)"), "ValueError: math domain error");
+        assert_eq!(error(&mut python, "cmath.exp(1000)"), "OverflowError: math range error");
+        assert_eq!(error(&mut python, "cmath.sqrt('x')"), "TypeError: must be real number, not str");
+    }
+}
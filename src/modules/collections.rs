@@ -0,0 +1,1346 @@
+use crate::ast::{BinOp, CmpOp};
+use crate::dict::{new_dict, Dict, HashKey};
+use crate::format::subscript;
+use crate::exceptions::key_error;
+use crate::interpreter::{
+    as_int, binary_op, compare, contains, equals, format_names, items_equal, not_an_int, set_item, EvalResult,
+    Interpreter,
+};
+use crate::iterator::{new_iterator, Iter};
+use crate::modules::builtins::{is_callable, merge_sort};
+use crate::native::{bind, instance, with_native, NativeClass};
+use crate::scanner::KEYWORDS;
+use crate::value::{new_list, new_tuple, Args, Builtin, Class, Exception, Module, NativeFn, Object, Value};
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::rc::Rc;
+
+/* collections - container datatypes
+ *
+ * deque, defaultdict, OrderedDict, Counter and ChainMap are native classes,
+ * and namedtuple() makes one for each call. defaultdict, OrderedDict and
+ * Counter are subclasses of dict that keep their items in a Dict of their
+ * own: the dict methods, isinstance(), dict(), ** and json.dumps() all see
+ * it, and they override only what they change. Counters add, subtract and
+ * take the union and intersection of each other with + - | &.
+ */
+pub fn build_module(object: Rc<Class>, dict: Rc<Class>) -> Rc<Module> {
+    let module = Module::build_new("collections", None);
+    module.set_class(deque_class().build(object.clone()));
+    for kind in [Kind::DefaultDict, Kind::Ordered, Kind::Counter] {
+        module.set_class(mapping_class(kind).build(dict.clone()));
+    }
+    module.set_class(chain_map_class().build(object.clone()));
+    let namedtuple: NativeFn = Rc::new(move |interpreter, args| new_namedtuple(interpreter, args, &object));
+    module.set("namedtuple", Builtin::build_native("namedtuple", namedtuple));
+    return Rc::new(module);
+}
+
+fn unhashable(name: &str) -> Exception {
+    return Exception::new("TypeError", &format!("unhashable type: '{}'", name));
+}
+
+fn int_arg(value: &Value) -> Result<i64, Exception> {
+    return as_int(value).ok_or_else(|| not_an_int(value));
+}
+
+fn join_reprs<'a>(items: impl Iterator<Item = &'a Value>) -> String {
+    return items.map(|item| item.repr()).collect::<Vec<String>>().join(", ");
+}
+
+/* a new instance of the class of args[0], a native instance
+ */
+fn same_class<T: 'static>(args: &Args, native: T) -> Value {
+    match &args.positional[0] {
+        Value::Object(object) => return Value::Object(Object::build_new(object.class.clone(), Some(Box::new(native)))),
+        _ => unreachable!(),
+    }
+}
+
+/* __reduce__() of the instance args[0]: its class and the arguments that
+ * make a copy of it, for pickle and saved sessions
+ */
+fn reduce(args: &Args, class_args: Vec<Value>) -> Value {
+    match &args.positional[0] {
+        Value::Object(object) => return new_tuple(vec![Value::Class(object.class.clone()), new_tuple(class_args)]),
+        _ => unreachable!(),
+    }
+}
+
+/* position of a sequence index, which may count from the end
+ */
+fn sequence_index(index: &Value, len: usize, name: &str) -> Result<usize, Exception> {
+    let pos = match as_int(index) {
+        Some(pos) => pos,
+        None if matches!(index, Value::BigInt(_)) => {
+            return Err(Exception::new("IndexError", "cannot fit 'int' into an index-sized integer"));
+        }
+        None => {
+            let message = format!("sequence index must be integer, not '{}'", index.type_name());
+            return Err(Exception::new("TypeError", &message));
+        }
+    };
+    let pos = if pos < 0 { pos + len as i64 } else { pos };
+    if pos < 0 || pos >= len as i64 {
+        return Err(Exception::new("IndexError", &format!("{} index out of range", name)));
+    }
+    return Ok(pos as usize);
+}
+
+const DEQUE: &str = "collections.deque";
+
+/* deque([iterable[, maxlen]]): a double-ended queue. Once it has maxlen
+ * items, adding one at one end drops one from the other.
+ */
+struct Deque {
+    items: VecDeque<Value>,
+    maxlen: Option<usize>,
+}
+
+impl Deque {
+    fn push(&mut self, item: Value, front: bool) {
+        if self.maxlen == Some(0) {
+            return;
+        }
+        if front {
+            self.items.push_front(item);
+        } else {
+            self.items.push_back(item);
+        }
+        if self.maxlen.is_some_and(|maxlen| self.items.len() > maxlen) {
+            if front {
+                self.items.pop_back();
+            } else {
+                self.items.pop_front();
+            }
+        }
+    }
+}
+
+fn new_deque(interpreter: &mut Interpreter, class: Rc<Class>, args: Args) -> EvalResult {
+    let bound = bind("deque", args, &["iterable", "maxlen"], 0)?;
+    let maxlen = match &bound[1] {
+        None | Some(Value::NoneT) => None,
+        Some(value) => match as_int(value) {
+            Some(maxlen) if maxlen >= 0 => Some(maxlen as usize),
+            Some(_) => return Err(Exception::new("ValueError", "maxlen must be non-negative")),
+            None => return Err(Exception::new("TypeError", "an integer is required")),
+        },
+    };
+    let mut deque = Deque {
+        items: VecDeque::new(),
+        maxlen,
+    };
+    if let Some(iterable) = &bound[0] {
+        for item in interpreter.iterate(iterable)? {
+            deque.push(item, false);
+        }
+    }
+    return Ok(Value::Object(Object::build_new(class, Some(Box::new(deque)))));
+}
+
+/* the items of the deque args[0], to be searched without it borrowed
+ */
+fn deque_items(name: &str, args: &Args) -> Result<Vec<Value>, Exception> {
+    return Ok(instance::<Deque>(DEQUE, name, args)?.items.iter().cloned().collect());
+}
+
+fn deque_class() -> NativeClass<Deque> {
+    let mut deque: NativeClass<Deque> = NativeClass::build_new(DEQUE);
+    deque.raw_new(new_deque);
+    for (name, front) in [("append", false), ("appendleft", true)] {
+        deque.raw_method(name, move |_, args| {
+            args.check(name, 2, 2)?;
+            instance::<Deque>(DEQUE, name, &args)?.push(args.positional[1].clone(), front);
+            return Ok(Value::NoneT);
+        });
+    }
+    for (name, front) in [("extend", false), ("extendleft", true)] {
+        deque.raw_method(name, move |interpreter, args| {
+            args.check(name, 2, 2)?;
+            instance::<Deque>(DEQUE, name, &args)?;
+            // the items are all taken first, so a deque can extend itself
+            let items = interpreter.iterate(&args.positional[1])?;
+            let mut this = instance::<Deque>(DEQUE, name, &args)?;
+            for item in items {
+                this.push(item, front);
+            }
+            return Ok(Value::NoneT);
+        });
+    }
+    for (name, front) in [("pop", false), ("popleft", true)] {
+        deque.raw_method(name, move |_, args| {
+            args.check(name, 1, 1)?;
+            let mut this = instance::<Deque>(DEQUE, name, &args)?;
+            let item = if front { this.items.pop_front() } else { this.items.pop_back() };
+            return item.ok_or_else(|| Exception::new("IndexError", "pop from an empty deque"));
+        });
+    }
+    deque.raw_method("rotate", |_, args| {
+        args.check("rotate", 1, 2)?;
+        let steps = match args.positional.get(1) {
+            Some(steps) => int_arg(steps)?,
+            None => 1,
+        };
+        let mut this = instance::<Deque>(DEQUE, "rotate", &args)?;
+        if !this.items.is_empty() {
+            let steps = steps.rem_euclid(this.items.len() as i64) as usize;
+            this.items.rotate_right(steps);
+        }
+        return Ok(Value::NoneT);
+    });
+    deque.raw_method("insert", |_, args| {
+        args.check("insert", 3, 3)?;
+        let pos = int_arg(&args.positional[1])?;
+        let mut this = instance::<Deque>(DEQUE, "insert", &args)?;
+        if this.maxlen == Some(this.items.len()) {
+            return Err(Exception::new("IndexError", "deque already at its maximum size"));
+        }
+        let len = this.items.len() as i64;
+        let pos = if pos < 0 { (pos + len).max(0) } else { pos.min(len) };
+        this.items.insert(pos as usize, args.positional[2].clone());
+        return Ok(Value::NoneT);
+    });
+    deque.raw_method("count", |_, args| {
+        args.check("count", 2, 2)?;
+        let items = deque_items("count", &args)?;
+        let count = items.iter().filter(|item| equals(item, &args.positional[1])).count();
+        return Ok(Value::Int(count as i64));
+    });
+    deque.raw_method("index", |_, args| {
+        args.check("index", 2, 2)?;
+        let items = deque_items("index", &args)?;
+        match items.iter().position(|item| equals(item, &args.positional[1])) {
+            Some(pos) => return Ok(Value::Int(pos as i64)),
+            None => return Err(Exception::new("ValueError", &format!("{} is not in deque", args.positional[1].repr()))),
+        }
+    });
+    deque.raw_method("remove", |_, args| {
+        args.check("remove", 2, 2)?;
+        let items = deque_items("remove", &args)?;
+        match items.iter().position(|item| equals(item, &args.positional[1])) {
+            Some(pos) => instance::<Deque>(DEQUE, "remove", &args)?.items.remove(pos),
+            None => return Err(Exception::new("ValueError", &format!("{} is not in deque", args.positional[1].repr()))),
+        };
+        return Ok(Value::NoneT);
+    });
+    deque.raw_method("reverse", |_, args| {
+        args.check("reverse", 1, 1)?;
+        instance::<Deque>(DEQUE, "reverse", &args)?.items.make_contiguous().reverse();
+        return Ok(Value::NoneT);
+    });
+    deque.raw_method("clear", |_, args| {
+        args.check("clear", 1, 1)?;
+        instance::<Deque>(DEQUE, "clear", &args)?.items.clear();
+        return Ok(Value::NoneT);
+    });
+    deque.raw_method("copy", |_, args| {
+        args.check("copy", 1, 1)?;
+        let this = instance::<Deque>(DEQUE, "copy", &args)?;
+        let copy = Deque {
+            items: this.items.clone(),
+            maxlen: this.maxlen,
+        };
+        return Ok(same_class(&args, copy));
+    });
+    deque.raw_method("__len__", |_, args| {
+        args.check("__len__", 1, 1)?;
+        return Ok(Value::Int(instance::<Deque>(DEQUE, "__len__", &args)?.items.len() as i64));
+    });
+    deque.raw_method("__getitem__", |_, args| {
+        args.check("__getitem__", 2, 2)?;
+        let this = instance::<Deque>(DEQUE, "__getitem__", &args)?;
+        let pos = sequence_index(&args.positional[1], this.items.len(), "deque")?;
+        return Ok(this.items[pos].clone());
+    });
+    deque.raw_method("__setitem__", |_, args| {
+        args.check("__setitem__", 3, 3)?;
+        let mut this = instance::<Deque>(DEQUE, "__setitem__", &args)?;
+        let pos = sequence_index(&args.positional[1], this.items.len(), "deque")?;
+        this.items[pos] = args.positional[2].clone();
+        return Ok(Value::NoneT);
+    });
+    deque.raw_method("__contains__", |_, args| {
+        args.check("__contains__", 2, 2)?;
+        let items = deque_items("__contains__", &args)?;
+        return Ok(Value::Bool(items.iter().any(|item| equals(item, &args.positional[1]))));
+    });
+    deque.raw_method("__iter__", |_, args| {
+        args.check("__iter__", 1, 1)?;
+        return Ok(new_list(deque_items("__iter__", &args)?));
+    });
+    deque.raw_method("__reversed__", |_, args| {
+        args.check("__reversed__", 1, 1)?;
+        let mut items = deque_items("__reversed__", &args)?;
+        items.reverse();
+        return Ok(new_iterator(Iter::Items(items, 0, "_collections._deque_reverse_iterator")));
+    });
+    deque.getter("maxlen", |this: &Deque| this.maxlen.map_or(Value::NoneT, |maxlen| Value::Int(maxlen as i64)));
+    deque.recursive_repr("[...]");
+    deque.repr(|this: &Deque| {
+        let items = join_reprs(this.items.iter());
+        match this.maxlen {
+            Some(maxlen) => return format!("deque([{}], maxlen={})", items, maxlen),
+            None => return format!("deque([{}])", items),
+        }
+    });
+    deque.eq(|this: &Deque, other: &Value| {
+        let same = |other: &Deque| {
+            this.items.len() == other.items.len() && this.items.iter().zip(&other.items).all(|(a, b)| items_equal(a, b))
+        };
+        return with_native(other, same).unwrap_or(false);
+    });
+    deque.hash(|_: &Deque| Err(unhashable(DEQUE)));
+    deque.truth(|this: &Deque| !this.items.is_empty());
+    deque.raw_method("__reduce__", |_, args| {
+        args.check("__reduce__", 1, 1)?;
+        let (items, maxlen) = {
+            let this = instance::<Deque>(DEQUE, "__reduce__", &args)?;
+            (new_list(this.items.iter().cloned().collect()), this.maxlen.map_or(Value::NoneT, |maxlen| Value::Int(maxlen as i64)))
+        };
+        return Ok(reduce(&args, vec![items, maxlen]));
+    });
+    return deque;
+}
+
+/* The dict-like classes, which keep their items in a Dict
+ */
+#[derive(Clone, Copy, PartialEq)]
+enum Kind {
+    // defaultdict([default_factory[, ...]]): missing keys are added with
+    // default_factory()
+    DefaultDict,
+    // OrderedDict([...]): compared with another, the order matters
+    Ordered,
+    // Counter([iterable-or-mapping]): counts of items, 0 for missing keys
+    Counter,
+}
+
+impl Kind {
+    fn name(self) -> &'static str {
+        match self {
+            Kind::DefaultDict => return "defaultdict",
+            Kind::Ordered => return "OrderedDict",
+            Kind::Counter => return "Counter",
+        }
+    }
+
+    fn class_name(self) -> &'static str {
+        match self {
+            Kind::DefaultDict => return "collections.defaultdict",
+            Kind::Ordered => return "collections.OrderedDict",
+            Kind::Counter => return "Counter",
+        }
+    }
+}
+
+struct Mapping {
+    kind: Kind,
+    dict: Rc<RefCell<Dict>>,
+    // a defaultdict's default_factory, or None
+    factory: Value,
+}
+
+/* the items of a dict, or of an object with keys() and __getitem__; None
+ * for anything else
+ */
+fn mapping_items(interpreter: &mut Interpreter, value: &Value) -> Result<Option<Vec<(Value, Value)>>, Exception> {
+    match value {
+        Value::Dict(dict) => return Ok(Some(dict.borrow().items())),
+        Value::Object(object) if object.class.lookup("keys").is_some() => {
+            let keys = interpreter.get_attr(value, "keys")?;
+            let keys = interpreter.call(&keys, Args::default())?;
+            let mut items: Vec<(Value, Value)> = Vec::new();
+            for key in interpreter.iterate(&keys)? {
+                let item = subscript(interpreter, value, &key)?;
+                items.push((key, item));
+            }
+            return Ok(Some(items));
+        }
+        _ => return Ok(None),
+    }
+}
+
+/* What dict.update(source, **keywords) would set: a mapping's items, or
+ * the key-value pairs source iterates over, then the keywords
+ */
+fn update_items(
+    interpreter: &mut Interpreter,
+    source: Option<&Value>,
+    keywords: Vec<(String, Value)>,
+) -> Result<Vec<(Value, Value)>, Exception> {
+    let mut items = match source {
+        None => Vec::new(),
+        Some(source) => match mapping_items(interpreter, source)? {
+            Some(items) => items,
+            None => {
+                let mut items: Vec<(Value, Value)> = Vec::new();
+                for (pos, item) in interpreter.iterate(source)?.iter().enumerate() {
+                    let pair = interpreter.iterate(item).map_err(|_| {
+                        let message = format!("cannot convert dictionary update sequence element #{} to a sequence", pos);
+                        return Exception::new("TypeError", &message);
+                    })?;
+                    if pair.len() != 2 {
+                        let message =
+                            format!("dictionary update sequence element #{} has length {}; 2 is required", pos, pair.len());
+                        return Err(Exception::new("ValueError", &message));
+                    }
+                    items.push((pair[0].clone(), pair[1].clone()));
+                }
+                items
+            }
+        },
+    };
+    items.extend(keywords.into_iter().map(|(key, value)| (Value::Str(key), value)));
+    return Ok(items);
+}
+
+/* What Counter.update(source, **keywords) adds: a mapping's counts, or 1
+ * for each item source iterates over, then the keywords
+ */
+fn count_items(
+    interpreter: &mut Interpreter,
+    source: Option<&Value>,
+    keywords: Vec<(String, Value)>,
+) -> Result<Vec<(Value, Value)>, Exception> {
+    let mut items = match source {
+        None => Vec::new(),
+        Some(source) => match mapping_items(interpreter, source)? {
+            Some(items) => items,
+            None => interpreter.iterate(source)?.into_iter().map(|item| (item, Value::Int(1))).collect(),
+        },
+    };
+    items.extend(keywords.into_iter().map(|(key, value)| (Value::Str(key), value)));
+    return Ok(items);
+}
+
+/* add (or subtract) counts to a Counter's
+ */
+fn add_counts(dict: &mut Dict, items: Vec<(Value, Value)>, op: BinOp) -> Result<(), Exception> {
+    for (key, count) in items {
+        let old = dict.get(&key)?.unwrap_or(Value::Int(0));
+        dict.set(key, binary_op(op, &old, &count)?)?;
+    }
+    return Ok(());
+}
+
+/* a Counter's items, highest count first, and in insertion order for
+ * equal counts
+ */
+fn most_common(dict: &Dict) -> Result<Vec<(Value, Value)>, Exception> {
+    let items = dict.items();
+    let counts = items.iter().enumerate().map(|(pos, (_, count))| (count.clone(), Value::Int(pos as i64))).collect();
+    let order = merge_sort(counts, true, &mut |left, right| compare(CmpOp::Lt, left, right))?;
+    return Ok(order.iter().map(|(_, pos)| items[as_int(pos).unwrap() as usize].clone()).collect());
+}
+
+fn dict_repr(dict: &Dict) -> String {
+    let items: Vec<String> = dict.entries().map(|(key, value)| format!("{}: {}", key.repr(), value.repr())).collect();
+    return format!("{{{}}}", items.join(", "));
+}
+
+/* == of two dicts' items, in the same order if ordered
+ */
+fn dict_equals(left: &Dict, right: &Dict, ordered: bool) -> bool {
+    if left.len() != right.len() {
+        return false;
+    }
+    if ordered {
+        return left.entries().zip(right.entries()).all(|((a, x), (b, y))| items_equal(a, b) && items_equal(x, y));
+    }
+    return left.entries().all(|(key, value)| matches!(right.get(key), Ok(Some(other)) if items_equal(value, &other)));
+}
+
+/* Counters are equal if every count is, with missing ones 0
+ */
+fn counter_equals(left: &Dict, right: &Dict) -> bool {
+    let covers = |left: &Dict, right: &Dict| {
+        left.entries().all(|(key, count)| match right.get(key) {
+            Ok(Some(other)) => equals(count, &other),
+            _ => equals(count, &Value::Int(0)),
+        })
+    };
+    return covers(left, right) && covers(right, left);
+}
+
+fn new_mapping(interpreter: &mut Interpreter, class: Rc<Class>, mut args: Args, kind: Kind) -> EvalResult {
+    let mut factory = Value::NoneT;
+    if kind == Kind::DefaultDict && !args.positional.is_empty() {
+        factory = args.positional.remove(0);
+        if factory != Value::NoneT && !is_callable(&factory) {
+            return Err(Exception::new("TypeError", "first argument must be callable or None"));
+        }
+    }
+    let keywords = std::mem::take(&mut args.keywords);
+    args.check(kind.name(), 0, 1)?;
+    let mut dict = Dict::build_new();
+    if kind == Kind::Counter {
+        add_counts(&mut dict, count_items(interpreter, args.positional.first(), keywords)?, BinOp::Add)?;
+    } else {
+        for (key, value) in update_items(interpreter, args.positional.first(), keywords)? {
+            dict.set(key, value)?;
+        }
+    }
+    let mapping = Mapping {
+        kind,
+        dict: Rc::new(RefCell::new(dict)),
+        factory,
+    };
+    return Ok(Value::Object(Object::build_new(class, Some(Box::new(mapping)))));
+}
+
+/* the dict of the Mapping args[0], for raw_method()s
+ */
+fn mapping_dict(class: &str, name: &str, args: &Args) -> Result<Rc<RefCell<Dict>>, Exception> {
+    return Ok(instance::<Mapping>(class, name, args)?.dict.clone());
+}
+
+fn mapping_class(kind: Kind) -> NativeClass<Mapping> {
+    let name = kind.class_name();
+    let mut class: NativeClass<Mapping> = NativeClass::build_new(name);
+    class.raw_new(move |interpreter, class, args| new_mapping(interpreter, class, args, kind));
+    class.dict(|this: &Mapping| this.dict.clone());
+    class.raw_method("__len__", move |_, args| {
+        args.check("__len__", 1, 1)?;
+        return Ok(Value::Int(mapping_dict(name, "__len__", &args)?.borrow().len() as i64));
+    });
+    class.raw_method("__contains__", move |_, args| {
+        args.check("__contains__", 2, 2)?;
+        let dict = mapping_dict(name, "__contains__", &args)?;
+        return Ok(Value::Bool(dict.borrow().get(&args.positional[1])?.is_some()));
+    });
+    class.raw_method("__iter__", move |_, args| {
+        args.check("__iter__", 1, 1)?;
+        return Ok(new_list(mapping_dict(name, "__iter__", &args)?.borrow().keys()));
+    });
+    class.raw_method("__getitem__", move |interpreter, args| {
+        args.check("__getitem__", 2, 2)?;
+        let key = &args.positional[1];
+        let (dict, factory) = {
+            let this = instance::<Mapping>(name, "__getitem__", &args)?;
+            (this.dict.clone(), this.factory.clone())
+        };
+        if let Some(value) = dict.borrow().get(key)? {
+            return Ok(value);
+        }
+        match kind {
+            Kind::Counter => return Ok(Value::Int(0)),
+            Kind::DefaultDict if factory != Value::NoneT => {
+                let value = interpreter.call(&factory, Args::default())?;
+                dict.borrow_mut().set(key.clone(), value.clone())?;
+                return Ok(value);
+            }
+            _ => return Err(key_error(key)),
+        }
+    });
+    class.raw_method("__setitem__", move |_, args| {
+        args.check("__setitem__", 3, 3)?;
+        let dict = mapping_dict(name, "__setitem__", &args)?;
+        dict.borrow_mut().set(args.positional[1].clone(), args.positional[2].clone())?;
+        return Ok(Value::NoneT);
+    });
+    class.raw_method("__delitem__", move |_, args| {
+        args.check("__delitem__", 2, 2)?;
+        let dict = mapping_dict(name, "__delitem__", &args)?;
+        let removed = dict.borrow_mut().remove(&args.positional[1])?;
+        // a Counter has nothing to delete for a missing key
+        if removed.is_none() && kind != Kind::Counter {
+            return Err(key_error(&args.positional[1]));
+        }
+        return Ok(Value::NoneT);
+    });
+    class.raw_method("copy", move |_, args| {
+        args.check("copy", 1, 1)?;
+        let this = instance::<Mapping>(name, "copy", &args)?;
+        let copy = Mapping {
+            kind,
+            dict: Rc::new(RefCell::new(this.dict.borrow().clone())),
+            factory: this.factory.clone(),
+        };
+        return Ok(same_class(&args, copy));
+    });
+    match kind {
+        Kind::DefaultDict => {
+            class.getter("default_factory", |this: &Mapping| this.factory.clone());
+            class.setter("default_factory", |this: &mut Mapping, factory: Value| this.factory = factory);
+            class.repr(|this: &Mapping| format!("defaultdict({}, {})", this.factory.repr(), dict_repr(&this.dict.borrow())));
+        }
+        Kind::Ordered => {
+            ordered_methods(&mut class);
+            class.repr(|this: &Mapping| {
+                if this.dict.borrow().is_empty() {
+                    return String::from("OrderedDict()");
+                }
+                let items: Vec<String> =
+                    this.dict.borrow().entries().map(|(key, value)| format!("({}, {})", key.repr(), value.repr())).collect();
+                return format!("OrderedDict([{}])", items.join(", "));
+            });
+        }
+        Kind::Counter => {
+            counter_methods(&mut class);
+            class.repr(|this: &Mapping| {
+                if this.dict.borrow().is_empty() {
+                    return String::from("Counter()");
+                }
+                // counts that can't be ordered are shown as they are
+                let items = most_common(&this.dict.borrow()).unwrap_or_else(|_| this.dict.borrow().items());
+                let items: Vec<String> = items.iter().map(|(key, count)| format!("{}: {}", key.repr(), count.repr())).collect();
+                return format!("Counter({{{}}})", items.join(", "));
+            });
+        }
+    };
+    class.eq(|this: &Mapping, other: &Value| match other {
+        Value::Dict(dict) => return dict.try_borrow().is_ok_and(|dict| dict_equals(&this.dict.borrow(), &dict, false)),
+        _ => {
+            let same = |other: &Mapping| match (this.kind, other.kind) {
+                (Kind::Counter, Kind::Counter) => return counter_equals(&this.dict.borrow(), &other.dict.borrow()),
+                (Kind::Ordered, Kind::Ordered) => return dict_equals(&this.dict.borrow(), &other.dict.borrow(), true),
+                _ => return dict_equals(&this.dict.borrow(), &other.dict.borrow(), false),
+            };
+            return with_native(other, same).unwrap_or(false);
+        }
+    });
+    class.hash(move |_: &Mapping| Err(unhashable(name)));
+    class.truth(|this: &Mapping| !this.dict.borrow().is_empty());
+    // defaultdict(default_factory, dict), OrderedDict(pairs) or
+    // Counter(dict)
+    class.raw_method("__reduce__", move |_, args| {
+        args.check("__reduce__", 1, 1)?;
+        let class_args = {
+            let this = instance::<Mapping>(name, "__reduce__", &args)?;
+            let dict = this.dict.borrow();
+            match kind {
+                Kind::DefaultDict => vec![this.factory.clone(), new_dict(dict.clone())],
+                Kind::Ordered => {
+                    let pairs = dict.entries().map(|(key, value)| new_tuple(vec![key.clone(), value.clone()])).collect();
+                    vec![new_list(pairs)]
+                }
+                Kind::Counter => vec![new_dict(dict.clone())],
+            }
+        };
+        return Ok(reduce(&args, class_args));
+    });
+    return class;
+}
+
+const ORDERED: &str = "collections.OrderedDict";
+
+fn ordered_methods(class: &mut NativeClass<Mapping>) {
+    // popitem(last=True) can take from the front too
+    class.raw_method("popitem", |_, mut args| {
+        let last = args.keyword("last");
+        args.check("popitem", 1, 2)?;
+        let last = last.or_else(|| args.positional.get(1).cloned()).is_none_or(|last| last.is_truthy());
+        let dict = mapping_dict(ORDERED, "popitem", &args)?;
+        let entry = if last { dict.borrow().entries().last().cloned() } else { dict.borrow().entries().next().cloned() };
+        let (key, value) = match entry {
+            Some(entry) => entry,
+            None => return Err(key_error(&Value::Str(String::from("dictionary is empty")))),
+        };
+        dict.borrow_mut().remove(&key)?;
+        return Ok(new_tuple(vec![key, value]));
+    });
+    class.raw_method("move_to_end", |_, mut args| {
+        let last = args.keyword("last");
+        args.check("move_to_end", 2, 3)?;
+        let last = last.or_else(|| args.positional.get(2).cloned()).is_none_or(|last| last.is_truthy());
+        let dict = mapping_dict(ORDERED, "move_to_end", &args)?;
+        let mut items = dict.borrow().items();
+        let pos = match items.iter().position(|(key, _)| equals(key, &args.positional[1])) {
+            Some(pos) => pos,
+            None => return Err(key_error(&args.positional[1])),
+        };
+        let item = items.remove(pos);
+        if last {
+            items.push(item);
+        } else {
+            items.insert(0, item);
+        }
+        let mut dict = dict.borrow_mut();
+        *dict = Dict::build_new();
+        for (key, value) in items {
+            dict.set(key, value)?;
+        }
+        return Ok(Value::NoneT);
+    });
+}
+
+const COUNTER: &str = "Counter";
+
+/* Counter + - | &: the counts added or subtracted, or the larger or
+ * smaller of each, keeping only those above zero
+ */
+fn combine_counts(left: &Dict, right: &Dict, op: BinOp) -> Result<Dict, Exception> {
+    let zero = Value::Int(0);
+    let mut counts = Dict::build_new();
+    for (key, count) in left.entries() {
+        let other = right.get(key)?.unwrap_or(Value::Int(0));
+        let count = match op {
+            BinOp::BitOr if compare(CmpOp::Lt, count, &other)? => other,
+            BinOp::BitAnd if compare(CmpOp::Lt, &other, count)? => other,
+            BinOp::BitOr | BinOp::BitAnd => count.clone(),
+            _ => binary_op(op, count, &other)?,
+        };
+        if compare(CmpOp::Gt, &count, &zero)? {
+            counts.set(key.clone(), count)?;
+        }
+    }
+    if op == BinOp::BitAnd {
+        return Ok(counts);
+    }
+    // the keys only on the right, as if 0 on the left
+    for (key, count) in right.entries() {
+        if left.get(key)?.is_some() {
+            continue;
+        }
+        let count = if op == BinOp::Sub { binary_op(op, &zero, count)? } else { count.clone() };
+        if compare(CmpOp::Gt, &count, &zero)? {
+            counts.set(key.clone(), count)?;
+        }
+    }
+    return Ok(counts);
+}
+
+fn counter_methods(class: &mut NativeClass<Mapping>) {
+    class.raw_method("most_common", |_, args| {
+        args.check("most_common", 1, 2)?;
+        let items = most_common(&instance::<Mapping>(COUNTER, "most_common", &args)?.dict.borrow())?;
+        let count = match args.positional.get(1) {
+            None | Some(Value::NoneT) => items.len(),
+            Some(count) => int_arg(count)?.clamp(0, items.len() as i64) as usize,
+        };
+        let items = items.into_iter().take(count).map(|(key, count)| new_tuple(vec![key, count]));
+        return Ok(new_list(items.collect()));
+    });
+    class.raw_method("elements", |_, args| {
+        args.check("elements", 1, 1)?;
+        let this = instance::<Mapping>(COUNTER, "elements", &args)?;
+        let mut items: Vec<Value> = Vec::new();
+        for (key, count) in this.dict.borrow().entries() {
+            let count = as_int(count).unwrap_or(0);
+            items.extend(std::iter::repeat_n(key.clone(), count.max(0) as usize));
+        }
+        return Ok(new_iterator(Iter::Items(items, 0, "itertools.chain")));
+    });
+    for (name, op) in [("update", BinOp::Add), ("subtract", BinOp::Sub)] {
+        class.raw_method(name, move |interpreter, mut args| {
+            let keywords = std::mem::take(&mut args.keywords);
+            args.check(name, 1, 2)?;
+            let dict = mapping_dict(COUNTER, name, &args)?;
+            let items = count_items(interpreter, args.positional.get(1), keywords)?;
+            add_counts(&mut dict.borrow_mut(), items, op)?;
+            return Ok(Value::NoneT);
+        });
+    }
+    for (name, op) in [("__add__", BinOp::Add), ("__sub__", BinOp::Sub), ("__or__", BinOp::BitOr), ("__and__", BinOp::BitAnd)] {
+        class.raw_method(name, move |interpreter, args| {
+            args.check(name, 2, 2)?;
+            let dict = mapping_dict(COUNTER, name, &args)?;
+            let other = with_native(&args.positional[1], |other: &Mapping| (other.kind, other.dict.clone()));
+            let other = match other {
+                Some((Kind::Counter, other)) => other,
+                _ => return Ok(interpreter.builtins.get("NotImplemented").unwrap()),
+            };
+            let counts = combine_counts(&dict.borrow(), &other.borrow(), op)?;
+            let counter = Mapping {
+                kind: Kind::Counter,
+                dict: Rc::new(RefCell::new(counts)),
+                factory: Value::NoneT,
+            };
+            return Ok(same_class(&args, counter));
+        });
+    }
+    class.raw_method("total", |_, args| {
+        args.check("total", 1, 1)?;
+        let this = instance::<Mapping>(COUNTER, "total", &args)?;
+        let mut total = Value::Int(0);
+        for (_, count) in this.dict.borrow().entries() {
+            total = binary_op(BinOp::Add, &total, count)?;
+        }
+        return Ok(total);
+    });
+}
+
+const CHAIN_MAP: &str = "ChainMap";
+
+/* ChainMap(*maps): lookups search the maps in turn; changes go to the
+ * first. maps is a list scripts can get at.
+ */
+struct Chain {
+    maps: Value,
+    // for the ChainMaps parents and new_child() make
+    class: Rc<Class>,
+}
+
+impl Chain {
+    fn maps(&self) -> Vec<Value> {
+        match &self.maps {
+            Value::List(maps) => return maps.borrow().clone(),
+            _ => return Vec::new(),
+        }
+    }
+
+    fn new_value(class: Rc<Class>, maps: Vec<Value>) -> Value {
+        let chain = Chain {
+            maps: new_list(maps),
+            class: class.clone(),
+        };
+        return Value::Object(Object::build_new(class, Some(Box::new(chain))));
+    }
+
+    /* the items of all the maps as one dict, if they can be read without
+     * running code
+     */
+    fn flatten(&self) -> Option<Dict> {
+        let mut dict = Dict::build_new();
+        for map in self.maps().iter().rev() {
+            let items = match map.as_dict() {
+                Some(dict) => dict.try_borrow().ok()?.items(),
+                None => with_native(map, |chain: &Chain| chain.flatten())??.items(),
+            };
+            for (key, value) in items {
+                dict.set(key, value).ok()?;
+            }
+        }
+        return Some(dict);
+    }
+}
+
+/* map[key], or None where that is a KeyError
+ */
+fn map_get(interpreter: &mut Interpreter, map: &Value, key: &Value) -> Result<Option<Value>, Exception> {
+    if let Value::Dict(dict) = map {
+        return dict.borrow().get(key);
+    }
+    match subscript(interpreter, map, key) {
+        Ok(value) => return Ok(Some(value)),
+        Err(err) if err.kind == "KeyError" => return Ok(None),
+        Err(err) => return Err(err),
+    }
+}
+
+fn chain_keys(interpreter: &mut Interpreter, args: &Args, name: &str) -> Result<Vec<Value>, Exception> {
+    let maps = instance::<Chain>(CHAIN_MAP, name, args)?.maps();
+    let mut keys = Dict::build_new();
+    for map in maps.iter().rev() {
+        for key in interpreter.iterate(map)? {
+            keys.set(key, Value::NoneT)?;
+        }
+    }
+    return Ok(keys.keys());
+}
+
+fn chain_get(interpreter: &mut Interpreter, args: &Args, name: &str, key: &Value) -> Result<Option<Value>, Exception> {
+    let maps = instance::<Chain>(CHAIN_MAP, name, args)?.maps();
+    for map in &maps {
+        if let Some(value) = map_get(interpreter, map, key)? {
+            return Ok(Some(value));
+        }
+    }
+    return Ok(None);
+}
+
+fn first_map(args: &Args, name: &str) -> Result<Value, Exception> {
+    let maps = instance::<Chain>(CHAIN_MAP, name, args)?.maps();
+    return maps.into_iter().next().ok_or_else(|| Exception::new("IndexError", "list index out of range"));
+}
+
+fn chain_map_class() -> NativeClass<Chain> {
+    let mut chain: NativeClass<Chain> = NativeClass::build_new(CHAIN_MAP);
+    chain.raw_new(|_, class, args| {
+        args.check(CHAIN_MAP, 0, usize::MAX)?;
+        let mut maps = args.positional;
+        if maps.is_empty() {
+            maps.push(new_dict(Dict::build_new()));
+        }
+        return Ok(Chain::new_value(class, maps));
+    });
+    chain.raw_method("__getitem__", |interpreter, args| {
+        args.check("__getitem__", 2, 2)?;
+        let value = chain_get(interpreter, &args, "__getitem__", &args.positional[1])?;
+        return value.ok_or_else(|| key_error(&args.positional[1]));
+    });
+    chain.raw_method("get", |interpreter, args| {
+        args.check("get", 2, 3)?;
+        let value = chain_get(interpreter, &args, "get", &args.positional[1])?;
+        return Ok(value.or_else(|| args.positional.get(2).cloned()).unwrap_or(Value::NoneT));
+    });
+    chain.raw_method("__contains__", |interpreter, args| {
+        args.check("__contains__", 2, 2)?;
+        let maps = instance::<Chain>(CHAIN_MAP, "__contains__", &args)?.maps();
+        for map in &maps {
+            let found = match interpreter.call_special(map, "__contains__", vec![args.positional[1].clone()]) {
+                Some(found) => found?.is_truthy(),
+                None => contains(map, &args.positional[1])?,
+            };
+            if found {
+                return Ok(Value::Bool(true));
+            }
+        }
+        return Ok(Value::Bool(false));
+    });
+    chain.raw_method("__setitem__", |interpreter, args| {
+        args.check("__setitem__", 3, 3)?;
+        let map = first_map(&args, "__setitem__")?;
+        let (key, value) = (args.positional[1].clone(), args.positional[2].clone());
+        match interpreter.call_special(&map, "__setitem__", vec![key.clone(), value.clone()]) {
+            Some(result) => {
+                result?;
+            }
+            None => set_item(&map, &key, value)?,
+        };
+        return Ok(Value::NoneT);
+    });
+    chain.raw_method("pop", |interpreter, args| {
+        args.check("pop", 2, 3)?;
+        let map = first_map(&args, "pop")?;
+        let key = &args.positional[1];
+        let value = match &map {
+            Value::Dict(dict) => dict.borrow_mut().remove(key)?,
+            _ => {
+                let pop = interpreter.get_attr(&map, "pop")?;
+                match interpreter.call(&pop, Args::build_new(vec![key.clone()])) {
+                    Ok(value) => Some(value),
+                    Err(err) if err.kind == "KeyError" => None,
+                    Err(err) => return Err(err),
+                }
+            }
+        };
+        return value.or_else(|| args.positional.get(2).cloned()).ok_or_else(|| {
+            return key_error(&Value::Str(format!("Key not found in the first mapping: {}", key.repr())));
+        });
+    });
+    chain.raw_method("__len__", |interpreter, args| {
+        args.check("__len__", 1, 1)?;
+        return Ok(Value::Int(chain_keys(interpreter, &args, "__len__")?.len() as i64));
+    });
+    chain.raw_method("__iter__", |interpreter, args| {
+        args.check("__iter__", 1, 1)?;
+        return Ok(new_list(chain_keys(interpreter, &args, "__iter__")?));
+    });
+    chain.raw_method("keys", |interpreter, args| {
+        args.check("keys", 1, 1)?;
+        return Ok(new_list(chain_keys(interpreter, &args, "keys")?));
+    });
+    for name in ["values", "items"] {
+        chain.raw_method(name, move |interpreter, args| {
+            args.check(name, 1, 1)?;
+            let mut items: Vec<Value> = Vec::new();
+            for key in chain_keys(interpreter, &args, name)? {
+                let value = chain_get(interpreter, &args, name, &key)?.unwrap_or(Value::NoneT);
+                items.push(if name == "values" { value } else { new_tuple(vec![key, value]) });
+            }
+            return Ok(new_list(items));
+        });
+    }
+    chain.raw_method("new_child", |_, mut args| {
+        let child = args.keyword("m");
+        args.check("new_child", 1, 2)?;
+        let this = instance::<Chain>(CHAIN_MAP, "new_child", &args)?;
+        let child = match child.or_else(|| args.positional.get(1).cloned()) {
+            None | Some(Value::NoneT) => new_dict(Dict::build_new()),
+            Some(child) => child,
+        };
+        let mut maps = vec![child];
+        maps.extend(this.maps());
+        return Ok(Chain::new_value(this.class.clone(), maps));
+    });
+    chain.raw_method("copy", |_, args| {
+        args.check("copy", 1, 1)?;
+        let this = instance::<Chain>(CHAIN_MAP, "copy", &args)?;
+        let mut maps = this.maps();
+        if let Some(Value::Dict(ref first)) = maps.first().cloned() {
+            maps[0] = new_dict(first.borrow().clone());
+        }
+        return Ok(Chain::new_value(this.class.clone(), maps));
+    });
+    chain.raw_method("__reduce__", |_, args| {
+        args.check("__reduce__", 1, 1)?;
+        let maps = instance::<Chain>(CHAIN_MAP, "__reduce__", &args)?.maps();
+        return Ok(reduce(&args, maps));
+    });
+    chain.raw_method("clear", |interpreter, args| {
+        args.check("clear", 1, 1)?;
+        match first_map(&args, "clear")? {
+            Value::Dict(ref dict) => *dict.borrow_mut() = Dict::build_new(),
+            map => {
+                let clear = interpreter.get_attr(&map, "clear")?;
+                interpreter.call(&clear, Args::default())?;
+            }
+        };
+        return Ok(Value::NoneT);
+    });
+    chain.getter("maps", |this: &Chain| this.maps.clone());
+    chain.getter("parents", |this: &Chain| Chain::new_value(this.class.clone(), this.maps().into_iter().skip(1).collect()));
+    chain.repr(|this: &Chain| format!("ChainMap({})", join_reprs(this.maps().iter())));
+    chain.eq(|this: &Chain, other: &Value| {
+        if with_native(other, |other: &Chain| std::ptr::eq(this, other)) == Some(true) {
+            return true;
+        }
+        let other = match other {
+            Value::Dict(dict) => dict.try_borrow().ok().map(|dict| dict.clone()),
+            _ => with_native(other, |mapping: &Mapping| mapping.dict.borrow().clone())
+                .or_else(|| with_native(other, |chain: &Chain| chain.flatten())?),
+        };
+        return match (this.flatten(), other) {
+            (Some(this), Some(other)) => dict_equals(&this, &other, false),
+            _ => false,
+        };
+    });
+    chain.hash(|_: &Chain| Err(unhashable(CHAIN_MAP)));
+    chain.truth(|this: &Chain| this.maps().iter().any(|map| map.is_truthy()));
+    return chain;
+}
+
+/* The layout of a namedtuple() class
+ */
+pub(crate) struct Layout {
+    pub name: String,
+    pub fields: Vec<String>,
+    // for the last fields
+    pub defaults: Vec<Value>,
+}
+
+const NAMEDTUPLE: &str = "namedtuple";
+
+/* An instance of a namedtuple() class
+ */
+struct Record {
+    layout: Rc<Layout>,
+    values: Vec<Value>,
+}
+
+pub(crate) fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    return chars.next().is_some_and(|c| c.is_alphabetic() || c == '_') && chars.all(|c| c.is_alphanumeric() || c == '_');
+}
+
+fn check_name(name: &str) -> Result<(), Exception> {
+    if !is_identifier(name) {
+        let message = format!("Type names and field names must be valid identifiers: '{}'", name);
+        return Err(Exception::new("ValueError", &message));
+    }
+    if KEYWORDS.contains(&name) {
+        let message = format!("Type names and field names cannot be a keyword: '{}'", name);
+        return Err(Exception::new("ValueError", &message));
+    }
+    return Ok(());
+}
+
+/* namedtuple(typename, field_names, rename=False, defaults=None,
+ * module=None): a class of records with a name for each field. field_names
+ * is a list of names, or a string of them separated by commas or spaces.
+ */
+fn new_namedtuple(interpreter: &mut Interpreter, args: Args, object: &Rc<Class>) -> EvalResult {
+    let params = ["typename", "field_names", "rename", "defaults", "module"];
+    let bound = bind("namedtuple", args, &params, 2)?;
+    let name = bound[0].as_ref().unwrap().to_string();
+    let mut fields: Vec<String> = match bound[1].as_ref().unwrap() {
+        Value::Str(names) => names.replace(',', " ").split_whitespace().map(String::from).collect(),
+        names => interpreter.iterate(names)?.iter().map(|name| name.to_string()).collect(),
+    };
+    let rename = bound[2].as_ref().is_some_and(|rename| rename.is_truthy());
+    if rename {
+        for pos in 0..fields.len() {
+            let field = &fields[pos];
+            let invalid = !is_identifier(field) || KEYWORDS.contains(&field.as_str()) || field.starts_with('_');
+            if invalid || fields[..pos].contains(field) {
+                fields[pos] = format!("_{}", pos);
+            }
+        }
+    }
+    check_name(&name)?;
+    for (pos, field) in fields.iter().enumerate() {
+        check_name(field)?;
+        if field.starts_with('_') && !rename {
+            let message = format!("Field names cannot start with an underscore: '{}'", field);
+            return Err(Exception::new("ValueError", &message));
+        }
+        if fields[..pos].contains(field) {
+            return Err(Exception::new("ValueError", &format!("Encountered duplicate field name: '{}'", field)));
+        }
+    }
+    let defaults = match &bound[3] {
+        None | Some(Value::NoneT) => Vec::new(),
+        Some(defaults) => interpreter.iterate(defaults)?,
+    };
+    if defaults.len() > fields.len() {
+        return Err(Exception::new("TypeError", "Got more default values than field names"));
+    }
+    let layout = Layout { name, fields, defaults };
+    return Ok(Value::Class(namedtuple_class(layout, object.clone())));
+}
+
+/* the field values of a call to a namedtuple() class, with the errors of
+ * its __new__
+ */
+fn bind_fields(layout: &Layout, args: Args) -> Result<Vec<Value>, Exception> {
+    let fields = &layout.fields;
+    let error = |message: String| Exception::new("TypeError", &format!("{}.__new__() {}", layout.name, message));
+    if args.positional.len() > fields.len() {
+        let given = args.positional.len() + 1;
+        return Err(error(format!("takes {} positional arguments but {} were given", fields.len() + 1, given)));
+    }
+    let mut values: Vec<Option<Value>> = args.positional.into_iter().map(Some).collect();
+    values.resize(fields.len(), None);
+    for (key, value) in args.keywords {
+        match fields.iter().position(|field| *field == key) {
+            Some(pos) if values[pos].is_some() => return Err(error(format!("got multiple values for argument '{}'", key))),
+            Some(pos) => values[pos] = Some(value),
+            None => return Err(error(format!("got an unexpected keyword argument '{}'", key))),
+        };
+    }
+    let first_default = fields.len() - layout.defaults.len();
+    let mut missing: Vec<&String> = Vec::new();
+    for (pos, value) in values.iter_mut().enumerate() {
+        if value.is_none() {
+            match pos.checked_sub(first_default) {
+                Some(default) => *value = Some(layout.defaults[default].clone()),
+                None => missing.push(&fields[pos]),
+            };
+        }
+    }
+    if !missing.is_empty() {
+        let plural = if missing.len() == 1 { "" } else { "s" };
+        let names = format_names(&missing);
+        return Err(error(format!("missing {} required positional argument{}: {}", missing.len(), plural, names)));
+    }
+    return Ok(values.into_iter().flatten().collect());
+}
+
+fn new_record(class: Rc<Class>, layout: &Rc<Layout>, values: Vec<Value>) -> Value {
+    let record = Record {
+        layout: layout.clone(),
+        values,
+    };
+    return Value::Object(Object::build_new(class, Some(Box::new(record))));
+}
+
+/* the class of a namedtuple() call, which other native modules use for
+ * their results too
+ */
+pub(crate) fn namedtuple_class(layout: Layout, object: Rc<Class>) -> Rc<Class> {
+    let layout = Rc::new(layout);
+    let name = NAMEDTUPLE;
+    let mut class: NativeClass<Record> = NativeClass::build_new(&layout.name);
+    let new_layout = layout.clone();
+    class.raw_new(move |_, class, args| {
+        let values = bind_fields(&new_layout, args)?;
+        return Ok(new_record(class, &new_layout, values));
+    });
+    let fields = layout.fields.iter().map(|field| Value::Str(field.clone())).collect();
+    class.attr("_fields", new_tuple(fields));
+    let mut defaults = Dict::build_new();
+    let first_default = layout.fields.len() - layout.defaults.len();
+    for (field, default) in layout.fields[first_default..].iter().zip(&layout.defaults) {
+        defaults.set(Value::Str(field.clone()), default.clone()).unwrap();
+    }
+    class.attr("_field_defaults", new_dict(defaults));
+    class.raw_method("_asdict", move |_, args| {
+        args.check("_asdict", 1, 1)?;
+        let this = instance::<Record>(name, "_asdict", &args)?;
+        let mut dict = Dict::build_new();
+        for (field, value) in this.layout.fields.iter().zip(&this.values) {
+            dict.set(Value::Str(field.clone()), value.clone())?;
+        }
+        return Ok(new_dict(dict));
+    });
+    class.raw_method("_replace", move |_, mut args| {
+        let keywords = std::mem::take(&mut args.keywords);
+        args.check("_replace", 1, 1)?;
+        let this = instance::<Record>(name, "_replace", &args)?;
+        let mut values = this.values.clone();
+        let mut unexpected: Vec<Value> = Vec::new();
+        for (key, value) in keywords {
+            match this.layout.fields.iter().position(|field| *field == key) {
+                Some(pos) => values[pos] = value,
+                None => unexpected.push(Value::Str(key)),
+            };
+        }
+        if !unexpected.is_empty() {
+            let message = format!("Got unexpected field names: {}", new_list(unexpected).repr());
+            return Err(Exception::new("ValueError", &message));
+        }
+        let layout = this.layout.clone();
+        return Ok(same_class(&args, Record { layout, values }));
+    });
+    class.raw_method("__len__", move |_, args| {
+        args.check("__len__", 1, 1)?;
+        return Ok(Value::Int(instance::<Record>(name, "__len__", &args)?.values.len() as i64));
+    });
+    class.raw_method("__getitem__", move |_, args| {
+        args.check("__getitem__", 2, 2)?;
+        let this = instance::<Record>(name, "__getitem__", &args)?;
+        let index = &args.positional[1];
+        if !matches!(index, Value::Int(_) | Value::Bool(_) | Value::BigInt(_)) {
+            let message = format!("tuple indices must be integers or slices, not {}", index.type_name());
+            return Err(Exception::new("TypeError", &message));
+        }
+        return Ok(this.values[sequence_index(index, this.values.len(), "tuple")?].clone());
+    });
+    class.raw_method("__iter__", move |_, args| {
+        args.check("__iter__", 1, 1)?;
+        return Ok(new_tuple(instance::<Record>(name, "__iter__", &args)?.values.clone()));
+    });
+    class.raw_method("__contains__", move |_, args| {
+        args.check("__contains__", 2, 2)?;
+        let values = instance::<Record>(name, "__contains__", &args)?.values.clone();
+        return Ok(Value::Bool(values.iter().any(|value| equals(value, &args.positional[1]))));
+    });
+    class.raw_method("count", move |_, args| {
+        args.check("count", 2, 2)?;
+        let values = instance::<Record>(name, "count", &args)?.values.clone();
+        return Ok(Value::Int(values.iter().filter(|value| equals(value, &args.positional[1])).count() as i64));
+    });
+    class.raw_method("index", move |_, args| {
+        args.check("index", 2, 2)?;
+        let values = instance::<Record>(name, "index", &args)?.values.clone();
+        match values.iter().position(|value| equals(value, &args.positional[1])) {
+            Some(pos) => return Ok(Value::Int(pos as i64)),
+            None => return Err(Exception::new("ValueError", "tuple.index(x): x not in tuple")),
+        }
+    });
+    // fields come after the methods, so one named like a method hides it
+    for (pos, field) in layout.fields.iter().enumerate() {
+        class.getter(field, move |this: &Record| this.values[pos].clone());
+    }
+    class.repr(|this: &Record| {
+        let fields = this.layout.fields.iter().zip(&this.values);
+        let fields: Vec<String> = fields.map(|(field, value)| format!("{}={}", field, value.repr())).collect();
+        return format!("{}({})", this.layout.name, fields.join(", "));
+    });
+    // records are equal to tuples (and records of other classes) with the
+    // same values, and hash like them
+    class.eq(|this: &Record, other: &Value| {
+        let same = |values: &[Value]| values.len() == this.values.len() && values.iter().zip(&this.values).all(|(a, b)| items_equal(a, b));
+        match other {
+            Value::Tuple(values) => return same(values),
+            _ => return with_native(other, |other: &Record| same(&other.values)).unwrap_or(false),
+        }
+    });
+    class.hash(|this: &Record| HashKey::from_value(&new_tuple(this.values.clone())));
+    class.truth(|this: &Record| !this.values.is_empty());
+    let class = class.build(object);
+    // _make(iterable) makes an instance of the class itself, so is added
+    // once it exists
+    let weak = Rc::downgrade(&class);
+    let make: NativeFn = Rc::new(move |interpreter, args| {
+        args.check("_make", 1, 1)?;
+        let class = weak.upgrade().ok_or_else(|| Exception::new("ReferenceError", "the class no longer exists"))?;
+        let values = interpreter.iterate(&args.positional[0])?;
+        if values.len() != layout.fields.len() {
+            let message = format!("Expected {} arguments, got {}", layout.fields.len(), values.len());
+            return Err(Exception::new("TypeError", &message));
+        }
+        return Ok(new_record(class, &layout, values));
+    });
+    class.attrs.borrow_mut().insert(String::from("_make"), Builtin::build_native("_make", make));
+    return class;
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testing::{eval, exec, python};
+
+    #[test]
+    fn test_deque() {
+        let mut python = python("from collections import deque, defaultdict, OrderedDict, Counter, namedtuple, ChainMap\n");
+        exec(&mut python, "d = deque([1, 2, 3], maxlen=4)\nd.append(4)\nd.append(5)\nd.appendleft(0)\nd.rotate(1)\n");
+        assert_eq!(eval(&mut python, "d"), "deque([4, 0, 2, 3], maxlen=4)");
+        assert_eq!(eval(&mut python, "(d.pop(), d.popleft(), d[0], d[-1], len(d), 1 in d, list(d))"), "(3, 4, 0, 2, 2, False, [0, 2])");
+        assert_eq!(eval(&mut python, "(d == deque([0, 2]), d == [0, 2], bool(deque()))"), "(True, False, False)");
+        assert_eq!(eval(&mut python, "deque().pop()"), "IndexError: pop from an empty deque");
+        assert_eq!(eval(&mut python, "d[5]"), "IndexError: deque index out of range");
+        assert_eq!(eval(&mut python, "{d: 1}"), "TypeError: unhashable type: 'collections.deque'");
+        assert_eq!(eval(&mut python, "deque(maxlen=-1)"), "ValueError: maxlen must be non-negative");
+        assert_eq!(eval(&mut python, "(list(reversed(deque([1, 2, 3]))), list(reversed(deque())))"), "([3, 2, 1], [])");
+    }
+
+    #[test]
+    fn test_defaultdict() {
+        let mut python = python("from collections import deque, defaultdict, OrderedDict, Counter, namedtuple, ChainMap\n");
+        exec(&mut python, "g = defaultdict(list)\ng['a'] = [g['x'], 1]\nn = g['b']\n");
+        assert_eq!(eval(&mut python, "g"), "defaultdict(<class 'list'>, {'x': [], 'a': [[], 1], 'b': []})");
+        assert_eq!(eval(&mut python, "(g.get('c'), 'c' in g, g == {'x': [], 'a': [[], 1], 'b': []})"), "(None, False, True)");
+        assert_eq!(eval(&mut python, "defaultdict(None)['x']"), "KeyError: 'x'");
+        assert_eq!(eval(&mut python, "defaultdict(1)"), "TypeError: first argument must be callable or None");
+        exec(&mut python, "import json\ncounts = defaultdict(int)\ncounts['a'] += 2\ncounts['b']\ndel counts['b']\n");
+        assert_eq!(eval(&mut python, "(isinstance(counts, dict), dict(counts), json.dumps(counts), '%(a)s' % counts, dict.keys(counts))"), "(True, {'a': 2}, '{\"a\": 2}', '2', dict_keys(['a']))");
+        assert_eq!(eval(&mut python, "(list(counts.items()), counts.pop('a'), counts)"), "([('a', 2)], 2, defaultdict(<class 'int'>, {}))");
+    }
+
+    #[test]
+    fn test_ordered_dict() {
+        let mut python = python("from collections import deque, defaultdict, OrderedDict, Counter, namedtuple, ChainMap\n");
+        exec(&mut python, "o = OrderedDict([('a', 1), ('b', 2), ('c', 3)])\no.move_to_end('a')\n");
+        assert_eq!(eval(&mut python, "(o, o.popitem(), o.popitem(last=False))"), "(OrderedDict([('c', 3)]), ('a', 1), ('b', 2))");
+        assert_eq!(eval(&mut python, "(OrderedDict(a=1, b=2) == OrderedDict(b=2, a=1), OrderedDict(a=1, b=2) == {'b': 2, 'a': 1})"), "(False, True)");
+    }
+
+    #[test]
+    fn test_counter() {
+        let mut python = python("from collections import deque, defaultdict, OrderedDict, Counter, namedtuple, ChainMap\n");
+        exec(&mut python, "c = Counter('abracadabra')\nc.update(['z', 'a'])\nc.subtract(z=1)\n");
+        assert_eq!(eval(&mut python, "c"), "Counter({'a': 6, 'b': 2, 'r': 2, 'c': 1, 'd': 1, 'z': 0})");
+        assert_eq!(eval(&mut python, "(c.most_common(2), c['q'], c.total(), sorted(Counter(x=2, y=1).elements()))"), "([('a', 6), ('b', 2)], 0, 12, ['x', 'x', 'y'])");
+        assert_eq!(eval(&mut python, "Counter(a=1) == Counter(a=1, b=0)"), "True");
+        exec(&mut python, "import json\na = Counter(a=3, b=1)\nb = Counter(a=1, b=2, c=-1)\n");
+        assert_eq!(eval(&mut python, "(a + b, a - b, a | b, a & b)"), "(Counter({'a': 4, 'b': 3}), Counter({'a': 2, 'c': 1}), Counter({'a': 3, 'b': 2}), Counter({'a': 1, 'b': 1}))");
+        assert_eq!(eval(&mut python, "(isinstance(a, dict), json.dumps(a), dict(**a))"), "(True, '{\"a\": 3, \"b\": 1}', {'a': 3, 'b': 1})");
+        assert_eq!(eval(&mut python, "a + {'a': 1}"), "TypeError: unsupported operand type(s) for +: 'Counter' and 'dict'");
+    }
+
+    #[test]
+    fn test_reduce() {
+        let mut python = python("from collections import deque, defaultdict, OrderedDict, Counter, namedtuple, ChainMap\nimport pickle\n");
+        assert_eq!(eval(&mut python, "deque([1], 2).__reduce__()"), "(<class 'collections.deque'>, ([1], 2))");
+        assert_eq!(eval(&mut python, "defaultdict(int, a=1).__reduce__()"), "(<class 'collections.defaultdict'>, (<class 'int'>, {'a': 1}))");
+        let source = "
+values = [deque([1, 2]), defaultdict(list, a=[1]), OrderedDict(b=1, a=2), Counter('aab'), ChainMap({'a': 1}, {})]
+for protocol in range(2, 6):
+    copies = pickle.loads(pickle.dumps(values, protocol))
+    assert [type(copy) for copy in copies] == [type(value) for value in values]
+    assert repr(copies) == repr(values)
+";
+        exec(&mut python, source);
+    }
+
+    #[test]
+    fn test_namedtuple() {
+        let mut python = python("from collections import deque, defaultdict, OrderedDict, Counter, namedtuple, ChainMap\n");
+        exec(&mut python, "Point = namedtuple('Point', 'x, y', defaults=[0])\np = Point(1)\nx, y = p\n");
+        assert_eq!(eval(&mut python, "(p, p.x, p[1], x, len(p), p == (1, 0), {p: 1}[(1, 0)])"), "(Point(x=1, y=0), 1, 0, 1, 2, True, 1)");
+        assert_eq!(eval(&mut python, "(p._replace(y=5), p._asdict(), Point._make([3, 4]), Point._fields)"), "(Point(x=1, y=5), {'x': 1, 'y': 0}, Point(x=3, y=4), ('x', 'y'))");
+        assert_eq!(eval(&mut python, "Point()"), "TypeError: Point.__new__() missing 1 required positional argument: 'x'");
+        assert_eq!(eval(&mut python, "Point(1, 2, 3)"), "TypeError: Point.__new__() takes 3 positional arguments but 4 were given");
+        assert_eq!(eval(&mut python, "namedtuple('P', 'x x')"), "ValueError: Encountered duplicate field name: 'x'");
+        assert_eq!(eval(&mut python, "namedtuple('P', 'x def', rename=True)._fields"), "('x', '_1')");
+    }
+
+    #[test]
+    fn test_chain_map() {
+        let mut python = python("from collections import deque, defaultdict, OrderedDict, Counter, namedtuple, ChainMap\n");
+        exec(&mut python, "inner = {'a': 1}\ncm = ChainMap({'b': 2}, inner)\ncm['a'] = 3\nchild = cm.new_child({'c': 4})\n");
+        assert_eq!(eval(&mut python, "(cm['a'], inner['a'], list(cm), len(child), 'c' in child, 'c' in cm)"), "(3, 1, ['a', 'b'], 3, True, False)");
+        assert_eq!(eval(&mut python, "(cm, child.parents == cm, cm == {'a': 3, 'b': 2})"), "(ChainMap({'b': 2, 'a': 3}, {'a': 1}), True, True)");
+        assert_eq!(eval(&mut python, "cm['z']"), "KeyError: 'z'");
+    }
+
+    #[test]
+    fn test_recursive_repr() {
+        let mut python = python("from collections import deque, OrderedDict, ChainMap
+");
+        exec(&mut python, "q = deque([1])\nq.append(q)\no = OrderedDict()\no['a'] = o\nc = ChainMap({})\nc['a'] = c\nr = deque([1])\nr.append(r)\n");
+        assert_eq!(eval(&mut python, "(q, o, c)"), "(deque([1, [...]]), OrderedDict([('a', ...)]), ChainMap({'a': ...}))");
+        assert_eq!(eval(&mut python, "(q == q, q in q)"), "(True, True)");
+        assert_eq!(eval(&mut python, "q == r"), "RecursionError: maximum recursion depth exceeded in comparison");
+    }
+
+    #[test]
+    fn test_class_names() {
+        let mut python = python("import collections, functools, itertools\n");
+        let source = "[(c.__name__, c.__module__) for c in (collections.deque, collections.OrderedDict, collections.Counter, collections.ChainMap, functools.partial, itertools.chain)]";
+        assert_eq!(eval(&mut python, source), "[('deque', 'collections'), ('OrderedDict', 'collections'), ('Counter', 'collections'), ('ChainMap', 'collections'), ('partial', 'functools'), ('chain', 'itertools')]");
+        assert_eq!(eval(&mut python, "(collections.deque, type(itertools.count()).__name__)"), "(<class 'collections.deque'>, 'count')");
+    }
+}
@@ -0,0 +1,3136 @@
+use crate::ast::{BinOp, UnaryOp};
+use crate::dict::{new_dict, Dict};
+use crate::interpreter::{as_bigint, as_int, not_an_int, EvalResult, Interpreter};
+use crate::modules::collections::{namedtuple_class, Layout};
+use crate::modules::fractions;
+use crate::native::{instance, NativeClass};
+use crate::value::{new_tuple, Args, Builtin, BuiltinFn, Class, Exception, Module, NativeFn, Object, Value};
+use num_bigint::{BigInt, BigUint};
+use num_rational::BigRational;
+use num_integer::Integer;
+use num_traits::{One, Pow, Signed, ToPrimitive, Zero};
+use std::cell::{RefCell, RefMut};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/* decimal - decimal floating point arithmetic, like CPython's decimal
+ *
+ * The arithmetic is a port of CPython's pure Python implementation,
+ * _pydecimal: a Decimal is a sign, a string of coefficient digits and an
+ * exponent, and each operation works out the exact result, or enough
+ * digits of it, before rounding to the context's precision. Results agree
+ * digit for digit with CPython's.
+ *
+ * The context is per thread, as in CPython; each interpreter built starts
+ * the thread over with a fresh default context, so the last interpreter
+ * built on a thread decides it. `with localcontext(prec=50):` works in a
+ * copy of the context, put back afterwards.
+ *
+ * Like the C implementation, an operation collects the conditions it
+ * meets, sets their flags, then raises once for all the trapped ones, e.g.
+ * "decimal.InvalidOperation: [<class 'decimal.ConversionSyntax'>]". Equal
+ * and not-equal comparisons never raise: a signaling NaN only sets the
+ * InvalidOperation flag. Missing: fma(), the logical and shift operations,
+ * next_plus() and friends, format specs, and a repr for contexts.
+ */
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Kind {
+    Finite,
+    Infinite,
+    NaN,
+    SNaN,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Decimal {
+    pub negative: bool,
+    // the coefficient's digits, without leading zeros; for a NaN its
+    // diagnostic, which may be empty
+    pub digits: String,
+    pub exp: i64,
+    pub kind: Kind,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Rounding {
+    Down,
+    HalfUp,
+    HalfEven,
+    Ceiling,
+    Floor,
+    Up,
+    HalfDown,
+    Up05,
+}
+
+const ROUNDINGS: &[(Rounding, &str)] = &[
+    (Rounding::Ceiling, "ROUND_CEILING"),
+    (Rounding::Floor, "ROUND_FLOOR"),
+    (Rounding::Up, "ROUND_UP"),
+    (Rounding::Down, "ROUND_DOWN"),
+    (Rounding::HalfUp, "ROUND_HALF_UP"),
+    (Rounding::HalfDown, "ROUND_HALF_DOWN"),
+    (Rounding::HalfEven, "ROUND_HALF_EVEN"),
+    (Rounding::Up05, "ROUND_05UP"),
+];
+
+// the conditions an operation can meet, as bits of Context::status
+const INVALID_OPERATION: u32 = 1;
+const CONVERSION_SYNTAX: u32 = 1 << 1;
+const DIVISION_IMPOSSIBLE: u32 = 1 << 2;
+const DIVISION_UNDEFINED: u32 = 1 << 3;
+const INVALID_CONTEXT: u32 = 1 << 4;
+const FLOAT_OPERATION: u32 = 1 << 5;
+const DIVISION_BY_ZERO: u32 = 1 << 6;
+const OVERFLOW: u32 = 1 << 7;
+const UNDERFLOW: u32 = 1 << 8;
+const SUBNORMAL: u32 = 1 << 9;
+const INEXACT: u32 = 1 << 10;
+const ROUNDED: u32 = 1 << 11;
+const CLAMPED: u32 = 1 << 12;
+
+// the signals, in the C implementation's order, with the conditions
+// signalling each
+const SIGNALS: &[(&str, u32)] = &[
+    (
+        "InvalidOperation",
+        INVALID_OPERATION | CONVERSION_SYNTAX | DIVISION_IMPOSSIBLE | DIVISION_UNDEFINED | INVALID_CONTEXT,
+    ),
+    ("FloatOperation", FLOAT_OPERATION),
+    ("DivisionByZero", DIVISION_BY_ZERO),
+    ("Overflow", OVERFLOW),
+    ("Underflow", UNDERFLOW),
+    ("Subnormal", SUBNORMAL),
+    ("Inexact", INEXACT),
+    ("Rounded", ROUNDED),
+    ("Clamped", CLAMPED),
+];
+
+// conditions that aren't signals themselves, each with its signal
+const CONDITIONS: &[(&str, u32)] = &[
+    ("ConversionSyntax", CONVERSION_SYNTAX),
+    ("DivisionImpossible", DIVISION_IMPOSSIBLE),
+    ("DivisionUndefined", DIVISION_UNDEFINED),
+    ("InvalidContext", INVALID_CONTEXT),
+];
+
+pub const MAX_PREC: i64 = 999_999_999_999_999_999;
+pub const MAX_EMAX: i64 = 999_999_999_999_999_999;
+pub const MIN_EMIN: i64 = -999_999_999_999_999_999;
+pub const MIN_ETINY: i64 = MIN_EMIN - (MAX_PREC - 1);
+
+/* The precision, rounding and exponent limits an operation works to, and
+ * the conditions it met along the way
+ */
+#[derive(Debug, Clone)]
+pub struct Context {
+    pub prec: i64,
+    pub rounding: Rounding,
+    pub emin: i64,
+    pub emax: i64,
+    pub capitals: bool,
+    pub clamp: bool,
+    pub status: u32,
+}
+
+impl Context {
+    pub fn build_new() -> Context {
+        return Context {
+            prec: 28,
+            rounding: Rounding::HalfEven,
+            emin: -999_999,
+            emax: 999_999,
+            capitals: true,
+            clamp: false,
+            status: 0,
+        };
+    }
+
+    fn etiny(&self) -> i64 {
+        return self.emin - self.prec + 1;
+    }
+
+    fn etop(&self) -> i64 {
+        return self.emax - self.prec + 1;
+    }
+
+    fn raise(&mut self, condition: u32) {
+        self.status |= condition;
+    }
+
+    /* the result of an invalid operation: NaN
+     */
+    fn invalid(&mut self, condition: u32) -> Decimal {
+        self.status |= condition;
+        return Decimal::nan();
+    }
+
+    /* the result of an operation on a signaling NaN: it quietened
+     */
+    fn invalid_nan(&mut self, operand: &Decimal) -> Decimal {
+        self.status |= INVALID_OPERATION;
+        let quiet = Decimal::special(operand.negative, Kind::NaN, &operand.digits);
+        return quiet.fix_nan(self);
+    }
+
+    fn division_by_zero(&mut self, negative: bool) -> Decimal {
+        self.status |= DIVISION_BY_ZERO;
+        return Decimal::infinity(negative);
+    }
+
+    /* the result of an overflow: infinity, or the largest number if the
+     * rounding is towards zero
+     */
+    fn overflow(&mut self, negative: bool) -> Decimal {
+        self.status |= OVERFLOW;
+        let largest = match self.rounding {
+            Rounding::HalfUp | Rounding::HalfEven | Rounding::HalfDown | Rounding::Up => false,
+            Rounding::Ceiling => negative,
+            Rounding::Floor => !negative,
+            _ => true,
+        };
+        if !largest {
+            return Decimal::infinity(negative);
+        }
+        return Decimal::finite(negative, "9".repeat(self.prec as usize), self.emax - self.prec + 1);
+    }
+
+    /* a copy rounding half to even, for the functions whose results are
+     * always rounded that way
+     */
+    fn half_even(&self) -> Context {
+        let mut context = self.clone();
+        context.rounding = Rounding::HalfEven;
+        return context;
+    }
+}
+
+fn ten_pow(exp: i64) -> BigInt {
+    return BigInt::from(10u32).pow(exp as u64);
+}
+
+fn digit_len(val: &BigInt) -> i64 {
+    return val.magnitude().to_string().len() as i64;
+}
+
+/* len(str(val)) in Python, which counts a minus sign
+ */
+fn str_len(val: &BigInt) -> i64 {
+    return val.to_string().len() as i64;
+}
+
+fn bit_len(val: &BigInt) -> i64 {
+    return val.bits() as i64;
+}
+
+/* the digits of a nonnegative integer
+ */
+fn digits_of(val: &BigInt) -> String {
+    return val.magnitude().to_string();
+}
+
+/* a / b rounded to the nearest integer, halves to even
+ */
+fn div_nearest(a: &BigInt, b: &BigInt) -> BigInt {
+    let (quotient, remainder) = a.div_mod_floor(b);
+    let up = BigInt::from(2) * remainder + BigInt::from(quotient.is_odd() as u8) > *b;
+    return quotient + BigInt::from(up as u8);
+}
+
+/* x / 2**shift rounded to the nearest integer, halves to even
+ */
+fn rshift_nearest(x: &BigInt, shift: i64) -> BigInt {
+    let unit = BigInt::one() << shift as usize;
+    let quotient = x >> shift as usize;
+    let low = x.mod_floor(&unit);
+    let up = BigInt::from(2) * low + BigInt::from(quotient.is_odd() as u8) > unit;
+    return quotient + BigInt::from(up as u8);
+}
+
+/* the integer nearest the square root of n, from the estimate a
+ */
+fn sqrt_nearest(n: &BigInt, a: BigInt) -> BigInt {
+    let (mut a, mut b) = (a, BigInt::zero());
+    while a != b {
+        let next = (&a - (-n).div_floor(&a)) >> 1usize;
+        b = std::mem::replace(&mut a, next);
+    }
+    return a;
+}
+
+/* n * 10**e if that's an integer
+ */
+fn decimal_lshift_exact(n: &BigInt, e: &BigInt) -> Option<BigInt> {
+    if n.is_zero() {
+        return Some(BigInt::zero());
+    }
+    if !e.is_negative() {
+        return Some(n * ten_pow(e.to_i64()?));
+    }
+    let text = digits_of(n);
+    let zeros = (text.len() - text.trim_end_matches('0').len()) as i64;
+    let shift = (-e).to_i64()?;
+    if zeros < shift {
+        return None;
+    }
+    return Some(n / ten_pow(shift));
+}
+
+/* number of terms of the Taylor series for a precision M
+ */
+fn terms(m: &BigInt, l: i64) -> i64 {
+    return (10 * str_len(m) + 3 * l - 1) / (3 * l);
+}
+
+/* an integer approximation to M*log(x/M)
+ */
+fn ilog(x: &BigInt, m: &BigInt) -> BigInt {
+    const L: i64 = 8;
+    let mut y = x - m;
+    let mut r: i64 = 0;
+    loop {
+        let reduce = if r <= L {
+            (y.abs() << (L - r) as usize) >= *m
+        } else {
+            (y.abs() >> (r - L) as usize) >= *m
+        };
+        if !reduce {
+            break;
+        }
+        let root = sqrt_nearest(&(m * (m + rshift_nearest(&y, r))), m.clone());
+        y = div_nearest(&((m * &y) << 1usize), &(m + root));
+        r += 1;
+    }
+    let t = terms(m, L);
+    let yshift = rshift_nearest(&y, r);
+    let mut w = div_nearest(m, &BigInt::from(t));
+    for k in (1..t).rev() {
+        w = div_nearest(m, &BigInt::from(k)) - div_nearest(&(&yshift * &w), m);
+    }
+    return div_nearest(&(w * y), m);
+}
+
+thread_local! {
+    // digits of log(10) worked out so far
+    static LOG10_DIGITS: RefCell<String> = RefCell::new(String::from("23025850929940456840179914546843642076011014886"));
+}
+
+/* floor(10**p * log(10))
+ */
+fn log10_digits(p: i64) -> BigInt {
+    return LOG10_DIGITS.with(|digits| {
+        let mut digits = digits.borrow_mut();
+        if p as usize >= digits.len() {
+            let mut extra = 3;
+            let found = loop {
+                let m = ten_pow(p + extra + 2);
+                let found = div_nearest(&ilog(&(BigInt::from(10) * &m), &m), &BigInt::from(100)).to_string();
+                if !found.ends_with(&"0".repeat(extra as usize)) {
+                    break found;
+                }
+                extra += 3;
+            };
+            let trimmed = found.trim_end_matches('0');
+            *digits = String::from(&trimmed[..trimmed.len() - 1]);
+        }
+        return digits[..p as usize + 1].parse().unwrap();
+    });
+}
+
+/* c*10**e as d*10**f, with 1 <= d <= 10 or 0.1 <= d <= 1
+ */
+fn log_exponent(c: &BigInt, e: i64) -> i64 {
+    let l = str_len(c);
+    return e + l - (e + l >= 1) as i64;
+}
+
+/* an integer approximation to 10**p * log10(c*10**e), within 1
+ */
+fn dlog10(c: &BigInt, e: i64, p: i64) -> BigInt {
+    let p = p + 2;
+    let f = log_exponent(c, e);
+    let (log_d, log_tenpower) = if p > 0 {
+        let m = ten_pow(p);
+        let k = e + p - f;
+        let c = if k >= 0 { c * ten_pow(k) } else { div_nearest(c, &ten_pow(-k)) };
+        let log_d = ilog(&c, &m);
+        let log_10 = log10_digits(p);
+        (div_nearest(&(log_d * &m), &log_10), BigInt::from(f) * m)
+    } else {
+        (BigInt::zero(), div_nearest(&BigInt::from(f), &ten_pow(-p)))
+    };
+    return div_nearest(&(log_tenpower + log_d), &BigInt::from(100));
+}
+
+/* an integer approximation to 10**p * log(c*10**e), within 1
+ */
+fn dlog(c: &BigInt, e: i64, p: i64) -> BigInt {
+    let p = p + 2;
+    let f = log_exponent(c, e);
+    let log_d = if p > 0 {
+        let k = e + p - f;
+        let c = if k >= 0 { c * ten_pow(k) } else { div_nearest(c, &ten_pow(-k)) };
+        ilog(&c, &ten_pow(p))
+    } else {
+        BigInt::zero()
+    };
+    let mut f_log_ten = BigInt::zero();
+    if f != 0 {
+        let extra = str_len(&BigInt::from(f.abs())) - 1;
+        if p + extra >= 0 {
+            f_log_ten = div_nearest(&(BigInt::from(f) * log10_digits(p + extra)), &ten_pow(extra));
+        }
+    }
+    return div_nearest(&(f_log_ten + log_d), &BigInt::from(100));
+}
+
+/* an integer approximation to M*exp(x/M), for small x/M
+ */
+fn iexp(x: &BigInt, m: &BigInt) -> BigInt {
+    const L: i64 = 8;
+    let r = bit_len(&(x << L as usize).div_floor(m));
+    let t = terms(m, L);
+    let mut y = div_nearest(x, &BigInt::from(t));
+    let mshift = m << r as usize;
+    for i in (1..t).rev() {
+        y = div_nearest(&(x * (&mshift + &y)), &(&mshift * BigInt::from(i)));
+    }
+    for k in (0..r).rev() {
+        let mshift = m << (k + 2) as usize;
+        y = div_nearest(&(&y * (&y + &mshift)), &mshift);
+    }
+    return m + y;
+}
+
+/* exp(c*10**e) as d*10**f, with p digits in d, within 1
+ */
+fn dexp(c: &BigInt, e: i64, p: i64) -> (BigInt, i64) {
+    let p = p + 2;
+    let extra = (e + str_len(c) - 1).max(0);
+    let q = p + extra;
+    let shift = e + q;
+    let cshift = if shift >= 0 { c * ten_pow(shift) } else { c.div_floor(&ten_pow(-shift)) };
+    let (quot, rem) = cshift.div_mod_floor(&log10_digits(q));
+    let rem = div_nearest(&rem, &ten_pow(extra));
+    let coeff = div_nearest(&iexp(&rem, &ten_pow(p)), &BigInt::from(1000));
+    return (coeff, quot.to_i64().unwrap_or(i64::MAX / 4) - p + 3);
+}
+
+/* x**y for x = xc*10**xe and y = yc*10**ye, as c*10**e with p digits in
+ * c, within 1; x is positive and not 1, and y isn't 0
+ */
+fn dpower(xc: &BigInt, xe: i64, yc: &BigInt, ye: i64, p: i64) -> (BigInt, i64) {
+    let b = digit_len(yc) + ye;
+    let lxc = dlog(xc, xe, p + b + 1);
+    let shift = ye - b;
+    let pc = if shift >= 0 { lxc * yc * ten_pow(shift) } else { div_nearest(&(lxc * yc), &ten_pow(-shift)) };
+    if pc.is_zero() {
+        // a result that isn't exactly 1 is easier to round correctly
+        if (str_len(xc) + xe >= 1) == yc.is_positive() {
+            return (ten_pow(p - 1) + 1, 1 - p);
+        }
+        return (ten_pow(p) - 1, -p);
+    }
+    let (coeff, exp) = dexp(&pc, -(p + 1), p + 1);
+    return (div_nearest(&coeff, &BigInt::from(10)), exp + 1);
+}
+
+/* a lower bound for 100*log10(c)
+ */
+fn log10_lb(c: &BigInt) -> i64 {
+    let text = c.to_string();
+    let correction = [100, 70, 53, 40, 31, 23, 16, 10, 5];
+    let first = (text.as_bytes()[0] - b'1') as usize;
+    return 100 * text.len() as i64 - correction[first];
+}
+
+/* whether the digits (of a coefficient being rounded) are all zeros
+ */
+fn all_zeros(digits: &[u8]) -> bool {
+    return digits.iter().all(|digit| *digit == b'0');
+}
+
+/* one more than a string of digits
+ */
+fn increment(digits: &str) -> String {
+    let mut bytes = digits.as_bytes().to_vec();
+    for byte in bytes.iter_mut().rev() {
+        if *byte == b'9' {
+            *byte = b'0';
+        } else {
+            *byte += 1;
+            return String::from_utf8(bytes).unwrap();
+        }
+    }
+    bytes.insert(0, b'1');
+    return String::from_utf8(bytes).unwrap();
+}
+
+/* whether a result with coefficient coeff, worked out to more digits than
+ * prec, can be rounded correctly: it isn't halfway or exact in its last
+ * digits
+ */
+fn rounds_correctly(coeff: &BigInt, prec: i64) -> bool {
+    let places = digit_len(coeff) - prec - 1;
+    if places < 0 {
+        return false;
+    }
+    return !(coeff % (BigInt::from(5) * ten_pow(places))).is_zero();
+}
+
+impl Decimal {
+    pub fn finite(negative: bool, digits: String, exp: i64) -> Decimal {
+        return Decimal {
+            negative,
+            digits,
+            exp,
+            kind: Kind::Finite,
+        };
+    }
+
+    pub fn special(negative: bool, kind: Kind, digits: &str) -> Decimal {
+        return Decimal {
+            negative,
+            digits: String::from(digits),
+            exp: 0,
+            kind,
+        };
+    }
+
+    pub fn nan() -> Decimal {
+        return Decimal::special(false, Kind::NaN, "");
+    }
+
+    pub fn infinity(negative: bool) -> Decimal {
+        return Decimal::special(negative, Kind::Infinite, "0");
+    }
+
+    fn zero(negative: bool, exp: i64) -> Decimal {
+        return Decimal::finite(negative, String::from("0"), exp);
+    }
+
+    fn one() -> Decimal {
+        return Decimal::finite(false, String::from("1"), 0);
+    }
+
+    pub fn from_i64(val: i64) -> Decimal {
+        return Decimal::finite(val < 0, val.unsigned_abs().to_string(), 0);
+    }
+
+    pub fn from_bigint(val: &BigInt) -> Decimal {
+        return Decimal::finite(val.is_negative(), digits_of(val), 0);
+    }
+
+    /* the exact value of a float
+     */
+    pub fn from_f64(val: f64) -> Decimal {
+        if val.is_nan() {
+            return Decimal::nan();
+        }
+        if val.is_infinite() {
+            return Decimal::infinity(val < 0.0);
+        }
+        let negative = val.is_sign_negative();
+        let (numer, denom) = fractions::float_ratio(val.abs());
+        // the denominator is a power of two, 2**k, and n/2**k = n*5**k/10**k
+        let k = denom.bits() as i64 - 1;
+        let coeff = numer * BigInt::from(5u32).pow(k as u64);
+        return Decimal::finite(negative, digits_of(&coeff), -k);
+    }
+
+    /* a string in the syntax of the Decimal constructor; whitespace and
+     * underscores are removed first. None if it isn't a number, and the
+     * conditions met if it is but can't be represented.
+     */
+    pub fn parse(text: &str) -> Result<Decimal, u32> {
+        let text: String = text.trim().chars().filter(|c| *c != '_').collect();
+        let lower = text.to_ascii_lowercase();
+        let (negative, body) = match lower.as_bytes().first() {
+            Some(b'-') => (true, &lower[1..]),
+            Some(b'+') => (false, &lower[1..]),
+            _ => (false, &lower[..]),
+        };
+        if body == "inf" || body == "infinity" {
+            return Ok(Decimal::infinity(negative));
+        }
+        for (prefix, kind) in [("snan", Kind::SNaN), ("nan", Kind::NaN)] {
+            if let Some(diagnostic) = body.strip_prefix(prefix) {
+                if !diagnostic.bytes().all(|c| c.is_ascii_digit()) {
+                    return Err(CONVERSION_SYNTAX);
+                }
+                return Ok(Decimal::special(negative, kind, diagnostic.trim_start_matches('0')));
+            }
+        }
+        let (mantissa, exponent) = match body.find('e') {
+            Some(pos) => (&body[..pos], Some(&body[pos + 1..])),
+            None => (body, None),
+        };
+        let (int_part, frac_part) = match mantissa.find('.') {
+            Some(pos) => (&mantissa[..pos], &mantissa[pos + 1..]),
+            None => (mantissa, ""),
+        };
+        let is_digits = |part: &str| part.bytes().all(|c| c.is_ascii_digit());
+        if int_part.len() + frac_part.len() == 0 || !is_digits(int_part) || !is_digits(frac_part) {
+            return Err(CONVERSION_SYNTAX);
+        }
+        let exp: i128 = match exponent {
+            Some(exponent) => {
+                let digits = exponent.strip_prefix(['+', '-']).unwrap_or(exponent);
+                if digits.is_empty() || !is_digits(digits) {
+                    return Err(CONVERSION_SYNTAX);
+                }
+                // exponents too large for an i128 are out of range anyway
+                let digits = digits.trim_start_matches('0');
+                let magnitude: i128 = if digits.len() > 30 { i128::MAX / 2 } else { digits.parse().unwrap_or(0) };
+                if exponent.starts_with('-') {
+                    -magnitude
+                } else {
+                    magnitude
+                }
+            }
+            None => 0,
+        };
+        let digits = format!("{}{}", int_part, frac_part);
+        let digits = match digits.trim_start_matches('0') {
+            "" => String::from("0"),
+            trimmed => String::from(trimmed),
+        };
+        let exp = exp - frac_part.len() as i128;
+        let adjusted = exp + digits.len() as i128 - 1;
+        if exp < MIN_ETINY as i128 || adjusted > MAX_EMAX as i128 {
+            return Err(INVALID_OPERATION);
+        }
+        return Ok(Decimal::finite(negative, digits, exp as i64));
+    }
+
+    pub fn is_special(&self) -> bool {
+        return self.kind != Kind::Finite;
+    }
+
+    pub fn is_nan(&self) -> bool {
+        return matches!(self.kind, Kind::NaN | Kind::SNaN);
+    }
+
+    pub fn is_zero(&self) -> bool {
+        return self.kind == Kind::Finite && self.digits == "0";
+    }
+
+    /* bool(): NaNs and infinities are true
+     */
+    pub fn is_truthy(&self) -> bool {
+        return !self.is_zero();
+    }
+
+    pub fn adjusted(&self) -> i64 {
+        if self.is_special() {
+            return 0;
+        }
+        return self.exp + self.digits.len() as i64 - 1;
+    }
+
+    fn coeff(&self) -> BigInt {
+        return self.digits.parse().unwrap_or_default();
+    }
+
+    fn is_integer(&self) -> bool {
+        if self.is_special() {
+            return false;
+        }
+        if self.exp >= 0 {
+            return true;
+        }
+        let places = (-self.exp) as usize;
+        return self.digits.len() <= places && all_zeros(self.digits.as_bytes()) || all_zeros(&self.digits.as_bytes()[self.digits.len().saturating_sub(places)..]);
+    }
+
+    /* whether an integer is even
+     */
+    fn is_even(&self) -> bool {
+        if self.is_zero() || self.exp > 0 {
+            return true;
+        }
+        let pos = self.digits.len() as i64 - 1 + self.exp;
+        return pos < 0 || b"02468".contains(&self.digits.as_bytes()[pos as usize]);
+    }
+
+    pub fn copy_abs(&self) -> Decimal {
+        let mut result = self.clone();
+        result.negative = false;
+        return result;
+    }
+
+    pub fn copy_negate(&self) -> Decimal {
+        let mut result = self.clone();
+        result.negative = !self.negative;
+        return result;
+    }
+
+    pub fn copy_sign(&self, other: &Decimal) -> Decimal {
+        let mut result = self.clone();
+        result.negative = other.negative;
+        return result;
+    }
+
+    /* the integer part, truncated
+     */
+    pub fn trunc(&self) -> BigInt {
+        let magnitude: BigInt = if self.exp >= 0 {
+            self.coeff() * ten_pow(self.exp)
+        } else {
+            let keep = self.digits.len() as i64 + self.exp;
+            if keep <= 0 {
+                BigInt::zero()
+            } else {
+                self.digits[..keep as usize].parse().unwrap()
+            }
+        };
+        return if self.negative { -magnitude } else { magnitude };
+    }
+
+    /* the value as an integer ratio in lowest terms, for finite numbers
+     */
+    pub fn as_integer_ratio(&self) -> (BigInt, BigInt) {
+        if self.is_zero() {
+            return (BigInt::zero(), BigInt::one());
+        }
+        let mut n = self.coeff();
+        let d = if self.exp >= 0 {
+            n *= ten_pow(self.exp);
+            BigInt::one()
+        } else {
+            let mut d5 = -self.exp;
+            let five = BigInt::from(5);
+            while d5 > 0 && (&n % &five).is_zero() {
+                n /= &five;
+                d5 -= 1;
+            }
+            let mut d2 = -self.exp;
+            let shift2 = (n.trailing_zeros().unwrap_or(0) as i64).min(d2);
+            n >>= shift2 as usize;
+            d2 -= shift2;
+            five.pow(d5 as u64) << d2 as usize
+        };
+        if self.negative {
+            n = -n;
+        }
+        return (n, d);
+    }
+
+    /* float(), rounding correctly
+     */
+    pub fn to_f64(&self) -> f64 {
+        let sign = if self.negative { -1.0 } else { 1.0 };
+        match self.kind {
+            Kind::NaN | Kind::SNaN => return sign * f64::NAN,
+            Kind::Infinite => return sign * f64::INFINITY,
+            Kind::Finite => (),
+        };
+        // anything this far out is 0 or inf, and needn't be written out
+        if self.adjusted() > 400 {
+            return sign * f64::INFINITY;
+        }
+        if self.adjusted() < -400 {
+            return sign * 0.0;
+        }
+        return format!("{}{}e{}", if self.negative { "-" } else { "" }, self.digits, self.exp).parse().unwrap();
+    }
+
+    /* str(), or to_eng_string() with eng
+     */
+    pub fn to_text(&self, eng: bool, capitals: bool) -> String {
+        let sign = if self.negative { "-" } else { "" };
+        match self.kind {
+            Kind::Infinite => return format!("{}Infinity", sign),
+            Kind::NaN => return format!("{}NaN{}", sign, self.digits),
+            Kind::SNaN => return format!("{}sNaN{}", sign, self.digits),
+            Kind::Finite => (),
+        };
+        let len = self.digits.len() as i64;
+        // digits of the coefficient before the point, without an exponent
+        let leftdigits = self.exp + len;
+        let dotplace = if self.exp <= 0 && leftdigits > -6 {
+            leftdigits
+        } else if !eng {
+            1
+        } else if self.digits == "0" {
+            (leftdigits + 1).rem_euclid(3) - 1
+        } else {
+            (leftdigits - 1).rem_euclid(3) + 1
+        };
+        let (intpart, fracpart) = if dotplace <= 0 {
+            (String::from("0"), format!(".{}{}", "0".repeat(-dotplace as usize), self.digits))
+        } else if dotplace >= len {
+            (format!("{}{}", self.digits, "0".repeat((dotplace - len) as usize)), String::new())
+        } else {
+            let (left, right) = self.digits.split_at(dotplace as usize);
+            (String::from(left), format!(".{}", right))
+        };
+        let exp = if leftdigits == dotplace {
+            String::new()
+        } else {
+            format!("{}{:+}", if capitals { "E" } else { "e" }, leftdigits - dotplace)
+        };
+        return format!("{}{}{}{}", sign, intpart, fracpart, exp);
+    }
+
+    /* 0 for a number, 1 for a quiet NaN and 2 for a signaling one
+     */
+    fn nan_kind(&self) -> u8 {
+        match self.kind {
+            Kind::NaN => return 1,
+            Kind::SNaN => return 2,
+            _ => return 0,
+        }
+    }
+
+    /* 1 for +Infinity, -1 for -Infinity, otherwise 0
+     */
+    fn infinity_sign(&self) -> i32 {
+        match (self.kind, self.negative) {
+            (Kind::Infinite, false) => return 1,
+            (Kind::Infinite, true) => return -1,
+            _ => return 0,
+        }
+    }
+
+    /* the NaN result of an operation on NaNs, if there are any
+     */
+    fn check_nans(&self, other: Option<&Decimal>, context: &mut Context) -> Option<Decimal> {
+        let other_kind = other.map_or(0, Decimal::nan_kind);
+        match (self.nan_kind(), other_kind) {
+            (0, 0) => return None,
+            (2, _) => return Some(context.invalid_nan(self)),
+            (_, 2) => return Some(context.invalid_nan(other.unwrap())),
+            (0, _) => return Some(other.unwrap().fix_nan(context)),
+            _ => return Some(self.fix_nan(context)),
+        }
+    }
+
+    /* cut a NaN's diagnostic down to the precision
+     */
+    fn fix_nan(&self, context: &Context) -> Decimal {
+        let max_len = (context.prec - context.clamp as i64) as usize;
+        if self.digits.len() > max_len {
+            let payload = self.digits[self.digits.len() - max_len..].trim_start_matches('0');
+            return Decimal::special(self.negative, self.kind, payload);
+        }
+        return self.clone();
+    }
+
+    /* Round to the context's precision and fit the exponent in its limits
+     */
+    pub fn fix(&self, context: &mut Context) -> Decimal {
+        match self.kind {
+            Kind::NaN | Kind::SNaN => return self.fix_nan(context),
+            Kind::Infinite => return self.clone(),
+            Kind::Finite => (),
+        };
+        let (etiny, etop) = (context.etiny(), context.etop());
+        if self.is_zero() {
+            let exp_max = if context.clamp { etop } else { context.emax };
+            let new_exp = self.exp.max(etiny).min(exp_max);
+            if new_exp != self.exp {
+                context.raise(CLAMPED);
+                return Decimal::zero(self.negative, new_exp);
+            }
+            return self.clone();
+        }
+        let len = self.digits.len() as i64;
+        // the smallest exponent the result can have
+        let mut exp_min = len + self.exp - context.prec;
+        if exp_min > etop {
+            let result = context.overflow(self.negative);
+            context.raise(INEXACT | ROUNDED);
+            return result;
+        }
+        let subnormal = exp_min < etiny;
+        if subnormal {
+            exp_min = etiny;
+        }
+        if self.exp < exp_min {
+            let mut keep = len + self.exp - exp_min;
+            let tiny;
+            let mut this = self;
+            if keep < 0 {
+                tiny = Decimal::finite(self.negative, String::from("1"), exp_min - 1);
+                this = &tiny;
+                keep = 0;
+            }
+            let changed = this.rounding_direction(keep as usize, context.rounding);
+            let mut coeff = match &this.digits[..keep as usize] {
+                "" => String::from("0"),
+                kept => String::from(kept),
+            };
+            if changed > 0 {
+                coeff = increment(&coeff);
+                if coeff.len() as i64 > context.prec {
+                    coeff.pop();
+                    exp_min += 1;
+                }
+            }
+            let result = if exp_min > etop {
+                context.overflow(self.negative)
+            } else {
+                Decimal::finite(self.negative, coeff, exp_min)
+            };
+            if changed != 0 && subnormal {
+                context.raise(UNDERFLOW);
+            }
+            if subnormal {
+                context.raise(SUBNORMAL);
+            }
+            if changed != 0 {
+                context.raise(INEXACT);
+            }
+            context.raise(ROUNDED);
+            if result.is_zero() {
+                context.raise(CLAMPED);
+            }
+            return result;
+        }
+        if subnormal {
+            context.raise(SUBNORMAL);
+        }
+        if context.clamp && self.exp > etop {
+            context.raise(CLAMPED);
+            let padded = format!("{}{}", self.digits, "0".repeat((self.exp - etop) as usize));
+            return Decimal::finite(self.negative, padded, etop);
+        }
+        return self.clone();
+    }
+
+    /* Which way rounding the coefficient to its first `keep` digits goes: 1
+     * away from zero, 0 if the digits dropped are all zeros, -1 towards zero
+     */
+    fn rounding_direction(&self, keep: usize, rounding: Rounding) -> i32 {
+        let digits = self.digits.as_bytes();
+        let down = if all_zeros(&digits[keep..]) { 0 } else { -1 };
+        let exact_half = digits[keep] == b'5' && all_zeros(&digits[keep + 1..]);
+        let half_up = if digits[keep] >= b'5' { 1 } else { down };
+        match rounding {
+            Rounding::Down => return down,
+            Rounding::Up => return -down,
+            Rounding::HalfUp => return half_up,
+            Rounding::HalfDown if exact_half => return -1,
+            Rounding::HalfDown => return half_up,
+            Rounding::HalfEven if exact_half && (keep == 0 || digits[keep - 1].is_multiple_of(2)) => return -1,
+            Rounding::HalfEven => return half_up,
+            Rounding::Ceiling if self.negative => return down,
+            Rounding::Ceiling => return -down,
+            Rounding::Floor if self.negative => return -down,
+            Rounding::Floor => return down,
+            Rounding::Up05 if keep > 0 && digits[keep - 1] != b'0' && digits[keep - 1] != b'5' => return down,
+            Rounding::Up05 => return -down,
+        }
+    }
+
+    /* The same value with exponent exp, padding the coefficient or
+     * rounding it. Quiet: it raises nothing.
+     */
+    fn rescale(&self, exp: i64, rounding: Rounding) -> Decimal {
+        if self.is_special() {
+            return self.clone();
+        }
+        if self.is_zero() {
+            return Decimal::zero(self.negative, exp);
+        }
+        if self.exp >= exp {
+            let padded = format!("{}{}", self.digits, "0".repeat((self.exp - exp) as usize));
+            return Decimal::finite(self.negative, padded, exp);
+        }
+        let mut keep = self.digits.len() as i64 + self.exp - exp;
+        let tiny;
+        let mut this = self;
+        if keep < 0 {
+            tiny = Decimal::finite(self.negative, String::from("1"), exp - 1);
+            this = &tiny;
+            keep = 0;
+        }
+        let changed = this.rounding_direction(keep as usize, rounding);
+        let mut coeff = match &this.digits[..keep as usize] {
+            "" => String::from("0"),
+            kept => String::from(kept),
+        };
+        if changed == 1 {
+            coeff = increment(&coeff);
+        }
+        return Decimal::finite(self.negative, coeff, exp);
+    }
+
+    /* Compare two numbers that aren't NaNs
+     */
+    pub fn cmp(&self, other: &Decimal) -> Ordering {
+        if self.is_special() || other.is_special() {
+            return self.infinity_sign().cmp(&other.infinity_sign());
+        }
+        let signed = |ordering: Ordering, negative: bool| if negative { ordering.reverse() } else { ordering };
+        if self.is_zero() {
+            if other.is_zero() {
+                return Ordering::Equal;
+            }
+            return signed(Ordering::Less, other.negative);
+        }
+        if other.is_zero() {
+            return signed(Ordering::Greater, self.negative);
+        }
+        if self.negative != other.negative {
+            return if self.negative { Ordering::Less } else { Ordering::Greater };
+        }
+        let (self_adjusted, other_adjusted) = (self.adjusted(), other.adjusted());
+        if self_adjusted != other_adjusted {
+            return signed(self_adjusted.cmp(&other_adjusted), self.negative);
+        }
+        // same number of digits before the point: compare the digits
+        let (a, b) = (self.digits.as_bytes(), other.digits.as_bytes());
+        let len = a.len().max(b.len());
+        for i in 0..len {
+            let (x, y) = (a.get(i).unwrap_or(&b'0'), b.get(i).unwrap_or(&b'0'));
+            if x != y {
+                return signed(x.cmp(y), self.negative);
+            }
+        }
+        return Ordering::Equal;
+    }
+
+    /* the total ordering of compare_total(), which tells 1.0 and 1.00 apart
+     * and orders NaNs
+     */
+    pub fn compare_total(&self, other: &Decimal) -> Ordering {
+        if self.negative != other.negative {
+            return if self.negative { Ordering::Less } else { Ordering::Greater };
+        }
+        let signed = |ordering: Ordering| if self.negative { ordering.reverse() } else { ordering };
+        let (self_nan, other_nan) = (self.nan_kind(), other.nan_kind());
+        if self_nan != 0 || other_nan != 0 {
+            if self_nan == other_nan {
+                let self_key = (self.digits.len(), &self.digits);
+                let other_key = (other.digits.len(), &other.digits);
+                return signed(self_key.cmp(&other_key));
+            }
+            // quiet NaNs above signaling NaNs above numbers
+            let rank = |kind: u8| match kind {
+                1 => 2,
+                2 => 1,
+                _ => 0,
+            };
+            return signed(rank(self_nan).cmp(&rank(other_nan)));
+        }
+        match self.cmp(other) {
+            Ordering::Equal => return signed(other.exp.cmp(&self.exp).reverse()),
+            ordering => return ordering,
+        }
+    }
+
+    /* compare(): -1, 0 or 1 as a Decimal, or NaN
+     */
+    pub fn compare(&self, other: &Decimal, context: &mut Context) -> Decimal {
+        if let Some(result) = self.check_nans(Some(other), context) {
+            return result;
+        }
+        match self.cmp(other) {
+            Ordering::Less => return Decimal::from_i64(-1),
+            Ordering::Equal => return Decimal::from_i64(0),
+            Ordering::Greater => return Decimal::from_i64(1),
+        }
+    }
+
+    /* max() or min(), which prefer a number to a quiet NaN
+     */
+    pub fn max_min(&self, other: &Decimal, max: bool, context: &mut Context) -> Decimal {
+        let (self_nan, other_nan) = (self.nan_kind(), other.nan_kind());
+        if self_nan != 0 || other_nan != 0 {
+            if other_nan == 1 && self_nan == 0 {
+                return self.fix(context);
+            }
+            if self_nan == 1 && other_nan == 0 {
+                return other.fix(context);
+            }
+            return self.check_nans(Some(other), context).unwrap();
+        }
+        let ordering = match self.cmp(other) {
+            Ordering::Equal => self.compare_total(other),
+            ordering => ordering,
+        };
+        let take_other = (ordering == Ordering::Less) == max;
+        return if take_other { other.fix(context) } else { self.fix(context) };
+    }
+
+    pub fn neg(&self, context: &mut Context) -> Decimal {
+        if let Some(result) = self.check_nans(None, context) {
+            return result;
+        }
+        // -0 is 0, except when rounding towards -Infinity
+        if self.is_zero() && context.rounding != Rounding::Floor {
+            return self.copy_abs().fix(context);
+        }
+        return self.copy_negate().fix(context);
+    }
+
+    pub fn pos(&self, context: &mut Context) -> Decimal {
+        if let Some(result) = self.check_nans(None, context) {
+            return result;
+        }
+        if self.is_zero() && context.rounding != Rounding::Floor {
+            return self.copy_abs().fix(context);
+        }
+        return self.fix(context);
+    }
+
+    pub fn abs(&self, context: &mut Context) -> Decimal {
+        if let Some(result) = self.check_nans(None, context) {
+            return result;
+        }
+        if self.negative {
+            return self.neg(context);
+        }
+        return self.pos(context);
+    }
+
+    pub fn add(&self, other: &Decimal, context: &mut Context) -> Decimal {
+        if self.is_special() || other.is_special() {
+            if let Some(result) = self.check_nans(Some(other), context) {
+                return result;
+            }
+            if self.kind == Kind::Infinite {
+                if self.negative != other.negative && other.kind == Kind::Infinite {
+                    return context.invalid(INVALID_OPERATION);
+                }
+                return self.clone();
+            }
+            if other.kind == Kind::Infinite {
+                return other.clone();
+            }
+        }
+        let mut exp = self.exp.min(other.exp);
+        // an exact zero sum is negative when rounding towards -Infinity
+        let negativezero = context.rounding == Rounding::Floor && self.negative != other.negative;
+        if self.is_zero() && other.is_zero() {
+            let negative = (self.negative && other.negative) || negativezero;
+            return Decimal::zero(negative, exp).fix(context);
+        }
+        if self.is_zero() {
+            exp = exp.max(other.exp - context.prec - 1);
+            return other.rescale(exp, context.rounding).fix(context);
+        }
+        if other.is_zero() {
+            exp = exp.max(self.exp - context.prec - 1);
+            return self.rescale(exp, context.rounding).fix(context);
+        }
+        let ((c1, e1), (c2, _)) = align(self, other, context.prec);
+        let (c1, c2) = (c1.parse::<BigUint>().unwrap(), c2.parse::<BigUint>().unwrap());
+        if self.negative == other.negative {
+            return Decimal::finite(self.negative, (c1 + c2).to_string(), e1).fix(context);
+        }
+        match c1.cmp(&c2) {
+            Ordering::Equal => return Decimal::zero(negativezero, exp).fix(context),
+            // the sign of the larger
+            Ordering::Greater => return Decimal::finite(self.negative, (c1 - c2).to_string(), e1).fix(context),
+            Ordering::Less => return Decimal::finite(other.negative, (c2 - c1).to_string(), e1).fix(context),
+        }
+    }
+
+    pub fn sub(&self, other: &Decimal, context: &mut Context) -> Decimal {
+        if self.is_special() || other.is_special() {
+            if let Some(result) = self.check_nans(Some(other), context) {
+                return result;
+            }
+        }
+        return self.add(&other.copy_negate(), context);
+    }
+
+    pub fn mul(&self, other: &Decimal, context: &mut Context) -> Decimal {
+        let negative = self.negative != other.negative;
+        if self.is_special() || other.is_special() {
+            if let Some(result) = self.check_nans(Some(other), context) {
+                return result;
+            }
+            if self.kind == Kind::Infinite && other.is_zero() || other.kind == Kind::Infinite && self.is_zero() {
+                return context.invalid(INVALID_OPERATION);
+            }
+            return Decimal::infinity(negative);
+        }
+        let exp = self.exp + other.exp;
+        if self.is_zero() || other.is_zero() {
+            return Decimal::zero(negative, exp).fix(context);
+        }
+        if self.digits == "1" {
+            return Decimal::finite(negative, other.digits.clone(), exp).fix(context);
+        }
+        if other.digits == "1" {
+            return Decimal::finite(negative, self.digits.clone(), exp).fix(context);
+        }
+        let product = self.coeff() * other.coeff();
+        return Decimal::finite(negative, digits_of(&product), exp).fix(context);
+    }
+
+    pub fn div(&self, other: &Decimal, context: &mut Context) -> Decimal {
+        let negative = self.negative != other.negative;
+        if self.is_special() || other.is_special() {
+            if let Some(result) = self.check_nans(Some(other), context) {
+                return result;
+            }
+            if self.kind == Kind::Infinite && other.kind == Kind::Infinite {
+                return context.invalid(INVALID_OPERATION);
+            }
+            if self.kind == Kind::Infinite {
+                return Decimal::infinity(negative);
+            }
+            context.raise(CLAMPED);
+            return Decimal::zero(negative, context.etiny());
+        }
+        if other.is_zero() {
+            if self.is_zero() {
+                return context.invalid(DIVISION_UNDEFINED);
+            }
+            return context.division_by_zero(negative);
+        }
+        let (coeff, exp) = if self.is_zero() {
+            (BigInt::zero(), self.exp - other.exp)
+        } else {
+            let shift = other.digits.len() as i64 - self.digits.len() as i64 + context.prec + 1;
+            let mut exp = self.exp - other.exp - shift;
+            let (mut coeff, remainder) = if shift >= 0 {
+                (self.coeff() * ten_pow(shift)).div_rem(&other.coeff())
+            } else {
+                self.coeff().div_rem(&(other.coeff() * ten_pow(-shift)))
+            };
+            let ten = BigInt::from(10);
+            if !remainder.is_zero() {
+                // inexact: make sure the last digit rounds the right way
+                if (&coeff % 5u32).is_zero() {
+                    coeff += 1;
+                }
+            } else {
+                // exact: get as close as possible to the ideal exponent
+                let ideal_exp = self.exp - other.exp;
+                while exp < ideal_exp && (&coeff % &ten).is_zero() {
+                    coeff /= &ten;
+                    exp += 1;
+                }
+            }
+            (coeff, exp)
+        };
+        return Decimal::finite(negative, digits_of(&coeff), exp).fix(context);
+    }
+
+    /* (self // other, self % other) for a finite self and nonzero other
+     */
+    fn divide(&self, other: &Decimal, context: &mut Context) -> (Decimal, Decimal) {
+        let negative = self.negative != other.negative;
+        let ideal_exp = if other.kind == Kind::Infinite { self.exp } else { self.exp.min(other.exp) };
+        let expdiff = self.adjusted() - other.adjusted();
+        if self.is_zero() || other.kind == Kind::Infinite || expdiff <= -2 {
+            return (Decimal::zero(negative, 0), self.rescale(ideal_exp, context.rounding));
+        }
+        if expdiff <= context.prec {
+            let (mut c1, mut c2) = (self.coeff(), other.coeff());
+            if self.exp >= other.exp {
+                c1 *= ten_pow(self.exp - other.exp);
+            } else {
+                c2 *= ten_pow(other.exp - self.exp);
+            }
+            let (q, r) = c1.div_rem(&c2);
+            if digit_len(&q) <= context.prec {
+                return (
+                    Decimal::finite(negative, digits_of(&q), 0),
+                    Decimal::finite(self.negative, digits_of(&r), ideal_exp),
+                );
+            }
+        }
+        let result = context.invalid(DIVISION_IMPOSSIBLE);
+        return (result.clone(), result);
+    }
+
+    pub fn divmod(&self, other: &Decimal, context: &mut Context) -> (Decimal, Decimal) {
+        if let Some(result) = self.check_nans(Some(other), context) {
+            return (result.clone(), result);
+        }
+        let negative = self.negative != other.negative;
+        if self.kind == Kind::Infinite {
+            if other.kind == Kind::Infinite {
+                let result = context.invalid(INVALID_OPERATION);
+                return (result.clone(), result);
+            }
+            return (Decimal::infinity(negative), context.invalid(INVALID_OPERATION));
+        }
+        if other.is_zero() {
+            if self.is_zero() {
+                let result = context.invalid(DIVISION_UNDEFINED);
+                return (result.clone(), result);
+            }
+            return (context.division_by_zero(negative), context.invalid(INVALID_OPERATION));
+        }
+        let (quotient, remainder) = self.divide(other, context);
+        return (quotient, remainder.fix(context));
+    }
+
+    pub fn floordiv(&self, other: &Decimal, context: &mut Context) -> Decimal {
+        if let Some(result) = self.check_nans(Some(other), context) {
+            return result;
+        }
+        let negative = self.negative != other.negative;
+        if self.kind == Kind::Infinite {
+            if other.kind == Kind::Infinite {
+                return context.invalid(INVALID_OPERATION);
+            }
+            return Decimal::infinity(negative);
+        }
+        if other.is_zero() {
+            if self.is_zero() {
+                return context.invalid(DIVISION_UNDEFINED);
+            }
+            return context.division_by_zero(negative);
+        }
+        return self.divide(other, context).0;
+    }
+
+    pub fn modulo(&self, other: &Decimal, context: &mut Context) -> Decimal {
+        if let Some(result) = self.check_nans(Some(other), context) {
+            return result;
+        }
+        if self.kind == Kind::Infinite {
+            return context.invalid(INVALID_OPERATION);
+        }
+        if other.is_zero() {
+            if self.is_zero() {
+                return context.invalid(DIVISION_UNDEFINED);
+            }
+            return context.invalid(INVALID_OPERATION);
+        }
+        return self.divide(other, context).1.fix(context);
+    }
+
+    /* the remainder nearest zero, which may be negative
+     */
+    pub fn remainder_near(&self, other: &Decimal, context: &mut Context) -> Decimal {
+        if let Some(result) = self.check_nans(Some(other), context) {
+            return result;
+        }
+        if self.kind == Kind::Infinite {
+            return context.invalid(INVALID_OPERATION);
+        }
+        if other.is_zero() {
+            return context.invalid(if self.is_zero() { DIVISION_UNDEFINED } else { INVALID_OPERATION });
+        }
+        if other.kind == Kind::Infinite {
+            return self.fix(context);
+        }
+        let ideal_exp = self.exp.min(other.exp);
+        if self.is_zero() {
+            return Decimal::zero(self.negative, ideal_exp).fix(context);
+        }
+        let expdiff = self.adjusted() - other.adjusted();
+        if expdiff > context.prec {
+            return context.invalid(DIVISION_IMPOSSIBLE);
+        }
+        if expdiff <= -2 {
+            return self.rescale(ideal_exp, context.rounding).fix(context);
+        }
+        let (mut c1, mut c2) = (self.coeff(), other.coeff());
+        if self.exp >= other.exp {
+            c1 *= ten_pow(self.exp - other.exp);
+        } else {
+            c2 *= ten_pow(other.exp - self.exp);
+        }
+        let (mut q, mut r) = c1.div_rem(&c2);
+        if BigInt::from(2) * &r + BigInt::from(q.is_odd() as u8) > c2 {
+            r -= &c2;
+            q += 1;
+        }
+        if digit_len(&q) > context.prec {
+            return context.invalid(DIVISION_IMPOSSIBLE);
+        }
+        let negative = self.negative != r.is_negative();
+        return Decimal::finite(negative, digits_of(&r), ideal_exp).fix(context);
+    }
+
+    pub fn pow(&self, other: &Decimal, context: &mut Context) -> Decimal {
+        if let Some(result) = self.check_nans(Some(other), context) {
+            return result;
+        }
+        if other.is_zero() {
+            if self.is_zero() {
+                return context.invalid(INVALID_OPERATION);
+            }
+            return Decimal::one();
+        }
+        // the result's sign, from here on working with abs(self)
+        let mut negative = false;
+        let base = self.copy_abs();
+        if self.negative {
+            if other.is_integer() {
+                negative = !other.is_even();
+            } else if self.is_truthy() {
+                return context.invalid(INVALID_OPERATION);
+            }
+        }
+        if base.is_zero() {
+            return if other.negative { Decimal::infinity(negative) } else { Decimal::zero(negative, 0) };
+        }
+        if base.kind == Kind::Infinite {
+            return if other.negative { Decimal::zero(negative, 0) } else { Decimal::infinity(negative) };
+        }
+        let prec = context.prec;
+        if base.cmp(&Decimal::one()) == Ordering::Equal {
+            // 1 ** y: as many zeros as an exact result would have
+            let exp = if other.is_integer() {
+                let multiplier = if other.negative {
+                    0
+                } else if other.cmp(&Decimal::from_i64(prec)) == Ordering::Greater {
+                    prec
+                } else {
+                    other.trunc().to_i64().unwrap()
+                };
+                let mut exp = base.exp.saturating_mul(multiplier);
+                if exp < 1 - prec {
+                    exp = 1 - prec;
+                    context.raise(ROUNDED);
+                }
+                exp
+            } else {
+                context.raise(INEXACT | ROUNDED);
+                1 - prec
+            };
+            return Decimal::finite(negative, format!("1{}", "0".repeat(-exp as usize)), exp);
+        }
+        let self_adjusted = base.adjusted();
+        if other.kind == Kind::Infinite {
+            if other.negative != (self_adjusted < 0) {
+                return Decimal::zero(negative, 0);
+            }
+            return Decimal::infinity(negative);
+        }
+        // a result certain to overflow or underflow
+        let mut result = None;
+        let bound = base.log10_exp_bound() + other.adjusted();
+        if (self_adjusted >= 0) != other.negative {
+            if bound >= context.emax.to_string().len() as i64 {
+                result = Some(Decimal::finite(negative, String::from("1"), context.emax + 1));
+            }
+        } else if bound >= (-context.etiny()).to_string().len() as i64 {
+            result = Some(Decimal::finite(negative, String::from("1"), context.etiny() - 1));
+        }
+        let mut exact = false;
+        if result.is_none() {
+            if let Some(mut found) = base.power_exact(other, prec + 1) {
+                found.negative = negative;
+                result = Some(found);
+                exact = true;
+            }
+        }
+        let mut result = match result {
+            Some(result) => result,
+            None => {
+                let (xc, xe) = (base.coeff(), base.exp);
+                let yc = if other.negative { -other.coeff() } else { other.coeff() };
+                let mut extra = 3;
+                let (coeff, exp) = loop {
+                    let (coeff, exp) = dpower(&xc, xe, &yc, other.exp, prec + extra);
+                    if rounds_correctly(&coeff, prec) {
+                        break (coeff, exp);
+                    }
+                    extra += 3;
+                };
+                Decimal::finite(negative, digits_of(&coeff), exp)
+            }
+        };
+        if exact && !other.is_integer() {
+            // an exact result for a fractional power is still Inexact, and
+            // is padded to make sure it's Rounded too
+            if result.digits.len() as i64 <= prec {
+                let expdiff = prec + 1 - result.digits.len() as i64;
+                result.digits.push_str(&"0".repeat(expdiff as usize));
+                result.exp -= expdiff;
+            }
+            let mut scratch = context.clone();
+            scratch.status = 0;
+            result = result.fix(&mut scratch);
+            let mut status = INEXACT | scratch.status & (UNDERFLOW | SUBNORMAL | INEXACT | ROUNDED | CLAMPED | OVERFLOW);
+            if scratch.status & SUBNORMAL != 0 {
+                status |= UNDERFLOW;
+            }
+            context.raise(status);
+            return result;
+        }
+        return result.fix(context);
+    }
+
+    /* self**other exactly, if it can be written with p digits; self and
+     * other are finite, self positive and not 1, and other nonzero
+     */
+    fn power_exact(&self, other: &Decimal, p: i64) -> Option<Decimal> {
+        let ten = BigInt::from(10);
+        let (mut xc, mut xe) = (self.coeff(), BigInt::from(self.exp));
+        while (&xc % &ten).is_zero() {
+            xc /= &ten;
+            xe += 1;
+        }
+        let (mut yc, mut ye) = (other.coeff(), BigInt::from(other.exp));
+        while (&yc % &ten).is_zero() {
+            yc /= &ten;
+            ye += 1;
+        }
+        let ideal_exp = |exp: i64| {
+            if other.is_integer() && !other.negative {
+                return Some(self.exp.saturating_mul(other.trunc().to_i64().unwrap_or(i64::MAX)));
+            }
+            let _ = exp;
+            return None;
+        };
+
+        // x is a power of 10
+        if xc.is_one() {
+            xe *= &yc;
+            while !xe.is_zero() && (&xe % &ten).is_zero() {
+                xe /= &ten;
+                ye += 1;
+            }
+            if ye.is_negative() {
+                return None;
+            }
+            let mut exponent = (xe * ten_pow(ye.to_i64()?)).to_i64()?;
+            if other.negative {
+                exponent = -exponent;
+            }
+            let zeros = match ideal_exp(exponent) {
+                Some(ideal) => (exponent.saturating_sub(ideal)).min(p - 1).max(0),
+                None => 0,
+            };
+            return Some(Decimal::finite(false, format!("1{}", "0".repeat(zeros as usize)), exponent - zeros));
+        }
+
+        // a negative power is only exact for x = 2**e or 5**e
+        if other.negative {
+            let last_digit = (&xc % &ten).to_u8().unwrap();
+            let e;
+            if last_digit % 2 == 0 {
+                // x must be a power of 2
+                if xc.trailing_zeros() != Some(xc.bits() - 1) {
+                    return None;
+                }
+                let emax = p * 93 / 65;
+                if ye >= BigInt::from(emax.to_string().len()) {
+                    return None;
+                }
+                let power = decimal_lshift_exact(&(BigInt::from(bit_len(&xc) - 1) * &yc), &ye)?;
+                xe = decimal_lshift_exact(&(&xe * &yc), &ye)?;
+                if power > BigInt::from(emax) {
+                    return None;
+                }
+                e = power.to_i64()?;
+                xc = BigInt::from(5).pow(e as u64);
+            } else if last_digit == 5 {
+                let mut power = bit_len(&xc) * 28 / 65;
+                let (quotient, remainder) = BigInt::from(5).pow(power as u64).div_rem(&xc);
+                if !remainder.is_zero() {
+                    return None;
+                }
+                xc = quotient;
+                while (&xc % 5u32).is_zero() {
+                    xc /= 5u32;
+                    power -= 1;
+                }
+                let emax = p * 10 / 3;
+                if ye >= BigInt::from(emax.to_string().len()) {
+                    return None;
+                }
+                let power = decimal_lshift_exact(&(BigInt::from(power) * &yc), &ye)?;
+                xe = decimal_lshift_exact(&(&xe * &yc), &ye)?;
+                if power > BigInt::from(emax) {
+                    return None;
+                }
+                e = power.to_i64()?;
+                xc = BigInt::one() << e as usize;
+            } else {
+                return None;
+            }
+            if xc >= ten_pow(p) {
+                return None;
+            }
+            let xe = (-e - xe).to_i64()?;
+            return Some(Decimal::finite(false, digits_of(&xc), xe));
+        }
+
+        // y = m/n in lowest terms, and x**y = (x**(1/n))**m
+        let (mut m, mut n) = (yc.clone(), BigInt::one());
+        let mut xc_bits = 0;
+        if !ye.is_negative() {
+            m = &yc * ten_pow(ye.to_i64()?);
+        } else {
+            let places = (-&ye).to_i64()?;
+            if !xe.is_zero() && str_len(&(&yc * &xe).abs()) <= places {
+                return None;
+            }
+            xc_bits = bit_len(&xc);
+            if str_len(&(&yc * BigInt::from(xc_bits))) <= places {
+                return None;
+            }
+            n = ten_pow(places);
+            let two = BigInt::from(2);
+            let five = BigInt::from(5);
+            while m.is_even() && n.is_even() {
+                m /= &two;
+                n /= &two;
+            }
+            while (&m % &five).is_zero() && (&n % &five).is_zero() {
+                m /= &five;
+                n /= &five;
+            }
+        }
+        if n > BigInt::one() {
+            // x must be a perfect nth power
+            if BigInt::from(xc_bits) <= n {
+                return None;
+            }
+            let (quotient, remainder) = xe.div_mod_floor(&n);
+            if !remainder.is_zero() {
+                return None;
+            }
+            xe = quotient;
+            let n = n.to_u64()?;
+            let mut a = BigInt::one() << (bit_len(&xc) as u64).div_ceil(n) as usize;
+            let (q, r) = loop {
+                let (q, r) = xc.div_rem(&a.clone().pow(n - 1));
+                if a <= q {
+                    break (q, r);
+                }
+                a = (&a * BigInt::from(n - 1) + q) / BigInt::from(n);
+            };
+            if !(a == q && r.is_zero()) {
+                return None;
+            }
+            xc = a;
+        }
+        if xc > BigInt::one() && m > BigInt::from(p * 100 / log10_lb(&xc)) {
+            return None;
+        }
+        let m = m.to_u64()?;
+        xc = xc.pow(m);
+        xe *= m;
+        if xc > ten_pow(p) {
+            return None;
+        }
+        let xe = xe.to_i64()?;
+        let digits = digits_of(&xc);
+        let zeros = match ideal_exp(xe) {
+            Some(ideal) => (xe.saturating_sub(ideal)).min(p - digits.len() as i64).max(0),
+            None => 0,
+        };
+        return Some(Decimal::finite(false, format!("{}{}", digits, "0".repeat(zeros as usize)), xe - zeros));
+    }
+
+    /* a lower bound r for the adjusted exponent of ln(self), so that
+     * ln(self) >= 10**r, for a finite positive self that isn't 1
+     */
+    fn ln_exp_bound(&self) -> i64 {
+        let adj = self.adjusted();
+        if adj >= 1 {
+            return (adj * 23 / 10).to_string().len() as i64 - 1;
+        }
+        if adj <= -2 {
+            return ((-1 - adj) * 23 / 10).to_string().len() as i64 - 1;
+        }
+        let (c, e) = (self.coeff(), self.exp);
+        if adj == 0 {
+            let num = (&c - ten_pow(-e)).to_string();
+            let den = c.to_string();
+            return num.len() as i64 - den.len() as i64 - (num < den) as i64;
+        }
+        return e + (ten_pow(-e) - c).to_string().len() as i64 - 1;
+    }
+
+    /* the same for log10(self)
+     */
+    fn log10_exp_bound(&self) -> i64 {
+        let adj = self.adjusted();
+        if adj >= 1 {
+            return adj.to_string().len() as i64 - 1;
+        }
+        if adj <= -2 {
+            return (-1 - adj).to_string().len() as i64 - 1;
+        }
+        let (c, e) = (self.coeff(), self.exp);
+        if adj == 0 {
+            let num = (&c - ten_pow(-e)).to_string();
+            let den = (BigInt::from(231) * c).to_string();
+            return num.len() as i64 - den.len() as i64 - (num < den) as i64 + 2;
+        }
+        let num = (ten_pow(-e) - c).to_string();
+        return num.len() as i64 + e - (num.as_str() < "231") as i64 - 1;
+    }
+
+    pub fn exp(&self, context: &mut Context) -> Decimal {
+        if let Some(result) = self.check_nans(None, context) {
+            return result;
+        }
+        match self.infinity_sign() {
+            -1 => return Decimal::zero(false, 0),
+            1 => return self.clone(),
+            _ => (),
+        };
+        if self.is_zero() {
+            return Decimal::one();
+        }
+        let p = context.prec;
+        let adj = self.adjusted();
+        let result = if !self.negative && adj > ((context.emax + 1) * 3).to_string().len() as i64 {
+            // overflows
+            Decimal::finite(false, String::from("1"), context.emax + 1)
+        } else if self.negative && adj > ((-context.etiny() + 1) * 3).to_string().len() as i64 {
+            // underflows to 0
+            Decimal::finite(false, String::from("1"), context.etiny() - 1)
+        } else if !self.negative && adj < -p {
+            // p+1 digits; final round will raise correct flags
+            Decimal::finite(false, format!("1{}1", "0".repeat((p - 1) as usize)), -p)
+        } else if self.negative && adj < -p - 1 {
+            Decimal::finite(false, "9".repeat((p + 1) as usize), -p - 1)
+        } else {
+            let c = if self.negative { -self.coeff() } else { self.coeff() };
+            let mut extra = 3;
+            let (coeff, exp) = loop {
+                let (coeff, exp) = dexp(&c, self.exp, p + extra);
+                if rounds_correctly(&coeff, p) {
+                    break (coeff, exp);
+                }
+                extra += 3;
+            };
+            Decimal::finite(false, digits_of(&coeff), exp)
+        };
+        return fix_half_even(&result, context);
+    }
+
+    pub fn ln(&self, context: &mut Context) -> Decimal {
+        if let Some(result) = self.check_nans(None, context) {
+            return result;
+        }
+        if self.is_zero() {
+            return Decimal::infinity(true);
+        }
+        if self.infinity_sign() == 1 {
+            return self.clone();
+        }
+        if self.cmp(&Decimal::one()) == Ordering::Equal {
+            return Decimal::zero(false, 0);
+        }
+        if self.negative {
+            return context.invalid(INVALID_OPERATION);
+        }
+        let (c, e) = (self.coeff(), self.exp);
+        let p = context.prec;
+        let mut places = p - self.ln_exp_bound() + 2;
+        let coeff = loop {
+            let coeff = dlog(&c, e, places);
+            if rounds_correctly(&coeff, p) {
+                break coeff;
+            }
+            places += 3;
+        };
+        let result = Decimal::finite(coeff.is_negative(), digits_of(&coeff), -places);
+        return fix_half_even(&result, context);
+    }
+
+    pub fn log10(&self, context: &mut Context) -> Decimal {
+        if let Some(result) = self.check_nans(None, context) {
+            return result;
+        }
+        if self.is_zero() {
+            return Decimal::infinity(true);
+        }
+        if self.infinity_sign() == 1 {
+            return self.clone();
+        }
+        if self.negative {
+            return context.invalid(INVALID_OPERATION);
+        }
+        let result = if self.digits.starts_with('1') && all_zeros(&self.digits.as_bytes()[1..]) {
+            // a power of 10
+            Decimal::from_i64(self.adjusted())
+        } else {
+            let (c, e) = (self.coeff(), self.exp);
+            let p = context.prec;
+            let mut places = p - self.log10_exp_bound() + 2;
+            let coeff = loop {
+                let coeff = dlog10(&c, e, places);
+                if rounds_correctly(&coeff, p) {
+                    break coeff;
+                }
+                places += 3;
+            };
+            Decimal::finite(coeff.is_negative(), digits_of(&coeff), -places)
+        };
+        return fix_half_even(&result, context);
+    }
+
+    pub fn sqrt(&self, context: &mut Context) -> Decimal {
+        if self.is_special() {
+            if let Some(result) = self.check_nans(None, context) {
+                return result;
+            }
+            if self.infinity_sign() == 1 {
+                return self.clone();
+            }
+        }
+        if self.is_zero() {
+            return Decimal::zero(self.negative, self.exp >> 1).fix(context);
+        }
+        if self.negative {
+            return context.invalid(INVALID_OPERATION);
+        }
+        // the root to prec+1 digits, with the last nonzero if inexact
+        let prec = context.prec + 1;
+        let mut e = self.exp >> 1;
+        let len = self.digits.len() as i64;
+        let (mut c, l) = if self.exp & 1 == 1 {
+            (self.coeff() * 10, (len >> 1) + 1)
+        } else {
+            (self.coeff(), (len + 1) >> 1)
+        };
+        let shift = prec - l;
+        let mut exact = if shift >= 0 {
+            c *= BigInt::from(100).pow(shift as u64);
+            true
+        } else {
+            let (quotient, remainder) = c.div_rem(&BigInt::from(100).pow(-shift as u64));
+            c = quotient;
+            remainder.is_zero()
+        };
+        e -= shift;
+        let mut n = ten_pow(prec);
+        loop {
+            let q = &c / &n;
+            if n <= q {
+                break;
+            }
+            n = (n + q) >> 1usize;
+        }
+        exact = exact && &n * &n == c;
+        if exact {
+            if shift >= 0 {
+                n /= ten_pow(shift);
+            } else {
+                n *= ten_pow(-shift);
+            }
+            e += shift;
+        } else if (&n % 5u32).is_zero() {
+            n += 1;
+        }
+        return fix_half_even(&Decimal::finite(false, digits_of(&n), e), context);
+    }
+
+    /* reduce(): trailing zeros stripped
+     */
+    pub fn normalize(&self, context: &mut Context) -> Decimal {
+        if let Some(result) = self.check_nans(None, context) {
+            return result;
+        }
+        let dup = self.fix(context);
+        if dup.kind == Kind::Infinite {
+            return dup;
+        }
+        if dup.is_zero() {
+            return Decimal::zero(dup.negative, 0);
+        }
+        let exp_max = if context.clamp { context.etop() } else { context.emax };
+        let mut end = dup.digits.len();
+        let mut exp = dup.exp;
+        while dup.digits.as_bytes()[end - 1] == b'0' && exp < exp_max {
+            exp += 1;
+            end -= 1;
+        }
+        return Decimal::finite(dup.negative, String::from(&dup.digits[..end]), exp);
+    }
+
+    /* self with the exponent of exp
+     */
+    pub fn quantize(&self, exp: &Decimal, rounding: Rounding, context: &mut Context) -> Decimal {
+        if self.is_special() || exp.is_special() {
+            if let Some(result) = self.check_nans(Some(exp), context) {
+                return result;
+            }
+            if exp.kind == Kind::Infinite || self.kind == Kind::Infinite {
+                if exp.kind == Kind::Infinite && self.kind == Kind::Infinite {
+                    return self.clone();
+                }
+                return context.invalid(INVALID_OPERATION);
+            }
+        }
+        if !(context.etiny() <= exp.exp && exp.exp <= context.emax) {
+            return context.invalid(INVALID_OPERATION);
+        }
+        if self.is_zero() {
+            return Decimal::zero(self.negative, exp.exp).fix(context);
+        }
+        let self_adjusted = self.adjusted();
+        if self_adjusted > context.emax || self_adjusted - exp.exp + 1 > context.prec {
+            return context.invalid(INVALID_OPERATION);
+        }
+        let result = self.rescale(exp.exp, rounding);
+        if result.adjusted() > context.emax || result.digits.len() as i64 > context.prec {
+            return context.invalid(INVALID_OPERATION);
+        }
+        if result.is_truthy() && result.adjusted() < context.emin {
+            context.raise(SUBNORMAL);
+        }
+        if result.exp > self.exp {
+            if result.cmp(self) != Ordering::Equal {
+                context.raise(INEXACT);
+            }
+            context.raise(ROUNDED);
+        }
+        return result.fix(context);
+    }
+
+    pub fn same_quantum(&self, other: &Decimal) -> bool {
+        if self.is_special() || other.is_special() {
+            return self.is_nan() && other.is_nan() || self.kind == Kind::Infinite && other.kind == Kind::Infinite;
+        }
+        return self.exp == other.exp;
+    }
+
+    /* self * 10**other, for an integer other
+     */
+    pub fn scaleb(&self, other: &Decimal, context: &mut Context) -> Decimal {
+        if let Some(result) = self.check_nans(Some(other), context) {
+            return result;
+        }
+        if other.kind != Kind::Finite || other.exp != 0 {
+            return context.invalid(INVALID_OPERATION);
+        }
+        let limit = 2 * (context.emax + context.prec);
+        let shift = match other.digits.parse::<i64>() {
+            Ok(shift) if shift <= limit => shift,
+            _ => return context.invalid(INVALID_OPERATION),
+        };
+        if self.kind == Kind::Infinite {
+            return self.clone();
+        }
+        let exp = if other.negative { self.exp - shift } else { self.exp + shift };
+        return Decimal::finite(self.negative, self.digits.clone(), exp).fix(context);
+    }
+
+    /* to_integral_exact(), which raises Inexact and Rounded, or
+     * to_integral_value(), which doesn't
+     */
+    pub fn to_integral(&self, rounding: Rounding, exact: bool, context: &mut Context) -> Decimal {
+        if self.is_special() {
+            if let Some(result) = self.check_nans(None, context) {
+                return result;
+            }
+            return self.clone();
+        }
+        if self.exp >= 0 {
+            return self.clone();
+        }
+        if !exact {
+            return self.rescale(0, rounding);
+        }
+        if self.is_zero() {
+            return Decimal::zero(self.negative, 0);
+        }
+        let result = self.rescale(0, rounding);
+        if result.cmp(self) != Ordering::Equal {
+            context.raise(INEXACT);
+        }
+        context.raise(ROUNDED);
+        return result;
+    }
+
+    /* round(), math.floor() and the like: the nearest integer in the
+     * direction given
+     */
+    pub fn to_bigint(&self, rounding: Rounding) -> Result<BigInt, Exception> {
+        match self.kind {
+            Kind::NaN | Kind::SNaN => return Err(Exception::new("ValueError", "cannot round a NaN")),
+            Kind::Infinite => return Err(Exception::new("OverflowError", "cannot round an infinity")),
+            Kind::Finite => return Ok(self.rescale(0, rounding).trunc()),
+        }
+    }
+}
+
+/* the coefficients and exponent of two nonzero numbers lined up for
+ * adding: the same exponent, with a much smaller number replaced by one
+ * that rounds the same
+ */
+fn align(a: &Decimal, b: &Decimal, prec: i64) -> ((String, i64), (String, i64)) {
+    let (mut x, mut y) = ((a.digits.clone(), a.exp), (b.digits.clone(), b.exp));
+    {
+        let (tmp, other) = if x.1 < y.1 { (&mut y, &mut x) } else { (&mut x, &mut y) };
+        let exp = tmp.1 + (-1i64).min(tmp.0.len() as i64 - prec - 2);
+        if other.0.len() as i64 + other.1 - 1 < exp {
+            *other = (String::from("1"), exp);
+        }
+        tmp.0.push_str(&"0".repeat((tmp.1 - other.1) as usize));
+        tmp.1 = other.1;
+    }
+    return (x, y);
+}
+
+/* round a result the way exp(), ln(), log10() and sqrt() are: always half
+ * to even
+ */
+fn fix_half_even(result: &Decimal, context: &mut Context) -> Decimal {
+    let mut half_even = context.half_even();
+    let result = result.fix(&mut half_even);
+    context.status = half_even.status;
+    return result;
+}
+
+/* The Python side: the Decimal class, contexts, and the module
+ */
+
+/* A decimal.Context: the arithmetic context, with the flags and traps as
+ * dicts from signal to whether it's set, which scripts change in place
+ */
+struct ContextObject {
+    context: Context,
+    flags: Rc<RefCell<Dict>>,
+    traps: Rc<RefCell<Dict>>,
+}
+
+/* What localcontext() returns: entering it makes a copy of a context
+ * current, and leaving it puts back the one that was
+ */
+struct ContextManager {
+    context: Value,
+    saved: Option<Value>,
+}
+
+/* What the module's functions and Decimal's operators share: the signals,
+ * as classes in SIGNALS' order, the DecimalTuple class as_tuple() returns,
+ * and the thread's current context
+ */
+struct State {
+    signals: Vec<Rc<Class>>,
+    decimal_tuple: Rc<Class>,
+    current: Value,
+}
+
+thread_local! {
+    static STATE: RefCell<Option<State>> = const { RefCell::new(None) };
+}
+
+// the signals trapped unless a context says otherwise
+const DEFAULT_TRAPS: u32 = INVALID_OPERATION | DIVISION_BY_ZERO | OVERFLOW;
+
+pub fn build_class(object: Rc<Class>) -> Rc<Class> {
+    let class = Class::build_new("decimal.Decimal", vec![object], Some(decimal_new));
+    let methods: &[(&str, BuiltinFn)] = &[
+        ("adjusted", adjusted),
+        ("as_integer_ratio", as_integer_ratio),
+        ("as_tuple", as_tuple),
+        ("canonical", canonical),
+        ("compare_total", compare_total),
+        ("conjugate", canonical),
+        ("copy_abs", copy_abs),
+        ("copy_negate", copy_negate),
+        ("copy_sign", copy_sign),
+        ("from_float", from_float),
+        ("is_canonical", is_canonical),
+        ("is_finite", is_finite),
+        ("is_infinite", is_infinite),
+        ("is_nan", is_nan),
+        ("is_normal", is_normal),
+        ("is_qnan", is_qnan),
+        ("is_signed", is_signed),
+        ("is_snan", is_snan),
+        ("is_subnormal", is_subnormal),
+        ("is_zero", is_zero),
+        ("quantize", quantize),
+        ("same_quantum", same_quantum),
+        ("to_eng_string", to_eng_string),
+        ("to_integral", to_integral_value),
+        ("to_integral_exact", to_integral_exact),
+        ("to_integral_value", to_integral_value),
+    ];
+    for (name, method) in methods {
+        class.attrs.borrow_mut().insert(String::from(*name), Builtin::build_new(name, *method));
+    }
+    for (name, op) in UNARY_METHODS {
+        let (name, op) = (*name, *op);
+        let method = move |_: &mut Interpreter, mut args: Args| {
+            let context = context_arg(&mut args, 1)?;
+            args.check(name, 1, 2)?;
+            let val = this(name, &args)?;
+            return apply(context.as_ref(), |context| op(&val, context)).map(new_decimal);
+        };
+        class.attrs.borrow_mut().insert(String::from(name), Builtin::build_native(name, Rc::new(method)));
+    }
+    for (name, op) in BINARY_METHODS {
+        let (name, op) = (*name, *op);
+        let method = move |_: &mut Interpreter, mut args: Args| {
+            let context = context_arg(&mut args, 2)?;
+            args.check(name, 2, 3)?;
+            let (val, other) = (this(name, &args)?, operand(&args.positional[1])?);
+            return apply(context.as_ref(), |context| op(&val, &other, context)).map(new_decimal);
+        };
+        class.attrs.borrow_mut().insert(String::from(name), Builtin::build_native(name, Rc::new(method)));
+    }
+    return class;
+}
+
+pub fn build_module(class: Rc<Class>, object: Rc<Class>, exceptions: &HashMap<String, Rc<Class>>) -> Rc<Module> {
+    let module = Module::build_new("decimal", None);
+    module.set("Decimal", Value::Class(class));
+
+    // the signals, under DecimalException, and the conditions, under the
+    // signal they raise; some are also the built-in exception they are like
+    let builtin = |name: &str| return exceptions[name].clone();
+    let base = Class::build_new("decimal.DecimalException", vec![builtin("ArithmeticError")], None);
+    module.set("DecimalException", Value::Class(base.clone()));
+    let mut signals: Vec<Rc<Class>> = Vec::new();
+    for (name, _) in SIGNALS {
+        let mut bases = vec![base.clone()];
+        match *name {
+            "DivisionByZero" => bases.push(builtin("ZeroDivisionError")),
+            "FloatOperation" => bases.push(builtin("TypeError")),
+            _ => {}
+        }
+        let signal = Class::build_new(&format!("decimal.{}", name), bases, None);
+        module.set(name, Value::Class(signal.clone()));
+        signals.push(signal);
+    }
+    for (name, _) in CONDITIONS {
+        let mut bases = vec![signals[0].clone()];
+        if *name == "DivisionUndefined" {
+            bases.push(builtin("ZeroDivisionError"));
+        }
+        let condition = Class::build_new(&format!("decimal.{}", name), bases, None);
+        module.set(name, Value::Class(condition));
+    }
+
+    let context_class = context_class().build(object.clone());
+    module.set("Context", Value::Class(context_class.clone()));
+    let template = |prec: i64, rounding: Rounding, traps: u32| {
+        let mut context = Context::build_new();
+        context.prec = prec;
+        context.rounding = rounding;
+        return new_context(&context_class, &signals, context, 0, traps);
+    };
+    let default = template(28, Rounding::HalfEven, DEFAULT_TRAPS);
+    let basic_traps = SIGNALS.iter().fold(0, |bits, (_, signal)| bits | signal) & !(INEXACT | ROUNDED | SUBNORMAL);
+    module.set("BasicContext", template(9, Rounding::HalfUp, basic_traps));
+    module.set("ExtendedContext", template(9, Rounding::HalfEven, 0));
+    // a thread starts with a copy of DefaultContext
+    let current = copy_context(&default, &signals);
+    module.set("DefaultContext", default);
+    let layout = Layout {
+        name: String::from("DecimalTuple"),
+        fields: ["sign", "digits", "exponent"].iter().map(|field| String::from(*field)).collect(),
+        defaults: Vec::new(),
+    };
+    let decimal_tuple = namedtuple_class(layout, object.clone());
+    module.set("DecimalTuple", Value::Class(decimal_tuple.clone()));
+    STATE.with(|state| {
+        *state.borrow_mut() = Some(State {
+            signals,
+            decimal_tuple,
+            current,
+        });
+    });
+
+    module.set("getcontext", Builtin::build_new("getcontext", getcontext));
+    module.set("setcontext", Builtin::build_new("setcontext", setcontext));
+    let manager_class = context_manager_class().build(object);
+    let localcontext: NativeFn = Rc::new(move |interpreter, args| local_context(interpreter, &manager_class, args));
+    module.set("localcontext", Builtin::build_native("localcontext", localcontext));
+    for (_, name) in ROUNDINGS {
+        module.set(name, Value::Str(String::from(*name)));
+    }
+    module.set("MAX_PREC", Value::Int(MAX_PREC));
+    module.set("MAX_EMAX", Value::Int(MAX_EMAX));
+    module.set("MIN_EMIN", Value::Int(MIN_EMIN));
+    module.set("MIN_ETINY", Value::Int(MIN_ETINY));
+    return Rc::new(module);
+}
+
+type UnaryFn = fn(&Decimal, &mut Context) -> Decimal;
+type BinaryFn = fn(&Decimal, &Decimal, &mut Context) -> Decimal;
+
+// methods of Decimal taking an optional context, which are methods of
+// Context too
+const UNARY_METHODS: &[(&str, UnaryFn)] = &[
+    ("exp", Decimal::exp),
+    ("ln", Decimal::ln),
+    ("log10", Decimal::log10),
+    ("normalize", Decimal::normalize),
+    ("sqrt", Decimal::sqrt),
+];
+const BINARY_METHODS: &[(&str, BinaryFn)] = &[
+    ("compare", Decimal::compare),
+    ("max", max),
+    ("min", min),
+    ("remainder_near", Decimal::remainder_near),
+    ("scaleb", Decimal::scaleb),
+];
+
+// the rest of Context's arithmetic
+const CONTEXT_UNARY: &[(&str, UnaryFn)] = &[
+    ("abs", Decimal::abs),
+    ("minus", Decimal::neg),
+    ("plus", Decimal::pos),
+    ("to_integral", integral_value),
+    ("to_integral_exact", integral_exact),
+    ("to_integral_value", integral_value),
+];
+const CONTEXT_BINARY: &[(&str, BinaryFn)] = &[
+    ("add", Decimal::add),
+    ("divide", Decimal::div),
+    ("divide_int", Decimal::floordiv),
+    ("multiply", Decimal::mul),
+    ("power", Decimal::pow),
+    ("quantize", quantize_op),
+    ("remainder", Decimal::modulo),
+    ("subtract", Decimal::sub),
+];
+
+// the context's settings, as attributes and Context() arguments
+const SETTINGS: &[&str] = &["prec", "rounding", "Emin", "Emax", "capitals", "clamp"];
+
+fn max(val: &Decimal, other: &Decimal, context: &mut Context) -> Decimal {
+    return val.max_min(other, true, context);
+}
+
+fn min(val: &Decimal, other: &Decimal, context: &mut Context) -> Decimal {
+    return val.max_min(other, false, context);
+}
+
+fn integral_value(val: &Decimal, context: &mut Context) -> Decimal {
+    return val.to_integral(context.rounding, false, context);
+}
+
+fn integral_exact(val: &Decimal, context: &mut Context) -> Decimal {
+    return val.to_integral(context.rounding, true, context);
+}
+
+fn quantize_op(val: &Decimal, exp: &Decimal, context: &mut Context) -> Decimal {
+    return val.quantize(exp, context.rounding, context);
+}
+
+fn context_class() -> NativeClass<ContextObject> {
+    let mut class: NativeClass<ContextObject> = NativeClass::build_new("decimal.Context");
+    class.raw_new(context_new);
+    for name in SETTINGS {
+        class.getter(name, move |this: &ContextObject| setting(&this.context, name));
+        class.setter(name, move |this: &mut ContextObject, value: Value| set_setting(&mut this.context, name, &value));
+    }
+    class.getter("flags", |this: &ContextObject| Value::Dict(this.flags.clone()));
+    class.getter("traps", |this: &ContextObject| Value::Dict(this.traps.clone()));
+    class.method("Etiny", |this: &mut ContextObject| this.context.etiny());
+    class.method("Etop", |this: &mut ContextObject| this.context.etop());
+    class.raw_method("copy", |_: &mut Interpreter, args: Args| {
+        args.check("copy", 1, 1)?;
+        instance::<ContextObject>("decimal.Context", "copy", &args)?;
+        return Ok(copy_context(&args.positional[0], &signals()));
+    });
+    for (name, clear) in [("clear_flags", true), ("clear_traps", false)] {
+        class.raw_method(name, move |_: &mut Interpreter, args: Args| {
+            args.check(name, 1, 1)?;
+            let this = instance::<ContextObject>("decimal.Context", name, &args)?;
+            let dict = if clear { &this.flags } else { &this.traps };
+            for signal in signals() {
+                dict.borrow_mut().set(Value::Class(signal), Value::Bool(false))?;
+            }
+            return Ok(Value::NoneT);
+        });
+    }
+    class.raw_method("create_decimal", |_: &mut Interpreter, args: Args| {
+        args.check("create_decimal", 1, 2)?;
+        instance::<ContextObject>("decimal.Context", "create_decimal", &args)?;
+        let value = args.positional.get(1).cloned().unwrap_or_else(|| Value::Str(String::from("0")));
+        let (val, status) = convert(&value)?;
+        return apply(Some(&args.positional[0]), |context| {
+            context.raise(status);
+            return val.fix(context);
+        })
+        .map(new_decimal);
+    });
+    class.raw_method("create_decimal_from_float", |_: &mut Interpreter, args: Args| {
+        args.check("create_decimal_from_float", 2, 2)?;
+        instance::<ContextObject>("decimal.Context", "create_decimal_from_float", &args)?;
+        let val = float_operand("create_decimal_from_float", &args.positional[1])?;
+        return apply(Some(&args.positional[0]), |context| val.fix(context)).map(new_decimal);
+    });
+    class.raw_method("divmod", |_: &mut Interpreter, args: Args| {
+        args.check("divmod", 3, 3)?;
+        instance::<ContextObject>("decimal.Context", "divmod", &args)?;
+        let (val, other) = (operand(&args.positional[1])?, operand(&args.positional[2])?);
+        let (quotient, remainder) = apply(Some(&args.positional[0]), |context| val.divmod(&other, context))?;
+        return Ok(new_tuple(vec![new_decimal(quotient), new_decimal(remainder)]));
+    });
+    for (name, eng) in [("to_sci_string", false), ("to_eng_string", true)] {
+        class.raw_method(name, move |_: &mut Interpreter, args: Args| {
+            args.check(name, 2, 2)?;
+            let capitals = instance::<ContextObject>("decimal.Context", name, &args)?.context.capitals;
+            let val = operand(&args.positional[1])?;
+            return Ok(Value::Str(val.to_text(eng, capitals)));
+        });
+    }
+    for (name, op) in UNARY_METHODS.iter().chain(CONTEXT_UNARY) {
+        let (name, op) = (*name, *op);
+        class.raw_method(name, move |_: &mut Interpreter, args: Args| {
+            args.check(name, 2, 2)?;
+            instance::<ContextObject>("decimal.Context", name, &args)?;
+            let val = operand(&args.positional[1])?;
+            return apply(Some(&args.positional[0]), |context| op(&val, context)).map(new_decimal);
+        });
+    }
+    for (name, op) in BINARY_METHODS.iter().chain(CONTEXT_BINARY) {
+        let (name, op) = (*name, *op);
+        class.raw_method(name, move |_: &mut Interpreter, args: Args| {
+            args.check(name, 3, 3)?;
+            instance::<ContextObject>("decimal.Context", name, &args)?;
+            let (val, other) = (operand(&args.positional[1])?, operand(&args.positional[2])?);
+            return apply(Some(&args.positional[0]), |context| op(&val, &other, context)).map(new_decimal);
+        });
+    }
+    return class;
+}
+
+/* Context(prec=None, rounding=None, Emin=None, Emax=None, capitals=None,
+ * clamp=None, flags=None, traps=None), where flags and traps are lists
+ * of signals or dicts from signal to bool
+ */
+fn context_new(interpreter: &mut Interpreter, class: Rc<Class>, mut args: Args) -> EvalResult {
+    let names = SETTINGS.iter().chain(&["flags", "traps"]);
+    let values: Vec<Option<Value>> = names
+        .enumerate()
+        .map(|(index, name)| optional(&mut args, index, name).filter(|value| *value != Value::NoneT))
+        .collect();
+    args.check("Context", 0, 8)?;
+    let mut context = Context::build_new();
+    for (name, value) in SETTINGS.iter().zip(&values) {
+        if let Some(value) = value {
+            set_setting(&mut context, name, value)?;
+        }
+    }
+    let signals = signals();
+    let flags = match &values[6] {
+        Some(flags) => signal_bits(interpreter, flags, &signals)?,
+        None => 0,
+    };
+    let traps = match &values[7] {
+        Some(traps) => signal_bits(interpreter, traps, &signals)?,
+        None => DEFAULT_TRAPS,
+    };
+    return Ok(new_context(&class, &signals, context, flags, traps));
+}
+
+fn setting(context: &Context, name: &str) -> Value {
+    match name {
+        "prec" => return Value::Int(context.prec),
+        "rounding" => {
+            let (_, name) = ROUNDINGS.iter().find(|(rounding, _)| *rounding == context.rounding).unwrap();
+            return Value::Str(String::from(*name));
+        }
+        "Emin" => return Value::Int(context.emin),
+        "Emax" => return Value::Int(context.emax),
+        "capitals" => return Value::Int(context.capitals as i64),
+        _ => return Value::Int(context.clamp as i64),
+    }
+}
+
+/* change a setting, if the value is in its range
+ */
+fn set_setting(context: &mut Context, name: &str, value: &Value) -> Result<(), Exception> {
+    if name == "rounding" {
+        context.rounding = rounding_arg(value)?;
+        return Ok(());
+    }
+    let val = match as_int(value) {
+        Some(val) => val,
+        None => return Err(not_an_int(value)),
+    };
+    let (low, high, range) = match name {
+        "prec" => (1, MAX_PREC, "[1, MAX_PREC]"),
+        "Emin" => (MIN_EMIN, 0, "[MIN_EMIN, 0]"),
+        "Emax" => (0, MAX_EMAX, "[0, MAX_EMAX]"),
+        _ => (0, 1, ""),
+    };
+    if !(low..=high).contains(&val) {
+        let message = match range {
+            "" => format!("valid values for {} are 0 or 1", name),
+            range => format!("valid range for {} is {}", name, range),
+        };
+        return Err(Exception::new("ValueError", &message));
+    }
+    match name {
+        "prec" => context.prec = val,
+        "Emin" => context.emin = val,
+        "Emax" => context.emax = val,
+        "capitals" => context.capitals = val == 1,
+        _ => context.clamp = val == 1,
+    };
+    return Ok(());
+}
+
+fn rounding_arg(value: &Value) -> Result<Rounding, Exception> {
+    if let Value::Str(text) = value {
+        if let Some((rounding, _)) = ROUNDINGS.iter().find(|(_, name)| name == text) {
+            return Ok(*rounding);
+        }
+    }
+    let names: Vec<&str> = ROUNDINGS.iter().map(|(_, name)| *name).collect();
+    let message = format!("valid values for rounding are: [{}]", names.join(", "));
+    return Err(Exception::new("TypeError", &message));
+}
+
+/* the signals a list of them names, or a dict from signal to bool sets
+ */
+fn signal_bits(interpreter: &mut Interpreter, value: &Value, signals: &[Rc<Class>]) -> Result<u32, Exception> {
+    if let Value::Dict(dict) = value {
+        return dict_bits(&dict.borrow(), signals);
+    }
+    let mut bits = 0;
+    for item in interpreter.iterate(value)? {
+        let position = signals.iter().position(|signal| item == Value::Class(signal.clone()));
+        match position {
+            Some(position) => bits |= SIGNALS[position].1,
+            None => {
+                let names: Vec<&str> = SIGNALS.iter().map(|(name, _)| *name).collect();
+                let message = format!("valid values for signals are: [{}]", names.join(", "));
+                return Err(Exception::new("KeyError", &message));
+            }
+        }
+    }
+    return Ok(bits);
+}
+
+fn dict_bits(dict: &Dict, signals: &[Rc<Class>]) -> Result<u32, Exception> {
+    let mut bits = 0;
+    for ((_, signal_bits), signal) in SIGNALS.iter().zip(signals) {
+        if dict.get(&Value::Class(signal.clone()))?.is_some_and(|value| value.is_truthy()) {
+            bits |= signal_bits;
+        }
+    }
+    return Ok(bits);
+}
+
+/* a dict from each signal to whether it's in bits
+ */
+fn signal_dict(signals: &[Rc<Class>], bits: u32) -> Rc<RefCell<Dict>> {
+    let mut dict = Dict::build_new();
+    for ((_, signal_bits), signal) in SIGNALS.iter().zip(signals) {
+        dict.set(Value::Class(signal.clone()), Value::Bool(bits & signal_bits != 0)).unwrap();
+    }
+    match new_dict(dict) {
+        Value::Dict(ref dict) => return dict.clone(),
+        _ => unreachable!(),
+    }
+}
+
+fn new_context(class: &Rc<Class>, signals: &[Rc<Class>], context: Context, flags: u32, traps: u32) -> Value {
+    let this = ContextObject {
+        context,
+        flags: signal_dict(signals, flags),
+        traps: signal_dict(signals, traps),
+    };
+    return Value::Object(Object::build_new(class.clone(), Some(Box::new(this))));
+}
+
+fn copy_context(value: &Value, signals: &[Rc<Class>]) -> Value {
+    let this = context_of(value).unwrap();
+    let flags = dict_bits(&this.flags.borrow(), signals).unwrap_or(0);
+    let traps = dict_bits(&this.traps.borrow(), signals).unwrap_or(0);
+    let class = match value {
+        Value::Object(object) => object.class.clone(),
+        _ => unreachable!(),
+    };
+    return new_context(&class, signals, this.context.clone(), flags, traps);
+}
+
+/* the ContextObject of a Context, if value is one
+ */
+fn context_of(value: &Value) -> Option<RefMut<'_, ContextObject>> {
+    match value {
+        Value::Object(object) => {
+            let native = object.native.as_ref()?;
+            return RefMut::filter_map(native.borrow_mut(), |native| native.downcast_mut::<ContextObject>()).ok();
+        }
+        _ => return None,
+    }
+}
+
+fn signals() -> Vec<Rc<Class>> {
+    return STATE.with(|state| state.borrow().as_ref().map(|state| state.signals.clone()).unwrap_or_default());
+}
+
+/* the thread's current context; None before the module is built
+ */
+fn current() -> Value {
+    return STATE.with(|state| state.borrow().as_ref().map_or(Value::NoneT, |state| state.current.clone()));
+}
+
+/* Run an operation in a context, the one given or the thread's current
+ * one. The conditions it meets set their flags, and the trapped ones
+ * raise.
+ */
+fn apply<T>(context: Option<&Value>, op: impl FnOnce(&mut Context) -> T) -> Result<T, Exception> {
+    let context = context.cloned().unwrap_or_else(current);
+    let mut working = context_of(&context).map_or_else(Context::build_new, |this| this.context.clone());
+    working.status = 0;
+    let result = op(&mut working);
+    signal(&context, working.status, true)?;
+    return Ok(result);
+}
+
+/* Set the flags of the signals for the conditions in status, then, if
+ * trap, raise for the trapped ones
+ */
+fn signal(context: &Value, status: u32, trap: bool) -> Result<(), Exception> {
+    let this = match context_of(context) {
+        Some(this) if status != 0 => this,
+        _ => return Ok(()),
+    };
+    let mut trapped = 0;
+    for ((_, bits), signal) in SIGNALS.iter().zip(signals()) {
+        if status & bits == 0 {
+            continue;
+        }
+        let key = Value::Class(signal);
+        if this.traps.borrow().get(&key)?.is_some_and(|value| value.is_truthy()) {
+            trapped |= bits;
+        }
+        this.flags.borrow_mut().set(key, Value::Bool(true))?;
+    }
+    if !trap || status & trapped == 0 {
+        return Ok(());
+    }
+    return Err(trap_error(status & trapped));
+}
+
+/* The exception for trapped conditions, like the C implementation's: the
+ * first signal's, listing the conditions, e.g.
+ * "decimal.InvalidOperation: [<class 'decimal.ConversionSyntax'>]"
+ */
+fn trap_error(status: u32) -> Exception {
+    let (kind, _) = SIGNALS.iter().find(|(_, bits)| status & bits != 0).unwrap();
+    let conditions: Vec<String> = [("InvalidOperation", INVALID_OPERATION)]
+        .iter()
+        .chain(CONDITIONS)
+        .chain(&SIGNALS[1..])
+        .filter(|(_, bits)| status & bits != 0)
+        .map(|(name, _)| format!("<class 'decimal.{}'>", name))
+        .collect();
+    return Exception::new(&format!("decimal.{}", kind), &format!("[{}]", conditions.join(", ")));
+}
+
+/* make a context the thread's current one
+ */
+fn set_current(context: Value) {
+    STATE.with(|state| {
+        if let Some(state) = state.borrow_mut().as_mut() {
+            state.current = context;
+        }
+    });
+}
+
+fn getcontext(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("getcontext", 0, 0)?;
+    return Ok(current());
+}
+
+fn setcontext(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("setcontext", 1, 1)?;
+    let context = args.positional[0].clone();
+    if context_of(&context).is_none() {
+        return Err(Exception::new("TypeError", "argument must be a context"));
+    }
+    set_current(context);
+    return Ok(Value::NoneT);
+}
+
+/* localcontext(ctx=None, **settings): a context manager for a copy of ctx
+ * or the current context, with the settings (and flags and traps) given
+ * changed
+ */
+fn local_context(interpreter: &mut Interpreter, class: &Rc<Class>, mut args: Args) -> EvalResult {
+    let context = optional(&mut args, 0, "ctx").filter(|value| *value != Value::NoneT).unwrap_or_else(current);
+    if context_of(&context).is_none() {
+        return Err(Exception::new("TypeError", "optional argument must be a context"));
+    }
+    let settings: Vec<(&str, Option<Value>)> = SETTINGS.iter().map(|name| (*name, args.keyword(name))).collect();
+    let (flags, traps) = (args.keyword("flags"), args.keyword("traps"));
+    args.check("localcontext", 0, 1)?;
+
+    let signals = signals();
+    let copy = copy_context(&context, &signals);
+    {
+        let mut this = context_of(&copy).unwrap();
+        for (name, value) in settings {
+            if let Some(value) = value {
+                set_setting(&mut this.context, name, &value)?;
+            }
+        }
+        if let Some(flags) = flags {
+            this.flags = signal_dict(&signals, signal_bits(interpreter, &flags, &signals)?);
+        }
+        if let Some(traps) = traps {
+            this.traps = signal_dict(&signals, signal_bits(interpreter, &traps, &signals)?);
+        }
+    }
+    let manager = ContextManager {
+        context: copy,
+        saved: None,
+    };
+    return Ok(Value::Object(Object::build_new(class.clone(), Some(Box::new(manager)))));
+}
+
+fn context_manager_class() -> NativeClass<ContextManager> {
+    let name = "decimal.ContextManager";
+    let mut class: NativeClass<ContextManager> = NativeClass::build_new(name);
+    class.raw_method("__enter__", move |_: &mut Interpreter, args: Args| {
+        args.check("__enter__", 1, 1)?;
+        let mut this = instance::<ContextManager>(name, "__enter__", &args)?;
+        this.saved = Some(current());
+        set_current(this.context.clone());
+        return Ok(this.context.clone());
+    });
+    class.raw_method("__exit__", move |_: &mut Interpreter, args: Args| {
+        let mut this = instance::<ContextManager>(name, "__exit__", &args)?;
+        if let Some(saved) = this.saved.take() {
+            set_current(saved);
+        }
+        return Ok(Value::NoneT);
+    });
+    return class;
+}
+
+pub fn new_decimal(val: Decimal) -> Value {
+    return Value::Decimal(Rc::new(val));
+}
+
+/* the Decimal of a Decimal or int, exactly
+ */
+fn as_decimal(value: &Value) -> Option<Decimal> {
+    match value {
+        Value::Decimal(val) => return Some((**val).clone()),
+        _ => return as_bigint(value).map(|val| Decimal::from_bigint(&val)),
+    }
+}
+
+/* an operand of a method: a Decimal, or an int
+ */
+fn operand(value: &Value) -> Result<Decimal, Exception> {
+    return as_decimal(value).ok_or_else(|| {
+        let message = format!("conversion from {} to Decimal is not supported", value.type_name());
+        return Exception::new("TypeError", &message);
+    });
+}
+
+/* the argument of from_float(): a float, or an int
+ */
+fn float_operand(name: &str, value: &Value) -> Result<Decimal, Exception> {
+    match value {
+        Value::Float(val) => return Ok(Decimal::from_f64(*val)),
+        _ if as_bigint(value).is_some() => return operand(value),
+        _ => {
+            let message = format!("{}() argument must be int or float, not {}", name, value.type_name());
+            return Err(Exception::new("TypeError", &message));
+        }
+    }
+}
+
+/* What Decimal() makes of a value, exactly, and the conditions that
+ * signals: a string that isn't a number is ConversionSyntax, and a float
+ * FloatOperation
+ */
+fn convert(value: &Value) -> Result<(Decimal, u32), Exception> {
+    match value {
+        Value::Str(text) => match Decimal::parse(text) {
+            Ok(val) => return Ok((val, 0)),
+            Err(condition) => return Ok((Decimal::nan(), condition)),
+        },
+        Value::Float(val) => return Ok((Decimal::from_f64(*val), FLOAT_OPERATION)),
+        _ => return Ok((operand(value)?, 0)),
+    }
+}
+
+/* positional argument index, or the keyword argument name
+ */
+fn optional(args: &mut Args, index: usize, name: &str) -> Option<Value> {
+    let keyword = args.keyword(name);
+    return args.positional.get(index).cloned().or(keyword);
+}
+
+/* the context argument of a method, at index or by keyword; None for the
+ * current context
+ */
+fn context_arg(args: &mut Args, index: usize) -> Result<Option<Value>, Exception> {
+    let context = optional(args, index, "context").filter(|value| *value != Value::NoneT);
+    if context.as_ref().is_some_and(|context| context_of(context).is_none()) {
+        return Err(Exception::new("TypeError", "optional argument must be a context"));
+    }
+    return Ok(context);
+}
+
+/* the rounding argument of a method, at index or by keyword, or the
+ * context's
+ */
+fn rounding_of(args: &mut Args, index: usize) -> Result<Option<Rounding>, Exception> {
+    match optional(args, index, "rounding").filter(|value| *value != Value::NoneT) {
+        Some(rounding) => return Ok(Some(rounding_arg(&rounding)?)),
+        None => return Ok(None),
+    }
+}
+
+/* Decimal(value="0", context=None)
+ */
+fn decimal_new(_interpreter: &mut Interpreter, mut args: Args) -> EvalResult {
+    let value = optional(&mut args, 0, "value").unwrap_or_else(|| Value::Str(String::from("0")));
+    let context = context_arg(&mut args, 1)?;
+    args.check("Decimal", 0, 2)?;
+    if let Value::Decimal(_) = value {
+        return Ok(value);
+    }
+    let (val, status) = convert(&value)?;
+    return apply(context.as_ref(), |context| {
+        context.raise(status);
+        return val;
+    })
+    .map(new_decimal);
+}
+
+/* the Decimal a method was called on
+ */
+fn this(name: &str, args: &Args) -> Result<Rc<Decimal>, Exception> {
+    match args.positional.first() {
+        Some(Value::Decimal(val)) => return Ok(val.clone()),
+        other => {
+            let given = other.map_or("nothing", |value| value.type_name());
+            let message = format!("descriptor '{}' for 'decimal.Decimal' objects doesn't apply to a '{}' object", name, given);
+            return Err(Exception::new("TypeError", &message));
+        }
+    }
+}
+
+fn adjusted(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("adjusted", 1, 1)?;
+    return Ok(Value::Int(this("adjusted", &args)?.adjusted()));
+}
+
+/* as_tuple(): DecimalTuple(sign, digits, exponent), where the exponent of
+ * an infinity is 'F', of a NaN 'n' and of a signaling NaN 'N'
+ */
+fn as_tuple(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("as_tuple", 1, 1)?;
+    let val = this("as_tuple", &args)?;
+    let digits = match val.kind {
+        Kind::Infinite => vec![Value::Int(0)],
+        _ => val.digits.bytes().map(|digit| Value::Int((digit - b'0') as i64)).collect(),
+    };
+    let exponent = match val.kind {
+        Kind::Finite => Value::Int(val.exp),
+        Kind::Infinite => Value::Str(String::from("F")),
+        Kind::NaN => Value::Str(String::from("n")),
+        Kind::SNaN => Value::Str(String::from("N")),
+    };
+    let fields = vec![Value::Int(val.negative as i64), new_tuple(digits), exponent];
+    let class = STATE.with(|state| state.borrow().as_ref().map(|state| state.decimal_tuple.clone()));
+    match class {
+        Some(class) => return interpreter.call(&Value::Class(class), Args::build_new(fields)),
+        None => return Ok(new_tuple(fields)),
+    }
+}
+
+fn as_integer_ratio(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("as_integer_ratio", 1, 1)?;
+    let ratio = to_rational(&*this("as_integer_ratio", &args)?)?;
+    return Ok(new_tuple(vec![fractions::int_value(ratio.numer())?, fractions::int_value(ratio.denom())?]));
+}
+
+/* canonical() and conjugate(), which are the number itself
+ */
+fn canonical(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("canonical", 1, 1)?;
+    return Ok(new_decimal((*this("canonical", &args)?).clone()));
+}
+
+fn compare_total(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("compare_total", 2, 2)?;
+    let (val, other) = (this("compare_total", &args)?, operand(&args.positional[1])?);
+    return Ok(new_decimal(Decimal::from_i64(val.compare_total(&other) as i64)));
+}
+
+fn copy_abs(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("copy_abs", 1, 1)?;
+    return Ok(new_decimal(this("copy_abs", &args)?.copy_abs()));
+}
+
+fn copy_negate(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("copy_negate", 1, 1)?;
+    return Ok(new_decimal(this("copy_negate", &args)?.copy_negate()));
+}
+
+fn copy_sign(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("copy_sign", 2, 2)?;
+    let (val, other) = (this("copy_sign", &args)?, operand(&args.positional[1])?);
+    return Ok(new_decimal(val.copy_sign(&other)));
+}
+
+/* Decimal.from_float(f), exactly, without signalling FloatOperation
+ */
+fn from_float(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("from_float", 1, 1)?;
+    return Ok(new_decimal(float_operand("from_float", &args.positional[0])?));
+}
+
+fn is_canonical(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("is_canonical", 1, 1)?;
+    this("is_canonical", &args)?;
+    return Ok(Value::Bool(true));
+}
+
+fn is_finite(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("is_finite", 1, 1)?;
+    return Ok(Value::Bool(!this("is_finite", &args)?.is_special()));
+}
+
+fn is_infinite(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("is_infinite", 1, 1)?;
+    return Ok(Value::Bool(this("is_infinite", &args)?.kind == Kind::Infinite));
+}
+
+fn is_nan(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("is_nan", 1, 1)?;
+    return Ok(Value::Bool(this("is_nan", &args)?.is_nan()));
+}
+
+fn is_qnan(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("is_qnan", 1, 1)?;
+    return Ok(Value::Bool(this("is_qnan", &args)?.kind == Kind::NaN));
+}
+
+fn is_snan(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("is_snan", 1, 1)?;
+    return Ok(Value::Bool(this("is_snan", &args)?.kind == Kind::SNaN));
+}
+
+fn is_signed(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("is_signed", 1, 1)?;
+    return Ok(Value::Bool(this("is_signed", &args)?.negative));
+}
+
+fn is_zero(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("is_zero", 1, 1)?;
+    return Ok(Value::Bool(this("is_zero", &args)?.is_zero()));
+}
+
+/* is_normal() and is_subnormal(): whether a nonzero finite number's
+ * exponent is at least the context's Emin
+ */
+fn normal(name: &str, mut args: Args, subnormal: bool) -> EvalResult {
+    let context = context_arg(&mut args, 1)?.unwrap_or_else(current);
+    args.check(name, 1, 2)?;
+    let val = this(name, &args)?;
+    if val.is_special() || val.is_zero() {
+        return Ok(Value::Bool(false));
+    }
+    let emin = context_of(&context).map_or(Context::build_new().emin, |this| this.context.emin);
+    return Ok(Value::Bool((val.adjusted() < emin) == subnormal));
+}
+
+fn is_normal(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    return normal("is_normal", args, false);
+}
+
+fn is_subnormal(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    return normal("is_subnormal", args, true);
+}
+
+/* quantize(exp, rounding=None, context=None)
+ */
+fn quantize(_interpreter: &mut Interpreter, mut args: Args) -> EvalResult {
+    let rounding = rounding_of(&mut args, 2)?;
+    let context = context_arg(&mut args, 3)?;
+    args.check("quantize", 2, 4)?;
+    let (val, exp) = (this("quantize", &args)?, operand(&args.positional[1])?);
+    return apply(context.as_ref(), |context| {
+        return val.quantize(&exp, rounding.unwrap_or(context.rounding), context);
+    })
+    .map(new_decimal);
+}
+
+fn same_quantum(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("same_quantum", 2, 2)?;
+    let (val, other) = (this("same_quantum", &args)?, operand(&args.positional[1])?);
+    return Ok(Value::Bool(val.same_quantum(&other)));
+}
+
+fn to_eng_string(_interpreter: &mut Interpreter, mut args: Args) -> EvalResult {
+    let context = context_arg(&mut args, 1)?.unwrap_or_else(current);
+    args.check("to_eng_string", 1, 2)?;
+    let capitals = context_of(&context).is_none_or(|this| this.context.capitals);
+    return Ok(Value::Str(this("to_eng_string", &args)?.to_text(true, capitals)));
+}
+
+/* to_integral_value() and to_integral_exact(), with arguments rounding=None
+ * and context=None
+ */
+fn to_integral(name: &str, mut args: Args, exact: bool) -> EvalResult {
+    let rounding = rounding_of(&mut args, 1)?;
+    let context = context_arg(&mut args, 2)?;
+    args.check(name, 1, 3)?;
+    let val = this(name, &args)?;
+    return apply(context.as_ref(), |context| {
+        return val.to_integral(rounding.unwrap_or(context.rounding), exact, context);
+    })
+    .map(new_decimal);
+}
+
+fn to_integral_value(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    return to_integral("to_integral_value", args, false);
+}
+
+fn to_integral_exact(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    return to_integral("to_integral_exact", args, true);
+}
+
+/* str() of a Decimal, with the current context's capitals
+ */
+pub fn to_string(val: &Decimal) -> String {
+    let capitals = context_of(&current()).is_none_or(|this| this.context.capitals);
+    return val.to_text(false, capitals);
+}
+
+/* the exact value of a finite Decimal, or the error Fraction() raises
+ */
+pub fn to_rational(val: &Decimal) -> Result<BigRational, Exception> {
+    match val.kind {
+        Kind::NaN | Kind::SNaN => return Err(Exception::new("ValueError", "cannot convert NaN to integer ratio")),
+        Kind::Infinite => return Err(Exception::new("OverflowError", "cannot convert Infinity to integer ratio")),
+        Kind::Finite => {
+            let (numer, denom) = val.as_integer_ratio();
+            return Ok(BigRational::new_raw(numer, denom));
+        }
+    }
+}
+
+/* float()
+ */
+pub fn to_float(val: &Decimal) -> Result<f64, Exception> {
+    if val.kind == Kind::SNaN {
+        return Err(Exception::new("ValueError", "cannot convert signaling NaN to float"));
+    }
+    return Ok(val.to_f64());
+}
+
+/* int(), which truncates
+ */
+pub fn to_int(val: &Decimal) -> EvalResult {
+    match val.kind {
+        Kind::NaN | Kind::SNaN => return Err(Exception::new("ValueError", "cannot convert NaN to integer")),
+        Kind::Infinite => return Err(Exception::new("OverflowError", "cannot convert Infinity to integer")),
+        Kind::Finite => return fractions::int_value(&val.trunc()),
+    }
+}
+
+/* round(): to an int, halves to even, or quantized to ndigits places in
+ * the current context
+ */
+pub fn round(val: &Decimal, ndigits: Option<i64>) -> EvalResult {
+    let ndigits = match ndigits {
+        Some(ndigits) => ndigits,
+        None => return fractions::int_value(&val.to_bigint(Rounding::HalfEven)?),
+    };
+    let exp = Decimal::finite(false, String::from("1"), ndigits.saturating_neg());
+    return apply(None, |context| val.quantize(&exp, context.rounding, context)).map(new_decimal);
+}
+
+pub fn abs(val: &Decimal) -> EvalResult {
+    return apply(None, |context| val.abs(context)).map(new_decimal);
+}
+
+pub fn unary_op(op: UnaryOp, operand: &Value) -> EvalResult {
+    let val = as_decimal(operand).unwrap();
+    return apply(None, |context| match op {
+        UnaryOp::Neg => val.neg(context),
+        _ => val.pos(context),
+    })
+    .map(new_decimal);
+}
+
+/* Arithmetic with a Decimal on either side; None if the other side isn't
+ * a Decimal or int, which is all a Decimal mixes with
+ */
+pub fn binary_op(op: BinOp, left: &Value, right: &Value) -> Option<EvalResult> {
+    if op.is_bitwise() || !matches!(left, Value::Decimal(_)) && !matches!(right, Value::Decimal(_)) {
+        return None;
+    }
+    let (left, right) = (as_decimal(left)?, as_decimal(right)?);
+    let result = apply(None, |context| match op {
+        BinOp::Add => left.add(&right, context),
+        BinOp::Sub => left.sub(&right, context),
+        BinOp::Mul => left.mul(&right, context),
+        BinOp::Div => left.div(&right, context),
+        BinOp::FloorDiv => left.floordiv(&right, context),
+        BinOp::Mod => left.modulo(&right, context),
+        BinOp::Pow => left.pow(&right, context),
+        BinOp::BitAnd | BinOp::BitOr | BinOp::BitXor | BinOp::LShift | BinOp::RShift => unreachable!(),
+    });
+    return Some(result.map(new_decimal));
+}
+
+/* a Decimal, int or float to compare a Decimal with, exactly, and the
+ * conditions comparing with it signals
+ */
+fn comparand(value: &Value) -> Option<(Decimal, u32)> {
+    match value {
+        Value::Float(val) => return Some((Decimal::from_f64(*val), FLOAT_OPERATION)),
+        _ => return as_decimal(value).map(|val| (val, 0)),
+    }
+}
+
+/* == with a Decimal on either side; None if the other side isn't a
+ * number it compares with here (a Fraction compares with it itself)
+ */
+pub fn equals(left: &Value, right: &Value) -> Option<bool> {
+    if !matches!(left, Value::Decimal(_)) && !matches!(right, Value::Decimal(_)) {
+        return None;
+    }
+    // a complex number is compared by its real part, if it has no other
+    let real = |value: &Value| match value {
+        Value::Complex(real, 0.0) => Some(Value::Float(*real)),
+        Value::Complex(..) => None,
+        _ => Some(value.clone()),
+    };
+    let (left, right) = match (real(left), real(right)) {
+        (Some(left), Some(right)) => (left, right),
+        _ => return Some(false),
+    };
+    let ((left, left_status), (right, right_status)) = (comparand(&left)?, comparand(&right)?);
+    let mut status = left_status | right_status;
+    if left.kind == Kind::SNaN || right.kind == Kind::SNaN {
+        status |= INVALID_OPERATION;
+    }
+    // == never raises, so there's nothing to do if the flags can't be set
+    let _ = signal(&current(), status, false);
+    return Some(!left.is_nan() && !right.is_nan() && left.cmp(&right) == Ordering::Equal);
+}
+
+/* How a Decimal compares with a Decimal, int or float; None if it doesn't
+ * here. Comparing a NaN signals InvalidOperation, and a float
+ * FloatOperation.
+ */
+pub fn partial_cmp(left: &Value, right: &Value) -> Option<Result<Option<Ordering>, Exception>> {
+    if !matches!(left, Value::Decimal(_)) && !matches!(right, Value::Decimal(_)) {
+        return None;
+    }
+    let ((left, left_status), (right, right_status)) = (comparand(left)?, comparand(right)?);
+    let mut status = left_status | right_status;
+    if left.is_nan() || right.is_nan() {
+        status |= INVALID_OPERATION;
+    }
+    if let Err(err) = signal(&current(), status, true) {
+        return Some(Err(err));
+    }
+    if left.is_nan() || right.is_nan() {
+        return Some(Ok(None));
+    }
+    return Some(Ok(Some(left.cmp(&right))));
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testing::{eval, exec, python};
+
+    const IMPORTS: &str = "from decimal import *\nfrom fractions import Fraction as F\nimport math\nD = Decimal";
+
+    #[test]
+    fn test_arithmetic() {
+        let mut python = python(IMPORTS);
+        assert_eq!(eval(&mut python, "(D('1.10') + D('2.20'), D('1.30') * 3, D(1) / D(7), D('7') // D('-2'), D('7') % D('-2'), -D('0.0'), D('10') ** -2)"), "(Decimal('3.30'), Decimal('3.90'), Decimal('0.1428571428571428571428571429'), Decimal('-3'), Decimal('1'), Decimal('0.0'), Decimal('0.01'))");
+        assert_eq!(eval(&mut python, "(str(D('1e3')), str(D('-0.000001')), str(D('1E-7')), str(D('123.4500')), D('  3.14 '), D('-Inf'), D('nan'), D('sNaN12'))"), "('1E+3', '-0.000001', '1E-7', '123.4500', Decimal('3.14'), Decimal('-Infinity'), Decimal('NaN'), Decimal('sNaN12'))");
+        assert_eq!(eval(&mut python, "(D(0.1), D.from_float(0.5), D(10).sqrt(), D(2).ln(), D(1).exp(), D(1000).log10())"), "(Decimal('0.1000000000000000055511151231257827021181583404541015625'), Decimal('0.5'), Decimal('3.162277660168379331998893544'), Decimal('0.6931471805599453094172321215'), Decimal('2.718281828459045235360287471'), Decimal('3'))");
+    }
+
+    #[test]
+    fn test_rounding() {
+        let mut python = python(IMPORTS);
+        assert_eq!(eval(&mut python, "(D('1.2345').quantize(D('0.01')), D('1.2355').quantize(D('0.001'), rounding=ROUND_UP), D('2.5').to_integral_value(), D('3.5').to_integral_value(ROUND_DOWN))"), "(Decimal('1.23'), Decimal('1.236'), Decimal('2'), Decimal('3'))");
+        assert_eq!(eval(&mut python, "(round(D('2.5')), round(D('2.675'), 2), int(D('-7.9')), float(D('0.1')), math.floor(D('-1.5')), math.ceil(D('1.2')), abs(D('-3')))"), "(2, Decimal('2.68'), -7, 0.1, -2, 2, Decimal('3'))");
+    }
+
+    #[test]
+    fn test_comparisons() {
+        let mut python = python(IMPORTS);
+        assert_eq!(eval(&mut python, "(D('1.0') == 1, D('0.5') == 0.5, D('1.5') < 2, D('0.1') < 0.2, D(1) == F(1), D('0.5') < F(2, 3), hash(D('2.0')) == hash(2), {D('0.5'): 'a'}[0.5])"), "(True, True, True, True, True, True, True, 'a')");
+    }
+
+    #[test]
+    fn test_methods() {
+        let mut python = python(IMPORTS);
+        assert_eq!(eval(&mut python, "(D('1.23E+5').adjusted(), D('-1.5').as_integer_ratio(), D('1.20').normalize(), D('12E+3').to_eng_string(), D(-2).copy_abs(), D(3).copy_sign(D(-1)), D(1).compare(D(2)), D(1).max(D('nan')))"), "(5, (-3, 2), Decimal('1.2'), '12E+3', Decimal('2'), Decimal('-3'), Decimal('-1'), Decimal('1'))");
+        assert_eq!(eval(&mut python, "(D('NaN').is_nan(), D('-0').is_signed(), D('Inf').is_infinite(), D('1.5').is_finite(), D('0.00').is_zero(), D('1.0').same_quantum(D('2.0')))"), "(True, True, True, True, True, True)");
+        assert_eq!(eval(&mut python, "(F(D('1.25')), F(1, 4) == D('0.25'), D(3) + 1, 1 - D('0.5'), 2 * D('1.5'))"), "(Fraction(5, 4), True, Decimal('4'), Decimal('0.5'), Decimal('3.0'))");
+        assert_eq!(eval(&mut python, "D('-1.20').as_tuple()"), "DecimalTuple(sign=1, digits=(1, 2, 0), exponent=-2)");
+        assert_eq!(eval(&mut python, "[(t.digits, t.exponent) for t in (D(x).as_tuple() for x in ('0', 'Inf', 'NaN', '-sNaN12'))]"), "[((0,), 0), ((0,), 'F'), ((), 'n'), ((1, 2), 'N')]");
+        assert_eq!(eval(&mut python, "(D('1.5').scaleb(3), D('7.50').scaleb(D(-2)), D('Inf').scaleb(2), getcontext().scaleb(D(7), 2))"), "(Decimal('1.5E+3'), Decimal('0.0750'), Decimal('Infinity'), Decimal('7E+2'))");
+        assert_eq!(eval(&mut python, "D(1).scaleb(D('1.5'))"), "decimal.InvalidOperation: [<class 'decimal.InvalidOperation'>]");
+        assert_eq!(eval(&mut python, "D(1).scaleb(10 ** 7)"), "decimal.InvalidOperation: [<class 'decimal.InvalidOperation'>]");
+        assert_eq!(eval(&mut python, "D(1).scaleb(1.5)"), "TypeError: conversion from float to Decimal is not supported");
+    }
+
+    #[test]
+    fn test_context() {
+        let mut python = python(IMPORTS);
+        exec(&mut python, "c = getcontext()\nc.prec = 6");
+        assert_eq!(eval(&mut python, "(D(1) / D(7), D('1234567.8') + 0)"), "(Decimal('0.142857'), Decimal('1.23457E+6'))");
+        exec(&mut python, "c.rounding = ROUND_DOWN");
+        assert_eq!(eval(&mut python, "D(2) / D(3)"), "Decimal('0.666666')");
+
+        // localcontext() works in a copy, and puts the context back
+        let source = "
+with localcontext() as local:
+    local.prec = 3
+    third = D(1) / D(3)
+with localcontext(prec=2, traps=[]) as local:
+    nan = D(1) / D(0)
+    flagged = local.flags[DivisionByZero]
+try:
+    with localcontext(c, rounding=ROUND_UP):
+        up = D(2) / D(3)
+        raise ValueError
+except ValueError:
+    pass
+";
+        exec(&mut python, source);
+        assert_eq!(eval(&mut python, "(third, nan, flagged, up, getcontext() is c, c.prec, c.rounding)"), "(Decimal('0.333'), Decimal('Infinity'), True, Decimal('0.666667'), True, 6, 'ROUND_DOWN')");
+        assert_eq!(eval(&mut python, "localcontext(prec=0)"), "ValueError: valid range for prec is [1, MAX_PREC]");
+        assert_eq!(eval(&mut python, "localcontext(precision=3)"), "TypeError: localcontext() got an unexpected keyword argument 'precision'");
+    }
+
+    #[test]
+    fn test_signals() {
+        let mut python = python(IMPORTS);
+        assert_eq!(eval(&mut python, "D(1) / 0"), "decimal.DivisionByZero: [<class 'decimal.DivisionByZero'>]");
+        assert_eq!(eval(&mut python, "D('abc')"), "decimal.InvalidOperation: [<class 'decimal.ConversionSyntax'>]");
+        assert_eq!(eval(&mut python, "D(0) / 0"), "decimal.InvalidOperation: [<class 'decimal.DivisionUndefined'>]");
+        assert_eq!(eval(&mut python, "D('1e999999999') * 10"), "decimal.Overflow: [<class 'decimal.Overflow'>]");
+        assert_eq!(eval(&mut python, "D('NaN') < 1"), "decimal.InvalidOperation: [<class 'decimal.InvalidOperation'>]");
+        assert_eq!(eval(&mut python, "D(5).quantize(D('1e-30'))"), "decimal.InvalidOperation: [<class 'decimal.InvalidOperation'>]");
+        assert_eq!(eval(&mut python, "D(1) + 1.5"), "TypeError: unsupported operand type(s) for +: 'decimal.Decimal' and 'float'");
+        assert_eq!(eval(&mut python, "Context(prec=0)"), "ValueError: valid range for prec is [1, MAX_PREC]");
+        assert_eq!(eval(&mut python, "Context(rounding='UP')"), "TypeError: valid values for rounding are: [ROUND_CEILING, ROUND_FLOOR, ROUND_UP, ROUND_DOWN, ROUND_HALF_UP, ROUND_HALF_DOWN, ROUND_HALF_EVEN, ROUND_05UP]");
+    }
+
+    #[test]
+    fn test_class_names() {
+        // the module is in __module__ and the repr, not in __name__
+        let mut python = python(IMPORTS);
+        assert_eq!(eval(&mut python, "(type(D(1)).__name__, type(D(1)).__module__, type(D(1)))"), "('Decimal', 'decimal', <class 'decimal.Decimal'>)");
+        assert_eq!(eval(&mut python, "(DivisionByZero.__name__, DivisionByZero.__module__, Context.__name__)"), "('DivisionByZero', 'decimal', 'Context')");
+        assert_eq!(eval(&mut python, "(F.__name__, F.__module__, int.__module__)"), "('Fraction', 'fractions', 'builtins')");
+    }
+
+    #[test]
+    fn test_context_methods() {
+        let mut python = python(IMPORTS);
+        exec(&mut python, "ctx = Context(prec=3, rounding=ROUND_HALF_UP, traps=[])");
+        assert_eq!(eval(&mut python, "(ctx.divide(D(1), D(0)), ctx.flags[DivisionByZero], ctx.prec, ctx.rounding, ctx.add(D('1.005'), 0), ctx.flags[Inexact])"), "(Decimal('Infinity'), True, 3, 'ROUND_HALF_UP', Decimal('1.01'), True)");
+        exec(&mut python, "ctx.clear_flags()");
+        assert_eq!(eval(&mut python, "(ctx.flags[Inexact], ctx.traps[Overflow], getcontext().traps[Overflow], getcontext().traps[Inexact])"), "(False, False, True, False)");
+        assert_eq!(eval(&mut python, "(ctx.create_decimal('3.14159'), ctx.Etiny(), ctx.Etop(), ExtendedContext.divide(D(1), D(0)))"), "(Decimal('3.14'), -1000001, 999997, Decimal('Infinity'))");
+    }
+}
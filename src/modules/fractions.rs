@@ -0,0 +1,530 @@
+use crate::ast::BinOp;
+use crate::interpreter::{self, as_bigint, as_complex, EvalResult, Interpreter};
+use crate::modules::decimal;
+use crate::value::{new_tuple, Args, Builtin, BuiltinFn, Class, Exception, Module, Value};
+use num_bigint::BigInt;
+use num_integer::Integer;
+use num_rational::BigRational;
+use num_traits::{One, Pow, Signed, ToPrimitive, Zero};
+use std::cmp::Ordering;
+use std::convert::TryFrom;
+use std::rc::Rc;
+
+/* fractions - rational numbers, like CPython's fractions module
+ *
+ * A Fraction is exact, with numerator and denominator of any size, and
+ * mixes with the other numbers the way CPython's does: with ints the
+ * result is a Fraction, with floats a float and with complex numbers a
+ * complex number. A fractional power gives a float (or a complex number,
+ * for a negative base). Fractions and Decimals can be compared, but not
+ * added and the like.
+ */
+
+pub fn build_class(object: Rc<Class>) -> Rc<Class> {
+    let class = Class::build_new("Fraction", vec![object], Some(fraction_new));
+    let methods: &[(&str, BuiltinFn)] = &[
+        ("as_integer_ratio", as_integer_ratio),
+        ("conjugate", conjugate),
+        ("from_decimal", from_decimal),
+        ("from_float", from_float),
+        ("limit_denominator", limit_denominator),
+    ];
+    for (name, method) in methods {
+        class.attrs.borrow_mut().insert(String::from(*name), Builtin::build_new(name, *method));
+    }
+    return class;
+}
+
+pub fn build_module(class: Rc<Class>) -> Rc<Module> {
+    let module = Module::build_new("fractions", None);
+    module.set_class(class);
+    return Rc::new(module);
+}
+
+pub fn new_fraction(val: BigRational) -> Value {
+    return Value::Fraction(Rc::new(val));
+}
+
+/* an int result
+ */
+pub(crate) fn int_value(val: &BigInt) -> EvalResult {
+    return Ok(interpreter::new_int(val.clone()));
+}
+
+/* the exact value of an int or Fraction
+ */
+pub fn as_rational(value: &Value) -> Option<BigRational> {
+    match value {
+        Value::Fraction(val) => return Some((**val).clone()),
+        _ => return as_bigint(value).map(BigRational::from_integer),
+    }
+}
+
+/* the numerator and denominator of a finite float, in lowest terms
+ */
+pub fn float_ratio(val: f64) -> (BigInt, BigInt) {
+    let ratio = BigRational::from_float(val).unwrap();
+    return (ratio.numer().clone(), ratio.denom().clone());
+}
+
+/* exact Fraction of a float, or the error as_integer_ratio() raises
+ */
+fn from_f64(val: f64) -> Result<BigRational, Exception> {
+    if val.is_nan() {
+        return Err(Exception::new("ValueError", "cannot convert NaN to integer ratio"));
+    }
+    if val.is_infinite() {
+        return Err(Exception::new("OverflowError", "cannot convert Infinity to integer ratio"));
+    }
+    return Ok(BigRational::from_float(val).unwrap());
+}
+
+/* 2**exp, for exponents a float can represent exactly
+ */
+fn pow2(exp: i64) -> f64 {
+    if exp >= -1022 {
+        return f64::from_bits(((exp + 1023) as u64) << 52);
+    }
+    return f64::from_bits(1 << (exp + 1074));
+}
+
+/* numer / denom as the nearest float, halves to even, like int / int is
+ * in CPython
+ */
+pub fn ratio_to_f64(numer: &BigInt, denom: &BigInt) -> f64 {
+    if numer.is_zero() {
+        return 0.0;
+    }
+    let negative = numer.is_negative() != denom.is_negative();
+    let (numer, denom) = (numer.abs(), denom.abs());
+    // a quotient of 55 or 56 bits, and whether anything was left over
+    let shift = numer.bits() as i64 - denom.bits() as i64 - 55;
+    let (mut quotient, remainder) = if shift >= 0 {
+        numer.div_rem(&(denom << shift as usize))
+    } else {
+        (numer << -shift as usize).div_rem(&denom)
+    };
+    let bits = quotient.bits() as i64;
+    // the exponent of the quotient's leading bit
+    let exp = bits + shift - 1;
+    if exp > 1023 {
+        return if negative { f64::NEG_INFINITY } else { f64::INFINITY };
+    }
+    // bits to drop: all but 53, or down to 2**-1074 for a subnormal
+    let drop = if exp >= -1022 { bits - 53 } else { -1074 - shift };
+    if drop > bits {
+        return if negative { -0.0 } else { 0.0 };
+    }
+    let unit = BigInt::one() << drop as usize;
+    let low = &quotient % &unit;
+    let half = &unit >> 1usize;
+    quotient >>= drop as usize;
+    if low > half || low == half && (!remainder.is_zero() || quotient.is_odd()) {
+        quotient += 1;
+    }
+    let result = quotient.to_f64().unwrap() * pow2(shift + drop);
+    return if negative { -result } else { result };
+}
+
+pub fn to_f64(val: &BigRational) -> f64 {
+    return ratio_to_f64(val.numer(), val.denom());
+}
+
+/* Fraction's string syntax: an optional sign, then an int, "n/d", or a
+ * number with a point and/or exponent, with whitespace around it
+ */
+fn parse(text: &str) -> Option<BigRational> {
+    let trimmed = text.trim();
+    let (negative, body) = match trimmed.as_bytes().first() {
+        Some(b'-') => (true, &trimmed[1..]),
+        Some(b'+') => (false, &trimmed[1..]),
+        _ => (false, trimmed),
+    };
+    // digits with single underscores between them
+    let digits = |part: &str| -> Option<BigInt> {
+        if part.is_empty() || part.starts_with('_') || part.ends_with('_') || part.contains("__") {
+            return None;
+        }
+        if !part.bytes().all(|c| c.is_ascii_digit() || c == b'_') {
+            return None;
+        }
+        return part.replace('_', "").parse().ok();
+    };
+    let (mut numer, mut denom) = if let Some((numer, denom)) = body.split_once('/') {
+        (digits(numer)?, digits(denom)?)
+    } else {
+        let lower = body.to_ascii_lowercase();
+        let (mantissa, exp) = match lower.split_once('e') {
+            Some((mantissa, exp)) => (String::from(mantissa), Some(String::from(exp))),
+            None => (lower, None),
+        };
+        let (int_part, frac_part) = match mantissa.split_once('.') {
+            Some((int_part, frac_part)) => (int_part, frac_part),
+            None => (mantissa.as_str(), ""),
+        };
+        // a digit must come first, before or after the point
+        if int_part.is_empty() && frac_part.is_empty() {
+            return None;
+        }
+        let mut numer = if int_part.is_empty() { BigInt::zero() } else { digits(int_part)? };
+        let mut denom = BigInt::one();
+        if !frac_part.is_empty() {
+            let scale = BigInt::from(10u32).pow(frac_part.replace('_', "").len() as u32);
+            numer = numer * &scale + digits(frac_part)?;
+            denom = scale;
+        }
+        if let Some(exp) = exp {
+            let exp_digits = exp.strip_prefix(['+', '-']).unwrap_or(&exp);
+            let power = u32::try_from(digits(exp_digits)?).ok()?;
+            let scale = BigInt::from(10u32).pow(power);
+            if exp.starts_with('-') {
+                denom *= scale;
+            } else {
+                numer *= scale;
+            }
+        }
+        (numer, denom)
+    };
+    if negative {
+        numer = -numer;
+    }
+    if denom.is_zero() {
+        denom = BigInt::zero();
+    }
+    return Some(BigRational::new_raw(numer, denom));
+}
+
+/* Fraction(numerator, denominator), checking for a zero denominator
+ */
+fn checked_new(numer: BigInt, denom: BigInt) -> Result<BigRational, Exception> {
+    if denom.is_zero() {
+        return Err(Exception::new("ZeroDivisionError", &format!("Fraction({}, 0)", numer)));
+    }
+    return Ok(BigRational::new(numer, denom));
+}
+
+fn fraction_new(_interpreter: &mut Interpreter, mut args: Args) -> EvalResult {
+    let numerator = args.keyword("numerator");
+    let denominator = args.keyword("denominator");
+    args.check("Fraction", 0, 2)?;
+    let numerator = args.positional.first().cloned().or(numerator).unwrap_or(Value::Int(0));
+    let denominator = args.positional.get(1).cloned().or(denominator).filter(|value| *value != Value::NoneT);
+    if let Some(denominator) = denominator {
+        return match (as_rational(&numerator), as_rational(&denominator)) {
+            (Some(numerator), Some(denominator)) => {
+                let numer = numerator.numer() * denominator.denom();
+                let denom = denominator.numer() * numerator.denom();
+                Ok(new_fraction(checked_new(numer, denom)?))
+            }
+            _ => Err(Exception::new("TypeError", "both arguments should be Rational instances")),
+        };
+    }
+    if let Some(val) = as_rational(&numerator) {
+        return Ok(new_fraction(val));
+    }
+    match &numerator {
+        Value::Float(val) => return Ok(new_fraction(from_f64(*val)?)),
+        Value::Decimal(val) => return Ok(new_fraction(decimal::to_rational(val)?)),
+        Value::Str(text) => match parse(text) {
+            Some(val) => return Ok(new_fraction(checked_new(val.numer().clone(), val.denom().clone())?)),
+            None => {
+                let message = format!("Invalid literal for Fraction: {}", numerator.repr());
+                return Err(Exception::new("ValueError", &message));
+            }
+        },
+        _ => return Err(Exception::new("TypeError", "argument should be a string or a Rational instance")),
+    }
+}
+
+/* the Fraction a method was called on
+ */
+fn this<'a>(name: &str, args: &'a Args) -> Result<&'a BigRational, Exception> {
+    match args.positional.first() {
+        Some(Value::Fraction(val)) => return Ok(val),
+        other => {
+            let given = other.map_or("nothing", |value| value.type_name());
+            let message = format!("descriptor '{}' for 'Fraction' objects doesn't apply to a '{}' object", name, given);
+            return Err(Exception::new("TypeError", &message));
+        }
+    }
+}
+
+fn as_integer_ratio(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("as_integer_ratio", 1, 1)?;
+    let val = this("as_integer_ratio", &args)?;
+    return Ok(new_tuple(vec![int_value(val.numer())?, int_value(val.denom())?]));
+}
+
+fn conjugate(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("conjugate", 1, 1)?;
+    return Ok(new_fraction(this("conjugate", &args)?.clone()));
+}
+
+/* Fraction.from_float(f)
+ */
+fn from_float(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("from_float", 1, 1)?;
+    let value = &args.positional[0];
+    match value {
+        Value::Float(val) => return Ok(new_fraction(from_f64(*val)?)),
+        _ if as_bigint(value).is_some() => return Ok(new_fraction(as_rational(value).unwrap())),
+        _ => {
+            let message = format!(
+                "Fraction.from_float() only takes floats, not {} ({})",
+                value.repr(),
+                value.type_name()
+            );
+            return Err(Exception::new("TypeError", &message));
+        }
+    }
+}
+
+/* Fraction.from_decimal(dec)
+ */
+fn from_decimal(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("from_decimal", 1, 1)?;
+    let value = &args.positional[0];
+    match value {
+        Value::Decimal(val) => return Ok(new_fraction(decimal::to_rational(val)?)),
+        _ if as_bigint(value).is_some() => return Ok(new_fraction(as_rational(value).unwrap())),
+        _ => {
+            let message = format!(
+                "Fraction.from_decimal() only takes Decimals, not {} ({})",
+                value.repr(),
+                value.type_name()
+            );
+            return Err(Exception::new("TypeError", &message));
+        }
+    }
+}
+
+/* The closest Fraction with a denominator at most max_denominator, from
+ * the continued fraction expansion, like CPython
+ */
+fn limit_denominator(_interpreter: &mut Interpreter, mut args: Args) -> EvalResult {
+    let max_denominator = args.keyword("max_denominator");
+    args.check("limit_denominator", 1, 2)?;
+    let val = this("limit_denominator", &args)?.clone();
+    let max_denominator = match args.positional.get(1).cloned().or(max_denominator) {
+        Some(value) => match as_bigint(&value) {
+            Some(max) => max,
+            None => {
+                let message = format!("'{}' object cannot be interpreted as an integer", value.type_name());
+                return Err(Exception::new("TypeError", &message));
+            }
+        },
+        None => BigInt::from(1_000_000),
+    };
+    if max_denominator < BigInt::one() {
+        return Err(Exception::new("ValueError", "max_denominator should be at least 1"));
+    }
+    if *val.denom() <= max_denominator {
+        return Ok(new_fraction(val));
+    }
+    let (mut p0, mut q0, mut p1, mut q1) = (BigInt::zero(), BigInt::one(), BigInt::one(), BigInt::zero());
+    let (mut n, mut d) = (val.numer().clone(), val.denom().clone());
+    loop {
+        let a = n.div_floor(&d);
+        let q2 = &q0 + &a * &q1;
+        if q2 > max_denominator {
+            break;
+        }
+        let p2 = &p0 + &a * &p1;
+        (p0, q0, p1, q1) = (p1, q1, p2, q2);
+        let rest = &n - &a * &d;
+        (n, d) = (d, rest);
+    }
+    let k = (&max_denominator - &q0).div_floor(&q1);
+    let bound1 = BigRational::new(&p0 + &k * &p1, &q0 + &k * &q1);
+    let bound2 = BigRational::new(p1, q1);
+    if (&bound2 - &val).abs() <= (&bound1 - &val).abs() {
+        return Ok(new_fraction(bound2));
+    }
+    return Ok(new_fraction(bound1));
+}
+
+/* val ** exp for an integer exponent; None if the exponent is too large
+ * to work out
+ */
+fn pow_int(val: &BigRational, exp: &BigInt) -> Option<Result<BigRational, Exception>> {
+    let power = exp.abs().to_u32()?;
+    let (numer, denom) = (val.numer().pow(power), val.denom().pow(power));
+    if !exp.is_negative() {
+        return Some(Ok(BigRational::new_raw(numer, denom)));
+    }
+    if numer.is_zero() {
+        return Some(Err(Exception::new("ZeroDivisionError", &format!("Fraction({}, 0)", denom))));
+    }
+    return Some(Ok(BigRational::new(denom, numer)));
+}
+
+fn rational_op(op: BinOp, left: &BigRational, right: &BigRational) -> EvalResult {
+    match op {
+        BinOp::Add => return Ok(new_fraction(left + right)),
+        BinOp::Sub => return Ok(new_fraction(left - right)),
+        BinOp::Mul => return Ok(new_fraction(left * right)),
+        BinOp::Div if right.is_zero() => {
+            // CPython divides out common factors first, which leaves the
+            // numerator's sign
+            let message = format!("Fraction({}, 0)", left.numer().signum());
+            return Err(Exception::new("ZeroDivisionError", &message));
+        }
+        BinOp::Div => return Ok(new_fraction(left / right)),
+        BinOp::FloorDiv if right.is_zero() => {
+            return Err(Exception::new("ZeroDivisionError", "integer division or modulo by zero"));
+        }
+        BinOp::FloorDiv => {
+            let quotient = (left.numer() * right.denom()).div_floor(&(left.denom() * right.numer()));
+            return int_value(&quotient);
+        }
+        BinOp::Mod if right.is_zero() => return Err(Exception::new("ZeroDivisionError", "integer modulo by zero")),
+        BinOp::Mod => {
+            let (left_denom, right_denom) = (left.denom(), right.denom());
+            let numer = (left.numer() * right_denom).mod_floor(&(right.numer() * left_denom));
+            return Ok(new_fraction(BigRational::new(numer, left_denom * right_denom)));
+        }
+        BinOp::Pow if right.is_integer() => match pow_int(left, right.numer()) {
+            Some(result) => return Ok(new_fraction(result?)),
+            None => return Err(Exception::new("OverflowError", "exponent too large")),
+        },
+        // a fractional power is usually irrational
+        BinOp::Pow => return interpreter::binary_op(op, &Value::Float(to_f64(left)), &Value::Float(to_f64(right))),
+        // binary_op leaves these to the other operand
+        BinOp::BitAnd | BinOp::BitOr | BinOp::BitXor | BinOp::LShift | BinOp::RShift => unreachable!(),
+    }
+}
+
+/* Arithmetic with a Fraction on either side; None if the other side isn't
+ * a number a Fraction mixes with
+ */
+pub fn binary_op(op: BinOp, left: &Value, right: &Value) -> Option<EvalResult> {
+    if op.is_bitwise() {
+        return None;
+    }
+    if let (Some(a), Some(b)) = (as_rational(left), as_rational(right)) {
+        // int ** Fraction stays an int if it can
+        if op == BinOp::Pow && as_bigint(left).is_some() && b.is_integer() && !b.is_negative() {
+            return Some(interpreter::binary_op(op, left, &int_value(b.numer()).ok()?));
+        }
+        return Some(rational_op(op, &a, &b));
+    }
+    // the Fraction becomes a float, and the other side decides
+    let float = |value: &Value| match value {
+        Value::Fraction(val) => Some(Value::Float(to_f64(val))),
+        Value::Float(_) | Value::Complex(..) => Some(value.clone()),
+        _ => None,
+    };
+    let (left, right) = (float(left)?, float(right)?);
+    return Some(interpreter::binary_op(op, &left, &right));
+}
+
+/* How a Fraction compares with a number: None if it doesn't, and
+ * Some(None) if it's unordered, against NaN
+ */
+pub fn partial_cmp(left: &Value, right: &Value) -> Option<Option<Ordering>> {
+    let exact = |value: &Value| match value {
+        Value::Float(val) if !val.is_finite() => None,
+        Value::Float(val) => Some(BigRational::from_float(*val).unwrap()),
+        Value::Decimal(val) => decimal::to_rational(val).ok(),
+        _ => as_rational(value),
+    };
+    // an infinity or NaN compares the same with any finite number
+    let finite = |value: &Value| match value {
+        Value::Float(val) if !val.is_finite() => Some(*val),
+        Value::Decimal(val) if val.is_special() => Some(val.to_f64()),
+        _ => None,
+    };
+    match (finite(left), finite(right)) {
+        (Some(left), _) => return Some(left.partial_cmp(&0.0)),
+        (_, Some(right)) => return Some(0.0.partial_cmp(&right)),
+        _ => return Some(exact(left)?.partial_cmp(&exact(right)?)),
+    }
+}
+
+/* == with a Fraction on either side; None if the other side isn't a
+ * number
+ */
+pub fn equals(left: &Value, right: &Value) -> Option<bool> {
+    // a complex number is compared by its real part, if it has no other
+    let real = |value: &Value| match value {
+        Value::Complex(real, 0.0) => Some(Value::Float(*real)),
+        Value::Complex(..) => None,
+        _ => Some(value.clone()),
+    };
+    let (left, right) = match (real(left), real(right)) {
+        (Some(left), Some(right)) => (left, right),
+        _ => return as_complex(left).or(as_complex(right)).map(|_| false),
+    };
+    return Some(partial_cmp(&left, &right)? == Some(Ordering::Equal));
+}
+
+/* round(): to an int, or to a Fraction with ndigits places, halves to even
+ */
+pub fn round(val: &BigRational, ndigits: Option<i64>) -> EvalResult {
+    let round_int = |val: &BigRational| {
+        let (floor, remainder) = val.numer().div_mod_floor(val.denom());
+        let doubled = remainder * 2u32;
+        let up = match doubled.cmp(val.denom()) {
+            Ordering::Less => false,
+            Ordering::Greater => true,
+            Ordering::Equal => floor.is_odd(),
+        };
+        return if up { floor + 1 } else { floor };
+    };
+    let ndigits = match ndigits {
+        Some(ndigits) => ndigits,
+        None => return int_value(&round_int(val)),
+    };
+    let power = u32::try_from(ndigits.unsigned_abs()).map_err(|_| Exception::new("OverflowError", "integer overflow"))?;
+    let shift = BigRational::from_integer(BigInt::from(10u32).pow(power));
+    if ndigits > 0 {
+        return Ok(new_fraction(BigRational::from_integer(round_int(&(val * &shift))) / shift));
+    }
+    return Ok(new_fraction(BigRational::from_integer(round_int(&(val / &shift))) * shift));
+}
+
+/* repr(): Fraction(1, 3)
+ */
+pub fn repr(val: &BigRational) -> String {
+    return format!("Fraction({}, {})", val.numer(), val.denom());
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testing::{eval, python};
+
+    #[test]
+    fn test_construction() {
+        let mut python = python("from fractions import Fraction as F");
+        assert_eq!(eval(&mut python, "(F(6, -4), F('3.25'), F(' -1_0/4 '), F('1e-2'), F(0.5), F())"), "(Fraction(-3, 2), Fraction(13, 4), Fraction(-5, 2), Fraction(1, 100), Fraction(1, 2), Fraction(0, 1))");
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        let mut python = python("from fractions import Fraction as F");
+        assert_eq!(eval(&mut python, "(F(1, 3) + F(1, 6), F(1, 2) * 3, 1 - F(1, 3), F(3, 4) / F(1, 4))"), "(Fraction(1, 2), Fraction(3, 2), Fraction(2, 3), Fraction(3, 1))");
+        assert_eq!(eval(&mut python, "(F(7, 2) // F(1, 3), F(7, 2) % F(1, 3), divmod(F(-7, 2), 2))"), "(10, Fraction(1, 6), (-2, Fraction(1, 2)))");
+        assert_eq!(eval(&mut python, "(F(2, 3) ** 2, F(2, 3) ** -2, 2 ** F(2), F(1, 4) ** F(1, 2), F(1, 2) + 0.25, F(1, 2) * 2j)"), "(Fraction(4, 9), Fraction(9, 4), 4, 0.5, 0.75, 1j)");
+        assert_eq!(eval(&mut python, "(-8) ** F(1, 3)"), "(1.0000000000000002+1.7320508075688772j)");
+    }
+
+    #[test]
+    fn test_conversions() {
+        let mut python = python("from fractions import Fraction as F");
+        assert_eq!(eval(&mut python, "(str(F(1, 3)), str(F(4, 2)), float(F(1, 3)), int(F(-7, 2)), round(F(5, 2)), round(F(7, 3), 2))"), "('1/3', '2', 0.3333333333333333, -3, 2, Fraction(233, 100))");
+        assert_eq!(eval(&mut python, "(F(1, 2) == 0.5, F(1, 3) < 0.34, F(1, 2) == 0.5 + 0j, F(1, 3) > float('-inf'), F(1) == 1)"), "(True, True, True, True, True)");
+        assert_eq!(eval(&mut python, "({F(1, 2): 'a'}[0.5], hash(F(3)) == hash(3), F(1, 3).numerator, F(1, 3).denominator)"), "('a', True, 1, 3)");
+        assert_eq!(eval(&mut python, "(F(3.14159).limit_denominator(1000), F(1, 3).as_integer_ratio(), F.from_float(0.25))"), "(Fraction(355, 113), (1, 3), Fraction(1, 4))");
+    }
+
+    #[test]
+    fn test_errors() {
+        let mut python = python("from fractions import Fraction as F");
+        assert_eq!(eval(&mut python, "F(3, 2) / 0"), "ZeroDivisionError: Fraction(1, 0)");
+        assert_eq!(eval(&mut python, "F(1, 0)"), "ZeroDivisionError: Fraction(1, 0)");
+        assert_eq!(eval(&mut python, "F('3 /4')"), "ValueError: Invalid literal for Fraction: '3 /4'");
+        assert_eq!(eval(&mut python, "F(1, 2.0)"), "TypeError: both arguments should be Rational instances");
+        assert_eq!(eval(&mut python, "F(float('inf'))"), "OverflowError: cannot convert Infinity to integer ratio");
+        assert_eq!(eval(&mut python, "F(1, 2) + 'a'"), "TypeError: unsupported operand type(s) for +: 'Fraction' and 'str'");
+    }
+}
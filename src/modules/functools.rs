@@ -0,0 +1,645 @@
+use crate::ast::FunctionDef;
+use crate::dict::{new_dict, Dict, HashKey};
+use crate::gc::{self, Tracked};
+use crate::interpreter::{get_attribute, EvalResult, Interpreter};
+use crate::modules::builtins::is_callable;
+use crate::modules::collections::{namedtuple_class, Layout};
+use crate::native::{bind, instance, with_native, NativeClass};
+use crate::value::{new_tuple, Args, Builtin, BuiltinFn, Class, Exception, Function, Module, NativeFn, Object, Property, Value};
+use std::collections::{BTreeMap, HashMap};
+use std::mem::take;
+use std::rc::Rc;
+
+/* functools - higher-order functions
+ *
+ * lru_cache, cache, wraps, cached_property, total_ordering and
+ * singledispatch work as decorators, or can be called directly:
+ * f = lru_cache(maxsize=32)(f).
+ */
+pub fn build_module(object: Rc<Class>) -> Rc<Module> {
+    let module = Module::build_new("functools", None);
+    let functions: &[(&str, BuiltinFn)] = &[
+        ("reduce", reduce),
+        ("cached_property", cached_property),
+        ("total_ordering", total_ordering),
+    ];
+    for (name, func) in functions {
+        module.set(name, Builtin::build_new(name, *func));
+    }
+    let partial = partial_class().build(object.clone());
+    module.set("partial", Value::Class(partial.clone()));
+    let update_wrapper = Builtin::build_new("update_wrapper", update_wrapper);
+    module.set("update_wrapper", update_wrapper.clone());
+    // wraps(wrapped) is partial(update_wrapper, wrapped=wrapped), as in CPython
+    let wraps: NativeFn = Rc::new(move |_, args| {
+        let bound = bind("wraps", args, &["wrapped", "assigned", "updated"], 1)?;
+        let wraps = Partial {
+            func: update_wrapper.clone(),
+            args: Vec::new(),
+            keywords: vec![(String::from("wrapped"), bound[0].clone().unwrap())],
+        };
+        return Ok(Value::Object(Object::build_new(partial.clone(), Some(Box::new(wraps)))));
+    });
+    module.set("wraps", Builtin::build_native("wraps", wraps));
+
+    let layout = Layout {
+        name: String::from("CacheInfo"),
+        fields: ["hits", "misses", "maxsize", "currsize"].iter().map(|field| String::from(*field)).collect(),
+        defaults: Vec::new(),
+    };
+    let cache = cache_class(namedtuple_class(layout, object.clone())).build(object.clone());
+    let lru_class = cache.clone();
+    let lru: NativeFn = Rc::new(move |_, args| lru_cache(&lru_class, args));
+    module.set("lru_cache", Builtin::build_native("lru_cache", lru));
+    let unbounded: NativeFn = Rc::new(move |_, args| {
+        args.check("cache", 1, 1)?;
+        return Ok(new_cache(&cache, args.positional[0].clone(), None, false));
+    });
+    module.set("cache", Builtin::build_native("cache", unbounded));
+
+    let dispatch = dispatch_class().build(object);
+    let singledispatch: NativeFn = Rc::new(move |_, args| {
+        args.check("singledispatch", 1, 1)?;
+        let func = args.positional[0].clone();
+        let name = match get_attribute(&func, "__name__") {
+            Ok(Value::Str(ref name)) => name.clone(),
+            _ => String::from("singledispatch function"),
+        };
+        let dispatcher = Dispatch {
+            name,
+            registry: vec![(dispatch.bases[0].clone(), func)],
+        };
+        return Ok(Value::Object(Object::build_new(dispatch.clone(), Some(Box::new(dispatcher)))));
+    });
+    module.set("singledispatch", Builtin::build_native("singledispatch", singledispatch));
+    return Rc::new(module);
+}
+
+/* reduce(function, iterable[, initial]): function applied cumulatively to
+ * the items, left to right
+ */
+fn reduce(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("reduce", 2, 3)?;
+    let iterator = match interpreter.get_iter(&args.positional[1]) {
+        Err(err) if err.kind == "TypeError" => {
+            return Err(Exception::new("TypeError", "reduce() arg 2 must support iteration"));
+        }
+        result => result?,
+    };
+    let mut total = args.positional.get(2).cloned();
+    while let Some(item) = interpreter.next_item(&iterator)? {
+        total = Some(match total {
+            Some(total) => interpreter.call(&args.positional[0], Args::build_new(vec![total, item]))?,
+            None => item,
+        });
+    }
+    return total.ok_or_else(|| Exception::new("TypeError", "reduce() of empty iterable with no initial value"));
+}
+
+/* partial(func, *args, **keywords): func with some arguments given ahead
+ * of the rest
+ */
+struct Partial {
+    func: Value,
+    args: Vec<Value>,
+    keywords: Vec<(String, Value)>,
+}
+
+const PARTIAL: &str = "functools.partial";
+
+fn merge_keywords(keywords: &mut Vec<(String, Value)>, given: Vec<(String, Value)>) {
+    for (name, value) in given {
+        match keywords.iter_mut().find(|(key, _)| *key == name) {
+            Some(keyword) => keyword.1 = value,
+            None => keywords.push((name, value)),
+        };
+    }
+}
+
+fn partial_class() -> NativeClass<Partial> {
+    let mut partial: NativeClass<Partial> = NativeClass::build_new(PARTIAL);
+    partial.raw_new(|_, class, mut args| {
+        if args.positional.is_empty() {
+            return Err(Exception::new("TypeError", "type 'partial' takes at least one argument"));
+        }
+        let func = args.positional.remove(0);
+        if !is_callable(&func) {
+            return Err(Exception::new("TypeError", "the first argument must be callable"));
+        }
+        // a partial of a partial is flattened
+        let inner = with_native(&func, |inner: &Partial| (inner.func.clone(), inner.args.clone(), inner.keywords.clone()));
+        let (func, mut positional, mut keywords) = inner.unwrap_or((func, Vec::new(), Vec::new()));
+        positional.extend(args.positional);
+        merge_keywords(&mut keywords, args.keywords);
+        let partial = Partial {
+            func,
+            args: positional,
+            keywords,
+        };
+        return Ok(Value::Object(Object::build_new(class, Some(Box::new(partial)))));
+    });
+    partial.raw_method("__call__", |interpreter, mut args| {
+        let (func, mut positional, mut keywords) = {
+            let this = instance::<Partial>(PARTIAL, "__call__", &args)?;
+            (this.func.clone(), this.args.clone(), this.keywords.clone())
+        };
+        positional.extend(args.positional.drain(1..));
+        merge_keywords(&mut keywords, take(&mut args.keywords));
+        return interpreter.call(&func, Args { positional, keywords });
+    });
+    partial.getter("func", |this: &Partial| this.func.clone());
+    partial.getter("args", |this: &Partial| new_tuple(this.args.clone()));
+    partial.getter("keywords", |this: &Partial| {
+        let mut keywords = Dict::build_new();
+        for (name, value) in &this.keywords {
+            keywords.set(Value::Str(name.clone()), value.clone()).unwrap();
+        }
+        return new_dict(keywords);
+    });
+    partial.repr(|this: &Partial| {
+        let mut parts = vec![this.func.repr()];
+        parts.extend(this.args.iter().map(|arg| arg.repr()));
+        parts.extend(this.keywords.iter().map(|(name, value)| format!("{}={}", name, value.repr())));
+        return format!("{}({})", PARTIAL, parts.join(", "));
+    });
+    return partial;
+}
+
+/* The function lru_cache() returns: func with the results of the last
+ * maxsize distinct calls remembered. entries maps a call's arguments to
+ * the result and when it was last used; order is entries by that.
+ */
+struct Cache {
+    func: Value,
+    maxsize: Option<usize>,
+    typed: bool,
+    entries: HashMap<HashKey, (Value, u64)>,
+    order: BTreeMap<u64, HashKey>,
+    clock: u64,
+    hits: i64,
+    misses: i64,
+}
+
+const CACHE: &str = "functools._lru_cache_wrapper";
+
+impl Cache {
+    /* what a call is remembered by; the arguments must be hashable
+     */
+    fn key(&self, args: &Args) -> Result<HashKey, Exception> {
+        let mut key: Vec<HashKey> = Vec::new();
+        for arg in &args.positional {
+            key.push(HashKey::from_value(arg)?);
+        }
+        if !args.keywords.is_empty() {
+            // no argument hashes to this, to mark where the keywords start
+            key.push(HashKey::Ptr(0));
+            for (name, value) in &args.keywords {
+                key.push(HashKey::Str(name.clone()));
+                key.push(HashKey::from_value(value)?);
+            }
+        }
+        if self.typed {
+            let values = args.positional.iter().chain(args.keywords.iter().map(|(_, value)| value));
+            key.extend(values.map(|value| HashKey::Str(String::from(value.type_name()))));
+        }
+        return Ok(HashKey::Tuple(key));
+    }
+
+    fn lookup(&mut self, key: &HashKey) -> Option<Value> {
+        self.clock += 1;
+        let clock = self.clock;
+        match self.entries.get_mut(key) {
+            Some((value, used)) => {
+                self.hits += 1;
+                self.order.remove(used);
+                self.order.insert(clock, key.clone());
+                *used = clock;
+                return Some(value.clone());
+            }
+            None => {
+                self.misses += 1;
+                return None;
+            }
+        }
+    }
+
+    fn store(&mut self, key: HashKey, value: Value) {
+        if self.maxsize == Some(0) {
+            return;
+        }
+        self.clock += 1;
+        if let Some((_, used)) = self.entries.insert(key.clone(), (value, self.clock)) {
+            self.order.remove(&used);
+        }
+        self.order.insert(self.clock, key);
+        if self.maxsize.is_some_and(|maxsize| self.entries.len() > maxsize) {
+            if let Some((_, oldest)) = self.order.pop_first() {
+                self.entries.remove(&oldest);
+            }
+        }
+    }
+}
+
+fn cache_class(cache_info: Rc<Class>) -> NativeClass<Cache> {
+    let mut cache: NativeClass<Cache> = NativeClass::build_new(CACHE);
+    cache.raw_method("__call__", |interpreter, mut args| {
+        let call = Args {
+            positional: args.positional.split_off(1),
+            keywords: take(&mut args.keywords),
+        };
+        let (func, key) = {
+            let mut this = instance::<Cache>(CACHE, "__call__", &args)?;
+            let key = this.key(&call)?;
+            if let Some(value) = this.lookup(&key) {
+                return Ok(value);
+            }
+            (this.func.clone(), key)
+        };
+        let value = interpreter.call(&func, call)?;
+        instance::<Cache>(CACHE, "__call__", &args)?.store(key, value.clone());
+        return Ok(value);
+    });
+    cache.raw_method("cache_info", move |interpreter, args| {
+        args.check("cache_info", 1, 1)?;
+        let info = {
+            let this = instance::<Cache>(CACHE, "cache_info", &args)?;
+            let maxsize = this.maxsize.map_or(Value::NoneT, |maxsize| Value::Int(maxsize as i64));
+            vec![Value::Int(this.hits), Value::Int(this.misses), maxsize, Value::Int(this.entries.len() as i64)]
+        };
+        return interpreter.call(&Value::Class(cache_info.clone()), Args::build_new(info));
+    });
+    cache.raw_method("cache_clear", |_, args| {
+        args.check("cache_clear", 1, 1)?;
+        let mut this = instance::<Cache>(CACHE, "cache_clear", &args)?;
+        this.entries.clear();
+        this.order.clear();
+        (this.hits, this.misses) = (0, 0);
+        return Ok(Value::NoneT);
+    });
+    cache.raw_method("cache_parameters", |_, args| {
+        args.check("cache_parameters", 1, 1)?;
+        let this = instance::<Cache>(CACHE, "cache_parameters", &args)?;
+        let mut parameters = Dict::build_new();
+        let maxsize = this.maxsize.map_or(Value::NoneT, |maxsize| Value::Int(maxsize as i64));
+        parameters.set(Value::Str(String::from("maxsize")), maxsize)?;
+        parameters.set(Value::Str(String::from("typed")), Value::Bool(this.typed))?;
+        return Ok(new_dict(parameters));
+    });
+    cache.getter("__wrapped__", |this: &Cache| this.func.clone());
+    cache.getter("__name__", |this: &Cache| get_attribute(&this.func, "__name__"));
+    cache.repr(|this: &Cache| format!("<functools._lru_cache_wrapper object wrapping {}>", this.func.repr()));
+    return cache;
+}
+
+fn new_cache(class: &Rc<Class>, func: Value, maxsize: Option<usize>, typed: bool) -> Value {
+    let cache = Cache {
+        func,
+        maxsize,
+        typed,
+        entries: HashMap::new(),
+        order: BTreeMap::new(),
+        clock: 0,
+        hits: 0,
+        misses: 0,
+    };
+    return Value::Object(Object::build_new(class.clone(), Some(Box::new(cache))));
+}
+
+/* lru_cache(maxsize=128, typed=False) gives a function that wraps its
+ * argument in a cache; lru_cache(func) wraps func with the defaults.
+ * maxsize=None never forgets; typed=True remembers 1 and 1.0 apart.
+ */
+fn lru_cache(class: &Rc<Class>, args: Args) -> EvalResult {
+    let bound = bind("lru_cache", args, &["maxsize", "typed"], 0)?;
+    let typed = bound[1].as_ref().is_some_and(|typed| typed.is_truthy());
+    let maxsize = match &bound[0] {
+        Some(func) if !matches!(func, Value::Int(_) | Value::Bool(_)) && is_callable(func) => {
+            return Ok(new_cache(class, func.clone(), Some(128), typed));
+        }
+        None => Some(128),
+        Some(Value::NoneT) => None,
+        Some(Value::Int(maxsize)) => Some((*maxsize).max(0) as usize),
+        Some(_) => {
+            let message = "Expected first argument to be an integer, a callable, or None";
+            return Err(Exception::new("TypeError", message));
+        }
+    };
+    let class = class.clone();
+    let decorating: NativeFn = Rc::new(move |_, args| {
+        args.check("decorating_function", 1, 1)?;
+        return Ok(new_cache(&class, args.positional[0].clone(), maxsize, typed));
+    });
+    return Ok(Builtin::build_native("decorating_function", decorating));
+}
+
+/* update_wrapper(wrapper, wrapped): wrapper under wrapped's name and
+ * docstring, with __wrapped__ set to wrapped. Functions can't be given
+ * attributes, so a function wrapper comes back as a copy with the three;
+ * a builtin gets only the name. assigned and updated are accepted but
+ * unused.
+ */
+fn update_wrapper(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    let bound = bind("update_wrapper", args, &["wrapper", "wrapped", "assigned", "updated"], 2)?;
+    let (wrapper, wrapped) = (bound[0].clone().unwrap(), bound[1].clone().unwrap());
+    let name = match get_attribute(&wrapped, "__name__") {
+        Ok(Value::Str(ref name)) => name.clone(),
+        _ => return Ok(wrapper),
+    };
+    let doc = match get_attribute(&wrapped, "__doc__") {
+        Ok(Value::Str(ref doc)) => Some(doc.clone()),
+        _ => None,
+    };
+    match &wrapper {
+        Value::Function(function) => {
+            let def = FunctionDef {
+                name,
+                doc,
+                ..(*function.def).clone()
+            };
+            let renamed = Rc::new(Function {
+                def: Rc::new(def),
+                defaults: function.defaults.clone(),
+                globals: function.globals.clone(),
+                closure: function.closure.clone(),
+                wrapped: Some(wrapped),
+            });
+            gc::track(Tracked::Function(Rc::downgrade(&renamed)));
+            return Ok(Value::Function(renamed));
+        }
+        Value::Builtin(builtin) => return Ok(Builtin::build_native(&name, builtin.func.clone())),
+        Value::Object(object) if object.native.is_none() => {
+            let mut attrs = object.attrs.borrow_mut();
+            attrs.insert(String::from("__name__"), Value::Str(name));
+            attrs.insert(String::from("__doc__"), doc.map_or(Value::NoneT, Value::Str));
+            attrs.insert(String::from("__wrapped__"), wrapped);
+        }
+        _ => (),
+    };
+    return Ok(wrapper);
+}
+
+/* cached_property(func): a property that calls func once per instance and
+ * keeps the result as an attribute of the instance, which it is found as
+ * from then on
+ */
+fn cached_property(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("cached_property", 1, 1)?;
+    let func = args.positional[0].clone();
+    let name = match get_attribute(&func, "__name__") {
+        Ok(Value::Str(ref name)) => name.clone(),
+        _ => return Err(Exception::new("TypeError", "cached_property() needs a function with a __name__")),
+    };
+    let attr = name.clone();
+    let getter: NativeFn = Rc::new(move |interpreter, args| {
+        args.check(&attr, 1, 1)?;
+        let instance = &args.positional[0];
+        if let Value::Object(object) = instance {
+            if let Some(value) = object.attrs.borrow().get(&attr) {
+                return Ok(value.clone());
+            }
+        }
+        let value = interpreter.call(&func, Args::build_new(vec![instance.clone()]))?;
+        if let Value::Object(object) = instance {
+            object.attrs.borrow_mut().insert(attr.clone(), value.clone());
+        }
+        return Ok(value);
+    });
+    let property = Property {
+        getter: Builtin::build_native(&name, getter),
+        setter: None,
+    };
+    return Ok(Value::Property(Rc::new(property)));
+}
+
+/* How total_ordering() makes a comparison from the one the class has: the
+ * result r of that, and whether the operands are equal
+ */
+#[derive(Clone, Copy)]
+enum Derived {
+    Not,
+    AndNotEqual,
+    OrEqual,
+    NotOrEqual,
+    NotAndNotEqual,
+}
+
+const ORDERINGS: &[(&str, [(&str, Derived); 3])] = &[
+    ("__lt__", [("__gt__", Derived::NotAndNotEqual), ("__le__", Derived::OrEqual), ("__ge__", Derived::Not)]),
+    ("__le__", [("__ge__", Derived::NotOrEqual), ("__lt__", Derived::AndNotEqual), ("__gt__", Derived::Not)]),
+    ("__gt__", [("__lt__", Derived::NotAndNotEqual), ("__ge__", Derived::OrEqual), ("__le__", Derived::Not)]),
+    ("__ge__", [("__le__", Derived::NotOrEqual), ("__gt__", Derived::AndNotEqual), ("__lt__", Derived::Not)]),
+];
+
+/* total_ordering(cls): cls with the comparison methods it doesn't have
+ * made from the first of __lt__, __le__, __gt__ and __ge__ it does
+ */
+fn total_ordering(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("total_ordering", 1, 1)?;
+    let found = match &args.positional[0] {
+        Value::Class(class) => ORDERINGS.iter().find_map(|(root, derived)| Some((class.clone(), class.lookup(root)?, derived))),
+        _ => None,
+    };
+    let (class, compare, derived) = match found {
+        Some(found) => found,
+        None => {
+            let message = "must define at least one ordering operation: < > <= >=";
+            return Err(Exception::new("ValueError", message));
+        }
+    };
+    for (name, how) in derived {
+        if class.lookup(name).is_some() {
+            continue;
+        }
+        let (compare, how) = (compare.clone(), *how);
+        let method: NativeFn = Rc::new(move |interpreter, args| {
+            args.check(name, 2, 2)?;
+            let r = interpreter.call(&compare, Args::build_new(args.positional.clone()))?.is_truthy();
+            let equal = interpreter.item_matches(&args.positional[0], &args.positional[1])?;
+            let value = match how {
+                Derived::Not => !r,
+                Derived::AndNotEqual => r && !equal,
+                Derived::OrEqual => r || equal,
+                Derived::NotOrEqual => !r || equal,
+                Derived::NotAndNotEqual => !r && !equal,
+            };
+            return Ok(Value::Bool(value));
+        });
+        class.attrs.borrow_mut().insert(String::from(*name), Builtin::build_native(name, method));
+    }
+    return Ok(Value::Class(class));
+}
+
+/* singledispatch(func): a function that calls the one registered for the
+ * class of its first argument (or of the nearest base class that has one),
+ * func being registered for object
+ */
+struct Dispatch {
+    name: String,
+    registry: Vec<(Rc<Class>, Value)>,
+}
+
+const DISPATCH: &str = "functools.singledispatch";
+
+impl Dispatch {
+    /* the function for class: its own, or the first found going through
+     * its bases depth first
+     */
+    fn find(&self, class: &Rc<Class>) -> Option<Value> {
+        let own = self.registry.iter().find(|(registered, _)| Rc::ptr_eq(registered, class));
+        if let Some((_, func)) = own {
+            return Some(func.clone());
+        }
+        return class.bases.iter().find_map(|base| self.find(base));
+    }
+}
+
+fn dispatch_class() -> NativeClass<Dispatch> {
+    let mut dispatch: NativeClass<Dispatch> = NativeClass::build_new(DISPATCH);
+    dispatch.raw_method("__call__", |interpreter, mut args| {
+        let (name, dispatched) = {
+            let this = instance::<Dispatch>(DISPATCH, "__call__", &args)?;
+            (this.name.clone(), args.positional.get(1).cloned())
+        };
+        let class = match dispatched {
+            Some(arg) => interpreter.type_of(&arg),
+            None => {
+                let message = format!("{} requires at least 1 positional argument", name);
+                return Err(Exception::new("TypeError", &message));
+            }
+        };
+        let func = instance::<Dispatch>(DISPATCH, "__call__", &args)?.find(&class).unwrap();
+        let call = Args {
+            positional: args.positional.split_off(1),
+            keywords: take(&mut args.keywords),
+        };
+        return interpreter.call(&func, call);
+    });
+    dispatch.raw_method("register", |_, args| {
+        args.check("register", 2, 3)?;
+        let class = match &args.positional[1] {
+            Value::Class(class) => class.clone(),
+            other => {
+                let message = format!(
+                    "Invalid first argument to `register()`: {}. Use either `@register(some_class)` or plain `@register` on an annotated function.",
+                    other.repr()
+                );
+                return Err(Exception::new("TypeError", &message));
+            }
+        };
+        if let Some(func) = args.positional.get(2) {
+            instance::<Dispatch>(DISPATCH, "register", &args)?.registry.push((class, func.clone()));
+            return Ok(func.clone());
+        }
+        // register(cls) gives a function that registers its argument
+        let this = args.positional[0].clone();
+        let register: NativeFn = Rc::new(move |_, args| {
+            args.check("register", 1, 1)?;
+            let func = args.positional[0].clone();
+            let args = Args::build_new(vec![this.clone()]);
+            instance::<Dispatch>(DISPATCH, "register", &args)?.registry.push((class.clone(), func.clone()));
+            return Ok(func);
+        });
+        return Ok(Builtin::build_native("register", register));
+    });
+    dispatch.raw_method("dispatch", |_, args| {
+        args.check("dispatch", 2, 2)?;
+        let class = match &args.positional[1] {
+            Value::Class(class) => class.clone(),
+            other => {
+                let message = format!("dispatch() argument must be a class, not '{}'", other.type_name());
+                return Err(Exception::new("TypeError", &message));
+            }
+        };
+        return Ok(instance::<Dispatch>(DISPATCH, "dispatch", &args)?.find(&class).unwrap());
+    });
+    dispatch.getter("registry", |this: &Dispatch| {
+        let mut registry = Dict::build_new();
+        for (class, func) in &this.registry {
+            registry.set(Value::Class(class.clone()), func.clone())?;
+        }
+        return Ok::<Value, Exception>(new_dict(registry));
+    });
+    dispatch.getter("__name__", |this: &Dispatch| this.name.clone());
+    return dispatch;
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::native::{with_native, NativeClass};
+    use crate::testing::{eval, exec, python};
+    use crate::value::Value;
+
+    struct Version(i64);
+
+    #[test]
+    fn test_reduce() {
+        let mut python = python("from functools import *\n");
+        assert_eq!(eval(&mut python, "(reduce(lambda a, b: a * b, [1, 2, 3, 4]), reduce(lambda a, b: a + b, [], 10))"), "(24, 10)");
+        assert_eq!(eval(&mut python, "reduce(max, [])"), "TypeError: reduce() of empty iterable with no initial value");
+    }
+
+    #[test]
+    fn test_partial() {
+        let mut python = python("from functools import *\n");
+        exec(&mut python, "def f(a, b, c=0):\n    return [a, b, c]\np = partial(f, 1, c=3)\nq = partial(p, 2)\n");
+        assert_eq!(eval(&mut python, "(p(2), p(5, c=4), q(), q.func is f, q.args, q.keywords)"), "([1, 2, 3], [1, 5, 4], [1, 2, 3], True, (1, 2), {'c': 3})");
+        assert_eq!(eval(&mut python, "partial(1)"), "TypeError: the first argument must be callable");
+    }
+
+    #[test]
+    fn test_caches() {
+        let mut python = python("from functools import *\n");
+        exec(&mut python, "calls = []\n@lru_cache(maxsize=None)\ndef fib(n):\n    calls.append(n)\n    return n if n < 2 else fib(n - 1) + fib(n - 2)\n");
+        assert_eq!(eval(&mut python, "(fib(30), len(calls), fib.cache_info())"), "(832040, 31, CacheInfo(hits=28, misses=31, maxsize=None, currsize=31))");
+        exec(&mut python, "square = lru_cache(2)(lambda x: x * x)\nsquare(1)\nsquare(2)\nsquare(1)\nsquare(3)\nsquare(2)\n");
+        assert_eq!(eval(&mut python, "(square.cache_info(), square.cache_parameters())"), "(CacheInfo(hits=1, misses=4, maxsize=2, currsize=2), {'maxsize': 2, 'typed': False})");
+        assert_eq!(eval(&mut python, "square([1])"), "TypeError: unhashable type: 'list'");
+        assert_eq!(eval(&mut python, "cache(len)('abc')"), "3");
+        exec(&mut python, "@lru_cache\ndef half(n):\n    return n // 2\n");
+        assert_eq!(eval(&mut python, "(half(10), half(10), half.cache_info().hits)"), "(5, 5, 1)");
+    }
+
+    #[test]
+    fn test_wraps() {
+        let mut python = python("from functools import *\n");
+        exec(&mut python, "def f(a, b, c=0):\n    'Make a list.'\n    return [a, b, c]\n");
+        exec(&mut python, "@wraps(f)\ndef wrapper(*args):\n    'Call f.'\n    return f(*args)\n");
+        assert_eq!(eval(&mut python, "(wrapper.__name__, wrapper.__doc__, wrapper(1, 2), wrapper.__wrapped__ is f)"), "('f', 'Make a list.', [1, 2, 0], True)");
+        exec(&mut python, "class Wrapper:\n    def __call__(self, *args):\n        return f(*args)\nw = update_wrapper(Wrapper(), f)\n");
+        assert_eq!(eval(&mut python, "(w.__name__, w.__doc__, w(3, 4), w.__wrapped__ is f)"), "('f', 'Make a list.', [3, 4, 0], True)");
+    }
+
+    #[test]
+    fn test_singledispatch() {
+        let mut python = python("from functools import *\n");
+        exec(&mut python, "@singledispatch\ndef fun(x):\n    return 'object'\nfun.register(int, lambda x: 'int')\n@fun.register(str)\ndef _(x):\n    return 'str'\n");
+        assert_eq!(eval(&mut python, "(fun(1.5), fun(True), fun('a'), fun.dispatch(bool)(0), len(fun.registry))"), "('object', 'int', 'str', 'int', 3)");
+        assert_eq!(eval(&mut python, "fun.register(1)"), "TypeError: Invalid first argument to `register()`: 1. Use either `@register(some_class)` or plain `@register` on an annotated function.");
+    }
+
+    #[test]
+    fn test_native_classes() {
+        let mut python = python("from functools import *\n");
+        assert_eq!(eval(&mut python, "total_ordering(object)"), "ValueError: must define at least one ordering operation: < > <= >=");
+        let mut version: NativeClass<Version> = NativeClass::build_new("Version");
+        version.constructor(Version);
+        version.method("__lt__", |this: &mut Version, other: Value| {
+            return with_native(&other, |other: &Version| this.0 < other.0).unwrap_or(false);
+        });
+        exec(&mut python, "calls = []\ndef double(self):\n    calls.append(self)\n    return 2\n");
+        version.attr("double", python.eval("cached_property(double)").unwrap());
+        python.register_class(version);
+        exec(&mut python, "Version = total_ordering(Version)\na, b = Version(1), Version(2)\n");
+        assert_eq!(eval(&mut python, "(a.__le__(b), a.__gt__(b), b.__ge__(a), a.__ge__(a), b.__le__(a))"), "(True, False, True, True, False)");
+        assert_eq!(eval(&mut python, "(a.double, a.double, b.double, len(calls))"), "(2, 2, 2, 2)");
+    }
+
+    #[test]
+    fn test_class_decorators() {
+        let mut python = python("from functools import *\n");
+        exec(&mut python, "@total_ordering\nclass V:\n    def __init__(self, n):\n        self.n = n\n    def __eq__(self, other):\n        return self.n == other.n\n    def __lt__(self, other):\n        return self.n < other.n\n");
+        assert_eq!(eval(&mut python, "(V(1) <= V(2), V(2) > V(2), V(2) >= V(2), V(3) > V(2), max(V(1), V(3)).n)"), "(True, False, True, True, 3)");
+        exec(&mut python, "class C:\n    runs = 0\n    @cached_property\n    def value(self):\n        C.runs += 1\n        return 42\nc = C()\n");
+        assert_eq!(eval(&mut python, "(c.value, c.value, C.runs)"), "(42, 42, 1)");
+    }
+}
@@ -0,0 +1,54 @@
+use crate::gc;
+use crate::interpreter::{EvalResult, Interpreter};
+use crate::value::{new_tuple, Args, Builtin, BuiltinFn, Module, Value};
+use std::rc::Rc;
+
+/* gc - the cycle collector
+ *
+ * collect() frees unreachable cycles now and returns how many containers
+ * they were made of. There is one generation, so get_count() is
+ * (containers created since the last collection, 0, 0).
+ */
+pub fn build_module() -> Rc<Module> {
+    let module = Module::build_new("gc", None);
+    let functions: &[(&str, BuiltinFn)] = &[
+        ("collect", collect),
+        ("get_count", get_count),
+        ("enable", enable),
+        ("disable", disable),
+        ("isenabled", isenabled),
+    ];
+    for (name, func) in functions {
+        module.set(name, Builtin::build_new(name, *func));
+    }
+    return Rc::new(module);
+}
+
+fn collect(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("collect", 0, 1)?;
+    let collected = interpreter.collect_garbage();
+    interpreter.run_finalizers();
+    return Ok(Value::Int(collected as i64));
+}
+
+fn get_count(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("get_count", 0, 0)?;
+    return Ok(new_tuple(vec![Value::Int(gc::count() as i64), Value::Int(0), Value::Int(0)]));
+}
+
+fn enable(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("enable", 0, 0)?;
+    gc::set_enabled(true);
+    return Ok(Value::NoneT);
+}
+
+fn disable(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("disable", 0, 0)?;
+    gc::set_enabled(false);
+    return Ok(Value::NoneT);
+}
+
+fn isenabled(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("isenabled", 0, 0)?;
+    return Ok(Value::Bool(gc::is_enabled()));
+}
@@ -0,0 +1,1327 @@
+use crate::bytes;
+use crate::interpreter::{not_an_int, EvalResult, Interpreter};
+use crate::iterator::{new_iterator, Iter};
+use crate::native::{bind, instance, NativeClass};
+use crate::value::{new_list, Args, Builtin, Class, Exception, Module, Object, Value};
+use std::convert::TryFrom;
+use std::fs::{self, OpenOptions};
+use std::io::{self, BufRead, ErrorKind, IsTerminal, Read, Seek, SeekFrom, Write};
+use std::rc::Rc;
+
+/* io - file objects
+ *
+ * open() returns a TextIOWrapper in text mode, and in binary mode a
+ * BufferedReader, BufferedWriter or BufferedRandom, or a FileIO when
+ * unbuffered. These are one File underneath: reads go through a buffer
+ * of read-ahead and writes through a buffer flushed once it's full, with
+ * the text layer decoding, encoding and translating newlines on top.
+ * tell() of a text file is the position in bytes.
+ *
+ * sys.stdin, sys.stdout and sys.stderr are TextIOWrappers over the
+ * process's streams, which print() and input() go through. StringIO and
+ * BytesIO keep their contents in memory.
+ */
+
+const DEFAULT_BUFFER_SIZE: usize = 8192;
+
+/* The classes open() makes instances of
+ */
+struct FileClasses {
+    text: Rc<Class>,
+    reader: Rc<Class>,
+    writer: Rc<Class>,
+    random: Rc<Class>,
+    raw: Rc<Class>,
+}
+
+pub fn build_module(object: Rc<Class>) -> Rc<Module> {
+    let classes = Rc::new(FileClasses {
+        text: file_class("TextIOWrapper", Value::Str(String::new())).build(object.clone()),
+        reader: file_class("BufferedReader", bytes::new_bytes(Vec::new())).build(object.clone()),
+        writer: file_class("BufferedWriter", bytes::new_bytes(Vec::new())).build(object.clone()),
+        random: file_class("BufferedRandom", bytes::new_bytes(Vec::new())).build(object.clone()),
+        raw: file_class("FileIO", bytes::new_bytes(Vec::new())).build(object.clone()),
+    });
+
+    let module = Module::build_new("io", None);
+    for class in [&classes.text, &classes.reader, &classes.writer, &classes.random, &classes.raw] {
+        module.set_class(class.clone());
+    }
+    let open = {
+        let classes = classes.clone();
+        move |interpreter: &mut Interpreter, args: Args| open(interpreter, &classes, args)
+    };
+    module.set("open", Builtin::build_native("open", Rc::new(open)));
+    module.set_class(string_io_class().build(object.clone()));
+    module.set_class(bytes_io_class().build(object));
+    module.set("DEFAULT_BUFFER_SIZE", Value::Int(DEFAULT_BUFFER_SIZE as i64));
+    module.set("SEEK_SET", Value::Int(0));
+    module.set("SEEK_CUR", Value::Int(1));
+    module.set("SEEK_END", Value::Int(2));
+    return Rc::new(module);
+}
+
+/* sys.stdin, sys.stdout and sys.stderr, as TextIOWrappers of the io
+ * module given
+ */
+pub fn standard_streams(io: &Module) -> Vec<(&'static str, Value)> {
+    let class = match io.get("TextIOWrapper") {
+        Some(Value::Class(ref class)) => class.clone(),
+        _ => unreachable!("io is built with TextIOWrapper"),
+    };
+    let streams = [
+        ("stdin", "<stdin>", "r", Raw::Stdin),
+        ("stdout", "<stdout>", "w", Raw::Stdout),
+        ("stderr", "<stderr>", "w", Raw::Stderr),
+    ];
+    let mut values = Vec::new();
+    for (var, name, mode, raw) in streams {
+        let stdout = matches!(raw, Raw::Stdout);
+        let console = stdout || matches!(raw, Raw::Stderr);
+        let file = File {
+            name: Value::Str(String::from(name)),
+            mode: String::from(mode),
+            readable: mode == "r",
+            writable: mode == "w",
+            text: Some(Text {
+                encoding: String::from("utf-8"),
+                errors: String::from("strict"),
+                multibyte: true,
+                newline: None,
+                line_buffering: stdout && raw.isatty(),
+            }),
+            // Rust buffers stdout itself
+            buffer_size: 0,
+            raw: Some(raw),
+            read_buf: Vec::new(),
+            read_pos: 0,
+            write_buf: Vec::new(),
+            console,
+        };
+        values.push((var, Value::Object(Object::build_new(class.clone(), Some(Box::new(file))))));
+    }
+    return values;
+}
+
+/* The exception for an operating system error, like CPython's
+ * FileNotFoundError: [Errno 2] No such file or directory: 'name'
+ */
+pub(crate) fn os_error(err: &io::Error, filename: Option<&Value>) -> Exception {
+    let kind = match err.kind() {
+        ErrorKind::NotFound => "FileNotFoundError",
+        ErrorKind::PermissionDenied => "PermissionError",
+        ErrorKind::AlreadyExists => "FileExistsError",
+        ErrorKind::IsADirectory => "IsADirectoryError",
+        ErrorKind::NotADirectory => "NotADirectoryError",
+        ErrorKind::BrokenPipe => "BrokenPipeError",
+        ErrorKind::Interrupted => "InterruptedError",
+        _ => "OSError",
+    };
+    let mut args: Option<Vec<Value>> = None;
+    let mut message = match err.raw_os_error() {
+        Some(code) => {
+            // Rust adds the code to the system's description of the error
+            let description = err.to_string();
+            let suffix = format!(" (os error {})", code);
+            let description = description.strip_suffix(&suffix).unwrap_or(&description);
+            args = Some(vec![Value::Int(code as i64), Value::Str(String::from(description))]);
+            format!("[Errno {}] {}", code, description)
+        }
+        None => err.to_string(),
+    };
+    if let Some(filename) = filename {
+        message = format!("{}: {}", message, filename.repr());
+        if let Some(args) = &mut args {
+            args.push(filename.clone());
+        }
+    }
+    let mut err = Exception::new(kind, &message);
+    // OSError(errno, strerror, filename)
+    err.details_mut().args = args;
+    return err;
+}
+
+fn closed_error() -> Exception {
+    return Exception::new("ValueError", "I/O operation on closed file.");
+}
+
+fn unsupported(message: &str) -> Exception {
+    return Exception::new("io.UnsupportedOperation", message);
+}
+
+fn type_error(message: String) -> Exception {
+    return Exception::new("TypeError", &message);
+}
+
+fn value_error(message: String) -> Exception {
+    return Exception::new("ValueError", &message);
+}
+
+/* a size argument: None or a negative number for no limit
+ */
+fn size_arg(value: Option<&Value>) -> Result<Option<usize>, Exception> {
+    match value {
+        None | Some(Value::NoneT) => return Ok(None),
+        Some(Value::Int(size)) => return Ok(usize::try_from(*size).ok()),
+        Some(Value::Bool(size)) => return Ok(Some(*size as usize)),
+        Some(other) => {
+            return Err(type_error(format!("argument should be integer or None, not '{}'", other.type_name())));
+        }
+    }
+}
+
+fn int_arg(value: Option<&Value>, default: i64) -> Result<i64, Exception> {
+    match value {
+        None => return Ok(default),
+        Some(Value::Int(val)) => return Ok(*val),
+        Some(Value::Bool(val)) => return Ok(*val as i64),
+        Some(other) => return Err(not_an_int(other)),
+    }
+}
+
+/* The newline argument of open() and StringIO(): None, or one of '',
+ * '\n', '\r' and '\r\n'
+ */
+fn newline_arg(value: Option<&Value>) -> Result<Option<String>, Exception> {
+    match value {
+        None | Some(Value::NoneT) => return Ok(None),
+        Some(Value::Str(newline)) if ["", "\n", "\r", "\r\n"].contains(&newline.as_str()) => {
+            return Ok(Some(newline.clone()));
+        }
+        Some(Value::Str(newline)) => return Err(value_error(format!("illegal newline value: {}", newline))),
+        Some(other) => return Err(type_error(format!("newline must be str or None, not {}", other.type_name()))),
+    }
+}
+
+/* Where the line at the start of data ends, after its end: lines end at
+ * newline, or at any of \n, \r and \r\n when that's None
+ */
+fn line_end<T: Copy + PartialEq + From<u8>>(data: &[T], newline: Option<&str>) -> Option<usize> {
+    match newline {
+        Some(newline) => {
+            let newline: Vec<T> = newline.bytes().map(T::from).collect();
+            let pos = data.windows(newline.len()).position(|window| window == &newline[..])?;
+            return Some(pos + newline.len());
+        }
+        None => {
+            let pos = data.iter().position(|c| *c == T::from(b'\n') || *c == T::from(b'\r'))?;
+            if data[pos] == T::from(b'\r') && data.get(pos + 1) == Some(&T::from(b'\n')) {
+                return Some(pos + 2);
+            }
+            return Some(pos + 1);
+        }
+    }
+}
+
+/* The length of the first count characters of data, which is utf-8 if
+ * multibyte and a byte a character otherwise
+ */
+fn char_boundary(data: &[u8], count: usize, multibyte: bool) -> usize {
+    if !multibyte {
+        return count.min(data.len());
+    }
+    let mut chars = 0;
+    for (pos, byte) in data.iter().enumerate() {
+        // continuation bytes are 10xxxxxx
+        if byte & 0xc0 != 0x80 {
+            if chars == count {
+                return pos;
+            }
+            chars += 1;
+        }
+    }
+    return data.len();
+}
+
+/* The length of utf-8 data without a character cut off at its end
+ */
+fn complete_chars(data: &[u8]) -> usize {
+    let start = data.len().saturating_sub(4);
+    let lead = match data[start..].iter().rposition(|byte| byte & 0xc0 != 0x80) {
+        Some(pos) => start + pos,
+        None => return data.len(),
+    };
+    let width = match data[lead] {
+        0xc0..=0xdf => 2,
+        0xe0..=0xef => 3,
+        0xf0..=0xf7 => 4,
+        _ => 1,
+    };
+    if lead + width > data.len() {
+        return lead;
+    }
+    return data.len();
+}
+
+/* What a File reads and writes in the end
+ */
+enum Raw {
+    File(fs::File),
+    Stdin,
+    Stdout,
+    Stderr,
+}
+
+impl Raw {
+    /* Read up to size more bytes onto the end of buf. Stdin is read a
+     * line at a time, so nothing past the line is taken from the REPL.
+     */
+    fn read_into(&mut self, buf: &mut Vec<u8>, size: usize) -> io::Result<usize> {
+        match self {
+            Raw::File(file) => {
+                let start = buf.len();
+                buf.resize(start + size, 0);
+                let result = file.read(&mut buf[start..]);
+                buf.truncate(start + *result.as_ref().unwrap_or(&0));
+                return result;
+            }
+            Raw::Stdin => return io::stdin().lock().read_until(b'\n', buf),
+            Raw::Stdout | Raw::Stderr => return Ok(0),
+        }
+    }
+
+    fn write_all(&mut self, data: &[u8]) -> io::Result<()> {
+        match self {
+            Raw::File(file) => return file.write_all(data),
+            // like CPython, a closed stdout is not an error worth dying for
+            Raw::Stdout => {
+                let _ = io::stdout().write_all(data);
+            }
+            Raw::Stderr => {
+                let _ = io::stderr().write_all(data);
+            }
+            Raw::Stdin => (),
+        };
+        return Ok(());
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Raw::File(file) => return file.flush(),
+            Raw::Stdout => {
+                let _ = io::stdout().flush();
+            }
+            Raw::Stdin | Raw::Stderr => (),
+        };
+        return Ok(());
+    }
+
+    fn seek(&mut self, pos: SeekFrom) -> Result<u64, Exception> {
+        match self {
+            Raw::File(file) => return file.seek(pos).map_err(|err| os_error(&err, None)),
+            _ => return Err(unsupported("underlying stream is not seekable")),
+        }
+    }
+
+    fn isatty(&self) -> bool {
+        match self {
+            Raw::File(file) => return file.is_terminal(),
+            Raw::Stdin => return io::stdin().is_terminal(),
+            Raw::Stdout => return io::stdout().is_terminal(),
+            Raw::Stderr => return io::stderr().is_terminal(),
+        }
+    }
+}
+
+/* The text layer of a TextIOWrapper
+ */
+struct Text {
+    encoding: String,
+    errors: String,
+    multibyte: bool,
+    // None for universal newlines: \r\n and \r are read as \n
+    newline: Option<String>,
+    // flush writes of a line end
+    line_buffering: bool,
+}
+
+/* A file from open(), or one of the standard streams
+ */
+struct File {
+    name: Value,
+    mode: String,
+    // None once closed
+    raw: Option<Raw>,
+    readable: bool,
+    writable: bool,
+    // None in binary mode
+    text: Option<Text>,
+    // writes wait until this much is written; 0 writes straight through
+    buffer_size: usize,
+    // read ahead of the position, from read_pos on
+    read_buf: Vec<u8>,
+    read_pos: usize,
+    // written but not yet flushed
+    write_buf: Vec<u8>,
+    // sys.stdout and sys.stderr count towards the output limit
+    console: bool,
+}
+
+impl File {
+    fn raw(&mut self) -> Result<&mut Raw, Exception> {
+        return self.raw.as_mut().ok_or_else(closed_error);
+    }
+
+    fn check_readable(&mut self) -> Result<(), Exception> {
+        self.raw()?;
+        if !self.readable {
+            return Err(unsupported("not readable"));
+        }
+        return Ok(());
+    }
+
+    fn check_writable(&mut self) -> Result<(), Exception> {
+        self.raw()?;
+        if !self.writable {
+            return Err(unsupported("not writable"));
+        }
+        return Ok(());
+    }
+
+    fn unread(&self) -> &[u8] {
+        return &self.read_buf[self.read_pos..];
+    }
+
+    fn take(&mut self, len: usize) -> Vec<u8> {
+        let data = self.unread()[..len].to_vec();
+        self.read_pos += len;
+        return data;
+    }
+
+    /* Read more into the read buffer; false at the end of the file
+     */
+    fn fill(&mut self) -> Result<bool, Exception> {
+        self.flush_buffer()?;
+        self.read_buf.drain(..self.read_pos);
+        self.read_pos = 0;
+        let size = self.buffer_size.max(DEFAULT_BUFFER_SIZE);
+        let raw = self.raw.as_mut().ok_or_else(closed_error)?;
+        let count = raw.read_into(&mut self.read_buf, size).map_err(|err| os_error(&err, None))?;
+        return Ok(count > 0);
+    }
+
+    fn peek(&mut self) -> Result<Option<u8>, Exception> {
+        if self.unread().is_empty() && !self.fill()? {
+            return Ok(None);
+        }
+        return Ok(Some(self.unread()[0]));
+    }
+
+    /* size bytes, or the rest of the file when None, or less at its end
+     */
+    fn read_bytes(&mut self, size: Option<usize>) -> Result<Vec<u8>, Exception> {
+        while size.is_none_or(|size| self.unread().len() < size) && self.fill()? {}
+        let len = size.map_or(self.unread().len(), |size| size.min(self.unread().len()));
+        return Ok(self.take(len));
+    }
+
+    /* The bytes of the next line, its end included, as line_end() finds
+     * it
+     */
+    fn read_line_bytes(&mut self, newline: Option<&str>) -> Result<Vec<u8>, Exception> {
+        loop {
+            if let Some(end) = line_end(self.unread(), newline) {
+                // a \r at the end of what has been read may start a \r\n
+                let pending = newline.is_none() && end == self.unread().len() && self.unread()[end - 1] == b'\r';
+                if !pending {
+                    return Ok(self.take(end));
+                }
+            }
+            if !self.fill()? {
+                let len = self.unread().len();
+                return Ok(self.take(len));
+            }
+        }
+    }
+
+    fn text(&self) -> &Text {
+        return self.text.as_ref().expect("text file");
+    }
+
+    /* data decoded, with universal newlines translated
+     */
+    fn decode(&self, data: &[u8]) -> Result<String, Exception> {
+        let text = self.text();
+        let decoded = bytes::decode(data, &text.encoding, &text.errors)?;
+        if text.newline.is_none() && decoded.contains('\r') {
+            return Ok(decoded.replace("\r\n", "\n").replace('\r', "\n"));
+        }
+        return Ok(decoded);
+    }
+
+    /* size characters, or the rest of the file when None
+     */
+    fn read_text(&mut self, size: Option<usize>) -> Result<String, Exception> {
+        let size = match size {
+            Some(size) => size,
+            None => {
+                let data = self.read_bytes(None)?;
+                return self.decode(&data);
+            }
+        };
+        let multibyte = self.text().multibyte;
+        let mut text = String::new();
+        let mut count = 0;
+        while count < size {
+            // whole characters only, so none is split between reads
+            let mut end = char_boundary(self.unread(), size - count, multibyte);
+            if multibyte {
+                end = end.min(complete_chars(self.unread()));
+            }
+            if end == 0 {
+                if self.fill()? {
+                    continue;
+                }
+                // what's left at the end of the file, even a part of a
+                // character
+                end = self.unread().len();
+                if end == 0 {
+                    break;
+                }
+            }
+            let mut data = self.take(end);
+            if self.text().newline.is_none() && data[end - 1] == b'\r' && self.peek()? == Some(b'\n') {
+                data.extend(self.take(1));
+            }
+            let chunk = self.decode(&data)?;
+            count += chunk.chars().count();
+            text.push_str(&chunk);
+        }
+        return Ok(text);
+    }
+
+    /* The next line, or up to size characters of it
+     */
+    fn read_line_text(&mut self, size: Option<usize>) -> Result<String, Exception> {
+        let newline = match &self.text().newline {
+            Some(newline) if !newline.is_empty() => Some(newline.clone()),
+            _ => None,
+        };
+        let mut line = self.read_line_bytes(newline.as_deref())?;
+        if let Some(size) = size {
+            let end = char_boundary(&line, size, self.text().multibyte);
+            // the rest of the line is still in the read buffer
+            self.read_pos -= line.len() - end;
+            line.truncate(end);
+        }
+        return self.decode(&line);
+    }
+
+    /* text encoded, with \n written as the newline argument
+     */
+    fn encode(&self, text: &str) -> Result<Vec<u8>, Exception> {
+        let config = self.text();
+        match config.newline.as_deref() {
+            Some(newline @ "\r") | Some(newline @ "\r\n") => {
+                return bytes::encode(&text.replace('\n', newline), &config.encoding, &config.errors);
+            }
+            _ => return bytes::encode(text, &config.encoding, &config.errors),
+        }
+    }
+
+    fn write_bytes(&mut self, data: &[u8]) -> Result<(), Exception> {
+        // what was read ahead is given back, so the write is at the position
+        let unread = self.unread().len();
+        if unread > 0 {
+            self.raw()?.seek(SeekFrom::Current(-(unread as i64)))?;
+        }
+        self.read_buf.clear();
+        self.read_pos = 0;
+        if self.buffer_size == 0 {
+            return self.raw()?.write_all(data).map_err(|err| os_error(&err, None));
+        }
+        self.write_buf.extend_from_slice(data);
+        if self.write_buf.len() >= self.buffer_size {
+            self.flush_buffer()?;
+        }
+        return Ok(());
+    }
+
+    fn flush_buffer(&mut self) -> Result<(), Exception> {
+        if self.write_buf.is_empty() {
+            return Ok(());
+        }
+        let data = std::mem::take(&mut self.write_buf);
+        return self.raw()?.write_all(&data).map_err(|err| os_error(&err, None));
+    }
+
+    fn flush(&mut self) -> Result<(), Exception> {
+        self.flush_buffer()?;
+        return self.raw()?.flush().map_err(|err| os_error(&err, None));
+    }
+
+    fn tell(&mut self) -> Result<u64, Exception> {
+        self.flush_buffer()?;
+        let unread = self.unread().len() as u64;
+        return Ok(self.raw()?.seek(SeekFrom::Current(0))? - unread);
+    }
+
+    fn seek(&mut self, pos: SeekFrom) -> Result<u64, Exception> {
+        self.flush_buffer()?;
+        let pos = match pos {
+            SeekFrom::Current(offset) => SeekFrom::Current(offset - self.unread().len() as i64),
+            pos => pos,
+        };
+        let pos = self.raw()?.seek(pos)?;
+        self.read_buf.clear();
+        self.read_pos = 0;
+        return Ok(pos);
+    }
+
+    fn truncate(&mut self, size: Option<u64>) -> Result<u64, Exception> {
+        // the read-ahead may be cut off, so it goes
+        let pos = self.tell()?;
+        self.seek(SeekFrom::Start(pos))?;
+        let size = size.unwrap_or(pos);
+        match self.raw()? {
+            Raw::File(file) => file.set_len(size).map_err(|err| os_error(&err, None))?,
+            _ => return Err(unsupported("underlying stream is not seekable")),
+        };
+        return Ok(size);
+    }
+
+    fn close(&mut self) -> Result<(), Exception> {
+        if self.raw.is_none() {
+            return Ok(());
+        }
+        let result = self.flush();
+        self.raw = None;
+        return result;
+    }
+}
+
+impl Drop for File {
+    fn drop(&mut self) {
+        let _ = self.close();
+    }
+}
+
+/* The arguments of seek(offset, whence), as a SeekFrom
+ */
+fn seek_args(args: &Args, text: bool) -> Result<SeekFrom, Exception> {
+    let offset = int_arg(args.positional.get(1), 0)?;
+    match int_arg(args.positional.get(2), 0)? {
+        0 if offset < 0 => {
+            let what = if text { "position" } else { "value" };
+            return Err(value_error(format!("negative seek {} {}", what, offset)));
+        }
+        0 => return Ok(SeekFrom::Start(offset as u64)),
+        1 if text && offset != 0 => return Err(unsupported("can't do nonzero cur-relative seeks")),
+        1 => return Ok(SeekFrom::Current(offset)),
+        2 if text && offset != 0 => return Err(unsupported("can't do nonzero end-relative seeks")),
+        2 => return Ok(SeekFrom::End(offset)),
+        whence if text => return Err(value_error(format!("invalid whence ({}, should be 0, 1 or 2)", whence))),
+        whence => return Err(value_error(format!("whence value {} unsupported", whence))),
+    }
+}
+
+/* One of the classes of open(): TextIOWrapper or a binary class, whose
+ * reads at the end of the file give empty
+ */
+fn file_class(name: &'static str, empty: Value) -> NativeClass<File> {
+    let mut class: NativeClass<File> = NativeClass::build_new(name);
+    class.raw_method("read", move |_, args| {
+        args.check("read", 1, 2)?;
+        let size = size_arg(args.positional.get(1))?;
+        let mut this = instance::<File>(name, "read", &args)?;
+        this.check_readable()?;
+        if this.text.is_some() {
+            return Ok(Value::Str(this.read_text(size)?));
+        }
+        return Ok(bytes::new_bytes(this.read_bytes(size)?));
+    });
+    class.raw_method("readline", move |_, args| {
+        args.check("readline", 1, 2)?;
+        let size = size_arg(args.positional.get(1))?;
+        let mut this = instance::<File>(name, "readline", &args)?;
+        this.check_readable()?;
+        if this.text.is_some() {
+            return Ok(Value::Str(this.read_line_text(size)?));
+        }
+        let mut line = this.read_line_bytes(Some("\n"))?;
+        if let Some(size) = size.filter(|size| *size < line.len()) {
+            this.read_pos -= line.len() - size;
+            line.truncate(size);
+        }
+        return Ok(bytes::new_bytes(line));
+    });
+    class.raw_method("write", move |interpreter, args| {
+        args.check("write", 2, 2)?;
+        let mut this = instance::<File>(name, "write", &args)?;
+        this.check_writable()?;
+        let (data, written) = match (&this.text, &args.positional[1]) {
+            (Some(_), Value::Str(text)) => (this.encode(text)?, text.chars().count()),
+            (Some(_), other) => {
+                return Err(type_error(format!("write() argument must be str, not {}", other.type_name())));
+            }
+            (None, other) => {
+                let data = bytes::buffer(other)?;
+                let len = data.len();
+                (data, len)
+            }
+        };
+        if this.console {
+            interpreter.output(data.len())?;
+        }
+        this.write_bytes(&data)?;
+        if let (Some(text), Value::Str(written)) = (&this.text, &args.positional[1]) {
+            if text.line_buffering && written.contains(['\n', '\r']) {
+                this.flush()?;
+            }
+        }
+        return Ok(Value::Int(written as i64));
+    });
+    class.raw_method("seek", move |_, args| {
+        args.check("seek", 2, 3)?;
+        let mut this = instance::<File>(name, "seek", &args)?;
+        this.raw()?;
+        let pos = seek_args(&args, this.text.is_some())?;
+        return Ok(Value::Int(this.seek(pos)? as i64));
+    });
+    class.raw_method("tell", move |_, args| {
+        args.check("tell", 1, 1)?;
+        let mut this = instance::<File>(name, "tell", &args)?;
+        return Ok(Value::Int(this.tell()? as i64));
+    });
+    class.raw_method("truncate", move |_, args| {
+        args.check("truncate", 1, 2)?;
+        let mut this = instance::<File>(name, "truncate", &args)?;
+        this.check_writable()?;
+        let size = match args.positional.get(1) {
+            None | Some(Value::NoneT) => None,
+            size => match u64::try_from(int_arg(size, 0)?) {
+                Ok(size) => Some(size),
+                Err(_) => return Err(Exception::new("OSError", "[Errno 22] Invalid argument")),
+            },
+        };
+        return Ok(Value::Int(this.truncate(size)? as i64));
+    });
+    class.raw_method("flush", move |_, args| {
+        args.check("flush", 1, 1)?;
+        instance::<File>(name, "flush", &args)?.flush()?;
+        return Ok(Value::NoneT);
+    });
+    class.raw_method("close", move |_, args| {
+        args.check("close", 1, 1)?;
+        instance::<File>(name, "close", &args)?.close()?;
+        return Ok(Value::NoneT);
+    });
+    class.raw_method("readable", move |_, args| {
+        args.check("readable", 1, 1)?;
+        let mut this = instance::<File>(name, "readable", &args)?;
+        this.raw()?;
+        return Ok(Value::Bool(this.readable));
+    });
+    class.raw_method("writable", move |_, args| {
+        args.check("writable", 1, 1)?;
+        let mut this = instance::<File>(name, "writable", &args)?;
+        this.raw()?;
+        return Ok(Value::Bool(this.writable));
+    });
+    class.raw_method("seekable", move |_, args| {
+        args.check("seekable", 1, 1)?;
+        let mut this = instance::<File>(name, "seekable", &args)?;
+        return Ok(Value::Bool(this.raw()?.seek(SeekFrom::Current(0)).is_ok()));
+    });
+    class.raw_method("isatty", move |_, args| {
+        args.check("isatty", 1, 1)?;
+        let mut this = instance::<File>(name, "isatty", &args)?;
+        return Ok(Value::Bool(this.raw()?.isatty()));
+    });
+    class.getter("closed", |this: &File| this.raw.is_none());
+    class.getter("name", |this: &File| this.name.clone());
+    class.getter("mode", |this: &File| this.mode.clone());
+    if name == "TextIOWrapper" {
+        class.getter("encoding", |this: &File| this.text().encoding.clone());
+        class.getter("errors", |this: &File| this.text().errors.clone());
+        class.getter("line_buffering", |this: &File| this.text().line_buffering);
+    }
+    stream_methods(&mut class, empty);
+    return class;
+}
+
+/* readlines(), writelines(), iteration by line and the context manager
+ * protocol, for every kind of file in terms of its other methods. empty
+ * is what readline() gives at the end.
+ */
+fn stream_methods<T: 'static>(class: &mut NativeClass<T>, empty: Value) {
+    class.raw_method("readlines", |interpreter, args| {
+        args.check("readlines", 1, 2)?;
+        // a hint of 0 or less is no limit, like None
+        let hint = size_arg(args.positional.get(1))?.filter(|hint| *hint > 0);
+        let readline = interpreter.get_attr(&args.positional[0], "readline")?;
+        let mut lines = Vec::new();
+        let mut total = 0;
+        loop {
+            let line = interpreter.call(&readline, Args::default())?;
+            let len = match &line {
+                Value::Str(line) => line.chars().count(),
+                line => bytes::byte_len(line).unwrap_or(0),
+            };
+            if len == 0 {
+                break;
+            }
+            lines.push(line);
+            total += len;
+            if hint.is_some_and(|hint| total >= hint) {
+                break;
+            }
+        }
+        return Ok(new_list(lines));
+    });
+    class.raw_method("writelines", |interpreter, args| {
+        args.check("writelines", 2, 2)?;
+        let write = interpreter.get_attr(&args.positional[0], "write")?;
+        for line in interpreter.iterate(&args.positional[1])? {
+            interpreter.call(&write, Args::build_new(vec![line]))?;
+        }
+        return Ok(Value::NoneT);
+    });
+    class.raw_method("__enter__", |interpreter, args| {
+        args.check("__enter__", 1, 1)?;
+        check_open(interpreter, &args.positional[0])?;
+        return Ok(args.positional[0].clone());
+    });
+    class.raw_method("__exit__", |interpreter, args| {
+        let close = interpreter.get_attr(&args.positional[0], "close")?;
+        interpreter.call(&close, Args::default())?;
+        return Ok(Value::NoneT);
+    });
+    class.raw_method("__iter__", move |interpreter, args| {
+        args.check("__iter__", 1, 1)?;
+        check_open(interpreter, &args.positional[0])?;
+        let readline = interpreter.get_attr(&args.positional[0], "readline")?;
+        return Ok(new_iterator(Iter::Callable(Some((readline, empty.clone())))));
+    });
+}
+
+fn check_open(interpreter: &mut Interpreter, file: &Value) -> Result<(), Exception> {
+    if interpreter.get_attr(file, "closed")?.is_truthy() {
+        return Err(closed_error());
+    }
+    return Ok(());
+}
+
+/* open(file, mode='r', buffering=-1, encoding=None, errors=None, newline=None)
+ */
+fn open(interpreter: &mut Interpreter, classes: &FileClasses, args: Args) -> EvalResult {
+    let params = ["file", "mode", "buffering", "encoding", "errors", "newline"];
+    let bound = bind("open", args, &params, 1)?;
+    interpreter.check_access("PermissionError", "file access")?;
+    let name = bound[0].clone().unwrap_or(Value::NoneT);
+    let path = match &name {
+        Value::Str(path) => path.clone(),
+        Value::Bytes(path) => String::from_utf8_lossy(path).into_owned(),
+        other => {
+            let message = format!("expected str, bytes or os.PathLike object, not {}", other.type_name());
+            return Err(type_error(message));
+        }
+    };
+    let optional_str = |value: &Option<Value>, param: &str| match value {
+        None | Some(Value::NoneT) => return Ok(None),
+        Some(Value::Str(text)) => return Ok(Some(text.clone())),
+        Some(other) => {
+            let message = format!("open() argument '{}' must be str or None, not {}", param, other.type_name());
+            return Err(type_error(message));
+        }
+    };
+    let mode = match &bound[1] {
+        None => String::from("r"),
+        Some(Value::Str(mode)) => mode.clone(),
+        Some(other) => return Err(type_error(format!("open() argument 'mode' must be str, not {}", other.type_name()))),
+    };
+    let buffering = int_arg(bound[2].as_ref(), -1)?;
+    let encoding = optional_str(&bound[3], "encoding")?;
+    let errors = optional_str(&bound[4], "errors")?;
+    let newline = match &bound[5] {
+        Some(Value::Str(_)) | None | Some(Value::NoneT) => newline_arg(bound[5].as_ref())?,
+        Some(other) => {
+            let message = format!("open() argument 'newline' must be str or None, not {}", other.type_name());
+            return Err(type_error(message));
+        }
+    };
+
+    // at most one of each letter, and one of r, w, a and x
+    let mut seen = String::new();
+    for c in mode.chars() {
+        if !"rwaxbt+".contains(c) || seen.contains(c) {
+            return Err(value_error(format!("invalid mode: '{}'", mode)));
+        }
+        seen.push(c);
+    }
+    let binary = seen.contains('b');
+    if binary && seen.contains('t') {
+        return Err(value_error(String::from("can't have text and binary mode at once")));
+    }
+    let bases: Vec<char> = seen.chars().filter(|c| "rwax".contains(*c)).collect();
+    if bases.len() != 1 {
+        return Err(value_error(String::from("must have exactly one of create/read/write/append mode")));
+    }
+    let (base, plus) = (bases[0], seen.contains('+'));
+    if binary {
+        let given = [("encoding", encoding.is_some()), ("errors", errors.is_some()), ("newline", newline.is_some())];
+        for (param, given) in given {
+            if given {
+                let article = if param == "newline" { "a" } else { "an" };
+                return Err(value_error(format!("binary mode doesn't take {} {} argument", article, param)));
+            }
+        }
+    } else if buffering == 0 {
+        return Err(value_error(String::from("can't have unbuffered text I/O")));
+    }
+    let encoding = encoding.unwrap_or_else(|| String::from("utf-8"));
+    let multibyte = bytes::is_multibyte(&encoding)?;
+
+    let mut options = OpenOptions::new();
+    match base {
+        'r' => options.read(true),
+        'w' => options.write(true).create(true).truncate(true),
+        'a' => options.append(true).create(true),
+        _ => options.write(true).create_new(true),
+    };
+    if plus {
+        options.read(true).write(true);
+    }
+    let mut file = options.open(&path).map_err(|err| os_error(&err, Some(&name)))?;
+    if file.metadata().is_ok_and(|metadata| metadata.is_dir()) {
+        return Err(Exception::new("IsADirectoryError", &format!("[Errno 21] Is a directory: {}", name.repr())));
+    }
+    if base == 'a' {
+        file.seek(SeekFrom::End(0)).map_err(|err| os_error(&err, Some(&name)))?;
+    }
+
+    let raw = Raw::File(file);
+    let text = match binary {
+        true => None,
+        false => Some(Text {
+            encoding,
+            errors: errors.unwrap_or_else(|| String::from("strict")),
+            multibyte,
+            newline,
+            line_buffering: buffering == 1 || (buffering < 0 && raw.isatty()),
+        }),
+    };
+    let (class, mode) = match (binary, buffering, plus, base) {
+        (false, ..) => (&classes.text, mode),
+        (true, 0, ..) => (&classes.raw, binary_mode(base, plus)),
+        (true, _, true, _) => (&classes.random, binary_mode(base, plus)),
+        (true, _, false, 'r') => (&classes.reader, binary_mode(base, plus)),
+        (true, ..) => (&classes.writer, binary_mode(base, plus)),
+    };
+    let file = File {
+        name,
+        mode,
+        raw: Some(raw),
+        readable: base == 'r' || plus,
+        writable: base != 'r' || plus,
+        text,
+        buffer_size: match buffering {
+            0 => 0,
+            size if size > 1 => size as usize,
+            _ => DEFAULT_BUFFER_SIZE,
+        },
+        read_buf: Vec::new(),
+        read_pos: 0,
+        write_buf: Vec::new(),
+        console: false,
+    };
+    return Ok(Value::Object(Object::build_new(class.clone(), Some(Box::new(file)))));
+}
+
+/* the mode of a binary file as CPython shows it, e.g. 'rb+' for 'w+b'
+ */
+fn binary_mode(base: char, plus: bool) -> String {
+    match (base, plus) {
+        ('w', true) => return String::from("rb+"),
+        (base, true) => return format!("{}b+", base),
+        (base, false) => return format!("{}b", base),
+    }
+}
+
+/* What StringIO and BytesIO hold: chars or bytes
+ */
+trait Unit: Copy + Default + PartialEq + From<u8> + 'static {
+    const TEXT: bool;
+
+    fn value(data: &[Self]) -> Value;
+
+    /* the units write() writes for value, and the count it returns
+     */
+    fn units(value: &Value, newline: Option<&str>) -> Result<(Vec<Self>, usize), Exception>;
+}
+
+impl Unit for char {
+    const TEXT: bool = true;
+
+    fn value(data: &[char]) -> Value {
+        return Value::Str(data.iter().collect());
+    }
+
+    fn units(value: &Value, newline: Option<&str>) -> Result<(Vec<char>, usize), Exception> {
+        let text = match value {
+            Value::Str(text) => text,
+            other => return Err(type_error(format!("string argument expected, got '{}'", other.type_name()))),
+        };
+        let count = text.chars().count();
+        let text = match newline {
+            None => text.replace("\r\n", "\n").replace('\r', "\n"),
+            Some(newline @ "\r") | Some(newline @ "\r\n") => text.replace('\n', newline),
+            Some(_) => text.clone(),
+        };
+        return Ok((text.chars().collect(), count));
+    }
+}
+
+impl Unit for u8 {
+    const TEXT: bool = false;
+
+    fn value(data: &[u8]) -> Value {
+        return bytes::new_bytes(data.to_vec());
+    }
+
+    fn units(value: &Value, _newline: Option<&str>) -> Result<(Vec<u8>, usize), Exception> {
+        let data = bytes::buffer(value)?;
+        let count = data.len();
+        return Ok((data, count));
+    }
+}
+
+/* A StringIO or BytesIO
+ */
+struct Memory<T> {
+    data: Vec<T>,
+    pos: usize,
+    // the newline argument of StringIO
+    newline: Option<String>,
+    closed: bool,
+}
+
+impl<T: Unit> Memory<T> {
+    fn build_new(initial: &Value, newline: Option<String>) -> Result<Memory<T>, Exception> {
+        let mut memory = Memory {
+            data: Vec::new(),
+            pos: 0,
+            newline,
+            closed: false,
+        };
+        memory.write(initial)?;
+        memory.pos = 0;
+        return Ok(memory);
+    }
+
+    fn check(&self) -> Result<(), Exception> {
+        // StringIO's message has no full stop
+        if self.closed && T::TEXT {
+            return Err(value_error(String::from("I/O operation on closed file")));
+        }
+        if self.closed {
+            return Err(closed_error());
+        }
+        return Ok(());
+    }
+
+    fn rest(&self) -> &[T] {
+        return &self.data[self.pos.min(self.data.len())..];
+    }
+
+    fn read(&mut self, size: Option<usize>) -> Value {
+        let rest = self.rest();
+        let len = size.map_or(rest.len(), |size| size.min(rest.len()));
+        let value = T::value(&rest[..len]);
+        self.pos += len;
+        return value;
+    }
+
+    fn readline(&mut self, size: Option<usize>) -> Value {
+        // StringIO translated newlines as they were written, except for ''
+        let newline = match (T::TEXT, self.newline.as_deref()) {
+            (true, Some("")) => None,
+            (true, Some(newline)) => Some(newline),
+            _ => Some("\n"),
+        };
+        let rest = self.rest();
+        let mut len = line_end(rest, newline).unwrap_or(rest.len());
+        if let Some(size) = size {
+            len = len.min(size);
+        }
+        let value = T::value(&rest[..len]);
+        self.pos += len;
+        return value;
+    }
+
+    fn write(&mut self, value: &Value) -> Result<usize, Exception> {
+        let (units, count) = T::units(value, self.newline.as_deref())?;
+        if self.pos > self.data.len() {
+            self.data.resize(self.pos, T::default());
+        }
+        let end = (self.pos + units.len()).min(self.data.len());
+        self.pos += units.len();
+        self.data.splice(self.pos - units.len()..end, units);
+        return Ok(count);
+    }
+
+    fn seek(&mut self, args: &Args) -> Result<usize, Exception> {
+        let offset = int_arg(args.positional.get(1), 0)?;
+        let whence = int_arg(args.positional.get(2), 0)?;
+        let base = match whence {
+            0 if offset < 0 && T::TEXT => return Err(value_error(format!("Negative seek position {}", offset))),
+            0 if offset < 0 => return Err(value_error(format!("negative seek value {}", offset))),
+            0 => 0,
+            1 | 2 if T::TEXT && offset != 0 => {
+                let relative = if whence == 1 { "cur" } else { "end" };
+                return Err(Exception::new("OSError", &format!("Can't do nonzero {}-relative seeks", relative)));
+            }
+            1 => self.pos as i64,
+            2 => self.data.len() as i64,
+            _ if T::TEXT => return Err(value_error(format!("Invalid whence ({}, should be 0, 1 or 2)", whence))),
+            _ => return Err(value_error(format!("invalid whence ({}, should be 0, 1 or 2)", whence))),
+        };
+        self.pos = base.saturating_add(offset).max(0) as usize;
+        return Ok(self.pos);
+    }
+}
+
+/* StringIO(initial_value='', newline='\n')
+ */
+fn string_io_class() -> NativeClass<Memory<char>> {
+    let mut class: NativeClass<Memory<char>> = NativeClass::build_new("StringIO");
+    class.raw_new(|_, class, args| {
+        let bound = bind("StringIO", args, &["initial_value", "newline"], 0)?;
+        let initial = match &bound[0] {
+            None | Some(Value::NoneT) => Value::Str(String::new()),
+            Some(Value::Str(text)) => Value::Str(text.clone()),
+            Some(other) => {
+                return Err(type_error(format!("initial_value must be str or None, not {}", other.type_name())));
+            }
+        };
+        let newline = match &bound[1] {
+            None => Some(String::from("\n")),
+            newline => newline_arg(newline.as_ref())?,
+        };
+        let memory = Memory::<char>::build_new(&initial, newline)?;
+        return Ok(Value::Object(Object::build_new(class, Some(Box::new(memory)))));
+    });
+    memory_methods(&mut class, "StringIO");
+    stream_methods(&mut class, Value::Str(String::new()));
+    return class;
+}
+
+/* BytesIO(initial_bytes=b'')
+ */
+fn bytes_io_class() -> NativeClass<Memory<u8>> {
+    let mut class: NativeClass<Memory<u8>> = NativeClass::build_new("BytesIO");
+    class.raw_new(|_, class, args| {
+        let bound = bind("BytesIO", args, &["initial_bytes"], 0)?;
+        let initial = match &bound[0] {
+            None | Some(Value::NoneT) => bytes::new_bytes(Vec::new()),
+            Some(initial) => initial.clone(),
+        };
+        let memory = Memory::<u8>::build_new(&initial, None)?;
+        return Ok(Value::Object(Object::build_new(class, Some(Box::new(memory)))));
+    });
+    memory_methods(&mut class, "BytesIO");
+    class.raw_method("read1", |_, args| {
+        args.check("read1", 1, 2)?;
+        let size = size_arg(args.positional.get(1))?;
+        let mut this = instance::<Memory<u8>>("BytesIO", "read1", &args)?;
+        this.check()?;
+        return Ok(this.read(size));
+    });
+    stream_methods(&mut class, bytes::new_bytes(Vec::new()));
+    return class;
+}
+
+/* the methods StringIO and BytesIO share
+ */
+fn memory_methods<T: Unit>(class: &mut NativeClass<Memory<T>>, name: &'static str) {
+    class.raw_method("getvalue", move |_, args| {
+        args.check("getvalue", 1, 1)?;
+        let this = instance::<Memory<T>>(name, "getvalue", &args)?;
+        this.check()?;
+        return Ok(T::value(&this.data));
+    });
+    class.raw_method("read", move |_, args| {
+        args.check("read", 1, 2)?;
+        let size = size_arg(args.positional.get(1))?;
+        let mut this = instance::<Memory<T>>(name, "read", &args)?;
+        this.check()?;
+        return Ok(this.read(size));
+    });
+    class.raw_method("readline", move |_, args| {
+        args.check("readline", 1, 2)?;
+        let size = size_arg(args.positional.get(1))?;
+        let mut this = instance::<Memory<T>>(name, "readline", &args)?;
+        this.check()?;
+        return Ok(this.readline(size));
+    });
+    class.raw_method("write", move |_, args| {
+        args.check("write", 2, 2)?;
+        let mut this = instance::<Memory<T>>(name, "write", &args)?;
+        this.check()?;
+        return Ok(Value::Int(this.write(&args.positional[1])? as i64));
+    });
+    class.raw_method("seek", move |_, args| {
+        args.check("seek", 2, 3)?;
+        let mut this = instance::<Memory<T>>(name, "seek", &args)?;
+        this.check()?;
+        return Ok(Value::Int(this.seek(&args)? as i64));
+    });
+    class.raw_method("tell", move |_, args| {
+        args.check("tell", 1, 1)?;
+        let this = instance::<Memory<T>>(name, "tell", &args)?;
+        this.check()?;
+        return Ok(Value::Int(this.pos as i64));
+    });
+    class.raw_method("truncate", move |_, args| {
+        args.check("truncate", 1, 2)?;
+        let mut this = instance::<Memory<T>>(name, "truncate", &args)?;
+        this.check()?;
+        let size = match args.positional.get(1) {
+            None | Some(Value::NoneT) => this.pos as i64,
+            size => int_arg(size, 0)?,
+        };
+        if size < 0 {
+            let what = if T::TEXT { "Negative size value" } else { "negative size value" };
+            return Err(value_error(format!("{} {}", what, size)));
+        }
+        this.data.truncate(size as usize);
+        return Ok(Value::Int(size));
+    });
+    class.raw_method("close", move |_, args| {
+        args.check("close", 1, 1)?;
+        instance::<Memory<T>>(name, "close", &args)?.closed = true;
+        return Ok(Value::NoneT);
+    });
+    for method in ["readable", "writable", "seekable", "isatty", "flush"] {
+        class.raw_method(method, move |_, args| {
+            args.check(method, 1, 1)?;
+            let this = instance::<Memory<T>>(name, method, &args)?;
+            // flushing a closed StringIO is allowed
+            if method != "flush" || !T::TEXT {
+                this.check()?;
+            }
+            match method {
+                "flush" => return Ok(Value::NoneT),
+                "isatty" => return Ok(Value::Bool(false)),
+                _ => return Ok(Value::Bool(true)),
+            }
+        });
+    }
+    class.getter("closed", |this: &Memory<T>| this.closed);
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::embed;
+    use crate::testing::{eval, exec};
+    use std::env;
+    use std::fs;
+    use std::path::PathBuf;
+
+    /* An interpreter with `path` set to a file in a new directory, which
+     * is returned to be removed at the end of the test
+     */
+    fn setup(name: &str) -> (embed::Interpreter, PathBuf) {
+        let dir = env::temp_dir().join(format!("rebar-io-{}-{}", name, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let mut python = embed::Interpreter::build_new();
+        python.set("path", dir.join("a.txt").to_str().unwrap());
+        return (python, dir);
+    }
+
+    #[test]
+    fn test_text_files() {
+        let (mut python, dir) = setup("text");
+        let source = "
+with open(path, 'w') as f:
+    n = f.write('one\\ntwo\\r\\nthree\\rfour')
+with open(path) as f:
+    lines = list(f)
+";
+        exec(&mut python, source);
+        assert_eq!(eval(&mut python, "(n, f.closed, f.mode, lines)"), "(19, True, 'r', ['one\\n', 'two\\n', 'three\\n', 'four'])");
+        assert_eq!(eval(&mut python, "open(path, newline='').readlines()"), "['one\\n', 'two\\r\\n', 'three\\r', 'four']");
+        assert_eq!(eval(&mut python, "open(path, 'rb').read()"), "b'one\\ntwo\\r\\nthree\\rfour'");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_binary_files() {
+        let (mut python, dir) = setup("binary");
+        exec(&mut python, "with open(path, 'w') as f:\n    f.write('one\\ntwo\\nthree\\nfour')\n");
+        exec(&mut python, "f = open(path, 'r+b')\n");
+        assert_eq!(eval(&mut python, "(f.readline(), f.tell(), f.seek(-4, 2), f.read(2), f.write(b'XY'))"), "(b'one\\n', 4, 14, b'fo', 2)");
+        exec(&mut python, "f.close()\n");
+        assert_eq!(eval(&mut python, "open(path).read()"), "'one\\ntwo\\nthree\\nfoXY'");
+        assert_eq!(eval(&mut python, "(type(f).__name__, type(f).__module__)"), "('BufferedRandom', 'io')");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_encodings() {
+        let (mut python, dir) = setup("encodings");
+        python.exec("with open(path, 'w', encoding='latin-1') as f:\n    f.write('caf\\xe9 \\u20ac')\n").unwrap_err();
+        exec(&mut python, "with open(path, 'w', encoding='utf-8') as f:\n    f.write('caf\\xe9\\n\\u20ac')\n");
+        exec(&mut python, "f = open(path)\n");
+        assert_eq!(eval(&mut python, "(f.read(4), f.tell(), f.readline(), f.read())"), "('café', 5, '\\n', '€')");
+        assert_eq!(eval(&mut python, "open(path, encoding='ascii', errors='replace').read()"), "'caf\u{fffd}\u{fffd}\\n\u{fffd}\u{fffd}\u{fffd}'");
+        exec(&mut python, "with open(path, 'a', newline='\\r\\n') as f:\n    f.write('x\\n')\n");
+        assert_eq!(eval(&mut python, "open(path, 'rb').read()[-5:]"), "b'\\x82\\xacx\\r\\n'");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_file_errors() {
+        let (mut python, dir) = setup("errors");
+        exec(&mut python, "f = open(path, 'w')\nf.close()\n");
+        assert_eq!(eval(&mut python, "f.read()"), "ValueError: I/O operation on closed file.");
+        assert_eq!(eval(&mut python, "open(path).write('x')"), "io.UnsupportedOperation: not writable");
+        assert_eq!(eval(&mut python, "open(path, 'rw')"), "ValueError: must have exactly one of create/read/write/append mode");
+        assert_eq!(eval(&mut python, "open(path, 'rb', encoding='utf-8')"), "ValueError: binary mode doesn't take an encoding argument");
+        assert_eq!(eval(&mut python, "open(path, 'x')").replace(&*dir.to_string_lossy(), "DIR"), "FileExistsError: [Errno 17] File exists: 'DIR/a.txt'");
+        assert_eq!(eval(&mut python, "open(path + 'nonexistent')").replace(&*dir.to_string_lossy(), "DIR"), "FileNotFoundError: [Errno 2] No such file or directory: 'DIR/a.txtnonexistent'");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_memory_streams() {
+        let mut python = embed::Interpreter::build_new();
+        let source = "
+import io
+import sys
+s = io.StringIO('a\\nb')
+read = (s.readline(), s.tell(), s.read(), s.write('c'), s.getvalue())
+b = io.BytesIO()
+b.write(b'xyz')
+b.seek(1)
+b.write(b'_')
+sys.stdout = io.StringIO()
+print('captured', 1, sep='-')
+sys.stdin = io.StringIO('typed\\n')
+typed = input('prompt? ')
+captured = sys.stdout.getvalue()
+sys.stdout = sys.__stdout__
+";
+        exec(&mut python, source);
+        assert_eq!(eval(&mut python, "read"), "('a\\n', 2, 'b', 1, 'a\\nbc')");
+        assert_eq!(eval(&mut python, "(b.getvalue(), b.tell(), b.read())"), "(b'x_z', 2, b'z')");
+        assert_eq!(eval(&mut python, "(typed, captured)"), "('typed', 'captured-1\\nprompt? ')");
+        assert_eq!(eval(&mut python, "input()"), "EOFError: EOF when reading a line");
+        assert_eq!(eval(&mut python, "list(io.StringIO('x\\r\\ny\\rz', newline=''))"), "['x\\r\\n', 'y\\r', 'z']");
+        assert_eq!(eval(&mut python, "io.StringIO().write(1)"), "TypeError: string argument expected, got 'int'");
+        assert_eq!(eval(&mut python, "io.BytesIO(b'ab').seek(-1)"), "ValueError: negative seek value -1");
+    }
+
+    #[test]
+    fn test_with() {
+        let mut python = embed::Interpreter::build_new();
+        exec(&mut python, "import io\ns = io.StringIO()\nwith s:\n    s.write('x')\n");
+        assert_eq!(eval(&mut python, "s.closed"), "True");
+        exec(&mut python, "with io.StringIO() as a, io.BytesIO() as b:\n    pass\n");
+        assert_eq!(eval(&mut python, "(a.closed, b.closed)"), "(True, True)");
+        let err = python.exec("with s:\n    pass\n").unwrap_err();
+        assert_eq!(err.to_string(), "ValueError: I/O operation on closed file.");
+        let err = python.exec("with 1:\n    pass\n").unwrap_err();
+        assert_eq!(err.to_string(), "TypeError: 'int' object does not support the context manager protocol");
+    }
+}
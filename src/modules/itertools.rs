@@ -0,0 +1,641 @@
+use crate::ast::BinOp;
+use crate::interpreter::{as_complex, as_int, binary_op, equals, not_an_int, EvalResult, Interpreter};
+use crate::iterator::native_iterator;
+use crate::native::{bind, NativeClass};
+use crate::value::{new_tuple, Args, Builtin, BuiltinFn, Class, Exception, Module, Value};
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::mem::size_of;
+use std::rc::Rc;
+
+/* itertools - iterator building blocks
+ *
+ * The iterators are lazy, like map() and filter(): each pulls what it needs
+ * from the iterators it was given as it is advanced, so the infinite ones
+ * (count, cycle, repeat) can be cut short with islice() or zip().
+ * product(), permutations() and the combinations read their input first,
+ * as CPython does, and then make the tuples one at a time.
+ */
+pub fn build_module(object: Rc<Class>) -> Rc<Module> {
+    let module = Module::build_new("itertools", None);
+    let functions: &[(&str, BuiltinFn)] = &[
+        ("count", count),
+        ("cycle", cycle),
+        ("repeat", repeat),
+        ("islice", islice),
+        ("product", product),
+        ("permutations", permutations),
+        ("combinations", combinations),
+        ("combinations_with_replacement", combinations_with_replacement),
+        ("groupby", groupby),
+        ("accumulate", accumulate),
+        ("zip_longest", zip_longest),
+        ("tee", tee),
+    ];
+    for (name, func) in functions {
+        module.set(name, Builtin::build_new(name, *func));
+    }
+    // chain is a class for chain.from_iterable()
+    let mut chain: NativeClass<()> = NativeClass::build_new("itertools.chain");
+    chain.raw_new(|interpreter, _, args| {
+        args.check("chain", 0, usize::MAX)?;
+        let iterables = interpreter.get_iter(&new_tuple(args.positional))?;
+        return Ok(chain_iterator(iterables));
+    });
+    chain.attr("from_iterable", Builtin::build_new("from_iterable", from_iterable));
+    module.set("chain", Value::Class(chain.build(object)));
+    return Rc::new(module);
+}
+
+fn int_arg(value: &Value) -> Result<i64, Exception> {
+    return as_int(value).ok_or_else(|| not_an_int(value));
+}
+
+/* count(start=0, step=1): start, start + step, start + 2 * step, ...
+ */
+fn count(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    let bound = bind("count", args, &["start", "step"], 0)?;
+    let mut next = bound[0].clone().unwrap_or(Value::Int(0));
+    let step = bound[1].clone().unwrap_or(Value::Int(1));
+    for value in [&next, &step] {
+        if as_complex(value).is_none() && !matches!(value, Value::Fraction(_) | Value::Decimal(_)) {
+            return Err(Exception::new("TypeError", "a number is required"));
+        }
+    }
+    return Ok(native_iterator("itertools.count", move |_| {
+        let item = next.clone();
+        next = binary_op(BinOp::Add, &next, &step)?;
+        return Ok(Some(item));
+    }));
+}
+
+/* cycle(iterable): the items of iterable, saved on the first pass, over and
+ * over
+ */
+fn cycle(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("cycle", 1, 1)?;
+    let iterator = interpreter.get_iter(&args.positional[0])?;
+    let mut saved: Vec<Value> = Vec::new();
+    // where the next item is in saved, once the iterator is exhausted
+    let mut pos: Option<usize> = None;
+    return Ok(native_iterator("itertools.cycle", move |interpreter| {
+        if pos.is_none() {
+            if let Some(item) = interpreter.next_item(&iterator)? {
+                interpreter.allocate(size_of::<Value>())?;
+                saved.push(item.clone());
+                return Ok(Some(item));
+            }
+            pos = Some(0);
+        }
+        if saved.is_empty() {
+            return Ok(None);
+        }
+        let current = pos.unwrap();
+        pos = Some((current + 1) % saved.len());
+        return Ok(Some(saved[current].clone()));
+    }));
+}
+
+/* repeat(object[, times]): object, times times or forever
+ */
+fn repeat(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    let bound = bind("repeat", args, &["object", "times"], 1)?;
+    let item = bound[0].clone().unwrap();
+    let mut left = match &bound[1] {
+        Some(times) => Some(int_arg(times)?.max(0)),
+        None => None,
+    };
+    return Ok(native_iterator("itertools.repeat", move |_| {
+        match &mut left {
+            Some(0) => return Ok(None),
+            Some(left) => *left -= 1,
+            None => (),
+        };
+        return Ok(Some(item.clone()));
+    }));
+}
+
+/* the items of each iterable iterables gives in turn
+ */
+fn chain_iterator(iterables: Value) -> Value {
+    let mut current: Option<Value> = None;
+    return native_iterator("itertools.chain", move |interpreter| loop {
+        let iterator = match &current {
+            Some(iterator) => iterator.clone(),
+            None => match interpreter.next_item(&iterables)? {
+                Some(iterable) => interpreter.get_iter(&iterable)?,
+                None => return Ok(None),
+            },
+        };
+        current = Some(iterator.clone());
+        match interpreter.next_item(&iterator)? {
+            Some(item) => return Ok(Some(item)),
+            None => current = None,
+        };
+    });
+}
+
+/* chain.from_iterable(iterable): chain(*iterable), with iterable read as
+ * it goes
+ */
+fn from_iterable(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("from_iterable", 1, 1)?;
+    let iterables = interpreter.get_iter(&args.positional[0])?;
+    return Ok(chain_iterator(iterables));
+}
+
+/* islice(iterable, stop) or islice(iterable, start, stop[, step]): the
+ * items of iterable at those positions, like a slice
+ */
+fn islice(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("islice", 2, 4)?;
+    let index = |value: Option<&Value>, default: Option<usize>, message: &str| match value {
+        None | Some(Value::NoneT) => return Ok(default),
+        Some(value) => match as_int(value) {
+            Some(index) if index >= 0 => return Ok(Some(index as usize)),
+            _ => return Err(Exception::new("ValueError", message)),
+        },
+    };
+    let (start, stop) = if args.positional.len() == 2 {
+        let message = "Stop argument for islice() must be None or an integer: 0 <= x <= sys.maxsize.";
+        (0, index(args.positional.get(1), None, message)?)
+    } else {
+        let message = "Indices for islice() must be None or an integer: 0 <= x <= sys.maxsize.";
+        (index(args.positional.get(1), Some(0), message)?.unwrap(), index(args.positional.get(2), None, message)?)
+    };
+    let step = match index(args.positional.get(3), Some(1), "")? {
+        Some(step) if step > 0 => step,
+        _ => return Err(Exception::new("ValueError", "Step for islice() must be a positive integer or None.")),
+    }
+    .max(1);
+    let mut iterator = Some(interpreter.get_iter(&args.positional[0])?);
+    // the position of the next item to give, and how many have been read
+    let (mut next, mut read) = (start, 0);
+    return Ok(native_iterator("itertools.islice", move |interpreter| {
+        let source = match &iterator {
+            Some(source) => source.clone(),
+            None => return Ok(None),
+        };
+        // the items up to the next one (or to stop) are read, like CPython
+        let stop_at = stop.map_or(next, |stop| next.min(stop));
+        while read < stop_at {
+            if interpreter.next_item(&source)?.is_none() {
+                iterator = None;
+                return Ok(None);
+            }
+            read += 1;
+        }
+        if stop.is_some_and(|stop| read >= stop) {
+            iterator = None;
+            return Ok(None);
+        }
+        match interpreter.next_item(&source)? {
+            Some(item) => {
+                read += 1;
+                next = next.saturating_add(step);
+                return Ok(Some(item));
+            }
+            None => {
+                iterator = None;
+                return Ok(None);
+            }
+        }
+    }));
+}
+
+/* product(*iterables, repeat=1): tuples of an item of each, like nested
+ * for loops with the last iterable innermost
+ */
+fn product(interpreter: &mut Interpreter, mut args: Args) -> EvalResult {
+    let repeat = match args.keyword("repeat") {
+        Some(repeat) => int_arg(&repeat)?,
+        None => 1,
+    };
+    args.check("product", 0, usize::MAX)?;
+    if repeat < 0 {
+        return Err(Exception::new("ValueError", "repeat argument cannot be negative"));
+    }
+    let mut pools: Vec<Vec<Value>> = Vec::new();
+    for iterable in &args.positional {
+        pools.push(interpreter.iterate(iterable)?);
+    }
+    let pools: Vec<Vec<Value>> = (0..repeat).flat_map(|_| pools.clone()).collect();
+    let mut indices: Option<Vec<usize>> = None;
+    let mut done = pools.iter().any(|pool| pool.is_empty());
+    return Ok(native_iterator("itertools.product", move |_| {
+        if done {
+            return Ok(None);
+        }
+        match &mut indices {
+            None => indices = Some(vec![0; pools.len()]),
+            Some(indices) => {
+                // the last index counts up first, like an odometer
+                let mut pos = indices.len();
+                loop {
+                    if pos == 0 {
+                        done = true;
+                        return Ok(None);
+                    }
+                    pos -= 1;
+                    indices[pos] += 1;
+                    if indices[pos] < pools[pos].len() {
+                        break;
+                    }
+                    indices[pos] = 0;
+                }
+            }
+        };
+        let indices = indices.as_ref().unwrap();
+        return Ok(Some(new_tuple(indices.iter().zip(&pools).map(|(index, pool)| pool[*index].clone()).collect())));
+    }));
+}
+
+/* r, the length of the tuples of permutations() and combinations()
+ */
+fn length_arg(value: &Option<Value>, default: usize) -> Result<usize, Exception> {
+    match value {
+        None | Some(Value::NoneT) => return Ok(default),
+        Some(value) => match as_int(value) {
+            Some(r) if r >= 0 => return Ok(r as usize),
+            Some(_) => return Err(Exception::new("ValueError", "r must be non-negative")),
+            None => return Err(Exception::new("TypeError", "Expected int as r")),
+        },
+    }
+}
+
+/* permutations(iterable, r=None): the r-length orderings of the items, in
+ * the order of itertools' documented algorithm
+ */
+fn permutations(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    let bound = bind("permutations", args, &["iterable", "r"], 1)?;
+    let pool = interpreter.iterate(bound[0].as_ref().unwrap())?;
+    let n = pool.len();
+    let r = length_arg(&bound[1], n)?;
+    let mut indices: Vec<usize> = (0..n).collect();
+    let mut cycles: Vec<usize> = (0..r.min(n)).map(|i| n - i).collect();
+    let mut started = false;
+    let mut done = r > n;
+    return Ok(native_iterator("itertools.permutations", move |_| {
+        if done {
+            return Ok(None);
+        }
+        if started {
+            let mut found = false;
+            for i in (0..r).rev() {
+                cycles[i] -= 1;
+                if cycles[i] == 0 {
+                    indices[i..].rotate_left(1);
+                    cycles[i] = n - i;
+                } else {
+                    indices.swap(i, n - cycles[i]);
+                    found = true;
+                    break;
+                }
+            }
+            if !found {
+                done = true;
+                return Ok(None);
+            }
+        }
+        started = true;
+        return Ok(Some(new_tuple(indices[..r].iter().map(|index| pool[*index].clone()).collect())));
+    }));
+}
+
+/* combinations(iterable, r): the r-length subsequences of the items, in
+ * order
+ */
+fn combinations(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    let bound = bind("combinations", args, &["iterable", "r"], 2)?;
+    let pool = interpreter.iterate(bound[0].as_ref().unwrap())?;
+    let n = pool.len();
+    let r = length_arg(&bound[1], 0)?;
+    let mut indices: Vec<usize> = (0..r).collect();
+    let mut started = false;
+    let mut done = r > n;
+    return Ok(native_iterator("itertools.combinations", move |_| {
+        if done {
+            return Ok(None);
+        }
+        if started {
+            // the last index that can still move up
+            match (0..r).rev().find(|i| indices[*i] != i + n - r) {
+                Some(i) => {
+                    indices[i] += 1;
+                    for j in i + 1..r {
+                        indices[j] = indices[j - 1] + 1;
+                    }
+                }
+                None => {
+                    done = true;
+                    return Ok(None);
+                }
+            };
+        }
+        started = true;
+        return Ok(Some(new_tuple(indices.iter().map(|index| pool[*index].clone()).collect())));
+    }));
+}
+
+/* combinations_with_replacement(iterable, r): the r-length sequences of
+ * the items, in order, where each item can be taken more than once
+ */
+fn combinations_with_replacement(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    let bound = bind("combinations_with_replacement", args, &["iterable", "r"], 2)?;
+    let pool = interpreter.iterate(bound[0].as_ref().unwrap())?;
+    let n = pool.len();
+    let r = length_arg(&bound[1], 0)?;
+    // unlike combinations(), r can be more than the number of items
+    interpreter.allocate(r.saturating_mul(size_of::<usize>()))?;
+    let mut indices: Vec<usize> = vec![0; r];
+    let mut started = false;
+    let mut done = n == 0 && r > 0;
+    return Ok(native_iterator("itertools.combinations_with_replacement", move |_| {
+        if done {
+            return Ok(None);
+        }
+        if started {
+            // the last index that isn't at the last item yet
+            match (0..r).rev().find(|i| indices[*i] != n - 1) {
+                Some(i) => {
+                    let index = indices[i] + 1;
+                    indices[i..].fill(index);
+                }
+                None => {
+                    done = true;
+                    return Ok(None);
+                }
+            };
+        }
+        started = true;
+        return Ok(Some(new_tuple(indices.iter().map(|index| pool[*index].clone()).collect())));
+    }));
+}
+
+/* What groupby() and its groups share: the iterator, the item read last
+ * and its key, the key of the group being given out, and a number for the
+ * group, so the groups before it stop
+ */
+struct GroupBy {
+    iterator: Value,
+    key: Value,
+    current: Option<(Value, Value)>,
+    target: Option<Value>,
+    group: usize,
+}
+
+/* read the next item and its key; false once there are none
+ */
+fn group_step(interpreter: &mut Interpreter, state: &Rc<RefCell<GroupBy>>) -> Result<bool, Exception> {
+    let (iterator, key) = {
+        let state = state.borrow();
+        (state.iterator.clone(), state.key.clone())
+    };
+    let item = match interpreter.next_item(&iterator)? {
+        Some(item) => item,
+        None => return Ok(false),
+    };
+    let item_key = match key {
+        Value::NoneT => item.clone(),
+        key => interpreter.call(&key, Args::build_new(vec![item.clone()]))?,
+    };
+    state.borrow_mut().current = Some((item_key, item));
+    return Ok(true);
+}
+
+/* whether the item read last is in the group being given out (both unset
+ * at the start)
+ */
+fn in_group(state: &GroupBy) -> bool {
+    match (&state.current, &state.target) {
+        (None, None) => return true,
+        (Some((key, _)), Some(target)) => return equals(key, target),
+        _ => return false,
+    }
+}
+
+/* groupby(iterable, key=None): (key, group) for each run of items with
+ * equal keys, where group iterates over the run. A group can only be used
+ * until the next one is asked for.
+ */
+fn groupby(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    let bound = bind("groupby", args, &["iterable", "key"], 1)?;
+    let state = Rc::new(RefCell::new(GroupBy {
+        iterator: interpreter.get_iter(bound[0].as_ref().unwrap())?,
+        key: bound[1].clone().unwrap_or(Value::NoneT),
+        current: None,
+        target: None,
+        group: 0,
+    }));
+    return Ok(native_iterator("itertools.groupby", move |interpreter| {
+        state.borrow_mut().group += 1;
+        // skip what is left of the last group
+        while in_group(&state.borrow()) {
+            if !group_step(interpreter, &state)? {
+                return Ok(None);
+            }
+        }
+        let (key, group) = {
+            let mut shared = state.borrow_mut();
+            let key = shared.current.as_ref().unwrap().0.clone();
+            shared.target = Some(key.clone());
+            (key.clone(), shared.group)
+        };
+        let grouper = grouper(state.clone(), key.clone(), group);
+        return Ok(Some(new_tuple(vec![key, grouper])));
+    }));
+}
+
+fn grouper(state: Rc<RefCell<GroupBy>>, target: Value, group: usize) -> Value {
+    let (mut started, mut done) = (false, false);
+    return native_iterator("itertools._grouper", move |interpreter| {
+        if done {
+            return Ok(None);
+        }
+        // the first item was read by groupby
+        if started && !group_step(interpreter, &state)? {
+            done = true;
+            return Ok(None);
+        }
+        started = true;
+        let shared = state.borrow();
+        match &shared.current {
+            Some((key, item)) if shared.group == group && equals(key, &target) => return Ok(Some(item.clone())),
+            _ => {
+                done = true;
+                return Ok(None);
+            }
+        }
+    });
+}
+
+/* accumulate(iterable, func=None, initial=None): running totals, or
+ * running results of func(total, item)
+ */
+fn accumulate(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    let bound = bind("accumulate", args, &["iterable", "func", "initial"], 1)?;
+    let iterator = interpreter.get_iter(bound[0].as_ref().unwrap())?;
+    let func = bound[1].clone().filter(|func| *func != Value::NoneT);
+    let mut total = bound[2].clone().filter(|initial| *initial != Value::NoneT);
+    let mut initial = total.is_some();
+    return Ok(native_iterator("itertools.accumulate", move |interpreter| {
+        if initial {
+            initial = false;
+            return Ok(total.clone());
+        }
+        let item = match interpreter.next_item(&iterator)? {
+            Some(item) => item,
+            None => return Ok(None),
+        };
+        let next = match (total.take(), &func) {
+            (None, _) => item,
+            (Some(total), None) => binary_op(BinOp::Add, &total, &item)?,
+            (Some(total), Some(func)) => interpreter.call(func, Args::build_new(vec![total, item]))?,
+        };
+        total = Some(next);
+        return Ok(total.clone());
+    }));
+}
+
+/* zip_longest(*iterables, fillvalue=None): like zip(), until the longest
+ * is exhausted, with fillvalue for the others
+ */
+fn zip_longest(interpreter: &mut Interpreter, mut args: Args) -> EvalResult {
+    let fill = args.keyword("fillvalue").unwrap_or(Value::NoneT);
+    args.check("zip_longest", 0, usize::MAX)?;
+    let mut iterators: Vec<Option<Value>> = Vec::new();
+    for iterable in &args.positional {
+        iterators.push(Some(interpreter.get_iter(iterable)?));
+    }
+    let mut active = iterators.len();
+    return Ok(native_iterator("itertools.zip_longest", move |interpreter| {
+        if active == 0 {
+            return Ok(None);
+        }
+        let mut items: Vec<Value> = Vec::new();
+        for slot in iterators.iter_mut() {
+            let item = match slot {
+                Some(iterator) => interpreter.next_item(iterator)?,
+                None => None,
+            };
+            let item = match item {
+                Some(item) => item,
+                None => {
+                    if slot.take().is_some() {
+                        active -= 1;
+                        if active == 0 {
+                            return Ok(None);
+                        }
+                    }
+                    fill.clone()
+                }
+            };
+            items.push(item);
+        }
+        return Ok(Some(new_tuple(items)));
+    }));
+}
+
+/* What the iterators of one tee() share: the items some have had and
+ * others not yet (the first being item number start), and where each is
+ */
+struct Tee {
+    iterator: Value,
+    items: VecDeque<Value>,
+    start: usize,
+    positions: Vec<usize>,
+}
+
+/* tee(iterable, n=2): n independent iterators over the items of iterable.
+ * Items are kept until every one of them has had them.
+ */
+fn tee(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("tee", 1, 2)?;
+    let n = match args.positional.get(1) {
+        Some(n) => int_arg(n)?,
+        None => 2,
+    };
+    if n < 0 {
+        return Err(Exception::new("ValueError", "n must be >= 0"));
+    }
+    let shared = Rc::new(RefCell::new(Tee {
+        iterator: interpreter.get_iter(&args.positional[0])?,
+        items: VecDeque::new(),
+        start: 0,
+        positions: vec![0; n as usize],
+    }));
+    let mut iterators: Vec<Value> = Vec::new();
+    for index in 0..n as usize {
+        let shared = shared.clone();
+        iterators.push(native_iterator("itertools._tee", move |interpreter| {
+            let (buffered, iterator) = {
+                let tee = shared.borrow();
+                let buffered = tee.items.get(tee.positions[index] - tee.start).cloned();
+                (buffered, tee.iterator.clone())
+            };
+            let item = match buffered {
+                Some(item) => item,
+                None => match interpreter.next_item(&iterator)? {
+                    Some(item) => {
+                        interpreter.allocate(size_of::<Value>())?;
+                        shared.borrow_mut().items.push_back(item.clone());
+                        item
+                    }
+                    None => return Ok(None),
+                },
+            };
+            let mut tee = shared.borrow_mut();
+            tee.positions[index] += 1;
+            let passed = tee.positions.iter().min().copied().unwrap_or(0);
+            while tee.start < passed {
+                tee.items.pop_front();
+                tee.start += 1;
+            }
+            return Ok(Some(item));
+        }));
+    }
+    return Ok(new_tuple(iterators));
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testing::{eval, exec, python};
+
+    #[test]
+    fn test_infinite() {
+        let mut python = python("from itertools import *\n");
+        assert_eq!(eval(&mut python, "list(zip(count(5, 2), 'abc'))"), "[(5, 'a'), (7, 'b'), (9, 'c')]");
+        assert_eq!(eval(&mut python, "(list(islice(cycle('ab'), 5)), list(repeat(1, 3)), list(islice(count(), 2, 10, 3)))"), "(['a', 'b', 'a', 'b', 'a'], [1, 1, 1], [2, 5, 8])");
+    }
+
+    #[test]
+    fn test_combinatorics() {
+        let mut python = python("from itertools import *\n");
+        assert_eq!(eval(&mut python, "list(product('ab', range(2)))"), "[('a', 0), ('a', 1), ('b', 0), ('b', 1)]");
+        assert_eq!(eval(&mut python, "(list(product([1, 2], repeat=2)), list(product()), list(product([], [1])))"), "([(1, 1), (1, 2), (2, 1), (2, 2)], [()], [])");
+        assert_eq!(eval(&mut python, "list(permutations('abc', 2))"), "[('a', 'b'), ('a', 'c'), ('b', 'a'), ('b', 'c'), ('c', 'a'), ('c', 'b')]");
+        assert_eq!(eval(&mut python, "(len(list(permutations(range(4)))), list(combinations('abcd', 3)), list(combinations('a', 2)))"), "(24, [('a', 'b', 'c'), ('a', 'b', 'd'), ('a', 'c', 'd'), ('b', 'c', 'd')], [])");
+        assert_eq!(eval(&mut python, "(list(combinations_with_replacement('abc', 2)), list(combinations_with_replacement('', 2)), list(combinations_with_replacement('', 0)))"), "([('a', 'a'), ('a', 'b'), ('a', 'c'), ('b', 'b'), ('b', 'c'), ('c', 'c')], [], [()])");
+    }
+
+    #[test]
+    fn test_grouping() {
+        let mut python = python("from itertools import *\n");
+        assert_eq!(eval(&mut python, "(list(chain('ab', [1])), list(chain.from_iterable(['xy', 'z'])))"), "(['a', 'b', 1], ['x', 'y', 'z'])");
+        assert_eq!(eval(&mut python, "list(map(lambda kg: (kg[0], list(kg[1])), groupby('aabccca')))"), "[('a', ['a', 'a']), ('b', ['b']), ('c', ['c', 'c', 'c']), ('a', ['a'])]");
+        assert_eq!(eval(&mut python, "list(map(lambda kg: kg[0], groupby([1, 3, 2, 4, 5], key=lambda x: x % 2)))"), "[1, 0, 1]");
+        assert_eq!(eval(&mut python, "(list(accumulate([1, 2, 3])), list(accumulate([1, 2, 3], lambda a, b: a * b, initial=10)))"), "([1, 3, 6], [10, 10, 20, 60])");
+        assert_eq!(eval(&mut python, "list(zip_longest('ab', [1, 2, 3], fillvalue='-'))"), "[('a', 1), ('b', 2), ('-', 3)]");
+        exec(&mut python, "a, b = tee(iter([1, 2, 3]))\nfirst = next(a)\n");
+        assert_eq!(eval(&mut python, "(first, list(b), list(a))"), "(1, [1, 2, 3], [2, 3])");
+    }
+
+    #[test]
+    fn test_errors() {
+        let mut python = python("from itertools import *\n");
+        assert_eq!(eval(&mut python, "islice('abc', -1)"), "ValueError: Stop argument for islice() must be None or an integer: 0 <= x <= sys.maxsize.");
+        assert_eq!(eval(&mut python, "combinations('abc')"), "TypeError: combinations() missing required argument 'r' (pos 2)");
+        assert_eq!(eval(&mut python, "count('a')"), "TypeError: a number is required");
+        assert_eq!(eval(&mut python, "next(chain([1], 2, [3]), None)"), "1");
+    }
+}
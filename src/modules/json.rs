@@ -0,0 +1,809 @@
+use crate::ast::CmpOp;
+use crate::dict::{new_dict, Dict};
+use crate::interpreter::{compare, new_int, EvalResult, Interpreter};
+use crate::modules::builtins::merge_sort;
+use crate::native::bind;
+use crate::value::{format_float, new_list, new_tuple, Args, Builtin, BuiltinFn, Exception, Module, Value};
+use std::rc::Rc;
+
+/* json - reading and writing JSON, like CPython's json module
+ *
+ * dumps() writes dicts, lists, tuples, strs, ints, floats, booleans and
+ * None, and hands anything else to default=. loads() reads any JSON
+ * document, plus the NaN, Infinity and -Infinity CPython writes. dump()
+ * and load() do the same through a file-like object's write() and read().
+ *
+ * A document that doesn't parse raises JSONDecodeError, a ValueError
+ * whose message ends with the line, column and character index of the
+ * problem, like "Expecting value: line 2 column 5 (char 12)", and which
+ * has msg, doc, pos, lineno and colno attributes, as in CPython. A str can't hold a lone
+ * surrogate, so an unpaired \ud800-\udfff escape decodes to U+FFFD.
+ */
+
+pub fn build_module() -> Rc<Module> {
+    let module = Module::build_new("json", None);
+    let functions: &[(&str, BuiltinFn)] = &[("dumps", dumps), ("dump", dump), ("loads", loads), ("load", load)];
+    for (name, func) in functions {
+        module.set(name, Builtin::build_new(name, *func));
+    }
+    return Rc::new(module);
+}
+
+/* a keyword argument that isn't None
+ */
+fn option(args: &mut Args, name: &str) -> Option<Value> {
+    return args.keyword(name).filter(|value| !matches!(value, Value::NoneT));
+}
+
+fn flag(args: &mut Args, name: &str, default: bool) -> bool {
+    return args.keyword(name).map_or(default, |value| value.is_truthy());
+}
+
+/* dumps(obj, *, skipkeys=False, ensure_ascii=True, check_circular=True,
+ *       allow_nan=True, indent=None, separators=None, default=None,
+ *       sort_keys=False)
+ */
+fn dumps(interpreter: &mut Interpreter, mut args: Args) -> EvalResult {
+    let options = EncodeOptions::from_args(&mut args)?;
+    args.check("dumps", 1, 1)?;
+    return Ok(Value::Str(interpreter.encode_json(&args.positional[0], &options)?));
+}
+
+/* dump(obj, fp, **kw): dumps(obj, **kw) written to fp
+ */
+fn dump(interpreter: &mut Interpreter, mut args: Args) -> EvalResult {
+    let options = EncodeOptions::from_args(&mut args)?;
+    args.check("dump", 2, 2)?;
+    let text = interpreter.encode_json(&args.positional[0], &options)?;
+    let write = interpreter.get_attr(&args.positional[1], "write")?;
+    interpreter.call(&write, Args::build_new(vec![Value::Str(text)]))?;
+    return Ok(Value::NoneT);
+}
+
+/* loads(s, *, object_hook=None, parse_float=None, parse_int=None,
+ *       parse_constant=None, object_pairs_hook=None, strict=True)
+ */
+fn loads(interpreter: &mut Interpreter, mut args: Args) -> EvalResult {
+    let options = DecodeOptions::from_args(&mut args);
+    args.check("loads", 1, 1)?;
+    return interpreter.decode_json(document(&args.positional[0])?, &options);
+}
+
+/* load(fp, **kw): loads(fp.read(), **kw)
+ */
+fn load(interpreter: &mut Interpreter, mut args: Args) -> EvalResult {
+    let options = DecodeOptions::from_args(&mut args);
+    args.check("load", 1, 1)?;
+    let read = interpreter.get_attr(&args.positional[0], "read")?;
+    let text = interpreter.call(&read, Args::build_new(Vec::new()))?;
+    return interpreter.decode_json(document(&text)?, &options);
+}
+
+fn document(value: &Value) -> Result<&str, Exception> {
+    match value {
+        Value::Str(text) => return Ok(text),
+        other => {
+            let message = format!("the JSON object must be str, not {}", other.type_name());
+            return Err(Exception::new("TypeError", &message));
+        }
+    }
+}
+
+/* How dumps() writes a document. The defaults are CPython's.
+ */
+#[derive(Debug, Clone)]
+pub struct EncodeOptions {
+    // leave out dict keys that aren't str, int, float, bool or None
+    pub skipkeys: bool,
+    // escape everything outside printable ASCII
+    pub ensure_ascii: bool,
+    pub check_circular: bool,
+    // write NaN and Infinity rather than raising ValueError
+    pub allow_nan: bool,
+    // written once per level of nesting, with a newline per item
+    pub indent: Option<String>,
+    pub item_separator: String,
+    pub key_separator: String,
+    // called with any value JSON can't hold, for one it can
+    pub default: Option<Value>,
+    pub sort_keys: bool,
+}
+
+impl EncodeOptions {
+    pub fn build_new() -> EncodeOptions {
+        return EncodeOptions {
+            skipkeys: false,
+            ensure_ascii: true,
+            check_circular: true,
+            allow_nan: true,
+            indent: None,
+            item_separator: String::from(", "),
+            key_separator: String::from(": "),
+            default: None,
+            sort_keys: false,
+        };
+    }
+
+    fn from_args(args: &mut Args) -> Result<EncodeOptions, Exception> {
+        let mut options = EncodeOptions::build_new();
+        options.skipkeys = flag(args, "skipkeys", false);
+        options.ensure_ascii = flag(args, "ensure_ascii", true);
+        options.check_circular = flag(args, "check_circular", true);
+        options.allow_nan = flag(args, "allow_nan", true);
+        options.sort_keys = flag(args, "sort_keys", false);
+        options.default = option(args, "default");
+        options.indent = match option(args, "indent") {
+            None => None,
+            Some(Value::Int(spaces)) => Some(" ".repeat(spaces.max(0) as usize)),
+            Some(Value::Str(ref indent)) => Some(indent.clone()),
+            Some(other) => {
+                let message = format!("indent must be an int or str, not '{}'", other.type_name());
+                return Err(Exception::new("TypeError", &message));
+            }
+        };
+        // items already end in a newline when indenting
+        if options.indent.is_some() {
+            options.item_separator = String::from(",");
+        }
+        if let Some(separators) = option(args, "separators") {
+            let pair = match &separators {
+                Value::Tuple(items) => items.to_vec(),
+                Value::List(items) => items.borrow().clone(),
+                _ => Vec::new(),
+            };
+            match pair.as_slice() {
+                [Value::Str(item), Value::Str(key)] => {
+                    options.item_separator = item.clone();
+                    options.key_separator = key.clone();
+                }
+                _ => return Err(Exception::new("TypeError", "separators must be an (item_separator, key_separator) pair of str")),
+            };
+        }
+        return Ok(options);
+    }
+}
+
+/* How loads() reads a document: the hooks are called with what was read,
+ * and return the value to use in its place.
+ */
+#[derive(Debug, Clone)]
+pub struct DecodeOptions {
+    // called with each decoded dict
+    pub object_hook: Option<Value>,
+    // called with each object's (key, value) pairs as a list instead
+    pub object_pairs_hook: Option<Value>,
+    // called with the text of each number, or NaN/Infinity/-Infinity
+    pub parse_float: Option<Value>,
+    pub parse_int: Option<Value>,
+    pub parse_constant: Option<Value>,
+    // reject control characters inside strings
+    pub strict: bool,
+}
+
+impl DecodeOptions {
+    pub fn build_new() -> DecodeOptions {
+        return DecodeOptions {
+            object_hook: None,
+            object_pairs_hook: None,
+            parse_float: None,
+            parse_int: None,
+            parse_constant: None,
+            strict: true,
+        };
+    }
+
+    fn from_args(args: &mut Args) -> DecodeOptions {
+        return DecodeOptions {
+            object_hook: option(args, "object_hook"),
+            object_pairs_hook: option(args, "object_pairs_hook"),
+            parse_float: option(args, "parse_float"),
+            parse_int: option(args, "parse_int"),
+            parse_constant: option(args, "parse_constant"),
+            strict: flag(args, "strict", true),
+        };
+    }
+}
+
+impl Interpreter {
+    /* value as a JSON document
+     */
+    pub fn encode_json(&mut self, value: &Value, options: &EncodeOptions) -> Result<String, Exception> {
+        let mut encoder = Encoder {
+            interpreter: self,
+            options,
+            out: String::new(),
+            markers: Vec::new(),
+        };
+        encoder.encode(value)?;
+        return Ok(encoder.out);
+    }
+
+    /* the value a JSON document holds
+     */
+    pub fn decode_json(&mut self, text: &str, options: &DecodeOptions) -> EvalResult {
+        let mut decoder = Decoder {
+            interpreter: self,
+            options,
+            text: text.chars().collect(),
+            pos: 0,
+            depth: 0,
+        };
+        return decoder.decode();
+    }
+}
+
+struct Encoder<'a> {
+    interpreter: &'a mut Interpreter,
+    options: &'a EncodeOptions,
+    out: String,
+    // ids of the containers being written, outermost first
+    markers: Vec<usize>,
+}
+
+impl Encoder<'_> {
+    fn encode(&mut self, value: &Value) -> Result<(), Exception> {
+        self.interpreter.tick()?;
+        let options = self.options;
+        match value {
+            Value::NoneT => self.out.push_str("null"),
+            Value::Bool(true) => self.out.push_str("true"),
+            Value::Bool(false) => self.out.push_str("false"),
+            Value::Int(_) | Value::BigInt(_) => self.out.push_str(&value.to_string()),
+            Value::Float(val) => {
+                let text = self.float_str(*val)?;
+                self.out.push_str(&text);
+            }
+            Value::Str(val) => self.encode_str(val),
+            Value::List(items) => {
+                let items = items.borrow().clone();
+                self.encode_list(value, &items)?;
+            }
+            Value::Tuple(items) => self.encode_list(value, items)?,
+            // dicts, and subclasses of dict like Counter
+            Value::Dict(_) | Value::Object(_) if value.as_dict().is_some() => {
+                let items = value.as_dict().unwrap().borrow().items();
+                self.encode_dict(value, items)?;
+            }
+            _ => match &options.default {
+                Some(default) => {
+                    // a default() that returns its argument is a cycle
+                    self.enter(value)?;
+                    let replacement = self.interpreter.call(default, Args::build_new(vec![value.clone()]))?;
+                    self.encode(&replacement)?;
+                    self.markers.pop();
+                }
+                None => {
+                    let message = format!("Object of type {} is not JSON serializable", value.type_name());
+                    return Err(Exception::new("TypeError", &message));
+                }
+            },
+        };
+        return Ok(());
+    }
+
+    /* start writing a container, or a value passed to default()
+     */
+    fn enter(&mut self, value: &Value) -> Result<(), Exception> {
+        let identity = value.identity();
+        if self.options.check_circular && self.markers.contains(&identity) {
+            return Err(Exception::new("ValueError", "Circular reference detected"));
+        }
+        if self.markers.len() >= self.interpreter.limits.max_recursion {
+            return Err(Exception::new("RecursionError", "maximum recursion depth exceeded while encoding a JSON object"));
+        }
+        self.markers.push(identity);
+        return Ok(());
+    }
+
+    /* the line break and indentation before an item or closing bracket
+     */
+    fn newline(&mut self) {
+        if let Some(indent) = &self.options.indent {
+            self.out.push('\n');
+            for _ in 0..self.markers.len() {
+                self.out.push_str(indent);
+            }
+        }
+    }
+
+    fn float_str(&self, val: f64) -> Result<String, Exception> {
+        if val.is_finite() {
+            return Ok(format_float(val));
+        }
+        if !self.options.allow_nan {
+            let message = format!("Out of range float values are not JSON compliant: {}", format_float(val));
+            return Err(Exception::new("ValueError", &message));
+        }
+        if val.is_nan() {
+            return Ok(String::from("NaN"));
+        }
+        return Ok(String::from(if val > 0.0 { "Infinity" } else { "-Infinity" }));
+    }
+
+    fn encode_str(&mut self, val: &str) {
+        self.out.push('"');
+        for c in val.chars() {
+            match c {
+                '"' => self.out.push_str("\\\""),
+                '\\' => self.out.push_str("\\\\"),
+                '\n' => self.out.push_str("\\n"),
+                '\r' => self.out.push_str("\\r"),
+                '\t' => self.out.push_str("\\t"),
+                '\x08' => self.out.push_str("\\b"),
+                '\x0c' => self.out.push_str("\\f"),
+                c if (c as u32) < 0x20 || (self.options.ensure_ascii && !(' '..='~').contains(&c)) => {
+                    // chars outside the BMP as a surrogate pair
+                    let mut units = [0; 2];
+                    for unit in c.encode_utf16(&mut units) {
+                        self.out.push_str(&format!("\\u{:04x}", unit));
+                    }
+                }
+                c => self.out.push(c),
+            };
+        }
+        self.out.push('"');
+    }
+
+    fn encode_list(&mut self, value: &Value, items: &[Value]) -> Result<(), Exception> {
+        if items.is_empty() {
+            self.out.push_str("[]");
+            return Ok(());
+        }
+        self.enter(value)?;
+        self.out.push('[');
+        for (i, item) in items.iter().enumerate() {
+            if i > 0 {
+                self.out.push_str(&self.options.item_separator);
+            }
+            self.newline();
+            self.encode(item)?;
+        }
+        self.markers.pop();
+        self.newline();
+        self.out.push(']');
+        return Ok(());
+    }
+
+    fn encode_dict(&mut self, value: &Value, mut items: Vec<(Value, Value)>) -> Result<(), Exception> {
+        if items.is_empty() {
+            self.out.push_str("{}");
+            return Ok(());
+        }
+        if self.options.sort_keys {
+            items = merge_sort(items, false, &mut |left, right| compare(CmpOp::Lt, left, right))?;
+        }
+        self.enter(value)?;
+        self.out.push('{');
+        let mut first = true;
+        for (key, item) in items {
+            let key = match key {
+                Value::Str(ref key) => key.clone(),
+                Value::Bool(true) => String::from("true"),
+                Value::Bool(false) => String::from("false"),
+                Value::NoneT => String::from("null"),
+                Value::Int(_) | Value::BigInt(_) => key.to_string(),
+                Value::Float(key) => self.float_str(key)?,
+                _ if self.options.skipkeys => continue,
+                other => {
+                    let message = format!("keys must be str, int, float, bool or None, not {}", other.type_name());
+                    return Err(Exception::new("TypeError", &message));
+                }
+            };
+            if !first {
+                self.out.push_str(&self.options.item_separator);
+            }
+            first = false;
+            self.newline();
+            self.encode_str(&key);
+            self.out.push_str(&self.options.key_separator);
+            self.encode(&item)?;
+        }
+        self.markers.pop();
+        self.newline();
+        self.out.push('}');
+        return Ok(());
+    }
+}
+
+/* JSONDecodeError for a problem at char index pos of the document
+ */
+fn decode_error(message: &str, text: &[char], pos: usize) -> Exception {
+    let (lineno, colno) = line_and_column(text, pos);
+    let mut err = Exception::new("JSONDecodeError", &decode_message(message, lineno, colno, pos));
+    let doc = Value::Str(text.iter().collect());
+    err.details_mut().args = Some(vec![Value::Str(String::from(message)), doc, Value::Int(pos as i64)]);
+    return err;
+}
+
+fn decode_message(message: &str, lineno: usize, colno: usize, pos: usize) -> String {
+    return format!("{}: line {} column {} (char {})", message, lineno, colno, pos);
+}
+
+/* the line and column, from 1, of char index pos of text
+ */
+fn line_and_column(text: &[char], pos: usize) -> (usize, usize) {
+    let before = &text[..pos.min(text.len())];
+    let lineno = before.iter().filter(|c| **c == '\n').count() + 1;
+    let colno = match before.iter().rposition(|c| *c == '\n') {
+        Some(newline) => pos - newline,
+        None => pos + 1,
+    };
+    return (lineno, colno);
+}
+
+/* JSONDecodeError(msg, doc, pos), a ValueError with those and the lineno
+ * and colno of pos as attributes, and the full message as its only arg
+ */
+pub fn decode_error_init(_interpreter: &mut Interpreter, mut args: Args) -> EvalResult {
+    if args.positional.is_empty() {
+        return Err(Exception::new("TypeError", "descriptor '__init__' requires a 'BaseException' object"));
+    }
+    let instance = args.positional.remove(0);
+    let bound = bind("JSONDecodeError", args, &["msg", "doc", "pos"], 3)?;
+    let (msg, doc, pos) = match (&bound[0], &bound[1], &bound[2]) {
+        (Some(Value::Str(msg)), Some(Value::Str(doc)), Some(Value::Int(pos))) if *pos >= 0 => (msg, doc, *pos as usize),
+        _ => return Err(Exception::new("TypeError", "JSONDecodeError takes a str msg and doc and an int pos")),
+    };
+    let (lineno, colno) = line_and_column(&doc.chars().collect::<Vec<char>>(), pos);
+    if let Value::Object(object) = &instance {
+        let mut attrs = object.attrs.borrow_mut();
+        let message = Value::Str(decode_message(msg, lineno, colno, pos));
+        attrs.insert(String::from("args"), new_tuple(vec![message]));
+        for (name, value) in [("msg", &bound[0]), ("doc", &bound[1]), ("pos", &bound[2])] {
+            attrs.insert(String::from(name), value.clone().unwrap());
+        }
+        attrs.insert(String::from("lineno"), Value::Int(lineno as i64));
+        attrs.insert(String::from("colno"), Value::Int(colno as i64));
+    }
+    return Ok(Value::NoneT);
+}
+
+struct Decoder<'a> {
+    interpreter: &'a mut Interpreter,
+    options: &'a DecodeOptions,
+    text: Vec<char>,
+    pos: usize,
+    // objects and arrays the decoder is inside
+    depth: usize,
+}
+
+impl Decoder<'_> {
+    fn decode(&mut self) -> EvalResult {
+        if self.text.first() == Some(&'\u{feff}') {
+            return Err(self.error("Unexpected UTF-8 BOM (decode using utf-8-sig)", 0));
+        }
+        self.skip_whitespace();
+        let value = self.value()?;
+        self.skip_whitespace();
+        if self.pos < self.text.len() {
+            return Err(self.error("Extra data", self.pos));
+        }
+        return Ok(value);
+    }
+
+    fn error(&self, message: &str, pos: usize) -> Exception {
+        return decode_error(message, &self.text, pos);
+    }
+
+    fn peek(&self) -> Option<char> {
+        return self.text.get(self.pos).copied();
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(' ' | '\t' | '\n' | '\r') = self.peek() {
+            self.pos += 1;
+        }
+    }
+
+    /* whether word is next, and if so move past it
+     */
+    fn keyword(&mut self, word: &str) -> bool {
+        let end = self.pos + word.len();
+        if end <= self.text.len() && self.text[self.pos..end].iter().copied().eq(word.chars()) {
+            self.pos = end;
+            return true;
+        }
+        return false;
+    }
+
+    fn value(&mut self) -> EvalResult {
+        self.interpreter.tick()?;
+        match self.peek() {
+            Some('"') => return Ok(Value::Str(self.string()?)),
+            Some('{') => return self.object(),
+            Some('[') => return self.array(),
+            _ => (),
+        };
+        if self.keyword("null") {
+            return Ok(Value::NoneT);
+        } else if self.keyword("true") {
+            return Ok(Value::Bool(true));
+        } else if self.keyword("false") {
+            return Ok(Value::Bool(false));
+        }
+        for (word, val) in &[("NaN", f64::NAN), ("Infinity", f64::INFINITY), ("-Infinity", f64::NEG_INFINITY)] {
+            if self.keyword(word) {
+                return match &self.options.parse_constant {
+                    Some(hook) => self.interpreter.call(hook, Args::build_new(vec![Value::Str(String::from(*word))])),
+                    None => Ok(Value::Float(*val)),
+                };
+            }
+        }
+        return self.number();
+    }
+
+    /* -?(0|[1-9][0-9]*)(\.[0-9]+)?([eE][-+]?[0-9]+)?
+     */
+    fn number(&mut self) -> EvalResult {
+        let start = self.pos;
+        let digit = |decoder: &Self, offset: usize| decoder.text.get(decoder.pos + offset).is_some_and(char::is_ascii_digit);
+        let skip_digits = |decoder: &mut Self| {
+            while digit(decoder, 0) {
+                decoder.pos += 1;
+            }
+        };
+        if self.peek() == Some('-') {
+            self.pos += 1;
+        }
+        match self.peek() {
+            Some('0') => self.pos += 1,
+            Some('1'..='9') => skip_digits(self),
+            _ => return Err(self.error("Expecting value", start)),
+        };
+        let mut is_float = false;
+        if self.peek() == Some('.') && digit(self, 1) {
+            self.pos += 1;
+            skip_digits(self);
+            is_float = true;
+        }
+        if let Some('e' | 'E') = self.peek() {
+            let sign = matches!(self.text.get(self.pos + 1), Some('-' | '+')) as usize;
+            if digit(self, 1 + sign) {
+                self.pos += 1 + sign;
+                skip_digits(self);
+                is_float = true;
+            }
+        }
+        let number: String = self.text[start..self.pos].iter().collect();
+        let hook = if is_float { &self.options.parse_float } else { &self.options.parse_int };
+        if let Some(hook) = hook {
+            return self.interpreter.call(hook, Args::build_new(vec![Value::Str(number)]));
+        }
+        if is_float {
+            return Ok(Value::Float(number.parse().unwrap()));
+        }
+        return Ok(new_int(number.parse().unwrap()));
+    }
+
+    /* a string, from its opening quote
+     */
+    fn string(&mut self) -> Result<String, Exception> {
+        let start = self.pos;
+        self.pos += 1;
+        let mut out = String::new();
+        loop {
+            let c = match self.peek() {
+                Some(c) => c,
+                None => return Err(self.error("Unterminated string starting at", start)),
+            };
+            self.pos += 1;
+            match c {
+                '"' => return Ok(out),
+                '\\' => self.escape(start, &mut out)?,
+                c if (c as u32) < 0x20 && self.options.strict => {
+                    return Err(self.error("Invalid control character at", self.pos - 1));
+                }
+                c => out.push(c),
+            };
+        }
+    }
+
+    /* the escape after a backslash
+     */
+    fn escape(&mut self, start: usize, out: &mut String) -> Result<(), Exception> {
+        let c = match self.peek() {
+            Some(c) => c,
+            None => return Err(self.error("Unterminated string starting at", start)),
+        };
+        self.pos += 1;
+        let unescaped = match c {
+            '"' => '"',
+            '\\' => '\\',
+            '/' => '/',
+            'b' => '\x08',
+            'f' => '\x0c',
+            'n' => '\n',
+            'r' => '\r',
+            't' => '\t',
+            'u' => {
+                let unit = self.hex4()?;
+                let mut decoded = char::from_u32(unit);
+                // a high surrogate pairs with a following \u low surrogate
+                if (0xd800..0xdc00).contains(&unit) && self.keyword("\\u") {
+                    let low = self.hex4()?;
+                    if (0xdc00..0xe000).contains(&low) {
+                        decoded = char::from_u32(0x10000 + ((unit - 0xd800) << 10) + (low - 0xdc00));
+                    } else {
+                        out.push(char::REPLACEMENT_CHARACTER);
+                        decoded = char::from_u32(low);
+                    }
+                }
+                decoded.unwrap_or(char::REPLACEMENT_CHARACTER)
+            }
+            _ => return Err(self.error("Invalid \\escape", self.pos - 2)),
+        };
+        out.push(unescaped);
+        return Ok(());
+    }
+
+    /* the four hex digits after \u
+     */
+    fn hex4(&mut self) -> Result<u32, Exception> {
+        let digits: Option<String> = self.text.get(self.pos..self.pos + 4).map(|digits| digits.iter().collect());
+        match digits.as_deref().filter(|digits| digits.chars().all(|c| c.is_ascii_hexdigit())) {
+            Some(digits) => {
+                self.pos += 4;
+                return Ok(u32::from_str_radix(digits, 16).unwrap());
+            }
+            None => return Err(self.error("Invalid \\uXXXX escape", self.pos - 1)),
+        }
+    }
+
+    /* start an object or array, from its opening bracket
+     */
+    fn enter(&mut self) -> Result<(), Exception> {
+        if self.depth >= self.interpreter.limits.max_recursion {
+            return Err(Exception::new("RecursionError", "maximum recursion depth exceeded while decoding a JSON document"));
+        }
+        self.depth += 1;
+        self.pos += 1;
+        self.skip_whitespace();
+        return Ok(());
+    }
+
+    /* after an item: whether the closing bracket follows, else move past
+     * the comma before the next item
+     */
+    fn next_item(&mut self, close: char, container: &str) -> Result<bool, Exception> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some(c) if c == close => {
+                self.pos += 1;
+                return Ok(true);
+            }
+            Some(',') => {
+                let comma = self.pos;
+                self.pos += 1;
+                self.skip_whitespace();
+                if self.peek() == Some(close) {
+                    let message = format!("Illegal trailing comma before end of {}", container);
+                    return Err(self.error(&message, comma));
+                }
+                return Ok(false);
+            }
+            _ => return Err(self.error("Expecting ',' delimiter", self.pos)),
+        }
+    }
+
+    fn array(&mut self) -> EvalResult {
+        self.enter()?;
+        let mut items = Vec::new();
+        if self.peek() == Some(']') {
+            self.pos += 1;
+        } else {
+            loop {
+                items.push(self.value()?);
+                if self.next_item(']', "array")? {
+                    break;
+                }
+            }
+        }
+        self.depth -= 1;
+        return Ok(new_list(items));
+    }
+
+    fn object(&mut self) -> EvalResult {
+        self.enter()?;
+        let mut pairs = Vec::new();
+        if self.peek() == Some('}') {
+            self.pos += 1;
+        } else {
+            loop {
+                if self.peek() != Some('"') {
+                    return Err(self.error("Expecting property name enclosed in double quotes", self.pos));
+                }
+                let key = self.string()?;
+                self.skip_whitespace();
+                if self.peek() != Some(':') {
+                    return Err(self.error("Expecting ':' delimiter", self.pos));
+                }
+                self.pos += 1;
+                self.skip_whitespace();
+                pairs.push((Value::Str(key), self.value()?));
+                if self.next_item('}', "object")? {
+                    break;
+                }
+            }
+        }
+        self.depth -= 1;
+
+        if let Some(hook) = &self.options.object_pairs_hook {
+            let pairs = pairs.into_iter().map(|(key, value)| new_tuple(vec![key, value])).collect();
+            return self.interpreter.call(hook, Args::build_new(vec![new_list(pairs)]));
+        }
+        let mut dict = Dict::build_new();
+        for (key, value) in pairs {
+            dict.set(key, value)?;
+        }
+        let dict = new_dict(dict);
+        if let Some(hook) = &self.options.object_hook {
+            return self.interpreter.call(hook, Args::build_new(vec![dict]));
+        }
+        return Ok(dict);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testing::{error, eval, eval_str, exec, python};
+
+    #[test]
+    fn test_dumps() {
+        let mut python = python("import json\n");
+        let source = "json.dumps({'a': [1, 2.5, None, True], 'b': ('x', {})})";
+        assert_eq!(eval_str(&mut python, source), r#"{"a": [1, 2.5, null, true], "b": ["x", {}]}"#);
+        let source = "json.dumps({'b': 1, 'a': [1, []]}, indent=2, sort_keys=True)";
+        assert_eq!(eval_str(&mut python, source), "{\n  \"a\": [\n    1,\n    []\n  ],\n  \"b\": 1\n}");
+        let source = "json.dumps({1: 'caf\u{e9} \u{1f600}', None: float('inf')}, separators=(',', ':'))";
+        assert_eq!(eval_str(&mut python, source), r#"{"1":"caf\u00e9 \ud83d\ude00","null":Infinity}"#);
+        let source = "json.dumps('caf\u{e9}\\n', ensure_ascii=False)";
+        assert_eq!(eval_str(&mut python, source), "\"caf\u{e9}\\n\"");
+        assert_eq!(eval_str(&mut python, "json.dumps(range(3), default=list)"), "[0, 1, 2]");
+    }
+
+    #[test]
+    fn test_loads() {
+        let mut python = python("import json\n");
+        let source = r#"json.loads(' {"a": [1, -2.5e1, "é😀\\t"], "b": {"c": NaN}} ')"#;
+        assert_eq!(eval(&mut python, source), "{'a': [1, -25.0, 'é😀\\t'], 'b': {'c': nan}}");
+        let source = r#"json.loads('{"a": {"b": 1}}', object_hook=len)"#;
+        assert_eq!(eval(&mut python, source), "1");
+        let source = r#"json.loads('[1, 2.0]', parse_int=str, parse_float=str)"#;
+        assert_eq!(eval(&mut python, source), "['1', '2.0']");
+    }
+
+    #[test]
+    fn test_decode_errors() {
+        let mut python = python("import json\n");
+        let message = "JSONDecodeError: Expecting ',' delimiter: line 2 column 5 (char 8)";
+        assert_eq!(error(&mut python, r#"json.loads('[1,\n  2 3]')"#), message);
+        let message = "JSONDecodeError: Expecting value: line 1 column 1 (char 0)";
+        assert_eq!(error(&mut python, "json.loads('')"), message);
+        let message = "JSONDecodeError: Unterminated string starting at: line 1 column 2 (char 1)";
+        assert_eq!(error(&mut python, r#"json.loads('["abc')"#), message);
+
+        let source = r#"
+try:
+    json.loads('[1,\n  2 3]')
+except ValueError as err:
+    caught = err
+"#;
+        exec(&mut python, source);
+        let attrs = "(caught.msg, caught.doc, caught.pos, caught.lineno, caught.colno, caught.args)";
+        let expected = r#"("Expecting ',' delimiter", '[1,\n  2 3]', 8, 2, 5, ("Expecting ',' delimiter: line 2 column 5 (char 8)",))"#;
+        assert_eq!(eval(&mut python, attrs), expected);
+        exec(&mut python, "made = json.JSONDecodeError('Oops', 'ab\\ncd', 4)\n");
+        assert_eq!(eval(&mut python, "(str(made), made.lineno, made.colno, isinstance(made, ValueError))"), "('Oops: line 2 column 2 (char 4)', 2, 2, True)");
+    }
+
+    #[test]
+    fn test_encode_errors() {
+        let mut python = python("import json\n");
+        let message = "ValueError: Out of range float values are not JSON compliant: nan";
+        assert_eq!(error(&mut python, "json.dumps(float('nan'), allow_nan=False)"), message);
+        let message = "TypeError: Object of type range is not JSON serializable";
+        assert_eq!(error(&mut python, "json.dumps([range(1)])"), message);
+        exec(&mut python, "cycle = [1]\ncycle[0:0] = [cycle]\n");
+        assert_eq!(error(&mut python, "json.dumps(cycle)"), "ValueError: Circular reference detected");
+    }
+}
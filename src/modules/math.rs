@@ -0,0 +1,1044 @@
+use crate::ast::BinOp;
+use crate::interpreter::{as_bigint, as_int, binary_op, int_to_float, new_int, not_an_int, EvalResult, Interpreter};
+use crate::modules::builtins::float_to_int;
+use crate::modules::{decimal, fractions};
+use crate::value::{new_tuple, Args, Builtin, BuiltinFn, Exception, Module, Value};
+use num_bigint::BigInt;
+use num_integer::Integer;
+use num_traits::{One, Signed, ToPrimitive, Zero};
+use std::f64::consts::{E, LN_2, PI, TAU};
+use std::rc::Rc;
+
+/* math - floating point functions, like CPython's math module
+ *
+ * Functions take ints, floats and bools, and instances whose class
+ * defines __float__. A result that isn't a number for its argument (e.g.
+ * sqrt(-1), log(0)) raises ValueError "math domain error", and a finite
+ * argument whose result is too large for a float (e.g. exp(1000)) raises
+ * OverflowError "math range error", as CPython does.
+ *
+ * erf and erfc come from libm, the same fdlibm code as the C library that
+ * CPython calls, so they give the same results.
+ */
+pub fn build_module() -> Rc<Module> {
+    let module = Module::build_new("math", None);
+    // one-argument float functions: (name, function, whether an infinite
+    // result for a finite argument is an overflow rather than a domain error)
+    let unary: &[Unary] = &[
+        ("acos", f64::acos, false),
+        ("acosh", acosh, false),
+        ("asin", f64::asin, false),
+        ("asinh", asinh, false),
+        ("atan", f64::atan, false),
+        ("atanh", atanh, false),
+        ("cbrt", f64::cbrt, false),
+        ("cos", f64::cos, false),
+        ("cosh", f64::cosh, true),
+        ("degrees", degrees, false),
+        ("erf", libm::erf, false),
+        ("erfc", libm::erfc, false),
+        ("exp", f64::exp, true),
+        ("exp2", f64::exp2, true),
+        ("expm1", f64::exp_m1, true),
+        ("fabs", f64::abs, false),
+        ("log10", f64::log10, false),
+        ("log1p", f64::ln_1p, false),
+        ("log2", f64::log2, false),
+        ("radians", radians, false),
+        ("sin", f64::sin, false),
+        ("sinh", f64::sinh, true),
+        ("sqrt", f64::sqrt, false),
+        ("tan", f64::tan, false),
+        ("tanh", f64::tanh, false),
+    ];
+    for (name, func, can_overflow) in unary {
+        let (name, func, can_overflow) = (*name, *func, *can_overflow);
+        let function = move |interpreter: &mut Interpreter, args: Args| {
+            args.check(name, 1, 1)?;
+            let x = real(interpreter, &args.positional[0])?;
+            return Ok(Value::Float(math_1(x, func(x), can_overflow)?));
+        };
+        module.set(name, Builtin::build_native(name, Rc::new(function)));
+    }
+    // two-argument float functions, which fail like math_1 does
+    let binary: &[Binary] =
+        &[("atan2", f64::atan2), ("copysign", f64::copysign), ("remainder", remainder)];
+    for (name, func) in binary {
+        let (name, func) = (*name, *func);
+        let function = move |interpreter: &mut Interpreter, args: Args| {
+            args.check(name, 2, 2)?;
+            let x = real(interpreter, &args.positional[0])?;
+            let y = real(interpreter, &args.positional[1])?;
+            return Ok(Value::Float(math_2(x, y, func(x, y))?));
+        };
+        module.set(name, Builtin::build_native(name, Rc::new(function)));
+    }
+    let functions: &[(&str, BuiltinFn)] = &[
+        ("ceil", ceil),
+        ("comb", comb),
+        ("dist", dist),
+        ("factorial", factorial),
+        ("floor", floor),
+        ("fmod", fmod),
+        ("frexp", frexp),
+        ("fsum", fsum),
+        ("gamma", gamma),
+        ("gcd", gcd),
+        ("hypot", hypot),
+        ("isclose", isclose),
+        ("isfinite", isfinite),
+        ("isinf", isinf),
+        ("isnan", isnan),
+        ("isqrt", isqrt),
+        ("lcm", lcm),
+        ("ldexp", ldexp),
+        ("lgamma", lgamma),
+        ("log", log),
+        ("modf", modf),
+        ("nextafter", nextafter),
+        ("perm", perm),
+        ("pow", pow),
+        ("prod", prod),
+        ("trunc", trunc),
+        ("ulp", ulp),
+    ];
+    for (name, func) in functions {
+        module.set(name, Builtin::build_new(name, *func));
+    }
+    module.set("pi", Value::Float(PI));
+    module.set("e", Value::Float(E));
+    module.set("tau", Value::Float(TAU));
+    module.set("inf", Value::Float(f64::INFINITY));
+    module.set("nan", Value::Float(f64::NAN));
+    return Rc::new(module);
+}
+
+// (name, function, whether it can overflow)
+type Unary = (&'static str, fn(f64) -> f64, bool);
+type Binary = (&'static str, fn(f64, f64) -> f64);
+
+pub(crate) fn domain_error() -> Exception {
+    return Exception::new("ValueError", "math domain error");
+}
+
+pub(crate) fn range_error() -> Exception {
+    return Exception::new("OverflowError", "math range error");
+}
+
+/* float value of a real number argument
+ */
+pub(crate) fn real(interpreter: &mut Interpreter, value: &Value) -> Result<f64, Exception> {
+    match value {
+        Value::Float(val) => return Ok(*val),
+        Value::Int(_) | Value::Bool(_) => return Ok(as_int(value).unwrap() as f64),
+        Value::BigInt(val) => return int_to_float(val),
+        Value::Fraction(val) => return Ok(fractions::to_f64(val)),
+        Value::Decimal(val) => return decimal::to_float(val),
+        _ => (),
+    };
+    match interpreter.call_special(value, "__float__", Vec::new()) {
+        Some(result) => match result? {
+            Value::Float(val) => return Ok(val),
+            other => {
+                let message = format!(
+                    "{}.__float__ returned non-float (type {})",
+                    value.type_name(),
+                    other.type_name()
+                );
+                return Err(Exception::new("TypeError", &message));
+            }
+        },
+        None => {
+            let message = format!("must be real number, not {}", value.type_name());
+            return Err(Exception::new("TypeError", &message));
+        }
+    }
+}
+
+/* int value of an integer argument
+ */
+fn big_index(interpreter: &mut Interpreter, value: &Value) -> Result<BigInt, Exception> {
+    if let Some(val) = as_bigint(value) {
+        return Ok(val);
+    }
+    if let Some(result) = interpreter.call_special(value, "__index__", Vec::new()) {
+        let result = result?;
+        return as_bigint(&result).ok_or_else(|| {
+            let message = format!("__index__ returned non-int (type {})", result.type_name());
+            return Exception::new("TypeError", &message);
+        });
+    }
+    return Err(not_an_int(value));
+}
+
+/* big_index() of an argument that must fit 64 bits
+ */
+fn index(interpreter: &mut Interpreter, value: &Value) -> Result<i64, Exception> {
+    let val = big_index(interpreter, value)?;
+    return val.to_i64().ok_or_else(|| not_an_int(&new_int(val)));
+}
+
+/* The errors of a one-argument function, as CPython's math_1 works them
+ * out from its result: NaN from a number is a domain error, and infinity
+ * from a finite number is an overflow or, e.g. for log(0), a domain error
+ */
+fn math_1(x: f64, result: f64, can_overflow: bool) -> Result<f64, Exception> {
+    if result.is_nan() && !x.is_nan() {
+        return Err(domain_error());
+    }
+    if result.is_infinite() && x.is_finite() {
+        return Err(if can_overflow { range_error() } else { domain_error() });
+    }
+    return Ok(result);
+}
+
+fn math_2(x: f64, y: f64, result: f64) -> Result<f64, Exception> {
+    if result.is_nan() && !x.is_nan() && !y.is_nan() {
+        return Err(domain_error());
+    }
+    if result.is_infinite() && x.is_finite() && y.is_finite() {
+        return Err(range_error());
+    }
+    return Ok(result);
+}
+
+fn degrees(x: f64) -> f64 {
+    return x * (180.0 / PI);
+}
+
+fn radians(x: f64) -> f64 {
+    return x * (PI / 180.0);
+}
+
+/* IEEE 754 remainder: x - n*y for the integer n nearest x/y, ties to even
+ */
+fn remainder(x: f64, y: f64) -> f64 {
+    if x.is_finite() && y.is_finite() {
+        if y == 0.0 {
+            return f64::NAN;
+        }
+        let (absx, absy) = (x.abs(), y.abs());
+        let m = absx % absy;
+        let c = absy - m;
+        let r = if m < c {
+            m
+        } else if m > c {
+            -c
+        } else {
+            // half way: whichever of m and -c makes n even
+            m - 2.0 * ((0.5 * (absx - m)) % absy)
+        };
+        return 1.0_f64.copysign(x) * r;
+    }
+    if x.is_nan() {
+        return x;
+    }
+    if y.is_nan() {
+        return y;
+    }
+    if x.is_infinite() {
+        return f64::NAN;
+    }
+    return x;
+}
+
+/* The inverse hyperbolic functions, as the C library (fdlibm) computes
+ * them; Rust's own are a little less accurate
+ */
+pub(crate) fn asinh(x: f64) -> f64 {
+    let ax = x.abs();
+    if !x.is_finite() || ax < 2f64.powi(-28) {
+        return x;
+    }
+    let w = if ax > 2f64.powi(28) {
+        ax.ln() + LN_2
+    } else if ax > 2.0 {
+        (2.0 * ax + 1.0 / ((x * x + 1.0).sqrt() + ax)).ln()
+    } else {
+        let t = x * x;
+        (ax + t / (1.0 + (1.0 + t).sqrt())).ln_1p()
+    };
+    return w.copysign(x);
+}
+
+fn acosh(x: f64) -> f64 {
+    if x < 1.0 {
+        return f64::NAN;
+    }
+    if x >= 2f64.powi(28) {
+        return if x.is_infinite() { x } else { x.ln() + LN_2 };
+    }
+    if x > 2.0 {
+        return (2.0 * x - 1.0 / (x + (x * x - 1.0).sqrt())).ln();
+    }
+    let t = x - 1.0;
+    return (t + (2.0 * t + t * t).sqrt()).ln_1p();
+}
+
+fn atanh(x: f64) -> f64 {
+    let ax = x.abs();
+    let t = if ax < 2f64.powi(-28) {
+        return x;
+    } else if ax < 0.5 {
+        let t = ax + ax;
+        0.5 * (t + t * ax / (1.0 - ax)).ln_1p()
+    } else if ax < 1.0 {
+        0.5 * ((ax + ax) / (1.0 - ax)).ln_1p()
+    } else if ax == 1.0 {
+        f64::INFINITY
+    } else {
+        f64::NAN
+    };
+    return t.copysign(x);
+}
+
+/* gamma and lgamma use CPython's Lanczos approximation
+ */
+const LANCZOS_G: f64 = 6.024_680_040_776_73;
+const LANCZOS_G_MINUS_HALF: f64 = 5.524_680_040_776_73;
+const LANCZOS_NUM_COEFFS: [f64; 13] = [
+    23_531_376_880.410_76,
+    42_919_803_642.649_1,
+    35_711_959_237.355_67,
+    17_921_034_426.037_21,
+    6_039_542_586.352_028,
+    1_439_720_407.311_721_6,
+    248_874_557.862_054_17,
+    31_426_415.585_400_194,
+    2_876_370.628_935_372_5,
+    186_056.265_395_223_48,
+    8_071.672_002_365_816,
+    210.824_277_751_579_36,
+    2.506_628_274_631_000_2,
+];
+const LANCZOS_DEN_COEFFS: [f64; 13] = [
+    0.0,
+    39_916_800.0,
+    120_543_840.0,
+    150_917_976.0,
+    105_258_076.0,
+    45_995_730.0,
+    13_339_535.0,
+    2_637_558.0,
+    357_423.0,
+    32_670.0,
+    1_925.0,
+    66.0,
+    1.0,
+];
+
+fn lanczos_sum(x: f64) -> f64 {
+    let (mut num, mut den) = (0.0, 0.0);
+    // evaluated from the end that keeps the terms from overflowing
+    if x < 5.0 {
+        for i in (0..13).rev() {
+            num = num * x + LANCZOS_NUM_COEFFS[i];
+            den = den * x + LANCZOS_DEN_COEFFS[i];
+        }
+    } else {
+        for i in 0..13 {
+            num = num / x + LANCZOS_NUM_COEFFS[i];
+            den = den / x + LANCZOS_DEN_COEFFS[i];
+        }
+    }
+    return num / den;
+}
+
+/* sin(pi * x), exactly 0 at integers
+ */
+fn sin_pi(x: f64) -> f64 {
+    let y = x.abs() % 2.0;
+    let r = match (2.0 * y).round() as i64 {
+        0 => (PI * y).sin(),
+        1 => (PI * (y - 0.5)).cos(),
+        2 => (PI * (1.0 - y)).sin(),
+        3 => -(PI * (y - 1.5)).cos(),
+        _ => (PI * (y - 2.0)).sin(),
+    };
+    return 1.0_f64.copysign(x) * r;
+}
+
+fn gamma_of(x: f64) -> Result<f64, Exception> {
+    if !x.is_finite() {
+        if x.is_nan() || x > 0.0 {
+            return Ok(x);
+        }
+        return Err(domain_error());
+    }
+    if x == 0.0 {
+        return Err(domain_error());
+    }
+    if x == x.floor() {
+        if x < 0.0 {
+            return Err(domain_error());
+        }
+        // exact for the factorials a float can hold
+        if x <= 23.0 {
+            return Ok((1..x as i64).fold(1.0, |acc, n| acc * n as f64));
+        }
+    }
+    let absx = x.abs();
+    if absx < 1e-20 {
+        let r = 1.0 / x;
+        return if r.is_infinite() { Err(range_error()) } else { Ok(r) };
+    }
+    if absx > 200.0 {
+        if x < 0.0 {
+            return Ok(0.0 / sin_pi(x));
+        }
+        return Err(range_error());
+    }
+    let y = absx + LANCZOS_G_MINUS_HALF;
+    // the error in computing y
+    let z = if absx > LANCZOS_G_MINUS_HALF {
+        (y - absx) - LANCZOS_G_MINUS_HALF
+    } else {
+        (y - LANCZOS_G_MINUS_HALF) - absx
+    };
+    let z = z * LANCZOS_G / y;
+    let mut r;
+    if x < 0.0 {
+        r = -PI / sin_pi(absx) / absx * y.exp() / lanczos_sum(absx);
+        r -= z * r;
+        if absx < 140.0 {
+            r /= y.powf(absx - 0.5);
+        } else {
+            let sqrtpow = y.powf(absx / 2.0 - 0.25);
+            r /= sqrtpow;
+            r /= sqrtpow;
+        }
+    } else {
+        r = lanczos_sum(absx) / y.exp();
+        r += z * r;
+        if absx < 140.0 {
+            r *= y.powf(absx - 0.5);
+        } else {
+            let sqrtpow = y.powf(absx / 2.0 - 0.25);
+            r *= sqrtpow;
+            r *= sqrtpow;
+        }
+    }
+    if r.is_infinite() {
+        return Err(range_error());
+    }
+    return Ok(r);
+}
+
+fn lgamma_of(x: f64) -> Result<f64, Exception> {
+    if !x.is_finite() {
+        return Ok(if x.is_nan() { x } else { f64::INFINITY });
+    }
+    if x == x.floor() && x <= 2.0 {
+        if x <= 0.0 {
+            return Err(domain_error());
+        }
+        return Ok(0.0);
+    }
+    let absx = x.abs();
+    if absx < 1e-20 {
+        return Ok(-absx.ln());
+    }
+    let mut r = lanczos_sum(absx).ln() - LANCZOS_G;
+    r += (absx - 0.5) * ((absx + LANCZOS_G - 0.5).ln() - 1.0);
+    if x < 0.0 {
+        r = PI.ln() - sin_pi(absx).abs().ln() - absx.ln() - r;
+    }
+    if r.is_infinite() {
+        return Err(range_error());
+    }
+    return Ok(r);
+}
+
+fn gamma(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("gamma", 1, 1)?;
+    let x = real(interpreter, &args.positional[0])?;
+    return Ok(Value::Float(gamma_of(x)?));
+}
+
+fn lgamma(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("lgamma", 1, 1)?;
+    let x = real(interpreter, &args.positional[0])?;
+    return Ok(Value::Float(lgamma_of(x)?));
+}
+
+/* floor(), ceil() and trunc() of an int are the int, of a float the
+ * nearest int in that direction, and of an instance what its __floor__,
+ * __ceil__ or __trunc__ returns
+ */
+fn to_integral(interpreter: &mut Interpreter, value: &Value, special: &str, func: fn(f64) -> f64) -> EvalResult {
+    match value {
+        Value::Int(_) | Value::Bool(_) | Value::BigInt(_) => return Ok(new_int(as_bigint(value).unwrap())),
+        Value::Float(val) => return float_to_int(func(*val)),
+        // exactly, not through a float
+        Value::Fraction(val) => {
+            let val = match special {
+                "__floor__" => val.floor(),
+                "__ceil__" => val.ceil(),
+                _ => val.trunc(),
+            };
+            return fractions::int_value(val.numer());
+        }
+        Value::Decimal(val) => {
+            let rounding = match special {
+                "__floor__" => decimal::Rounding::Floor,
+                "__ceil__" => decimal::Rounding::Ceiling,
+                _ => decimal::Rounding::Down,
+            };
+            return fractions::int_value(&val.to_bigint(rounding)?);
+        }
+        _ => (),
+    };
+    if let Some(result) = interpreter.call_special(value, special, Vec::new()) {
+        return result;
+    }
+    if special == "__trunc__" {
+        let message = format!("type {} doesn't define __trunc__ method", value.type_name());
+        return Err(Exception::new("TypeError", &message));
+    }
+    return float_to_int(func(real(interpreter, value)?));
+}
+
+fn floor(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("floor", 1, 1)?;
+    return to_integral(interpreter, &args.positional[0], "__floor__", f64::floor);
+}
+
+fn ceil(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("ceil", 1, 1)?;
+    return to_integral(interpreter, &args.positional[0], "__ceil__", f64::ceil);
+}
+
+fn trunc(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("trunc", 1, 1)?;
+    return to_integral(interpreter, &args.positional[0], "__trunc__", f64::trunc);
+}
+
+/* log(x[, base])
+ */
+fn log(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("log", 1, 2)?;
+    let x = real(interpreter, &args.positional[0])?;
+    let num = math_1(x, x.ln(), false)?;
+    if args.positional.len() == 1 {
+        return Ok(Value::Float(num));
+    }
+    let base = real(interpreter, &args.positional[1])?;
+    let den = math_1(base, base.ln(), false)?;
+    if den == 0.0 {
+        return Err(Exception::new("ZeroDivisionError", "float division by zero"));
+    }
+    return Ok(Value::Float(num / den));
+}
+
+/* pow(x, y), which unlike ** never returns an int
+ */
+fn pow(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("pow", 2, 2)?;
+    let x = real(interpreter, &args.positional[0])?;
+    let y = real(interpreter, &args.positional[1])?;
+    // powf follows C99 for infinities and NaNs, like CPython
+    let r = x.powf(y);
+    if x.is_finite() && y.is_finite() {
+        // NaN from a negative number to a fractional power, infinity from
+        // 0 to a negative power or from overflow
+        if r.is_nan() || (r.is_infinite() && x == 0.0) {
+            return Err(domain_error());
+        }
+        if r.is_infinite() {
+            return Err(range_error());
+        }
+    }
+    return Ok(Value::Float(r));
+}
+
+fn fmod(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("fmod", 2, 2)?;
+    let x = real(interpreter, &args.positional[0])?;
+    let y = real(interpreter, &args.positional[1])?;
+    if y.is_infinite() && x.is_finite() {
+        return Ok(Value::Float(x));
+    }
+    let r = x % y;
+    if r.is_nan() && !x.is_nan() && !y.is_nan() {
+        return Err(domain_error());
+    }
+    return Ok(Value::Float(r));
+}
+
+/* frexp(x) = (m, e) with x == m * 2**e and 0.5 <= abs(m) < 1
+ */
+fn frexp(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("frexp", 1, 1)?;
+    let x = real(interpreter, &args.positional[0])?;
+    let (m, e) = split_exponent(x);
+    return Ok(new_tuple(vec![Value::Float(m), Value::Int(e)]));
+}
+
+fn split_exponent(x: f64) -> (f64, i64) {
+    if x == 0.0 || !x.is_finite() {
+        return (x, 0);
+    }
+    // subnormals are scaled up into the normal range first
+    let (x, adjust) = if x.abs() < f64::MIN_POSITIVE { (x * 2f64.powi(54), -54) } else { (x, 0) };
+    let bits = x.to_bits();
+    let exponent = ((bits >> 52) & 0x7ff) as i64 - 1022;
+    let mantissa = f64::from_bits((bits & !(0x7ff << 52)) | (1022 << 52));
+    return (mantissa, exponent + adjust);
+}
+
+/* x * 2**n, rounded once, as C's scalbn
+ */
+pub(crate) fn scale(x: f64, n: i64) -> f64 {
+    let mut y = x;
+    let mut n = n.clamp(-10000, 10000);
+    if n > 1023 {
+        y *= 2f64.powi(1023);
+        n -= 1023;
+        if n > 1023 {
+            y *= 2f64.powi(1023);
+            n = (n - 1023).min(1023);
+        }
+    } else if n < -1022 {
+        // leave the last step below 2**-53 so a subnormal result isn't rounded twice
+        y *= 2f64.powi(-1022) * 2f64.powi(53);
+        n += 1022 - 53;
+        if n < -1022 {
+            y *= 2f64.powi(-1022) * 2f64.powi(53);
+            n = (n + 1022 - 53).max(-1022);
+        }
+    }
+    return y * f64::from_bits(((0x3ff + n) as u64) << 52);
+}
+
+fn ldexp(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("ldexp", 2, 2)?;
+    let x = real(interpreter, &args.positional[0])?;
+    let exponent = match as_int(&args.positional[1]) {
+        Some(exponent) => exponent,
+        None => return Err(Exception::new("TypeError", "Expected an int as second argument to ldexp.")),
+    };
+    if x == 0.0 || !x.is_finite() {
+        return Ok(Value::Float(x));
+    }
+    let r = scale(x, exponent);
+    if r.is_infinite() {
+        return Err(range_error());
+    }
+    return Ok(Value::Float(r));
+}
+
+/* modf(x) = (fractional part, integer part), both with the sign of x
+ */
+fn modf(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("modf", 1, 1)?;
+    let x = real(interpreter, &args.positional[0])?;
+    if x.is_infinite() {
+        return Ok(new_tuple(vec![Value::Float(0.0_f64.copysign(x)), Value::Float(x)]));
+    }
+    let fraction = (x - x.trunc()).copysign(x);
+    return Ok(new_tuple(vec![Value::Float(fraction), Value::Float(x.trunc())]));
+}
+
+fn isfinite(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("isfinite", 1, 1)?;
+    return Ok(Value::Bool(real(interpreter, &args.positional[0])?.is_finite()));
+}
+
+fn isinf(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("isinf", 1, 1)?;
+    return Ok(Value::Bool(real(interpreter, &args.positional[0])?.is_infinite()));
+}
+
+fn isnan(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("isnan", 1, 1)?;
+    return Ok(Value::Bool(real(interpreter, &args.positional[0])?.is_nan()));
+}
+
+/* the rel_tol and abs_tol keyword arguments of isclose()
+ */
+pub(crate) fn tolerances(interpreter: &mut Interpreter, args: &mut Args) -> Result<(f64, f64), Exception> {
+    let rel_tol = match args.keyword("rel_tol") {
+        Some(value) => real(interpreter, &value)?,
+        None => 1e-09,
+    };
+    let abs_tol = match args.keyword("abs_tol") {
+        Some(value) => real(interpreter, &value)?,
+        None => 0.0,
+    };
+    if rel_tol < 0.0 || abs_tol < 0.0 {
+        return Err(Exception::new("ValueError", "tolerances must be non-negative"));
+    }
+    return Ok((rel_tol, abs_tol));
+}
+
+/* isclose(a, b, *, rel_tol=1e-09, abs_tol=0.0)
+ */
+fn isclose(interpreter: &mut Interpreter, mut args: Args) -> EvalResult {
+    let (rel_tol, abs_tol) = tolerances(interpreter, &mut args)?;
+    args.check("isclose", 2, 2)?;
+    let a = real(interpreter, &args.positional[0])?;
+    let b = real(interpreter, &args.positional[1])?;
+    if a == b {
+        return Ok(Value::Bool(true));
+    }
+    if a.is_infinite() || b.is_infinite() {
+        return Ok(Value::Bool(false));
+    }
+    let diff = (b - a).abs();
+    return Ok(Value::Bool(diff <= (rel_tol * b).abs() || diff <= (rel_tol * a).abs() || diff <= abs_tol));
+}
+
+fn nextafter(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("nextafter", 2, 2)?;
+    let x = real(interpreter, &args.positional[0])?;
+    let y = real(interpreter, &args.positional[1])?;
+    return Ok(Value::Float(next_toward(x, y)));
+}
+
+fn next_toward(x: f64, y: f64) -> f64 {
+    if x.is_nan() || y.is_nan() {
+        return x + y;
+    }
+    if x == y {
+        return y;
+    }
+    if x == 0.0 {
+        return f64::from_bits(1).copysign(y);
+    }
+    // the bits of a float's magnitude count up with it
+    let bits = x.to_bits();
+    if (y > x) == (x > 0.0) {
+        return f64::from_bits(bits + 1);
+    }
+    return f64::from_bits(bits - 1);
+}
+
+/* ulp(x): the gap between abs(x) and the next float after it
+ */
+fn ulp(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("ulp", 1, 1)?;
+    let x = real(interpreter, &args.positional[0])?.abs();
+    if !x.is_finite() {
+        return Ok(Value::Float(x));
+    }
+    let next = next_toward(x, f64::INFINITY);
+    if next.is_infinite() {
+        return Ok(Value::Float(x - next_toward(x, 0.0)));
+    }
+    return Ok(Value::Float(next - x));
+}
+
+/* sqrt of the sum of squares of values that are all at most max, which
+ * CPython computes with extra precision so the result is almost always
+ * correctly rounded
+ */
+fn vector_norm(values: &mut [f64], max: f64, found_nan: bool) -> f64 {
+    if max.is_infinite() {
+        return max;
+    }
+    if found_nan {
+        return f64::NAN;
+    }
+    if max == 0.0 || values.len() <= 1 {
+        return max;
+    }
+    let (_, max_e) = split_exponent(max);
+    if max_e < -1023 {
+        // 2**-max_e would overflow, so subnormals are made normal first
+        for value in values.iter_mut() {
+            *value /= f64::MIN_POSITIVE;
+        }
+        return f64::MIN_POSITIVE * vector_norm(values, max / f64::MIN_POSITIVE, found_nan);
+    }
+    let factor = scale(1.0, -max_e);
+    let (mut csum, mut frac1, mut frac2) = (1.0, 0.0, 0.0);
+    for value in values.iter() {
+        let x = value * factor;
+        let (hi, lo) = product(x, x);
+        let (sum, error) = fast_sum(csum, hi);
+        csum = sum;
+        frac1 += lo;
+        frac2 += error;
+    }
+    let mut h = (csum - 1.0 + (frac1 + frac2)).sqrt();
+    let (hi, lo) = product(-h, h);
+    let (sum, error) = fast_sum(csum, hi);
+    csum = sum;
+    frac1 += lo;
+    frac2 += error;
+    let x = csum - 1.0 + (frac1 + frac2);
+    h += x / (2.0 * h);
+    return h / factor;
+}
+
+/* a + b and the rounding error of it, for abs(a) >= abs(b)
+ */
+fn fast_sum(a: f64, b: f64) -> (f64, f64) {
+    let x = a + b;
+    return (x, b - (x - a));
+}
+
+/* x * y and the rounding error of it
+ */
+fn product(x: f64, y: f64) -> (f64, f64) {
+    let z = x * y;
+    return (z, x.mul_add(y, -z));
+}
+
+fn norm_of(coordinates: Vec<f64>) -> f64 {
+    let mut values: Vec<f64> = coordinates.into_iter().map(f64::abs).collect();
+    let found_nan = values.iter().any(|value| value.is_nan());
+    let max = values.iter().cloned().fold(0.0, f64::max);
+    return vector_norm(&mut values, max, found_nan);
+}
+
+/* hypot(*coordinates): the distance from the origin
+ */
+fn hypot(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("hypot", 0, usize::MAX)?;
+    let mut coordinates: Vec<f64> = Vec::new();
+    for value in &args.positional {
+        coordinates.push(real(interpreter, value)?);
+    }
+    return Ok(Value::Float(norm_of(coordinates)));
+}
+
+/* dist(p, q): the distance between two points given as sequences
+ */
+fn dist(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("dist", 2, 2)?;
+    let points = [interpreter.iterate(&args.positional[0])?, interpreter.iterate(&args.positional[1])?];
+    if points[0].len() != points[1].len() {
+        return Err(Exception::new("ValueError", "both points must have the same number of dimensions"));
+    }
+    let mut differences: Vec<f64> = Vec::new();
+    for (p, q) in points[0].iter().zip(&points[1]) {
+        differences.push(real(interpreter, p)? - real(interpreter, q)?);
+    }
+    return Ok(Value::Float(norm_of(differences)));
+}
+
+/* fsum(iterable): the correctly rounded sum, kept exactly as a list of
+ * non-overlapping partial sums (Shewchuk's algorithm, as CPython does it)
+ */
+fn fsum(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("fsum", 1, 1)?;
+    let iterator = interpreter.get_iter(&args.positional[0])?;
+    let mut partials: Vec<f64> = Vec::new();
+    // infinities and NaNs are summed separately
+    let (mut special_sum, mut inf_sum) = (0.0, 0.0);
+    while let Some(item) = interpreter.next_item(&iterator)? {
+        let original = real(interpreter, &item)?;
+        let mut x = original;
+        let mut kept = 0;
+        for i in 0..partials.len() {
+            let mut y = partials[i];
+            if x.abs() < y.abs() {
+                std::mem::swap(&mut x, &mut y);
+            }
+            let hi = x + y;
+            let lo = y - (hi - x);
+            if lo != 0.0 {
+                partials[kept] = lo;
+                kept += 1;
+            }
+            x = hi;
+        }
+        partials.truncate(kept);
+        if x != 0.0 {
+            if !x.is_finite() {
+                if original.is_finite() {
+                    return Err(Exception::new("OverflowError", "intermediate overflow in fsum"));
+                }
+                if original.is_infinite() {
+                    inf_sum += original;
+                }
+                special_sum += original;
+                partials.clear();
+            } else {
+                partials.push(x);
+            }
+        }
+    }
+    if special_sum != 0.0 {
+        if inf_sum.is_nan() {
+            return Err(Exception::new("ValueError", "-inf + inf in fsum"));
+        }
+        return Ok(Value::Float(special_sum));
+    }
+    let mut hi = 0.0;
+    if let Some(top) = partials.pop() {
+        hi = top;
+        let mut lo = 0.0;
+        // add from the top down, until the sum is inexact
+        while let Some(y) = partials.pop() {
+            let x = hi;
+            hi = x + y;
+            lo = y - (hi - x);
+            if lo != 0.0 {
+                break;
+            }
+        }
+        // round half to even across the remaining partials
+        if let Some(next) = partials.last() {
+            if (lo < 0.0 && *next < 0.0) || (lo > 0.0 && *next > 0.0) {
+                let y = lo * 2.0;
+                let x = hi + y;
+                if y == x - hi {
+                    hi = x;
+                }
+            }
+        }
+    }
+    return Ok(Value::Float(hi));
+}
+
+/* prod(iterable, *, start=1), multiplying with * so ints stay ints
+ */
+fn prod(interpreter: &mut Interpreter, mut args: Args) -> EvalResult {
+    let start = args.keyword("start");
+    args.check("prod", 1, 1)?;
+    let mut total = start.unwrap_or(Value::Int(1));
+    let iterator = interpreter.get_iter(&args.positional[0])?;
+    while let Some(item) = interpreter.next_item(&iterator)? {
+        total = binary_op(BinOp::Mul, &total, &item)?;
+    }
+    return Ok(total);
+}
+
+fn factorial(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("factorial", 1, 1)?;
+    let n = index(interpreter, &args.positional[0])?;
+    return factorial_of(n);
+}
+
+fn factorial_of(n: i64) -> EvalResult {
+    if n < 0 {
+        return Err(Exception::new("ValueError", "factorial() not defined for negative values"));
+    }
+    return Ok(new_int((2..=n).map(BigInt::from).product()));
+}
+
+fn non_negative(interpreter: &mut Interpreter, value: &Value, name: &str) -> Result<i64, Exception> {
+    let val = index(interpreter, value)?;
+    if val < 0 {
+        let message = format!("{} must be a non-negative integer", name);
+        return Err(Exception::new("ValueError", &message));
+    }
+    return Ok(val);
+}
+
+/* comb(n, k): the number of ways to choose k of n items
+ */
+fn comb(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("comb", 2, 2)?;
+    let n = non_negative(interpreter, &args.positional[0], "n")?;
+    let k = non_negative(interpreter, &args.positional[1], "k")?;
+    if k > n {
+        return Ok(Value::Int(0));
+    }
+    let k = k.min(n - k);
+    // each step is comb(n - k + i, i), so the division is exact
+    let mut result = BigInt::one();
+    for i in 1..=k {
+        result = result * (n - k + i) / i;
+    }
+    return Ok(new_int(result));
+}
+
+/* perm(n, k=None): the number of ways to arrange k of n items
+ */
+fn perm(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("perm", 1, 2)?;
+    let k = match args.positional.get(1) {
+        None | Some(Value::NoneT) => return factorial_of(index(interpreter, &args.positional[0])?),
+        Some(value) => value.clone(),
+    };
+    let n = non_negative(interpreter, &args.positional[0], "n")?;
+    let k = non_negative(interpreter, &k, "k")?;
+    if k > n {
+        return Ok(Value::Int(0));
+    }
+    return Ok(new_int(((n - k + 1)..=n).map(BigInt::from).product()));
+}
+
+fn gcd(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    let mut result = BigInt::zero();
+    for value in &args.positional {
+        result = result.gcd(&big_index(interpreter, value)?);
+    }
+    return Ok(new_int(result));
+}
+
+fn lcm(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    let mut result = BigInt::one();
+    for value in &args.positional {
+        result = result.lcm(&big_index(interpreter, value)?);
+    }
+    return Ok(new_int(result));
+}
+
+/* isqrt(n): the integer part of the square root of n
+ */
+fn isqrt(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("isqrt", 1, 1)?;
+    let n = big_index(interpreter, &args.positional[0])?;
+    if n.is_negative() {
+        return Err(Exception::new("ValueError", "isqrt() argument must be nonnegative"));
+    }
+    return Ok(new_int(n.sqrt()));
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testing::{error, eval, python};
+
+    #[test]
+    fn test_float_functions() {
+        let mut python = python("import math\n");
+        assert_eq!(eval(&mut python, "math.sqrt(2)"), "1.4142135623730951");
+        assert_eq!(eval(&mut python, "(math.floor(-2.5), math.ceil(2.1), math.trunc(-2.9))"), "(-3, 3, -2)");
+        assert_eq!(eval(&mut python, "(math.log(8, 2), math.log10(1000), math.log2(1024))"), "(3.0, 3.0, 10.0)");
+        assert_eq!(eval(&mut python, "math.fsum([0.1, 0.1, 0.1, 0.1, 0.1, 0.1, 0.1, 0.1, 0.1, 0.1])"), "1.0");
+        assert_eq!(eval(&mut python, "math.hypot(3, 4)"), "5.0");
+        assert_eq!(eval(&mut python, "(math.gamma(5), math.lgamma(1), math.frexp(8.0))"), "(24.0, 0.0, (0.5, 4))");
+        assert_eq!(eval(&mut python, "math.isclose(1.0, 1.0 + 1e-10)"), "True");
+        let erf = "[(math.erf(x), math.erfc(x)) for x in (0.1, 1.6, 3.0, -0.7, 27.0)]";
+        let cpython = "[(0.1124629160182849, 0.8875370839817152), (0.976348383344644, 0.023651616655355985), \
+                       (0.9999779095030014, 2.2090496998585438e-05), (-0.6778011938374184, 1.6778011938374184), \
+                       (1.0, 5.23705e-319)]";
+        assert_eq!(eval(&mut python, erf), cpython);
+        assert_eq!(eval(&mut python, "(math.pi, math.tau, math.inf, math.nan)"), "(3.141592653589793, 6.283185307179586, inf, nan)");
+    }
+
+    #[test]
+    fn test_integer_functions() {
+        let mut python = python("import math\n");
+        assert_eq!(eval(&mut python, "(math.gcd(12, -18), math.lcm(4, 6), math.isqrt(99))"), "(6, 12, 9)");
+        assert_eq!(eval(&mut python, "(math.factorial(20), math.comb(10, 3), math.perm(5))"), "(2432902008176640000, 120, 120)");
+        assert_eq!(eval(&mut python, "(math.prod(range(1, 6)), math.prod([2.5], start=2))"), "(120, 5.0)");
+        assert_eq!(eval(&mut python, "(math.comb(66, 33), math.lcm(*range(1, 41)))"), "(7219428434016265740, 5342931457063200)");
+
+        // results past 64 bits
+        assert_eq!(eval(&mut python, "math.factorial(21)"), "51090942171709440000");
+        assert_eq!(eval(&mut python, "math.comb(100, 50)"), "100891344545564193334812497256");
+        assert_eq!(eval(&mut python, "math.perm(30, 20)"), "73096577329197271449600000");
+        assert_eq!(eval(&mut python, "math.prod(range(1, 22)) == math.factorial(21)"), "True");
+        assert_eq!(eval(&mut python, "math.lcm(*range(1, 50))"), "3099044504245996706400");
+        assert_eq!(eval(&mut python, "(math.gcd(2**100, 6**50), math.isqrt(10**40 + 1))"), "(1125899906842624, 100000000000000000000)");
+        assert_eq!(eval(&mut python, "(math.floor(1e20), math.trunc(-2**70))"), "(100000000000000000000, -1180591620717411303424)");
+    }
+
+    #[test]
+    fn test_errors() {
+        let mut python = python("import math\n");
+        assert_eq!(error(&mut python, "math.sqrt(-1)"), "ValueError: math domain error");
+        assert_eq!(error(&mut python, "math.log(0)"), "ValueError: math domain error");
+        assert_eq!(error(&mut python, "math.exp(1000)"), "OverflowError: math range error");
+        assert_eq!(error(&mut python, "math.floor(math.inf)"), "OverflowError: cannot convert float infinity to integer");
+        assert_eq!(error(&mut python, "math.factorial(-1)"), "ValueError: factorial() not defined for negative values");
+        assert_eq!(error(&mut python, "math.factorial(2.0)"), "TypeError: 'float' object cannot be interpreted as an integer");
+        assert_eq!(error(&mut python, "math.sin('x')"), "TypeError: must be real number, not str");
+        assert_eq!(error(&mut python, "math.fsum([math.inf, -math.inf])"), "ValueError: -inf + inf in fsum");
+    }
+}
@@ -0,0 +1,19 @@
+/* Modules implemented natively in Rust, available to `import` without a
+ * source file.
+ */
+pub mod builtins;
+pub mod cmath;
+pub mod collections;
+pub mod decimal;
+pub mod fractions;
+pub mod functools;
+pub mod gc;
+pub mod io;
+pub mod itertools;
+pub mod json;
+pub mod math;
+pub mod os;
+pub mod pickle;
+pub mod re;
+pub mod sys;
+pub mod weakref;
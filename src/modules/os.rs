@@ -0,0 +1,627 @@
+use crate::dict::{new_dict, Dict};
+use crate::exceptions::key_error;
+use crate::interpreter::{not_an_int, EvalResult, Interpreter};
+use crate::iterator::{new_iterator, Iter};
+use crate::modules::io::os_error;
+use crate::native::{bind, instance, NativeClass};
+use crate::value::{new_list, new_tuple, Args, Builtin, BuiltinFn, Class, Exception, Module, NativeFn, Object, Value};
+use std::cell::RefCell;
+use std::env;
+use std::fs::{self, DirBuilder};
+use std::path::Path;
+use std::rc::Rc;
+
+/* os - the operating system, portably
+ *
+ * The working directory, directory listings, making and removing files,
+ * and the environment. os.environ reads and writes the process's
+ * environment as it is used, so it always agrees with getenv(). Paths are
+ * strings; os.path works on them with posixpath's rules on every
+ * platform, which Windows also understands.
+ *
+ * Touching the file system or the environment is not allowed in the
+ * sandbox, but os.path functions that only look at the string are.
+ */
+pub fn build_module(object: Rc<Class>) -> Rc<Module> {
+    let module = Module::build_new("os", None);
+    let functions: &[(&str, BuiltinFn)] = &[
+        ("getcwd", getcwd),
+        ("listdir", listdir),
+        ("makedirs", makedirs),
+        ("remove", remove),
+        ("unlink", remove),
+        ("getenv", getenv),
+        ("walk", walk),
+    ];
+    for (name, func) in functions {
+        module.set(name, Builtin::build_new(name, *func));
+    }
+    let environ = Object::build_new(environ_class().build(object), Some(Box::new(Environ)));
+    module.set("environ", Value::Object(environ));
+    module.set("name", Value::Str(String::from(if cfg!(windows) { "nt" } else { "posix" })));
+    module.set("linesep", Value::Str(String::from(if cfg!(windows) { "\r\n" } else { "\n" })));
+    module.set("sep", Value::Str(String::from("/")));
+    module.set("pathsep", Value::Str(String::from(if cfg!(windows) { ";" } else { ":" })));
+    module.set("curdir", Value::Str(String::from(".")));
+    module.set("pardir", Value::Str(String::from("..")));
+    return Rc::new(module);
+}
+
+/* os.path, which is also the path attribute of the os module given
+ */
+pub fn path_module(os: &Module) -> Rc<Module> {
+    let module = Module::build_new("os.path", None);
+    let functions: &[(&str, BuiltinFn)] = &[
+        ("join", join),
+        ("exists", exists),
+        ("isfile", isfile),
+        ("isdir", isdir),
+        ("isabs", isabs),
+        ("splitext", splitext),
+        ("split", split),
+        ("basename", basename),
+        ("dirname", dirname),
+        ("normpath", normpath),
+        ("abspath", abspath),
+    ];
+    for (name, func) in functions {
+        module.set(name, Builtin::build_new(name, *func));
+    }
+    module.set("sep", Value::Str(String::from("/")));
+    let module = Rc::new(module);
+    os.set("path", Value::Module(module.clone()));
+    return module;
+}
+
+fn file_access(interpreter: &Interpreter) -> Result<(), Exception> {
+    return interpreter.check_access("PermissionError", "file access");
+}
+
+fn environment_access(interpreter: &Interpreter) -> Result<(), Exception> {
+    return interpreter.check_access("PermissionError", "environment access");
+}
+
+/* a path argument of an os function, like CPython's message for the
+ * wrong type
+ */
+fn path_arg(function: &str, value: &Value) -> Result<String, Exception> {
+    match value {
+        Value::Str(path) => return Ok(path.clone()),
+        Value::Bytes(path) => return Ok(String::from_utf8_lossy(path).into_owned()),
+        other => {
+            let message = format!("{}: path should be string, bytes or os.PathLike, not {}", function, other.type_name());
+            return Err(Exception::new("TypeError", &message));
+        }
+    }
+}
+
+/* the str an os.path function works on
+ */
+fn path_str(value: &Value) -> Result<String, Exception> {
+    match value {
+        Value::Str(path) => return Ok(path.clone()),
+        other => {
+            let message = format!("expected str, bytes or os.PathLike object, not {}", other.type_name());
+            return Err(Exception::new("TypeError", &message));
+        }
+    }
+}
+
+fn str_arg(value: &Value) -> Result<String, Exception> {
+    match value {
+        Value::Str(text) => return Ok(text.clone()),
+        other => return Err(Exception::new("TypeError", &format!("str expected, not {}", other.type_name()))),
+    }
+}
+
+fn getcwd(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("getcwd", 0, 0)?;
+    file_access(interpreter)?;
+    let cwd = env::current_dir().map_err(|err| os_error(&err, None))?;
+    return Ok(Value::Str(cwd.to_string_lossy().into_owned()));
+}
+
+/* the names in a directory, in the order the system lists them
+ */
+fn listdir(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    let bound = bind("listdir", args, &["path"], 0)?;
+    file_access(interpreter)?;
+    let path = match &bound[0] {
+        None | Some(Value::NoneT) => String::from("."),
+        Some(path @ (Value::Str(_) | Value::Bytes(_))) => path_arg("listdir", path)?,
+        Some(other) => {
+            let message = format!(
+                "listdir: path should be string, bytes, os.PathLike or None, not {}",
+                other.type_name()
+            );
+            return Err(Exception::new("TypeError", &message));
+        }
+    };
+    let filename = Value::Str(path.clone());
+    let entries = fs::read_dir(&path).map_err(|err| os_error(&err, Some(&filename)))?;
+    let mut names = Vec::new();
+    for entry in entries {
+        let entry = entry.map_err(|err| os_error(&err, Some(&filename)))?;
+        names.push(Value::Str(entry.file_name().to_string_lossy().into_owned()));
+    }
+    return Ok(new_list(names));
+}
+
+/* makedirs(name, mode=0o777, exist_ok=False): make a directory and any
+ * parents it needs
+ */
+fn makedirs(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    let bound = bind("makedirs", args, &["name", "mode", "exist_ok"], 1)?;
+    file_access(interpreter)?;
+    let name = bound[0].clone().unwrap();
+    let path = path_arg("makedirs", &name)?;
+    let mode = match &bound[1] {
+        None => 0o777,
+        Some(Value::Int(mode)) => *mode,
+        Some(other) => return Err(not_an_int(other)),
+    };
+    let exist_ok = bound[2].as_ref().is_some_and(Value::is_truthy);
+
+    let mut builder = DirBuilder::new();
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::DirBuilderExt;
+        builder.mode(mode as u32);
+    }
+    #[cfg(not(unix))]
+    let _ = mode;
+    let result = match fs::metadata(&path) {
+        Ok(metadata) if metadata.is_dir() && exist_ok => Ok(()),
+        // fails with FileExistsError
+        Ok(_) => builder.create(&path),
+        Err(_) => builder.recursive(true).create(&path),
+    };
+    result.map_err(|err| os_error(&err, Some(&name)))?;
+    return Ok(Value::NoneT);
+}
+
+fn remove(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    let bound = bind("remove", args, &["path"], 1)?;
+    file_access(interpreter)?;
+    let name = bound[0].clone().unwrap();
+    let path = path_arg("remove", &name)?;
+    fs::remove_file(&path).map_err(|err| os_error(&err, Some(&name)))?;
+    return Ok(Value::NoneT);
+}
+
+fn getenv(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    let bound = bind("getenv", args, &["key", "default"], 1)?;
+    let key = str_arg(bound[0].as_ref().unwrap())?;
+    environment_access(interpreter)?;
+    match env::var_os(&key) {
+        Some(value) => return Ok(Value::Str(value.to_string_lossy().into_owned())),
+        None => return Ok(bound[1].clone().unwrap_or(Value::NoneT)),
+    }
+}
+
+/* os.environ: a mapping over the process's environment
+ */
+struct Environ;
+
+fn variables() -> Vec<(Value, Value)> {
+    return env::vars_os()
+        .map(|(key, value)| {
+            let key = Value::Str(key.to_string_lossy().into_owned());
+            (key, Value::Str(value.to_string_lossy().into_owned()))
+        })
+        .collect();
+}
+
+fn variable(key: &Value) -> Result<Option<Value>, Exception> {
+    let key = str_arg(key)?;
+    return Ok(env::var_os(key).map(|value| Value::Str(value.to_string_lossy().into_owned())));
+}
+
+fn environ_class() -> NativeClass<Environ> {
+    let mut environ: NativeClass<Environ> = NativeClass::build_new("_Environ");
+    environ.raw_method("__getitem__", |interpreter, args| {
+        args.check("__getitem__", 2, 2)?;
+        instance::<Environ>("_Environ", "__getitem__", &args)?;
+        environment_access(interpreter)?;
+        return variable(&args.positional[1])?.ok_or_else(|| key_error(&args.positional[1]));
+    });
+    environ.raw_method("__setitem__", |interpreter, args| {
+        args.check("__setitem__", 3, 3)?;
+        instance::<Environ>("_Environ", "__setitem__", &args)?;
+        let key = str_arg(&args.positional[1])?;
+        let value = str_arg(&args.positional[2])?;
+        environment_access(interpreter)?;
+        // what the system would refuse, and Rust panic over
+        if key.contains('\0') || value.contains('\0') {
+            return Err(Exception::new("ValueError", "embedded null byte"));
+        }
+        if key.is_empty() || key.contains('=') {
+            return Err(Exception::new("ValueError", "illegal environment variable name"));
+        }
+        env::set_var(key, value);
+        return Ok(Value::NoneT);
+    });
+    environ.raw_method("__contains__", |interpreter, args| {
+        args.check("__contains__", 2, 2)?;
+        instance::<Environ>("_Environ", "__contains__", &args)?;
+        environment_access(interpreter)?;
+        // like a dict of strs, anything else just isn't in it
+        return Ok(Value::Bool(variable(&args.positional[1]).ok().flatten().is_some()));
+    });
+    environ.raw_method("__len__", |interpreter, args| {
+        args.check("__len__", 1, 1)?;
+        instance::<Environ>("_Environ", "__len__", &args)?;
+        environment_access(interpreter)?;
+        return Ok(Value::Int(env::vars_os().count() as i64));
+    });
+    environ.raw_method("__iter__", |interpreter, args| {
+        args.check("__iter__", 1, 1)?;
+        instance::<Environ>("_Environ", "__iter__", &args)?;
+        environment_access(interpreter)?;
+        return Ok(new_list(variables().into_iter().map(|(key, _)| key).collect()));
+    });
+    environ.raw_method("get", |interpreter, args| {
+        args.check("get", 2, 3)?;
+        instance::<Environ>("_Environ", "get", &args)?;
+        environment_access(interpreter)?;
+        let value = variable(&args.positional[1])?;
+        return Ok(value.or_else(|| args.positional.get(2).cloned()).unwrap_or(Value::NoneT));
+    });
+    environ.raw_method("pop", |interpreter, args| {
+        args.check("pop", 2, 3)?;
+        instance::<Environ>("_Environ", "pop", &args)?;
+        environment_access(interpreter)?;
+        let value = variable(&args.positional[1])?;
+        if value.is_some() {
+            env::remove_var(str_arg(&args.positional[1])?);
+        }
+        return value.or_else(|| args.positional.get(2).cloned()).ok_or_else(|| key_error(&args.positional[1]));
+    });
+    environ.raw_method("keys", |interpreter, args| {
+        args.check("keys", 1, 1)?;
+        instance::<Environ>("_Environ", "keys", &args)?;
+        environment_access(interpreter)?;
+        return Ok(new_list(variables().into_iter().map(|(key, _)| key).collect()));
+    });
+    environ.raw_method("values", |interpreter, args| {
+        args.check("values", 1, 1)?;
+        instance::<Environ>("_Environ", "values", &args)?;
+        environment_access(interpreter)?;
+        return Ok(new_list(variables().into_iter().map(|(_, value)| value).collect()));
+    });
+    environ.raw_method("items", |interpreter, args| {
+        args.check("items", 1, 1)?;
+        instance::<Environ>("_Environ", "items", &args)?;
+        environment_access(interpreter)?;
+        let items = variables().into_iter().map(|(key, value)| new_tuple(vec![key, value])).collect();
+        return Ok(new_list(items));
+    });
+    // a dict of the environment as it is now
+    environ.raw_method("copy", |interpreter, args| {
+        args.check("copy", 1, 1)?;
+        instance::<Environ>("_Environ", "copy", &args)?;
+        environment_access(interpreter)?;
+        let mut dict = Dict::build_new();
+        for (key, value) in variables() {
+            dict.set(key, value)?;
+        }
+        return Ok(new_dict(dict));
+    });
+    return environ;
+}
+
+/* The subdirectories and other entries of a directory, the subdirectories
+ * including links to directories like CPython's
+ */
+fn scan(dir: &str) -> std::io::Result<(Vec<String>, Vec<String>)> {
+    let mut dirs = Vec::new();
+    let mut files = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(_) => continue,
+        };
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if entry.path().is_dir() {
+            dirs.push(name);
+        } else {
+            files.push(name);
+        }
+    }
+    return Ok((dirs, files));
+}
+
+fn is_link(path: &str) -> bool {
+    return fs::symlink_metadata(path).is_ok_and(|metadata| metadata.file_type().is_symlink());
+}
+
+fn str_list(names: Vec<String>) -> Value {
+    return new_list(names.into_iter().map(Value::Str).collect());
+}
+
+/* os.walk, top down: what's left to visit
+ */
+struct Walk {
+    stack: Vec<String>,
+    // the directory yielded last and its list of subdirectories, which
+    // are only visited once the loop body has had the chance to prune it
+    yielded: Option<(String, Value)>,
+    followlinks: bool,
+}
+
+impl Walk {
+    fn next(&mut self) -> Value {
+        if let Some((dir, Value::List(ref names))) = self.yielded.take() {
+            for name in names.borrow().iter().rev() {
+                if let Value::Str(name) = name {
+                    let path = join_path(&dir, name);
+                    if self.followlinks || !is_link(&path) {
+                        self.stack.push(path);
+                    }
+                }
+            }
+        }
+        // directories that can't be listed are skipped
+        while let Some(dir) = self.stack.pop() {
+            if let Ok((dirs, files)) = scan(&dir) {
+                let dirnames = str_list(dirs);
+                self.yielded = Some((dir.clone(), dirnames.clone()));
+                return new_tuple(vec![Value::Str(dir), dirnames, str_list(files)]);
+            }
+        }
+        return Value::NoneT;
+    }
+}
+
+fn walk_bottom_up(dir: String, followlinks: bool, out: &mut Vec<Value>) {
+    let (dirs, files) = match scan(&dir) {
+        Ok(found) => found,
+        Err(_) => return,
+    };
+    for name in &dirs {
+        let path = join_path(&dir, name);
+        if followlinks || !is_link(&path) {
+            walk_bottom_up(path, followlinks, out);
+        }
+    }
+    out.push(new_tuple(vec![Value::Str(dir), str_list(dirs), str_list(files)]));
+}
+
+/* walk(top, topdown=True, followlinks=False): (dirpath, dirnames,
+ * filenames) for each directory in the tree. Top down, removing names
+ * from dirnames keeps walk out of them; bottom up, the whole tree is
+ * listed when walk is called.
+ */
+fn walk(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    let bound = bind("walk", args, &["top", "topdown", "followlinks"], 1)?;
+    file_access(interpreter)?;
+    let top = path_arg("walk", bound[0].as_ref().unwrap())?;
+    let topdown = bound[1].as_ref().is_none_or(Value::is_truthy);
+    let followlinks = bound[2].as_ref().is_some_and(Value::is_truthy);
+    if !topdown {
+        let mut out = Vec::new();
+        walk_bottom_up(top, followlinks, &mut out);
+        return Ok(new_iterator(Iter::Items(out, 0, "generator")));
+    }
+    let state = RefCell::new(Walk {
+        stack: vec![top],
+        yielded: None,
+        followlinks,
+    });
+    let next: NativeFn = Rc::new(move |_, _| Ok(state.borrow_mut().next()));
+    return Ok(new_iterator(Iter::Callable(Some((Builtin::build_native("walk", next), Value::NoneT)))));
+}
+
+// posixpath.join
+fn join_path(path: &str, name: &str) -> String {
+    if name.starts_with('/') {
+        return String::from(name);
+    }
+    if path.is_empty() || path.ends_with('/') {
+        return format!("{}{}", path, name);
+    }
+    return format!("{}/{}", path, name);
+}
+
+fn join(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    if let Some((key, _)) = args.keywords.first() {
+        let message = format!("join() got an unexpected keyword argument '{}'", key);
+        return Err(Exception::new("TypeError", &message));
+    }
+    if args.positional.is_empty() {
+        return Err(Exception::new("TypeError", "join() missing 1 required positional argument: 'a'"));
+    }
+    let mut parts = Vec::new();
+    for part in &args.positional {
+        match part {
+            Value::Str(part) => parts.push(part),
+            other => {
+                let message = format!(
+                    "join() argument must be str, bytes, or os.PathLike object, not '{}'",
+                    other.type_name()
+                );
+                return Err(Exception::new("TypeError", &message));
+            }
+        }
+    }
+    let mut path = parts[0].clone();
+    for part in &parts[1..] {
+        path = join_path(&path, part);
+    }
+    return Ok(Value::Str(path));
+}
+
+fn one_path(name: &str, args: Args) -> Result<String, Exception> {
+    let bound = bind(name, args, &["path"], 1)?;
+    return path_str(bound[0].as_ref().unwrap());
+}
+
+fn exists(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    let path = one_path("exists", args)?;
+    file_access(interpreter)?;
+    return Ok(Value::Bool(Path::new(&path).exists()));
+}
+
+fn isfile(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    let path = one_path("isfile", args)?;
+    file_access(interpreter)?;
+    return Ok(Value::Bool(Path::new(&path).is_file()));
+}
+
+fn isdir(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    let path = one_path("isdir", args)?;
+    file_access(interpreter)?;
+    return Ok(Value::Bool(Path::new(&path).is_dir()));
+}
+
+fn isabs(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    return Ok(Value::Bool(one_path("isabs", args)?.starts_with('/')));
+}
+
+/* the path before its extension and the extension; leading dots of the
+ * last component don't start an extension, so '.bashrc' has none
+ */
+fn splitext(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    let path = one_path("splitext", args)?;
+    let start = path.rfind('/').map_or(0, |slash| slash + 1);
+    if let Some(dot) = path.rfind('.') {
+        if dot > start && path[start..dot].chars().any(|c| c != '.') {
+            let (root, ext) = path.split_at(dot);
+            return Ok(new_tuple(vec![Value::Str(String::from(root)), Value::Str(String::from(ext))]));
+        }
+    }
+    return Ok(new_tuple(vec![Value::Str(path), Value::Str(String::new())]));
+}
+
+/* (head, tail): tail is everything after the last slash, and head has
+ * trailing slashes removed unless it's only slashes
+ */
+fn split_path(path: &str) -> (String, String) {
+    let start = path.rfind('/').map_or(0, |slash| slash + 1);
+    let (head, tail) = path.split_at(start);
+    let mut head = String::from(head);
+    if head.chars().any(|c| c != '/') {
+        head = String::from(head.trim_end_matches('/'));
+    }
+    return (head, String::from(tail));
+}
+
+fn split(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    let (head, tail) = split_path(&one_path("split", args)?);
+    return Ok(new_tuple(vec![Value::Str(head), Value::Str(tail)]));
+}
+
+fn basename(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    return Ok(Value::Str(split_path(&one_path("basename", args)?).1));
+}
+
+fn dirname(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    return Ok(Value::Str(split_path(&one_path("dirname", args)?).0));
+}
+
+/* posixpath.normpath: collapse repeated slashes and . and .. components,
+ * keeping leading ..s of a relative path. Two leading slashes are kept,
+ * as POSIX allows them a meaning of their own.
+ */
+fn normalize(path: &str) -> String {
+    if path.is_empty() {
+        return String::from(".");
+    }
+    let slashes = if path.starts_with("//") && !path.starts_with("///") {
+        2
+    } else if path.starts_with('/') {
+        1
+    } else {
+        0
+    };
+    let mut parts: Vec<&str> = Vec::new();
+    for part in path.split('/') {
+        if part.is_empty() || part == "." {
+            continue;
+        }
+        if part != ".." || (slashes == 0 && parts.is_empty()) || parts.last() == Some(&"..") {
+            parts.push(part);
+        } else if !parts.is_empty() {
+            parts.pop();
+        }
+    }
+    let path = format!("{}{}", "/".repeat(slashes), parts.join("/"));
+    return if path.is_empty() { String::from(".") } else { path };
+}
+
+fn normpath(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    return Ok(Value::Str(normalize(&one_path("normpath", args)?)));
+}
+
+fn abspath(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    let path = one_path("abspath", args)?;
+    if path.starts_with('/') {
+        return Ok(Value::Str(normalize(&path)));
+    }
+    file_access(interpreter)?;
+    let cwd = env::current_dir().map_err(|err| os_error(&err, None))?;
+    return Ok(Value::Str(normalize(&join_path(&cwd.to_string_lossy(), &path))));
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::embed;
+    use crate::testing::{eval, exec, python};
+    use std::env;
+    use std::fs;
+
+    #[test]
+    fn test_files() {
+        let dir = env::temp_dir().join(format!("rebar-os-{}", std::process::id()));
+        let mut python = python("");
+        python.set("top", dir.to_str().unwrap());
+        let eval = |python: &mut embed::Interpreter, source: &str| {
+            return eval(python, source).replace(&*dir.to_string_lossy(), "DIR");
+        };
+        let source = "
+import os
+import os.path
+os.makedirs(os.path.join(top, 'a', 'b'))
+os.makedirs(os.path.join(top, 'a'), exist_ok=True)
+open(os.path.join(top, 'a', 'x.txt'), 'w').close()
+open(os.path.join(top, 'a', 'b', 'y.txt'), 'w').close()
+";
+        exec(&mut python, source);
+        assert_eq!(eval(&mut python, "sorted(os.listdir(os.path.join(top, 'a')))"), "['b', 'x.txt']");
+        assert_eq!(eval(&mut python, "list(os.walk(top))[1][1:]"), "(['b'], ['x.txt'])");
+        let source = "list(map(lambda entry: entry[0][len(top):], os.walk(top, topdown=False)))";
+        assert_eq!(eval(&mut python, source), "['/a/b', '/a', '']");
+        let source = "(os.path.isdir(top), os.path.isfile(top), os.path.exists(os.path.join(top, 'a', 'x.txt')))";
+        assert_eq!(eval(&mut python, source), "(True, False, True)");
+        assert_eq!(eval(&mut python, "os.makedirs(os.path.join(top, 'a'))"), "FileExistsError: [Errno 17] File exists: 'DIR/a'");
+        exec(&mut python, "os.remove(os.path.join(top, 'a', 'x.txt'))\n");
+        assert_eq!(eval(&mut python, "os.listdir(os.path.join(top, 'a'))"), "['b']");
+        assert_eq!(eval(&mut python, "os.remove(os.path.join(top, 'a'))"), "IsADirectoryError: [Errno 21] Is a directory: 'DIR/a'");
+        assert_eq!(eval(&mut python, "os.listdir(os.path.join(top, 'c'))"), "FileNotFoundError: [Errno 2] No such file or directory: 'DIR/c'");
+        assert_eq!(eval(&mut python, "list(os.walk(os.path.join(top, 'c')))"), "[]");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_path() {
+        let mut python = python("import os.path\n");
+        assert_eq!(eval(&mut python, "os.path.join('a', 'b/', 'c')"), "'a/b/c'");
+        assert_eq!(eval(&mut python, "os.path.join('a', '/b')"), "'/b'");
+        assert_eq!(eval(&mut python, "os.path.join('a', 1)"), "TypeError: join() argument must be str, bytes, or os.PathLike object, not 'int'");
+        assert_eq!(eval(&mut python, "(os.path.splitext('a/b.tar.gz'), os.path.splitext('.bashrc'))"), "(('a/b.tar', '.gz'), ('.bashrc', ''))");
+        assert_eq!(eval(&mut python, "(os.path.split('/a//b'), os.path.split('//'), os.path.basename('a/'))"), "(('/a', 'b'), ('//', ''), '')");
+        assert_eq!(eval(&mut python, "(os.path.normpath('a//./b/../../..'), os.path.normpath('/../a/'), os.path.normpath(''))"), "('..', '/a', '.')");
+        assert_eq!(eval(&mut python, "os.path.abspath('a/../b') == os.path.join(os.getcwd(), 'b')"), "True");
+    }
+
+    #[test]
+    fn test_environ() {
+        let mut python = python("import os\n");
+        exec(&mut python, "os.environ['REBAR_TEST_OS'] = 'value'\n");
+        assert_eq!(eval(&mut python, "(os.environ['REBAR_TEST_OS'], os.getenv('REBAR_TEST_OS'), 'REBAR_TEST_OS' in os.environ)"), "('value', 'value', True)");
+        assert_eq!(eval(&mut python, "(os.environ.pop('REBAR_TEST_OS'), os.getenv('REBAR_TEST_OS', 'gone'))"), "('value', 'gone')");
+        assert_eq!(eval(&mut python, "os.environ['REBAR_TEST_OS']"), "KeyError: 'REBAR_TEST_OS'");
+        assert_eq!(eval(&mut python, "os.environ.get('REBAR_TEST_OS', 1)"), "1");
+        assert_eq!(eval(&mut python, "os.environ.__setitem__('A=B', 'x')"), "ValueError: illegal environment variable name");
+    }
+}
@@ -0,0 +1,1294 @@
+use crate::bytes;
+use crate::dict::{new_dict, new_set, Dict};
+use crate::interpreter::{new_int, EvalResult, Interpreter};
+use crate::value::{new_list, new_tuple, Args, Builtin, BuiltinFn, Exception, Module, Value};
+use num_bigint::BigInt;
+use std::collections::HashMap;
+use std::convert::{TryFrom, TryInto};
+use std::rc::Rc;
+
+/* pickle - CPython's serialization format, protocols 2 to 5
+ *
+ * dumps() and loads() handle None, booleans, ints, floats, strings, bytes
+ * and bytearrays, lists, tuples, dicts and ranges, classes and functions by
+ * their __module__ and name, and instances through __reduce_ex__,
+ * __reduce__ or __getstate__ and __setstate__, or else as their class and
+ * attributes, which loading sets without running __init__. Sets and frozensets use their opcodes from protocol 4
+ * and builtins.set() and builtins.frozenset() of a list before that, like
+ * CPython. dump() and load() write and read pickles through a file's
+ * write() and read().
+ *
+ * Loading a pickle calls whatever functions it names, so a pickle from
+ * somewhere untrusted can run any code. safe_loads(data, allowed) only
+ * reconstructs plain data, the harmless builtins in SAFE_GLOBALS, and the
+ * 'module.name' globals listed in allowed.
+ */
+
+const HIGHEST_PROTOCOL: i64 = 5;
+const DEFAULT_PROTOCOL: i64 = 4;
+
+// globals any pickle may refer to
+const SAFE_GLOBALS: &[&str] = &[
+    "builtins.bytearray",
+    "builtins.bytes",
+    "builtins.complex",
+    "builtins.range",
+    "_codecs.encode",
+];
+
+// a global as (module, name)
+type Global = (&'static str, &'static str);
+
+// Python 2 names of globals, used in protocol 2 pickles
+const PYTHON2_NAMES: &[(Global, Global)] = &[(("__builtin__", "xrange"), ("builtins", "range"))];
+const PYTHON2_MODULES: &[(&str, &str)] = &[("__builtin__", "builtins"), ("copy_reg", "copyreg")];
+
+// items per APPENDS or SETITEMS, like CPython
+const BATCH_SIZE: usize = 1000;
+
+mod op {
+    pub const MARK: u8 = b'(';
+    pub const STOP: u8 = b'.';
+    pub const POP: u8 = b'0';
+    pub const POP_MARK: u8 = b'1';
+    pub const DUP: u8 = b'2';
+    pub const BININT: u8 = b'J';
+    pub const BININT1: u8 = b'K';
+    pub const BININT2: u8 = b'M';
+    pub const NONE: u8 = b'N';
+    pub const BINSTRING: u8 = b'T';
+    pub const SHORT_BINSTRING: u8 = b'U';
+    pub const BINUNICODE: u8 = b'X';
+    pub const APPEND: u8 = b'a';
+    pub const BUILD: u8 = b'b';
+    pub const GLOBAL: u8 = b'c';
+    pub const APPENDS: u8 = b'e';
+    pub const GET: u8 = b'g';
+    pub const BINGET: u8 = b'h';
+    pub const LONG_BINGET: u8 = b'j';
+    pub const PUT: u8 = b'p';
+    pub const BINPUT: u8 = b'q';
+    pub const LONG_BINPUT: u8 = b'r';
+    pub const SETITEM: u8 = b's';
+    pub const TUPLE: u8 = b't';
+    pub const SETITEMS: u8 = b'u';
+    pub const BINFLOAT: u8 = b'G';
+    pub const REDUCE: u8 = b'R';
+    pub const EMPTY_TUPLE: u8 = b')';
+    pub const EMPTY_LIST: u8 = b']';
+    pub const EMPTY_DICT: u8 = b'}';
+    pub const BINBYTES: u8 = b'B';
+    pub const SHORT_BINBYTES: u8 = b'C';
+    // protocol 2
+    pub const PROTO: u8 = 0x80;
+    pub const NEWOBJ: u8 = 0x81;
+    pub const TUPLE1: u8 = 0x85;
+    pub const TUPLE2: u8 = 0x86;
+    pub const TUPLE3: u8 = 0x87;
+    pub const NEWTRUE: u8 = 0x88;
+    pub const NEWFALSE: u8 = 0x89;
+    pub const LONG1: u8 = 0x8a;
+    pub const LONG4: u8 = 0x8b;
+    // protocol 4
+    pub const SHORT_BINUNICODE: u8 = 0x8c;
+    pub const BINUNICODE8: u8 = 0x8d;
+    pub const BINBYTES8: u8 = 0x8e;
+    pub const EMPTY_SET: u8 = 0x8f;
+    pub const ADDITEMS: u8 = 0x90;
+    pub const FROZENSET: u8 = 0x91;
+    pub const NEWOBJ_EX: u8 = 0x92;
+    pub const STACK_GLOBAL: u8 = 0x93;
+    pub const MEMOIZE: u8 = 0x94;
+    pub const FRAME: u8 = 0x95;
+    // protocol 5
+    pub const BYTEARRAY8: u8 = 0x96;
+    pub const NEXT_BUFFER: u8 = 0x97;
+    pub const READONLY_BUFFER: u8 = 0x98;
+}
+
+pub fn build_module() -> Rc<Module> {
+    let module = Module::build_new("pickle", None);
+    let functions: &[(&str, BuiltinFn)] =
+        &[("dump", dump), ("dumps", dumps), ("load", load), ("loads", loads), ("safe_loads", safe_loads)];
+    for (name, func) in functions {
+        module.set(name, Builtin::build_new(name, *func));
+    }
+    module.set("HIGHEST_PROTOCOL", Value::Int(HIGHEST_PROTOCOL));
+    module.set("DEFAULT_PROTOCOL", Value::Int(DEFAULT_PROTOCOL));
+    return Rc::new(module);
+}
+
+fn pickling_error(message: &str) -> Exception {
+    return Exception::new("PicklingError", message);
+}
+
+fn unpickling_error(message: &str) -> Exception {
+    return Exception::new("UnpicklingError", message);
+}
+
+/* dumps(obj, protocol=None)
+ */
+fn dumps(interpreter: &mut Interpreter, mut args: Args) -> EvalResult {
+    let protocol = args.keyword("protocol");
+    args.check("dumps", 1, 2)?;
+    let protocol = match args.positional.get(1).or(protocol.as_ref()) {
+        None | Some(Value::NoneT) => DEFAULT_PROTOCOL,
+        Some(Value::Int(protocol)) if *protocol < 0 => HIGHEST_PROTOCOL,
+        Some(Value::Int(protocol)) if (2..=HIGHEST_PROTOCOL).contains(protocol) => *protocol,
+        Some(Value::Int(protocol)) => {
+            let message = format!("pickle protocol must be 2 to {} here, not {}", HIGHEST_PROTOCOL, protocol);
+            return Err(Exception::new("ValueError", &message));
+        }
+        Some(other) => {
+            let message = format!("an integer is required (got type {})", other.type_name());
+            return Err(Exception::new("TypeError", &message));
+        }
+    };
+    let data = interpreter.pickle(&args.positional[0], protocol as u8)?;
+    return Ok(bytes::new_bytes(data));
+}
+
+/* dump(obj, file, protocol=None), writing dumps(obj, protocol) to
+ * file.write()
+ */
+fn dump(interpreter: &mut Interpreter, mut args: Args) -> EvalResult {
+    let protocol = args.keyword("protocol");
+    args.check("dump", 2, 3)?;
+    let write = match interpreter.get_attr(&args.positional[1], "write") {
+        Ok(write) => write,
+        Err(_) => return Err(Exception::new("TypeError", "file must have a 'write' attribute")),
+    };
+    let mut positional = vec![args.positional[0].clone()];
+    positional.extend(args.positional.get(2).or(protocol.as_ref()).cloned());
+    let data = dumps(interpreter, Args::build_new(positional))?;
+    interpreter.call(&write, Args::build_new(vec![data]))?;
+    return Ok(Value::NoneT);
+}
+
+/* _codecs.encode(obj, encoding='utf-8', errors='strict'), which protocol 2
+ * pickles of bytes call, as there are no bytes opcodes before protocol 3
+ */
+fn codecs_encode(_interpreter: &mut Interpreter, mut args: Args) -> EvalResult {
+    let (encoding, errors) = bytes::codec_args("encode", &mut args, 1)?;
+    args.check("encode", 1, 3)?;
+    match &args.positional[0] {
+        Value::Str(text) => return Ok(bytes::new_bytes(bytes::encode(text, &encoding, &errors)?)),
+        other => {
+            let message = format!("utf_8_encode() argument 1 must be str, not {}", other.type_name());
+            return Err(Exception::new("TypeError", &message));
+        }
+    }
+}
+
+/* loads(data)
+ */
+fn loads(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("loads", 1, 1)?;
+    return interpreter.unpickle(&bytes::buffer(&args.positional[0])?, None);
+}
+
+/* load(file), reading the pickle from file.read(). Whatever follows it is
+ * left in the file, for the next load().
+ */
+fn load(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("load", 1, 1)?;
+    let file = &args.positional[0];
+    let read = match interpreter.get_attr(file, "read") {
+        Ok(read) => read,
+        Err(_) => return Err(Exception::new("TypeError", "file must have a 'read' attribute")),
+    };
+    let data = interpreter.call(&read, Args::default())?;
+    let data = bytes::buffer(&data)?;
+    let (value, len) = interpreter.unpickle_prefix(&data, None)?;
+    if len < data.len() {
+        let tell = interpreter.get_attr(file, "tell")?;
+        let end = interpreter.call(&tell, Args::default())?;
+        let end = match end {
+            Value::Int(end) => end,
+            _ => return Err(Exception::new("TypeError", "tell() must return an int")),
+        };
+        let seek = interpreter.get_attr(file, "seek")?;
+        let pos = end - (data.len() - len) as i64;
+        interpreter.call(&seek, Args::build_new(vec![Value::Int(pos)]))?;
+    }
+    return Ok(value);
+}
+
+/* safe_loads(data, allowed=()): loads() that only allows the globals in
+ * SAFE_GLOBALS and allowed
+ */
+fn safe_loads(interpreter: &mut Interpreter, mut args: Args) -> EvalResult {
+    let allowed = args.keyword("allowed");
+    args.check("safe_loads", 1, 2)?;
+    let mut names: Vec<String> = SAFE_GLOBALS.iter().map(|name| String::from(*name)).collect();
+    if let Some(allowed) = args.positional.get(1).or(allowed.as_ref()) {
+        for name in interpreter.iterate(allowed)? {
+            match name {
+                Value::Str(ref name) => names.push(name.clone()),
+                other => {
+                    let message = format!("allowed globals must be str, not '{}'", other.type_name());
+                    return Err(Exception::new("TypeError", &message));
+                }
+            };
+        }
+    }
+    return interpreter.unpickle(&bytes::buffer(&args.positional[0])?, Some(&names));
+}
+
+impl Interpreter {
+    /* value pickled with protocol 2 to 5
+     */
+    pub fn pickle(&mut self, value: &Value, protocol: u8) -> Result<Vec<u8>, Exception> {
+        let mut pickler = Pickler {
+            interpreter: self,
+            protocol,
+            out: Vec::new(),
+            memo: HashMap::new(),
+            memo_len: 0,
+            kept: Vec::new(),
+            codecs_encode: Builtin::build_new("encode", codecs_encode),
+        };
+        pickler.save(value)?;
+        pickler.out.push(op::STOP);
+
+        let mut data = vec![op::PROTO, protocol];
+        if protocol >= 4 {
+            // a single frame
+            data.push(op::FRAME);
+            data.extend_from_slice(&(pickler.out.len() as u64).to_le_bytes());
+        }
+        data.append(&mut pickler.out);
+        return Ok(data);
+    }
+
+    /* The value a pickle holds. allowed, if given, is the globals it may
+     * refer to, as 'module.name'.
+     */
+    pub fn unpickle(&mut self, data: &[u8], allowed: Option<&[String]>) -> EvalResult {
+        return Ok(self.unpickle_prefix(data, allowed)?.0);
+    }
+
+    /* unpickle() of the pickle at the start of data, and its length
+     */
+    fn unpickle_prefix(&mut self, data: &[u8], allowed: Option<&[String]>) -> Result<(Value, usize), Exception> {
+        let mut unpickler = Unpickler {
+            interpreter: self,
+            data,
+            pos: 0,
+            protocol: 0,
+            stack: Vec::new(),
+            metastack: Vec::new(),
+            memo: HashMap::new(),
+            allowed,
+        };
+        let value = unpickler.load()?;
+        return Ok((value, unpickler.pos));
+    }
+}
+
+struct Pickler<'a> {
+    interpreter: &'a mut Interpreter,
+    protocol: u8,
+    out: Vec<u8>,
+    // memo index of each container written, by id()
+    memo: HashMap<usize, usize>,
+    // what has been memoized, including values without an id like strs
+    memo_len: usize,
+    // what is in the memo, so ids aren't reused while pickling
+    kept: Vec<Value>,
+    // _codecs.encode, for bytes with protocol 2
+    codecs_encode: Value,
+}
+
+impl Pickler<'_> {
+    fn save(&mut self, value: &Value) -> Result<(), Exception> {
+        self.interpreter.tick()?;
+        if let Some(index) = self.memo.get(&value.identity()) {
+            let index = *index;
+            self.write_get(index);
+            return Ok(());
+        }
+        match value {
+            Value::NoneT => self.out.push(op::NONE),
+            Value::Bool(true) => self.out.push(op::NEWTRUE),
+            Value::Bool(false) => self.out.push(op::NEWFALSE),
+            Value::Int(val) => self.save_int(*val),
+            Value::BigInt(val) => self.save_long(val),
+            Value::Float(val) => {
+                self.out.push(op::BINFLOAT);
+                self.out.extend_from_slice(&val.to_be_bytes());
+            }
+            Value::Str(val) => {
+                self.save_str(val);
+                self.memoize(value);
+            }
+            Value::Bytes(data) => {
+                self.save_bytes(data)?;
+                self.memoize(value);
+            }
+            Value::ByteArray(array) => {
+                let data = array.data.borrow().clone();
+                if self.protocol >= 5 {
+                    self.out.push(op::BYTEARRAY8);
+                    self.out.extend_from_slice(&(data.len() as u64).to_le_bytes());
+                    self.out.extend_from_slice(&data);
+                } else {
+                    let bytearray = self.interpreter.builtins.get("bytearray").unwrap();
+                    self.save_reduce(&bytearray, &[bytes::new_bytes(data)], op::REDUCE)?;
+                }
+                self.memoize(value);
+            }
+            Value::List(items) => {
+                self.out.push(op::EMPTY_LIST);
+                self.memoize(value);
+                let items = items.borrow().clone();
+                self.save_appends(&items)?;
+            }
+            Value::Tuple(items) => self.save_tuple(value, items)?,
+            Value::Dict(dict) => {
+                self.out.push(op::EMPTY_DICT);
+                self.memoize(value);
+                let items = dict.borrow().items();
+                self.save_setitems(&items)?;
+            }
+            Value::Set(_) | Value::FrozenSet(_) => self.save_set(value)?,
+            Value::Range(start, stop, step) => {
+                let range = self.interpreter.builtins.get("range").unwrap();
+                let args = vec![Value::Int(*start), Value::Int(*stop), Value::Int(*step)];
+                self.save_reduce(&range, &args, op::REDUCE)?;
+                self.memoize(value);
+            }
+            Value::Complex(real, imag) => {
+                let complex = self.interpreter.builtins.get("complex").unwrap();
+                self.save_reduce(&complex, &[Value::Float(*real), Value::Float(*imag)], op::REDUCE)?;
+                self.memoize(value);
+            }
+            Value::Function(_) | Value::Class(_) | Value::Builtin(_) => self.save_global(value)?,
+            Value::Object(_) => self.save_object(value)?,
+            _ => {
+                let message = format!("cannot pickle '{}' object", value.type_name());
+                return Err(Exception::new("TypeError", &message));
+            }
+        };
+        return Ok(());
+    }
+
+    fn save_int(&mut self, val: i64) {
+        if (0..=0xff).contains(&val) {
+            self.out.extend_from_slice(&[op::BININT1, val as u8]);
+        } else if (0..=0xffff).contains(&val) {
+            self.out.push(op::BININT2);
+            self.out.extend_from_slice(&(val as u16).to_le_bytes());
+        } else if let Ok(val) = i32::try_from(val) {
+            self.out.push(op::BININT);
+            self.out.extend_from_slice(&val.to_le_bytes());
+        } else {
+            self.save_long(&BigInt::from(val));
+        }
+    }
+
+    /* an int as its shortest little-endian two's complement
+     */
+    fn save_long(&mut self, val: &BigInt) {
+        let bytes = val.to_signed_bytes_le();
+        match u8::try_from(bytes.len()) {
+            Ok(len) => self.out.extend_from_slice(&[op::LONG1, len]),
+            Err(_) => {
+                self.out.push(op::LONG4);
+                self.out.extend_from_slice(&(bytes.len() as u32).to_le_bytes());
+            }
+        }
+        self.out.extend_from_slice(&bytes);
+    }
+
+    fn save_str(&mut self, val: &str) {
+        let bytes = val.as_bytes();
+        if self.protocol >= 4 && bytes.len() < 256 {
+            self.out.extend_from_slice(&[op::SHORT_BINUNICODE, bytes.len() as u8]);
+        } else if let Ok(len) = u32::try_from(bytes.len()) {
+            self.out.push(op::BINUNICODE);
+            self.out.extend_from_slice(&len.to_le_bytes());
+        } else {
+            self.out.push(op::BINUNICODE8);
+            self.out.extend_from_slice(&(bytes.len() as u64).to_le_bytes());
+        }
+        self.out.extend_from_slice(bytes);
+    }
+
+    /* bytes, which protocol 2 writes as _codecs.encode(str, 'latin1') with
+     * a char for each byte, as CPython does
+     */
+    fn save_bytes(&mut self, data: &[u8]) -> Result<(), Exception> {
+        if self.protocol >= 3 {
+            if data.len() < 256 {
+                self.out.extend_from_slice(&[op::SHORT_BINBYTES, data.len() as u8]);
+            } else if let Ok(len) = u32::try_from(data.len()) {
+                self.out.push(op::BINBYTES);
+                self.out.extend_from_slice(&len.to_le_bytes());
+            } else {
+                self.out.push(op::BINBYTES8);
+                self.out.extend_from_slice(&(data.len() as u64).to_le_bytes());
+            }
+            self.out.extend_from_slice(data);
+            return Ok(());
+        }
+        if data.is_empty() {
+            let bytes = self.interpreter.builtins.get("bytes").unwrap();
+            return self.save_reduce(&bytes, &[], op::REDUCE);
+        }
+        let encode = self.codecs_encode.clone();
+        match self.memo.get(&encode.identity()) {
+            Some(index) => {
+                let index = *index;
+                self.write_get(index);
+            }
+            None => {
+                self.out.push(op::GLOBAL);
+                self.out.extend_from_slice(b"_codecs\nencode\n");
+                self.memoize(&encode);
+            }
+        };
+        let text = Value::Str(data.iter().map(|byte| *byte as char).collect());
+        self.save(&new_tuple(vec![text, Value::Str(String::from("latin1"))]))?;
+        self.out.push(op::REDUCE);
+        return Ok(());
+    }
+
+    fn save_tuple(&mut self, value: &Value, items: &[Value]) -> Result<(), Exception> {
+        if items.is_empty() {
+            self.out.push(op::EMPTY_TUPLE);
+            return Ok(());
+        }
+        if items.len() > 3 {
+            self.out.push(op::MARK);
+        }
+        for item in items {
+            self.save(item)?;
+        }
+        // a tuple that contains itself (through a list, say) was memoized
+        // while its items were written: use that one
+        if let Some(index) = self.memo.get(&value.identity()) {
+            let index = *index;
+            match items.len() {
+                1..=3 => self.out.extend(std::iter::repeat_n(op::POP, items.len())),
+                _ => self.out.push(op::POP_MARK),
+            };
+            self.write_get(index);
+            return Ok(());
+        }
+        match items.len() {
+            1 => self.out.push(op::TUPLE1),
+            2 => self.out.push(op::TUPLE2),
+            3 => self.out.push(op::TUPLE3),
+            _ => self.out.push(op::TUPLE),
+        };
+        self.memoize(value);
+        return Ok(());
+    }
+
+    fn save_appends(&mut self, items: &[Value]) -> Result<(), Exception> {
+        for batch in items.chunks(BATCH_SIZE) {
+            if batch.len() == 1 {
+                self.save(&batch[0])?;
+                self.out.push(op::APPEND);
+                continue;
+            }
+            self.out.push(op::MARK);
+            for item in batch {
+                self.save(item)?;
+            }
+            self.out.push(op::APPENDS);
+        }
+        return Ok(());
+    }
+
+    fn save_setitems(&mut self, items: &[(Value, Value)]) -> Result<(), Exception> {
+        for batch in items.chunks(BATCH_SIZE) {
+            if batch.len() == 1 {
+                self.save(&batch[0].0)?;
+                self.save(&batch[0].1)?;
+                self.out.push(op::SETITEM);
+                continue;
+            }
+            self.out.push(op::MARK);
+            for (key, value) in batch {
+                self.save(key)?;
+                self.save(value)?;
+            }
+            self.out.push(op::SETITEMS);
+        }
+        return Ok(());
+    }
+
+    /* a set as EMPTY_SET and ADDITEMS, a frozenset as FROZENSET, or before
+     * protocol 4 either as a call with a list of its items
+     */
+    fn save_set(&mut self, value: &Value) -> Result<(), Exception> {
+        let (items, frozen) = match value {
+            Value::Set(items) => (items.borrow().keys(), false),
+            Value::FrozenSet(items) => (items.borrow().keys(), true),
+            _ => return Ok(()),
+        };
+        if self.protocol < 4 {
+            let class = self.interpreter.builtins.get(if frozen { "frozenset" } else { "set" }).unwrap();
+            self.save_reduce(&class, &[new_list(items)], op::REDUCE)?;
+            self.memoize(value);
+            return Ok(());
+        }
+        if frozen {
+            self.out.push(op::MARK);
+            for item in &items {
+                self.save(item)?;
+            }
+            // only possible if an item refers back to the frozenset
+            if let Some(index) = self.memo.get(&value.identity()) {
+                let index = *index;
+                self.out.push(op::POP_MARK);
+                self.write_get(index);
+                return Ok(());
+            }
+            self.out.push(op::FROZENSET);
+            self.memoize(value);
+            return Ok(());
+        }
+        self.out.push(op::EMPTY_SET);
+        self.memoize(value);
+        for batch in items.chunks(BATCH_SIZE) {
+            self.out.push(op::MARK);
+            for item in batch {
+                self.save(item)?;
+            }
+            self.out.push(op::ADDITEMS);
+        }
+        return Ok(());
+    }
+
+    fn save_global(&mut self, value: &Value) -> Result<(), Exception> {
+        let (module, name) = match self.interpreter.global_name(value) {
+            Some(found) => found,
+            None => {
+                let message = format!("Can't pickle {}: it's not found as an attribute of any imported module", value.repr());
+                return Err(pickling_error(&message));
+            }
+        };
+        if self.protocol >= 4 {
+            for part in [module, name] {
+                let part = Value::Str(part);
+                self.save(&part)?;
+            }
+            self.out.push(op::STACK_GLOBAL);
+        } else {
+            let (mut module, mut name) = (module.as_str(), name.as_str());
+            if self.protocol < 3 {
+                if let Some((python2, _)) = PYTHON2_NAMES.iter().find(|(_, global)| *global == (module, name)) {
+                    (module, name) = *python2;
+                } else if let Some((python2, _)) = PYTHON2_MODULES.iter().find(|(_, python3)| *python3 == module) {
+                    module = python2;
+                }
+            }
+            self.out.push(op::GLOBAL);
+            self.out.extend_from_slice(format!("{}\n{}\n", module, name).as_bytes());
+        }
+        self.memoize(value);
+        return Ok(());
+    }
+
+    /* callable(*args), with REDUCE, or NEWOBJ for callable.__new__
+     */
+    fn save_reduce(&mut self, callable: &Value, args: &[Value], opcode: u8) -> Result<(), Exception> {
+        self.save(callable)?;
+        self.save(&new_tuple(args.to_vec()))?;
+        self.out.push(opcode);
+        return Ok(());
+    }
+
+    /* An instance, through __reduce_ex__(protocol) or __reduce__(), which
+     * return (callable, args[, state[, listitems[, dictitems]]]), or else
+     * as NEWOBJ of its class and BUILD of __getstate__() or its attributes
+     */
+    fn save_object(&mut self, value: &Value) -> Result<(), Exception> {
+        let class = match value {
+            Value::Object(object) => object.class.clone(),
+            _ => return Ok(()),
+        };
+        let protocol = Value::Int(self.protocol as i64);
+        let reduced = match self.interpreter.call_special(value, "__reduce_ex__", vec![protocol]) {
+            Some(reduced) => Some(reduced?),
+            None => self.interpreter.call_special(value, "__reduce__", vec![]).transpose()?,
+        };
+        let reduced: Vec<Value> = match reduced {
+            Some(Value::Tuple(ref items)) if (2..=5).contains(&items.len()) => items.to_vec(),
+            Some(_) => return Err(pickling_error("__reduce__ must return a tuple of 2 to 5 items")),
+            None => {
+                // object.__reduce_ex__(): class.__new__(class), and the
+                // instance's __dict__, if it has anything in it
+                let state = match self.interpreter.call_special(value, "__getstate__", vec![]) {
+                    Some(state) => state?,
+                    None if self.interpreter.is_plain_class(&class) => match value {
+                        Value::Object(object) if !object.attrs.borrow().is_empty() => {
+                            new_dict(Dict::from_strings(&object.attrs.borrow()))
+                        }
+                        _ => Value::NoneT,
+                    },
+                    None => {
+                        let message = format!("cannot pickle '{}' object", class.name);
+                        return Err(Exception::new("TypeError", &message));
+                    }
+                };
+                self.save_reduce(&Value::Class(class), &[], op::NEWOBJ)?;
+                if state == Value::NoneT {
+                    self.memoize(value);
+                    return Ok(());
+                }
+                return self.save_state(value, &state);
+            }
+        };
+        let args = match &reduced[1] {
+            Value::Tuple(args) => args.to_vec(),
+            _ => return Err(pickling_error("the second item of the tuple returned by __reduce__ must be a tuple")),
+        };
+        self.save_reduce(&reduced[0], &args, op::REDUCE)?;
+        // the object may have been pickled through args, if it refers to
+        // itself through them
+        if let Some(index) = self.memo.get(&value.identity()) {
+            let index = *index;
+            self.out.push(op::POP);
+            self.write_get(index);
+            return Ok(());
+        }
+        self.memoize(value);
+        if let Some(items) = reduced.get(3).filter(|items| **items != Value::NoneT) {
+            let items = self.interpreter.iterate(items)?;
+            self.save_appends(&items)?;
+        }
+        if let Some(items) = reduced.get(4).filter(|items| **items != Value::NoneT) {
+            let mut pairs: Vec<(Value, Value)> = Vec::new();
+            for item in self.interpreter.iterate(items)? {
+                match &item {
+                    Value::Tuple(pair) if pair.len() == 2 => pairs.push((pair[0].clone(), pair[1].clone())),
+                    _ => return Err(pickling_error("dictitems must yield (key, value) pairs")),
+                };
+            }
+            self.save_setitems(&pairs)?;
+        }
+        match reduced.get(2) {
+            Some(state) if *state != Value::NoneT => return self.save_state(value, state),
+            _ => return Ok(()),
+        }
+    }
+
+    fn save_state(&mut self, value: &Value, state: &Value) -> Result<(), Exception> {
+        if !self.memo.contains_key(&value.identity()) {
+            self.memoize(value);
+        }
+        self.save(state)?;
+        self.out.push(op::BUILD);
+        return Ok(());
+    }
+
+    fn memoize(&mut self, value: &Value) {
+        let index = self.memo_len;
+        self.memo_len += 1;
+        if self.protocol >= 4 {
+            self.out.push(op::MEMOIZE);
+        } else if index < 256 {
+            self.out.extend_from_slice(&[op::BINPUT, index as u8]);
+        } else {
+            self.out.push(op::LONG_BINPUT);
+            self.out.extend_from_slice(&(index as u32).to_le_bytes());
+        }
+        if value.identity() != 0 {
+            self.memo.insert(value.identity(), index);
+            self.kept.push(value.clone());
+        }
+    }
+
+    fn write_get(&mut self, index: usize) {
+        if index < 256 {
+            self.out.extend_from_slice(&[op::BINGET, index as u8]);
+        } else {
+            self.out.push(op::LONG_BINGET);
+            self.out.extend_from_slice(&(index as u32).to_le_bytes());
+        }
+    }
+}
+
+struct Unpickler<'a> {
+    interpreter: &'a mut Interpreter,
+    data: &'a [u8],
+    pos: usize,
+    protocol: u8,
+    stack: Vec<Value>,
+    // the stacks under each MARK
+    metastack: Vec<Vec<Value>>,
+    memo: HashMap<usize, Value>,
+    allowed: Option<&'a [String]>,
+}
+
+impl Unpickler<'_> {
+    fn load(&mut self) -> EvalResult {
+        loop {
+            self.interpreter.tick()?;
+            let opcode = self.read(1)?[0];
+            match opcode {
+                op::PROTO => {
+                    self.protocol = self.read(1)?[0];
+                    if self.protocol as i64 > HIGHEST_PROTOCOL {
+                        let message = format!("unsupported pickle protocol: {}", self.protocol);
+                        return Err(Exception::new("ValueError", &message));
+                    }
+                }
+                // frames only group the opcodes that follow, for reading
+                op::FRAME => {
+                    self.read(8)?;
+                }
+                op::STOP => return self.pop(),
+                op::NONE => self.stack.push(Value::NoneT),
+                op::NEWTRUE => self.stack.push(Value::Bool(true)),
+                op::NEWFALSE => self.stack.push(Value::Bool(false)),
+                op::BININT1 => {
+                    let val = self.read(1)?[0];
+                    self.stack.push(Value::Int(val as i64));
+                }
+                op::BININT2 => {
+                    let val = u16::from_le_bytes(self.read(2)?.try_into().unwrap());
+                    self.stack.push(Value::Int(val as i64));
+                }
+                op::BININT => {
+                    let val = i32::from_le_bytes(self.read(4)?.try_into().unwrap());
+                    self.stack.push(Value::Int(val as i64));
+                }
+                op::LONG1 | op::LONG4 => {
+                    let len = if opcode == op::LONG1 { self.read(1)?[0] as usize } else { self.read_len(4)? };
+                    let val = BigInt::from_signed_bytes_le(self.read(len)?);
+                    self.stack.push(new_int(val));
+                }
+                op::BINFLOAT => {
+                    let val = f64::from_be_bytes(self.read(8)?.try_into().unwrap());
+                    self.stack.push(Value::Float(val));
+                }
+                op::SHORT_BINUNICODE | op::BINUNICODE | op::BINUNICODE8 => {
+                    let len = match opcode {
+                        op::SHORT_BINUNICODE => self.read(1)?[0] as usize,
+                        op::BINUNICODE => self.read_len(4)?,
+                        _ => self.read_len(8)?,
+                    };
+                    let val = String::from_utf8(self.read(len)?.to_vec())
+                        .map_err(|_| unpickling_error("invalid utf-8 in a str"))?;
+                    self.stack.push(Value::Str(val));
+                }
+                // Python 2 str, decoded as ASCII like CPython does by
+                // default
+                op::SHORT_BINSTRING | op::BINSTRING => {
+                    let len = if opcode == op::SHORT_BINSTRING { self.read(1)?[0] as usize } else { self.read_len(4)? };
+                    let bytes = self.read(len)?.to_vec();
+                    if !bytes.is_ascii() {
+                        return Err(unpickling_error("a Python 2 str isn't ASCII"));
+                    }
+                    self.stack.push(Value::Str(String::from_utf8(bytes).unwrap()));
+                }
+                op::SHORT_BINBYTES | op::BINBYTES | op::BINBYTES8 => {
+                    let len = match opcode {
+                        op::SHORT_BINBYTES => self.read(1)?[0] as usize,
+                        op::BINBYTES => self.read_len(4)?,
+                        _ => self.read_len(8)?,
+                    };
+                    let data = self.read(len)?.to_vec();
+                    self.stack.push(bytes::new_bytes(data));
+                }
+                op::BYTEARRAY8 => {
+                    let len = self.read_len(8)?;
+                    let data = self.read(len)?.to_vec();
+                    self.stack.push(bytes::new_bytearray(data));
+                }
+                op::EMPTY_SET => self.stack.push(new_set(Dict::build_new(), false)),
+                op::ADDITEMS => {
+                    let items = self.pop_mark()?;
+                    self.add_items(items)?;
+                }
+                op::FROZENSET => {
+                    let mut set = Dict::build_new();
+                    for item in self.pop_mark()? {
+                        set.set(item, Value::NoneT)?;
+                    }
+                    self.stack.push(new_set(set, true));
+                }
+                op::NEXT_BUFFER | op::READONLY_BUFFER => {
+                    return Err(unpickling_error("out-of-band buffers aren't supported"));
+                }
+                op::EMPTY_LIST => self.stack.push(new_list(Vec::new())),
+                op::APPEND => {
+                    let item = self.pop()?;
+                    self.extend(vec![item])?;
+                }
+                op::APPENDS => {
+                    let items = self.pop_mark()?;
+                    self.extend(items)?;
+                }
+                op::EMPTY_TUPLE => self.stack.push(new_tuple(Vec::new())),
+                op::TUPLE1 | op::TUPLE2 | op::TUPLE3 => {
+                    let len = (opcode - op::TUPLE1 + 1) as usize;
+                    if self.stack.len() < len {
+                        return Err(unpickling_error("unpickling stack underflow"));
+                    }
+                    let items = self.stack.split_off(self.stack.len() - len);
+                    self.stack.push(new_tuple(items));
+                }
+                op::TUPLE => {
+                    let items = self.pop_mark()?;
+                    self.stack.push(new_tuple(items));
+                }
+                op::EMPTY_DICT => self.stack.push(new_dict(Dict::build_new())),
+                op::SETITEM => {
+                    let value = self.pop()?;
+                    let key = self.pop()?;
+                    self.set_items(vec![key, value])?;
+                }
+                op::SETITEMS => {
+                    let items = self.pop_mark()?;
+                    self.set_items(items)?;
+                }
+                op::MARK => self.metastack.push(std::mem::take(&mut self.stack)),
+                op::POP => {
+                    if self.stack.is_empty() {
+                        self.pop_mark()?;
+                    } else {
+                        self.pop()?;
+                    }
+                }
+                op::POP_MARK => {
+                    self.pop_mark()?;
+                }
+                op::DUP => {
+                    let top = self.top()?;
+                    self.stack.push(top);
+                }
+                op::MEMOIZE => {
+                    let top = self.top()?;
+                    self.memo.insert(self.memo.len(), top);
+                }
+                op::BINPUT | op::LONG_BINPUT | op::PUT => {
+                    let index = match opcode {
+                        op::BINPUT => self.read(1)?[0] as usize,
+                        op::LONG_BINPUT => self.read_len(4)?,
+                        _ => self.read_line_int()?,
+                    };
+                    let top = self.top()?;
+                    self.memo.insert(index, top);
+                }
+                op::BINGET | op::LONG_BINGET | op::GET => {
+                    let index = match opcode {
+                        op::BINGET => self.read(1)?[0] as usize,
+                        op::LONG_BINGET => self.read_len(4)?,
+                        _ => self.read_line_int()?,
+                    };
+                    match self.memo.get(&index) {
+                        Some(value) => self.stack.push(value.clone()),
+                        None => return Err(unpickling_error(&format!("Memo value not found at index {}", index))),
+                    };
+                }
+                op::GLOBAL => {
+                    let module = self.read_line()?;
+                    let name = self.read_line()?;
+                    let global = self.find_class(&module, &name)?;
+                    self.stack.push(global);
+                }
+                op::STACK_GLOBAL => {
+                    let (name, module) = (self.pop()?, self.pop()?);
+                    let global = match (&module, &name) {
+                        (Value::Str(module), Value::Str(name)) => self.find_class(module, name)?,
+                        _ => return Err(unpickling_error("STACK_GLOBAL requires str")),
+                    };
+                    self.stack.push(global);
+                }
+                op::REDUCE => {
+                    let args = self.pop()?;
+                    let callable = self.pop()?;
+                    let value = self.call(&callable, args, Vec::new())?;
+                    self.stack.push(value);
+                }
+                op::NEWOBJ => {
+                    let args = self.pop()?;
+                    let class = self.pop()?;
+                    let value = self.new_object(&class, args, Vec::new())?;
+                    self.stack.push(value);
+                }
+                op::NEWOBJ_EX => {
+                    let kwargs = match self.pop()? {
+                        Value::Dict(ref dict) => dict.borrow().items(),
+                        _ => return Err(unpickling_error("NEWOBJ_EX kwargs must be a dict")),
+                    };
+                    let args = self.pop()?;
+                    let class = self.pop()?;
+                    let mut keywords: Vec<(String, Value)> = Vec::new();
+                    for (key, value) in kwargs {
+                        match key {
+                            Value::Str(ref key) => keywords.push((key.clone(), value)),
+                            _ => return Err(unpickling_error("keywords must be strings")),
+                        };
+                    }
+                    let value = self.new_object(&class, args, keywords)?;
+                    self.stack.push(value);
+                }
+                op::BUILD => {
+                    let state = self.pop()?;
+                    let object = self.top()?;
+                    self.build(&object, state)?;
+                }
+                _ => {
+                    let message = format!("invalid load key, '{}'.", (opcode as char).escape_default());
+                    return Err(unpickling_error(&message));
+                }
+            };
+        }
+    }
+
+    fn read(&mut self, len: usize) -> Result<&[u8], Exception> {
+        if self.data.len() - self.pos < len {
+            return Err(unpickling_error("pickle data was truncated"));
+        }
+        self.pos += len;
+        return Ok(&self.data[self.pos - len..self.pos]);
+    }
+
+    fn read_len(&mut self, size: usize) -> Result<usize, Exception> {
+        let bytes = self.read(size)?;
+        let len = match size {
+            4 => i32::from_le_bytes(bytes.try_into().unwrap()) as i64,
+            _ => u64::from_le_bytes(bytes.try_into().unwrap()) as i64,
+        };
+        if len < 0 {
+            return Err(unpickling_error("negative length in pickle data"));
+        }
+        return Ok(len as usize);
+    }
+
+    fn read_line(&mut self) -> Result<String, Exception> {
+        let rest = &self.data[self.pos..];
+        let len = rest.iter().position(|byte| *byte == b'\n').ok_or_else(|| unpickling_error("pickle data was truncated"))?;
+        let line = String::from_utf8_lossy(&rest[..len]).into_owned();
+        self.pos += len + 1;
+        return Ok(line);
+    }
+
+    fn read_line_int(&mut self) -> Result<usize, Exception> {
+        let line = self.read_line()?;
+        return line.trim().parse().map_err(|_| unpickling_error("invalid memo index"));
+    }
+
+    fn pop(&mut self) -> EvalResult {
+        return self.stack.pop().ok_or_else(|| unpickling_error("unpickling stack underflow"));
+    }
+
+    fn top(&self) -> EvalResult {
+        return self.stack.last().cloned().ok_or_else(|| unpickling_error("unpickling stack underflow"));
+    }
+
+    /* what has been pushed since the last MARK
+     */
+    fn pop_mark(&mut self) -> Result<Vec<Value>, Exception> {
+        let below = self.metastack.pop().ok_or_else(|| unpickling_error("could not find MARK"))?;
+        return Ok(std::mem::replace(&mut self.stack, below));
+    }
+
+    fn extend(&mut self, items: Vec<Value>) -> Result<(), Exception> {
+        match self.top()? {
+            Value::List(ref list) => list.borrow_mut().extend(items),
+            // e.g. an instance that __reduce__ gave listitems for
+            other => {
+                let append = self.interpreter.get_attr(&other, "append")?;
+                for item in items {
+                    self.interpreter.call(&append, Args::build_new(vec![item]))?;
+                }
+            }
+        };
+        return Ok(());
+    }
+
+    fn add_items(&mut self, items: Vec<Value>) -> Result<(), Exception> {
+        match self.top()? {
+            Value::Set(ref set) => {
+                for item in items {
+                    set.borrow_mut().set(item, Value::NoneT)?;
+                }
+            }
+            // e.g. an instance that __reduce__ gave set-like state
+            other => {
+                let add = self.interpreter.get_attr(&other, "add")?;
+                for item in items {
+                    self.interpreter.call(&add, Args::build_new(vec![item]))?;
+                }
+            }
+        };
+        return Ok(());
+    }
+
+    fn set_items(&mut self, items: Vec<Value>) -> Result<(), Exception> {
+        let target = self.top()?;
+        for pair in items.chunks(2) {
+            if pair.len() != 2 {
+                return Err(unpickling_error("odd number of items for SETITEMS"));
+            }
+            match &target {
+                Value::Dict(dict) => dict.borrow_mut().set(pair[0].clone(), pair[1].clone())?,
+                _ => match self.interpreter.call_special(&target, "__setitem__", pair.to_vec()) {
+                    Some(result) => {
+                        result?;
+                    }
+                    None => return Err(unpickling_error("SETITEMS on an object that isn't a dict")),
+                },
+            };
+        }
+        return Ok(());
+    }
+
+    fn call(&mut self, callable: &Value, args: Value, keywords: Vec<(String, Value)>) -> EvalResult {
+        let positional = match args {
+            Value::Tuple(ref args) => args.to_vec(),
+            _ => return Err(unpickling_error("the arguments of a call must be a tuple")),
+        };
+        return self.interpreter.call(callable, Args { positional, keywords });
+    }
+
+    /* class.__new__(class, *args, **keywords): a plain object that
+     * __init__ hasn't run on, or else what a native class makes of args
+     */
+    fn new_object(&mut self, class: &Value, args: Value, keywords: Vec<(String, Value)>) -> EvalResult {
+        match class {
+            Value::Class(class) if self.interpreter.is_plain_class(class) => match args {
+                Value::Tuple(ref args) => return Ok(self.interpreter.new_object(class, args)),
+                _ => return Err(unpickling_error("the arguments of a call must be a tuple")),
+            },
+            Value::Class(_) => return self.call(class, args, keywords),
+            _ => return Err(unpickling_error("NEWOBJ class argument isn't a type object")),
+        }
+    }
+
+    /* __setstate__(state), or else setting the attributes in state, which
+     * can also be (state, slotstate)
+     */
+    fn build(&mut self, object: &Value, state: Value) -> Result<(), Exception> {
+        if let Some(result) = self.interpreter.call_special(object, "__setstate__", vec![state.clone()]) {
+            result?;
+            return Ok(());
+        }
+        let states = match &state {
+            Value::Tuple(items) if items.len() == 2 => items.to_vec(),
+            _ => vec![state],
+        };
+        for state in states {
+            match state {
+                Value::NoneT => (),
+                Value::Dict(ref dict) => {
+                    for (key, value) in dict.borrow().items() {
+                        match key {
+                            Value::Str(ref attr) => self.interpreter.set_attr(object, attr, value)?,
+                            _ => return Err(unpickling_error("attribute names must be strings")),
+                        };
+                    }
+                }
+                _ => return Err(unpickling_error("state is not a dictionary")),
+            };
+        }
+        return Ok(());
+    }
+
+    fn find_class(&mut self, module: &str, name: &str) -> EvalResult {
+        let (mut module, mut name) = (module, name);
+        if self.protocol < 3 {
+            if let Some((_, global)) = PYTHON2_NAMES.iter().find(|(python2, _)| *python2 == (module, name)) {
+                (module, name) = *global;
+            } else if let Some((_, python3)) = PYTHON2_MODULES.iter().find(|(python2, _)| *python2 == module) {
+                module = python3;
+            }
+        }
+        let full_name = format!("{}.{}", module, name);
+        if let Some(allowed) = self.allowed {
+            if !allowed.contains(&full_name) {
+                return Err(unpickling_error(&format!("global '{}' is forbidden", full_name)));
+            }
+        }
+        // there's no _codecs module, only this function of it
+        if full_name == "_codecs.encode" {
+            return Ok(Builtin::build_new("encode", codecs_encode));
+        }
+        let mut value = Value::Module(self.interpreter.import_module(module)?);
+        for part in name.split('.') {
+            value = self.interpreter.get_attr(&value, part)?;
+        }
+        return Ok(value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testing::{error, eval, exec, python};
+
+    #[test]
+    fn test_cpython_pickles() {
+        let mut python = python("import pickle\n");
+        // pickle.dumps([1, 'a', (2.5, None)], protocol=2) in CPython
+        let cpython = b"\x80\x02]q\x00(K\x01X\x01\x00\x00\x00aq\x01G@\x04\x00\x00\x00\x00\x00\x00N\x86q\x02e.";
+        python.set("data", &cpython[..]);
+        assert_eq!(eval(&mut python, "pickle.loads(data)"), "[1, 'a', (2.5, None)]");
+        assert_eq!(eval(&mut python, "pickle.dumps(pickle.loads(data), 2) == data"), "True");
+
+        // {'key': [True, -300, 2 ** 40]} with protocol 4, framed
+        let cpython = b"\x80\x04\x95\x1c\x00\x00\x00\x00\x00\x00\x00}\x94\x8c\x03key\x94]\x94(\x88J\xd4\xfe\xff\xff\x8a\x06\x00\x00\x00\x00\x00\x01es.";
+        python.set("data", &cpython[..]);
+        assert_eq!(eval(&mut python, "pickle.loads(data)"), "{'key': [True, -300, 1099511627776]}");
+        assert_eq!(eval(&mut python, "pickle.dumps(pickle.loads(data)) == data"), "True");
+
+        // range(1, 9, 2) with protocol 2, as xrange
+        let cpython = b"\x80\x02c__builtin__\nxrange\nq\x00K\x01K\tK\x02\x87q\x01Rq\x02.";
+        python.set("data", &cpython[..]);
+        assert_eq!(eval(&mut python, "pickle.loads(data)"), "range(1, 9, 2)");
+        assert_eq!(eval(&mut python, "pickle.dumps(range(1, 9, 2), 2) == data"), "True");
+    }
+
+    #[test]
+    fn test_sets() {
+        let mut python = python("import pickle\n");
+        // {1, 2} with protocol 2, as __builtin__.set([1, 2])
+        let cpython = b"\x80\x02c__builtin__\nset\nq\x00]q\x01(K\x01K\x02e\x85q\x02Rq\x03.";
+        python.set("data", &cpython[..]);
+        assert_eq!(eval(&mut python, "pickle.loads(data)"), "{1, 2}");
+        assert_eq!(eval(&mut python, "pickle.dumps({1, 2}, 2) == data"), "True");
+
+        // [{1}, frozenset({2})] with protocol 4
+        let cpython = b"\x80\x04\x95\x10\x00\x00\x00\x00\x00\x00\x00]\x94(\x8f\x94(K\x01\x90(K\x02\x91\x94e.";
+        python.set("data", &cpython[..]);
+        assert_eq!(eval(&mut python, "pickle.loads(data)"), "[{1}, frozenset({2})]");
+        assert_eq!(eval(&mut python, "pickle.dumps([{1}, frozenset({2})]) == data"), "True");
+        let source = "
+shared = frozenset({'a'})
+for protocol in range(2, 6):
+    copy = pickle.loads(pickle.dumps([set(), frozenset(), {shared}, shared], protocol))
+    assert copy == [set(), frozenset(), {shared}, shared]
+    assert type(copy[0]) is set and type(copy[1]) is frozenset
+    assert next(iter(copy[2])) is copy[3]
+";
+        exec(&mut python, source);
+    }
+
+    #[test]
+    fn test_shared_references() {
+        let mut python = python("import pickle\n");
+        // shared and recursive containers keep their shape
+        let source = "
+shared = [1]
+nested = [shared, shared, range(3)]
+nested[2:3] = [nested]
+for protocol in range(2, 6):
+    copy = pickle.loads(pickle.dumps(nested, protocol))
+    assert copy[0] is copy[1] and copy[2] is copy and copy[0] == [1]
+";
+        exec(&mut python, source);
+    }
+
+    #[test]
+    fn test_bytes() {
+        let mut python = python("import pickle\n");
+        // [b'x', b'x'] with protocol 2, which has no bytes opcodes
+        let cpython = b"\x80\x02]q\x00(c_codecs\nencode\nq\x01X\x01\x00\x00\x00xq\x02X\x06\x00\x00\x00latin1q\x03\x86q\x04Rq\x05h\x05e.";
+        python.set("data", &cpython[..]);
+        assert_eq!(eval(&mut python, "pickle.safe_loads(data)"), "[b'x', b'x']");
+        exec(&mut python, "x = b'x'\n");
+        assert_eq!(eval(&mut python, "pickle.dumps([x, x], 2) == data"), "True");
+        let source = "
+for protocol in range(2, 6):
+    copy = pickle.loads(pickle.dumps([b'', bytearray(b'ab')], protocol))
+    assert copy == [b'', b'ab'] and type(copy[1]) is bytearray
+";
+        exec(&mut python, source);
+    }
+
+    #[test]
+    fn test_safe_loads() {
+        let mut python = python("import pickle\n");
+        // len('abc'): safe_loads() only calls what it's allowed to
+        let call: &[u8] = b"\x80\x02c__builtin__\nlen\nX\x03\x00\x00\x00abcq\x00\x85R.";
+        python.set("call", call);
+        assert_eq!(eval(&mut python, "pickle.loads(call)"), "3");
+        let message = "UnpicklingError: global 'builtins.len' is forbidden";
+        assert_eq!(error(&mut python, "pickle.safe_loads(call)"), message);
+        assert_eq!(eval(&mut python, "pickle.safe_loads(call, ['builtins.len'])"), "3");
+    }
+
+    #[test]
+    fn test_instances() {
+        let mut python = python("import pickle\n");
+        let source = "
+class Point:
+    def __init__(self, x, y):
+        self.x, self.y = x, y
+    def __reduce__(self):
+        return (Point, (self.x, self.y))
+
+class Counter:
+    def __init__(self):
+        self.count = 0
+        self.log = []
+    def __getstate__(self):
+        return {'count': self.count}
+    def __setstate__(self, saved):
+        self.count = saved['count']
+        self.log = ['restored']
+
+class Plain:
+    def __init__(self, x):
+        self.x = x
+        self.me = self
+
+counter = Counter()
+counter.count = 3
+for protocol in range(2, 6):
+    point = pickle.loads(pickle.dumps(Point(1, 2), protocol))
+    assert type(point) is Point and (point.x, point.y) == (1, 2)
+    copy = pickle.loads(pickle.dumps(counter, protocol))
+    assert type(copy) is Counter and copy.count == 3 and copy.log == ['restored']
+    plain = pickle.loads(pickle.dumps(Plain([1]), protocol))
+    assert type(plain) is Plain and plain.x == [1] and plain.me is plain
+";
+        exec(&mut python, source);
+
+        // an instance of class P: pass with p.x = 1, in CPython's __main__
+        let cpython = b"\x80\x04\x95\x1f\x00\x00\x00\x00\x00\x00\x00\x8c\x08__main__\x94\x8c\x01P\x94\x93\x94)\x81\x94}\x94\x8c\x01x\x94K\x01sb.";
+        python.set("data", &cpython[..]);
+        exec(&mut python, "class P:\n    def __init__(self):\n        raise ValueError\n");
+        exec(&mut python, "p = pickle.loads(data)\n");
+        assert_eq!(eval(&mut python, "(type(p) is P, p.x, pickle.dumps(p) == data)"), "(True, 1, True)");
+
+        // functions and classes are found by their module and name
+        exec(&mut python, "def f():\n    pass\ng = f\nh = lambda: 0\n");
+        assert_eq!(eval(&mut python, "pickle.loads(pickle.dumps(g)) is f"), "True");
+        let message = error(&mut python, "pickle.dumps(h)");
+        assert!(message.starts_with("PicklingError: Can't pickle <function <lambda> at "), "{}", message);
+    }
+
+    #[test]
+    fn test_files() {
+        let mut python = python("import io, pickle\n");
+        let source = "
+f = io.BytesIO()
+pickle.dump([1, 'a'], f)
+pickle.dump({'b': 2}, f, protocol=2)
+f.seek(0)
+first = pickle.load(f)
+second = pickle.load(f)
+";
+        exec(&mut python, source);
+        assert_eq!(eval(&mut python, "(first, second, f.read())"), "([1, 'a'], {'b': 2}, b'')");
+        assert_eq!(error(&mut python, "pickle.dump(1, 2)"), "TypeError: file must have a 'write' attribute");
+        assert_eq!(error(&mut python, "pickle.load(2)"), "TypeError: file must have a 'read' attribute");
+        assert_eq!(error(&mut python, "pickle.load(io.BytesIO())"), "UnpicklingError: pickle data was truncated");
+    }
+
+    #[test]
+    fn test_errors() {
+        let mut python = python("import pickle\n");
+        assert_eq!(error(&mut python, "pickle.dumps(iter([]))"), "TypeError: cannot pickle 'list_iterator' object");
+        assert_eq!(error(&mut python, "pickle.loads(b'\\x80\\x04]')"), "UnpicklingError: pickle data was truncated");
+        assert_eq!(error(&mut python, "pickle.loads('')"), "TypeError: a bytes-like object is required, not 'str'");
+    }
+}
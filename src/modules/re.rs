@@ -0,0 +1,1185 @@
+use crate::dict::{new_dict, Dict};
+use crate::interpreter::{not_an_int, EvalResult, Interpreter};
+use crate::iterator::{new_iterator, Iter};
+use crate::native::{bind, instance, NativeClass};
+use crate::value::{new_list, new_tuple, Args, Builtin, Class, Exception, Module, Object, Value};
+use regex::{CaptureLocations, Regex};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::rc::Rc;
+
+/* re - Python regular expressions, matched by the regex crate
+ *
+ * Patterns are translated from Python's syntax to the regex crate's, which
+ * matches in linear time without backtracking. What it can't do raises
+ * re.error when the pattern is compiled: backreferences, look-ahead and
+ * look-behind assertions, atomic groups, possessive quantifiers and
+ * conditional groups. Without MULTILINE, $ only matches at the very end of
+ * the string, not also before a newline ending it. Positions are counted
+ * in characters, like CPython. There's no | on ints, so flags are
+ * combined with +, e.g. re.IGNORECASE + re.MULTILINE.
+ */
+
+const IGNORECASE: i64 = 2;
+const LOCALE: i64 = 4;
+const MULTILINE: i64 = 8;
+const DOTALL: i64 = 16;
+const UNICODE: i64 = 32;
+const VERBOSE: i64 = 64;
+const ASCII: i64 = 256;
+
+// compiled patterns kept for the module-level functions, like CPython
+const MAX_CACHE: usize = 512;
+
+type MethodFn = fn(&mut Interpreter, &Call) -> EvalResult;
+
+// Pattern methods: name, parameters after the pattern, how many of them
+// are required. The module-level functions are the same without pos and
+// endpos, with the pattern first and flags last.
+const METHODS: &[(&str, &[&str], usize, MethodFn)] = &[
+    ("match", &["string", "pos", "endpos"], 1, match_method),
+    ("search", &["string", "pos", "endpos"], 1, search),
+    ("fullmatch", &["string", "pos", "endpos"], 1, fullmatch),
+    ("findall", &["string", "pos", "endpos"], 1, findall),
+    ("finditer", &["string", "pos", "endpos"], 1, finditer),
+    ("sub", &["repl", "string", "count"], 2, sub),
+    ("subn", &["repl", "string", "count"], 2, subn),
+    ("split", &["string", "maxsplit"], 1, split),
+];
+
+pub fn build_module(object: Rc<Class>) -> Rc<Module> {
+    let matched = match_class().build(object.clone());
+    let re = Rc::new(Re {
+        pattern: pattern_class(&matched).build(object),
+        matched,
+        cache: RefCell::new(HashMap::new()),
+    });
+
+    let module = Module::build_new("re", None);
+    for (name, params, required, method) in METHODS {
+        let (name, required, method) = (*name, *required, *method);
+        let mut module_params = vec!["pattern"];
+        module_params.extend(params.iter().filter(|param| !["pos", "endpos"].contains(param)));
+        module_params.push("flags");
+        let re = re.clone();
+        let func = move |interpreter: &mut Interpreter, args: Args| {
+            let bound = bind(name, args, &module_params, 1 + required)?;
+            let flags = int_arg(&bound[bound.len() - 1], 0)?;
+            let (pattern, compiled) = re.compile(&bound[0], flags)?;
+            let args = params
+                .iter()
+                .map(|param| module_params.iter().position(|name| name == param).and_then(|pos| bound[pos].clone()))
+                .collect();
+            let call = Call {
+                pattern,
+                compiled,
+                matched: &re.matched,
+                args,
+            };
+            return method(interpreter, &call);
+        };
+        module.set(name, Builtin::build_native(name, Rc::new(func)));
+    }
+    let compile = {
+        let re = re.clone();
+        move |_: &mut Interpreter, args: Args| {
+            let bound = bind("compile", args, &["pattern", "flags"], 1)?;
+            return Ok(re.compile(&bound[0], int_arg(&bound[1], 0)?)?.0);
+        }
+    };
+    module.set("compile", Builtin::build_native("compile", Rc::new(compile)));
+    let purge = {
+        let re = re.clone();
+        move |_: &mut Interpreter, args: Args| {
+            args.check("purge", 0, 0)?;
+            re.cache.borrow_mut().clear();
+            return Ok(Value::NoneT);
+        }
+    };
+    module.set("purge", Builtin::build_native("purge", Rc::new(purge)));
+    module.set("escape", Builtin::build_new("escape", escape));
+
+    let flags = [
+        ("IGNORECASE", "I", IGNORECASE),
+        ("LOCALE", "L", LOCALE),
+        ("MULTILINE", "M", MULTILINE),
+        ("DOTALL", "S", DOTALL),
+        ("UNICODE", "U", UNICODE),
+        ("VERBOSE", "X", VERBOSE),
+        ("ASCII", "A", ASCII),
+    ];
+    for (name, short, flag) in &flags {
+        module.set(name, Value::Int(*flag));
+        module.set(short, Value::Int(*flag));
+    }
+    module.set("NOFLAG", Value::Int(0));
+    module.set_class(re.pattern.clone());
+    module.set_class(re.matched.clone());
+    return Rc::new(module);
+}
+
+fn error(message: &str, pos: usize) -> Exception {
+    let message = format!("{} at position {}", message, pos);
+    return Exception::new("re.error", &message);
+}
+
+fn int_arg(value: &Option<Value>, default: i64) -> Result<i64, Exception> {
+    match value {
+        None | Some(Value::NoneT) => return Ok(default),
+        Some(Value::Int(val)) => return Ok(*val),
+        Some(Value::Bool(val)) => return Ok(*val as i64),
+        Some(other) => return Err(not_an_int(other)),
+    }
+}
+
+fn text(value: &Option<Value>) -> Result<Rc<str>, Exception> {
+    match value {
+        Some(Value::Str(text)) => return Ok(Rc::from(text.as_str())),
+        other => {
+            let given = other.as_ref().map_or("NoneType", Value::type_name);
+            let message = format!("expected string or bytes-like object, got '{}'", given);
+            return Err(Exception::new("TypeError", &message));
+        }
+    }
+}
+
+/* the byte offset of a position in characters
+ */
+fn byte_offset(text: &str, pos: usize) -> usize {
+    return text.char_indices().nth(pos).map_or(text.len(), |(offset, _)| offset);
+}
+
+/* escape(pattern): pattern with the characters that mean something in a
+ * regular expression escaped
+ */
+fn escape(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("escape", 1, 1)?;
+    let pattern = text(&args.positional.first().cloned())?;
+    let mut out = String::new();
+    for c in pattern.chars() {
+        if "()[]{}?*+-|^$\\.&~# \t\n\r\x0b\x0c".contains(c) {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    return Ok(Value::Str(out));
+}
+
+/* The module's classes, and the patterns compiled by its functions
+ */
+struct Re {
+    pattern: Rc<Class>,
+    matched: Rc<Class>,
+    cache: RefCell<HashMap<(String, i64), Value>>,
+}
+
+impl Re {
+    /* a Pattern object for pattern, which may already be one
+     */
+    fn compile(&self, pattern: &Option<Value>, flags: i64) -> Result<(Value, Rc<Compiled>), Exception> {
+        if let Some(compiled) = pattern.as_ref().and_then(compiled_of) {
+            if flags != 0 {
+                return Err(Exception::new("ValueError", "cannot process flags argument with a compiled pattern"));
+            }
+            return Ok((pattern.clone().unwrap(), compiled));
+        }
+        let source = match pattern {
+            Some(Value::Str(source)) => source,
+            _ => return Err(Exception::new("TypeError", "first argument must be string or compiled pattern")),
+        };
+        let key = (source.clone(), flags);
+        if let Some(pattern) = self.cache.borrow().get(&key) {
+            return Ok((pattern.clone(), compiled_of(pattern).unwrap()));
+        }
+        let compiled = Rc::new(Compiled::build_new(source, flags)?);
+        let native: Box<Rc<Compiled>> = Box::new(compiled.clone());
+        let pattern = Value::Object(Object::build_new(self.pattern.clone(), Some(native)));
+        let mut cache = self.cache.borrow_mut();
+        if cache.len() >= MAX_CACHE {
+            cache.clear();
+        }
+        cache.insert(key, pattern.clone());
+        return Ok((pattern, compiled));
+    }
+}
+
+fn compiled_of(value: &Value) -> Option<Rc<Compiled>> {
+    if let Value::Object(object) = value {
+        if let Some(native) = &object.native {
+            return native.borrow().downcast_ref::<Rc<Compiled>>().cloned();
+        }
+    }
+    return None;
+}
+
+/* A pattern translated and compiled
+ */
+struct Compiled {
+    pattern: String,
+    flags: i64,
+    // leftmost-first, like Python's backtracking
+    search: Regex,
+    // the same, matching up to the end, for fullmatch()
+    full: Regex,
+    // the name of each group, by number
+    names: Vec<Option<String>>,
+}
+
+impl Compiled {
+    fn build_new(pattern: &str, flags: i64) -> Result<Compiled, Exception> {
+        if flags & LOCALE != 0 {
+            return Err(Exception::new("ValueError", "cannot use LOCALE flag with a str pattern"));
+        }
+        if flags & ASCII != 0 && flags & UNICODE != 0 {
+            return Err(Exception::new("ValueError", "ASCII and UNICODE flags are incompatible"));
+        }
+        let mut translator = Translator {
+            chars: pattern.chars().collect(),
+            pos: 0,
+            out: String::new(),
+            flags,
+            open: Vec::new(),
+            global_end: 0,
+            repeatable: false,
+            quantified: false,
+        };
+        translator.translate()?;
+        let flags = translator.flags;
+
+        let mut prefix = String::new();
+        for (flag, letter) in &[(IGNORECASE, 'i'), (MULTILINE, 'm'), (DOTALL, 's')] {
+            if flags & flag != 0 {
+                prefix.push(*letter);
+            }
+        }
+        if !prefix.is_empty() {
+            prefix = format!("(?{})", prefix);
+        }
+        let search = Regex::new(&format!("{}{}", prefix, translator.out)).map_err(compile_error)?;
+        let full = Regex::new(&format!("{}(?:{})\\z", prefix, translator.out)).map_err(compile_error)?;
+        let names = search.capture_names().map(|name| name.map(String::from)).collect();
+        return Ok(Compiled {
+            pattern: String::from(pattern),
+            flags: if flags & ASCII == 0 { flags | UNICODE } else { flags },
+            search,
+            full,
+            names,
+        });
+    }
+
+    fn groups(&self) -> usize {
+        return self.names.len() - 1;
+    }
+}
+
+/* what the regex crate says is wrong with a translated pattern, without
+ * its picture of the pattern
+ */
+fn compile_error(err: regex::Error) -> Exception {
+    let message = err.to_string();
+    let last = message.lines().last().unwrap_or_default();
+    return Exception::new("re.error", last.trim_start_matches("error: "));
+}
+
+/* Python pattern syntax, rewritten for the regex crate
+ */
+struct Translator {
+    chars: Vec<char>,
+    pos: usize,
+    out: String,
+    // the flags in effect, with those set by (?x) and (?a:...)
+    flags: i64,
+    // the flags outside each open group, and where the group started
+    open: Vec<(i64, usize)>,
+    // where the leading (?flags) groups end; global flags can't come later
+    global_end: usize,
+    // whether what was just written can take a quantifier
+    repeatable: bool,
+    // whether it was a quantifier
+    quantified: bool,
+}
+
+impl Translator {
+    fn next(&mut self) -> Option<char> {
+        let c = self.chars.get(self.pos).copied();
+        self.pos += 1;
+        return c;
+    }
+
+    fn peek(&self) -> Option<char> {
+        return self.chars.get(self.pos).copied();
+    }
+
+    fn literal(&mut self, c: char) {
+        self.out.push_str(&regex::escape(c.encode_utf8(&mut [0; 4])));
+    }
+
+    fn translate(&mut self) -> Result<(), Exception> {
+        while self.pos < self.chars.len() {
+            let start = self.pos;
+            let c = self.next().unwrap();
+            let quantified = std::mem::replace(&mut self.quantified, false);
+            match c {
+                '\\' => self.repeatable = self.escape(false)?,
+                '[' => {
+                    self.class(start)?;
+                    self.repeatable = true;
+                }
+                '(' => self.group(start)?,
+                ')' => {
+                    let (flags, _) = self.open.pop().ok_or_else(|| error("unbalanced parenthesis", start))?;
+                    self.flags = flags;
+                    self.out.push(')');
+                    self.repeatable = true;
+                }
+                '*' | '+' | '?' => self.quantifier(c.to_string(), start, quantified)?,
+                '{' => match self.repeat_count(start)? {
+                    Some(count) => self.quantifier(count, start, quantified)?,
+                    None => {
+                        self.literal('{');
+                        self.repeatable = true;
+                    }
+                },
+                '|' | '^' | '$' => {
+                    self.out.push(c);
+                    self.repeatable = false;
+                }
+                '.' => {
+                    self.out.push(c);
+                    self.repeatable = true;
+                }
+                c if self.flags & VERBOSE != 0 && " \t\n\r\x0b\x0c".contains(c) => self.quantified = quantified,
+                '#' if self.flags & VERBOSE != 0 => {
+                    while let Some(c) = self.next() {
+                        if c == '\n' {
+                            break;
+                        }
+                    }
+                    self.quantified = quantified;
+                }
+                c => {
+                    self.literal(c);
+                    self.repeatable = true;
+                }
+            };
+        }
+        if let Some((_, start)) = self.open.last() {
+            return Err(error("missing ), unterminated subpattern", *start));
+        }
+        return Ok(());
+    }
+
+    fn quantifier(&mut self, quantifier: String, start: usize, quantified: bool) -> Result<(), Exception> {
+        if quantified {
+            return Err(error("multiple repeat", start));
+        } else if !self.repeatable {
+            return Err(error("nothing to repeat", start));
+        }
+        self.out.push_str(&quantifier);
+        match self.peek() {
+            Some('?') => {
+                self.pos += 1;
+                self.out.push('?');
+            }
+            Some('+') => return Err(error("possessive quantifiers are not supported", start)),
+            _ => (),
+        };
+        self.repeatable = false;
+        self.quantified = true;
+        return Ok(());
+    }
+
+    /* The {m,n} after a '{', in the regex crate's syntax. Anything else
+     * leaves the '{' a literal, like in Python.
+     */
+    fn repeat_count(&mut self, start: usize) -> Result<Option<String>, Exception> {
+        let digits = |translator: &Self, from: usize| -> String {
+            translator.chars[from..].iter().take_while(|c| c.is_ascii_digit()).collect()
+        };
+        let min = digits(self, self.pos);
+        let mut end = self.pos + min.len();
+        let max = if self.chars.get(end) == Some(&',') {
+            let max = digits(self, end + 1);
+            end += 1 + max.len();
+            Some(max)
+        } else {
+            None
+        };
+        if self.chars.get(end) != Some(&'}') || (min.is_empty() && max.is_none()) {
+            return Ok(None);
+        }
+        self.pos = end + 1;
+        let parse = |count: &str| count.parse::<u32>().map_err(|_| error("the repetition number is too large", start));
+        let min = if min.is_empty() { 0 } else { parse(&min)? };
+        match max.as_deref() {
+            None => return Ok(Some(format!("{{{}}}", min))),
+            Some("") => return Ok(Some(format!("{{{},}}", min))),
+            Some(max) => {
+                let max = parse(max)?;
+                if max < min {
+                    return Err(error("min repeat greater than max repeat", start + 1));
+                }
+                return Ok(Some(format!("{{{},{}}}", min, max)));
+            }
+        }
+    }
+
+    /* the escape after a backslash; returns whether it can be repeated,
+     * which anchors can't
+     */
+    fn escape(&mut self, in_class: bool) -> Result<bool, Exception> {
+        let start = self.pos - 1;
+        let c = self.next().ok_or_else(|| error("bad escape (end of pattern)", start))?;
+        let ascii = self.flags & ASCII != 0;
+        match c {
+            'A' | 'Z' | 'b' | 'B' if !in_class => {
+                let anchor = match c {
+                    'A' => "\\A",
+                    'Z' => "\\z",
+                    'b' if ascii => "(?-u:\\b)",
+                    'b' => "\\b",
+                    _ if ascii => "(?-u:\\B)",
+                    _ => "\\B",
+                };
+                self.out.push_str(anchor);
+                return Ok(false);
+            }
+            'b' => self.literal('\x08'),
+            'd' | 'D' | 'w' | 'W' | 's' | 'S' if ascii => {
+                let class = match c.to_ascii_lowercase() {
+                    'd' => "0-9",
+                    'w' => "0-9A-Za-z_",
+                    _ => "\\t\\n\\x0b\\x0c\\r ",
+                };
+                let negated = if c.is_ascii_uppercase() { "^" } else { "" };
+                self.out.push_str(&format!("[{}{}]", negated, class));
+            }
+            // Python's whitespace also has the separators \x1c to \x1f
+            's' => self.out.push_str("[\\s\\x1c-\\x1f]"),
+            'S' => self.out.push_str("[^\\s\\x1c-\\x1f]"),
+            'd' | 'D' | 'w' | 'W' => {
+                self.out.push('\\');
+                self.out.push(c);
+            }
+            'a' => self.literal('\x07'),
+            'f' => self.literal('\x0c'),
+            'n' => self.literal('\n'),
+            'r' => self.literal('\r'),
+            't' => self.literal('\t'),
+            'v' => self.literal('\x0b'),
+            'x' | 'u' | 'U' => {
+                let len = match c {
+                    'x' => 2,
+                    'u' => 4,
+                    _ => 8,
+                };
+                let digits: String = self.chars[self.pos..].iter().take(len).take_while(|c| c.is_ascii_hexdigit()).collect();
+                self.pos += digits.len();
+                let escape: String = self.chars[start..self.pos].iter().collect();
+                if digits.len() < len {
+                    return Err(error(&format!("incomplete escape {}", escape), start));
+                }
+                match char::from_u32(u32::from_str_radix(&digits, 16).unwrap()) {
+                    Some(c) => self.literal(c),
+                    None => return Err(error(&format!("bad escape {}", escape), start)),
+                };
+            }
+            'N' => return Err(error("named Unicode escapes are not supported", start)),
+            '0'..='9' => {
+                // \0 and three octal digits are characters, other numbers
+                // refer to groups
+                let octal = self.chars[self.pos..].iter().take(2).take_while(|c| ('0'..='7').contains(*c)).count();
+                if c == '0' || (c <= '7' && octal == 2) {
+                    let digits = if c == '0' { octal } else { 2 };
+                    self.pos += digits;
+                    let escape: String = self.chars[start + 1..self.pos].iter().collect();
+                    let code = u32::from_str_radix(&escape, 8).unwrap();
+                    if code > 0o377 {
+                        let message = format!("octal escape value \\{} outside of range 0-0o377", escape);
+                        return Err(error(&message, start));
+                    }
+                    self.literal(char::from_u32(code).unwrap());
+                } else if in_class {
+                    return Err(error(&format!("bad escape \\{}", c), start));
+                } else {
+                    return Err(error("backreferences are not supported", start));
+                }
+            }
+            c if c.is_ascii_alphanumeric() => return Err(error(&format!("bad escape \\{}", c), start)),
+            c => self.literal(c),
+        };
+        return Ok(true);
+    }
+
+    /* a character class, from its '['
+     */
+    fn class(&mut self, start: usize) -> Result<(), Exception> {
+        self.out.push('[');
+        if self.peek() == Some('^') {
+            self.pos += 1;
+            self.out.push('^');
+        }
+        // a ']' first is part of the set
+        if self.peek() == Some(']') {
+            self.pos += 1;
+            self.out.push_str("\\]");
+        }
+        loop {
+            match self.next() {
+                None => return Err(error("unterminated character set", start)),
+                Some(']') => break,
+                Some('\\') => {
+                    self.escape(true)?;
+                }
+                // nested classes and set operations in the regex crate
+                Some(c @ '[') | Some(c @ '&') | Some(c @ '~') => self.literal(c),
+                Some('-') if self.peek() == Some('-') => self.literal('-'),
+                Some(c) => self.out.push(c),
+            };
+        }
+        self.out.push(']');
+        return Ok(());
+    }
+
+    /* a group, from its '('
+     */
+    fn group(&mut self, start: usize) -> Result<(), Exception> {
+        if self.peek() != Some('?') {
+            self.open_group("(", start);
+            return Ok(());
+        }
+        self.pos += 1;
+        let c = self.next().ok_or_else(|| error("unexpected end of pattern", start + 2))?;
+        match c {
+            ':' => self.open_group("(?:", start),
+            '#' => loop {
+                match self.next() {
+                    Some(')') => break,
+                    Some(_) => (),
+                    None => return Err(error("missing ), unterminated comment", start)),
+                };
+            },
+            'P' => match self.next() {
+                Some('<') => {
+                    let name: String = self.chars[self.pos..].iter().take_while(|c| **c != '>').collect();
+                    self.pos += name.len() + 1;
+                    if self.pos > self.chars.len() {
+                        return Err(error("missing >, unterminated name", start + 4));
+                    }
+                    let mut chars = name.chars();
+                    let first = chars.next().is_some_and(|c| c.is_alphabetic() || c == '_');
+                    if !first || !chars.all(|c| c.is_alphanumeric() || c == '_') {
+                        return Err(error(&format!("bad character in group name '{}'", name), start + 4));
+                    }
+                    self.open_group(&format!("(?P<{}>", name), start);
+                }
+                Some('=') => return Err(error("backreferences are not supported", start)),
+                _ => return Err(error("unknown extension ?P", start + 1)),
+            },
+            '=' | '!' => return Err(error("look-ahead assertions are not supported", start)),
+            '<' if matches!(self.peek(), Some('=') | Some('!')) => {
+                return Err(error("look-behind assertions are not supported", start));
+            }
+            '>' => return Err(error("atomic groups are not supported", start)),
+            '(' => return Err(error("conditional groups are not supported", start)),
+            'a' | 'i' | 'L' | 'm' | 's' | 'u' | 'x' | '-' => {
+                self.pos -= 1;
+                self.inline_flags(start)?;
+            }
+            c => return Err(error(&format!("unknown extension ?{}", c), start + 1)),
+        };
+        return Ok(());
+    }
+
+    fn open_group(&mut self, opening: &str, start: usize) {
+        self.open.push((self.flags, start));
+        self.out.push_str(opening);
+        self.repeatable = false;
+    }
+
+    /* (?aiLmsux) for the whole pattern, or (?aiLmsux-imsx:...) for a group
+     */
+    fn inline_flags(&mut self, start: usize) -> Result<(), Exception> {
+        let (mut on, mut off) = (0, 0);
+        let mut turning_off = false;
+        let end = loop {
+            let flag = match self.next() {
+                Some('a') => ASCII,
+                Some('i') => IGNORECASE,
+                Some('L') => return Err(error("bad inline flag: cannot use 'L' flag with a str pattern", self.pos - 1)),
+                Some('m') => MULTILINE,
+                Some('s') => DOTALL,
+                Some('u') => UNICODE,
+                Some('x') => VERBOSE,
+                Some('-') if !turning_off => {
+                    turning_off = true;
+                    continue;
+                }
+                Some(end @ ':') | Some(end @ ')') => break end,
+                Some(_) => return Err(error("unknown flag", self.pos - 1)),
+                None => return Err(error("missing -, : or )", self.pos - 1)),
+            };
+            if turning_off && flag & (ASCII | UNICODE) != 0 {
+                return Err(error("bad inline flag: cannot turn off flags 'a', 'u' and 'L'", self.pos - 1));
+            }
+            if turning_off {
+                off |= flag;
+            } else {
+                on |= flag;
+            }
+        };
+
+        if end == ')' {
+            if turning_off {
+                return Err(error("missing :", self.pos - 1));
+            }
+            if start != self.global_end {
+                return Err(error("global flags not at the start of the expression", start));
+            }
+            // written in front of the whole pattern
+            self.flags |= on;
+            self.global_end = self.pos;
+            self.repeatable = false;
+            return Ok(());
+        }
+        let letters = |flags: i64| -> String {
+            let letters = [(IGNORECASE, 'i'), (MULTILINE, 'm'), (DOTALL, 's')];
+            return letters.iter().filter(|(flag, _)| flags & flag != 0).map(|(_, letter)| *letter).collect();
+        };
+        let (on_letters, off_letters) = (letters(on), letters(off));
+        let opening = match (on_letters.is_empty(), off_letters.is_empty()) {
+            (true, true) => String::from("(?:"),
+            (_, true) => format!("(?{}:", on_letters),
+            _ => format!("(?{}-{}:", on_letters, off_letters),
+        };
+        self.open_group(&opening, start);
+        self.flags = (self.flags | on) & !off;
+        return Ok(());
+    }
+}
+
+/* the byte span of each group of a match, None for those that didn't
+ * take part
+ */
+type Spans = Vec<Option<(usize, usize)>>;
+
+/* the string searched, the pos and endpos given in characters, and the
+ * same in bytes
+ */
+type Bounds = (Rc<str>, (usize, usize), (usize, usize));
+
+/* The matches in text[start..end] (bytes), at most limit of them if it
+ * isn't 0. Like CPython, an empty match can't start where an empty match
+ * just ended.
+ */
+fn find_all(
+    interpreter: &mut Interpreter,
+    regex: &Regex,
+    text: &str,
+    start: usize,
+    end: usize,
+    limit: usize,
+) -> Result<Vec<Spans>, Exception> {
+    let text = &text[..end];
+    let mut locations: CaptureLocations = regex.capture_locations();
+    let mut found = Vec::new();
+    let mut pos = start;
+    let mut after_empty = false;
+    while pos <= text.len() && (limit == 0 || found.len() < limit) {
+        interpreter.tick()?;
+        if regex.captures_read_at(&mut locations, text, pos).is_none() {
+            break;
+        }
+        let (match_start, match_end) = locations.get(0).unwrap();
+        if after_empty && match_start == pos && match_end == pos {
+            match text[pos..].chars().next() {
+                Some(c) => pos += c.len_utf8(),
+                None => break,
+            };
+            after_empty = false;
+            continue;
+        }
+        found.push((0..locations.len()).map(|group| locations.get(group)).collect());
+        after_empty = match_start == match_end;
+        pos = match_end;
+    }
+    return Ok(found);
+}
+
+/* A call of a Pattern method, or of the module function that does the same
+ */
+struct Call<'a> {
+    pattern: Value,
+    compiled: Rc<Compiled>,
+    matched: &'a Rc<Class>,
+    // the method's parameters, as bound
+    args: Vec<Option<Value>>,
+}
+
+impl Call<'_> {
+    /* the string args[0] and the part of it args[1] and args[2] give
+     */
+    fn bounds(&self) -> Result<Bounds, Exception> {
+        let text = text(&self.args[0])?;
+        let len = text.chars().count() as i64;
+        let pos = int_arg(&self.args[1], 0)?.max(0).min(len) as usize;
+        let endpos = int_arg(&self.args[2], len)?.max(0).min(len) as usize;
+        let bytes = (byte_offset(&text, pos), byte_offset(&text, endpos));
+        return Ok((text, (pos, endpos), bytes));
+    }
+
+    fn new_match(&self, text: &Rc<str>, spans: Spans, (pos, endpos): (usize, usize)) -> Value {
+        let native = Match {
+            text: text.clone(),
+            spans,
+            pattern: self.pattern.clone(),
+            compiled: self.compiled.clone(),
+            pos,
+            endpos,
+        };
+        return Value::Object(Object::build_new(self.matched.clone(), Some(Box::new(native))));
+    }
+
+    /* the match at pos, or anywhere after it
+     */
+    fn find(&self, interpreter: &mut Interpreter, regex: &Regex, anchored: bool) -> EvalResult {
+        let (text, chars, (start, end)) = self.bounds()?;
+        match find_all(interpreter, regex, &text, start, end, 1)?.pop() {
+            Some(spans) if !anchored || spans[0].unwrap().0 == start => return Ok(self.new_match(&text, spans, chars)),
+            _ => return Ok(Value::NoneT),
+        }
+    }
+}
+
+fn match_method(interpreter: &mut Interpreter, call: &Call) -> EvalResult {
+    return call.find(interpreter, &call.compiled.search, true);
+}
+
+fn search(interpreter: &mut Interpreter, call: &Call) -> EvalResult {
+    return call.find(interpreter, &call.compiled.search, false);
+}
+
+fn fullmatch(interpreter: &mut Interpreter, call: &Call) -> EvalResult {
+    return call.find(interpreter, &call.compiled.full, true);
+}
+
+fn findall(interpreter: &mut Interpreter, call: &Call) -> EvalResult {
+    let (text, _, (start, end)) = call.bounds()?;
+    let group = |spans: &Spans, group: usize| Value::Str(spans[group].map_or("", |(start, end)| &text[start..end]).to_string());
+    let mut items = Vec::new();
+    for spans in find_all(interpreter, &call.compiled.search, &text, start, end, 0)? {
+        let item = match call.compiled.groups() {
+            0 => group(&spans, 0),
+            1 => group(&spans, 1),
+            groups => new_tuple((1..=groups).map(|index| group(&spans, index)).collect()),
+        };
+        items.push(item);
+    }
+    return Ok(new_list(items));
+}
+
+fn finditer(interpreter: &mut Interpreter, call: &Call) -> EvalResult {
+    let (text, chars, (start, end)) = call.bounds()?;
+    let found = find_all(interpreter, &call.compiled.search, &text, start, end, 0)?;
+    let matches = found.into_iter().map(|spans| call.new_match(&text, spans, chars)).collect();
+    return Ok(new_iterator(Iter::Items(matches, 0, "callable_iterator")));
+}
+
+/* string with the first count matches (all for 0) replaced, and how many
+ * there were
+ */
+fn substitute(interpreter: &mut Interpreter, call: &Call) -> Result<(String, usize), Exception> {
+    let repl = call.args[0].clone().unwrap();
+    let text = text(&call.args[1])?;
+    let count = int_arg(&call.args[2], 0)?;
+    if count < 0 {
+        return Ok((text.to_string(), 0));
+    }
+    let template = match &repl {
+        Value::Str(template) => Some(parse_template(template, &call.compiled)?),
+        _ => None,
+    };
+    let found = find_all(interpreter, &call.compiled.search, &text, 0, text.len(), count as usize)?;
+    let replaced = found.len();
+    let mut out = String::new();
+    let mut last = 0;
+    for spans in found {
+        let (start, end) = spans[0].unwrap();
+        out.push_str(&text[last..start]);
+        match &template {
+            Some(template) => expand(template, &text, &spans, &mut out),
+            None => {
+                let matched = call.new_match(&text, spans, (0, text.chars().count()));
+                match interpreter.call(&repl, Args::build_new(vec![matched]))? {
+                    Value::Str(ref replacement) => out.push_str(replacement),
+                    other => {
+                        let message = format!("expected str instance, {} found", other.type_name());
+                        return Err(Exception::new("TypeError", &message));
+                    }
+                };
+            }
+        };
+        last = end;
+    }
+    out.push_str(&text[last..]);
+    return Ok((out, replaced));
+}
+
+fn sub(interpreter: &mut Interpreter, call: &Call) -> EvalResult {
+    return Ok(Value::Str(substitute(interpreter, call)?.0));
+}
+
+fn subn(interpreter: &mut Interpreter, call: &Call) -> EvalResult {
+    let (text, replaced) = substitute(interpreter, call)?;
+    return Ok(new_tuple(vec![Value::Str(text), Value::Int(replaced as i64)]));
+}
+
+fn split(interpreter: &mut Interpreter, call: &Call) -> EvalResult {
+    let text = text(&call.args[0])?;
+    let maxsplit = int_arg(&call.args[1], 0)?;
+    if maxsplit < 0 {
+        return Ok(new_list(vec![Value::Str(text.to_string())]));
+    }
+    let mut pieces = Vec::new();
+    let mut last = 0;
+    for spans in find_all(interpreter, &call.compiled.search, &text, 0, text.len(), maxsplit as usize)? {
+        let (start, end) = spans[0].unwrap();
+        pieces.push(Value::Str(text[last..start].to_string()));
+        for span in &spans[1..] {
+            pieces.push(span.map_or(Value::NoneT, |(start, end)| Value::Str(text[start..end].to_string())));
+        }
+        last = end;
+    }
+    pieces.push(Value::Str(text[last..].to_string()));
+    return Ok(new_list(pieces));
+}
+
+/* A part of a replacement template
+ */
+enum Piece {
+    Literal(String),
+    Group(usize),
+}
+
+/* A replacement string: \g<name>, \g<1> and \1 refer to groups, and the
+ * escapes \n, \t, \\ and so on are characters, like in CPython
+ */
+fn parse_template(template: &str, compiled: &Compiled) -> Result<Vec<Piece>, Exception> {
+    let chars: Vec<char> = template.chars().collect();
+    let mut pieces = Vec::new();
+    let mut literal = String::new();
+    let mut pos = 0;
+    let group = |index: usize, start: usize| -> Result<Piece, Exception> {
+        if index > compiled.groups() {
+            return Err(error(&format!("invalid group reference {}", index), start));
+        }
+        return Ok(Piece::Group(index));
+    };
+    while pos < chars.len() {
+        let c = chars[pos];
+        pos += 1;
+        if c != '\\' {
+            literal.push(c);
+            continue;
+        }
+        let start = pos - 1;
+        let c = match chars.get(pos) {
+            Some(c) => *c,
+            None => return Err(error("bad escape (end of pattern)", start)),
+        };
+        pos += 1;
+        let piece = match c {
+            'g' => {
+                if chars.get(pos) != Some(&'<') {
+                    return Err(error("missing <", pos));
+                }
+                let name: String = chars[pos + 1..].iter().take_while(|c| **c != '>').collect();
+                if pos + 1 + name.len() >= chars.len() {
+                    return Err(error("missing >, unterminated name", pos + 1));
+                }
+                pos += name.len() + 2;
+                if !name.is_empty() && name.chars().all(|c| c.is_ascii_digit()) {
+                    group(name.parse().unwrap_or(usize::MAX), start + 3)?
+                } else if let Some(index) = compiled.names.iter().position(|group| group.as_deref() == Some(&*name)) {
+                    Piece::Group(index)
+                } else if name.chars().next().is_some_and(|c| c.is_alphabetic() || c == '_') {
+                    let message = format!("unknown group name '{}'", name);
+                    return Err(Exception::new("IndexError", &message));
+                } else {
+                    return Err(error(&format!("bad character in group name '{}'", name), start + 3));
+                }
+            }
+            '0'..='9' => {
+                let octal = chars[pos..].iter().take(2).take_while(|c| ('0'..='7').contains(*c)).count();
+                if c == '0' || (c <= '7' && octal == 2) {
+                    let digits = if c == '0' { octal } else { 2 };
+                    let escape: String = chars[pos - 1..pos + digits].iter().collect();
+                    pos += digits;
+                    let code = u32::from_str_radix(&escape, 8).unwrap();
+                    if code > 0o377 {
+                        let message = format!("octal escape value \\{} outside of range 0-0o377", escape);
+                        return Err(error(&message, start));
+                    }
+                    literal.push(char::from_u32(code).unwrap());
+                    continue;
+                }
+                let mut index = c.to_digit(10).unwrap() as usize;
+                if let Some(digit) = chars.get(pos).and_then(|c| c.to_digit(10)) {
+                    index = index * 10 + digit as usize;
+                    pos += 1;
+                }
+                group(index, start + 1)?
+            }
+            c => {
+                match c {
+                    'a' => literal.push('\x07'),
+                    'b' => literal.push('\x08'),
+                    'f' => literal.push('\x0c'),
+                    'n' => literal.push('\n'),
+                    'r' => literal.push('\r'),
+                    't' => literal.push('\t'),
+                    'v' => literal.push('\x0b'),
+                    '\\' => literal.push('\\'),
+                    c if c.is_ascii_alphabetic() => return Err(error(&format!("bad escape \\{}", c), start)),
+                    // other escapes are left alone
+                    c => {
+                        literal.push('\\');
+                        literal.push(c);
+                    }
+                };
+                continue;
+            }
+        };
+        if !literal.is_empty() {
+            pieces.push(Piece::Literal(std::mem::take(&mut literal)));
+        }
+        pieces.push(piece);
+    }
+    if !literal.is_empty() {
+        pieces.push(Piece::Literal(literal));
+    }
+    return Ok(pieces);
+}
+
+/* template filled in from a match; groups that didn't match are empty
+ */
+fn expand(template: &[Piece], text: &str, spans: &Spans, out: &mut String) {
+    for piece in template {
+        match piece {
+            Piece::Literal(literal) => out.push_str(literal),
+            Piece::Group(index) => {
+                if let Some((start, end)) = spans[*index] {
+                    out.push_str(&text[start..end]);
+                }
+            }
+        };
+    }
+}
+
+fn pattern_class(matched: &Rc<Class>) -> NativeClass<Rc<Compiled>> {
+    let mut pattern: NativeClass<Rc<Compiled>> = NativeClass::build_new("Pattern");
+    for (name, params, required, method) in METHODS {
+        let (name, params, required, method) = (*name, *params, *required, *method);
+        let matched = matched.clone();
+        pattern.raw_method(name, move |interpreter, mut args| {
+            let compiled = instance::<Rc<Compiled>>("Pattern", name, &args)?.clone();
+            let this = args.positional.remove(0);
+            let call = Call {
+                pattern: this,
+                compiled,
+                matched: &matched,
+                args: bind(name, args, params, required)?,
+            };
+            return method(interpreter, &call);
+        });
+    }
+    pattern.getter("pattern", |this: &Rc<Compiled>| this.pattern.clone());
+    pattern.getter("flags", |this: &Rc<Compiled>| this.flags);
+    pattern.getter("groups", |this: &Rc<Compiled>| this.groups() as i64);
+    pattern.getter("groupindex", |this: &Rc<Compiled>| {
+        let mut groups = Dict::build_new();
+        for (index, name) in this.names.iter().enumerate() {
+            if let Some(name) = name {
+                groups.set(Value::Str(name.clone()), Value::Int(index as i64)).unwrap();
+            }
+        }
+        return new_dict(groups);
+    });
+    return pattern;
+}
+
+/* A successful match
+ */
+struct Match {
+    text: Rc<str>,
+    spans: Spans,
+    pattern: Value,
+    compiled: Rc<Compiled>,
+    // where the search was limited to, in characters
+    pos: usize,
+    endpos: usize,
+}
+
+impl Match {
+    /* the number of the group given by number or name
+     */
+    fn index(&self, group: &Value) -> Result<usize, Exception> {
+        let index = match group {
+            Value::Int(index) => usize::try_from(*index).ok(),
+            Value::Str(name) => self.compiled.names.iter().position(|group| group.as_deref() == Some(name.as_str())),
+            _ => None,
+        };
+        return index.filter(|index| *index < self.spans.len()).ok_or_else(|| Exception::new("IndexError", "no such group"));
+    }
+
+    fn group(&self, index: usize, default: &Value) -> Value {
+        return self.spans[index].map_or(default.clone(), |(start, end)| Value::Str(self.text[start..end].to_string()));
+    }
+
+    /* the span of a group in characters, (-1, -1) if it didn't match
+     */
+    fn span(&self, index: usize) -> (i64, i64) {
+        let chars = |offset: usize| self.text[..offset].chars().count() as i64;
+        return self.spans[index].map_or((-1, -1), |(start, end)| (chars(start), chars(end)));
+    }
+
+    /* The group that closed last: the one ending last, and of those the
+     * outermost
+     */
+    fn lastindex(&self) -> Option<usize> {
+        let groups = self.spans.iter().enumerate().skip(1);
+        let matched = groups.filter_map(|(index, span)| span.map(|(start, end)| (end, std::cmp::Reverse(start), std::cmp::Reverse(index))));
+        return matched.max().map(|(_, _, std::cmp::Reverse(index))| index);
+    }
+}
+
+fn match_class() -> NativeClass<Match> {
+    let mut matched: NativeClass<Match> = NativeClass::build_new("Match");
+    matched.raw_method("group", |_, args| {
+        let this = instance::<Match>("Match", "group", &args)?;
+        match &args.positional[1..] {
+            [] => return Ok(this.group(0, &Value::NoneT)),
+            [group] => return Ok(this.group(this.index(group)?, &Value::NoneT)),
+            groups => {
+                let groups: Result<Vec<Value>, Exception> =
+                    groups.iter().map(|group| Ok(this.group(this.index(group)?, &Value::NoneT))).collect();
+                return Ok(new_tuple(groups?));
+            }
+        }
+    });
+    matched.raw_method("__getitem__", |_, args| {
+        args.check("__getitem__", 2, 2)?;
+        let this = instance::<Match>("Match", "__getitem__", &args)?;
+        return Ok(this.group(this.index(&args.positional[1])?, &Value::NoneT));
+    });
+    matched.raw_method("groups", |_, mut args| {
+        let default = args.keyword("default");
+        args.check("groups", 1, 2)?;
+        let this = instance::<Match>("Match", "groups", &args)?;
+        let default = args.positional.get(1).cloned().or(default).unwrap_or(Value::NoneT);
+        return Ok(new_tuple((1..this.spans.len()).map(|index| this.group(index, &default)).collect()));
+    });
+    matched.raw_method("groupdict", |_, mut args| {
+        let default = args.keyword("default");
+        args.check("groupdict", 1, 2)?;
+        let this = instance::<Match>("Match", "groupdict", &args)?;
+        let default = args.positional.get(1).cloned().or(default).unwrap_or(Value::NoneT);
+        let mut groups = Dict::build_new();
+        for (index, name) in this.compiled.names.iter().enumerate() {
+            if let Some(name) = name {
+                groups.set(Value::Str(name.clone()), this.group(index, &default))?;
+            }
+        }
+        return Ok(new_dict(groups));
+    });
+    for name in &["start", "end", "span"] {
+        let name = *name;
+        matched.raw_method(name, move |_, args| {
+            args.check(name, 1, 2)?;
+            let this = instance::<Match>("Match", name, &args)?;
+            let index = args.positional.get(1).map_or(Ok(0), |group| this.index(group))?;
+            let (start, end) = this.span(index);
+            match name {
+                "start" => return Ok(Value::Int(start)),
+                "end" => return Ok(Value::Int(end)),
+                _ => return Ok(new_tuple(vec![Value::Int(start), Value::Int(end)])),
+            }
+        });
+    }
+    matched.raw_method("expand", |_, args| {
+        args.check("expand", 2, 2)?;
+        let this = instance::<Match>("Match", "expand", &args)?;
+        let template = match &args.positional[1] {
+            Value::Str(template) => parse_template(template, &this.compiled)?,
+            other => {
+                let message = format!("expected str instance, {} found", other.type_name());
+                return Err(Exception::new("TypeError", &message));
+            }
+        };
+        let mut out = String::new();
+        expand(&template, &this.text, &this.spans, &mut out);
+        return Ok(Value::Str(out));
+    });
+    matched.getter("string", |this: &Match| this.text.to_string());
+    matched.getter("re", |this: &Match| this.pattern.clone());
+    matched.getter("pos", |this: &Match| this.pos as i64);
+    matched.getter("endpos", |this: &Match| this.endpos as i64);
+    matched.getter("lastindex", |this: &Match| this.lastindex().map_or(Value::NoneT, |index| Value::Int(index as i64)));
+    matched.getter("lastgroup", |this: &Match| {
+        let name = this.lastindex().and_then(|index| this.compiled.names[index].clone());
+        return name.map_or(Value::NoneT, Value::Str);
+    });
+    return matched;
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testing::{error, eval, exec, python};
+
+    #[test]
+    fn test_match() {
+        let mut python = python("import re\n");
+        let source = "re.match(r'(?P<key>\\w+)\\s*=\\s*(\\d+)?', 'size = 42').groups()";
+        assert_eq!(eval(&mut python, source), "('size', '42')");
+        exec(&mut python, "m = re.search(r'(?P<word>é\\w)(x)?', 'abc éte')\n");
+        assert_eq!(eval(&mut python, "(m.span(), m.group('word'), m[2], m.lastgroup)"), "((4, 6), 'ét', None, 'word')");
+        assert_eq!(eval(&mut python, "re.fullmatch('a|ab', 'ab').group()"), "'ab'");
+        assert_eq!(eval(&mut python, "re.match('b', 'ab')"), "None");
+        assert_eq!(eval(&mut python, "re.compile('b').match('ab', 1).end()"), "2");
+    }
+
+    #[test]
+    fn test_find_and_replace() {
+        let mut python = python("import re\n");
+        assert_eq!(eval(&mut python, "re.findall('a*', 'baaa')"), "['', 'aaa', '']");
+        assert_eq!(eval(&mut python, "re.findall(r'(\\d)(\\w)?', '1a 2')"), "[('1', 'a'), ('2', '')]");
+        assert_eq!(eval(&mut python, "list(map(lambda m: m.start(), re.finditer('o', 'foo')))"), "[1, 2]");
+        assert_eq!(eval(&mut python, "re.sub('x*', '-', 'abxd')"), "'-a-b--d-'");
+        assert_eq!(eval(&mut python, "re.sub(r'(\\w+)@(?P<host>\\w+)', r'\\g<host>:\\1', 'me@here')"), "'here:me'");
+        assert_eq!(eval(&mut python, "re.subn('[aeiou]', lambda m: m.group() * 2, 'banana', 2)"), "('baanaana', 2)");
+        assert_eq!(eval(&mut python, "re.split(r'(,)\\s*', 'a, b,c', maxsplit=1)"), "['a', ',', 'b,c']");
+    }
+
+    #[test]
+    fn test_flags() {
+        let mut python = python("import re\n");
+        let source = "re.findall('^ \\\\w+   # a word\\n [ ]x', 'ab x\\ncd x', re.MULTILINE + re.VERBOSE)";
+        assert_eq!(eval(&mut python, source), "['ab x', 'cd x']");
+        assert_eq!(eval(&mut python, "re.findall('(?i)a.b', 'A\\nB aXb', re.S)"), "['A\\nB', 'aXb']");
+        assert_eq!(eval(&mut python, "re.findall(r'\\w+', 'é1 x', re.ASCII)"), "['1', 'x']");
+        assert_eq!(eval(&mut python, "re.findall('a{,2}b{', 'aaab{')"), "['aab{']");
+    }
+
+    #[test]
+    fn test_errors() {
+        let mut python = python("import re\n");
+        let message = "re.error: backreferences are not supported at position 3";
+        assert_eq!(error(&mut python, "re.compile(r'(a)\\1')"), message);
+        let message = "re.error: look-behind assertions are not supported at position 0";
+        assert_eq!(error(&mut python, "re.compile('(?<=a)b')"), message);
+        assert_eq!(error(&mut python, "re.compile('(a')"), "re.error: missing ), unterminated subpattern at position 0");
+        assert_eq!(error(&mut python, "re.compile('a**')"), "re.error: multiple repeat at position 2");
+        assert_eq!(error(&mut python, "re.sub('a', r'\\2', 'a')"), "re.error: invalid group reference 2 at position 1");
+    }
+}
@@ -0,0 +1,241 @@
+use crate::dict::{new_dict, Dict};
+use crate::interpreter::{not_an_int, EvalResult, Interpreter};
+use crate::modules::io;
+use crate::native::NativeClass;
+use crate::value::{new_list, new_tuple, Args, Builtin, BuiltinFn, Class, Exception, Module, Object, Value};
+use std::collections::HashMap;
+use std::env;
+use std::rc::Rc;
+
+/* sys - interpreter state visible to scripts
+ *
+ * path: module search path, filled in by Interpreter::init_path. Scripts may
+ * modify it; the importer reads it on every import.
+ * argv: the script and its arguments, set by the rebar binary (or the
+ * host); [''] otherwise.
+ * modules: the modules imported so far, kept up to date by the importer.
+ * stdin, stdout, stderr: file objects of the io module given. print() and
+ * input() use whatever these are set to; __stdin__ and the others keep the
+ * originals.
+ * flags: the command line options in effect (-O and -B).
+ * exit([status]) raises SystemExit, which ends the script; the rebar
+ * binary exits with the status.
+ * The recursion limit is the interpreter's Limits::max_recursion.
+ */
+pub fn build_module(io: &Module, object: Rc<Class>) -> Rc<Module> {
+    let module = Module::build_new("sys", None);
+    module.set("path", new_list(Vec::new()));
+    module.set("argv", new_list(vec![Value::Str(String::new())]));
+    for (name, stream) in io::standard_streams(io) {
+        module.set(&format!("__{}__", name), stream.clone());
+        module.set(name, stream);
+    }
+    let version = format!("3.11.0 (rebar {})", env!("CARGO_PKG_VERSION"));
+    module.set("version", Value::Str(version));
+    let release = Value::Str(String::from("final"));
+    module.set("version_info", new_tuple(vec![Value::Int(3), Value::Int(11), Value::Int(0), release, Value::Int(0)]));
+    module.set("platform", Value::Str(String::from(platform())));
+    module.set("maxsize", Value::Int(i64::MAX));
+    let flags = Flags {
+        optimize: 0,
+        write_cache: true,
+    };
+    module.set("flags", Value::Object(Object::build_new(flags_class().build(object), Some(Box::new(flags)))));
+    let functions: &[(&str, BuiltinFn)] = &[
+        ("exit", exit),
+        ("getrecursionlimit", getrecursionlimit),
+        ("setrecursionlimit", setrecursionlimit),
+        ("exc_info", exc_info),
+    ];
+    for (name, func) in functions {
+        module.set(name, Builtin::build_new(name, *func));
+    }
+    return Rc::new(module);
+}
+
+/* sys.platform's name for the operating system
+ */
+fn platform() -> &'static str {
+    match env::consts::OS {
+        "macos" => return "darwin",
+        "windows" => return "win32",
+        os => return os,
+    }
+}
+
+/* sys.modules, for the modules loaded when the interpreter is made
+ */
+pub fn set_modules(sys: &Module, modules: &HashMap<String, Rc<Module>>) {
+    let mut names: Vec<&String> = modules.keys().collect();
+    names.sort();
+    let mut dict = Dict::build_new();
+    for name in names {
+        dict.set(Value::Str(name.clone()), Value::Module(modules[name].clone())).unwrap();
+    }
+    sys.set("modules", new_dict(dict));
+}
+
+pub fn set_argv(sys: &Module, argv: &[String]) {
+    sys.set("argv", new_list(argv.iter().map(|arg| Value::Str(arg.clone())).collect()));
+}
+
+/* sys.flags: the options the interpreter runs with. The ones rebar doesn't
+ * have are always 0.
+ */
+struct Flags {
+    optimize: u8,
+    write_cache: bool,
+}
+
+const UNSUPPORTED_FLAGS: &[&str] = &[
+    "debug",
+    "inspect",
+    "interactive",
+    "no_user_site",
+    "no_site",
+    "ignore_environment",
+    "verbose",
+    "bytes_warning",
+    "quiet",
+    "isolated",
+];
+
+fn flags_class() -> NativeClass<Flags> {
+    let mut flags: NativeClass<Flags> = NativeClass::build_new("flags");
+    flags.getter("optimize", |this: &Flags| this.optimize as i64);
+    flags.getter("dont_write_bytecode", |this: &Flags| !this.write_cache as i64);
+    for name in UNSUPPORTED_FLAGS {
+        flags.attr(name, 0);
+    }
+    return flags;
+}
+
+/* replace sys.flags after the interpreter's options change
+ */
+pub fn set_flags(sys: &Module, optimize: u8, write_cache: bool) {
+    if let Some(Value::Object(ref flags)) = sys.get("flags") {
+        let native = Flags { optimize, write_cache };
+        sys.set("flags", Value::Object(Object::build_new(flags.class.clone(), Some(Box::new(native)))));
+    }
+}
+
+// exit([status]): the SystemExit's message is str(status)
+fn exit(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("exit", 0, 1)?;
+    let message = match args.positional.first() {
+        None | Some(Value::NoneT) => String::new(),
+        Some(status) => status.to_string(),
+    };
+    return Err(Exception::new("SystemExit", &message));
+}
+
+fn getrecursionlimit(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("getrecursionlimit", 0, 0)?;
+    return Ok(Value::Int(interpreter.limits.max_recursion as i64));
+}
+
+/* setrecursionlimit(n). Sandboxed code may lower the limit the host set,
+ * but not raise it.
+ */
+fn setrecursionlimit(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("setrecursionlimit", 1, 1)?;
+    let limit = match &args.positional[0] {
+        Value::Int(limit) => *limit,
+        value => return Err(not_an_int(value)),
+    };
+    if limit < 1 {
+        return Err(Exception::new("ValueError", "recursion limit must be greater or equal than 1"));
+    }
+    if limit as usize > interpreter.limits.max_recursion {
+        interpreter.check_access("PermissionError", "raising the recursion limit")?;
+    }
+    interpreter.limits.max_recursion = limit as usize;
+    return Ok(Value::NoneT);
+}
+
+/* The exception being handled, by the innermost except clause or a finally
+ * block it is passing through, as (type, value, traceback), or
+ * (None, None, None). Tracebacks aren't objects, so that is None.
+ */
+fn exc_info(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("exc_info", 0, 0)?;
+    let mut err = match interpreter.handling.last() {
+        Some(err) => err.clone(),
+        None => return Ok(new_tuple(vec![Value::NoneT, Value::NoneT, Value::NoneT])),
+    };
+    let value = interpreter.exception_value(&mut err);
+    let class = Value::Class(interpreter.type_of(&value));
+    return Ok(new_tuple(vec![class, value, Value::NoneT]));
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testing::{eval, exec, python};
+
+    #[test]
+    fn test_attributes() {
+        let mut python = python("import sys\n");
+        assert_eq!(eval(&mut python, "sys.argv"), "['']");
+        python.set_argv(&[String::from("script.py"), String::from("-v")]);
+        assert_eq!(eval(&mut python, "sys.argv"), "['script.py', '-v']");
+        assert_eq!(eval(&mut python, "(sys.maxsize, sys.version_info >= (3, 8))"), "(9223372036854775807, True)");
+        assert_eq!(eval(&mut python, "(sys.modules['sys'] is sys, 'os.path' in sys.modules)"), "(True, True)");
+        exec(&mut python, "import json\n");
+        assert_eq!(eval(&mut python, "sys.modules['json'] is json"), "True");
+    }
+
+    #[test]
+    fn test_exc_info() {
+        let mut python = python("import sys\n");
+        assert_eq!(eval(&mut python, "sys.exc_info()"), "(None, None, None)");
+        let source = "
+seen = []
+try:
+    try:
+        raise KeyError('outer')
+    except KeyError as err:
+        seen.append(sys.exc_info()[:2] == (KeyError, err))
+        try:
+            1 / 0
+        except ZeroDivisionError:
+            seen.append(sys.exc_info()[0])
+        seen.append(sys.exc_info()[1].args)
+        raise ValueError('inner')
+    finally:
+        seen.append(sys.exc_info()[0])
+except ValueError:
+    pass
+seen.append(sys.exc_info())
+";
+        exec(&mut python, source);
+        let seen = "[True, <class 'ZeroDivisionError'>, ('outer',), <class 'ValueError'>, (None, None, None)]";
+        assert_eq!(eval(&mut python, "seen"), seen);
+    }
+
+    #[test]
+    fn test_flags() {
+        let mut python = python("import sys\n");
+        assert_eq!(eval(&mut python, "(sys.flags.optimize, sys.flags.dont_write_bytecode, sys.flags.verbose)"), "(0, 0, 0)");
+        python.set_optimize(1);
+        python.set_write_cache(false);
+        assert_eq!(eval(&mut python, "(sys.flags.optimize, sys.flags.dont_write_bytecode)"), "(1, 1)");
+    }
+
+    #[test]
+    fn test_recursion_limit() {
+        let mut python = python("import sys\n");
+        assert_eq!(eval(&mut python, "sys.getrecursionlimit()"), "1000");
+        exec(&mut python, "sys.setrecursionlimit(30)\ndef f(n):\n    return n and f(n - 1)\n");
+        assert_eq!(eval(&mut python, "f(20)"), "0");
+        assert_eq!(eval(&mut python, "f(40)"), "RecursionError: maximum recursion depth exceeded");
+        assert_eq!(eval(&mut python, "sys.setrecursionlimit(0)"), "ValueError: recursion limit must be greater or equal than 1");
+    }
+
+    #[test]
+    fn test_exit() {
+        let mut python = python("import sys\n");
+        assert_eq!(eval(&mut python, "sys.exit()"), "SystemExit");
+        assert_eq!(eval(&mut python, "sys.exit(2)"), "SystemExit: 2");
+        assert_eq!(eval(&mut python, "sys.exit('failed')"), "SystemExit: failed");
+    }
+}
@@ -0,0 +1,562 @@
+use crate::dict::{new_dict, Dict, HashKey};
+use crate::gc;
+use crate::exceptions::key_error;
+use crate::interpreter::{contains, get_item, set_item, EvalResult, Interpreter};
+use crate::native::{instance, NativeClass, NativeModule};
+use crate::value::{new_list, new_tuple, Args, Builtin, Class, Exception, Function, Module, NativeFn, Object, Value};
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::rc::{Rc, Weak};
+
+/* weakref - references that don't keep their object alive
+ *
+ * Instances, functions, classes and modules can be referred to weakly.
+ * Callbacks, and the calls of finalize objects, happen at the statement
+ * after the object is freed, like __del__.
+ */
+pub fn build_module(object: Rc<Class>) -> Rc<Module> {
+    let mut module = NativeModule::build_new("weakref");
+
+    let mut reference: NativeClass<Reference> = NativeClass::build_new("ref");
+    reference.raw_new(|_, class, args| new_reference("ref", class, args));
+    reference.raw_method("__call__", |_, args| {
+        args.check("ref", 1, 1)?;
+        let this = instance::<Reference>("ref", "__call__", &args)?;
+        return Ok(this.target.get().unwrap_or(Value::NoneT));
+    });
+    reference.getter("__callback__", |this: &Reference| this.callback.clone());
+    module.class(reference);
+    module.class(proxy_class());
+
+    let mut value_dict: NativeClass<Table> = NativeClass::build_new("WeakValueDictionary");
+    value_dict.raw_new(|interpreter, class, args| new_table(interpreter, class, args, Kind::ValueDict));
+    dict_methods(&mut value_dict, "WeakValueDictionary");
+    module.class(value_dict);
+
+    let mut key_dict: NativeClass<Table> = NativeClass::build_new("WeakKeyDictionary");
+    key_dict.raw_new(|interpreter, class, args| new_table(interpreter, class, args, Kind::KeyDict));
+    dict_methods(&mut key_dict, "WeakKeyDictionary");
+    module.class(key_dict);
+
+    let mut set: NativeClass<Table> = NativeClass::build_new("WeakSet");
+    set.raw_new(|interpreter, class, args| new_table(interpreter, class, args, Kind::Set));
+    set_methods(&mut set);
+    module.class(set);
+
+    module.class(finalize_class());
+    return module.build(object);
+}
+
+/* What a weak reference refers to
+ */
+enum Target {
+    Object(Weak<Object>),
+    Function(Weak<Function>),
+    Class(Weak<Class>),
+    Module(Weak<Module>),
+}
+
+impl Target {
+    fn build_new(value: &Value) -> Result<Target, Exception> {
+        match value {
+            Value::Object(object) => return Ok(Target::Object(Rc::downgrade(object))),
+            Value::Function(function) => return Ok(Target::Function(Rc::downgrade(function))),
+            Value::Class(class) => return Ok(Target::Class(Rc::downgrade(class))),
+            Value::Module(module) => return Ok(Target::Module(Rc::downgrade(module))),
+            _ => {
+                let message = format!("cannot create weak reference to '{}' object", value.type_name());
+                return Err(Exception::new("TypeError", &message));
+            }
+        }
+    }
+
+    /* the object, or None once it has been freed
+     */
+    fn get(&self) -> Option<Value> {
+        match self {
+            Target::Object(object) => return object.upgrade().map(Value::Object),
+            Target::Function(function) => return function.upgrade().map(Value::Function),
+            Target::Class(class) => return class.upgrade().map(Value::Class),
+            Target::Module(module) => return module.upgrade().map(Value::Module),
+        }
+    }
+}
+
+/* A ref or proxy, and what to call with it when its object is freed
+ */
+struct Reference {
+    target: Target,
+    callback: Option<Value>,
+}
+
+/* ref(object[, callback]) and proxy(object[, callback])
+ */
+fn new_reference(name: &str, class: Rc<Class>, args: Args) -> EvalResult {
+    args.check(name, 1, 2)?;
+    let target = Target::build_new(&args.positional[0])?;
+    let callback = args.positional.get(1).filter(|callback| **callback != Value::NoneT).cloned();
+    let reference = Object::build_new(class, Some(Box::new(Reference { target, callback: callback.clone() })));
+    if let Some(callback) = callback {
+        // the callback isn't called if the reference has gone first
+        let reference = Rc::downgrade(&reference);
+        gc::watch(
+            args.positional[0].identity(),
+            Box::new(move || Some((callback, vec![Value::Object(reference.upgrade()?)]))),
+        );
+    }
+    return Ok(Value::Object(reference));
+}
+
+/* The object a proxy stands for, as long as it is alive
+ */
+fn referent(name: &str, args: &Args) -> EvalResult {
+    let target = instance::<Reference>("proxy", name, args)?.target.get();
+    return target.ok_or_else(|| Exception::new("ReferenceError", "weakly-referenced object no longer exists"));
+}
+
+/* proxy(object[, callback]): uses of the proxy are passed on to the object
+ */
+fn proxy_class() -> NativeClass<Reference> {
+    let mut proxy: NativeClass<Reference> = NativeClass::build_new("proxy");
+    proxy.raw_new(|_, class, args| new_reference("proxy", class, args));
+    proxy.raw_method("__getattr__", |interpreter, args| {
+        args.check("__getattr__", 2, 2)?;
+        let target = referent("__getattr__", &args)?;
+        match &args.positional[1] {
+            Value::Str(attr) => return interpreter.get_attr(&target, attr),
+            _ => return Err(Exception::new("TypeError", "attribute name must be string")),
+        }
+    });
+    proxy.raw_method("__setattr__", |interpreter, args| {
+        args.check("__setattr__", 3, 3)?;
+        let target = referent("__setattr__", &args)?;
+        match &args.positional[1] {
+            Value::Str(attr) => interpreter.set_attr(&target, attr, args.positional[2].clone())?,
+            _ => return Err(Exception::new("TypeError", "attribute name must be string")),
+        };
+        return Ok(Value::NoneT);
+    });
+    proxy.raw_method("__call__", |interpreter, mut args| {
+        let target = referent("__call__", &args)?;
+        args.positional.remove(0);
+        return interpreter.call(&target, args);
+    });
+    proxy.raw_method("__len__", |interpreter, args| {
+        args.check("__len__", 1, 1)?;
+        let target = referent("__len__", &args)?;
+        let len = interpreter.builtins.get("len").unwrap();
+        return interpreter.call(&len, Args::build_new(vec![target]));
+    });
+    proxy.raw_method("__getitem__", |interpreter, args| {
+        args.check("__getitem__", 2, 2)?;
+        let (target, index) = (referent("__getitem__", &args)?, &args.positional[1]);
+        match interpreter.call_special(&target, "__getitem__", vec![index.clone()]) {
+            Some(result) => return result,
+            None => return get_item(&target, index),
+        }
+    });
+    proxy.raw_method("__setitem__", |interpreter, args| {
+        args.check("__setitem__", 3, 3)?;
+        let target = referent("__setitem__", &args)?;
+        let (index, value) = (args.positional[1].clone(), args.positional[2].clone());
+        match interpreter.call_special(&target, "__setitem__", vec![index.clone(), value.clone()]) {
+            Some(result) => {
+                result?;
+            }
+            None => set_item(&target, &index, value)?,
+        };
+        return Ok(Value::NoneT);
+    });
+    proxy.raw_method("__contains__", |interpreter, args| {
+        args.check("__contains__", 2, 2)?;
+        let (target, item) = (referent("__contains__", &args)?, &args.positional[1]);
+        match interpreter.call_special(&target, "__contains__", vec![item.clone()]) {
+            Some(result) => return result,
+            None => return Ok(Value::Bool(contains(&target, item)?)),
+        }
+    });
+    proxy.raw_method("__iter__", |interpreter, args| {
+        args.check("__iter__", 1, 1)?;
+        let target = referent("__iter__", &args)?;
+        return interpreter.get_iter(&target);
+    });
+    return proxy;
+}
+
+/* A key or value of a weak container
+ */
+enum Slot {
+    Strong(Value),
+    Weak(Target),
+}
+
+impl Slot {
+    fn get(&self) -> Option<Value> {
+        match self {
+            Slot::Strong(value) => return Some(value.clone()),
+            Slot::Weak(target) => return target.get(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Kind {
+    ValueDict,
+    KeyDict,
+    Set,
+}
+
+/* The insertion-ordered entries of a WeakValueDictionary,
+ * WeakKeyDictionary or WeakSet (whose values are all None). An entry goes
+ * when an object it refers to weakly is freed. Weakly held keys are hashed
+ * by identity, which can't be reused while the entry is there.
+ */
+struct Table {
+    kind: Kind,
+    index: HashMap<HashKey, usize>,
+    entries: Vec<Option<(Slot, Slot)>>,
+    // set when an object entries refer to has been freed, so they are
+    // purged before the table is next used
+    stale: Rc<Cell<bool>>,
+}
+
+impl Table {
+    fn build_new(kind: Kind) -> Table {
+        return Table {
+            kind,
+            index: HashMap::new(),
+            entries: Vec::new(),
+            stale: Rc::new(Cell::new(false)),
+        };
+    }
+
+    fn slot(&self, value: &Value, weak: bool) -> Result<Slot, Exception> {
+        if !weak {
+            return Ok(Slot::Strong(value.clone()));
+        }
+        let target = Target::build_new(value)?;
+        let stale = Rc::downgrade(&self.stale);
+        gc::watch(
+            value.identity(),
+            Box::new(move || {
+                if let Some(stale) = stale.upgrade() {
+                    stale.set(true);
+                }
+                return None;
+            }),
+        );
+        return Ok(Slot::Weak(target));
+    }
+
+    fn purge(&mut self) {
+        if !self.stale.replace(false) {
+            return;
+        }
+        let entries = std::mem::take(&mut self.entries);
+        self.index.clear();
+        for (key, value) in entries.into_iter().flatten() {
+            if let (Some(live_key), Some(_)) = (key.get(), value.get()) {
+                self.index.insert(HashKey::from_value(&live_key).unwrap(), self.entries.len());
+                self.entries.push(Some((key, value)));
+            }
+        }
+    }
+
+    fn len(&mut self) -> usize {
+        self.purge();
+        return self.index.len();
+    }
+
+    fn get(&mut self, key: &Value) -> Result<Option<Value>, Exception> {
+        self.purge();
+        let key = HashKey::from_value(key)?;
+        let entry = self.index.get(&key).and_then(|pos| self.entries[*pos].as_ref());
+        return Ok(entry.and_then(|(_, value)| value.get()));
+    }
+
+    fn set(&mut self, key: &Value, value: &Value) -> Result<(), Exception> {
+        self.purge();
+        let hash_key = HashKey::from_value(key)?;
+        if let Some(pos) = self.index.get(&hash_key) {
+            // keep the original key, like a dict
+            let (_, old) = self.entries[*pos].as_ref().unwrap();
+            let same = self.kind == Kind::ValueDict && old.get().is_some_and(|old| old.identity() == value.identity());
+            if !same {
+                let value = self.slot(value, self.kind == Kind::ValueDict)?;
+                self.entries[*pos].as_mut().unwrap().1 = value;
+            }
+            return Ok(());
+        }
+        let weak_values = self.kind == Kind::ValueDict;
+        let entry = (self.slot(key, !weak_values)?, self.slot(value, weak_values)?);
+        self.index.insert(hash_key, self.entries.len());
+        self.entries.push(Some(entry));
+        return Ok(());
+    }
+
+    fn remove(&mut self, key: &Value) -> Result<Option<Value>, Exception> {
+        self.purge();
+        let key = HashKey::from_value(key)?;
+        let entry = self.index.remove(&key).and_then(|pos| self.entries[pos].take());
+        return Ok(entry.and_then(|(_, value)| value.get()));
+    }
+
+    fn items(&mut self) -> Vec<(Value, Value)> {
+        self.purge();
+        let entries = self.entries.iter().flatten();
+        return entries.filter_map(|(key, value)| Some((key.get()?, value.get()?))).collect();
+    }
+}
+
+/* WeakValueDictionary([dict]), WeakKeyDictionary([dict]) and
+ * WeakSet([iterable])
+ */
+fn new_table(interpreter: &mut Interpreter, class: Rc<Class>, args: Args, kind: Kind) -> EvalResult {
+    args.check(&class.name, 0, 1)?;
+    let mut table = Table::build_new(kind);
+    if let Some(initial) = args.positional.first() {
+        match initial {
+            Value::Dict(dict) if kind != Kind::Set => {
+                for (key, value) in dict.borrow().entries() {
+                    table.set(key, value)?;
+                }
+            }
+            _ => {
+                for item in interpreter.iterate(initial)? {
+                    match kind {
+                        Kind::Set => table.set(&item, &Value::NoneT)?,
+                        _ => {
+                            let pair = interpreter.iterate(&item)?;
+                            if pair.len() != 2 {
+                                let message = "dictionary update sequence element has wrong length";
+                                return Err(Exception::new("ValueError", message));
+                            }
+                            table.set(&pair[0], &pair[1])?;
+                        }
+                    };
+                }
+            }
+        };
+    }
+    return Ok(Value::Object(Object::build_new(class, Some(Box::new(table)))));
+}
+
+/* __len__, __contains__ and __iter__ (over the keys), shared by all three
+ */
+fn table_methods(class: &mut NativeClass<Table>, name: &'static str) {
+    class.raw_method("__len__", move |_, args| {
+        args.check("__len__", 1, 1)?;
+        return Ok(Value::Int(instance::<Table>(name, "__len__", &args)?.len() as i64));
+    });
+    class.raw_method("__contains__", move |_, args| {
+        args.check("__contains__", 2, 2)?;
+        let mut table = instance::<Table>(name, "__contains__", &args)?;
+        // like a dict, only hashable values can be in it
+        return Ok(Value::Bool(table.get(&args.positional[1])?.is_some()));
+    });
+    class.raw_method("__iter__", move |_, args| {
+        args.check("__iter__", 1, 1)?;
+        let items = instance::<Table>(name, "__iter__", &args)?.items();
+        return Ok(new_list(items.into_iter().map(|(key, _)| key).collect()));
+    });
+}
+
+fn dict_methods(class: &mut NativeClass<Table>, name: &'static str) {
+    table_methods(class, name);
+    class.raw_method("__getitem__", move |_, args| {
+        args.check("__getitem__", 2, 2)?;
+        let value = instance::<Table>(name, "__getitem__", &args)?.get(&args.positional[1])?;
+        return value.ok_or_else(|| key_error(&args.positional[1]));
+    });
+    class.raw_method("__setitem__", move |_, args| {
+        args.check("__setitem__", 3, 3)?;
+        instance::<Table>(name, "__setitem__", &args)?.set(&args.positional[1], &args.positional[2])?;
+        return Ok(Value::NoneT);
+    });
+    class.raw_method("get", move |_, args| {
+        args.check("get", 2, 3)?;
+        let value = instance::<Table>(name, "get", &args)?.get(&args.positional[1])?;
+        return Ok(value.or_else(|| args.positional.get(2).cloned()).unwrap_or(Value::NoneT));
+    });
+    class.raw_method("pop", move |_, args| {
+        args.check("pop", 2, 3)?;
+        let value = instance::<Table>(name, "pop", &args)?.remove(&args.positional[1])?;
+        return value.or_else(|| args.positional.get(2).cloned()).ok_or_else(|| key_error(&args.positional[1]));
+    });
+    class.raw_method("keys", move |_, args| {
+        args.check("keys", 1, 1)?;
+        let items = instance::<Table>(name, "keys", &args)?.items();
+        return Ok(new_list(items.into_iter().map(|(key, _)| key).collect()));
+    });
+    class.raw_method("values", move |_, args| {
+        args.check("values", 1, 1)?;
+        let items = instance::<Table>(name, "values", &args)?.items();
+        return Ok(new_list(items.into_iter().map(|(_, value)| value).collect()));
+    });
+    class.raw_method("items", move |_, args| {
+        args.check("items", 1, 1)?;
+        let items = instance::<Table>(name, "items", &args)?.items();
+        return Ok(new_list(items.into_iter().map(|(key, value)| new_tuple(vec![key, value])).collect()));
+    });
+}
+
+fn set_methods(class: &mut NativeClass<Table>) {
+    table_methods(class, "WeakSet");
+    class.raw_method("add", |_, args| {
+        args.check("add", 2, 2)?;
+        instance::<Table>("WeakSet", "add", &args)?.set(&args.positional[1], &Value::NoneT)?;
+        return Ok(Value::NoneT);
+    });
+    class.raw_method("discard", |_, args| {
+        args.check("discard", 2, 2)?;
+        instance::<Table>("WeakSet", "discard", &args)?.remove(&args.positional[1])?;
+        return Ok(Value::NoneT);
+    });
+    class.raw_method("remove", |_, args| {
+        args.check("remove", 2, 2)?;
+        let mut table = instance::<Table>("WeakSet", "remove", &args)?;
+        if table.get(&args.positional[1])?.is_none() {
+            return Err(key_error(&args.positional[1]));
+        }
+        table.remove(&args.positional[1])?;
+        return Ok(Value::NoneT);
+    });
+}
+
+/* finalize(object, func, *args, **kwargs): calls func(*args, **kwargs)
+ * once, when object is freed or the finalize object is called, whichever
+ * is first
+ */
+struct Finalizer {
+    target: Target,
+    // the call still to make; taken when it is made or detached
+    pending: Rc<RefCell<Option<(Value, Args)>>>,
+}
+
+fn finalize_class() -> NativeClass<Finalizer> {
+    let mut finalize: NativeClass<Finalizer> = NativeClass::build_new("finalize");
+    finalize.raw_new(|_, class, mut args| {
+        if args.positional.len() < 2 {
+            let message = format!("finalize expected at least 2 arguments, got {}", args.positional.len());
+            return Err(Exception::new("TypeError", &message));
+        }
+        let object = args.positional.remove(0);
+        let func = args.positional.remove(0);
+        let target = Target::build_new(&object)?;
+        let pending = Rc::new(RefCell::new(Some((func, args))));
+
+        // the object being freed makes the call, even if this finalize
+        // object has gone by then
+        let waiting = pending.clone();
+        gc::watch(
+            object.identity(),
+            Box::new(move || {
+                let call = RefCell::new(waiting.borrow_mut().take()?);
+                let func: NativeFn = Rc::new(move |interpreter: &mut Interpreter, _| {
+                    let (func, args) = call.replace((Value::NoneT, Args::default()));
+                    return interpreter.call(&func, args);
+                });
+                return Some((Builtin::build_native("finalize", func), Vec::new()));
+            }),
+        );
+        let finalizer = Finalizer { target, pending };
+        return Ok(Value::Object(Object::build_new(class, Some(Box::new(finalizer)))));
+    });
+    finalize.raw_method("__call__", |interpreter, args| {
+        args.check("finalize", 1, 1)?;
+        let call = instance::<Finalizer>("finalize", "__call__", &args)?.pending.borrow_mut().take();
+        match call {
+            Some((func, args)) => return interpreter.call(&func, args),
+            None => return Ok(Value::NoneT),
+        }
+    });
+    finalize.raw_method("detach", |_, args| {
+        args.check("detach", 1, 1)?;
+        let this = instance::<Finalizer>("finalize", "detach", &args)?;
+        let info = finalizer_info(&this);
+        if info != Value::NoneT {
+            this.pending.borrow_mut().take();
+        }
+        return Ok(info);
+    });
+    finalize.raw_method("peek", |_, args| {
+        args.check("peek", 1, 1)?;
+        let this = instance::<Finalizer>("finalize", "peek", &args)?;
+        return Ok(finalizer_info(&this));
+    });
+    finalize.getter("alive", |this: &Finalizer| this.pending.borrow().is_some());
+    return finalize;
+}
+
+/* (object, func, args, kwargs) while the finalizer is alive, else None
+ */
+fn finalizer_info(this: &Finalizer) -> Value {
+    let (object, pending) = (this.target.get(), this.pending.borrow());
+    match (object, pending.as_ref()) {
+        (Some(object), Some((func, args))) => {
+            let mut kwargs = Dict::build_new();
+            for (name, value) in &args.keywords {
+                kwargs.set(Value::Str(name.clone()), value.clone()).unwrap();
+            }
+            let positional = new_tuple(args.positional.clone());
+            return new_tuple(vec![object, func.clone(), positional, new_dict(kwargs)]);
+        }
+        _ => return Value::NoneT,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testing::{error, eval, exec, python};
+
+    #[test]
+    fn test_ref_and_proxy() {
+        let mut python = python("import weakref\nlog = []\n");
+        let source = "
+def f():
+    return 1
+def g():
+    return 2
+def callback(r):
+    log[0:0] = ['callback']
+r = weakref.ref(f, callback)
+p = weakref.proxy(g)
+alive = (r() is f, p())
+f = None
+g = None
+";
+        exec(&mut python, source);
+        assert_eq!(eval(&mut python, "alive"), "(True, 2)");
+        assert_eq!(eval(&mut python, "(r(), log)"), "(None, ['callback'])");
+        assert_eq!(error(&mut python, "p()"), "ReferenceError: weakly-referenced object no longer exists");
+        assert_eq!(error(&mut python, "weakref.ref(1)"), "TypeError: cannot create weak reference to 'int' object");
+    }
+
+    #[test]
+    fn test_weak_collections() {
+        let mut python = python("import weakref\nlog = []\n");
+        let source = "
+def f():
+    return 1
+def g():
+    return 2
+def record(what):
+    log[0:0] = [what]
+values = weakref.WeakValueDictionary()
+values['f'] = f
+values['g'] = g
+keys = weakref.WeakKeyDictionary({f: 'f'})
+fs = weakref.WeakSet([f, g])
+fin = weakref.finalize(f, record, what='finalized')
+before = (len(values), 'f' in values, keys[f], f in fs, fin.alive)
+f = None
+after = (sorted(values.keys()), len(keys), len(fs), fin.alive)
+g = None
+";
+        exec(&mut python, source);
+        assert_eq!(eval(&mut python, "before"), "(2, True, 'f', True, True)");
+        assert_eq!(eval(&mut python, "after"), "(['g'], 0, 1, False)");
+        assert_eq!(eval(&mut python, "log"), "['finalized']");
+        assert_eq!(error(&mut python, "values['g']"), "KeyError: 'g'");
+    }
+}
@@ -0,0 +1,538 @@
+use crate::convert::FromValue;
+use crate::interpreter::Interpreter;
+use crate::dict::{Dict, HashKey};
+use crate::value::{Args, Builtin, Class, Exception, Hooks, Module, NativeFn, Object, Property, Value};
+use std::any::Any;
+use std::cell::{RefCell, RefMut};
+use std::collections::HashMap;
+use std::rc::{Rc, Weak};
+
+/* Native functions, classes and modules: Rust code the host exposes to
+ * scripts.
+ *
+ * Any closure taking up to six FromValue arguments can be registered as a
+ * function; arguments are converted before it is called, and a wrong count
+ * or type raises TypeError. It returns something Into<Value>, or a Result
+ * whose error becomes the raised exception:
+ *
+ *     python.register_function("greet", |name: String| format!("hi {}", name));
+ *
+ * A NativeClass wraps a Rust type: its constructor makes the value each
+ * instance holds, and methods and properties get it as their first
+ * argument. A NativeModule is a module of these that scripts can import.
+ */
+
+/* What a native function returns: a value, or a Result whose error is
+ * raised
+ */
+pub trait IntoResult {
+    fn into_result(self) -> Result<Value, Exception>;
+}
+
+impl<T: Into<Value>> IntoResult for T {
+    fn into_result(self) -> Result<Value, Exception> {
+        return Ok(self.into());
+    }
+}
+
+impl<T: Into<Value>, E: Into<Exception>> IntoResult for Result<T, E> {
+    fn into_result(self) -> Result<Value, Exception> {
+        return self.map(Into::into).map_err(Into::into);
+    }
+}
+
+/* What a native constructor returns: the instance's value, or a Result
+ */
+pub trait IntoInstance<T> {
+    fn into_instance(self) -> Result<T, Exception>;
+}
+
+impl<T> IntoInstance<T> for T {
+    fn into_instance(self) -> Result<T, Exception> {
+        return Ok(self);
+    }
+}
+
+impl<T, E: Into<Exception>> IntoInstance<T> for Result<T, E> {
+    fn into_instance(self) -> Result<T, Exception> {
+        return self.map_err(Into::into);
+    }
+}
+
+/* Closures callable with converted arguments. Params is the tuple of
+ * argument types, which tells the implementations for each arity apart.
+ */
+pub trait NativeFunction<Params>: 'static {
+    fn invoke(&self, name: &str, args: &[Value]) -> Result<Value, Exception>;
+}
+
+pub trait NativeMethod<T, Params>: 'static {
+    fn invoke(&self, name: &str, this: &mut T, args: &[Value]) -> Result<Value, Exception>;
+}
+
+pub trait NativeConstructor<T, Params>: 'static {
+    fn invoke(&self, name: &str, args: &[Value]) -> Result<T, Exception>;
+}
+
+fn check_count(name: &str, args: &[Value], count: usize) -> Result<(), Exception> {
+    if args.len() == count {
+        return Ok(());
+    }
+    let plural = if count == 1 { "" } else { "s" };
+    let message = format!("{}() takes exactly {} argument{} ({} given)", name, count, plural, args.len());
+    return Err(Exception::new("TypeError", &message));
+}
+
+fn extract<A: FromValue>(name: &str, args: &[Value], index: usize) -> Result<A, Exception> {
+    return A::from_value(&args[index]).map_err(|mut err| {
+        err.message = format!("{}() argument {} {}", name, index + 1, err.message);
+        err
+    });
+}
+
+macro_rules! native_arities {
+    ($($count:expr => ($($param:ident $index:tt),*)),*) => {$(
+        impl<F, R, $($param),*> NativeFunction<($($param,)*)> for F
+        where
+            F: Fn($($param),*) -> R + 'static,
+            R: IntoResult,
+            $($param: FromValue,)*
+        {
+            fn invoke(&self, name: &str, args: &[Value]) -> Result<Value, Exception> {
+                check_count(name, args, $count)?;
+                return self($(extract::<$param>(name, args, $index)?),*).into_result();
+            }
+        }
+
+        impl<T, F, R, $($param),*> NativeMethod<T, ($($param,)*)> for F
+        where
+            F: Fn(&mut T, $($param),*) -> R + 'static,
+            R: IntoResult,
+            $($param: FromValue,)*
+        {
+            fn invoke(&self, name: &str, this: &mut T, args: &[Value]) -> Result<Value, Exception> {
+                check_count(name, args, $count)?;
+                return self(this, $(extract::<$param>(name, args, $index)?),*).into_result();
+            }
+        }
+
+        impl<T, F, R, $($param),*> NativeConstructor<T, ($($param,)*)> for F
+        where
+            F: Fn($($param),*) -> R + 'static,
+            R: IntoInstance<T>,
+            $($param: FromValue,)*
+        {
+            fn invoke(&self, name: &str, args: &[Value]) -> Result<T, Exception> {
+                check_count(name, args, $count)?;
+                return self($(extract::<$param>(name, args, $index)?),*).into_instance();
+            }
+        }
+    )*};
+}
+
+native_arities!(
+    0 => (),
+    1 => (A1 0),
+    2 => (A1 0, A2 1),
+    3 => (A1 0, A2 1, A3 2),
+    4 => (A1 0, A2 1, A3 2, A4 3),
+    5 => (A1 0, A2 1, A3 2, A4 3, A5 4),
+    6 => (A1 0, A2 1, A3 2, A4 3, A5 4, A6 5)
+);
+
+fn no_keywords(name: &str, args: &Args) -> Result<(), Exception> {
+    if args.keywords.is_empty() {
+        return Ok(());
+    }
+    let message = format!("{}() takes no keyword arguments", name);
+    return Err(Exception::new("TypeError", &message));
+}
+
+/* name's parameters, by position or keyword; the first required of them
+ * have to be given
+ */
+pub(crate) fn bind(name: &str, mut args: Args, params: &[&str], required: usize) -> Result<Vec<Option<Value>>, Exception> {
+    let keywords: Vec<Option<Value>> = params.iter().map(|param| args.keyword(param)).collect();
+    args.check(name, 0, params.len())?;
+    let mut bound = Vec::new();
+    for (pos, keyword) in keywords.into_iter().enumerate() {
+        let value = match (args.positional.get(pos), keyword) {
+            (Some(_), Some(_)) => {
+                let message = format!("argument for {}() given by name ('{}') and position ({})", name, params[pos], pos + 1);
+                return Err(Exception::new("TypeError", &message));
+            }
+            (Some(value), None) => Some(value.clone()),
+            (None, keyword) => keyword,
+        };
+        if value.is_none() && pos < required {
+            let message = format!("{}() missing required argument '{}' (pos {})", name, params[pos], pos + 1);
+            return Err(Exception::new("TypeError", &message));
+        }
+        bound.push(value);
+    }
+    return Ok(bound);
+}
+
+pub(crate) fn native_function<P, F: NativeFunction<P>>(name: &str, function: F) -> Value {
+    let qualname = String::from(name);
+    let func: NativeFn = Rc::new(move |_: &mut Interpreter, args: Args| {
+        no_keywords(&qualname, &args)?;
+        return function.invoke(&qualname, &args.positional);
+    });
+    return Builtin::build_native(name, func);
+}
+
+/* The Rust value of the native instance args[0], for raw_method()s
+ */
+pub(crate) fn instance<'a, T: 'static>(class: &str, name: &str, args: &'a Args) -> Result<RefMut<'a, T>, Exception> {
+    if let Some(Value::Object(object)) = args.positional.first() {
+        if let Some(native) = &object.native {
+            if let Ok(this) = RefMut::filter_map(native.borrow_mut(), |native| native.downcast_mut::<T>()) {
+                return Ok(this);
+            }
+        }
+    }
+    let given = args.positional.first().map_or("nothing", |value| value.type_name());
+    let message = format!("descriptor '{}' for '{}' objects doesn't apply to a '{}' object", name, class, given);
+    return Err(Exception::new("TypeError", &message));
+}
+
+/* f of the Rust value of value, if it is a native instance of T that isn't
+ * in use
+ */
+pub(crate) fn with_native<T: 'static, R>(value: &Value, f: impl FnOnce(&T) -> R) -> Option<R> {
+    if let Value::Object(object) = value {
+        let native = object.native.as_ref()?.try_borrow().ok()?;
+        return native.downcast_ref::<T>().map(f);
+    }
+    return None;
+}
+
+/* Call f with the Rust value of the native instance args[0]
+ */
+fn with_instance<T: 'static, R>(
+    class: &str,
+    name: &str,
+    args: &[Value],
+    f: impl FnOnce(&mut T, &[Value]) -> Result<R, Exception>,
+) -> Result<R, Exception> {
+    if let Some((Value::Object(object), rest)) = args.split_first() {
+        if let Some(native) = &object.native {
+            if let Some(this) = native.borrow_mut().downcast_mut::<T>() {
+                return f(this, rest);
+            }
+        }
+    }
+    let given = args.first().map_or("nothing", |value| value.type_name());
+    let message = format!("descriptor '{}' for '{}' objects doesn't apply to a '{}' object", name, class, given);
+    return Err(Exception::new("TypeError", &message));
+}
+
+type Construct<T> = Rc<dyn Fn(&[Value]) -> Result<T, Exception>>;
+
+/* makes an instance of the class given, in place of a constructor
+ */
+type New = Rc<dyn Fn(&mut Interpreter, Rc<Class>, Args) -> Result<Value, Exception>>;
+
+/* builds a native class once the object base class is known
+ */
+type ClassBuilder = Box<dyn FnOnce(Rc<Class>) -> Rc<Class>>;
+
+/* A class wrapping the Rust type T
+ */
+pub struct NativeClass<T> {
+    name: String,
+    constructor: Option<Construct<T>>,
+    new: Option<New>,
+    attrs: HashMap<String, Value>,
+    getters: HashMap<String, Value>,
+    setters: HashMap<String, Value>,
+    hooks: Hooks,
+}
+
+impl<T: 'static> NativeClass<T> {
+    pub fn build_new(name: &str) -> NativeClass<T> {
+        return NativeClass {
+            name: String::from(name),
+            constructor: None,
+            new: None,
+            attrs: HashMap::new(),
+            getters: HashMap::new(),
+            setters: HashMap::new(),
+            hooks: Hooks::default(),
+        };
+    }
+
+    /* What calling the class does. Without one, the class can't be
+     * instantiated from scripts.
+     */
+    pub fn constructor<P, F: NativeConstructor<T, P>>(&mut self, constructor: F) {
+        let name = self.name.clone();
+        self.constructor = Some(Rc::new(move |args: &[Value]| constructor.invoke(&name, args)));
+    }
+
+    /* Like Python's __new__, for the native modules of the standard
+     * library: makes the instance itself, with Object::build_new(), from
+     * the arguments as they are, so it can hand the instance out
+     */
+    pub(crate) fn raw_new<F>(&mut self, new: F)
+    where
+        F: Fn(&mut Interpreter, Rc<Class>, Args) -> Result<Value, Exception> + 'static,
+    {
+        self.new = Some(Rc::new(new));
+    }
+
+    /* A method given the interpreter and the arguments as they are, the
+     * instance first. It gets at the instance's value with instance(), and
+     * mustn't hold on to it while calling back into the interpreter.
+     */
+    pub(crate) fn raw_method<F>(&mut self, name: &str, method: F)
+    where
+        F: Fn(&mut Interpreter, Args) -> Result<Value, Exception> + 'static,
+    {
+        self.attrs.insert(String::from(name), Builtin::build_native(name, Rc::new(method)));
+    }
+
+    pub fn method<P, F: NativeMethod<T, P>>(&mut self, name: &str, method: F) {
+        let (class, qualname) = (self.name.clone(), String::from(name));
+        let func: NativeFn = Rc::new(move |_: &mut Interpreter, args: Args| {
+            no_keywords(&qualname, &args)?;
+            return with_instance(&class, &qualname, &args.positional, |this: &mut T, rest| {
+                method.invoke(&qualname, this, rest)
+            });
+        });
+        self.attrs.insert(String::from(name), Builtin::build_native(name, func));
+    }
+
+    /* a read-only attribute computed by getter
+     */
+    pub fn getter<R: IntoResult, F: Fn(&T) -> R + 'static>(&mut self, name: &str, getter: F) {
+        let (class, qualname) = (self.name.clone(), String::from(name));
+        let func: NativeFn = Rc::new(move |_: &mut Interpreter, args: Args| {
+            return with_instance(&class, &qualname, &args.positional, |this: &mut T, _| getter(this).into_result());
+        });
+        self.getters.insert(String::from(name), Builtin::build_native(name, func));
+    }
+
+    /* makes the attribute of getter() assignable
+     */
+    pub fn setter<A: FromValue, R: IntoResult, F: Fn(&mut T, A) -> R + 'static>(&mut self, name: &str, setter: F) {
+        let (class, qualname) = (self.name.clone(), String::from(name));
+        let func: NativeFn = Rc::new(move |_: &mut Interpreter, args: Args| {
+            return with_instance(&class, &qualname, &args.positional, |this: &mut T, rest| {
+                let value = extract::<A>(&qualname, rest, 0)?;
+                return setter(this, value).into_result();
+            });
+        });
+        self.setters.insert(String::from(name), Builtin::build_native(name, func));
+    }
+
+    /* a class attribute, e.g. a constant
+     */
+    pub fn attr<V: Into<Value>>(&mut self, name: &str, value: V) {
+        self.attrs.insert(String::from(name), value.into());
+    }
+
+    /* repr() of the instances, in place of <X object at ...>
+     */
+    pub(crate) fn repr<F: Fn(&T) -> String + 'static>(&mut self, repr: F) {
+        self.hooks.repr = Some(Rc::new(move |native: &dyn Any| native.downcast_ref::<T>().map(&repr)));
+    }
+
+    /* what the repr of an instance that contains itself shows for it,
+     * e.g. "[...]"; "..." if not set
+     */
+    pub(crate) fn recursive_repr(&mut self, placeholder: &'static str) {
+        self.hooks.recursive_repr = Some(placeholder);
+    }
+
+    /* ==, with the instance on either side, in place of comparing by
+     * identity
+     */
+    pub(crate) fn eq<F: Fn(&T, &Value) -> bool + 'static>(&mut self, eq: F) {
+        self.hooks.eq = Some(Rc::new(move |native: &dyn Any, other: &Value| {
+            return native.downcast_ref::<T>().map(|this| eq(this, other));
+        }));
+    }
+
+    /* the key the instances hash to as dict keys, or the error for an
+     * unhashable type; it must agree with eq()
+     */
+    pub(crate) fn hash<F: Fn(&T) -> Result<HashKey, Exception> + 'static>(&mut self, hash: F) {
+        self.hooks.hash = Some(Rc::new(move |native: &dyn Any| native.downcast_ref::<T>().map(&hash)));
+    }
+
+    /* the dict the instances keep their items in, for a class built on
+     * dict
+     */
+    pub(crate) fn dict<F: Fn(&T) -> Rc<RefCell<Dict>> + 'static>(&mut self, dict: F) {
+        self.hooks.dict = Some(Rc::new(move |native: &dyn Any| native.downcast_ref::<T>().map(&dict)));
+    }
+
+    /* bool() of the instances, e.g. false when empty
+     */
+    pub(crate) fn truth<F: Fn(&T) -> bool + 'static>(&mut self, truth: F) {
+        self.hooks.truth = Some(Rc::new(move |native: &dyn Any| native.downcast_ref::<T>().map(&truth)));
+    }
+
+    /* The class, derived from base: object, or dict for a class with a
+     * dict() hook
+     */
+    pub(crate) fn build(self, base: Rc<Class>) -> Rc<Class> {
+        let NativeClass {
+            name,
+            constructor,
+            new,
+            mut attrs,
+            getters,
+            mut setters,
+            hooks,
+        } = self;
+        for (attr, getter) in getters {
+            let setter = setters.remove(&attr);
+            attrs.insert(attr, Value::Property(Rc::new(Property { getter, setter })));
+        }
+        // instances are made by the class, so the constructor refers back
+        // to it, weakly so the class can still be freed
+        return Rc::new_cyclic(|class: &Weak<Class>| {
+            let class = class.clone();
+            let new = new.or_else(|| {
+                constructor.map(|constructor| -> New {
+                    Rc::new(move |_: &mut Interpreter, class: Rc<Class>, args: Args| {
+                        no_keywords(&class.name, &args)?;
+                        let native: Box<dyn Any> = Box::new(constructor(&args.positional)?);
+                        return Ok(Value::Object(Object::build_new(class, Some(native))));
+                    })
+                })
+            });
+            let constructor = new.map(|new| -> NativeFn {
+                Rc::new(move |interpreter: &mut Interpreter, args: Args| {
+                    return new(interpreter, class.upgrade().unwrap(), args);
+                })
+            });
+            return Class {
+                name,
+                mro: std::iter::once(base.clone()).chain(base.mro.iter().cloned()).collect(),
+                bases: vec![base],
+                attrs: RefCell::new(attrs),
+                user_defined: false,
+                constructor,
+                hooks,
+            };
+        });
+    }
+}
+
+/* A module scripts can import by name
+ */
+pub struct NativeModule {
+    pub name: String,
+    vars: HashMap<String, Value>,
+    classes: Vec<ClassBuilder>,
+}
+
+impl NativeModule {
+    pub fn build_new(name: &str) -> NativeModule {
+        return NativeModule {
+            name: String::from(name),
+            vars: HashMap::new(),
+            classes: Vec::new(),
+        };
+    }
+
+    pub fn function<P, F: NativeFunction<P>>(&mut self, name: &str, function: F) {
+        self.vars.insert(String::from(name), native_function(name, function));
+    }
+
+    pub fn class<T: 'static>(&mut self, class: NativeClass<T>) {
+        self.classes.push(Box::new(move |object| class.build(object)));
+    }
+
+    pub fn constant<V: Into<Value>>(&mut self, name: &str, value: V) {
+        self.vars.insert(String::from(name), value.into());
+    }
+
+    pub(crate) fn build(self, object: Rc<Class>) -> Rc<Module> {
+        let module = Module::build_new(&self.name, None);
+        for (name, value) in self.vars {
+            module.set(&name, value);
+        }
+        for class in self.classes {
+            module.set_class(class(object.clone()));
+        }
+        return Rc::new(module);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::embed;
+    use crate::testing::{error, exec};
+
+    struct Counter {
+        count: i64,
+    }
+
+    /* An interpreter with native functions registered, and a module with
+     * a native class
+     */
+    fn setup() -> embed::Interpreter {
+        let mut python = embed::Interpreter::build_new();
+        python.register_function("greet", |name: String, times: i64| name.repeat(times as usize));
+        python.register_function("checked", |val: i64| {
+            if val < 0 {
+                return Err(Exception::new("ValueError", "negative"));
+            }
+            return Ok(val);
+        });
+
+        let mut counter: NativeClass<Counter> = NativeClass::build_new("Counter");
+        counter.constructor(|start: i64| Counter { count: start });
+        counter.method("increment", |this: &mut Counter, by: i64| {
+            this.count += by;
+            return this.count;
+        });
+        counter.getter("count", |this: &Counter| this.count);
+        counter.setter("count", |this: &mut Counter, count: i64| this.count = count);
+        counter.getter("doubled", |this: &Counter| this.count * 2);
+        let mut module = NativeModule::build_new("counters");
+        module.class(counter);
+        module.function("total", |counts: Vec<i64>| counts.iter().sum::<i64>());
+        module.constant("VERSION", "1.0");
+        python.register_module(module);
+        exec(&mut python, "from counters import Counter, total, VERSION\nc = Counter(5)\n");
+        return python;
+    }
+
+    #[test]
+    fn test_functions() {
+        let mut python = setup();
+        assert_eq!(python.eval_as::<String>("greet('ab', 2)").unwrap(), "abab");
+        assert_eq!(python.eval_as::<i64>("checked(3)").unwrap(), 3);
+        assert_eq!(python.eval_as::<i64>("total([1, 2, 3])").unwrap(), 6);
+        assert_eq!(python.eval_as::<String>("VERSION").unwrap(), "1.0");
+    }
+
+    #[test]
+    fn test_classes() {
+        let mut python = setup();
+        exec(&mut python, "c.increment(2)\nc.count = c.count + 1\n");
+        assert_eq!(python.eval_as::<i64>("c.count").unwrap(), 8);
+        assert_eq!(python.eval_as::<i64>("c.doubled").unwrap(), 16);
+        assert!(python.eval_as::<bool>("isinstance(c, Counter) and isinstance(c, object)").unwrap());
+    }
+
+    #[test]
+    fn test_errors() {
+        let mut python = setup();
+        assert_eq!(error(&mut python, "checked(-1)"), "ValueError: negative");
+        assert_eq!(error(&mut python, "greet('a')"), "TypeError: greet() takes exactly 2 arguments (1 given)");
+        assert_eq!(error(&mut python, "greet(1, 2)"), "TypeError: greet() argument 1 must be str, not int");
+        assert_eq!(error(&mut python, "greet('a', times=2)"), "TypeError: greet() takes no keyword arguments");
+        assert_eq!(error(&mut python, "setattr(c, 'doubled', 1)"), "AttributeError: property 'doubled' of 'Counter' object has no setter");
+        assert_eq!(error(&mut python, "setattr(c, 'other', 1)"), "AttributeError: 'Counter' object has no attribute 'other'");
+        let message = "TypeError: descriptor 'increment' for 'Counter' objects doesn't apply to a 'int' object";
+        assert_eq!(error(&mut python, "Counter.increment(1, 2)"), message);
+    }
+}
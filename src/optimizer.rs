@@ -0,0 +1,353 @@
+use crate::ast::{Arg, BinOp, BoolOp, ClassDef, Comprehension, ComprehensionFor, Expr, FunctionDef, Handler, Params, Stmt, StmtKind};
+use crate::bytes;
+use crate::interpreter;
+use crate::limits;
+use crate::value::Value;
+use std::rc::Rc;
+
+/* AST optimization pass, run between parsing and execution.
+ *
+ * - folds arithmetic, comparisons and boolean operators on constants, using
+ *   the interpreter's own operators so results are identical. Anything that
+ *   would raise (1/0, 'a' + 1, overflow) is left for runtime so the error
+ *   happens when, and only if, the expression is executed
+ * - removes if/while blocks whose test is a constant false (and inlines the
+ *   body of a constant true if)
+ * - drops statements after break/continue/return
+ * - optimizes function bodies and default values
+ * - level >= 1 (-O): strips asserts and folds __debug__ to False
+ */
+
+// CPython's limits on folded constants, so that e.g. "x" * 10**8 doesn't
+// end up in the AST
+const MAX_STR_SIZE: usize = 4096;
+
+pub fn optimize(block: Vec<Stmt>, level: u8) -> Vec<Stmt> {
+    let mut optimized: Vec<Stmt> = Vec::new();
+    for stmt in block {
+        let jumps = matches!(stmt.kind, StmtKind::Break | StmtKind::Continue | StmtKind::Return(_));
+        optimized.extend(optimize_statement(stmt, level));
+        if jumps {
+            break;
+        }
+    }
+    return optimized;
+}
+
+fn optimize_statement(stmt: Stmt, level: u8) -> Vec<Stmt> {
+    let line = stmt.line;
+    let at_line = |kind: StmtKind| vec![Stmt { line, kind }];
+    match stmt.kind {
+        StmtKind::Expr(expr) => return at_line(StmtKind::Expr(fold(expr, level))),
+        StmtKind::Assign(targets, expr) => return at_line(StmtKind::Assign(targets, fold(expr, level))),
+        StmtKind::AugAssign(target, op, expr) => return at_line(StmtKind::AugAssign(target, op, fold(expr, level))),
+        StmtKind::If(test, body, orelse) => {
+            let test = fold(test, level);
+            match constant(&test) {
+                Some(value) if value.is_truthy() => return optimize(body, level),
+                Some(_) => return optimize(orelse, level),
+                None => {
+                    return at_line(StmtKind::If(test, optimize(body, level), optimize(orelse, level)));
+                }
+            }
+        }
+        StmtKind::While(test, body, orelse) => {
+            let test = fold(test, level);
+            match constant(&test) {
+                Some(value) if !value.is_truthy() => return optimize(orelse, level),
+                _ => return at_line(StmtKind::While(test, optimize(body, level), optimize(orelse, level))),
+            }
+        }
+        StmtKind::For(target, iter, body, orelse) => {
+            let iter = fold(iter, level);
+            return at_line(StmtKind::For(target, iter, optimize(body, level), optimize(orelse, level)));
+        }
+        StmtKind::With(items, body) => {
+            let items = items.into_iter().map(|(context, target)| (fold(context, level), target)).collect();
+            return at_line(StmtKind::With(items, optimize(body, level)));
+        }
+        StmtKind::Try(body, handlers, orelse, finalbody) => {
+            let handlers = handlers
+                .into_iter()
+                .map(|handler| Handler {
+                    kind: handler.kind.map(|kind| fold(kind, level)),
+                    name: handler.name,
+                    body: optimize(handler.body, level),
+                    line: handler.line,
+                })
+                .collect();
+            let (body, orelse, finalbody) = (optimize(body, level), optimize(orelse, level), optimize(finalbody, level));
+            return at_line(StmtKind::Try(body, handlers, orelse, finalbody));
+        }
+        StmtKind::Raise(exc, cause) => {
+            return at_line(StmtKind::Raise(exc.map(|exc| fold(exc, level)), cause.map(|cause| fold(cause, level))));
+        }
+        StmtKind::FunctionDef(def) => return at_line(StmtKind::FunctionDef(optimize_function(def, level))),
+        StmtKind::ClassDef(def) => {
+            let def = Rc::try_unwrap(def).unwrap_or_else(|def| (*def).clone());
+            let (bases, body) = (fold_args(def.bases, level), optimize(def.body, level));
+            let decorators = def.decorators.into_iter().map(|decorator| fold(decorator, level)).collect();
+            return at_line(StmtKind::ClassDef(Rc::new(ClassDef {
+                name: def.name,
+                bases,
+                body,
+                decorators,
+            })));
+        }
+        StmtKind::Return(value) => return at_line(StmtKind::Return(value.map(|value| fold(value, level)))),
+        StmtKind::Assert(test, msg) => {
+            if level >= 1 {
+                return vec![];
+            }
+            return at_line(StmtKind::Assert(fold(test, level), msg.map(|msg| fold(msg, level))));
+        }
+        kind @ (StmtKind::Import(_)
+        | StmtKind::ImportFrom(..)
+        | StmtKind::Global(_)
+        | StmtKind::Nonlocal(_)
+        | StmtKind::Delete(_)
+        | StmtKind::Pass
+        | StmtKind::Break
+        | StmtKind::Continue) => {
+            return at_line(kind);
+        }
+    }
+}
+
+fn optimize_function(def: Rc<FunctionDef>, level: u8) -> Rc<FunctionDef> {
+    let def = Rc::try_unwrap(def).unwrap_or_else(|def| (*def).clone());
+    let fold_params = |params: Vec<(String, Option<Expr>)>| {
+        return params
+            .into_iter()
+            .map(|(name, default)| (name, default.map(|default| fold(default, level))))
+            .collect();
+    };
+    let params = Params {
+        args: fold_params(def.params.args),
+        vararg: def.params.vararg,
+        kwonly: fold_params(def.params.kwonly),
+        kwarg: def.params.kwarg,
+    };
+    return Rc::new(FunctionDef {
+        name: def.name,
+        params,
+        body: optimize(def.body, level),
+        // what the source binds, even in code optimized away
+        locals: def.locals,
+        doc: def.doc,
+        decorators: def.decorators.into_iter().map(|decorator| fold(decorator, level)).collect(),
+    });
+}
+
+fn fold_args(args: Vec<Arg>, level: u8) -> Vec<Arg> {
+    return args
+        .into_iter()
+        .map(|arg| match arg {
+            Arg::Positional(expr) => Arg::Positional(fold(expr, level)),
+            Arg::Keyword(name, expr) => Arg::Keyword(name, fold(expr, level)),
+            Arg::Star(expr) => Arg::Star(fold(expr, level)),
+            Arg::DoubleStar(expr) => Arg::DoubleStar(fold(expr, level)),
+        })
+        .collect();
+}
+
+fn fold_box(mut expr: Box<Expr>, level: u8) -> Box<Expr> {
+    // reuses the allocation
+    *expr = fold(std::mem::replace(&mut *expr, Expr::NoneT), level);
+    return expr;
+}
+
+/* the value of a literal expression, if it is one
+ */
+fn constant(expr: &Expr) -> Option<Value> {
+    match expr {
+        Expr::NoneT => return Some(Value::NoneT),
+        Expr::Bool(val) => return Some(Value::Bool(*val)),
+        Expr::Int(val) => return Some(Value::Int(*val)),
+        Expr::BigInt(val) => return Some(Value::BigInt(val.clone())),
+        Expr::Float(val) => return Some(Value::Float(*val)),
+        Expr::Imaginary(val) => return Some(Value::Complex(0.0, *val)),
+        Expr::Str(val) => return Some(Value::Str(val.clone())),
+        Expr::Bytes(val) => return Some(bytes::new_bytes(val.clone())),
+        _ => return None,
+    }
+}
+
+/* Whether a %-format may have a field wider or more precise than a string
+ * worth folding: a count over MAX_STR_SIZE anywhere in it, or a '*'
+ * count from the arguments
+ */
+fn wide_fields(format: &str) -> bool {
+    let too_big = |digits: &str| digits.parse::<usize>().map_or(!digits.is_empty(), |count| count > MAX_STR_SIZE);
+    return format.contains('*') || format.split(|c: char| !c.is_ascii_digit()).any(too_big);
+}
+
+/* literal expression for a folded value, or None if it's too big to keep
+ */
+fn literal(value: Value) -> Option<Expr> {
+    match value {
+        Value::NoneT => return Some(Expr::NoneT),
+        Value::Bool(val) => return Some(Expr::Bool(val)),
+        Value::Int(val) => return Some(Expr::Int(val)),
+        Value::BigInt(ref val) if val.bits() <= MAX_STR_SIZE as u64 * 8 => return Some(Expr::BigInt(val.clone())),
+        Value::Float(val) => return Some(Expr::Float(val)),
+        // only a positive zero real part can be written as a literal
+        Value::Complex(real, imag) if real == 0.0 && real.is_sign_positive() => return Some(Expr::Imaginary(imag)),
+        Value::Str(ref val) if val.len() <= MAX_STR_SIZE => return Some(Expr::Str(val.clone())),
+        Value::Bytes(ref val) if val.len() <= MAX_STR_SIZE => return Some(Expr::Bytes(val.to_vec())),
+        _ => return None,
+    }
+}
+
+fn fold(expr: Expr, level: u8) -> Expr {
+    return limits::with_stack(|| fold_expr(expr, level));
+}
+
+fn fold_expr(expr: Expr, level: u8) -> Expr {
+    match expr {
+        Expr::Name(name) if name == "__debug__" => return Expr::Bool(level == 0),
+        Expr::Attribute(value, attr) => return Expr::Attribute(Box::new(fold(*value, level)), attr),
+        Expr::List(items) => return Expr::List(items.into_iter().map(|item| fold(item, level)).collect()),
+        Expr::Set(items) => return Expr::Set(items.into_iter().map(|item| fold(item, level)).collect()),
+        Expr::Tuple(items) => return Expr::Tuple(items.into_iter().map(|item| fold(item, level)).collect()),
+        Expr::Dict(items) => {
+            let items = items.into_iter().map(|(key, value)| (fold(key, level), fold(value, level)));
+            return Expr::Dict(items.collect());
+        }
+        Expr::Call(func, args) => return Expr::Call(fold_box(func, level), fold_args(args, level)),
+        Expr::Subscript(value, index) => return Expr::Subscript(fold_box(value, level), fold_box(index, level)),
+        Expr::Slice(lower, upper, step) => {
+            let fold_bound = |bound: Option<Box<Expr>>| bound.map(|bound| fold_box(bound, level));
+            return Expr::Slice(fold_bound(lower), fold_bound(upper), fold_bound(step));
+        }
+        Expr::Lambda(def) => return Expr::Lambda(optimize_function(def, level)),
+        Expr::Comprehension(comp) => {
+            let comp = Rc::try_unwrap(comp).unwrap_or_else(|comp| (*comp).clone());
+            let clauses = comp.clauses.into_iter().map(|clause| ComprehensionFor {
+                target: clause.target,
+                iter: fold(clause.iter, level),
+                conditions: clause.conditions.into_iter().map(|condition| fold(condition, level)).collect(),
+            });
+            return Expr::Comprehension(Rc::new(Comprehension {
+                kind: comp.kind,
+                element: fold(comp.element, level),
+                value: comp.value.map(|value| fold(value, level)),
+                clauses: clauses.collect(),
+                line: comp.line,
+            }));
+        }
+        Expr::IfExp(test, body, orelse) => {
+            let test = fold(*test, level);
+            match constant(&test) {
+                Some(value) if value.is_truthy() => return fold(*body, level),
+                Some(_) => return fold(*orelse, level),
+                None => return Expr::IfExp(Box::new(test), fold_box(body, level), fold_box(orelse, level)),
+            }
+        }
+        Expr::UnaryOp(op, operand) => {
+            let operand = fold(*operand, level);
+            if let Some(value) = constant(&operand) {
+                if let Some(folded) = interpreter::unary_op(op, &value).ok().and_then(literal) {
+                    return folded;
+                }
+            }
+            return Expr::UnaryOp(op, Box::new(operand));
+        }
+        Expr::BinOp(left, op, right) => {
+            let left = fold(*left, level);
+            let right = fold(*right, level);
+            if let (Some(left), Some(right)) = (constant(&left), constant(&right)) {
+                // a string too big to keep isn't built at all, as building
+                // it could exhaust memory
+                let too_big = interpreter::str_result_len(op, &left, &right).is_some_and(|len| len > MAX_STR_SIZE)
+                    || interpreter::int_result_len(op, &left, &right).is_some_and(|len| len > MAX_STR_SIZE)
+                    || matches!((op, &left), (BinOp::Mod, Value::Str(format)) if wide_fields(format));
+                if !too_big {
+                    if let Some(folded) = interpreter::binary_op(op, &left, &right).ok().and_then(literal) {
+                        return folded;
+                    }
+                }
+            }
+            return Expr::BinOp(Box::new(left), op, Box::new(right));
+        }
+        Expr::BoolOp(left, op, right) => {
+            let left = fold(*left, level);
+            let right = fold(*right, level);
+            match (op, constant(&left).map(|value| value.is_truthy())) {
+                (BoolOp::And, Some(false)) | (BoolOp::Or, Some(true)) => return left,
+                (_, Some(_)) => return right,
+                (_, None) => return Expr::BoolOp(Box::new(left), op, Box::new(right)),
+            }
+        }
+        Expr::Compare(first, rest) => {
+            let first = fold(*first, level);
+            let rest: Vec<_> = rest.into_iter().map(|(op, expr)| (op, fold(expr, level))).collect();
+            if let Some(mut left) = constant(&first) {
+                // operands after the first false comparison are never
+                // evaluated, so they don't need to be constant
+                for (index, (op, right)) in rest.iter().enumerate() {
+                    let right = match constant(right) {
+                        Some(right) => right,
+                        None => break,
+                    };
+                    match interpreter::compare(*op, &left, &right) {
+                        Ok(false) => return Expr::Bool(false),
+                        Ok(true) if index == rest.len() - 1 => return Expr::Bool(true),
+                        Ok(true) => left = right,
+                        Err(_) => break,
+                    }
+                }
+            }
+            return Expr::Compare(Box::new(first), rest);
+        }
+        _ => return expr,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::{BinOp, CmpOp};
+
+    fn int(val: i64) -> Box<Expr> {
+        return Box::new(Expr::Int(val));
+    }
+
+    #[test]
+    fn test_fold_arithmetic() {
+        // 2 * 3 + 1
+        let expr = Expr::BinOp(Box::new(Expr::BinOp(int(2), BinOp::Mul, int(3))), BinOp::Add, int(1));
+        assert_eq!(fold(expr, 0), Expr::Int(7));
+
+        // 1 / 0 raises at runtime, so it must survive folding
+        let expr = Expr::BinOp(int(1), BinOp::Div, int(0));
+        assert_eq!(fold(expr.clone(), 0), expr);
+
+        // "ab" * 5000 is over the size limit
+        let expr = Expr::BinOp(Box::new(Expr::Str(String::from("ab"))), BinOp::Mul, int(5000));
+        assert_eq!(fold(expr.clone(), 0), expr);
+
+        // 1 < 2 < x can't be folded
+        let expr = Expr::Compare(int(1), vec![(CmpOp::Lt, Expr::Int(2)), (CmpOp::Lt, Expr::Name(String::from("x")))]);
+        assert_eq!(fold(expr.clone(), 0), expr);
+    }
+
+    fn stmt(kind: StmtKind) -> Stmt {
+        return Stmt { line: 1, kind };
+    }
+
+    #[test]
+    fn test_dead_code() {
+        let assert = stmt(StmtKind::Assert(Expr::Name(String::from("x")), None));
+        let block = vec![
+            stmt(StmtKind::If(Expr::Bool(false), vec![stmt(StmtKind::Pass)], vec![])),
+            stmt(StmtKind::If(Expr::Name(String::from("__debug__")), vec![assert.clone()], vec![])),
+        ];
+        assert_eq!(optimize(block.clone(), 0), vec![assert]);
+        assert_eq!(optimize(block, 1), vec![]);
+
+        let block = vec![stmt(StmtKind::While(Expr::Bool(true), vec![stmt(StmtKind::Break), stmt(StmtKind::Pass)], vec![]))];
+        let optimized = vec![stmt(StmtKind::While(Expr::Bool(true), vec![stmt(StmtKind::Break)], vec![]))];
+        assert_eq!(optimize(block, 0), optimized);
+    }
+}
@@ -1,43 +1,47 @@
 //pub mod scanner;
+use crate::ast::{
+    Arg, BinOp, BoolOp, ClassDef, CmpOp, Comprehension, ComprehensionFor, ComprehensionKind, Expr, FunctionDef, Handler,
+    Params, Stmt, StmtKind, UnaryOp,
+};
+use crate::interpreter::Interpreter;
+use crate::limits;
+use crate::optimizer;
+use crate::readline::LineReader;
 use crate::scanner;
-use std::collections::{HashMap, VecDeque};
-
-#[derive(Debug)]
-#[allow(dead_code)]
-enum Type {
-    Int,
-    Float,
-    Str,
-    Var,
-}
+use crate::scanner::Token;
+use crate::value::{Args, Exception, Value};
+use std::collections::{HashSet, VecDeque};
+use std::path::Path;
+use std::rc::Rc;
 
-#[derive(Debug, Clone)]
-#[allow(dead_code)]
-enum Value {
-    Int(i64),
-    Float(f64),
-    Str(String),
-    Terminator,
-    Exit,
-    Error,
-    SpecificError(String),
-    NotImplementedError,
-    NameError,
-}
+type ParseResult<T> = Result<T, Exception>;
 
-#[derive(Debug)]
-struct Data {
-    type_meta: Type,
-    value_meta: Value,
-}
+// expressions nested deeper than this are too much for the optimizer and
+// interpreter, which recurse the same way; CPython's compiler gives up at a
+// few thousand levels too
+const MAX_NESTING: usize = 2000;
 
-struct State {
-    vars: HashMap<String, Data>,
+fn syntax_error(message: &str) -> Exception {
+    return Exception::new("SyntaxError", message);
 }
 
 pub struct Parser {
     input: scanner::Input,
-    state: State,
+    // indentation of the line the current token is on
+    indent: usize,
+    // true when the current token is the first token of a line that has not
+    // been parsed yet, i.e. a block has read one line past its end
+    line_pending: bool,
+    // number of enclosing blocks/loops, for interactive block termination
+    // and break/continue checking
+    depth: usize,
+    loop_depth: usize,
+    // number of enclosing defs, for checking return
+    func_depth: usize,
+    // indentation of the enclosing blocks, to check dedents return to one
+    indents: Vec<usize>,
+    // number of expressions the current one is nested in
+    nesting: usize,
 }
 
 impl Parser {
@@ -45,286 +49,1853 @@ impl Parser {
         let parser = Parser {
             input: scanner::Input {
                 stream: String::from(""),
-                current: scanner::Token::NewLine,
+                current: Token::NewLine,
                 history: VecDeque::new(),
+                tokens: None,
+                line: 1,
+                next_line: 1,
+                nesting: 0,
+                reader: LineReader::Plain,
             },
-            state: State {
-                vars: HashMap::new(),
-            },
+            indent: 0,
+            line_pending: false,
+            depth: 0,
+            loop_depth: 0,
+            func_depth: 0,
+            indents: Vec::new(),
+            nesting: 0,
         };
         return parser;
     }
 
-    /* program ::= exit | state | var_ref_print | statement | program statement
+    /* parser over a pre-lexed script rather than stdin
+     */
+    pub fn build_from_tokens(tokens: Vec<(Token, usize)>) -> Parser {
+        let mut parser = Parser::build_new();
+        parser.input.tokens = Some(tokens.into_iter().collect());
+        return parser;
+    }
+
+    /* Whether parsing source fails before reaching its end, i.e. no more
+     * lines could make it valid.
+     */
+    pub fn fails_before_end(source: &str) -> bool {
+        let tokens = match scanner::lex(source) {
+            Ok((tokens, _)) => tokens,
+            Err(_) => return true,
+        };
+        let mut parser = Parser::build_from_tokens(tokens);
+        return parser.parse_module().is_err() && !matches!(parser.input.current, Token::Exit);
+    }
+
+    fn interactive(&self) -> bool {
+        return self.input.tokens.is_none();
+    }
+
+    /* A syntax error is found at the current token
+     */
+    fn locate(&self, mut err: Exception) -> Exception {
+        err.line.get_or_insert(self.input.line);
+        return err;
+    }
+
+    /* How a syntax error is reported: "message (file, line N)". Code
+     * nested too deeply is a RecursionError, which doesn't say where.
+     */
+    pub fn syntax_error_at(mut err: Exception, filename: &str) -> Exception {
+        if err.kind == "RecursionError" {
+            err.line = None;
+            return err;
+        }
+        match err.line.take() {
+            Some(line) => err.message = format!("{} ({}, line {})", err.message, filename, line),
+            None => err.message = format!("{} ({})", err.message, filename),
+        };
+        return err;
+    }
+
+    /* module ::= statement*
      *
+     * Parse a whole pre-lexed file (a script or an imported module) up
+     * front, so syntax errors are reported before any of it runs.
      */
-    pub fn parse_program(&mut self) {
+    pub fn parse_module(&mut self) -> ParseResult<Vec<Stmt>> {
+        return self.parse_module_body().map_err(|err| self.locate(err));
+    }
+
+    fn parse_module_body(&mut self) -> ParseResult<Vec<Stmt>> {
+        let mut block: Vec<Stmt> = Vec::new();
         loop {
-            // update current token
+            if !self.line_pending {
+                self.start_line();
+            }
+            self.line_pending = false;
+            match self.input.current {
+                Token::Exit => return Ok(block),
+                Token::NewLine => continue,
+                _ if self.indent > 0 => {
+                    return Err(Exception::new("IndentationError", "unexpected indent"));
+                }
+                _ => block.extend(self.parse_statement()?),
+            };
+        }
+    }
+
+    /* eval_input ::= expr_list NEWLINE*
+     *
+     * A single expression, for the embedding API's eval
+     */
+    pub fn parse_eval(&mut self) -> ParseResult<Expr> {
+        return self.parse_eval_body().map_err(|err| self.locate(err));
+    }
+
+    fn parse_eval_body(&mut self) -> ParseResult<Expr> {
+        self.start_line();
+        let expr = self.parse_expression_list()?;
+        while let Token::NewLine = self.input.current {
             self.input.get_next_token(true);
+        }
+        match self.input.current {
+            Token::Exit => return Ok(expr),
+            _ => return Err(syntax_error("invalid syntax")),
+        }
+    }
 
-            match self.input.current {
-                scanner::Token::Exit => break,
-                scanner::Token::State => {
-                    self.parse_state();
-                },
-                scanner::Token::NewLine => {
-                    self.parse_newline();
+    /* program ::= exit | state | statement | program statement
+     *
+     * The interactive prompt: parses, optimizes and runs one top-level
+     * statement at a time, reporting and skipping errors, until the input
+     * ends or sys.exit() raises SystemExit, which is returned.
+     */
+    pub fn parse_program(&mut self, interpreter: &mut Interpreter) -> Result<(), Exception> {
+        self.input.reader = LineReader::build_new(interpreter);
+        loop {
+            if !self.line_pending {
+                self.start_line();
+            }
+            self.line_pending = false;
+
+            match (&self.input.current, self.prompt_command()) {
+                (Token::Exit, _) | (_, Some("exit")) => return Ok(()),
+                (_, Some(_)) => {
+                    self.parse_state(interpreter);
+                    continue;
                 }
-                _ => {
-                    let value: Value = self.parse_statement();
-                    match value {
-                        Value::Exit => break,
-                        _ => {
-                            // TODO: catch early termination -> syntax error?
-                            println!("Result: {:?}", value)
-                        }
-                    }
+                (Token::NewLine, _) => continue,
+                _ => (),
+            };
+
+            let result = if self.indent > 0 {
+                Err(Exception::new("IndentationError", "unexpected indent"))
+            } else {
+                self.parse_statement()
+            };
+            let result = match result {
+                Ok(stmts) => {
+                    // Ctrl-C at the prompt is for the line being typed
+                    interpreter.interrupt.clear();
+                    Parser::run_statements(interpreter, stmts).map_err(|mut err| {
+                        err.add_frame(&interpreter.globals, "<module>");
+                        err
+                    })
                 }
+                Err(err) => Err(Parser::syntax_error_at(self.locate(err), "<stdin>")),
             };
+            if let Err(err) = result {
+                if err.kind == "SystemExit" {
+                    return Err(err);
+                }
+                eprintln!("{}", err.format_traceback());
+                self.skip_line();
+            }
         }
     }
 
-    #[allow(dead_code)]
-    fn parse_newline(&mut self) -> Value {
-        // consume token
-        self.input.get_next_token(true);
-        return Value::Terminator;
+    /* Run the statements typed on a line at the prompt. Like
+     * sys.displayhook, the repr of an expression statement's value is
+     * written to sys.stdout and bound to `_` in builtins, unless it is None.
+     */
+    fn run_statements(interpreter: &mut Interpreter, stmts: Vec<Stmt>) -> Result<(), Exception> {
+        for stmt in optimizer::optimize(stmts, interpreter.optimize) {
+            match &stmt.kind {
+                StmtKind::Expr(expr) => {
+                    let value: Value = interpreter.eval(expr).map_err(|mut err| {
+                        err.line.get_or_insert(stmt.line);
+                        err
+                    })?;
+                    if !matches!(value, Value::NoneT) {
+                        Parser::display(interpreter, value).map_err(|mut err| {
+                            err.line.get_or_insert(stmt.line);
+                            err
+                        })?;
+                    }
+                }
+                _ => {
+                    interpreter.exec_statement(&stmt)?;
+                }
+            }
+        }
+        interpreter.run_gc();
+        return Ok(());
     }
 
-    /*
-     * TODO: could serve purpose for logging in the future
+    /* Echo a value at the prompt. It goes through sys.stdout like print(),
+     * so it can be redirected and counts against the output limit.
      */
-    #[allow(dead_code)]
-    fn parse_exit(&mut self) -> Value {
-        self.input.get_next_token(true);
-        return Value::Exit;
+    fn display(interpreter: &mut Interpreter, value: Value) -> Result<(), Exception> {
+        let stdout = interpreter.modules.get("sys").and_then(|sys| sys.get("stdout"));
+        let stdout = match stdout {
+            Some(Value::NoneT) | None => return Err(Exception::new("RuntimeError", "lost sys.stdout")),
+            Some(stdout) => stdout,
+        };
+        let text = format!("{}\n", interpreter.repr(&value)?);
+        let write = interpreter.get_attr(&stdout, "write")?;
+        interpreter.call(&write, Args::build_new(vec![Value::Str(text)]))?;
+        interpreter.builtins.set("_", value);
+        return Ok(());
     }
 
-    /* debugging function
-     * display current state
+    /* Move to the first token of the next non-blank line, recording its
+     * indentation. At the prompt, a blank line ends any open block, but a
+     * line holding only a comment does not.
      */
-    fn parse_state(&mut self) -> Value {
-        // consume token
-        self.input.get_next_token(true);
-        println!("current state:");
-        for (key, value) in &self.state.vars {
-            println!("{}: {:?}", key, value);
+    fn start_line(&mut self) {
+        loop {
+            self.indent = 0;
+            let comment = self.input.stream.lines().next().is_some_and(|line| line.trim_start().starts_with('#'));
+            self.input.get_next_token(false);
+            if let Token::WhiteSpace(len) = self.input.current {
+                self.indent = len;
+                self.input.get_next_token(true);
+            }
+            match self.input.current {
+                Token::NewLine if comment || !(self.interactive() && self.depth > 0) => continue,
+                _ => break,
+            }
         }
-        println!("current stream: {:?}", self.input.stream);
-        return Value::Terminator;
+        self.line_pending = true;
     }
 
-    /* statement ::= expr | var = expr | epsilon
-     * TODO think about utility of epsilon
-     * TODO think about how to terminate successfully
-     * TODO thinking about incomplete lines - currently, trying to allow for
-     * just a single var, but need to enable different incomplete statements to
-     * prompt on a newline for completion
+    /* error recovery: discard the rest of the current line, or at the
+     * prompt everything typed for the statement
      */
-    fn parse_statement(&mut self) -> Value {
-        match self.input.current {
-            scanner::Token::Variable(_) => {
-                match self.input.look_ahead(true) {
-                    scanner::Token::Equals => return self.parse_assign(),
-                    _ => return self.parse_var_ref(),
-                }
-            },
-            _ => return self.parse_expression(),
+    fn skip_line(&mut self) {
+        if self.interactive() {
+            self.input.flush_line();
+            self.input.current = Token::NewLine;
+        }
+        loop {
+            match self.input.current {
+                Token::NewLine | Token::Exit | Token::SyntaxError => break,
+                _ => self.input.get_next_token(true),
+            };
         }
+        self.line_pending = false;
+        self.depth = 0;
+        self.loop_depth = 0;
+        self.func_depth = 0;
+        self.indents.clear();
     }
 
-    fn parse_assign(&mut self) -> Value {
-        let var_name: String;
-        if let scanner::Token::Variable(var) = &mut self.input.current {
-            var_name = var.to_string();
-        } else {
-            return Value::Error;
-        }
-        self.input.get_next_token(true);
-        self.input.get_next_token(true);
-        let var_value = self.parse_expression();
-        let var_type: Type;
-        match var_value {
-            Value::Int(_) => var_type = Type::Int,
-            Value::Float(_) => var_type = Type::Float,
-            _ => return Value::Error,
-        };
-        let return_value: Value = var_value.clone();
-        let data = Data {
-            value_meta: var_value,
-            type_meta: var_type,
+    /* The prompt command a line starts with: exit on its own, or state on
+     * its own or followed by save or load. Anywhere else, and in scripts,
+     * exit and state are ordinary names, as in `state = {}`.
+     */
+    fn prompt_command(&self) -> Option<&'static str> {
+        let name = match &self.input.current {
+            Token::Variable(name) if self.indent == 0 && self.input.history.is_empty() => name.as_str(),
+            _ => return None,
         };
-        self.state.vars.insert(var_name, data);
-        return return_value;
+        let rest = self.input.stream.split('\n').next().unwrap_or("");
+        let word = rest.split_whitespace().next();
+        match (name, word) {
+            ("exit", None) => return Some("exit"),
+            ("state", None) => return Some("state"),
+            ("state", Some("save" | "load")) if rest.starts_with(char::is_whitespace) => return Some("state"),
+            _ => return None,
+        }
     }
 
-    /* expr ::= term | expr + term | expr - term
+    /* state ::= "state" | "state save" path | "state load" path
      *
+     * Commands at the prompt: display the current state (for debugging),
+     * or save the global variables to a session file and load them back
+     * (see session.rs). The path is the rest of the line, as typed.
      */
-    fn parse_expression(&mut self) -> Value {
-        let mut return_value = self.parse_term();
-        loop {
-            match &self.input.current {
-                scanner::Token::Plus => {
-                    self.input.get_next_token(true);
-                    // TODO var ref not getting recognized as an int?
-                    if let Value::Int(val_int) = return_value {
-                        if let Value::Int(val_parsed) = self.parse_term() {
-                            return_value = Value::Int(val_int + val_parsed);
-                        }
-                    }
+    fn parse_state(&mut self, interpreter: &mut Interpreter) {
+        let line_end = self.input.stream.find('\n').unwrap_or(self.input.stream.len());
+        let line: String = self.input.stream.drain(..line_end).collect();
+        let (command, path) = match line.trim().split_once(char::is_whitespace) {
+            Some((command, path)) => (command, Path::new(path.trim())),
+            None => (line.trim(), Path::new("")),
+        };
+        match command {
+            "" => {
+                println!("current state:");
+                for (key, value) in interpreter.globals.vars.borrow().iter() {
+                    println!("{}: {}", key, value.repr());
                 }
-                scanner::Token::Minus => {
-                    self.input.get_next_token(true);
-                    if let Value::Int(val_int) = return_value {
-                        if let Value::Int(val_parsed) = self.parse_term() {
-                            return_value = Value::Int(val_int + val_parsed);
-                        }
+                println!("current stream: {:?}", self.input.stream);
+            }
+            "save" if !path.as_os_str().is_empty() => match interpreter.save_session(path) {
+                Ok(skipped) if skipped.is_empty() => println!("saved to {}", path.display()),
+                Ok(skipped) => println!("saved to {}, except {}", path.display(), skipped.join(", ")),
+                Err(err) => eprintln!("{}", err.format_traceback()),
+            },
+            "load" if !path.as_os_str().is_empty() => match interpreter.load_session(path) {
+                Ok((count, problems)) => {
+                    println!("loaded {} variables from {}", count, path.display());
+                    for problem in problems {
+                        eprintln!("skipped {}", problem);
                     }
                 }
-                _ => break,
+                Err(err) => eprintln!("{}", err.format_traceback()),
+            },
+            _ => eprintln!("usage: state | state save <path> | state load <path>"),
+        };
+    }
+
+    fn expect(&mut self, token: Token, message: &str) -> ParseResult<()> {
+        if std::mem::discriminant(&self.input.current) != std::mem::discriminant(&token) {
+            return Err(syntax_error(message));
+        }
+        self.input.get_next_token(true);
+        return Ok(());
+    }
+
+    /* The closing bracket of something bracketed, open being its opening
+     * bracket. With comma, two items side by side are taken to be missing
+     * a comma between them.
+     */
+    fn expect_close(&mut self, token: Token, open: &str, comma: bool) -> ParseResult<()> {
+        if std::mem::discriminant(&self.input.current) == std::mem::discriminant(&token) {
+            self.input.get_next_token(true);
+            return Ok(());
+        }
+        match self.input.current {
+            // input ended at the prompt
+            Token::Exit => return Err(syntax_error(&format!("'{}' was never closed", open))),
+            Token::Variable(_)
+            | Token::Int(_)
+            | Token::BigInt(_)
+            | Token::Float(_)
+            | Token::Imaginary(_)
+            | Token::Str(_)
+            | Token::Bytes(_)
+            | Token::True
+            | Token::False
+            | Token::NoneT
+            | Token::OpenParen
+            | Token::OpenBracket
+            | Token::OpenBrace
+            | Token::Lambda
+            | Token::Tilde
+                if comma =>
+            {
+                return Err(syntax_error("invalid syntax. Perhaps you forgot a comma?"));
             }
+            _ => return Err(syntax_error("invalid syntax")),
         }
-        return return_value;
     }
 
-    /* term ::= power | term * power | term / power
+    /* statement ::= simple_statements NEWLINE | if_statement | while_statement
+     *             | for_statement | with_statement | try_statement
+     *             | def_statement | class_statement | decorated
+     * simple_statements ::= simple_statement (; simple_statement)* [;]
      *
+     * Simple statements leave the terminating newline as the current token,
+     * so nothing past the statement is read before it runs. Compound
+     * statements read one line past their block (line_pending).
      */
-    fn parse_term(&mut self) -> Value {
-        let mut return_value = self.parse_power();
+    fn parse_statement(&mut self) -> ParseResult<Vec<Stmt>> {
+        let line = self.input.line;
+        let kind = match self.input.current {
+            Token::If => self.parse_if()?,
+            Token::While => self.parse_while()?,
+            Token::For => self.parse_for()?,
+            Token::With => self.parse_with()?,
+            Token::Try => self.parse_try()?,
+            Token::Def => self.parse_def(Vec::new())?,
+            Token::Class => self.parse_class(Vec::new())?,
+            Token::At => self.parse_decorated()?,
+            _ => return self.parse_simple_statements(),
+        };
+        return Ok(vec![Stmt { line, kind }]);
+    }
+
+    fn parse_simple_statements(&mut self) -> ParseResult<Vec<Stmt>> {
+        let mut stmts: Vec<Stmt> = Vec::new();
         loop {
-            match self.input.current {
-                scanner::Token::Multiply => {
-                    self.input.get_next_token(true);
-                    if let Value::Int(val_int) = return_value {
-                        if let Value::Int(val_parsed) = self.parse_power() {
-                            return_value = Value::Int(val_int * val_parsed);
-                        }
-                    }
+            let line = self.input.line;
+            let kind = self.parse_simple_statement()?;
+            match (&kind, &self.input.current) {
+                (_, Token::NewLine | Token::Exit) => {
+                    stmts.push(Stmt { line, kind });
+                    return Ok(stmts);
                 }
-                scanner::Token::Divide => {
+                (_, Token::Semicolon) => {
+                    stmts.push(Stmt { line, kind });
                     self.input.get_next_token(true);
-                    if let Value::Int(val_int) = return_value {
-                        if let Value::Int(val_parsed) = self.parse_power() {
-                            return_value = Value::Int(val_int / val_parsed);
-                        }
+                    if matches!(self.input.current, Token::NewLine | Token::Exit) {
+                        return Ok(stmts);
                     }
                 }
-                _ => break,
-            }
+                (StmtKind::Expr(Expr::Name(name)), _) if name == "print" => {
+                    return Err(syntax_error("Missing parentheses in call to 'print'. Did you mean print(...)?"));
+                }
+                _ => return Err(syntax_error("invalid syntax")),
+            };
         }
-        return return_value;
     }
 
-    /* power ::= factor | factor ** power
-     * TODO handle floats
+    /* simple_statement ::= expr_list | (target_list =)+ expr_list
+     *                    | target aug_op expr_list | pass | break | continue
+     *                    | assert expr [, expr] | import_statement
+     *                    | from_statement | return [expr_list]
+     *                    | global NAME (, NAME)* | nonlocal NAME (, NAME)*
+     *                    | del target_list | raise [expr [from expr]]
+     * aug_op ::= += | -= | *= | /= | //= | %= | **= | &= | |= | ^= | <<= | >>=
      */
-    fn parse_power(&mut self) -> Value {
-        let factor: Value = self.parse_factor();
+    fn parse_simple_statement(&mut self) -> ParseResult<StmtKind> {
         match self.input.current {
-            scanner::Token::NewLine => {
-                return factor;
-            }
-            scanner::Token::Exponent => {
-                self.input.get_next_token(true); // consume operator
-                let power: Value = self.parse_power();
-                if let Value::Int(base_int) = factor {
-                    if let Value::Int(power_int) = power {
-                        let total: u32 = power_int as u32; // TODO should make this panic
-                        match base_int.checked_pow(total) {
-                            Some(pow) => return Value::Int(pow),
-                            _ => return Value::Error
-                        };
+            Token::Pass => {
+                self.input.get_next_token(true);
+                return Ok(StmtKind::Pass);
+            }
+            Token::Break | Token::Continue => {
+                match (&self.input.current, self.loop_depth) {
+                    (Token::Break, 0) => return Err(syntax_error("'break' outside loop")),
+                    (_, 0) => return Err(syntax_error("'continue' not properly in loop")),
+                    _ => (),
+                };
+                let stmt = match self.input.current {
+                    Token::Break => StmtKind::Break,
+                    _ => StmtKind::Continue,
+                };
+                self.input.get_next_token(true);
+                return Ok(stmt);
+            }
+            Token::Return => {
+                if self.func_depth == 0 {
+                    return Err(syntax_error("'return' outside function"));
+                }
+                self.input.get_next_token(true);
+                if self.at_expression_end() {
+                    return Ok(StmtKind::Return(None));
+                }
+                return Ok(StmtKind::Return(Some(self.parse_expression_list()?)));
+            }
+            Token::Global | Token::Nonlocal => {
+                let global = matches!(self.input.current, Token::Global);
+                if !global && self.func_depth == 0 {
+                    return Err(syntax_error("nonlocal declaration not allowed at module level"));
+                }
+                let mut names = vec![];
+                loop {
+                    self.input.get_next_token(true); // consume keyword / ","
+                    names.push(self.parse_name()?);
+                    if !matches!(self.input.current, Token::Comma) {
+                        break;
                     }
                 }
-                return Value::Error;
+                return Ok(if global { StmtKind::Global(names) } else { StmtKind::Nonlocal(names) });
+            }
+            Token::Del => {
+                self.input.get_next_token(true);
+                let targets = match self.parse_expression_list()? {
+                    Expr::Tuple(targets) => targets,
+                    target => vec![target],
+                };
+                for target in &targets {
+                    Parser::check_target(target, "delete")?;
+                }
+                return Ok(StmtKind::Delete(targets));
+            }
+            Token::Raise => {
+                self.input.get_next_token(true);
+                if self.at_expression_end() {
+                    return Ok(StmtKind::Raise(None, None));
+                }
+                let exc = self.parse_expression()?;
+                let mut cause = None;
+                if let Token::FromImport = self.input.current {
+                    self.input.get_next_token(true);
+                    cause = Some(self.parse_expression()?);
+                }
+                return Ok(StmtKind::Raise(Some(exc), cause));
             }
-            _ => return factor,
+            Token::Assert => return self.parse_assert(),
+            Token::Import => return self.parse_import(),
+            Token::FromImport => return self.parse_from_import(),
+            _ => (),
         };
-    }
 
-    /* factor ::= var_ref | number | (exp) | -exp
-     * TODO how to parse negative number?
-     */
-    fn parse_factor(&mut self) -> Value {
+        let expr = self.parse_expression_list()?;
         match self.input.current {
-            scanner::Token::OpenParen => return self.parse_parens(),
-            scanner::Token::Int(_) | scanner::Token::Float(_) => return self.parse_number(),
-            scanner::Token::Variable(_) => return self.parse_var_ref(),
-            _ => return Value::Error,
-        };
+            Token::Equals => return self.parse_assign(expr),
+            Token::AugAssign(op) => {
+                let kind = match expr {
+                    Expr::Name(_) | Expr::Attribute(..) | Expr::Subscript(..) => None,
+                    Expr::Tuple(_) => Some("tuple"),
+                    Expr::List(_) => Some("list"),
+                    _ => Some(Parser::describe(&expr)),
+                };
+                if let Some(kind) = kind {
+                    return Err(syntax_error(&format!("'{}' is an illegal expression for augmented assignment", kind)));
+                }
+                self.input.get_next_token(true);
+                return Ok(StmtKind::AugAssign(expr, op, self.parse_expression_list()?));
+            }
+            _ => return Ok(StmtKind::Expr(expr)),
+        }
     }
 
-    fn parse_var_ref(&mut self) -> Value {
-        let var = self.input.current.clone();
-        match var {
-            scanner::Token::Variable(name) => {
-                self.input.get_next_token(true);
-                match self.state.vars.get(&name) {
-                    Some(data) => {
-                        return data.value_meta.clone();
-                    },
-                    None => return Value::NameError,
-                }
-            },
-            _ => {
-                    self.input.get_next_token(true);
-                    return Value::SpecificError(String::from("Unknown error: parsing var ref"));
+    /* what an expression that can't be assigned to is called in errors
+     */
+    fn describe(expr: &Expr) -> &'static str {
+        match expr {
+            Expr::Call(..) => return "function call",
+            Expr::Int(_) | Expr::BigInt(_) | Expr::Float(_) | Expr::Imaginary(_) | Expr::Str(_) | Expr::Bytes(_) => {
+                return "literal";
+            }
+            Expr::NoneT => return "None",
+            Expr::Bool(true) => return "True",
+            Expr::Bool(false) => return "False",
+            Expr::Compare(..) => return "comparison",
+            Expr::Lambda(_) => return "lambda",
+            Expr::IfExp(..) => return "conditional expression",
+            Expr::Dict(_) => return "dict literal",
+            Expr::Set(_) => return "set display",
+            Expr::Comprehension(comp) => match comp.kind {
+                ComprehensionKind::List => return "list comprehension",
+                ComprehensionKind::Dict => return "dict comprehension",
+                ComprehensionKind::Set => return "set comprehension",
+                ComprehensionKind::Generator => return "generator expression",
             },
-        };
+            _ => return "expression",
+        }
     }
 
-    /* number ::= int | float
-     *
+    /* the first part of a target that can't be assigned to
      */
-    fn parse_number(&mut self) -> Value {
-        match self.input.current {
-            scanner::Token::Int(_) => return self.parse_int(),
-            scanner::Token::Float(_) => return self.parse_float(),
-            _ => {
-                self.input.get_next_token(true);
-                return Value::Error;
+    fn invalid_target(target: &Expr) -> Option<&Expr> {
+        match target {
+            Expr::Name(_) | Expr::Attribute(..) | Expr::Subscript(..) => return None,
+            Expr::Tuple(items) | Expr::List(items) => return items.iter().find_map(Parser::invalid_target),
+            _ => return Some(target),
+        }
+    }
+
+    /* action is what is done to the target, for errors: "assign to" or
+     * "delete"
+     */
+    fn check_target(target: &Expr, action: &str) -> ParseResult<()> {
+        match Parser::invalid_target(target) {
+            Some(expr) => return Err(syntax_error(&format!("cannot {} {}", action, Parser::describe(expr)))),
+            None => return Ok(()),
+        }
+    }
+
+    fn parse_assign(&mut self, target: Expr) -> ParseResult<StmtKind> {
+        let mut targets = vec![target];
+        let value = loop {
+            self.input.get_next_token(true); // consume "="
+            let value = self.parse_expression_list()?;
+            if !matches!(self.input.current, Token::Equals) {
+                break value;
             }
+            targets.push(value);
         };
+        // e.g. `f() = 1` or `x + 1 = 2` may be a typo for ==
+        let target = &targets[0];
+        let comparable = match target {
+            Expr::NoneT
+            | Expr::Bool(_)
+            | Expr::Compare(..)
+            | Expr::BoolOp(..)
+            | Expr::UnaryOp(UnaryOp::Not, _)
+            | Expr::Lambda(_)
+            | Expr::IfExp(..) => false,
+            Expr::Comprehension(comp) => comp.kind != ComprehensionKind::Generator,
+            _ => true,
+        };
+        if let (Some(expr), true, 1) = (Parser::invalid_target(target), comparable, targets.len()) {
+            if std::ptr::eq(expr, target) {
+                let message = format!(
+                    "cannot assign to {} here. Maybe you meant '==' instead of '='?",
+                    Parser::describe(expr)
+                );
+                return Err(syntax_error(&message));
+            }
+        }
+        for target in &targets {
+            Parser::check_target(target, "assign to")?;
+        }
+        return Ok(StmtKind::Assign(targets, value));
     }
 
-    fn parse_int(&mut self) -> Value {
-        if let scanner::Token::Int(val) = self.input.current {
+    fn parse_name(&mut self) -> ParseResult<String> {
+        if let Token::Variable(name) = &self.input.current {
+            let name = name.clone();
             self.input.get_next_token(true);
-            return Value::Int(val);
-        } else {
+            return Ok(name);
+        }
+        return Err(syntax_error("invalid syntax"));
+    }
+
+    /* dotted_name ::= NAME | dotted_name . NAME
+     */
+    fn parse_dotted_name(&mut self) -> ParseResult<String> {
+        let mut name = self.parse_name()?;
+        while let Token::Dot = self.input.current {
+            self.input.get_next_token(true);
+            name.push('.');
+            name.push_str(&self.parse_name()?);
+        }
+        return Ok(name);
+    }
+
+    /* [as NAME]
+     */
+    fn parse_alias(&mut self) -> ParseResult<Option<String>> {
+        if let Token::As = self.input.current {
             self.input.get_next_token(true);
-            return Value::Error;
+            return Ok(Some(self.parse_name()?));
+        }
+        return Ok(None);
+    }
+
+    /* import_statement ::= import dotted_name [as NAME] (, dotted_name [as NAME])*
+     */
+    fn parse_import(&mut self) -> ParseResult<StmtKind> {
+        let mut names: Vec<(String, Option<String>)> = Vec::new();
+        loop {
+            self.input.get_next_token(true); // consume "import" / ","
+            let name = self.parse_dotted_name()?;
+            names.push((name, self.parse_alias()?));
+            if !matches!(self.input.current, Token::Comma) {
+                return Ok(StmtKind::Import(names));
+            }
         }
     }
 
-    fn parse_float(&mut self) -> Value {
-        if let scanner::Token::Float(val) = self.input.current {
+    /* from_statement ::= from .* dotted_name import import_names
+     *                  | from .+ import import_names
+     * import_names ::= * | names | ( names [,] )
+     * names ::= NAME [as NAME] (, NAME [as NAME])*
+     */
+    fn parse_from_import(&mut self) -> ParseResult<StmtKind> {
+        self.input.get_next_token(true); // consume "from"
+        let mut level: usize = 0;
+        while let Token::Dot = self.input.current {
+            level += 1;
             self.input.get_next_token(true);
-            return Value::Float(val);
-        } else {
+        }
+        let module = match (&self.input.current, level) {
+            (Token::Import, 1..) => String::new(),
+            _ => self.parse_dotted_name()?,
+        };
+        self.expect(Token::Import, "invalid syntax")?;
+
+        if let Token::Multiply = self.input.current {
+            self.input.get_next_token(true);
+            return Ok(StmtKind::ImportFrom(level, module, vec![(String::from("*"), None)]));
+        }
+        let parens = matches!(self.input.current, Token::OpenParen);
+        if parens {
+            self.input.get_next_token(true);
+        }
+        let mut names: Vec<(String, Option<String>)> = Vec::new();
+        loop {
+            let name = self.parse_name()?;
+            names.push((name, self.parse_alias()?));
+            if !matches!(self.input.current, Token::Comma) {
+                break;
+            }
             self.input.get_next_token(true);
-            return Value::Error;
+            // trailing comma is only allowed inside parentheses
+            if parens && matches!(self.input.current, Token::CloseParen) {
+                break;
+            }
         }
+        if parens {
+            self.expect_close(Token::CloseParen, "(", false)?;
+        }
+        return Ok(StmtKind::ImportFrom(level, module, names));
     }
 
-    fn parse_parens(&mut self) -> Value {
-        self.input.get_next_token(true); // consume "("
-        let value: Value = self.parse_expression();
-        self.input.get_next_token(true); // consume ")"
-        return value;
+    fn parse_assert(&mut self) -> ParseResult<StmtKind> {
+        self.input.get_next_token(true); // consume "assert"
+        let test = self.parse_expression()?;
+        let mut msg: Option<Expr> = None;
+        if let Token::Comma = self.input.current {
+            self.input.get_next_token(true);
+            msg = Some(self.parse_expression()?);
+        }
+        return Ok(StmtKind::Assert(test, msg));
     }
 
-    /* for debugging/etc - simply repeats tokens back to user, 1 per line
-    */
-    #[allow(dead_code)]
-    fn repeat_tokens(&mut self) {
+    /* if_statement ::= if expr : block (elif expr : block)* [else : block]
+     */
+    fn parse_if(&mut self) -> ParseResult<StmtKind> {
+        let indent = self.indent;
+        let (header, line) = match self.input.current {
+            Token::Elif => ("'elif' statement", self.input.line),
+            _ => ("'if' statement", self.input.line),
+        };
+        self.input.get_next_token(true); // consume "if" / "elif"
+        let test = self.parse_expression()?;
+        let body = self.parse_block(header, line)?;
+        let mut orelse: Vec<Stmt> = Vec::new();
+        if self.indent == indent {
+            match self.input.current {
+                Token::Elif => {
+                    self.line_pending = false;
+                    self.indent = indent;
+                    let line = self.input.line;
+                    orelse.push(Stmt { line, kind: self.parse_if()? });
+                }
+                Token::Else => {
+                    self.line_pending = false;
+                    let line = self.input.line;
+                    self.input.get_next_token(true);
+                    orelse = self.parse_block("'else' statement", line)?;
+                }
+                _ => (),
+            };
+        }
+        return Ok(StmtKind::If(test, body, orelse));
+    }
+
+    /* while_statement ::= while expr : block [else : block]
+     */
+    fn parse_while(&mut self) -> ParseResult<StmtKind> {
+        let indent = self.indent;
+        let line = self.input.line;
+        self.input.get_next_token(true); // consume "while"
+        let test = self.parse_expression()?;
+        self.loop_depth += 1;
+        let body = self.parse_block("'while' statement", line);
+        self.loop_depth -= 1;
+        let body = body?;
+        let mut orelse: Vec<Stmt> = Vec::new();
+        if let (true, Token::Else) = (self.indent == indent, &self.input.current) {
+            self.line_pending = false;
+            let line = self.input.line;
+            self.input.get_next_token(true);
+            orelse = self.parse_block("'else' statement", line)?;
+        }
+        return Ok(StmtKind::While(test, body, orelse));
+    }
+
+    /* for_statement ::= for target_list in expr_list : block [else : block]
+     */
+    fn parse_for(&mut self) -> ParseResult<StmtKind> {
+        let indent = self.indent;
+        let line = self.input.line;
+        self.input.get_next_token(true); // consume "for"
+        let target = self.parse_target_list()?;
+        self.expect(Token::In, "invalid syntax")?;
+        let iter = self.parse_expression_list()?;
+        self.loop_depth += 1;
+        let body = self.parse_block("'for' statement", line);
+        self.loop_depth -= 1;
+        let body = body?;
+        let mut orelse: Vec<Stmt> = Vec::new();
+        if let (true, Token::Else) = (self.indent == indent, &self.input.current) {
+            self.line_pending = false;
+            let line = self.input.line;
+            self.input.get_next_token(true);
+            orelse = self.parse_block("'else' statement", line)?;
+        }
+        return Ok(StmtKind::For(target, iter, body, orelse));
+    }
+
+    /* with_statement ::= with with_item (, with_item)* : block
+     * with_item ::= expr [as target]
+     */
+    fn parse_with(&mut self) -> ParseResult<StmtKind> {
+        let line = self.input.line;
+        self.input.get_next_token(true); // consume "with"
+        let mut items: Vec<(Expr, Option<Expr>)> = Vec::new();
         loop {
-            let token = self.input.get_next_token(true);
-            println!("{:?}", token);
+            let context = self.parse_expression()?;
+            let mut target = None;
+            if let Token::As = self.input.current {
+                self.input.get_next_token(true);
+                let expr = self.parse_sum()?;
+                Parser::check_target(&expr, "assign to")?;
+                target = Some(expr);
+            }
+            items.push((context, target));
+            if !matches!(self.input.current, Token::Comma) {
+                break;
+            }
+            self.input.get_next_token(true);
         }
+        let body = self.parse_block("'with' statement", line)?;
+        return Ok(StmtKind::With(items, body));
+    }
+
+    /* try_statement ::= try : block handler+ [else : block] [finally : block]
+     *                 | try : block finally : block
+     * handler ::= except [expr [as NAME]] : block
+     */
+    fn parse_try(&mut self) -> ParseResult<StmtKind> {
+        let indent = self.indent;
+        let line = self.input.line;
+        self.input.get_next_token(true); // consume "try"
+        let body = self.parse_block("'try' statement", line)?;
+        let mut handlers: Vec<Handler> = Vec::new();
+        while let (true, Token::Except) = (self.indent == indent, &self.input.current) {
+            if let Some(Handler { kind: None, line, .. }) = handlers.last() {
+                // reported on the bare except, not the one after it
+                let mut err = syntax_error("default 'except:' must be last");
+                err.line = Some(*line);
+                return Err(err);
+            }
+            self.line_pending = false;
+            let line = self.input.line;
+            self.input.get_next_token(true);
+            let (mut kind, mut name) = (None, None);
+            if !matches!(self.input.current, Token::Colon) {
+                kind = Some(self.parse_expression()?);
+                if let Token::Comma = self.input.current {
+                    return Err(syntax_error("multiple exception types must be parenthesized"));
+                }
+                if let Token::As = self.input.current {
+                    self.input.get_next_token(true);
+                    name = Some(self.parse_name()?);
+                    if !matches!(self.input.current, Token::Colon) {
+                        return Err(syntax_error("invalid syntax"));
+                    }
+                }
+            }
+            let body = self.parse_block("'except' statement", line)?;
+            handlers.push(Handler { kind, name, body, line });
+        }
+        let mut orelse: Vec<Stmt> = Vec::new();
+        if let (true, false, Token::Else) = (self.indent == indent, handlers.is_empty(), &self.input.current) {
+            self.line_pending = false;
+            let line = self.input.line;
+            self.input.get_next_token(true);
+            orelse = self.parse_block("'else' statement", line)?;
+        }
+        let mut finalbody: Vec<Stmt> = Vec::new();
+        if let (true, Token::Finally) = (self.indent == indent, &self.input.current) {
+            self.line_pending = false;
+            let line = self.input.line;
+            self.input.get_next_token(true);
+            finalbody = self.parse_block("'finally' statement", line)?;
+        } else if handlers.is_empty() {
+            return Err(syntax_error("expected 'except' or 'finally' block"));
+        }
+        return Ok(StmtKind::Try(body, handlers, orelse, finalbody));
     }
-}
 
+    /* target_list ::= target (, target)* [,]
+     *
+     * Parsed below the comparison level, so the `in` of a for statement
+     * isn't taken as an operator.
+     */
+    fn parse_target_list(&mut self) -> ParseResult<Expr> {
+        let first = self.parse_sum()?;
+        let target = if matches!(self.input.current, Token::Comma) {
+            let mut items = vec![first];
+            while let Token::Comma = self.input.current {
+                self.input.get_next_token(true);
+                if matches!(self.input.current, Token::In) {
+                    break;
+                }
+                items.push(self.parse_sum()?);
+            }
+            Expr::Tuple(items)
+        } else {
+            first
+        };
+        Parser::check_target(&target, "assign to")?;
+        return Ok(target);
+    }
+
+    /* decorated ::= (@ expr NEWLINE)+ (def_statement | class_statement)
+     */
+    fn parse_decorated(&mut self) -> ParseResult<StmtKind> {
+        let indent = self.indent;
+        let mut decorators: Vec<Expr> = Vec::new();
+        while let Token::At = self.input.current {
+            self.input.get_next_token(true);
+            decorators.push(self.parse_expression()?);
+            if !matches!(self.input.current, Token::NewLine) {
+                return Err(syntax_error("invalid syntax"));
+            }
+            self.start_line();
+            if self.indent > indent {
+                return Err(Exception::new("IndentationError", "unexpected indent"));
+            }
+            if self.indent < indent && !self.indents.contains(&self.indent) {
+                let message = "unindent does not match any outer indentation level";
+                return Err(Exception::new("IndentationError", message));
+            }
+            if self.indent < indent {
+                return Err(syntax_error("invalid syntax"));
+            }
+        }
+        // the def or class reads its own lines from here
+        self.line_pending = false;
+        match self.input.current {
+            Token::Def => return self.parse_def(decorators),
+            Token::Class => return self.parse_class(decorators),
+            _ => return Err(syntax_error("invalid syntax")),
+        }
+    }
+
+    /* def_statement ::= def NAME ( [params] ) : block
+     */
+    fn parse_def(&mut self, decorators: Vec<Expr>) -> ParseResult<StmtKind> {
+        let line = self.input.line;
+        self.input.get_next_token(true); // consume "def"
+        let name = self.parse_name()?;
+        self.expect(Token::OpenParen, "invalid syntax")?;
+        let params = self.parse_params(false)?;
+        self.expect_close(Token::CloseParen, "(", false)?;
+
+        // break/continue can't reach a loop outside the function
+        let loop_depth = std::mem::replace(&mut self.loop_depth, 0);
+        self.func_depth += 1;
+        let body = self.parse_block("function definition", line);
+        self.func_depth -= 1;
+        self.loop_depth = loop_depth;
+        let def = FunctionDef {
+            decorators,
+            ..FunctionDef::build_new(name, params, body?)
+        };
+        return Ok(StmtKind::FunctionDef(Rc::new(def)));
+    }
+
+    /* class_statement ::= class NAME [( [args] )] : block
+     */
+    fn parse_class(&mut self, decorators: Vec<Expr>) -> ParseResult<StmtKind> {
+        let line = self.input.line;
+        self.input.get_next_token(true); // consume "class"
+        let name = self.parse_name()?;
+        let mut bases: Vec<Arg> = Vec::new();
+        if let Token::OpenParen = self.input.current {
+            self.input.get_next_token(true);
+            bases = self.parse_args()?;
+        }
+        // the body is neither in a loop nor in a function, even inside one
+        let loop_depth = std::mem::replace(&mut self.loop_depth, 0);
+        let func_depth = std::mem::replace(&mut self.func_depth, 0);
+        let body = self.parse_block("class definition", line);
+        self.func_depth = func_depth;
+        self.loop_depth = loop_depth;
+        return Ok(StmtKind::ClassDef(Rc::new(ClassDef {
+            name,
+            bases,
+            body: body?,
+            decorators,
+        })));
+    }
+
+    /* params ::= param (, param)* [,]
+     * param ::= NAME [= expr] | * [NAME] | ** NAME
+     *
+     * Parameters after * are keyword-only. Lambda parameters end at the
+     * colon instead of a closing parenthesis.
+     */
+    fn parse_params(&mut self, lambda: bool) -> ParseResult<Params> {
+        let mut params = Params::default();
+        let mut star = false;
+        let mut seen_default = false;
+        loop {
+            match (&self.input.current, lambda) {
+                (Token::CloseParen, false) | (Token::Colon, true) => break,
+                _ if params.kwarg.is_some() => {
+                    return Err(syntax_error("arguments cannot follow var-keyword argument"));
+                }
+                (Token::Multiply, _) => {
+                    if star {
+                        return Err(syntax_error("* argument may appear only once"));
+                    }
+                    star = true;
+                    self.input.get_next_token(true);
+                    if let Token::Variable(_) = self.input.current {
+                        params.vararg = Some(self.parse_name()?);
+                    }
+                }
+                (Token::Exponent, _) => {
+                    self.input.get_next_token(true);
+                    params.kwarg = Some(self.parse_name()?);
+                }
+                _ => {
+                    let name = self.parse_name()?;
+                    let mut default: Option<Expr> = None;
+                    if let Token::Equals = self.input.current {
+                        self.input.get_next_token(true);
+                        default = Some(self.parse_expression()?);
+                    }
+                    if star {
+                        params.kwonly.push((name, default));
+                    } else {
+                        if default.is_none() && seen_default {
+                            return Err(syntax_error("parameter without a default follows parameter with a default"));
+                        }
+                        seen_default |= default.is_some();
+                        params.args.push((name, default));
+                    }
+                }
+            };
+            if !matches!(self.input.current, Token::Comma) {
+                break;
+            }
+            self.input.get_next_token(true);
+        }
+        if star && params.vararg.is_none() && params.kwonly.is_empty() {
+            return Err(syntax_error("named arguments must follow bare *"));
+        }
+        let mut seen: HashSet<&String> = HashSet::new();
+        for name in params.names() {
+            if !seen.insert(name) {
+                return Err(syntax_error(&format!("duplicate argument '{}' in function definition", name)));
+            }
+        }
+        return Ok(params);
+    }
+
+    /* block ::= : simple_statements NEWLINE | : NEWLINE INDENT statement+ DEDENT
+     *
+     * header (e.g. "'if' statement") and line describe the statement the
+     * block belongs to, for errors. Returns with line_pending set: the
+     * current token starts the first line after the block.
+     */
+    fn parse_block(&mut self, header: &str, line: usize) -> ParseResult<Vec<Stmt>> {
+        self.expect(Token::Colon, "expected ':'")?;
+        let outer_indent = self.indent;
+        self.depth += 1;
+        let block = self.parse_block_body(outer_indent, header, line);
+        if block.is_ok() && !self.line_pending {
+            self.start_line();
+        }
+        self.depth -= 1;
+        return block;
+    }
+
+    fn parse_block_body(&mut self, outer_indent: usize, header: &str, line: usize) -> ParseResult<Vec<Stmt>> {
+        // body on the same line as the colon
+        if !matches!(self.input.current, Token::NewLine) {
+            return self.parse_statement();
+        }
+
+        self.start_line();
+        let block_indent = self.indent;
+        if block_indent <= outer_indent || matches!(self.input.current, Token::Exit) {
+            let message = format!("expected an indented block after {} on line {}", header, line);
+            return Err(Exception::new("IndentationError", &message));
+        }
+        self.indents.push(outer_indent);
+        let block = self.parse_indented(block_indent);
+        self.indents.pop();
+        return block;
+    }
+
+    fn parse_indented(&mut self, block_indent: usize) -> ParseResult<Vec<Stmt>> {
+        let mut block: Vec<Stmt> = Vec::new();
+        loop {
+            self.line_pending = false;
+            block.extend(self.parse_statement()?);
+            if !self.line_pending {
+                self.start_line();
+            }
+            if matches!(self.input.current, Token::Exit | Token::NewLine) {
+                return Ok(block);
+            }
+            if self.indent < block_indent {
+                // a dedent has to go back to an enclosing block's level
+                if !self.indents.contains(&self.indent) {
+                    let message = "unindent does not match any outer indentation level";
+                    return Err(Exception::new("IndentationError", message));
+                }
+                return Ok(block);
+            }
+            if self.indent > block_indent {
+                return Err(Exception::new("IndentationError", "unexpected indent"));
+            }
+        }
+    }
+
+    /* expr_list ::= expr | expr , | expr , expr_list
+     *
+     * A bare comma-separated list is a tuple, e.g. `x, y = y, x`
+     */
+    fn parse_expression_list(&mut self) -> ParseResult<Expr> {
+        let first = self.parse_expression()?;
+        return self.parse_expression_list_rest(first);
+    }
+
+    // the rest of an expression list whose first item has been parsed
+    fn parse_expression_list_rest(&mut self, first: Expr) -> ParseResult<Expr> {
+        if !matches!(self.input.current, Token::Comma) {
+            return Ok(first);
+        }
+        let mut items = vec![first];
+        while let Token::Comma = self.input.current {
+            self.input.get_next_token(true);
+            if self.at_expression_end() {
+                break;
+            }
+            items.push(self.parse_expression()?);
+        }
+        return Ok(Expr::Tuple(items));
+    }
+
+    fn at_expression_end(&self) -> bool {
+        return matches!(
+            self.input.current,
+            Token::NewLine | Token::Exit | Token::Equals | Token::CloseParen | Token::CloseBracket | Token::Semicolon
+        );
+    }
+
+    /* expr ::= disjunction [if disjunction else expr] | lambda
+     */
+    fn parse_expression(&mut self) -> ParseResult<Expr> {
+        return self.parse_nested(Parser::parse_expression_body);
+    }
+
+    /* Parse an expression inside another one
+     */
+    fn parse_nested(&mut self, parse: fn(&mut Parser) -> ParseResult<Expr>) -> ParseResult<Expr> {
+        if self.nesting >= MAX_NESTING {
+            return Err(Exception::new("RecursionError", "maximum recursion depth exceeded during compilation"));
+        }
+        self.nesting += 1;
+        let result = limits::with_stack(|| parse(self));
+        self.nesting -= 1;
+        return result;
+    }
+
+    fn parse_expression_body(&mut self) -> ParseResult<Expr> {
+        if let Token::Lambda = self.input.current {
+            return self.parse_lambda();
+        }
+        let expr = self.parse_disjunction()?;
+        if let Token::If = self.input.current {
+            self.input.get_next_token(true);
+            let test = self.parse_disjunction()?;
+            self.expect(Token::Else, "expected 'else' after 'if' expression")?;
+            let orelse = self.parse_expression()?;
+            return Ok(Expr::IfExp(Box::new(test), Box::new(expr), Box::new(orelse)));
+        }
+        return Ok(expr);
+    }
+
+    /* lambda ::= lambda [params] : expr
+     */
+    fn parse_lambda(&mut self) -> ParseResult<Expr> {
+        self.input.get_next_token(true); // consume "lambda"
+        let params = self.parse_params(true)?;
+        self.expect(Token::Colon, "invalid syntax")?;
+        let line = self.input.line;
+        let body = vec![Stmt { line, kind: StmtKind::Return(Some(self.parse_expression()?)) }];
+        let name = String::from("<lambda>");
+        return Ok(Expr::Lambda(Rc::new(FunctionDef::build_new(name, params, body))));
+    }
+
+    /* disjunction ::= conjunction | disjunction or conjunction
+     */
+    fn parse_disjunction(&mut self) -> ParseResult<Expr> {
+        let mut expr = self.parse_conjunction()?;
+        while let Token::Or = self.input.current {
+            self.input.get_next_token(true);
+            let right = self.parse_conjunction()?;
+            expr = Expr::BoolOp(Box::new(expr), BoolOp::Or, Box::new(right));
+        }
+        return Ok(expr);
+    }
+
+    /* conjunction ::= inversion | conjunction and inversion
+     */
+    fn parse_conjunction(&mut self) -> ParseResult<Expr> {
+        let mut expr = self.parse_inversion()?;
+        while let Token::And = self.input.current {
+            self.input.get_next_token(true);
+            let right = self.parse_inversion()?;
+            expr = Expr::BoolOp(Box::new(expr), BoolOp::And, Box::new(right));
+        }
+        return Ok(expr);
+    }
+
+    /* inversion ::= not inversion | comparison
+     */
+    fn parse_inversion(&mut self) -> ParseResult<Expr> {
+        if let Token::Not = self.input.current {
+            self.input.get_next_token(true);
+            let operand = self.parse_inversion()?;
+            return Ok(Expr::UnaryOp(UnaryOp::Not, Box::new(operand)));
+        }
+        return self.parse_comparison();
+    }
+
+    /* comparison ::= bitwise_or | comparison comp_op bitwise_or
+     * comp_op ::= == | != | < | <= | > | >= | is | is not | in | not in
+     */
+    fn parse_comparison(&mut self) -> ParseResult<Expr> {
+        let first = self.parse_bitwise_or()?;
+        let mut rest: Vec<(CmpOp, Expr)> = Vec::new();
+        loop {
+            let op = match self.input.current {
+                Token::DoubleEquals => CmpOp::Eq,
+                Token::NotEquals => CmpOp::NotEq,
+                Token::Less => CmpOp::Lt,
+                Token::LessEquals => CmpOp::LtE,
+                Token::Greater => CmpOp::Gt,
+                Token::GreaterEquals => CmpOp::GtE,
+                Token::Is => CmpOp::Is,
+                Token::In => CmpOp::In,
+                Token::Not => CmpOp::NotIn,
+                _ => break,
+            };
+            self.input.get_next_token(true);
+            let op = match (op, &self.input.current) {
+                (CmpOp::Is, Token::Not) => {
+                    self.input.get_next_token(true);
+                    CmpOp::IsNot
+                }
+                (CmpOp::NotIn, Token::In) => {
+                    self.input.get_next_token(true);
+                    CmpOp::NotIn
+                }
+                (CmpOp::NotIn, _) => return Err(syntax_error("invalid syntax")),
+                _ => op,
+            };
+            rest.push((op, self.parse_bitwise_or()?));
+        }
+        if rest.is_empty() {
+            return Ok(first);
+        }
+        return Ok(Expr::Compare(Box::new(first), rest));
+    }
+
+    /* bitwise_or ::= bitwise_xor | bitwise_or "|" bitwise_xor
+     * bitwise_xor ::= bitwise_and | bitwise_xor ^ bitwise_and
+     * bitwise_and ::= shift | bitwise_and & shift
+     */
+    fn parse_bitwise_or(&mut self) -> ParseResult<Expr> {
+        let mut expr = self.parse_bitwise_xor()?;
+        while let Token::Pipe = self.input.current {
+            self.input.get_next_token(true);
+            let right = self.parse_bitwise_xor()?;
+            expr = Expr::BinOp(Box::new(expr), BinOp::BitOr, Box::new(right));
+        }
+        return Ok(expr);
+    }
+
+    fn parse_bitwise_xor(&mut self) -> ParseResult<Expr> {
+        let mut expr = self.parse_bitwise_and()?;
+        while let Token::Caret = self.input.current {
+            self.input.get_next_token(true);
+            let right = self.parse_bitwise_and()?;
+            expr = Expr::BinOp(Box::new(expr), BinOp::BitXor, Box::new(right));
+        }
+        return Ok(expr);
+    }
+
+    fn parse_bitwise_and(&mut self) -> ParseResult<Expr> {
+        let mut expr = self.parse_shift()?;
+        while let Token::Ampersand = self.input.current {
+            self.input.get_next_token(true);
+            let right = self.parse_shift()?;
+            expr = Expr::BinOp(Box::new(expr), BinOp::BitAnd, Box::new(right));
+        }
+        return Ok(expr);
+    }
+
+    /* shift ::= sum | shift << sum | shift >> sum
+     */
+    fn parse_shift(&mut self) -> ParseResult<Expr> {
+        let mut expr = self.parse_sum()?;
+        loop {
+            let op = match self.input.current {
+                Token::LeftShift => BinOp::LShift,
+                Token::RightShift => BinOp::RShift,
+                _ => break,
+            };
+            self.input.get_next_token(true);
+            let right = self.parse_sum()?;
+            expr = Expr::BinOp(Box::new(expr), op, Box::new(right));
+        }
+        return Ok(expr);
+    }
+
+    /* sum ::= term | sum + term | sum - term
+     */
+    fn parse_sum(&mut self) -> ParseResult<Expr> {
+        let mut expr = self.parse_term()?;
+        loop {
+            let op = match self.input.current {
+                Token::Plus => BinOp::Add,
+                Token::Minus => BinOp::Sub,
+                _ => break,
+            };
+            self.input.get_next_token(true);
+            let right = self.parse_term()?;
+            expr = Expr::BinOp(Box::new(expr), op, Box::new(right));
+        }
+        return Ok(expr);
+    }
+
+    /* term ::= unary | term * unary | term / unary | term // unary | term % unary
+     */
+    fn parse_term(&mut self) -> ParseResult<Expr> {
+        let mut expr = self.parse_unary()?;
+        loop {
+            let op = match self.input.current {
+                Token::Multiply => BinOp::Mul,
+                Token::Divide => BinOp::Div,
+                Token::FloorDivide => BinOp::FloorDiv,
+                Token::Modulo => BinOp::Mod,
+                _ => break,
+            };
+            self.input.get_next_token(true);
+            let right = self.parse_unary()?;
+            expr = Expr::BinOp(Box::new(expr), op, Box::new(right));
+        }
+        return Ok(expr);
+    }
+
+    /* unary ::= - unary | + unary | ~ unary | power
+     */
+    fn parse_unary(&mut self) -> ParseResult<Expr> {
+        let op = match self.input.current {
+            Token::Minus => UnaryOp::Neg,
+            Token::Plus => UnaryOp::Pos,
+            Token::Tilde => UnaryOp::Invert,
+            _ => return self.parse_power(),
+        };
+        self.input.get_next_token(true);
+        let operand = self.parse_nested(Parser::parse_unary)?;
+        return Ok(Expr::UnaryOp(op, Box::new(operand)));
+    }
+
+    /* power ::= primary | primary ** unary
+     */
+    fn parse_power(&mut self) -> ParseResult<Expr> {
+        let factor = self.parse_primary()?;
+        if let Token::Exponent = self.input.current {
+            self.input.get_next_token(true); // consume operator
+            let power = self.parse_unary()?;
+            return Ok(Expr::BinOp(Box::new(factor), BinOp::Pow, Box::new(power)));
+        }
+        return Ok(factor);
+    }
+
+    /* primary ::= factor | primary . NAME | primary ( [args] ) | primary [ subscript ]
+     */
+    fn parse_primary(&mut self) -> ParseResult<Expr> {
+        let mut expr = self.parse_factor()?;
+        loop {
+            match self.input.current {
+                Token::Dot => {
+                    self.input.get_next_token(true);
+                    let attr = self.parse_name()?;
+                    expr = Expr::Attribute(Box::new(expr), attr);
+                }
+                Token::OpenParen => {
+                    self.input.get_next_token(true);
+                    let args = self.parse_args()?;
+                    expr = Expr::Call(Box::new(expr), args);
+                }
+                Token::OpenBracket => {
+                    self.input.get_next_token(true);
+                    let index = self.parse_subscript()?;
+                    expr = Expr::Subscript(Box::new(expr), Box::new(index));
+                }
+                _ => return Ok(expr),
+            };
+        }
+    }
+
+    /* args ::= arg (, arg)* [,]
+     * arg ::= expr | NAME = expr | * expr | ** expr
+     *       | expr comprehension_for+ (the only argument)
+     */
+    fn parse_args(&mut self) -> ParseResult<Vec<Arg>> {
+        let mut args: Vec<Arg> = Vec::new();
+        let mut keywords: HashSet<String> = HashSet::new();
+        let mut double_star = false;
+        while !matches!(self.input.current, Token::CloseParen) {
+            let arg = match self.input.current {
+                Token::Multiply => {
+                    self.input.get_next_token(true);
+                    if double_star {
+                        return Err(syntax_error("iterable argument unpacking follows keyword argument unpacking"));
+                    }
+                    Arg::Star(self.parse_expression()?)
+                }
+                Token::Exponent => {
+                    self.input.get_next_token(true);
+                    double_star = true;
+                    Arg::DoubleStar(self.parse_expression()?)
+                }
+                _ => {
+                    let expr = self.parse_expression()?;
+                    if let Token::Equals = self.input.current {
+                        let name = match expr {
+                            Expr::Name(name) => name,
+                            Expr::NoneT | Expr::Bool(_) => {
+                                return Err(syntax_error(&format!("cannot assign to {}", Parser::describe(&expr))));
+                            }
+                            _ => {
+                                return Err(syntax_error(
+                                    "expression cannot contain assignment, perhaps you meant \"==\"?",
+                                ));
+                            }
+                        };
+                        self.input.get_next_token(true);
+                        if !keywords.insert(name.clone()) {
+                            return Err(syntax_error(&format!("keyword argument repeated: {}", name)));
+                        }
+                        Arg::Keyword(name, self.parse_expression()?)
+                    } else if let Token::For = self.input.current {
+                        // f(x for x in y) needs no parentheses of its own,
+                        // if it's the only argument
+                        let generator = self.parse_comprehension(ComprehensionKind::Generator, expr, None)?;
+                        if !args.is_empty() || !matches!(self.input.current, Token::CloseParen) {
+                            return Err(syntax_error("Generator expression must be parenthesized"));
+                        }
+                        Arg::Positional(generator)
+                    } else if double_star {
+                        return Err(syntax_error("positional argument follows keyword argument unpacking"));
+                    } else if !keywords.is_empty() {
+                        return Err(syntax_error("positional argument follows keyword argument"));
+                    } else {
+                        Arg::Positional(expr)
+                    }
+                }
+            };
+            args.push(arg);
+            if !matches!(self.input.current, Token::Comma) {
+                break;
+            }
+            self.input.get_next_token(true);
+        }
+        self.expect_close(Token::CloseParen, "(", true)?;
+        return Ok(args);
+    }
+
+    /* subscript ::= slice | slice , ... [,]
+     */
+    fn parse_subscript(&mut self) -> ParseResult<Expr> {
+        let mut items = vec![self.parse_slice()?];
+        let mut tuple = false;
+        while let Token::Comma = self.input.current {
+            tuple = true;
+            self.input.get_next_token(true);
+            if matches!(self.input.current, Token::CloseBracket) {
+                break;
+            }
+            items.push(self.parse_slice()?);
+        }
+        self.expect_close(Token::CloseBracket, "[", true)?;
+        if tuple {
+            return Ok(Expr::Tuple(items));
+        }
+        return Ok(items.pop().unwrap());
+    }
+
+    /* slice ::= expr | [expr] : [expr] [: [expr]]
+     */
+    fn parse_slice(&mut self) -> ParseResult<Expr> {
+        let lower = match self.input.current {
+            Token::Colon => None,
+            _ => {
+                let expr = self.parse_expression()?;
+                if !matches!(self.input.current, Token::Colon) {
+                    return Ok(expr);
+                }
+                Some(Box::new(expr))
+            }
+        };
+        self.input.get_next_token(true); // consume ":"
+        let mut bounds: Vec<Option<Box<Expr>>> = Vec::new();
+        for _ in 0..2 {
+            match self.input.current {
+                Token::Colon | Token::CloseBracket | Token::Comma => bounds.push(None),
+                _ => bounds.push(Some(Box::new(self.parse_expression()?))),
+            };
+            if bounds.len() == 2 || !matches!(self.input.current, Token::Colon) {
+                break;
+            }
+            self.input.get_next_token(true);
+        }
+        let step = if bounds.len() == 2 { bounds.pop().unwrap() } else { None };
+        let upper = bounds.pop().unwrap();
+        return Ok(Expr::Slice(lower, upper, step));
+    }
+
+    /* factor ::= var_ref | number | string | True | False | None | (expr)
+     *          | tuple | generator | list | dict | set
+     */
+    fn parse_factor(&mut self) -> ParseResult<Expr> {
+        let expr = match &self.input.current {
+            Token::OpenParen => return self.parse_parens(),
+            Token::OpenBracket => return self.parse_list(),
+            Token::OpenBrace => return self.parse_dict(),
+            Token::Int(val) => Expr::Int(*val),
+            Token::BigInt(val) => Expr::BigInt(val.clone()),
+            Token::Float(val) => Expr::Float(*val),
+            Token::Imaginary(val) => Expr::Imaginary(*val),
+            Token::Str(_) | Token::Bytes(_) => return self.parse_string(),
+            Token::True => Expr::Bool(true),
+            Token::False => Expr::Bool(false),
+            Token::NoneT => Expr::NoneT,
+            Token::Variable(name) => Expr::Name(name.clone()),
+            _ => return Err(syntax_error("invalid syntax")),
+        };
+        self.input.get_next_token(true);
+        return Ok(expr);
+    }
+
+    /* string ::= str | bytes | string str | string bytes  (adjacent literals
+     * are concatenated, and must all be str or all bytes)
+     */
+    fn parse_string(&mut self) -> ParseResult<Expr> {
+        let mut value = String::new();
+        let mut data: Vec<u8> = Vec::new();
+        let binary = matches!(self.input.current, Token::Bytes(_));
+        loop {
+            match &self.input.current {
+                Token::Str(val) if !binary => value.push_str(val),
+                Token::Bytes(val) if binary => data.extend_from_slice(val),
+                Token::Str(_) | Token::Bytes(_) => return Err(syntax_error("cannot mix bytes and nonbytes literals")),
+                _ => break,
+            };
+            self.input.get_next_token(true);
+        }
+        if binary {
+            return Ok(Expr::Bytes(data));
+        }
+        return Ok(Expr::Str(value));
+    }
+
+    /* tuple ::= ( ) | ( expr , ) | ( expr , expr_list )
+     * generator ::= ( expr comprehension_for+ )
+     */
+    fn parse_parens(&mut self) -> ParseResult<Expr> {
+        self.input.get_next_token(true); // consume "("
+        if let Token::CloseParen = self.input.current {
+            self.input.get_next_token(true);
+            return Ok(Expr::Tuple(Vec::new()));
+        }
+        let first = self.parse_expression()?;
+        let expr = match self.input.current {
+            Token::For => self.parse_comprehension(ComprehensionKind::Generator, first, None)?,
+            _ => self.parse_expression_list_rest(first)?,
+        };
+        self.expect_close(Token::CloseParen, "(", true)?;
+        return Ok(expr);
+    }
+
+    /* list ::= [ ] | [ expr (, expr)* [,] ] | [ expr comprehension_for+ ]
+     */
+    fn parse_list(&mut self) -> ParseResult<Expr> {
+        self.input.get_next_token(true); // consume "["
+        let mut items: Vec<Expr> = Vec::new();
+        while !matches!(self.input.current, Token::CloseBracket) {
+            items.push(self.parse_expression()?);
+            if let Token::For = self.input.current {
+                if items.len() > 1 {
+                    return Err(syntax_error("did you forget parentheses around the comprehension target?"));
+                }
+                let comp = self.parse_comprehension(ComprehensionKind::List, items.pop().unwrap(), None)?;
+                self.expect_close(Token::CloseBracket, "[", true)?;
+                return Ok(comp);
+            }
+            if !matches!(self.input.current, Token::Comma) {
+                break;
+            }
+            self.input.get_next_token(true);
+        }
+        self.expect_close(Token::CloseBracket, "[", true)?;
+        return Ok(Expr::List(items));
+    }
+
+    /* dict ::= { } | { expr : expr (, expr : expr)* [,] }
+     *        | { expr : expr comprehension_for+ }
+     * set ::= { expr (, expr)* [,] } | { expr comprehension_for+ }
+     *
+     * Which it is depends on whether the first item has a colon.
+     */
+    fn parse_dict(&mut self) -> ParseResult<Expr> {
+        self.input.get_next_token(true); // consume "{"
+        let mut items: Vec<(Expr, Expr)> = Vec::new();
+        while !matches!(self.input.current, Token::CloseBrace) {
+            let key = self.parse_expression()?;
+            if items.is_empty() && !matches!(self.input.current, Token::Colon) {
+                return self.parse_set(key);
+            }
+            self.expect(Token::Colon, "':' expected after dictionary key")?;
+            let value = self.parse_expression()?;
+            if let (Token::For, true) = (&self.input.current, items.is_empty()) {
+                let comp = self.parse_comprehension(ComprehensionKind::Dict, key, Some(value))?;
+                self.expect_close(Token::CloseBrace, "{", true)?;
+                return Ok(comp);
+            }
+            items.push((key, value));
+            if !matches!(self.input.current, Token::Comma) {
+                break;
+            }
+            self.input.get_next_token(true);
+        }
+        self.expect_close(Token::CloseBrace, "{", true)?;
+        return Ok(Expr::Dict(items));
+    }
+
+    /* the rest of a set display, whose first item is first
+     */
+    fn parse_set(&mut self, first: Expr) -> ParseResult<Expr> {
+        if let Token::For = self.input.current {
+            let comp = self.parse_comprehension(ComprehensionKind::Set, first, None)?;
+            self.expect_close(Token::CloseBrace, "{", true)?;
+            return Ok(comp);
+        }
+        let mut items = vec![first];
+        while let Token::Comma = self.input.current {
+            self.input.get_next_token(true);
+            if let Token::CloseBrace = self.input.current {
+                break;
+            }
+            items.push(self.parse_expression()?);
+        }
+        self.expect_close(Token::CloseBrace, "{", true)?;
+        return Ok(Expr::Set(items));
+    }
+
+    /* comprehension_for ::= for target_list in disjunction (if disjunction)*
+     *
+     * The clauses after the element of a comprehension, which is the
+     * current token's for; the closing bracket is left to the caller.
+     */
+    fn parse_comprehension(&mut self, kind: ComprehensionKind, element: Expr, value: Option<Expr>) -> ParseResult<Expr> {
+        let line = self.input.line;
+        let mut clauses: Vec<ComprehensionFor> = Vec::new();
+        while let Token::For = self.input.current {
+            self.input.get_next_token(true); // consume "for"
+            let target = self.parse_target_list()?;
+            self.expect(Token::In, "invalid syntax")?;
+            let iter = self.parse_disjunction()?;
+            let mut conditions: Vec<Expr> = Vec::new();
+            while let Token::If = self.input.current {
+                self.input.get_next_token(true);
+                conditions.push(self.parse_disjunction()?);
+            }
+            clauses.push(ComprehensionFor { target, iter, conditions });
+        }
+        let comp = Comprehension { kind, element, value, clauses, line };
+        return Ok(Expr::Comprehension(Rc::new(comp)));
+    }
+
+    /* for debugging/etc - simply repeats tokens back to user, 1 per line
+    */
+    #[allow(dead_code)]
+    fn repeat_tokens(&mut self) {
+        loop {
+            let token = self.input.get_next_token(true);
+            println!("{:?}", token);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Parser;
+    use crate::interpreter::Interpreter;
+    use crate::limits::Limits;
+    use crate::scanner;
+    use crate::testing::{eval, exec, python, traceback};
+
+    fn syntax_error(source: &str) -> String {
+        let mut python = python("");
+        return traceback(&mut python, source);
+    }
+
+    #[test]
+    fn test_traceback_lines() {
+        let mut python = python("def f(x):\n    y = 1\n    return x / 0\n");
+        let source = "a = 1\nf(a)\n";
+        // code run in __main__ has no __file__
+        let expected = "Traceback (most recent call last):\n  File \"<stdin>\", line 2, in <module>\n  File \"<stdin>\", line 3, in f\nZeroDivisionError: division by zero";
+        assert_eq!(traceback(&mut python, source), expected);
+    }
+
+    #[test]
+    fn test_prompt_commands() {
+        let command = |line: &str| {
+            let mut parser = Parser::build_new();
+            parser.input.stream = String::from(line);
+            parser.start_line();
+            return parser.prompt_command();
+        };
+        assert_eq!(command("exit\n"), Some("exit"));
+        assert_eq!(command("state\n"), Some("state"));
+        assert_eq!(command("state save session.py\n"), Some("state"));
+        assert_eq!(command("state load session.py\n"), Some("state"));
+        for line in ["exit = 3\n", "exit()\n", "state = {}\n", "state.x\n", "state in states\n", "  exit\n", "x = exit\n"] {
+            assert_eq!(command(line), None, "{}", line);
+        }
+
+        // scripts may use them as names
+        let mut python = python("exit = 3\nstate = {'a': exit}\nexit\n");
+        exec(&mut python, "state['b'] = 4\n");
+        assert_eq!(eval(&mut python, "(exit, state)"), "(3, {'a': 3, 'b': 4})");
+    }
+
+    #[test]
+    fn test_echo() {
+        // statements typed at the prompt, with sys.stdout redirected unless it is the real one
+        let run = |limits: Limits, setup: &str, source: &str| -> (String, String) {
+            let mut interpreter = Interpreter::build_new();
+            interpreter.limits = limits;
+            let code = interpreter.compile(setup, "<string>").unwrap();
+            interpreter.exec_module(interpreter.globals.clone(), &code).unwrap();
+            let stmts = Parser::build_from_tokens(scanner::tokenize(source).unwrap()).parse_module().unwrap();
+            let result = match Parser::run_statements(&mut interpreter, stmts) {
+                Ok(()) => String::from("ok"),
+                Err(err) => err.to_string(),
+            };
+            let echoed = Parser::build_from_tokens(scanner::tokenize("(out.getvalue(), _)").unwrap()).parse_eval().unwrap();
+            return (result, interpreter.eval(&echoed).map_or_else(|err| err.to_string(), |value| value.repr()));
+        };
+        let redirect = "import io, sys\nout = io.StringIO()\nsys.stdout = out\n";
+        let source = "1 + 2\nNone\nclass A:\n    def __repr__(self): return 'A!'\nA()\n'x'\n";
+        let expected = (String::from("ok"), String::from("(\"3\\nA!\\n'x'\\n\", 'x')"));
+        assert_eq!(run(Limits::default(), redirect, source), expected);
+        // None is neither echoed nor bound to _
+        let expected = (String::from("ok"), String::from("('4\\n', 4)"));
+        assert_eq!(run(Limits::default(), redirect, "4\nNone\n"), expected);
+        let lost = (String::from("RuntimeError: lost sys.stdout"), String::from("NameError: name '_' is not defined"));
+        assert_eq!(run(Limits::default(), "import io, sys\nout = io.StringIO()\nsys.stdout = None\n", "4\n"), lost);
+
+        let limits = Limits {
+            max_output: Some(3),
+            ..Limits::default()
+        };
+        let (result, _) = run(limits, "", "12\n345\n");
+        assert_eq!(result, "ResourceLimitError: output limit of 3 bytes exceeded");
+    }
+
+    #[test]
+    fn test_syntax_error_lines() {
+        assert_eq!(syntax_error("x = 1\ny = (1,\n"), "SyntaxError: '(' was never closed (<string>, line 2)");
+        assert_eq!(syntax_error("x = [1, 2)\n"), "SyntaxError: closing parenthesis ')' does not match opening parenthesis '[' (<string>, line 1)");
+        assert_eq!(syntax_error("x = 1\n)\n"), "SyntaxError: unmatched ')' (<string>, line 2)");
+        assert_eq!(syntax_error("s = 'abc\n"), "SyntaxError: unterminated string literal (detected at line 1) (<string>, line 1)");
+        assert_eq!(syntax_error("x = 1\nx = 2 3\n"), "SyntaxError: invalid syntax (<string>, line 2)");
+    }
+
+    #[test]
+    fn test_syntax_error_messages() {
+        assert_eq!(syntax_error("f() = 1\n"), "SyntaxError: cannot assign to function call here. Maybe you meant '==' instead of '='? (<string>, line 1)");
+        assert_eq!(syntax_error("(f(), 1) = 1\n"), "SyntaxError: cannot assign to function call (<string>, line 1)");
+        assert_eq!(syntax_error("print 1\n"), "SyntaxError: Missing parentheses in call to 'print'. Did you mean print(...)? (<string>, line 1)");
+        assert_eq!(syntax_error("break\n"), "SyntaxError: 'break' outside loop (<string>, line 1)");
+        assert_eq!(syntax_error("f(1 2)\n"), "SyntaxError: invalid syntax. Perhaps you forgot a comma? (<string>, line 1)");
+        assert_eq!(syntax_error("if x:\npass\n"), "IndentationError: expected an indented block after 'if' statement on line 1 (<string>, line 2)");
+        assert_eq!(syntax_error("if x:\n        a = 1\n    b = 2\n"), "IndentationError: unindent does not match any outer indentation level (<string>, line 3)");
+    }
+
+    #[test]
+    fn test_simple_statement_errors() {
+        assert_eq!(syntax_error("a = 1;; b\n"), "SyntaxError: invalid syntax (<string>, line 1)");
+        assert_eq!(syntax_error("x = 1 = 2\n"), "SyntaxError: cannot assign to literal (<string>, line 1)");
+        assert_eq!(syntax_error("x = f() = 2\n"), "SyntaxError: cannot assign to function call (<string>, line 1)");
+        assert_eq!(syntax_error("{1} = 2\n"), "SyntaxError: cannot assign to set display here. Maybe you meant '==' instead of '='? (<string>, line 1)");
+        assert_eq!(syntax_error("{x for x in y} += 1\n"), "SyntaxError: 'set comprehension' is an illegal expression for augmented assignment (<string>, line 1)");
+        assert_eq!(syntax_error("x = y += 1\n"), "SyntaxError: invalid syntax (<string>, line 1)");
+        assert_eq!(syntax_error("f() += 1\n"), "SyntaxError: 'function call' is an illegal expression for augmented assignment (<string>, line 1)");
+        assert_eq!(syntax_error("a, b += 1\n"), "SyntaxError: 'tuple' is an illegal expression for augmented assignment (<string>, line 1)");
+        assert_eq!(syntax_error("del (a, 1)\n"), "SyntaxError: cannot delete literal (<string>, line 1)");
+        assert_eq!(syntax_error("del x + 1\n"), "SyntaxError: cannot delete expression (<string>, line 1)");
+        assert_eq!(syntax_error("del\n"), "SyntaxError: invalid syntax (<string>, line 1)");
+    }
+
+    #[test]
+    fn test_compound_statement_errors() {
+        assert_eq!(syntax_error("if x\n    pass\n"), "SyntaxError: expected ':' (<string>, line 1)");
+        assert_eq!(syntax_error("if 1:\n    pass\nelif:\n    pass\n"), "SyntaxError: invalid syntax (<string>, line 3)");
+        assert_eq!(syntax_error("else:\n    pass\n"), "SyntaxError: invalid syntax (<string>, line 1)");
+        assert_eq!(syntax_error("while 1:\npass\n"), "IndentationError: expected an indented block after 'while' statement on line 1 (<string>, line 2)");
+        assert_eq!(syntax_error("for x 1:\n    pass\n"), "SyntaxError: invalid syntax (<string>, line 1)");
+        assert_eq!(syntax_error("for 1 in x:\n    pass\n"), "SyntaxError: cannot assign to literal (<string>, line 1)");
+        assert_eq!(syntax_error("with open(x) as 1:\n    pass\n"), "SyntaxError: cannot assign to literal (<string>, line 1)");
+        assert_eq!(syntax_error("continue\n"), "SyntaxError: 'continue' not properly in loop (<string>, line 1)");
+    }
+
+    #[test]
+    fn test_def_errors() {
+        assert_eq!(syntax_error("def (x):\n    pass\n"), "SyntaxError: invalid syntax (<string>, line 1)");
+        assert_eq!(syntax_error("def f(a=1, b):\n    pass\n"), "SyntaxError: parameter without a default follows parameter with a default (<string>, line 1)");
+        assert_eq!(syntax_error("def f(a, a):\n    pass\n"), "SyntaxError: duplicate argument 'a' in function definition (<string>, line 1)");
+        assert_eq!(syntax_error("def f(*):\n    pass\n"), "SyntaxError: named arguments must follow bare * (<string>, line 1)");
+        assert_eq!(syntax_error("def f(**k, a):\n    pass\n"), "SyntaxError: arguments cannot follow var-keyword argument (<string>, line 1)");
+        assert_eq!(syntax_error("nonlocal x\n"), "SyntaxError: nonlocal declaration not allowed at module level (<string>, line 1)");
+        assert_eq!(syntax_error("global x = 1\n"), "SyntaxError: invalid syntax (<string>, line 1)");
+    }
+
+    #[test]
+    fn test_import_errors() {
+        assert_eq!(syntax_error("import\n"), "SyntaxError: invalid syntax (<string>, line 1)");
+        assert_eq!(syntax_error("import a.\n"), "SyntaxError: invalid syntax (<string>, line 1)");
+        assert_eq!(syntax_error("from x import\n"), "SyntaxError: invalid syntax (<string>, line 1)");
+        assert_eq!(syntax_error("assert\n"), "SyntaxError: invalid syntax (<string>, line 1)");
+    }
+
+    #[test]
+    fn test_comprehension_errors() {
+        assert_eq!(syntax_error("f(x for x in y, 1)\n"), "SyntaxError: Generator expression must be parenthesized (<string>, line 1)");
+        assert_eq!(syntax_error("f(1, x for x in y)\n"), "SyntaxError: Generator expression must be parenthesized (<string>, line 1)");
+        assert_eq!(syntax_error("[x, y for x in z]\n"), "SyntaxError: did you forget parentheses around the comprehension target? (<string>, line 1)");
+        assert_eq!(syntax_error("[x for 1 in y]\n"), "SyntaxError: cannot assign to literal (<string>, line 1)");
+        assert_eq!(syntax_error("[x for x in a if b else c]\n"), "SyntaxError: invalid syntax (<string>, line 1)");
+        assert_eq!(syntax_error("(x for x in y) = 1\n"), "SyntaxError: cannot assign to generator expression (<string>, line 1)");
+    }
+
+    #[test]
+    fn test_try_errors() {
+        assert_eq!(syntax_error("try:\n    pass\nx = 1\n"), "SyntaxError: expected 'except' or 'finally' block (<string>, line 3)");
+        assert_eq!(syntax_error("try:\n    pass\nexcept:\n    pass\nexcept ValueError:\n    pass\n"), "SyntaxError: default 'except:' must be last (<string>, line 3)");
+        assert_eq!(syntax_error("try:\n    pass\nexcept ValueError, TypeError:\n    pass\n"), "SyntaxError: multiple exception types must be parenthesized (<string>, line 3)");
+        assert_eq!(syntax_error("try:\n    pass\nexcept ValueError as e.x:\n    pass\n"), "SyntaxError: invalid syntax (<string>, line 3)");
+        assert_eq!(syntax_error("try:\nx = 1\n"), "IndentationError: expected an indented block after 'try' statement on line 1 (<string>, line 2)");
+        assert_eq!(syntax_error("try:\n    pass\nfinally:\npass\n"), "IndentationError: expected an indented block after 'finally' statement on line 3 (<string>, line 4)");
+        assert_eq!(syntax_error("raise ValueError from\n"), "SyntaxError: invalid syntax (<string>, line 1)");
+    }
+
+    #[test]
+    fn test_class_errors() {
+        assert_eq!(syntax_error("class:\n    pass\n"), "SyntaxError: invalid syntax (<string>, line 1)");
+        assert_eq!(syntax_error("class C\n    pass\n"), "SyntaxError: expected ':' (<string>, line 1)");
+        assert_eq!(syntax_error("class C:\nx = 1\n"), "IndentationError: expected an indented block after class definition on line 1 (<string>, line 2)");
+        assert_eq!(syntax_error("class C:\n    return 1\n"), "SyntaxError: 'return' outside function (<string>, line 2)");
+        assert_eq!(syntax_error("while 1:\n    class C:\n        break\n"), "SyntaxError: 'break' outside loop (<string>, line 3)");
+    }
+
+    #[test]
+    fn test_decorator_errors() {
+        assert_eq!(syntax_error("@d\nx = 1\n"), "SyntaxError: invalid syntax (<string>, line 2)");
+        assert_eq!(syntax_error("@d x\ndef f(): pass\n"), "SyntaxError: invalid syntax (<string>, line 1)");
+        assert_eq!(syntax_error("@\ndef f(): pass\n"), "SyntaxError: invalid syntax (<string>, line 1)");
+        assert_eq!(syntax_error("if 1:\n    @d\n        def f(): pass\n"), "IndentationError: unexpected indent (<string>, line 3)");
+    }
+
+    #[test]
+    fn test_int_literal_errors() {
+        assert_eq!(syntax_error("x = 09\n"), "SyntaxError: leading zeros in decimal integer literals are not permitted; use an 0o prefix for octal integers (<string>, line 1)");
+    }
+
+    #[test]
+    fn test_nesting_limit() {
+        let message = "RecursionError: maximum recursion depth exceeded during compilation";
+        assert_eq!(syntax_error(&format!("x = {}1\n", "-".repeat(3000))), message);
+        assert_eq!(syntax_error(&format!("x = {}1{}\n", "(".repeat(3000), ")".repeat(3000))), message);
+    }
+}
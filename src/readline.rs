@@ -0,0 +1,285 @@
+use crate::interpreter::{get_attribute, Interpreter};
+use crate::scanner::KEYWORDS;
+use crate::value::{Class, Module, Value};
+use rustyline::completion::{Completer, Pair};
+use rustyline::config::{CompletionType, Config};
+use rustyline::error::ReadlineError;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::history::DefaultHistory;
+use rustyline::validate::Validator;
+use rustyline::{Context, Editor, Helper};
+use std::collections::{BTreeSet, HashMap};
+use std::env;
+use std::fmt;
+use std::io::{stdin, stdout, ErrorKind, IsTerminal, Write};
+use std::path::PathBuf;
+use std::rc::Rc;
+
+const HISTORY_FILE: &str = ".rebar_history";
+
+/* Where the REPL's lines come from. At a terminal, lines are read with
+ * rustyline: arrow-key editing, Ctrl-R reverse search, history kept in
+ * ~/.rebar_history and tab completion. Otherwise (piped input, or a
+ * terminal rustyline can't drive) plain lines are read from stdin.
+ */
+pub enum LineReader {
+    Plain,
+    Editor(Box<Editor<ReplHelper, DefaultHistory>>, Option<PathBuf>),
+}
+
+pub enum Line {
+    Text(String),
+    // Ctrl-C
+    Interrupted,
+    Eof,
+}
+
+impl LineReader {
+    pub fn build_new(interpreter: &Interpreter) -> LineReader {
+        if !stdin().is_terminal() {
+            return LineReader::Plain;
+        }
+        let config = Config::builder()
+            .completion_type(CompletionType::List)
+            .auto_add_history(false)
+            .build();
+        let mut editor: Editor<ReplHelper, DefaultHistory> = match Editor::with_config(config) {
+            Ok(editor) => editor,
+            Err(_) => return LineReader::Plain,
+        };
+        editor.set_helper(Some(ReplHelper {
+            globals: interpreter.globals.clone(),
+            builtins: interpreter.builtins.clone(),
+            types: interpreter.types.clone(),
+        }));
+        let history = env::var_os("HOME").map(|home| PathBuf::from(home).join(HISTORY_FILE));
+        if let Some(path) = &history {
+            // there is no history file the first time
+            let _ = editor.load_history(path);
+        }
+        return LineReader::Editor(Box::new(editor), history);
+    }
+
+    /* Read one line, including its newline. A line that isn't valid UTF-8
+     * is reported and skipped; any other read error ends the input.
+     */
+    pub fn read_line(&mut self, prompt: &str) -> Line {
+        match self {
+            LineReader::Plain => loop {
+                print!("{}", prompt);
+                stdout().flush().expect("Could not flush stdout");
+                let mut line = String::new();
+                match stdin().read_line(&mut line) {
+                    Ok(0) => return Line::Eof,
+                    Ok(_) => return Line::Text(line),
+                    // the bad line has been consumed, so carry on with the next
+                    Err(err) if err.kind() == ErrorKind::InvalidData => eprintln!("Failed to read line: {}", err),
+                    Err(err) => {
+                        eprintln!("Failed to read line: {}", err);
+                        return Line::Eof;
+                    }
+                }
+            },
+            LineReader::Editor(editor, history) => match editor.readline(prompt) {
+                Ok(line) => {
+                    if !line.trim().is_empty() {
+                        let _ = editor.add_history_entry(line.as_str());
+                        if let Some(path) = history {
+                            // saved as we go, so history survives a crash
+                            let _ = editor.append_history(path);
+                        }
+                    }
+                    return Line::Text(line + "\n");
+                }
+                Err(ReadlineError::Interrupted) => return Line::Interrupted,
+                Err(ReadlineError::Eof) => return Line::Eof,
+                Err(err) => {
+                    eprintln!("Failed to read line: {}", err);
+                    return Line::Eof;
+                }
+            },
+        }
+    }
+}
+
+impl fmt::Debug for LineReader {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LineReader::Plain => return write!(f, "LineReader::Plain"),
+            LineReader::Editor(..) => return write!(f, "LineReader::Editor"),
+        }
+    }
+}
+
+/* Tab completion in the style of rlcompleter: keywords, builtins and
+ * global names, or attributes after a dotted name. Nothing is called to
+ * work out a completion, only names and attributes are looked up.
+ */
+pub struct ReplHelper {
+    globals: Rc<Module>,
+    builtins: Rc<Module>,
+    types: HashMap<String, Rc<Class>>,
+}
+
+impl ReplHelper {
+    /* the value of a dotted name like `os.path`, if it exists
+     */
+    fn resolve(&self, expr: &str) -> Option<Value> {
+        let mut parts = expr.split('.');
+        let first = parts.next()?;
+        let mut value = self.globals.get(first).or_else(|| self.builtins.get(first))?;
+        for part in parts {
+            value = get_attribute(&value, part).ok()?;
+        }
+        return Some(value);
+    }
+
+    fn attribute_names(&self, value: &Value) -> BTreeSet<String> {
+        let mut names: BTreeSet<String> = BTreeSet::new();
+        let class = match value {
+            Value::Module(module) => {
+                names.extend(module.vars.borrow().keys().cloned());
+                return names;
+            }
+            Value::Class(class) => {
+                names.insert(String::from("__name__"));
+                class.clone()
+            }
+            Value::Object(object) => {
+                names.extend(object.attrs.borrow().keys().cloned());
+                object.class.clone()
+            }
+            _ => match self.types.get(value.type_name()) {
+                Some(class) => class.clone(),
+                None => return names,
+            },
+        };
+        let mut classes = vec![class];
+        while let Some(class) = classes.pop() {
+            names.extend(class.attrs.borrow().keys().cloned());
+            classes.extend(class.bases.iter().cloned());
+        }
+        return names;
+    }
+
+    fn completions(&self, word: &str) -> Vec<Pair> {
+        let mut matches: Vec<(String, Option<Value>)> = Vec::new();
+        match word.rfind('.') {
+            Some(dot) => {
+                let (expr, prefix) = (&word[..dot], &word[dot + 1..]);
+                let object = match self.resolve(expr) {
+                    Some(object) => object,
+                    None => return Vec::new(),
+                };
+                for name in self.attribute_names(&object) {
+                    // private names only once the prefix asks for them
+                    if name.starts_with(prefix) && (prefix.starts_with('_') || !name.starts_with('_')) {
+                        let value = get_attribute(&object, &name).ok();
+                        matches.push((format!("{}.{}", expr, name), value));
+                    }
+                }
+            }
+            None => {
+                let mut names: BTreeSet<String> = KEYWORDS.iter().map(|name| String::from(*name)).collect();
+                names.extend(self.globals.vars.borrow().keys().cloned());
+                names.extend(self.builtins.vars.borrow().keys().cloned());
+                for name in names {
+                    if name.starts_with(word) {
+                        let value = self.globals.get(&name).or_else(|| self.builtins.get(&name));
+                        matches.push((name, value));
+                    }
+                }
+            }
+        }
+        return matches
+            .into_iter()
+            .map(|(name, value)| {
+                // callables complete with the opening paren
+                let replacement = match value {
+                    Some(Value::Function(_) | Value::Builtin(_) | Value::Class(_) | Value::Method(_)) => {
+                        format!("{}(", name)
+                    }
+                    _ => name.clone(),
+                };
+                return Pair {
+                    display: name,
+                    replacement,
+                };
+            })
+            .collect();
+    }
+}
+
+impl Completer for ReplHelper {
+    type Candidate = Pair;
+
+    fn complete(&self, line: &str, pos: usize, _: &Context<'_>) -> rustyline::Result<(usize, Vec<Pair>)> {
+        let before = &line[..pos];
+        // tab at the start of a line indents it
+        if before.trim().is_empty() {
+            let indent = Pair {
+                display: String::new(),
+                replacement: String::from("    "),
+            };
+            return Ok((pos, vec![indent]));
+        }
+        let start = before
+            .rfind(|c: char| !(c.is_alphanumeric() || c == '_' || c == '.'))
+            .map_or(0, |found| found + 1);
+        return Ok((start, self.completions(&before[start..])));
+    }
+}
+
+impl Hinter for ReplHelper {
+    type Hint = String;
+}
+
+impl Highlighter for ReplHelper {}
+
+impl Validator for ReplHelper {}
+
+impl Helper for ReplHelper {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_completions() {
+        let interpreter = Interpreter::build_new();
+        interpreter.globals.set("spam", Value::Int(1));
+        interpreter.globals.set("sys", Value::Module(interpreter.modules["sys"].clone()));
+        let helper = ReplHelper {
+            globals: interpreter.globals.clone(),
+            builtins: interpreter.builtins.clone(),
+            types: interpreter.types.clone(),
+        };
+        let replacements = |word: &str| -> Vec<String> {
+            return helper.completions(word).into_iter().map(|pair| pair.replacement).collect();
+        };
+        assert_eq!(replacements("sp"), vec!["spam"]);
+        assert_eq!(replacements("whi"), vec!["while"]);
+        assert_eq!(replacements("isinst"), vec!["isinstance("]);
+        assert!(replacements("sys.pa").contains(&String::from("sys.path")));
+        assert!(replacements("sys.").iter().all(|name| !name.starts_with("sys._")));
+        assert_eq!(replacements("sys.__na"), vec!["sys.__name__"]);
+        assert!(replacements("nothing.").is_empty());
+    }
+
+    #[test]
+    fn test_instance_completions() {
+        let mut interpreter = Interpreter::build_new();
+        let source = "class Base:\n    def greet(self): pass\nclass Point(Base):\n    def norm(self): pass\n\
+                      p = Point()\np.x = 1\n";
+        let code = interpreter.compile(source, "<string>").unwrap();
+        interpreter.exec_module(interpreter.globals.clone(), &code).unwrap();
+        let helper = ReplHelper {
+            globals: interpreter.globals.clone(),
+            builtins: interpreter.builtins.clone(),
+            types: interpreter.types.clone(),
+        };
+        let replacements: Vec<String> = helper.completions("p.").into_iter().map(|pair| pair.replacement).collect();
+        assert_eq!(replacements, vec!["p.greet(", "p.norm(", "p.x"]);
+    }
+}
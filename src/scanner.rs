@@ -1,18 +1,29 @@
 extern crate regex;
 
+use crate::ast::BinOp;
+use crate::parser::Parser;
+use crate::readline::{Line, LineReader};
+use crate::value::Exception;
+use num_bigint::BigInt;
 use regex::Regex;
 use std::collections::VecDeque;
-use std::io::{stdin, stdout, Write}; // TODO double check necessary
+use std::rc::Rc;
+use std::sync::OnceLock;
 
 #[derive(Debug, Clone)]
 #[allow(dead_code)]
 pub enum Token {
     SyntaxError,
-    State,
+    // the end of the input
     Exit,
     // values (WIP)
     Float(f64),
+    Imaginary(f64),
     Int(i64),
+    // an int literal too big for Int
+    BigInt(Rc<BigInt>),
+    Str(String),
+    Bytes(Vec<u8>),
     // variables
     Variable(String),
     // misc - could easily move to terminals
@@ -24,7 +35,24 @@ pub enum Token {
     Minus,
     Multiply,
     Divide,
+    FloorDivide,
+    Modulo,
     Exponent,
+    Ampersand,
+    Pipe,
+    Caret,
+    Tilde,
+    LeftShift,
+    RightShift,
+    // x += 1 is AugAssign(Add)
+    AugAssign(BinOp),
+    // comparisons
+    DoubleEquals,
+    NotEquals,
+    Less,
+    LessEquals,
+    Greater,
+    GreaterEquals,
     // organization
     Equals,
     OpenParen,
@@ -36,6 +64,11 @@ pub enum Token {
     SingleQuote,
     DoubeleQuote,
     Colon,
+    Comma,
+    Dot,
+    Semicolon,
+    // @decorator
+    At,
     // functions and classes, etc
     Def,
     Return,
@@ -87,150 +120,557 @@ pub struct RegexMatch {
 
 /* Basic input struct. Holds current input remaining to be parsed, as well as
  * a basic history deque in case of lookahead
+ *
+ * tokens: pre-lexed token stream (e.g. a script lexed by tokenize), with the
+ * line each token starts on. If set, tokens are taken from here instead of
+ * lexing user input from stdin, and running out of tokens yields Token::Exit.
+ *
+ * line: line the current token starts on. next_line: line the stream
+ * continues on; lines typed at the prompt count from 1 for each statement.
+ *
+ * nesting: open brackets in the input lexed so far; newlines inside them
+ * don't end the line.
+ *
+ * reader: where lines typed at the prompt are read from
  */
 #[derive(Debug)]
 pub struct Input {
     pub stream: String,
     pub current: Token,
     pub history: VecDeque<Token>,
+    pub tokens: Option<VecDeque<(Token, usize)>>,
+    pub line: usize,
+    pub next_line: usize,
+    pub nesting: usize,
+    pub reader: LineReader,
+}
+
+type TokenBuilder = fn(&str) -> Token;
+
+/* Token patterns, compiled once on first use.
+ */
+fn patterns() -> &'static Vec<(Regex, TokenBuilder)> {
+    static PATTERNS: OnceLock<Vec<(Regex, TokenBuilder)>> = OnceLock::new();
+    return PATTERNS.get_or_init(|| {
+        let table: Vec<(&str, TokenBuilder)> = vec![
+            (r"^\n", |_| Token::NewLine),
+            // comments are lexed as whitespace; so is a backslash continuation
+            (r"^([ \t]+(#[^\n]*)?|#[^\n]*|\\\n)", |matched| Token::WhiteSpace(matched.len())),
+            (r#"^([rR][bB]?|[bB][rR]?)?("""(?s:.)*?"""|'''(?s:.)*?''')"#, build_string),
+            (r#"^([rR][bB]?|[bB][rR]?)?("([^"\\\n]|\\.)*"|'([^'\\\n]|\\.)*')"#, build_string),
+            (r"^[A-Za-z_][A-Za-z0-9_]*", keyword_or_variable),
+            (r"^(\*\*|//|<<|>>|[-+*/%&|^])=", build_aug_assign),
+            (r"^\*\*", |_| Token::Exponent),
+            (r"^\*", |_| Token::Multiply),
+            (r"^//", |_| Token::FloorDivide),
+            (r"^/", |_| Token::Divide),
+            (r"^%", |_| Token::Modulo),
+            (r"^\+", |_| Token::Plus),
+            (r"^-", |_| Token::Minus),
+            (r"^&", |_| Token::Ampersand),
+            (r"^\|", |_| Token::Pipe),
+            (r"^\^", |_| Token::Caret),
+            (r"^~", |_| Token::Tilde),
+            (r"^<<", |_| Token::LeftShift),
+            (r"^>>", |_| Token::RightShift),
+            (r"^==", |_| Token::DoubleEquals),
+            (r"^!=", |_| Token::NotEquals),
+            (r"^<=", |_| Token::LessEquals),
+            (r"^<", |_| Token::Less),
+            (r"^>=", |_| Token::GreaterEquals),
+            (r"^>", |_| Token::Greater),
+            (r"^=", |_| Token::Equals),
+            (r"^\(", |_| Token::OpenParen),
+            (r"^\)", |_| Token::CloseParen),
+            (r"^\[", |_| Token::OpenBracket),
+            (r"^\]", |_| Token::CloseBracket),
+            (r"^\{", |_| Token::OpenBrace),
+            (r"^\}", |_| Token::CloseBrace),
+            (r"^:", |_| Token::Colon),
+            (r"^,", |_| Token::Comma),
+            (r"^;", |_| Token::Semicolon),
+            (r"^@", |_| Token::At),
+            // digits may be grouped with single underscores, e.g. 1_000
+            (r"^(([0-9](_?[0-9])*)?\.[0-9](_?[0-9])*([eE][+-]?[0-9](_?[0-9])*)?|[0-9](_?[0-9])*(\.|\.?[eE][+-]?[0-9](_?[0-9])*)?)[jJ]", |matched| {
+                match matched[..matched.len() - 1].replace('_', "").parse() {
+                    Ok(val) => return Token::Imaginary(val),
+                    Err(_) => return Token::SyntaxError,
+                }
+            }),
+            (r"^(([0-9](_?[0-9])*)?\.[0-9](_?[0-9])*|[0-9](_?[0-9])*\.)([eE][+-]?[0-9](_?[0-9])*)?|^[0-9](_?[0-9])*[eE][+-]?[0-9](_?[0-9])*", |matched| {
+                match matched.replace('_', "").parse() {
+                    Ok(val) => return Token::Float(val),
+                    Err(_) => return Token::SyntaxError,
+                }
+            }),
+            (r"^\.", |_| Token::Dot),
+            (r"^0[xX](_?[0-9a-fA-F])+|^0[oO](_?[0-7])+|^0[bB](_?[01])+|^[0-9](_?[0-9])*", build_int),
+        ];
+        return table
+            .into_iter()
+            .map(|(pattern, build)| (Regex::new(pattern).unwrap(), build))
+            .collect();
+    });
+}
+
+/* An int literal: decimal, or hex, octal or binary with a 0x, 0o or 0b
+ * prefix. Decimal literals can't have leading zeros (they used to mean
+ * octal). A literal too big for 64 bits is a BigInt.
+ */
+fn build_int(literal: &str) -> Token {
+    let digits = literal.replace('_', "");
+    let radix = match digits.get(..2) {
+        Some("0x" | "0X") => 16,
+        Some("0o" | "0O") => 8,
+        Some("0b" | "0B") => 2,
+        _ if digits.starts_with('0') && digits.contains(|c| c != '0') => return Token::SyntaxError,
+        _ => 10,
+    };
+    let digits = if radix == 10 { &digits[..] } else { &digits[2..] };
+    if let Ok(val) = i64::from_str_radix(digits, radix) {
+        return Token::Int(val);
+    }
+    match BigInt::parse_bytes(digits.as_bytes(), radix) {
+        Some(val) => return Token::BigInt(Rc::new(val)),
+        None => return Token::SyntaxError,
+    }
+}
+
+fn build_aug_assign(operator: &str) -> Token {
+    let op = match &operator[..operator.len() - 1] {
+        "+" => BinOp::Add,
+        "-" => BinOp::Sub,
+        "*" => BinOp::Mul,
+        "/" => BinOp::Div,
+        "//" => BinOp::FloorDiv,
+        "%" => BinOp::Mod,
+        "**" => BinOp::Pow,
+        "&" => BinOp::BitAnd,
+        "|" => BinOp::BitOr,
+        "^" => BinOp::BitXor,
+        "<<" => BinOp::LShift,
+        _ => BinOp::RShift,
+    };
+    return Token::AugAssign(op);
+}
+
+// reserved words, for completion at the prompt
+pub const KEYWORDS: &[&str] = &[
+    "False", "None", "True", "and", "as", "assert", "async", "await", "break", "class", "continue", "def", "del",
+    "elif", "else", "except", "finally", "for", "from", "global", "if", "import", "in", "is", "lambda", "nonlocal",
+    "not", "or", "pass", "raise", "return", "try", "while", "with", "yield",
+];
+
+fn keyword_or_variable(name: &str) -> Token {
+    match name {
+        "None" => return Token::NoneT,
+        "True" => return Token::True,
+        "False" => return Token::False,
+        "and" => return Token::And,
+        "or" => return Token::Or,
+        "not" => return Token::Not,
+        "is" => return Token::Is,
+        "in" => return Token::In,
+        "if" => return Token::If,
+        "elif" => return Token::Elif,
+        "else" => return Token::Else,
+        "while" => return Token::While,
+        "for" => return Token::For,
+        "break" => return Token::Break,
+        "continue" => return Token::Continue,
+        "pass" => return Token::Pass,
+        "def" => return Token::Def,
+        "return" => return Token::Return,
+        "lambda" => return Token::Lambda,
+        "with" => return Token::With,
+        "as" => return Token::As,
+        "class" => return Token::Class,
+        "try" => return Token::Try,
+        "except" => return Token::Except,
+        "raise" => return Token::Raise,
+        "finally" => return Token::Finally,
+        "yield" => return Token::Yield,
+        "from" => return Token::FromImport,
+        "import" => return Token::Import,
+        "async" => return Token::Async,
+        "await" => return Token::Await,
+        "assert" => return Token::Assert,
+        "del" => return Token::Del,
+        "global" => return Token::Global,
+        "nonlocal" => return Token::Nonlocal,
+        _ => return Token::Variable(String::from(name)),
+    }
+}
+
+/* Strip quotes (and an r or b prefix) from a string literal and process
+ * escapes. A b prefix makes a bytes literal, which must be ASCII.
+ */
+fn build_string(literal: &str) -> Token {
+    let prefix = literal.find(['"', '\'']).unwrap();
+    let raw = literal[..prefix].contains(['r', 'R']);
+    let binary = literal[..prefix].contains(['b', 'B']);
+    let literal = &literal[prefix..];
+    let quote_len = if literal.starts_with("\"\"\"") || literal.starts_with("'''") { 3 } else { 1 };
+    let body = &literal[quote_len..literal.len() - quote_len];
+    if binary {
+        if !body.is_ascii() {
+            return Token::SyntaxError;
+        }
+        if raw {
+            return Token::Bytes(body.as_bytes().to_vec());
+        }
+        match unescape_bytes(body) {
+            Some(val) => return Token::Bytes(val),
+            None => return Token::SyntaxError,
+        }
+    }
+    if raw {
+        return Token::Str(String::from(body));
+    }
+    match unescape(body) {
+        Some(val) => return Token::Str(val),
+        None => return Token::SyntaxError,
+    }
+}
+
+/* What is wrong with a string literal build_string rejects, worded like
+ * CPython's codec errors. Positions count a non-ASCII character as the 10
+ * bytes of its \U escape, as CPython's do.
+ */
+fn escape_error(literal: &str) -> String {
+    let prefix = literal.find(['"', '\'']).unwrap();
+    let binary = literal[..prefix].contains(['b', 'B']);
+    let literal = &literal[prefix..];
+    let quote_len = if literal.starts_with("\"\"\"") || literal.starts_with("'''") { 3 } else { 1 };
+    let body: Vec<char> = literal[quote_len..literal.len() - quote_len].chars().collect();
+    if binary && !body.iter().all(char::is_ascii) {
+        return String::from("bytes can only contain ASCII literal characters");
+    }
+    let mut pos: usize = 0;
+    let mut index: usize = 0;
+    while index < body.len() {
+        if body[index] != '\\' {
+            pos += if body[index].is_ascii() { 1 } else { 10 };
+            index += 1;
+            continue;
+        }
+        let (len, name) = match body.get(index + 1) {
+            Some('x') => (2, "\\xXX"),
+            Some('u') if !binary => (4, "\\uXXXX"),
+            Some('U') if !binary => (8, "\\UXXXXXXXX"),
+            _ => {
+                pos += 2;
+                index += 2;
+                continue;
+            }
+        };
+        let digits: String = body[index + 2..].iter().take(len).take_while(|c| c.is_ascii_hexdigit()).collect();
+        if digits.len() < len && binary {
+            return format!("(value error) invalid \\x escape at position {}", pos);
+        }
+        if digits.len() < len {
+            return format!(
+                "(unicode error) 'unicodeescape' codec can't decode bytes in position {}-{}: truncated {} escape",
+                pos,
+                pos + 1 + digits.len(),
+                name
+            );
+        }
+        if char::from_u32(u32::from_str_radix(&digits, 16).unwrap()).is_none() {
+            return format!(
+                "(unicode error) 'unicodeescape' codec can't decode bytes in position {}-{}: illegal Unicode character",
+                pos,
+                pos + 1 + len
+            );
+        }
+        pos += 2 + len;
+        index += 2 + len;
+    }
+    return String::from("invalid syntax");
+}
+
+/* Process the escapes of a bytes literal: those of a string but \u and \U,
+ * which are kept as-is. Returns None for malformed \x escapes.
+ */
+fn unescape_bytes(body: &str) -> Option<Vec<u8>> {
+    let mut out: Vec<u8> = Vec::new();
+    let mut bytes = body.bytes();
+    while let Some(byte) = bytes.next() {
+        if byte != b'\\' {
+            out.push(byte);
+            continue;
+        }
+        match bytes.next() {
+            Some(b'n') => out.push(b'\n'),
+            Some(b't') => out.push(b'\t'),
+            Some(b'r') => out.push(b'\r'),
+            Some(b'0') => out.push(0),
+            Some(b'\\') => out.push(b'\\'),
+            Some(b'\'') => out.push(b'\''),
+            Some(b'"') => out.push(b'"'),
+            Some(b'\n') => (),
+            Some(b'x') => {
+                let digits: Vec<u8> = bytes.by_ref().take(2).collect();
+                if digits.len() != 2 {
+                    return None;
+                }
+                out.push(u8::from_str_radix(std::str::from_utf8(&digits).ok()?, 16).ok()?);
+            }
+            Some(other) => out.extend_from_slice(&[b'\\', other]),
+            None => out.push(b'\\'),
+        }
+    }
+    return Some(out);
+}
+
+/* Process backslash escapes. Unknown escapes are kept as-is, as in Python.
+ * Returns None for malformed \x / \u escapes.
+ */
+pub fn unescape(body: &str) -> Option<String> {
+    let mut out = String::new();
+    let mut chars = body.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => out.push('\n'),
+            Some('t') => out.push('\t'),
+            Some('r') => out.push('\r'),
+            Some('0') => out.push('\0'),
+            Some('\\') => out.push('\\'),
+            Some('\'') => out.push('\''),
+            Some('"') => out.push('"'),
+            Some('\n') => (),
+            Some(kind @ ('x' | 'u' | 'U')) => {
+                let len = match kind {
+                    'x' => 2,
+                    'u' => 4,
+                    _ => 8,
+                };
+                let digits: String = chars.by_ref().take(len).collect();
+                if digits.len() != len {
+                    return None;
+                }
+                out.push(char::from_u32(u32::from_str_radix(&digits, 16).ok()?)?);
+            }
+            Some(other) => {
+                out.push('\\');
+                out.push(other);
+            }
+            None => out.push('\\'),
+        }
+    }
+    return Some(out);
+}
+
+/* Lex an entire source string up front. Used for scripts and modules, which
+ * are parsed as a whole before they run. Each token comes with the line it
+ * starts on.
+ *
+ * Errors are SyntaxErrors with the line of the offending input set.
+ */
+pub fn tokenize(source: &str) -> Result<Vec<(Token, usize)>, Exception> {
+    let (tokens, mut brackets) = lex(source)?;
+    if let Some((open, line)) = brackets.pop() {
+        return Err(error_at(&format!("'{}' was never closed", open), line));
+    }
+    return Ok(tokens);
+}
+
+/* Tokens with their lines, and the brackets still open at the end with
+ * the lines they were opened on
+ */
+type Lexed = (Vec<(Token, usize)>, Vec<(char, usize)>);
+
+/* tokenize, but brackets left open at the end aren't an error: they are
+ * returned instead
+ */
+pub fn lex(source: &str) -> Result<Lexed, Exception> {
+    let mut input = Input {
+        stream: String::from(source),
+        current: Token::NewLine,
+        history: VecDeque::new(),
+        tokens: None,
+        line: 1,
+        next_line: 1,
+        nesting: 0,
+        reader: LineReader::Plain,
+    };
+    let mut tokens: Vec<(Token, usize)> = Vec::new();
+    // open brackets and their lines: newlines inside them don't end the line
+    let mut brackets: Vec<(char, usize)> = Vec::new();
+    let mut line: usize = 1;
+    while !input.stream.is_empty() {
+        let next_token_match: RegexMatch = input.re_match();
+        let token_line = line;
+        line += input.stream[..next_token_match.token_len].matches('\n').count();
+        match next_token_match.token {
+            Token::SyntaxError => return Err(lex_error(&input.stream, token_line)),
+            Token::OpenParen | Token::OpenBracket | Token::OpenBrace => {
+                brackets.push((input.stream.chars().next().unwrap(), token_line));
+            }
+            Token::CloseParen | Token::CloseBracket | Token::CloseBrace => {
+                let close = input.stream.chars().next().unwrap();
+                let message = match brackets.pop() {
+                    Some(('(', _)) if close == ')' => None,
+                    Some(('[', _)) if close == ']' => None,
+                    Some(('{', _)) if close == '}' => None,
+                    Some((open, _)) => Some(format!(
+                        "closing parenthesis '{}' does not match opening parenthesis '{}'",
+                        close, open
+                    )),
+                    None => Some(format!("unmatched '{}'", close)),
+                };
+                if let Some(message) = message {
+                    return Err(error_at(&message, token_line));
+                }
+            }
+            Token::NewLine | Token::WhiteSpace(_) if !brackets.is_empty() => {
+                input.stream = String::from(&input.stream[next_token_match.token_len..]);
+                continue;
+            }
+            _ => (),
+        }
+        input.stream = String::from(&input.stream[next_token_match.token_len..]);
+        input.current = next_token_match.token.clone();
+        tokens.push((next_token_match.token, token_line));
+    }
+    return Ok((tokens, brackets));
+}
+
+fn error_at(message: &str, line: usize) -> Exception {
+    let mut err = Exception::new("SyntaxError", message);
+    err.line = Some(line);
+    return err;
+}
+
+/* The SyntaxError for input at line that no token matches
+ */
+fn lex_error(stream: &str, line: usize) -> Exception {
+    // a whole literal, so one of its escapes is malformed
+    for (re, _) in &patterns()[2..4] {
+        if let Some(found) = re.find(stream) {
+            return error_at(&escape_error(found.as_str()), line);
+        }
+    }
+    // an int literal build_int rejects, for its leading zeros
+    if let Some(found) = patterns().last().unwrap().0.find(stream) {
+        let literal = found.as_str();
+        if literal.starts_with('0') && literal.chars().all(|c| c.is_ascii_digit() || c == '_') {
+            let message = "leading zeros in decimal integer literals are not permitted; use an 0o prefix for octal integers";
+            return error_at(message, line);
+        }
+    }
+    let prefix = stream.chars().take(2).take_while(|c| "rRbB".contains(*c)).count();
+    let body = &stream[prefix..];
+    // an unterminated string runs to the end of its line, or for a
+    // triple-quoted one to the end of the input
+    if body.starts_with("\"\"\"") || body.starts_with("'''") {
+        let end = line + stream.lines().count() - 1;
+        let message = format!("unterminated triple-quoted string literal (detected at line {})", end);
+        return error_at(&message, line);
+    }
+    if body.starts_with(['"', '\'']) {
+        return error_at(&format!("unterminated string literal (detected at line {})", line), line);
+    }
+    return error_at("invalid syntax", line);
+}
+
+/* Whether source typed at the prompt needs more lines before it can run,
+ * like codeop.compile_command returning None: it has an open bracket, a
+ * trailing backslash or an unterminated triple-quoted string, or it is a
+ * compound statement that hasn't been ended by a blank line. Invalid input
+ * counts as complete, so the parser reports it.
+ */
+pub fn needs_more_input(source: &str) -> bool {
+    let mut input = Input {
+        stream: String::from(source),
+        current: Token::NewLine,
+        history: VecDeque::new(),
+        tokens: None,
+        line: 1,
+        next_line: 1,
+        nesting: 0,
+        reader: LineReader::Plain,
+    };
+    let mut first = true;
+    let mut compound = false;
+    // whether the last token on the current line so far is a colon
+    let mut colon = false;
+    while !input.stream.is_empty() {
+        // the closing quotes may be on a later line
+        if unclosed_triple_quote(&input.stream) {
+            return true;
+        }
+        let next_token_match: RegexMatch = input.re_match();
+        input.stream = String::from(&input.stream[next_token_match.token_len..]);
+        match next_token_match.token {
+            Token::SyntaxError => return false,
+            Token::WhiteSpace(_) => continue,
+            Token::NewLine => {
+                compound = compound || (colon && input.nesting == 0);
+                continue;
+            }
+            Token::OpenParen | Token::OpenBracket | Token::OpenBrace => input.nesting += 1,
+            Token::CloseParen | Token::CloseBracket | Token::CloseBrace => {
+                input.nesting = input.nesting.saturating_sub(1)
+            }
+            Token::If | Token::While | Token::For | Token::Def | Token::Class | Token::Try | Token::With | Token::At
+                if first =>
+            {
+                compound = true
+            }
+            _ => (),
+        };
+        colon = matches!(next_token_match.token, Token::Colon);
+        first = false;
+    }
+    // a compound statement ends at an empty line
+    let last_line = source.strip_suffix('\n').map(|rest| &rest[rest.rfind('\n').map_or(0, |pos| pos + 1)..]);
+    let blank = matches!(last_line, Some(line) if line.trim().is_empty());
+    let incomplete = input.nesting > 0 || source.ends_with("\\\n") || colon || (compound && !blank);
+    // unless it is already invalid, e.g. `f(:`
+    return incomplete && !Parser::fails_before_end(source);
+}
+
+fn unclosed_triple_quote(stream: &str) -> bool {
+    let prefix = stream.chars().take(2).take_while(|c| "rRbB".contains(*c)).count();
+    let body = &stream[prefix..];
+    for quote in ["\"\"\"", "'''"] {
+        if let Some(rest) = body.strip_prefix(quote) {
+            return !rest.contains(quote);
+        }
+    }
+    return false;
 }
 
 impl Input {
     /* for bailing out when errors arise
      */
-    fn flush_line(&mut self) {
+    pub fn flush_line(&mut self) {
         self.stream = String::from("");
         self.history.clear();
+        self.nesting = 0;
     }
 
-    /* Returns result of attempt to match given regex pattern to the stream.
-     * If no match, returns None; otherwise, returns a string slice of the match
-     * from the stream
+    /* Match the next token at the front of the stream. Patterns are tried
+     * in order, so e.g. ** must come before *.
      */
-    fn check_match(stream: &str, re: Regex) -> Option<&str> {
-        if re.is_match(stream) {
-            return Some(re.find(stream).unwrap().as_str());
-        } else {
-            return None;
-        }
-    }
-
     pub fn re_match(&mut self) -> RegexMatch {
-        // regex options
-        let re_newline = Regex::new(r"^\n").unwrap();
-        let re_whitespace = Regex::new(r"^[ ]+").unwrap();
-        let re_del = Regex::new(r"^del[\n ]").unwrap();
-        let re_exit = Regex::new(r"^exit[\n ]").unwrap();
-        let re_state = Regex::new(r"^state[\n ]").unwrap();
-        let re_none = Regex::new(r"^None[\n ]").unwrap();
-        let re_variable = Regex::new(r"^[A-z][A-z0-9]*").unwrap();
-        let re_plus = Regex::new(r"^\+").unwrap();
-        let re_minus = Regex::new(r"^-").unwrap();
-        let re_exponent = Regex::new(r"^\*\*").unwrap();
-        let re_multiply = Regex::new(r"^\*").unwrap();
-        let re_divide = Regex::new(r"^/").unwrap();
-        let re_openparen = Regex::new(r"^\(").unwrap();
-        let re_closeparen = Regex::new(r"^\)").unwrap();
-        let re_eq = Regex::new(r"^=").unwrap();
-        let re_float = Regex::new(r"^[0-9]+\.[0-9]*").unwrap();
-        let re_int = Regex::new(r"^[0-9]+").unwrap();
-
-        if let Some(_) = Input::check_match(&self.stream, re_newline) {
-            return RegexMatch {
-                token: Token::NewLine,
-                token_len: 1,
-            };
-        } else if let Some(whitespace) = Input::check_match(&self.stream, re_whitespace) {
-            let len: usize = whitespace.len();
-            return RegexMatch {
-                token: Token::WhiteSpace(len),
-                token_len: len,
-            };
-        } else if let Some(_) = Input::check_match(&self.stream, re_del) {
-            return RegexMatch {
-                token: Token::NewLine,
-                token_len: 3,
-            };
-        } else if let Some(_) = Input::check_match(&self.stream, re_exit) {
-            return RegexMatch {
-                token: Token::Exit,
-                token_len: 4,
-            };
-        } else if let Some(_) = Input::check_match(&self.stream, re_state) {
-            return RegexMatch {
-                token: Token::State,
-                token_len: 4,
-            };
-        } else if let Some(_) = Input::check_match(&self.stream, re_none) {
-            return RegexMatch {
-                token: Token::NoneT,
-                token_len: 4,
-            };
-        } else if let Some(name) = Input::check_match(&self.stream, re_variable) {
-            let name_clone = String::from(name.clone());
-            let name_clone_len = name_clone.len();
-            return RegexMatch {
-                token: Token::Variable(name_clone),
-                token_len: name_clone_len,
-            };
-        } else if let Some(_) = Input::check_match(&self.stream, re_plus) {
-            return RegexMatch {
-                token: Token::Plus,
-                token_len: 1,
-            };
-        } else if let Some(_) = Input::check_match(&self.stream, re_minus) {
-            return RegexMatch {
-                token: Token::Minus,
-                token_len: 1,
-            };
-        } else if let Some(_) = Input::check_match(&self.stream, re_exponent) {
-            return RegexMatch {
-                token: Token::Exponent,
-                token_len: 2,
-            };
-        } else if let Some(_) = Input::check_match(&self.stream, re_multiply) {
-            return RegexMatch {
-                token: Token::Multiply,
-                token_len: 1,
-            };
-        } else if let Some(_) = Input::check_match(&self.stream, re_divide) {
-            return RegexMatch {
-                token: Token::Divide,
-                token_len: 1,
-            };
-        } else if let Some(_) = Input::check_match(&self.stream, re_openparen) {
-            return RegexMatch {
-                token: Token::OpenParen,
-                token_len: 1,
-            };
-        } else if let Some(_) = Input::check_match(&self.stream, re_closeparen) {
-            return RegexMatch {
-                token: Token::CloseParen,
-                token_len: 1,
-            };
-        } else if let Some(_) = Input::check_match(&self.stream, re_eq) {
-            return RegexMatch {
-                token: Token::Equals,
-                token_len: 1,
-            };
-        } else if let Some(val) = Input::check_match(&self.stream, re_float) {
-            let val_parsed = val.parse().unwrap();
-            return RegexMatch {
-                token: Token::Float(val_parsed),
-                token_len: val.len(),
-            };
-        } else if let Some(val) = Input::check_match(&self.stream, re_int) {
-            let val_parsed = val.parse().unwrap();
-            return RegexMatch {
-                token: Token::Int(val_parsed),
-                token_len: val.len(),
-            };
-        } else {
-            return RegexMatch {
-                token: Token::SyntaxError,
-                token_len: 0,
-            };
+        for (re, build) in patterns() {
+            if let Some(found) = re.find(&self.stream) {
+                return RegexMatch {
+                    token: build(found.as_str()),
+                    token_len: found.end(),
+                };
+            }
         }
+        return RegexMatch {
+            token: Token::SyntaxError,
+            token_len: 0,
+        };
     }
 
     /* Look ahead - assists w/ parsing
@@ -238,7 +678,17 @@ impl Input {
      *
      * ignore_whitespace: if true, consumes + skips whitespace (modifying self.stream)
      */
+    #[allow(dead_code, clippy::single_match, clippy::needless_borrow)]
     pub fn look_ahead(&mut self, ignore_whitespace: bool) -> &Token {
+        if let Some(tokens) = &mut self.tokens {
+            let mut next_token = tokens.pop_front().map_or(Token::Exit, |(token, _)| token);
+            while let (true, Token::WhiteSpace(_)) = (ignore_whitespace, &next_token) {
+                next_token = tokens.pop_front().map_or(Token::Exit, |(token, _)| token);
+            }
+            self.history.push_back(next_token);
+            return self.history.back().unwrap();
+        }
+
         let mut next_token_match: RegexMatch = self.re_match();
 
         if ignore_whitespace {
@@ -268,6 +718,33 @@ impl Input {
         return &self.history.back().unwrap();
     }
 
+    /* Read lines into the stream until they make up a complete statement,
+     * prompting with ">>> " and then "... ". Returns false at the end of
+     * input (e.g. Ctrl-D or piped stdin).
+     */
+    fn read_statement(&mut self) -> bool {
+        let mut prompt = ">>> ";
+        self.next_line = 1;
+        loop {
+            match self.reader.read_line(prompt) {
+                Line::Text(line) => self.stream.push_str(&line),
+                // input ending mid-statement runs what there is
+                Line::Eof => return !self.stream.is_empty(),
+                // Ctrl-C abandons the statement being typed
+                Line::Interrupted => {
+                    println!("KeyboardInterrupt");
+                    self.stream.clear();
+                    prompt = ">>> ";
+                    continue;
+                }
+            };
+            if !needs_more_input(&self.stream) {
+                return true;
+            }
+            prompt = "... ";
+        }
+    }
+
     /* Get next token. Either pop from history queue, or consume next token
      * from input stream.
      *
@@ -275,40 +752,67 @@ impl Input {
      *
      * Updates self.current and returns the new current token as a borrow
      */
+    #[allow(clippy::single_match)]
     pub fn get_next_token(&mut self, skip_whitespace: bool) -> &Token {
         if !self.history.is_empty() {
             self.current = self.history.pop_front().unwrap();
             return &self.current;
         }
 
+        // pre-lexed input: end of tokens means end of program
+        if let Some(tokens) = &mut self.tokens {
+            loop {
+                match tokens.pop_front() {
+                    Some((token, line)) => {
+                        self.current = token;
+                        self.line = line;
+                    }
+                    None => self.current = Token::Exit,
+                };
+                if !(skip_whitespace && matches!(self.current, Token::WhiteSpace(_))) {
+                    return &self.current;
+                }
+            }
+        }
+
         // if stream blank, get user input
-        if self.stream == "" {
-            print!(">> ");
-            stdout().flush().expect("Could not flush stdout");
-            stdin()
-                .read_line(&mut self.stream)
-                .expect("Failed to read line");
+        if self.stream.is_empty() && !self.read_statement() {
+            self.current = Token::Exit;
+            return &self.current;
         }
 
-        // get first match
-        let next_token_match: RegexMatch = self.re_match();
+        loop {
+            // get first match
+            let next_token_match: RegexMatch = self.re_match();
 
-        // update current, stream
-        match next_token_match.token {
-            Token::SyntaxError => {
-                self.current = next_token_match.token;
-                self.flush_line();
-            }
-            _ => {
-                self.stream = String::from(&self.stream[next_token_match.token_len..]);
-                self.current = next_token_match.token;
+            // update current, stream
+            match next_token_match.token {
+                Token::SyntaxError => {
+                    self.current = next_token_match.token;
+                    self.flush_line();
+                }
+                _ => {
+                    self.line = self.next_line;
+                    self.next_line += self.stream[..next_token_match.token_len].matches('\n').count();
+                    self.stream = String::from(&self.stream[next_token_match.token_len..]);
+                    self.current = next_token_match.token;
+                }
             }
+            match self.current {
+                Token::OpenParen | Token::OpenBracket | Token::OpenBrace => self.nesting += 1,
+                Token::CloseParen | Token::CloseBracket | Token::CloseBrace => {
+                    self.nesting = self.nesting.saturating_sub(1)
+                }
+                Token::NewLine | Token::WhiteSpace(_) if self.nesting > 0 => continue,
+                _ => (),
+            };
+            break;
         }
 
         if skip_whitespace {
             match &self.current {
                 Token::WhiteSpace(_) => {
-                    self.get_next_token(false);
+                    self.get_next_token(true);
                 },
                 _ => ()
             }
@@ -322,6 +826,7 @@ impl Input {
 mod tests {
     use super::*;
 
+    #[allow(clippy::needless_late_init)]
     fn setup(stream_in: Option<String>, current_in: Option<Token>, history_in: Option<VecDeque<Token>>) -> Input {
         let stream: String;
         let current: Token;
@@ -338,10 +843,16 @@ mod tests {
             Some(val) => history = val,
             None => history = VecDeque::new(),
         }
+        #[allow(clippy::redundant_field_names)]
         let input = Input {
             stream: stream,
             current: current,
             history: history,
+            tokens: None,
+            line: 1,
+            next_line: 1,
+            nesting: 0,
+            reader: LineReader::Plain,
         };
         return input;
     }
@@ -365,4 +876,69 @@ mod tests {
         result = input.get_next_token(false);
         assert!(same_token(result, Token::Int(4)));
     }
+
+    #[test]
+    fn test_pre_lexed_tokens() {
+        let tokens = tokenize("x = 2\n").unwrap();
+        let mut input = setup(None, None, None);
+        input.tokens = Some(tokens.into_iter().collect());
+        assert!(same_token(input.get_next_token(true), Token::Variable(String::from("x"))));
+        assert!(same_token(input.look_ahead(true), Token::Equals));
+        assert!(same_token(input.get_next_token(true), Token::Equals));
+        assert!(same_token(input.get_next_token(true), Token::Int(2)));
+        assert!(same_token(input.get_next_token(true), Token::NewLine));
+        assert!(same_token(input.get_next_token(true), Token::Exit));
+        assert_eq!(tokenize("x = 2\n$\n").unwrap_err().line, Some(2));
+        // the prompt's commands are only commands at the prompt
+        let tokens = tokenize("sys.exit\nexit = state\n").unwrap();
+        assert!(same_token(&tokens[2].0, Token::Variable(String::from("exit"))));
+        assert!(same_token(&tokens[4].0, Token::Variable(String::from("exit"))));
+        assert!(same_token(&tokens[8].0, Token::Variable(String::from("state"))));
+    }
+
+    #[test]
+    fn test_lines() {
+        // tokens inside brackets and strings can span lines
+        let tokens = tokenize("x = (1,\n2)\ns = '''a\nb'''\ny\n").unwrap();
+        let lines: Vec<usize> = tokens.iter().map(|(_, line)| *line).collect();
+        assert_eq!(lines, vec![1, 1, 1, 1, 1, 1, 1, 2, 2, 2, 3, 3, 3, 3, 3, 4, 5, 5]);
+
+        let mut input = setup(Some(String::from("a\n(b,\nc)\n")), None, None);
+        let mut lines: Vec<usize> = Vec::new();
+        while !input.stream.is_empty() {
+            input.get_next_token(true);
+            lines.push(input.line);
+        }
+        assert_eq!(lines, vec![1, 1, 2, 2, 2, 3, 3, 3]);
+    }
+
+    #[test]
+    fn test_needs_more_input() {
+        assert!(!needs_more_input("x = 1\n"));
+        assert!(!needs_more_input("\n"));
+        assert!(needs_more_input("x = (1,\n"));
+        assert!(!needs_more_input("x = (1,\n2)\n"));
+        assert!(needs_more_input("x = 1 + \\\n"));
+        assert!(needs_more_input("s = \"\"\"abc\n"));
+        assert!(needs_more_input("s = r'''abc\n"));
+        assert!(!needs_more_input("s = \"\"\"abc\ndef\"\"\"\n"));
+        assert!(needs_more_input("if x: pass\n"));
+        assert!(needs_more_input("for i in x:\n"));
+        assert!(needs_more_input("for i in x:\n    print(i)\n"));
+        assert!(!needs_more_input("for i in x:\n    print(i)\n\n"));
+        assert!(!needs_more_input("for i in x:\n    print(i)\n   \n"));
+        assert!(needs_more_input("if 1:\n    # c\n"));
+        assert!(needs_more_input("if 1:\n    # c\n    y = 3\n"));
+        assert!(!needs_more_input("if 1:\n    # c\n    y = 3\n\n"));
+        assert!(needs_more_input("x = {1:\n"));
+        assert!(needs_more_input("@decorator\n"));
+        assert!(needs_more_input("@decorator\ndef f(): pass\n"));
+        assert!(!needs_more_input("@decorator\ndef f(): pass\n\n"));
+        // errors are left for the parser
+        assert!(!needs_more_input("1 +\n"));
+        assert!(!needs_more_input("'abc\n"));
+        assert!(!needs_more_input("def f(:\n"));
+        assert!(!needs_more_input("x = [1, 2 3\n"));
+        assert!(!needs_more_input("if x +:\n"));
+    }
 }
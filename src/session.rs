@@ -0,0 +1,642 @@
+use crate::ast::{Arg, Expr, Stmt, StmtKind, UnaryOp};
+use crate::bytes;
+use crate::dict::{new_dict, new_set, Dict};
+use crate::interpreter::{unary_op, EvalResult, Interpreter};
+use crate::modules::collections::is_identifier;
+use crate::scanner::KEYWORDS;
+use crate::value::{new_list, new_tuple, repr_str, Args, Exception, Value};
+use std::fs;
+use std::path::Path;
+use std::rc::Rc;
+
+/* Saving the global variables of an interactive session to a file, and
+ * loading them into a later one: `state save <path>` and
+ * `state load <path>` at the prompt.
+ *
+ * A session file is Python source with one statement per variable:
+ *
+ *     # rebar session
+ *     import os.path as p
+ *     count = 3
+ *     ratio = float('inf')
+ *     items = [1, (2.5, 'text'), {'a': None}, range(0, 10, 2)]
+ *     counter = counters.Counter(5)
+ *
+ * - None, booleans, ints, floats, strings, bytes, lists, tuples, dicts,
+ *   sets and ranges are literals; floats that aren't finite are
+ *   float('inf'), float('-inf') and float('nan'), the int with no positive
+ *   counterpart is int('-9223372036854775808'), and a bytearray is
+ *   bytearray(b'...')
+ * - modules are imports
+ * - instances of classes that define __reduce__, like the collections
+ *   types, are a call: __reduce__() returns (class, args), where class is
+ *   a class of an imported module, and loading calls it with args
+ * - other instances of class statements' classes are
+ *   module.C.__new__(module.C, attr=value, ...), which loading makes
+ *   without running __init__ and gives those attributes; the class has to
+ *   be defined (in __main__, say) before loading
+ * - classes inside other values, like a defaultdict's default_factory,
+ *   are module.C
+ *
+ * Functions, classes and builtins are code rather than state, so they are
+ * left out and reported. Anything else (native instances without
+ * __reduce__, iterators, values that contain themselves) can't be saved,
+ * and saving raises TypeError without writing the file. Values shared
+ * between variables are saved separately, so they are loaded as copies.
+ *
+ * Loading only accepts these statements: it imports modules and calls the
+ * classes the file names and the builtins above, but runs no other code;
+ * naming a function, like os.remove, is an error. Each line is read on
+ * its own, so one that doesn't load (a module that's gone, or a line
+ * edited by hand) is reported and the others are still assigned.
+ */
+
+const HEADER: &str = "# rebar session\n";
+
+impl Interpreter {
+    /* Write the global variables to path, returning the names of those
+     * left out
+     */
+    pub fn save_session(&mut self, path: &Path) -> Result<Vec<String>, Exception> {
+        self.check_access("PermissionError", "file access")?;
+        let mut vars: Vec<(String, Value)> = self.globals.vars.borrow().clone().into_iter().collect();
+        vars.sort_by(|(left, _), (right, _)| left.cmp(right));
+
+        let mut out = String::from(HEADER);
+        let mut skipped: Vec<String> = Vec::new();
+        for (name, value) in vars {
+            if name.starts_with("__") && name.ends_with("__") {
+                continue;
+            }
+            match &value {
+                Value::Function(_) | Value::Class(_) | Value::Builtin(_) => skipped.push(name),
+                Value::Module(module) if module.name == name => out.push_str(&format!("import {}\n", name)),
+                Value::Module(module) => out.push_str(&format!("import {} as {}\n", module.name, name)),
+                _ => {
+                    let mut text = String::new();
+                    self.write_value(&value, &mut text, &mut Vec::new()).map_err(|err| {
+                        let message = format!("cannot save '{}': {}", name, err.message);
+                        Exception::new(&err.kind, &message)
+                    })?;
+                    out.push_str(&format!("{} = {}\n", name, text));
+                }
+            };
+        }
+        fs::write(path, out).map_err(|err| file_error("write", path, err))?;
+        return Ok(skipped);
+    }
+
+    /* value as an expression that load_session() can read. containing is
+     * the containers being written, to detect ones that contain themselves.
+     */
+    fn write_value(&mut self, value: &Value, out: &mut String, containing: &mut Vec<usize>) -> Result<(), Exception> {
+        match value {
+            Value::NoneT | Value::Bool(_) | Value::Int(_) | Value::BigInt(_) | Value::Range(..) => {
+                out.push_str(&value.repr())
+            }
+            Value::Float(val) if val.is_nan() => out.push_str("float('nan')"),
+            Value::Float(val) if val.is_infinite() => {
+                out.push_str(if *val > 0.0 { "float('inf')" } else { "float('-inf')" })
+            }
+            Value::Float(_) => out.push_str(&value.repr()),
+            Value::Complex(real, imag) => {
+                out.push_str("complex(");
+                self.write_value(&Value::Float(*real), out, containing)?;
+                out.push_str(", ");
+                self.write_value(&Value::Float(*imag), out, containing)?;
+                out.push(')');
+            }
+            Value::Str(val) => out.push_str(&repr_str(val)),
+            Value::Bytes(_) | Value::ByteArray(_) => out.push_str(&value.repr()),
+            Value::Set(items) | Value::FrozenSet(items) => {
+                let frozen = matches!(value, Value::FrozenSet(_));
+                let items = items.borrow().keys();
+                match (frozen, items.is_empty()) {
+                    (false, true) => out.push_str("set()"),
+                    (true, true) => out.push_str("frozenset()"),
+                    (false, false) => self.write_items("{", &items, "}", out, containing)?,
+                    (true, false) => self.write_items("frozenset({", &items, "})", out, containing)?,
+                };
+            }
+            Value::List(_) | Value::Tuple(_) | Value::Dict(_) => {
+                if containing.contains(&value.identity()) {
+                    let message = format!("the {} contains itself", value.type_name());
+                    return Err(Exception::new("TypeError", &message));
+                }
+                containing.push(value.identity());
+                self.write_container(value, out, containing)?;
+                containing.pop();
+            }
+            Value::Class(_) => self.write_global(value, out)?,
+            Value::Object(_) => {
+                if containing.contains(&value.identity()) {
+                    let message = format!("the '{}' object contains itself", value.type_name());
+                    return Err(Exception::new("TypeError", &message));
+                }
+                containing.push(value.identity());
+                self.write_object(value, out, containing)?;
+                containing.pop();
+            }
+            _ => {
+                let message = format!("'{}' object can't be saved", value.type_name());
+                return Err(Exception::new("TypeError", &message));
+            }
+        };
+        return Ok(());
+    }
+
+    /* An instance as a call of the class __reduce__() gives, or else, for
+     * instances of class statements, as module.C.__new__(module.C) with
+     * its attributes as keywords
+     */
+    fn write_object(&mut self, value: &Value, out: &mut String, containing: &mut Vec<usize>) -> Result<(), Exception> {
+        let object = match value {
+            Value::Object(object) => object.clone(),
+            _ => return Ok(()),
+        };
+        let reduced = match self.call_special(value, "__reduce__", vec![]) {
+            Some(reduced) => reduced?,
+            None if object.native.is_none() && self.is_plain_class(&object.class) => {
+                let class = Value::Class(object.class.clone());
+                let mut name = String::new();
+                self.write_global(&class, &mut name)?;
+                out.push_str(&format!("{}.__new__({}", name, name));
+                let mut attrs: Vec<(String, Value)> = object.attrs.borrow().clone().into_iter().collect();
+                attrs.sort_by(|(left, _), (right, _)| left.cmp(right));
+                for (attr, value) in attrs {
+                    if !is_identifier(&attr) || KEYWORDS.contains(&attr.as_str()) {
+                        let message = format!("attribute name {} isn't an identifier", repr_str(&attr));
+                        return Err(Exception::new("TypeError", &message));
+                    }
+                    out.push_str(&format!(", {}=", attr));
+                    self.write_value(&value, out, containing)?;
+                }
+                out.push(')');
+                return Ok(());
+            }
+            None => {
+                let message = format!("'{}' object has no __reduce__", object.class.name);
+                return Err(Exception::new("TypeError", &message));
+            }
+        };
+        let (class, args) = match &reduced {
+            Value::Tuple(items) if items.len() == 2 && matches!(items[0], Value::Class(_)) => (&items[0], &items[1]),
+            _ => return Err(Exception::new("TypeError", "__reduce__ must return a (class, args) tuple")),
+        };
+        self.write_global(class, out)?;
+        match args {
+            Value::Tuple(args) => return self.write_items("(", args, ")", out, containing),
+            _ => return Err(Exception::new("TypeError", "the args __reduce__ returns must be a tuple")),
+        }
+    }
+
+    /* a class as module.name
+     */
+    fn write_global(&mut self, value: &Value, out: &mut String) -> Result<(), Exception> {
+        let (module, name) = self.global_name(value).ok_or_else(|| {
+            let message = format!("{} isn't an attribute of any imported module", value.repr());
+            return Exception::new("TypeError", &message);
+        })?;
+        out.push_str(&format!("{}.{}", module, name));
+        return Ok(());
+    }
+
+    fn write_container(&mut self, value: &Value, out: &mut String, containing: &mut Vec<usize>) -> Result<(), Exception> {
+        match value {
+            Value::List(items) => {
+                let items = items.borrow().clone();
+                return self.write_items("[", &items, "]", out, containing);
+            }
+            Value::Tuple(items) if items.len() == 1 => return self.write_items("(", items, ",)", out, containing),
+            Value::Tuple(items) => return self.write_items("(", items, ")", out, containing),
+            Value::Dict(dict) => {
+                let items = dict.borrow().items();
+                out.push('{');
+                for (i, (key, value)) in items.iter().enumerate() {
+                    if i > 0 {
+                        out.push_str(", ");
+                    }
+                    self.write_value(key, out, containing)?;
+                    out.push_str(": ");
+                    self.write_value(value, out, containing)?;
+                }
+                out.push('}');
+                return Ok(());
+            }
+            _ => return Ok(()),
+        }
+    }
+
+    fn write_items(
+        &mut self,
+        open: &str,
+        items: &[Value],
+        close: &str,
+        out: &mut String,
+        containing: &mut Vec<usize>,
+    ) -> Result<(), Exception> {
+        out.push_str(open);
+        for (i, item) in items.iter().enumerate() {
+            if i > 0 {
+                out.push_str(", ");
+            }
+            self.write_value(item, out, containing)?;
+        }
+        out.push_str(close);
+        return Ok(());
+    }
+
+    /* The module and name a class or function can be found by: the
+     * module its __module__ or globals name, if the name there is the same
+     * object, or else the imported module that has it, like pickle's
+     * whichmodule(), for builtins
+     */
+    pub fn global_name(&self, value: &Value) -> Option<(String, String)> {
+        let named = match value {
+            Value::Class(class) => match class.attrs.borrow().get("__module__") {
+                Some(Value::Str(module)) => Some((module.clone(), String::from(class.bare_name()))),
+                _ => None,
+            },
+            Value::Function(function) => Some((function.globals.name.clone(), function.def.name.clone())),
+            Value::Builtin(_) => None,
+            _ => return None,
+        };
+        if let Some((module, name)) = named {
+            let found = self.modules.get(&module)?.vars.borrow().get(&name).cloned()?;
+            if found.identity() != value.identity() {
+                return None;
+            }
+            return Some((module, name));
+        }
+        let mut names: Vec<&String> = self.modules.keys().filter(|name| *name != "__main__").collect();
+        names.sort();
+        for module_name in names {
+            let vars = self.modules[module_name].vars.borrow();
+            let mut found: Vec<&String> = vars
+                .iter()
+                .filter(|(_, var)| var.identity() == value.identity())
+                .map(|(name, _)| name)
+                .collect();
+            found.sort();
+            if let Some(name) = found.first() {
+                return Some((module_name.clone(), (*name).clone()));
+            }
+        }
+        return None;
+    }
+
+    /* Read a file save_session() wrote into the global variables,
+     * returning how many it set and the errors of the lines it couldn't
+     * read, as "Kind: message (file, line N)"
+     */
+    pub fn load_session(&mut self, path: &Path) -> Result<(usize, Vec<String>), Exception> {
+        self.check_access("PermissionError", "file access")?;
+        let source = fs::read_to_string(path).map_err(|err| file_error("read", path, err))?;
+        let filename = path.display().to_string();
+
+        let mut vars: Vec<(String, Value)> = Vec::new();
+        let mut problems: Vec<String> = Vec::new();
+        for (index, line) in source.lines().enumerate() {
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let read = self.compile_at(line, &filename, index).and_then(|code| self.read_line(&code, path));
+            match read {
+                Ok(line_vars) => vars.extend(line_vars),
+                Err(err) if err.kind == "SyntaxError" => problems.push(err.to_string()),
+                Err(err) => problems.push(format!("{} ({}, line {})", err, filename, index + 1)),
+            };
+        }
+        let count = vars.len();
+        for (name, value) in vars {
+            self.globals.set(&name, value);
+        }
+        return Ok((count, problems));
+    }
+
+    /* The variables one line of a session file sets
+     */
+    fn read_line(&mut self, code: &[Stmt], path: &Path) -> Result<Vec<(String, Value)>, Exception> {
+        let mut vars: Vec<(String, Value)> = Vec::new();
+        for stmt in code {
+            match &stmt.kind {
+                StmtKind::Import(names) => {
+                    for (name, alias) in names {
+                        let module = self.import_module(name)?;
+                        match alias {
+                            Some(alias) => vars.push((alias.clone(), Value::Module(module))),
+                            None => {
+                                let top = name.split('.').next().unwrap();
+                                vars.push((String::from(top), Value::Module(self.import_module(top)?)));
+                            }
+                        };
+                    }
+                }
+                StmtKind::Assign(targets, expr) => match &targets[..] {
+                    [Expr::Name(name)] => vars.push((name.clone(), self.read_value(expr, path)?)),
+                    _ => return Err(invalid_session(path, "only imports and assignments to names are allowed")),
+                },
+                _ => return Err(invalid_session(path, "only imports and assignments to names are allowed")),
+            };
+        }
+        return Ok(vars);
+    }
+
+    /* The value of an expression write_value() wrote
+     */
+    fn read_value(&mut self, expr: &Expr, path: &Path) -> EvalResult {
+        match expr {
+            Expr::NoneT => return Ok(Value::NoneT),
+            Expr::Bool(val) => return Ok(Value::Bool(*val)),
+            Expr::Int(val) => return Ok(Value::Int(*val)),
+            Expr::BigInt(val) => return Ok(Value::BigInt(val.clone())),
+            Expr::Float(val) => return Ok(Value::Float(*val)),
+            Expr::Imaginary(val) => return Ok(Value::Complex(0.0, *val)),
+            Expr::Str(val) => return Ok(Value::Str(val.clone())),
+            Expr::Bytes(val) => return Ok(bytes::new_bytes(val.clone())),
+            // negative numbers, unless the optimizer has folded them
+            Expr::UnaryOp(op @ (UnaryOp::Neg | UnaryOp::Pos), operand) if matches!(**operand, Expr::Int(_) | Expr::BigInt(_) | Expr::Float(_)) => {
+                return unary_op(*op, &self.read_value(operand, path)?);
+            }
+            Expr::List(items) => return Ok(new_list(self.read_values(items, path)?)),
+            Expr::Tuple(items) => return Ok(new_tuple(self.read_values(items, path)?)),
+            Expr::Dict(items) => {
+                let mut dict = Dict::build_new();
+                for (key, value) in items {
+                    dict.set(self.read_value(key, path)?, self.read_value(value, path)?)?;
+                }
+                return Ok(new_dict(dict));
+            }
+            Expr::Set(items) => {
+                let mut set = Dict::build_new();
+                for item in items {
+                    set.set(self.read_value(item, path)?, Value::NoneT)?;
+                }
+                return Ok(new_set(set, false));
+            }
+            Expr::Attribute(..) => return self.read_class(expr, path),
+            Expr::Call(func, args) => {
+                let mut values: Vec<Value> = Vec::new();
+                let mut keywords: Vec<(String, Value)> = Vec::new();
+                for arg in args {
+                    match arg {
+                        Arg::Positional(expr) => values.push(self.read_value(expr, path)?),
+                        Arg::Keyword(name, expr) => keywords.push((name.clone(), self.read_value(expr, path)?)),
+                        _ => return Err(invalid_session(path, "calls can't unpack arguments")),
+                    };
+                }
+                let builtins = ["bytearray", "complex", "float", "frozenset", "int", "range", "set"];
+                let func = match &**func {
+                    Expr::Name(name) if builtins.contains(&name.as_str()) => self.builtins.get(name).unwrap(),
+                    Expr::Attribute(class, attr) if attr == "__new__" => {
+                        return self.read_instance(class, values, keywords, path);
+                    }
+                    Expr::Attribute(..) => self.read_class(func, path)?,
+                    _ => return Err(invalid_session(path, "only classes of modules can be called")),
+                };
+                return self.call(&func, Args { positional: values, keywords });
+            }
+            _ => return Err(invalid_session(path, "values must be literals or calls")),
+        }
+    }
+
+    fn read_values(&mut self, exprs: &[Expr], path: &Path) -> Result<Vec<Value>, Exception> {
+        let mut values: Vec<Value> = Vec::new();
+        for expr in exprs {
+            values.push(self.read_value(expr, path)?);
+        }
+        return Ok(values);
+    }
+
+    /* module.C.__new__(module.C, attr=value, ...): an instance of a class
+     * statement's class, with those attributes and without running
+     * __init__
+     */
+    fn read_instance(&mut self, class: &Expr, args: Vec<Value>, attrs: Vec<(String, Value)>, path: &Path) -> EvalResult {
+        let class = match (&self.read_class(class, path)?, &args[..]) {
+            (Value::Class(class), [Value::Class(arg)]) if Rc::ptr_eq(class, arg) && self.is_plain_class(class) => class.clone(),
+            _ => return Err(invalid_session(path, "__new__ only makes instances of the class it is called on")),
+        };
+        let object = self.new_object(&class, &[]);
+        for (attr, value) in attrs {
+            self.set_attr(&object, &attr, value)?;
+        }
+        return Ok(object);
+    }
+
+    /* a class, as module.C
+     */
+    fn read_class(&mut self, expr: &Expr, path: &Path) -> EvalResult {
+        match self.read_global(expr, path)? {
+            class @ Value::Class(_) => return Ok(class),
+            _ => return Err(invalid_session(path, "only classes can be called")),
+        }
+    }
+
+    /* package.module.name: import package.module and get name from it
+     */
+    fn read_global(&mut self, expr: &Expr, path: &Path) -> EvalResult {
+        let mut parts: Vec<&str> = Vec::new();
+        let mut expr = expr;
+        while let Expr::Attribute(object, attr) = expr {
+            parts.insert(0, attr);
+            expr = object;
+        }
+        match expr {
+            Expr::Name(name) => parts.insert(0, name),
+            _ => return Err(invalid_session(path, "only classes of modules can be called")),
+        };
+        let name = parts.pop().unwrap();
+        let module = self.import_module(&parts.join("."))?;
+        return self.get_attr(&Value::Module(module), name);
+    }
+}
+
+fn file_error(action: &str, path: &Path, err: std::io::Error) -> Exception {
+    let message = format!("can't {} file '{}': {}", action, path.display(), err);
+    return Exception::new("OSError", &message);
+}
+
+fn invalid_session(path: &Path, problem: &str) -> Exception {
+    let message = format!("invalid session file '{}': {}", path.display(), problem);
+    return Exception::new("ValueError", &message);
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::embed;
+    use crate::native::{instance, NativeClass, NativeModule};
+    use crate::value::{new_tuple, Value};
+    use std::env;
+    use std::fs;
+
+    struct Point {
+        x: i64,
+        y: i64,
+    }
+
+    fn points() -> NativeModule {
+        let mut point: NativeClass<Point> = NativeClass::build_new("Point");
+        point.constructor(|x: i64, y: i64| Point { x, y });
+        point.raw_method("__reduce__", |_, args| {
+            let this = instance::<Point>("Point", "__reduce__", &args)?;
+            let class = match &args.positional[0] {
+                Value::Object(object) => Value::Class(object.class.clone()),
+                _ => unreachable!(),
+            };
+            return Ok(new_tuple(vec![class, new_tuple(vec![Value::Int(this.x), Value::Int(this.y)])]));
+        });
+        point.getter("x", |this: &Point| this.x);
+        let mut module = NativeModule::build_new("points");
+        module.class(point);
+        return module;
+    }
+
+    #[test]
+    fn test_session() {
+        let path = env::temp_dir().join(format!("rebar-session-{}.py", std::process::id()));
+        let mut python = embed::Interpreter::build_new();
+        python.register_module(points());
+        let source = "
+import sys as system
+import points
+n = -3
+f = float('-inf')
+data = {'a': [1, 2.5, (True, None)], 'b': ('x', b'\\xff', bytearray(b'y')), 'c': range(0, 10, 2)}
+def g():
+    pass
+p = points.Point(1, 2)
+it = iter([1])
+";
+        python.exec(source).unwrap();
+        let err = python.save_session(&path).unwrap_err();
+        assert_eq!(err.to_string(), "TypeError: cannot save 'it': 'list_iterator' object can't be saved");
+        python.exec("class Call:\n    def __reduce__(self):\n        return (len, ('abc',))\nit = Call()\n").unwrap();
+        let err = python.save_session(&path).unwrap_err();
+        assert_eq!(err.to_string(), "TypeError: cannot save 'it': __reduce__ must return a (class, args) tuple");
+        python.exec("it = None\ndel Call\n").unwrap();
+        assert_eq!(python.save_session(&path).unwrap(), vec![String::from("g")]);
+        let saved = fs::read_to_string(&path).unwrap();
+        let expected = "# rebar session
+data = {'a': [1, 2.5, (True, None)], 'b': ('x', b'\\xff', bytearray(b'y')), 'c': range(0, 10, 2)}
+f = float('-inf')
+it = None
+n = -3
+p = points.Point(1, 2)
+import points
+import sys as system
+";
+        assert_eq!(saved, expected);
+
+        let mut loaded = embed::Interpreter::build_new();
+        loaded.register_module(points());
+        assert_eq!(loaded.load_session(&path).unwrap(), (7, vec![]));
+        assert_eq!(loaded.eval_as::<i64>("p.x").unwrap(), 1);
+        assert_eq!(loaded.eval("data").unwrap().repr(), python.eval("data").unwrap().repr());
+        assert!(loaded.eval_as::<bool>("f == float('-inf') and n == -3 and system.path is not None").unwrap());
+
+        fs::write(&path, "x = 1 + y\n").unwrap();
+        let (count, problems) = loaded.load_session(&path).unwrap();
+        assert_eq!(count, 0);
+        assert!(problems[0].ends_with(&format!("values must be literals or calls ({}, line 1)", path.display())), "{:?}", problems);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let path = env::temp_dir().join(format!("rebar-session-values-{}.py", std::process::id()));
+        let mut python = embed::Interpreter::build_new();
+        let source = "
+low = -9223372036854775807 - 1
+high = 9223372036854775807
+big = -2**64
+nested = [low, (low,), {low: -0.0}]
+s = {1, 'a', (2, 3)}
+e = set()
+fs = frozenset({frozenset(), 1})
+c = complex(-1.5, float('nan'))
+";
+        python.exec(source).unwrap();
+        python.save_session(&path).unwrap();
+        let saved = fs::read_to_string(&path).unwrap();
+        assert!(saved.contains("low = -9223372036854775808\n") && saved.contains("big = -18446744073709551616\n"), "{}", saved);
+        let mut loaded = embed::Interpreter::build_new();
+        assert_eq!(loaded.load_session(&path).unwrap(), (8, vec![]));
+        for name in ["low", "high", "big", "nested", "s", "e", "fs", "c"] {
+            assert_eq!(loaded.eval(name).unwrap().repr(), python.eval(name).unwrap().repr(), "{}", name);
+        }
+        assert!(loaded.eval_as::<bool>("low == -9223372036854775807 - 1 and type(e) is set and type(fs) is frozenset").unwrap());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_instances() {
+        let path = env::temp_dir().join(format!("rebar-session-instances-{}.py", std::process::id()));
+        let classes = "
+import collections
+class Plain:
+    def __init__(self, x):
+        self.x = x
+        self.runs = 1
+";
+        let mut python = embed::Interpreter::build_new();
+        python.exec(classes).unwrap();
+        let source = "
+p = Plain([Plain(2)])
+counts = collections.defaultdict(list, a=[1])
+others = [collections.OrderedDict(b=2, a=1), collections.Counter('aab'), collections.deque([1, 2], 3)]
+chain = collections.ChainMap({'a': 1}, {'b': 2})
+";
+        python.exec(source).unwrap();
+        python.save_session(&path).unwrap();
+        let saved = fs::read_to_string(&path).unwrap();
+        assert!(saved.contains("p = __main__.Plain.__new__(__main__.Plain, runs=1, x=[__main__.Plain.__new__(__main__.Plain, runs=1, x=2)])\n"), "{}", saved);
+        assert!(saved.contains("counts = collections.defaultdict(builtins.list, {'a': [1]})\n"), "{}", saved);
+
+        let mut loaded = embed::Interpreter::build_new();
+        loaded.exec(classes).unwrap();
+        assert_eq!(loaded.load_session(&path).unwrap(), (5, vec![]));
+        for name in ["p.x[0].x", "counts", "others", "chain"] {
+            assert_eq!(loaded.eval(name).unwrap().repr(), python.eval(name).unwrap().repr(), "{}", name);
+        }
+        assert!(loaded.eval_as::<bool>("type(p) is Plain and p.runs == 1 and counts['new'] == []").unwrap());
+
+        python.exec("p.me = p\n").unwrap();
+        let err = python.save_session(&path).unwrap_err();
+        assert_eq!(err.to_string(), "TypeError: cannot save 'p': the 'Plain' object contains itself");
+        fs::write(&path, "x = collections.deque.__new__(collections.deque)\n").unwrap();
+        let (_, problems) = loaded.load_session(&path).unwrap();
+        assert!(problems[0].contains("__new__ only makes instances of the class it is called on"), "{:?}", problems);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_partial_load() {
+        // lines that don't load are reported, and the rest still are
+        let path = env::temp_dir().join(format!("rebar-session-partial-{}.py", std::process::id()));
+        let source = "# rebar session\na = 1\nb = 1 +\nimport no_such_module\nc = [2, print('hi')]\nd = 'kept'\n";
+        fs::write(&path, source).unwrap();
+        let mut loaded = embed::Interpreter::build_new();
+        let (count, problems) = loaded.load_session(&path).unwrap();
+        assert_eq!(count, 2);
+        let at = |line: usize| format!("({}, line {})", path.display(), line);
+        assert_eq!(problems.len(), 3, "{:?}", problems);
+        assert!(problems[0].starts_with("SyntaxError: ") && problems[0].ends_with(&at(3)), "{}", problems[0]);
+        assert_eq!(problems[1], format!("ModuleNotFoundError: No module named 'no_such_module' {}", at(4)));
+        assert!(problems[2].ends_with(&format!("only classes of modules can be called {}", at(5))), "{}", problems[2]);
+        assert_eq!(loaded.eval("(a, d)").unwrap().repr(), "(1, 'kept')");
+
+        // only classes are called, not any function a module has
+        let victim = env::temp_dir().join(format!("rebar-session-victim-{}", std::process::id()));
+        fs::write(&victim, "").unwrap();
+        let source = format!("import os\nx = os.remove({:?})\ny = os.path.join('a', 'b')\n", victim.display().to_string());
+        fs::write(&path, source).unwrap();
+        let (count, problems) = loaded.load_session(&path).unwrap();
+        assert_eq!(count, 1);
+        assert!(problems[0].ends_with(&format!("only classes can be called {}", at(2))), "{}", problems[0]);
+        assert!(problems[1].ends_with(&format!("only classes can be called {}", at(3))), "{}", problems[1]);
+        assert!(victim.exists());
+        fs::remove_file(&victim).unwrap();
+        fs::remove_file(&path).unwrap();
+    }
+}
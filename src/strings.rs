@@ -0,0 +1,1061 @@
+use crate::bytes;
+use crate::dict::{new_dict, Dict};
+use crate::format::{self, Names};
+use crate::interpreter::{as_int, not_an_int, slice_index, EvalResult, Interpreter};
+use crate::limits::TICK_BYTES;
+use crate::value::{new_list, new_tuple, Args, Builtin, BuiltinFn, Class, Exception, Value};
+use regex::Regex;
+use std::convert::TryFrom;
+use std::rc::Rc;
+
+/* The methods of str, which are functions on the str class.
+ *
+ * Positions are in characters (code points), as in Python. Character
+ * classes come from Rust's char methods, and from the regex crate's
+ * Unicode tables where those don't match Python's: isdecimal() is
+ * category Nd, and isdigit() adds the superscript and circled digits.
+ * Case mapping has no title case of its own, so title() uppercases.
+ *
+ * Methods that make a long string from a short one (padding, tabs,
+ * replace) count its memory before building it, and searches of long
+ * strings go a chunk at a time, ticking between chunks.
+ */
+
+fn type_error(message: String) -> Exception {
+    return Exception::new("TypeError", &message);
+}
+
+fn value_error(message: &str) -> Exception {
+    return Exception::new("ValueError", message);
+}
+
+pub fn add_methods(class: &Rc<Class>) {
+    let methods: &[(&str, BuiltinFn)] = &[
+        ("capitalize", capitalize),
+        ("casefold", casefold),
+        ("center", center),
+        ("count", count),
+        ("encode", encode),
+        ("endswith", endswith),
+        ("expandtabs", expandtabs),
+        ("find", find),
+        ("format", format),
+        ("format_map", format_map),
+        ("index", index),
+        ("isalnum", isalnum),
+        ("isalpha", isalpha),
+        ("isascii", isascii),
+        ("isdecimal", isdecimal),
+        ("isdigit", isdigit),
+        ("isidentifier", isidentifier),
+        ("islower", islower),
+        ("isnumeric", isnumeric),
+        ("isprintable", isprintable),
+        ("isspace", isspace),
+        ("istitle", istitle),
+        ("isupper", isupper),
+        ("join", join),
+        ("ljust", ljust),
+        ("lower", lower),
+        ("lstrip", lstrip),
+        ("maketrans", maketrans),
+        ("partition", partition),
+        ("removeprefix", removeprefix),
+        ("removesuffix", removesuffix),
+        ("replace", replace),
+        ("rfind", rfind),
+        ("rindex", rindex),
+        ("rjust", rjust),
+        ("rpartition", rpartition),
+        ("rsplit", rsplit),
+        ("rstrip", rstrip),
+        ("split", split),
+        ("splitlines", splitlines),
+        ("startswith", startswith),
+        ("strip", strip),
+        ("swapcase", swapcase),
+        ("title", title),
+        ("translate", translate),
+        ("upper", upper),
+        ("zfill", zfill),
+    ];
+    for (name, method) in methods {
+        class.attrs.borrow_mut().insert(String::from(*name), Builtin::build_new(name, *method));
+    }
+}
+
+/* the string a method was called on
+ */
+fn this(name: &str, args: &Args) -> Result<String, Exception> {
+    match args.positional.first() {
+        Some(Value::Str(text)) => return Ok(text.clone()),
+        other => {
+            return Err(type_error(format!(
+                "descriptor '{}' for 'str' objects doesn't apply to a '{}' object",
+                name,
+                other.map_or("nothing", Value::type_name)
+            )));
+        }
+    }
+}
+
+/* text * count, for the interpreter and constant folding: OverflowError
+ * if the length doesn't fit, MemoryError if it can't be had
+ */
+pub fn repeat(text: &str, count: i64) -> EvalResult {
+    let data = bytes::repeated(text.as_bytes(), count, "repeated string is too long")?;
+    return Ok(Value::Str(String::from_utf8(data).unwrap()));
+}
+
+/* positional argument index, or the keyword argument name
+ */
+fn optional_arg(args: &mut Args, index: usize, name: &str) -> Option<Value> {
+    let keyword = args.keyword(name);
+    return args.positional.get(index).cloned().or(keyword);
+}
+
+fn str_arg(name: &str, value: &Value) -> Result<String, Exception> {
+    match value {
+        Value::Str(text) => return Ok(text.clone()),
+        other => return Err(type_error(format!("{}() argument must be str, not {}", name, other.type_name()))),
+    }
+}
+
+fn int_arg(value: &Value) -> Result<i64, Exception> {
+    return as_int(value).ok_or_else(|| not_an_int(value));
+}
+
+/* Python's whitespace: Rust's, plus the separator controls \x1c-\x1f
+ */
+fn is_space(c: char) -> bool {
+    return c.is_whitespace() || ('\x1c'..='\x1f').contains(&c);
+}
+
+fn is_cased(c: char) -> bool {
+    return c.is_lowercase() || c.is_uppercase();
+}
+
+fn upper_char(c: char, out: &mut String) {
+    out.extend(c.to_uppercase());
+}
+
+fn lower_char(c: char, out: &mut String) {
+    out.extend(c.to_lowercase());
+}
+
+/* Whether every character of the string matches a Unicode property,
+ * checked with the regex crate's tables, e.g. r"\p{Nd}"
+ */
+fn all_match(text: &str, class: &str) -> bool {
+    thread_local! {
+        static CLASSES: std::cell::RefCell<Vec<(String, Regex)>> = const { std::cell::RefCell::new(Vec::new()) };
+    }
+    if text.is_empty() {
+        return false;
+    }
+    return CLASSES.with(|classes| {
+        let mut classes = classes.borrow_mut();
+        if !classes.iter().any(|(name, _)| name == class) {
+            let regex = Regex::new(&format!("^(?:{})*$", class)).unwrap();
+            classes.push((String::from(class), regex));
+        }
+        let (_, regex) = classes.iter().find(|(name, _)| name == class).unwrap();
+        return regex.is_match(text);
+    });
+}
+
+// the digits with a numeric value besides the decimal ones
+const DIGITS: &str = r"[\p{Nd}\u{b2}\u{b3}\u{b9}\u{2070}\u{2074}-\u{2079}\u{2080}-\u{2089}\u{2460}-\u{2468}\u{2474}-\u{247c}\u{2488}-\u{2490}\u{24ea}\u{24f5}-\u{24fd}\u{24ff}\u{2776}-\u{277e}\u{2780}-\u{2788}\u{278a}-\u{2792}]";
+
+// a range of a string: its first character's position, and its byte
+// offsets
+type CharRange = (usize, usize, usize);
+
+/* The range of characters start and end select, as a slice would, in
+ * character positions, and as byte offsets into text. None if start is
+ * past the end, where not even an empty string is found.
+ */
+fn char_range(text: &str, start: &Option<Value>, end: &Option<Value>) -> Result<Option<CharRange>, Exception> {
+    if let (None | Some(Value::NoneT), None | Some(Value::NoneT)) = (start, end) {
+        // the whole string, without counting its characters
+        return Ok(Some((0, 0, text.len())));
+    }
+    let len = text.chars().count() as i64;
+    let bound = |value: &Option<Value>, default: i64| -> Result<i64, Exception> {
+        match value {
+            None | Some(Value::NoneT) => return Ok(default),
+            Some(value) => {
+                let val = slice_index(value).ok_or_else(|| {
+                    type_error(String::from("slice indices must be integers or None or have an __index__ method"))
+                })?;
+                return Ok(if val < 0 { (val + len).max(0) } else { val });
+            }
+        }
+    };
+    let (start, end) = (bound(start, 0)?, bound(end, len)?.min(len));
+    if start > len {
+        return Ok(None);
+    }
+    let end = end.max(start);
+    let offset = |position: i64| text.char_indices().nth(position as usize).map_or(text.len(), |(offset, _)| offset);
+    return Ok(Some((start as usize, offset(start), offset(end))));
+}
+
+/* The text, substring and byte range of find() and friends: (sub[,
+ * start[, end]])
+ */
+fn search_args(name: &str, args: &Args) -> Result<(String, String, Option<CharRange>), Exception> {
+    args.check(name, 2, 4)?;
+    let text = this(name, args)?;
+    let sub = match &args.positional[1] {
+        Value::Str(sub) => sub.clone(),
+        other => return Err(type_error(format!("must be str, not {}", other.type_name()))),
+    };
+    let range = char_range(&text, &args.positional.get(2).cloned(), &args.positional.get(3).cloned())?;
+    return Ok((text, sub, range));
+}
+
+/* The byte offset of the first (or last) sub in text[from..to], looked
+ * for in chunks of TICK_BYTES, each overlapping the next by the length of
+ * sub, with a tick between them
+ */
+fn find_in(interpreter: &mut Interpreter, text: &str, sub: &str, (from, to): (usize, usize), last: bool) -> Result<Option<usize>, Exception> {
+    if sub.is_empty() {
+        return Ok(Some(if last { to } else { from }));
+    }
+    let (mut start, mut end) = (from, to);
+    loop {
+        if last {
+            let window = text.ceil_char_boundary(end.saturating_sub(TICK_BYTES + sub.len()).max(from));
+            if let Some(offset) = text[window..end].rfind(sub) {
+                return Ok(Some(window + offset));
+            }
+            if window == from {
+                return Ok(None);
+            }
+            end = text.ceil_char_boundary(end - TICK_BYTES);
+        } else {
+            let window = text.floor_char_boundary(start.saturating_add(TICK_BYTES + sub.len()).min(to));
+            if let Some(offset) = text[start..window].find(sub) {
+                return Ok(Some(start + offset));
+            }
+            if window == to {
+                return Ok(None);
+            }
+            start = text.floor_char_boundary(start + TICK_BYTES);
+        }
+        interpreter.tick()?;
+    }
+}
+
+/* the character position of the first (or last) sub in the range
+ */
+fn search(interpreter: &mut Interpreter, name: &str, args: &Args, last: bool) -> Result<Option<usize>, Exception> {
+    let (text, sub, range) = search_args(name, args)?;
+    let (start, from, to) = match range {
+        Some(range) => range,
+        None => return Ok(None),
+    };
+    let found = find_in(interpreter, &text, &sub, (from, to), last)?;
+    return Ok(found.map(|offset| start + text[from..offset].chars().count()));
+}
+
+fn find(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    return Ok(Value::Int(search(interpreter, "find", &args, false)?.map_or(-1, |position| position as i64)));
+}
+
+fn rfind(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    return Ok(Value::Int(search(interpreter, "rfind", &args, true)?.map_or(-1, |position| position as i64)));
+}
+
+fn index(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    match search(interpreter, "index", &args, false)? {
+        Some(position) => return Ok(Value::Int(position as i64)),
+        None => return Err(value_error("substring not found")),
+    }
+}
+
+fn rindex(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    match search(interpreter, "rindex", &args, true)? {
+        Some(position) => return Ok(Value::Int(position as i64)),
+        None => return Err(value_error("substring not found")),
+    }
+}
+
+fn count(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    let (text, sub, range) = search_args("count", &args)?;
+    let (_, mut from, to) = match range {
+        Some(range) => range,
+        None => return Ok(Value::Int(0)),
+    };
+    if sub.is_empty() {
+        return Ok(Value::Int(text[from..to].chars().count() as i64 + 1));
+    }
+    let mut found = 0;
+    let mut next_tick = from + TICK_BYTES;
+    while let Some(offset) = find_in(interpreter, &text, &sub, (from, to), false)? {
+        found += 1;
+        from = offset + sub.len();
+        if from >= next_tick {
+            interpreter.tick()?;
+            next_tick = from + TICK_BYTES;
+        }
+    }
+    return Ok(Value::Int(found));
+}
+
+/* startswith() and endswith(): (prefix[, start[, end]]), where prefix
+ * may be a tuple of strings to try
+ */
+fn affix(name: &str, args: Args, end: bool) -> EvalResult {
+    args.check(name, 2, 4)?;
+    let text = this(name, &args)?;
+    let affixes = match &args.positional[1] {
+        Value::Str(affix) => vec![affix.clone()],
+        Value::Tuple(items) => {
+            let mut affixes = Vec::new();
+            for item in items.iter() {
+                match item {
+                    Value::Str(affix) => affixes.push(affix.clone()),
+                    other => {
+                        return Err(type_error(format!(
+                            "tuple for {} must only contain str, not {}",
+                            name,
+                            other.type_name()
+                        )));
+                    }
+                }
+            }
+            affixes
+        }
+        other => {
+            return Err(type_error(format!(
+                "{} first arg must be str or a tuple of str, not {}",
+                name,
+                other.type_name()
+            )));
+        }
+    };
+    let (_, from, to) = match char_range(&text, &args.positional.get(2).cloned(), &args.positional.get(3).cloned())? {
+        Some(range) => range,
+        None => return Ok(Value::Bool(false)),
+    };
+    let text = &text[from..to];
+    let found = affixes.iter().any(|affix| if end { text.ends_with(affix.as_str()) } else { text.starts_with(affix.as_str()) });
+    return Ok(Value::Bool(found));
+}
+
+fn startswith(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    return affix("startswith", args, false);
+}
+
+fn endswith(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    return affix("endswith", args, true);
+}
+
+/* a method taking no arguments, mapping the string to another
+ */
+fn map(name: &str, args: Args, op: fn(&str) -> String) -> EvalResult {
+    args.check(name, 1, 1)?;
+    return Ok(Value::Str(op(&this(name, &args)?)));
+}
+
+fn upper(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    return map("upper", args, str::to_uppercase);
+}
+
+fn lower(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    return map("lower", args, str::to_lowercase);
+}
+
+fn casefold(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    return map("casefold", args, |text| {
+        let mut out = String::new();
+        for c in text.chars() {
+            match c {
+                'ß' | 'ẞ' => out.push_str("ss"),
+                'ς' => out.push('σ'),
+                c => lower_char(c, &mut out),
+            };
+        }
+        return out;
+    });
+}
+
+fn swapcase(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    return map("swapcase", args, |text| {
+        let mut out = String::new();
+        for c in text.chars() {
+            if c.is_uppercase() {
+                lower_char(c, &mut out);
+            } else if c.is_lowercase() {
+                upper_char(c, &mut out);
+            } else {
+                out.push(c);
+            }
+        }
+        return out;
+    });
+}
+
+fn capitalize(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    return map("capitalize", args, |text| {
+        let mut out = String::new();
+        for (index, c) in text.chars().enumerate() {
+            if index == 0 {
+                upper_char(c, &mut out);
+            } else {
+                lower_char(c, &mut out);
+            }
+        }
+        return out;
+    });
+}
+
+/* title(): each word's first cased character upper case, the rest lower,
+ * where a word is a run of cased characters
+ */
+fn title(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    return map("title", args, |text| {
+        let mut out = String::new();
+        let mut previous_cased = false;
+        for c in text.chars() {
+            if previous_cased {
+                lower_char(c, &mut out);
+            } else {
+                upper_char(c, &mut out);
+            }
+            previous_cased = is_cased(c);
+        }
+        return out;
+    });
+}
+
+/* a method taking no arguments that tests the string
+ */
+fn test(name: &str, args: Args, op: fn(&str) -> bool) -> EvalResult {
+    args.check(name, 1, 1)?;
+    return Ok(Value::Bool(op(&this(name, &args)?)));
+}
+
+fn isalnum(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    return test("isalnum", args, |text| !text.is_empty() && text.chars().all(char::is_alphanumeric));
+}
+
+fn isalpha(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    return test("isalpha", args, |text| all_match(text, r"\p{L}"));
+}
+
+fn isascii(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    return test("isascii", args, |text| text.is_ascii());
+}
+
+fn isdecimal(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    return test("isdecimal", args, |text| all_match(text, r"\p{Nd}"));
+}
+
+fn isdigit(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    return test("isdigit", args, |text| all_match(text, DIGITS));
+}
+
+fn isnumeric(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    return test("isnumeric", args, |text| !text.is_empty() && text.chars().all(char::is_numeric));
+}
+
+fn isspace(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    return test("isspace", args, |text| !text.is_empty() && text.chars().all(is_space));
+}
+
+/* isprintable(): no control, format, surrogate, private use, unassigned or
+ * separator characters, except the space
+ */
+fn isprintable(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    return test("isprintable", args, |text| text.is_empty() || all_match(text, r"[^\p{C}\p{Z}]| "));
+}
+
+fn isidentifier(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    return test("isidentifier", args, |text| {
+        let mut chars = text.chars();
+        match chars.next() {
+            Some(first) if first == '_' || all_match(&first.to_string(), r"\p{XID_Start}") => {
+                return chars.as_str().is_empty() || all_match(chars.as_str(), r"\p{XID_Continue}");
+            }
+            _ => return false,
+        }
+    });
+}
+
+fn islower(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    return test("islower", args, |text| text.chars().any(is_cased) && !text.chars().any(char::is_uppercase));
+}
+
+fn isupper(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    return test("isupper", args, |text| text.chars().any(is_cased) && !text.chars().any(char::is_lowercase));
+}
+
+/* istitle(): upper case characters only start words, lower case ones
+ * only continue them, and there's at least one
+ */
+fn istitle(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    return test("istitle", args, |text| {
+        let mut previous_cased = false;
+        let mut any_cased = false;
+        for c in text.chars() {
+            if c.is_uppercase() {
+                if previous_cased {
+                    return false;
+                }
+                previous_cased = true;
+                any_cased = true;
+            } else if c.is_lowercase() {
+                if !previous_cased {
+                    return false;
+                }
+                previous_cased = true;
+                any_cased = true;
+            } else {
+                previous_cased = false;
+            }
+        }
+        return any_cased;
+    });
+}
+
+/* strip(), lstrip() and rstrip(): ([chars]), whitespace by default
+ */
+fn strip_ends(name: &str, mut args: Args, left: bool, right: bool) -> EvalResult {
+    let chars = optional_arg(&mut args, 1, "chars");
+    args.check(name, 1, 2)?;
+    let text = this(name, &args)?;
+    let chars: Option<Vec<char>> = match chars {
+        None | Some(Value::NoneT) => None,
+        Some(Value::Str(ref chars)) => Some(chars.chars().collect()),
+        Some(_) => return Err(type_error(format!("{} arg must be None or str", name))),
+    };
+    let strip = |c: char| match &chars {
+        Some(chars) => chars.contains(&c),
+        None => is_space(c),
+    };
+    let mut result = text.as_str();
+    if left {
+        result = result.trim_start_matches(strip);
+    }
+    if right {
+        result = result.trim_end_matches(strip);
+    }
+    return Ok(Value::Str(String::from(result)));
+}
+
+fn strip(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    return strip_ends("strip", args, true, true);
+}
+
+fn lstrip(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    return strip_ends("lstrip", args, true, false);
+}
+
+fn rstrip(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    return strip_ends("rstrip", args, false, true);
+}
+
+/* split() and rsplit(): (sep=None, maxsplit=-1). With no separator, runs
+ * of whitespace separate, and there are no empty pieces.
+ */
+fn split_by(name: &str, mut args: Args, from_right: bool) -> EvalResult {
+    let sep = optional_arg(&mut args, 1, "sep");
+    let maxsplit = optional_arg(&mut args, 2, "maxsplit");
+    args.check(name, 1, 3)?;
+    let text = this(name, &args)?;
+    let maxsplit = match maxsplit {
+        Some(maxsplit) => int_arg(&maxsplit)?,
+        None => -1,
+    };
+    let limit = if maxsplit < 0 { usize::MAX } else { maxsplit as usize + 1 };
+    let pieces: Vec<String> = match sep {
+        None | Some(Value::NoneT) => split_whitespace(&text, limit, from_right),
+        Some(Value::Str(ref sep)) if sep.is_empty() => return Err(value_error("empty separator")),
+        Some(Value::Str(ref sep)) if from_right => {
+            let mut pieces: Vec<String> = text.rsplitn(limit, sep.as_str()).map(String::from).collect();
+            pieces.reverse();
+            pieces
+        }
+        Some(Value::Str(ref sep)) => text.splitn(limit, sep.as_str()).map(String::from).collect(),
+        Some(other) => return Err(type_error(format!("must be str or None, not {}", other.type_name()))),
+    };
+    return Ok(new_list(pieces.into_iter().map(Value::Str).collect()));
+}
+
+/* Pieces of text between runs of whitespace, at most limit of them; the
+ * last keeps the rest of the text as it is
+ */
+fn split_whitespace(text: &str, limit: usize, from_right: bool) -> Vec<String> {
+    let mut chars: Vec<char> = text.chars().collect();
+    if from_right {
+        chars.reverse();
+    }
+    let mut pieces: Vec<String> = Vec::new();
+    let mut pos = 0;
+    loop {
+        while pos < chars.len() && is_space(chars[pos]) {
+            pos += 1;
+        }
+        if pos == chars.len() {
+            break;
+        }
+        if pieces.len() + 1 == limit {
+            pieces.push(chars[pos..].iter().collect());
+            break;
+        }
+        let start = pos;
+        while pos < chars.len() && !is_space(chars[pos]) {
+            pos += 1;
+        }
+        pieces.push(chars[start..pos].iter().collect());
+    }
+    if from_right {
+        pieces.reverse();
+        for piece in pieces.iter_mut() {
+            *piece = piece.chars().rev().collect();
+        }
+    }
+    return pieces;
+}
+
+fn split(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    return split_by("split", args, false);
+}
+
+fn rsplit(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    return split_by("rsplit", args, true);
+}
+
+/* splitlines(keepends=False): lines end at \n, \r, \r\n, and the other
+ * line boundaries Unicode has
+ */
+fn splitlines(_interpreter: &mut Interpreter, mut args: Args) -> EvalResult {
+    let keepends = optional_arg(&mut args, 1, "keepends").is_some_and(|keepends| keepends.is_truthy());
+    args.check("splitlines", 1, 2)?;
+    let text = this("splitlines", &args)?;
+    let chars: Vec<char> = text.chars().collect();
+    let mut lines: Vec<Value> = Vec::new();
+    let mut start = 0;
+    let mut pos = 0;
+    while pos < chars.len() {
+        let boundary = match chars[pos] {
+            '\r' if chars.get(pos + 1) == Some(&'\n') => 2,
+            '\n' | '\r' | '\x0b' | '\x0c' | '\x1c' | '\x1d' | '\x1e' | '\u{85}' | '\u{2028}' | '\u{2029}' => 1,
+            _ => 0,
+        };
+        if boundary == 0 {
+            pos += 1;
+            continue;
+        }
+        let end = if keepends { pos + boundary } else { pos };
+        lines.push(Value::Str(chars[start..end].iter().collect()));
+        pos += boundary;
+        start = pos;
+    }
+    if start < chars.len() {
+        lines.push(Value::Str(chars[start..].iter().collect()));
+    }
+    return Ok(new_list(lines));
+}
+
+fn join(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("join", 2, 2)?;
+    let sep = this("join", &args)?;
+    let mut pieces: Vec<String> = Vec::new();
+    let mut len = Some(0usize);
+    for (index, item) in interpreter.iterate(&args.positional[1])?.into_iter().enumerate() {
+        match item {
+            Value::Str(ref piece) => {
+                let sep_len = if index > 0 { sep.len() } else { 0 };
+                len = len.and_then(|len| len.checked_add(sep_len)).and_then(|len| len.checked_add(piece.len()));
+                pieces.push(piece.clone());
+            }
+            other => {
+                return Err(type_error(format!(
+                    "sequence item {}: expected str instance, {} found",
+                    index,
+                    other.type_name()
+                )));
+            }
+        }
+    }
+    let mut out = interpreter.allocate_string(len)?;
+    for (index, piece) in pieces.iter().enumerate() {
+        if index > 0 {
+            out.push_str(&sep);
+        }
+        out.push_str(piece);
+    }
+    return Ok(Value::Str(out));
+}
+
+/* Add piece to out, counting its memory first, for the methods that
+ * can't tell how long their result is until they've built it
+ */
+fn append(interpreter: &mut Interpreter, out: &mut String, piece: &str) -> Result<(), Exception> {
+    interpreter.allocate(piece.len())?;
+    out.try_reserve(piece.len()).map_err(|_| Exception::new("MemoryError", ""))?;
+    out.push_str(piece);
+    return Ok(());
+}
+
+/* replace(old, new, count=-1)
+ */
+fn replace(interpreter: &mut Interpreter, mut args: Args) -> EvalResult {
+    let count = optional_arg(&mut args, 3, "count");
+    args.check("replace", 3, 4)?;
+    let text = this("replace", &args)?;
+    let old = str_arg("replace", &args.positional[1])?;
+    let new = str_arg("replace", &args.positional[2])?;
+    let count = match count.map(|count| int_arg(&count)).transpose()? {
+        Some(count) if count >= 0 => count as usize,
+        _ => usize::MAX,
+    };
+    if old.is_empty() {
+        // new goes before each character and at the end
+        let count = count.min(text.chars().count() + 1);
+        let len = new.len().checked_mul(count).and_then(|len| len.checked_add(text.len()));
+        let mut out = interpreter.allocate_string(len)?;
+        for (index, c) in text.chars().enumerate() {
+            if index < count {
+                out.push_str(&new);
+            }
+            out.push(c);
+        }
+        if count > text.chars().count() {
+            out.push_str(&new);
+        }
+        return Ok(Value::Str(out));
+    }
+    let mut out = String::new();
+    let mut from = 0;
+    let mut next_tick = TICK_BYTES;
+    for _ in 0..count {
+        let offset = match find_in(interpreter, &text, &old, (from, text.len()), false)? {
+            Some(offset) => offset,
+            None => break,
+        };
+        append(interpreter, &mut out, &text[from..offset])?;
+        append(interpreter, &mut out, &new)?;
+        from = offset + old.len();
+        if from >= next_tick {
+            interpreter.tick()?;
+            next_tick = from + TICK_BYTES;
+        }
+    }
+    append(interpreter, &mut out, &text[from..])?;
+    return Ok(Value::Str(out));
+}
+
+fn removeprefix(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("removeprefix", 2, 2)?;
+    let text = this("removeprefix", &args)?;
+    let prefix = str_arg("removeprefix", &args.positional[1])?;
+    return Ok(Value::Str(String::from(text.strip_prefix(&prefix).unwrap_or(&text))));
+}
+
+fn removesuffix(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("removesuffix", 2, 2)?;
+    let text = this("removesuffix", &args)?;
+    let suffix = str_arg("removesuffix", &args.positional[1])?;
+    return Ok(Value::Str(String::from(text.strip_suffix(&suffix).unwrap_or(&text))));
+}
+
+/* partition() and rpartition(): (before, sep, after) around the first
+ * (or last) sep, or the whole string and two empty ones
+ */
+fn partition_at(name: &str, args: Args, last: bool) -> EvalResult {
+    args.check(name, 2, 2)?;
+    let text = this(name, &args)?;
+    let sep = str_arg(name, &args.positional[1])?;
+    if sep.is_empty() {
+        return Err(value_error("empty separator"));
+    }
+    let found = if last { text.rfind(&sep) } else { text.find(&sep) };
+    let parts = match found {
+        Some(offset) => [&text[..offset], sep.as_str(), &text[offset + sep.len()..]],
+        None if last => ["", "", text.as_str()],
+        None => [text.as_str(), "", ""],
+    };
+    return Ok(new_tuple(parts.iter().map(|part| Value::Str(String::from(*part))).collect()));
+}
+
+fn partition(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    return partition_at("partition", args, false);
+}
+
+fn rpartition(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    return partition_at("rpartition", args, true);
+}
+
+/* the width and fill character of center(), ljust() and rjust()
+ */
+fn justify_args(name: &str, args: &Args) -> Result<(String, usize, char), Exception> {
+    args.check(name, 2, 3)?;
+    let text = this(name, args)?;
+    let width = int_arg(&args.positional[1])?.max(0) as usize;
+    let fill = match args.positional.get(2) {
+        None => ' ',
+        Some(Value::Str(fill)) if fill.chars().count() == 1 => fill.chars().next().unwrap(),
+        Some(Value::Str(_)) => return Err(type_error(String::from("The fill character must be exactly one character long"))),
+        Some(other) => {
+            return Err(type_error(format!(
+                "The fill character must be a unicode character, not {}",
+                other.type_name()
+            )));
+        }
+    };
+    return Ok((text, width, fill));
+}
+
+/* text padded with left and right fill characters to make width
+ */
+fn justify(interpreter: &mut Interpreter, name: &str, args: Args, left_share: fn(usize, usize) -> usize) -> EvalResult {
+    let (text, width, fill) = justify_args(name, &args)?;
+    let padding = width.saturating_sub(text.chars().count());
+    let left = left_share(padding, width);
+    let len = padding.checked_mul(fill.len_utf8()).and_then(|len| len.checked_add(text.len()));
+    let mut out = interpreter.allocate_string(len)?;
+    out.extend(std::iter::repeat_n(fill, left));
+    out.push_str(&text);
+    out.extend(std::iter::repeat_n(fill, padding - left));
+    return Ok(Value::Str(out));
+}
+
+fn ljust(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    return justify(interpreter, "ljust", args, |_, _| 0);
+}
+
+fn rjust(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    return justify(interpreter, "rjust", args, |padding, _| padding);
+}
+
+/* center() gives an odd character of padding to the left when the width
+ * is odd, as CPython does
+ */
+fn center(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    return justify(interpreter, "center", args, |padding, width| padding / 2 + (padding & width & 1));
+}
+
+/* encode(encoding='utf-8', errors='strict'), see bytes.rs for the codecs
+ */
+fn encode(_interpreter: &mut Interpreter, mut args: Args) -> EvalResult {
+    let (encoding, errors) = bytes::codec_args("encode", &mut args, 1)?;
+    args.check("encode", 1, 3)?;
+    let text = this("encode", &args)?;
+    return Ok(bytes::new_bytes(bytes::encode(&text, &encoding, &errors)?));
+}
+
+/* zfill(width): zeros on the left, after any sign
+ */
+fn zfill(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("zfill", 2, 2)?;
+    let text = this("zfill", &args)?;
+    let width = int_arg(&args.positional[1])?.max(0) as usize;
+    let zeros = width.saturating_sub(text.chars().count());
+    let mut out = interpreter.allocate_string(text.len().checked_add(zeros))?;
+    let sign = if text.starts_with(['+', '-']) { 1 } else { 0 };
+    out.push_str(&text[..sign]);
+    out.extend(std::iter::repeat_n('0', zeros));
+    out.push_str(&text[sign..]);
+    return Ok(Value::Str(out));
+}
+
+/* expandtabs(tabsize=8): each tab spaces to the next multiple of tabsize
+ * columns, counting from the start of the line
+ */
+fn expandtabs(interpreter: &mut Interpreter, mut args: Args) -> EvalResult {
+    let tabsize = optional_arg(&mut args, 1, "tabsize");
+    args.check("expandtabs", 1, 2)?;
+    let text = this("expandtabs", &args)?;
+    let tabsize = match tabsize {
+        Some(tabsize) => int_arg(&tabsize)?,
+        None => 8,
+    };
+    let mut out = String::new();
+    let mut column: i64 = 0;
+    for (offset, c) in text.char_indices() {
+        if offset % TICK_BYTES == 0 && offset > 0 {
+            interpreter.tick()?;
+        }
+        match c {
+            '\t' if tabsize > 0 => {
+                let spaces = tabsize - column % tabsize;
+                interpreter.allocate(spaces as usize)?;
+                out.try_reserve(spaces as usize).map_err(|_| Exception::new("MemoryError", ""))?;
+                out.extend(std::iter::repeat_n(' ', spaces as usize));
+                column += spaces;
+            }
+            '\t' => (),
+            '\n' | '\r' => {
+                out.push(c);
+                column = 0;
+            }
+            c => {
+                out.push(c);
+                column += 1;
+            }
+        };
+    }
+    return Ok(Value::Str(out));
+}
+
+/* str.maketrans(x[, y[, z]]): a table for translate() from a dict of
+ * characters (or their ordinals), or from the characters of x to those
+ * of y, with z's characters deleted
+ */
+fn maketrans(_interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("maketrans", 1, 3)?;
+    let mut table = Dict::build_new();
+    let ordinal = |c: char| Value::Int(c as i64);
+    if args.positional.len() == 1 {
+        let dict = match &args.positional[0] {
+            Value::Dict(dict) => dict.borrow().items(),
+            _ => return Err(type_error(String::from("if you give only one argument to maketrans it must be a dict"))),
+        };
+        for (key, value) in dict {
+            let key = match &key {
+                Value::Str(text) if text.chars().count() == 1 => ordinal(text.chars().next().unwrap()),
+                Value::Str(_) => return Err(value_error("string keys in translate table must be of length 1")),
+                _ if as_int(&key).is_some() => Value::Int(as_int(&key).unwrap()),
+                _ => return Err(type_error(String::from("keys in translate table must be strings or integers"))),
+            };
+            table.set(key, value)?;
+        }
+        return Ok(new_dict(table));
+    }
+    let from = str_arg("maketrans", &args.positional[0])?;
+    let to = str_arg("maketrans", &args.positional[1])?;
+    if from.chars().count() != to.chars().count() {
+        return Err(value_error("the first two maketrans arguments must have equal length"));
+    }
+    for (from, to) in from.chars().zip(to.chars()) {
+        table.set(ordinal(from), ordinal(to))?;
+    }
+    if let Some(delete) = args.positional.get(2) {
+        for c in str_arg("maketrans", delete)?.chars() {
+            table.set(ordinal(c), Value::NoneT)?;
+        }
+    }
+    return Ok(new_dict(table));
+}
+
+/* translate(table): each character looked up by ordinal in the table,
+ * and replaced by the character or string found, or deleted for None;
+ * kept if the lookup fails
+ */
+fn translate(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("translate", 2, 2)?;
+    let text = this("translate", &args)?;
+    let table = &args.positional[1];
+    let mut out = String::new();
+    for (offset, c) in text.char_indices() {
+        if offset % TICK_BYTES == 0 && offset > 0 {
+            interpreter.tick()?;
+        }
+        match format::subscript(interpreter, table, &Value::Int(c as i64)) {
+            Ok(Value::NoneT) => (),
+            Ok(Value::Str(ref replacement)) => append(interpreter, &mut out, replacement)?,
+            Ok(Value::Int(code)) => match u32::try_from(code).ok().and_then(char::from_u32) {
+                Some(c) => out.push(c),
+                None => return Err(value_error("character mapping must be in range(0x110000)")),
+            },
+            Ok(_) => return Err(type_error(String::from("character mapping must return integer, None or str"))),
+            Err(err) if matches!(err.kind.as_str(), "KeyError" | "IndexError" | "LookupError") => out.push(c),
+            Err(err) => return Err(err),
+        };
+    }
+    return Ok(Value::Str(out));
+}
+
+/* format(*args, **kwargs)
+ */
+fn format(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    let text = this("format", &args)?;
+    let text = format::format_fields(interpreter, &text, &args.positional[1..], Names::Keywords(&args.keywords))?;
+    return Ok(Value::Str(text));
+}
+
+/* format_map(mapping): format() with fields named looked up in mapping
+ */
+fn format_map(interpreter: &mut Interpreter, args: Args) -> EvalResult {
+    args.check("format_map", 2, 2)?;
+    let text = this("format_map", &args)?;
+    let text = format::format_fields(interpreter, &text, &[], Names::Mapping(&args.positional[1]))?;
+    return Ok(Value::Str(text));
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testing::{eval, python};
+
+    #[test]
+    fn test_split_and_join() {
+        let mut python = python("");
+        assert_eq!(eval(&mut python, "('a,b,,c'.split(','), '  a b  c '.split(), '  a b  c '.split(None, 1), '  a b  c '.rsplit(None, 1), 'a,b,c'.rsplit(',', 1))"), "(['a', 'b', '', 'c'], ['a', 'b', 'c'], ['a', 'b  c '], ['  a b', 'c'], ['a,b', 'c'])");
+        assert_eq!(eval(&mut python, "('-'.join(['a', 'b']), '  xx  '.strip(), 'xxaxx'.strip('x'), '  a'.lstrip(), 'a  '.rstrip(), 'hello'.replace('l', 'L', 1), 'abc'.replace('', '-'))"), "('a-b', 'xx', 'a', 'a', 'a', 'heLlo', '-a-b-c-')");
+    }
+
+    #[test]
+    fn test_search() {
+        let mut python = python("");
+        assert_eq!(eval(&mut python, "('héllo'.find('l'), 'hello'.rfind('l'), 'hello'.find('l', 3), 'hello'.find('', 10), 'hello'.count('l'), 'hello'.count(''), 'abc'.find('a', -10, -2))"), "(2, 3, 3, -1, 2, 6, 0)");
+        assert_eq!(eval(&mut python, "('hello'.startswith(('x', 'he')), 'hello'.endswith('lo', 0, 5), 'hello'.startswith('', 6))"), "(True, True, False)");
+    }
+
+    #[test]
+    fn test_long_search() {
+        // matches across the chunks searches go in, and before and after
+        // multi-byte characters at their edges
+        let mut python = python("s = 'é' * 40000 + 'xyz' + 'a' * 65533 + 'xyz' + 'é' * 100000");
+        assert_eq!(eval(&mut python, "(s.find('xyz'), s.rfind('xyz'), s.find('xyz', 40001), s.rfind('xyz', 0, 105535), s.count('xyz'), s.count('éx'), s.index('zé'))"), "(40000, 105536, 105536, 40000, 2, 1, 105538)");
+        assert_eq!(eval(&mut python, "(len(s.replace('xyz', '')), s.replace('a', '').count('xyzxyz'), ('ab' * 50000).count('ba'), ('ab' * 50000).replace('ba', '-')[-4:])"), "(205533, 1, 49999, '---b')");
+    }
+
+    #[test]
+    fn test_case() {
+        let mut python = python("");
+        assert_eq!(eval(&mut python, "('ß straße'.upper(), 'Straße'.casefold(), \"they're bill's\".title(), 'hello World'.capitalize(), 'Hello'.swapcase())"), "('SS STRASSE', 'strasse', \"They'Re Bill'S\", 'Hello world', 'hELLO')");
+    }
+
+    #[test]
+    fn test_partition_and_lines() {
+        let mut python = python("");
+        assert_eq!(eval(&mut python, "('a=b=c'.partition('='), 'a=b=c'.rpartition('='), 'abc'.partition('x'), 'abc'.rpartition('x'))"), "(('a', '=', 'b=c'), ('a=b', '=', 'c'), ('abc', '', ''), ('', '', 'abc'))");
+        assert_eq!(eval(&mut python, "('a\\nb\\r\\nc\\rd'.splitlines(), 'a\\nb\\n'.splitlines(True), ''.splitlines())"), "(['a', 'b', 'c', 'd'], ['a\\n', 'b\\n'], [])");
+    }
+
+    #[test]
+    fn test_padding() {
+        let mut python = python("");
+        assert_eq!(eval(&mut python, "('-42'.zfill(5), 'abc'.center(6, '*'), 'ab'.center(5), 'ab'.ljust(4, '-'), 'ab'.rjust(4), 'a\\tbc\\td'.expandtabs(4))"), "('-0042', '*abc**', '  ab ', 'ab--', '  ab', 'a   bc  d')");
+        assert_eq!(eval(&mut python, "('+7'.zfill(3), 'é'.center(4, 'ß'), 'a'.ljust(-1), 'ab' * 3, 'ab' * -1, 'a'.replace('', '-'), 'abc'.replace('', '-', 2), ','.join(['a', 'b']))"), "('+07', 'ßéßß', 'a', 'ababab', '', '-a-', '-a-bc', 'a,b')");
+        assert_eq!(eval(&mut python, "('{:>5}'.format(1), '{:,}'.format(1234), '{:012,}'.format(1234), '%05d' % -3, '%.4d' % 7, '%-6.2f|' % 1.5)"), "('    1', '1,234', '0,000,001,234', '-0003', '0007', '1.50  |')");
+    }
+
+    #[test]
+    fn test_predicates() {
+        let mut python = python("");
+        assert_eq!(eval(&mut python, "('123'.isdigit(), '²'.isdigit(), '²'.isdecimal(), '½'.isnumeric(), '½'.isdigit(), ''.isdigit(), 'abc'.isalpha(), ' \\t'.isspace())"), "(True, True, False, True, False, False, True, True)");
+        assert_eq!(eval(&mut python, "('Abc'.istitle(), 'ABC'.isupper(), 'abc1'.islower(), 'a\\x00'.isprintable(), '_x1'.isidentifier(), '1x'.isidentifier(), 'é'.isascii())"), "(True, True, True, False, True, False, False)");
+    }
+
+    #[test]
+    fn test_translate() {
+        let mut python = python("");
+        assert_eq!(eval(&mut python, "('abc'.translate(str.maketrans('ab', 'xy', 'c')), 'abc'.translate({97: 'AA', 98: None}), str.maketrans({'a': 1}), 'abc'.removeprefix('ab'), 'abc'.removesuffix('bc'))"), "('xy', 'AAc', {97: 1}, 'c', 'a')");
+    }
+
+    #[test]
+    fn test_errors() {
+        let mut python = python("");
+        assert_eq!(eval(&mut python, "''.split('')"), "ValueError: empty separator");
+        assert_eq!(eval(&mut python, "'x'.join([1])"), "TypeError: sequence item 0: expected str instance, int found");
+        assert_eq!(eval(&mut python, "'hello'.index('z')"), "ValueError: substring not found");
+        assert_eq!(eval(&mut python, "'a'.center(3, 'ab')"), "TypeError: The fill character must be exactly one character long");
+        assert_eq!(eval(&mut python, "'abc'.startswith(1)"), "TypeError: startswith first arg must be str or a tuple of str, not int");
+    }
+}
@@ -0,0 +1,58 @@
+/* Helpers for the unit tests, which run snippets of Python in an embedded
+ * interpreter and compare what they give as text.
+ */
+use crate::embed::Interpreter;
+
+/* An interpreter with statements already run in it, e.g. imports
+ */
+pub fn python(setup: &str) -> Interpreter {
+    let mut python = Interpreter::build_new();
+    exec(&mut python, setup);
+    return python;
+}
+
+/* Run statements, failing the test with the traceback if they raise
+ */
+pub fn exec(python: &mut Interpreter, source: &str) {
+    if let Err(err) = python.exec(source) {
+        panic!("{}", err.format_traceback());
+    }
+}
+
+/* repr() of the value of an expression, or the exception it raises as
+ * "Kind: message"
+ */
+pub fn eval(python: &mut Interpreter, source: &str) -> String {
+    match python.eval(source) {
+        Ok(value) => return value.repr(),
+        Err(err) => return err.to_string(),
+    }
+}
+
+/* str() of the value of an expression, or the exception it raises
+ */
+pub fn eval_str(python: &mut Interpreter, source: &str) -> String {
+    match python.eval(source) {
+        Ok(value) => return value.to_string(),
+        Err(err) => return err.to_string(),
+    }
+}
+
+/* The exception an expression raises, as "Kind: message"; fails the test
+ * if it doesn't raise
+ */
+pub fn error(python: &mut Interpreter, source: &str) -> String {
+    match python.eval(source) {
+        Ok(value) => panic!("{} gave {} instead of raising", source, value.repr()),
+        Err(err) => return err.to_string(),
+    }
+}
+
+/* The traceback statements raise; fails the test if they don't raise
+ */
+pub fn traceback(python: &mut Interpreter, source: &str) -> String {
+    match python.exec(source) {
+        Ok(()) => panic!("{} ran without raising", source),
+        Err(err) => return err.format_traceback(),
+    }
+}
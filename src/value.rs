@@ -0,0 +1,1165 @@
+use crate::ast::FunctionDef;
+use crate::bytes;
+use crate::dict::{Dict, HashKey};
+use crate::gc::{self, Tracked};
+use crate::interpreter::Interpreter;
+use crate::iterator::Iter;
+use crate::limits;
+use crate::modules::{decimal, fractions};
+use num_bigint::BigInt;
+use num_rational::BigRational;
+use num_traits::Zero;
+use std::any::Any;
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::path::PathBuf;
+use std::rc::Rc;
+
+/* Runtime values. Mutable containers and modules are shared references, so
+ * cloning a Value aliases them like Python does.
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    NoneT,
+    Bool(bool),
+    Int(i64),
+    // an int too big for Int; ints that fit are always Int
+    BigInt(Rc<BigInt>),
+    Float(f64),
+    // complex(real, imag)
+    Complex(f64, f64),
+    // fractions.Fraction and decimal.Decimal
+    Fraction(Rc<BigRational>),
+    Decimal(Rc<decimal::Decimal>),
+    Str(String),
+    // bytes, and bytearray and memoryview, which share their bytes
+    Bytes(Rc<Vec<u8>>),
+    ByteArray(Rc<bytes::ByteArray>),
+    MemoryView(Rc<bytes::MemoryView>),
+    List(Rc<RefCell<Vec<Value>>>),
+    Tuple(Rc<Vec<Value>>),
+    Dict(Rc<RefCell<Dict>>),
+    // a set is a dict of its items to None. A frozenset's is never changed
+    // once made, but is in a RefCell so the cycle collector can clear it.
+    Set(Rc<RefCell<Dict>>),
+    FrozenSet(Rc<RefCell<Dict>>),
+    // range(start, stop, step)
+    Range(i64, i64, i64),
+    Iterator(Rc<RefCell<Iter>>),
+    Function(Rc<Function>),
+    Builtin(Rc<Builtin>),
+    Class(Rc<Class>),
+    Module(Rc<Module>),
+    Object(Rc<Object>),
+    Method(Rc<Method>),
+    Property(Rc<Property>),
+}
+
+/* Dropping a list that holds a list that holds a list... would recurse
+ * once for each level, and overflow the stack for deep enough nesting. So
+ * the items of containers nothing else refers to are taken out and
+ * dropped here one at a time, each with its own items already taken.
+ */
+impl Drop for Value {
+    fn drop(&mut self) {
+        let mut pending: Vec<Value> = Vec::new();
+        take_items(self, &mut pending);
+        while let Some(mut value) = pending.pop() {
+            take_items(&mut value, &mut pending);
+        }
+    }
+}
+
+thread_local! {
+    // what an emptied tuple is left holding
+    static EMPTY_ITEMS: Rc<Vec<Value>> = Rc::new(Vec::new());
+}
+
+fn take_items(value: &mut Value, pending: &mut Vec<Value>) {
+    match value {
+        Value::List(items) if Rc::strong_count(items) == 1 => pending.append(&mut items.borrow_mut()),
+        Value::Tuple(items) if Rc::strong_count(items) == 1 && !items.is_empty() => {
+            // (thread locals holding tuples may outlive EMPTY_ITEMS)
+            if let Ok(empty) = EMPTY_ITEMS.try_with(Rc::clone) {
+                if let Ok(items) = Rc::try_unwrap(std::mem::replace(items, empty)) {
+                    pending.extend(items);
+                }
+            }
+        }
+        Value::Dict(dict) | Value::Set(dict) | Value::FrozenSet(dict) if Rc::strong_count(dict) == 1 => {
+            for (key, value) in dict.borrow_mut().take_entries() {
+                pending.push(key);
+                pending.push(value);
+            }
+        }
+        _ => {}
+    }
+}
+
+/* A new list. Lists, tuples, dicts and the other containers that can
+ * refer to each other are made through functions like this one, which
+ * track them for the cycle collector.
+ */
+pub fn new_list(items: Vec<Value>) -> Value {
+    let items = Rc::new(RefCell::new(items));
+    gc::track(Tracked::List(Rc::downgrade(&items)));
+    return Value::List(items);
+}
+
+pub fn new_tuple(items: Vec<Value>) -> Value {
+    let items = Rc::new(items);
+    // a tuple of ints and strings can't be part of a cycle
+    if items.iter().any(gc::is_container) {
+        gc::track(Tracked::Tuple(Rc::downgrade(&items)));
+    }
+    return Value::Tuple(items);
+}
+
+/* A module's namespace. The same Module is shared by every importer (and
+ * by sys.modules), so it is compared and printed by identity.
+ */
+pub struct Module {
+    pub name: String,
+    pub file: Option<PathBuf>,
+    pub vars: RefCell<HashMap<String, Value>>,
+    // true while the module's code is still running, to detect circular
+    // `from x import y`
+    pub initializing: Cell<bool>,
+}
+
+impl Module {
+    pub fn build_new(name: &str, file: Option<PathBuf>) -> Module {
+        let module = Module {
+            name: String::from(name),
+            file,
+            vars: RefCell::new(HashMap::new()),
+            initializing: Cell::new(false),
+        };
+        module.set("__name__", Value::Str(String::from(name)));
+        return module;
+    }
+
+    pub fn get(&self, name: &str) -> Option<Value> {
+        return self.vars.borrow().get(name).cloned();
+    }
+
+    pub fn set(&self, name: &str, value: Value) {
+        self.vars.borrow_mut().insert(String::from(name), value);
+    }
+
+    /* Add a native class under its bare name. One named without its
+     * module gets this module as its __module__.
+     */
+    pub fn set_class(&self, class: Rc<Class>) {
+        if !class.name.contains('.') {
+            class.attrs.borrow_mut().entry(String::from("__module__")).or_insert(Value::Str(self.name.clone()));
+        }
+        let name = String::from(class.bare_name());
+        self.set(&name, Value::Class(class));
+    }
+}
+
+impl PartialEq for Module {
+    fn eq(&self, other: &Module) -> bool {
+        return std::ptr::eq(self, other);
+    }
+}
+
+impl fmt::Debug for Module {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.file {
+            Some(file) => return write!(f, "<module '{}' from '{}'>", self.name, file.display()),
+            None => return write!(f, "<module '{}' (built-in)>", self.name),
+        }
+    }
+}
+
+/* Local variables of a running function. Functions defined inside a
+ * function keep a reference to its Scope (parent), which is how closures
+ * see their enclosing function's variables.
+ */
+pub struct Scope {
+    pub vars: RefCell<HashMap<String, Value>>,
+    pub parent: Option<Rc<Scope>>,
+    // names declared `global` / `nonlocal` in this scope
+    pub globals: RefCell<HashSet<String>>,
+    pub nonlocals: RefCell<HashSet<String>>,
+    // the function whose call this is, for super(); None for comprehensions
+    pub function: Option<Rc<FunctionDef>>,
+    // a class body's namespace, which the functions defined in it don't see
+    pub class_body: bool,
+}
+
+impl Scope {
+    pub fn build_new(parent: Option<Rc<Scope>>) -> Scope {
+        return Scope {
+            vars: RefCell::new(HashMap::new()),
+            parent,
+            globals: RefCell::new(HashSet::new()),
+            nonlocals: RefCell::new(HashSet::new()),
+            function: None,
+            class_body: false,
+        };
+    }
+}
+
+/* A function defined with def or lambda
+ */
+pub struct Function {
+    pub def: Rc<FunctionDef>,
+    // default values by parameter name, evaluated when the def ran
+    pub defaults: HashMap<String, Value>,
+    pub globals: Rc<Module>,
+    pub closure: Option<Rc<Scope>>,
+    // what functools.update_wrapper() made this a wrapper of
+    pub wrapped: Option<Value>,
+}
+
+/* Arguments to a call, after * and ** unpacking
+ */
+#[derive(Debug, Default)]
+pub struct Args {
+    pub positional: Vec<Value>,
+    pub keywords: Vec<(String, Value)>,
+}
+
+impl Args {
+    pub fn build_new(positional: Vec<Value>) -> Args {
+        return Args {
+            positional,
+            keywords: Vec::new(),
+        };
+    }
+
+    /* remove and return a keyword argument
+     */
+    pub fn keyword(&mut self, name: &str) -> Option<Value> {
+        let pos = self.keywords.iter().position(|(key, _)| key == name)?;
+        return Some(self.keywords.remove(pos).1);
+    }
+
+    /* check the number of positional arguments, and that every keyword
+     * argument has been taken
+     */
+    pub fn check(&self, name: &str, min: usize, max: usize) -> Result<(), Exception> {
+        if let Some((key, _)) = self.keywords.first() {
+            let message = format!("{}() got an unexpected keyword argument '{}'", name, key);
+            return Err(Exception::new("TypeError", &message));
+        }
+        let given = self.positional.len();
+        if given >= min && given <= max {
+            return Ok(());
+        }
+        let message = if min == max {
+            let plural = if min == 1 { "" } else { "s" };
+            format!("{}() takes exactly {} argument{} ({} given)", name, min, plural, given)
+        } else if given < min {
+            format!("{} expected at least {} argument{}, got {}", name, min, if min == 1 { "" } else { "s" }, given)
+        } else {
+            format!("{} expected at most {} argument{}, got {}", name, max, if max == 1 { "" } else { "s" }, given)
+        };
+        return Err(Exception::new("TypeError", &message));
+    }
+}
+
+pub type BuiltinFn = fn(&mut Interpreter, Args) -> Result<Value, Exception>;
+
+// a builtin that can capture state, like the functions a host registers
+pub type NativeFn = Rc<dyn Fn(&mut Interpreter, Args) -> Result<Value, Exception>>;
+
+/* A function implemented in Rust
+ */
+pub struct Builtin {
+    pub name: String,
+    pub func: NativeFn,
+}
+
+impl Builtin {
+    pub fn build_new(name: &str, func: BuiltinFn) -> Value {
+        return Builtin::build_native(name, Rc::new(func));
+    }
+
+    pub fn build_native(name: &str, func: NativeFn) -> Value {
+        return Value::Builtin(Rc::new(Builtin {
+            name: String::from(name),
+            func,
+        }));
+    }
+}
+
+/* A type. The built-in types are Class objects too, so they can be passed
+ * to isinstance() and called to convert values.
+ */
+pub struct Class {
+    pub name: String,
+    pub bases: Vec<Rc<Class>>,
+    // the bases and their ancestors, in the order attributes are looked up
+    // in after the class itself
+    pub mro: Vec<Rc<Class>>,
+    pub attrs: RefCell<HashMap<String, Value>>,
+    // made by a class statement, so scripts may set its attributes
+    pub user_defined: bool,
+    // called with the arguments when the class is called
+    pub constructor: Option<NativeFn>,
+    pub hooks: Hooks,
+}
+
+/* What instances of a native class do in place of being printed as
+ * <X object at ...>, compared and hashed by identity and always true.
+ * Each is given the instance's Rust value, and gives None for the default
+ * (e.g. for a value of another type).
+ */
+#[derive(Clone, Default)]
+pub struct Hooks {
+    pub repr: Option<Hook<String>>,
+    pub eq: Option<EqHook>,
+    pub hash: Option<Hook<Result<HashKey, Exception>>>,
+    pub truth: Option<Hook<bool>>,
+    // the dict a subclass of dict keeps its items in, which dict's methods
+    // and the functions taking a dict work on
+    pub dict: Option<Hook<Rc<RefCell<Dict>>>>,
+    // the repr of an instance inside its own repr, in place of "..."
+    pub recursive_repr: Option<&'static str>,
+}
+
+pub type Hook<R> = Rc<dyn Fn(&dyn Any) -> Option<R>>;
+pub type EqHook = Rc<dyn Fn(&dyn Any, &Value) -> Option<bool>>;
+
+impl Class {
+    pub fn build_new(name: &str, bases: Vec<Rc<Class>>, constructor: Option<BuiltinFn>) -> Rc<Class> {
+        // the built-in hierarchies all have one
+        let mro = Class::linearize(&bases).unwrap_or_default();
+        return Rc::new(Class {
+            name: String::from(name),
+            bases,
+            mro,
+            attrs: RefCell::new(HashMap::new()),
+            user_defined: false,
+            constructor: constructor.map(|constructor| Rc::new(constructor) as NativeFn),
+            hooks: Hooks::default(),
+        });
+    }
+
+    /* The class of a class statement, or of type(name, bases, dict)
+     */
+    pub fn build_user(name: &str, bases: Vec<Rc<Class>>, attrs: HashMap<String, Value>) -> Result<Rc<Class>, Exception> {
+        return Ok(Rc::new(Class {
+            name: String::from(name),
+            mro: Class::linearize(&bases)?,
+            bases,
+            attrs: RefCell::new(attrs),
+            user_defined: true,
+            constructor: None,
+            hooks: Hooks::default(),
+        }));
+    }
+
+    /* The C3 linearization of bases: each base before its own bases, and
+     * the bases in the order given, as CPython orders them
+     */
+    pub fn linearize(bases: &[Rc<Class>]) -> Result<Vec<Rc<Class>>, Exception> {
+        for (i, base) in bases.iter().enumerate() {
+            if bases[..i].iter().any(|other| Rc::ptr_eq(other, base)) {
+                return Err(Exception::new("TypeError", &format!("duplicate base class {}", base.name)));
+            }
+        }
+        let mut sequences: Vec<Vec<Rc<Class>>> = bases
+            .iter()
+            .map(|base| std::iter::once(base.clone()).chain(base.mro.iter().cloned()).collect())
+            .collect();
+        sequences.push(bases.to_vec());
+        let mut mro: Vec<Rc<Class>> = Vec::new();
+        loop {
+            sequences.retain(|sequence| !sequence.is_empty());
+            if sequences.is_empty() {
+                return Ok(mro);
+            }
+            // the first head that isn't in the tail of any sequence
+            let in_tail = |class: &Rc<Class>| {
+                sequences.iter().any(|sequence| sequence[1..].iter().any(|other| Rc::ptr_eq(other, class)))
+            };
+            let next = match sequences.iter().map(|sequence| &sequence[0]).find(|head| !in_tail(head)) {
+                Some(next) => next.clone(),
+                None => {
+                    let names: Vec<&str> = bases.iter().map(|base| base.name.as_str()).collect();
+                    let message =
+                        format!("Cannot create a consistent method resolution order (MRO) for bases {}", names.join(", "));
+                    return Err(Exception::new("TypeError", &message));
+                }
+            };
+            for sequence in &mut sequences {
+                if Rc::ptr_eq(&sequence[0], &next) {
+                    sequence.remove(0);
+                }
+            }
+            mro.push(next);
+        }
+    }
+
+    pub fn is_subclass(&self, other: &Class) -> bool {
+        return std::ptr::eq(self, other) || self.mro.iter().any(|base| std::ptr::eq(&**base, other));
+    }
+
+    /* The name without its module. Native classes of the standard library
+     * may be named like `decimal.Decimal`, the form repr and error messages
+     * use, while __name__ is just `Decimal`.
+     */
+    pub fn bare_name(&self) -> &str {
+        return self.name.rsplit('.').next().unwrap_or(&self.name);
+    }
+
+    /* __module__: set by the class statement or the module that made the
+     * class, or else the module in its name, or else builtins
+     */
+    pub fn module(&self) -> Value {
+        if let Some(module) = self.attrs.borrow().get("__module__") {
+            return module.clone();
+        }
+        return match self.name.rsplit_once('.') {
+            Some((module, _)) => Value::Str(String::from(module)),
+            None => Value::Str(String::from("builtins")),
+        };
+    }
+
+    /* look up an attribute on the class or its bases
+     */
+    pub fn lookup(&self, name: &str) -> Option<Value> {
+        if let Some(value) = self.attrs.borrow().get(name) {
+            return Some(value.clone());
+        }
+        return self.mro.iter().find_map(|base| base.attrs.borrow().get(name).cloned());
+    }
+}
+
+/* An instance of a class. Instances of native classes wrap the Rust value
+ * the host's constructor made, and have no attributes of their own.
+ */
+pub struct Object {
+    pub class: Rc<Class>,
+    pub attrs: RefCell<HashMap<String, Value>>,
+    pub native: Option<RefCell<Box<dyn Any>>>,
+    // __del__ has run, and isn't run again
+    pub finalized: Cell<bool>,
+    // the __hash__() it had when first used as a dict key, which dicts keep
+    pub key_hash: Cell<Option<i64>>,
+}
+
+impl Object {
+    pub fn build_new(class: Rc<Class>, native: Option<Box<dyn Any>>) -> Rc<Object> {
+        let object = Rc::new(Object {
+            class,
+            attrs: RefCell::new(HashMap::new()),
+            native: native.map(RefCell::new),
+            finalized: Cell::new(false),
+            key_hash: Cell::new(None),
+        });
+        gc::track(Tracked::Object(Rc::downgrade(&object)));
+        return object;
+    }
+
+    /* call one of the class's hooks with the instance's Rust value; None
+     * if there is no such hook, or the value is in use
+     */
+    fn hook<H: ?Sized, R>(&self, hook: &Option<Rc<H>>, call: impl FnOnce(&H, &dyn Any) -> Option<R>) -> Option<R> {
+        let hook = hook.as_ref()?;
+        let native = self.native.as_ref()?.try_borrow().ok()?;
+        return call(hook, &**native);
+    }
+
+    pub fn native_repr(&self) -> Option<String> {
+        return self.hook(&self.class.hooks.repr, |repr, native| repr(native));
+    }
+
+    pub fn native_eq(&self, other: &Value) -> Option<bool> {
+        return self.hook(&self.class.hooks.eq, |eq, native| eq(native, other));
+    }
+
+    pub fn native_hash(&self) -> Option<Result<HashKey, Exception>> {
+        return self.hook(&self.class.hooks.hash, |hash, native| hash(native));
+    }
+
+    pub fn native_truth(&self) -> Option<bool> {
+        return self.hook(&self.class.hooks.truth, |truth, native| truth(native));
+    }
+
+    pub fn native_dict(&self) -> Option<Rc<RefCell<Dict>>> {
+        return self.hook(&self.class.hooks.dict, |dict, native| dict(native));
+    }
+}
+
+impl Drop for Object {
+    /* __del__ can't run here, without the interpreter, so it runs at the
+     * next statement, on a new object with everything this one had (and so
+     * a different id())
+     */
+    fn drop(&mut self) {
+        gc::reclaimed(self as *const Object as usize);
+        if self.finalized.get() {
+            return;
+        }
+        if let Some(del) = gc::finalizer(&self.class) {
+            let object = Rc::new(Object {
+                class: self.class.clone(),
+                attrs: RefCell::new(std::mem::take(self.attrs.get_mut())),
+                native: self.native.take(),
+                finalized: Cell::new(true),
+                key_hash: Cell::new(self.key_hash.get()),
+            });
+            gc::track(Tracked::Object(Rc::downgrade(&object)));
+            gc::schedule(del, vec![Value::Object(object)]);
+        }
+    }
+}
+
+/* A function looked up on an instance, which is called with the instance
+ * as its first argument
+ */
+pub struct Method {
+    pub object: Value,
+    pub function: Value,
+}
+
+impl Method {
+    pub fn build_new(object: Value, function: Value) -> Value {
+        let method = Rc::new(Method { object, function });
+        gc::track(Tracked::Method(Rc::downgrade(&method)));
+        return Value::Method(method);
+    }
+}
+
+/* A computed attribute: reading it on an instance calls getter(instance),
+ * and assigning it calls setter(instance, value)
+ */
+pub struct Property {
+    pub getter: Value,
+    pub setter: Option<Value>,
+}
+
+// functions, classes and iterators are compared and printed by identity
+macro_rules! identity_eq {
+    ($($kind:ty),*) => {
+        $(impl PartialEq for $kind {
+            fn eq(&self, other: &$kind) -> bool {
+                return std::ptr::eq(self, other);
+            }
+        })*
+    };
+}
+identity_eq!(Function, Builtin, Class, Iter, Object, Method, Property);
+
+// the values weakref can refer to tell it when they are freed
+macro_rules! weakly_referenceable {
+    ($($kind:ty),*) => {
+        $(impl Drop for $kind {
+            fn drop(&mut self) {
+                gc::reclaimed(self as *const $kind as usize);
+            }
+        })*
+    };
+}
+weakly_referenceable!(Function, Class, Module);
+
+impl fmt::Debug for Function {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return write!(f, "<function {} at {:#x}>", self.def.name, self as *const Function as usize);
+    }
+}
+
+impl fmt::Debug for Builtin {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return write!(f, "<built-in function {}>", self.name);
+    }
+}
+
+impl fmt::Debug for Iter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return write!(f, "<{} object at {:#x}>", self.type_name(), self as *const Iter as usize);
+    }
+}
+
+impl fmt::Debug for Class {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.user_defined {
+            // classes only come from the main script
+            return write!(f, "<class '__main__.{}'>", self.name);
+        }
+        return write!(f, "<class '{}'>", self.name);
+    }
+}
+
+impl fmt::Debug for Object {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(repr) = self.native_repr() {
+            return write!(f, "{}", repr);
+        }
+        return write!(f, "<{} object at {:#x}>", self.class.name, self as *const Object as usize);
+    }
+}
+
+impl fmt::Debug for Method {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match &self.function {
+            Value::Function(function) => function.def.name.as_str(),
+            Value::Builtin(builtin) => builtin.name.as_str(),
+            _ => "?",
+        };
+        return write!(f, "<bound method {}.{} of {}>", self.object.type_name(), name, self.object.repr());
+    }
+}
+
+impl fmt::Debug for Property {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return write!(f, "<property object at {:#x}>", self as *const Property as usize);
+    }
+}
+
+impl Value {
+    /* name of the value's type, as reported in error messages
+     */
+    pub fn type_name(&self) -> &str {
+        match self {
+            Value::NoneT => return "NoneType",
+            Value::Bool(_) => return "bool",
+            Value::Int(_) | Value::BigInt(_) => return "int",
+            Value::Float(_) => return "float",
+            Value::Complex(..) => return "complex",
+            Value::Fraction(_) => return "Fraction",
+            Value::Decimal(_) => return "decimal.Decimal",
+            Value::Str(_) => return "str",
+            Value::Bytes(_) => return "bytes",
+            Value::ByteArray(_) => return "bytearray",
+            Value::MemoryView(_) => return "memoryview",
+            Value::List(_) => return "list",
+            Value::Tuple(_) => return "tuple",
+            Value::Dict(_) => return "dict",
+            Value::Set(_) => return "set",
+            Value::FrozenSet(_) => return "frozenset",
+            Value::Range(..) => return "range",
+            Value::Iterator(iter) => return iter.borrow().type_name(),
+            Value::Function(_) => return "function",
+            Value::Builtin(_) => return "builtin_function_or_method",
+            Value::Class(_) => return "type",
+            Value::Module(_) => return "module",
+            Value::Object(object) => return &object.class.name,
+            Value::Method(_) => return "method",
+            Value::Property(_) => return "property",
+        }
+    }
+
+    /* address of a shared object, which is its identity; 0 for plain values
+     */
+    pub fn identity(&self) -> usize {
+        match self {
+            Value::Bytes(data) => return Rc::as_ptr(data) as *const u8 as usize,
+            Value::ByteArray(array) => return Rc::as_ptr(array) as *const u8 as usize,
+            Value::MemoryView(view) => return Rc::as_ptr(view) as *const u8 as usize,
+            Value::List(items) => return Rc::as_ptr(items) as *const u8 as usize,
+            Value::Tuple(items) => return Rc::as_ptr(items) as *const u8 as usize,
+            Value::Dict(dict) | Value::Set(dict) | Value::FrozenSet(dict) => return Rc::as_ptr(dict) as *const u8 as usize,
+            Value::Iterator(iter) => return Rc::as_ptr(iter) as *const u8 as usize,
+            Value::Function(function) => return Rc::as_ptr(function) as *const u8 as usize,
+            Value::Builtin(builtin) => return Rc::as_ptr(builtin) as *const u8 as usize,
+            Value::Class(class) => return Rc::as_ptr(class) as *const u8 as usize,
+            Value::Module(module) => return Rc::as_ptr(module) as *const u8 as usize,
+            Value::Object(object) => return Rc::as_ptr(object) as *const u8 as usize,
+            Value::Method(method) => return Rc::as_ptr(method) as *const u8 as usize,
+            Value::Property(property) => return Rc::as_ptr(property) as *const u8 as usize,
+            _ => return 0,
+        }
+    }
+
+    /* the items of a dict, or of an instance of a subclass of dict like
+     * Counter
+     */
+    pub fn as_dict(&self) -> Option<Rc<RefCell<Dict>>> {
+        match self {
+            Value::Dict(dict) => return Some(dict.clone()),
+            Value::Object(object) => return object.native_dict(),
+            _ => return None,
+        }
+    }
+
+    pub fn is_truthy(&self) -> bool {
+        match self {
+            Value::NoneT => return false,
+            Value::Bool(val) => return *val,
+            Value::Int(val) => return *val != 0,
+            Value::Float(val) => return *val != 0.0,
+            Value::Complex(real, imag) => return *real != 0.0 || *imag != 0.0,
+            Value::Fraction(val) => return !val.is_zero(),
+            Value::Decimal(val) => return val.is_truthy(),
+            Value::Str(val) => return !val.is_empty(),
+            Value::Bytes(data) => return !data.is_empty(),
+            Value::ByteArray(array) => return !array.data.borrow().is_empty(),
+            Value::MemoryView(view) => return view.len > 0,
+            Value::List(items) => return !items.borrow().is_empty(),
+            Value::Tuple(items) => return !items.is_empty(),
+            Value::Dict(dict) | Value::Set(dict) | Value::FrozenSet(dict) => return !dict.borrow().is_empty(),
+            Value::Range(start, stop, step) => return range_len(*start, *stop, *step) > 0,
+            Value::Object(object) => return object.native_truth().unwrap_or(true),
+            _ => return true,
+        }
+    }
+
+    /* repr() of a value
+     */
+    pub fn repr(&self) -> String {
+        let result: Result<String, Exception> = self.repr_with(&mut |_| Ok(None));
+        return result.unwrap();
+    }
+
+    /* repr() of a value, with what object_repr gives for the instances in
+     * it (None for the default). The interpreter uses it to call __repr__.
+     * A container inside itself is shown as [...], {...} or (...).
+     */
+    pub fn repr_with<E>(&self, object_repr: &mut dyn FnMut(&Value) -> Result<Option<String>, E>) -> Result<String, E> {
+        let placeholder = match self {
+            Value::List(_) => "[...]",
+            Value::Tuple(_) => "(...)",
+            Value::Dict(_) => "{...}",
+            Value::Set(_) => "set(...)",
+            Value::FrozenSet(_) => "frozenset(...)",
+            Value::Object(object) if object.class.hooks.repr.is_some() => {
+                if let Some(repr) = object_repr(self)? {
+                    return Ok(repr);
+                }
+                object.class.hooks.recursive_repr.unwrap_or("...")
+            }
+            _ => return self.container_repr(object_repr),
+        };
+        let _guard = match ReprGuard::enter(self.identity()) {
+            Some(guard) => guard,
+            None => return Ok(String::from(placeholder)),
+        };
+        return limits::with_stack(|| self.container_repr(object_repr));
+    }
+
+    fn container_repr<E>(&self, object_repr: &mut dyn FnMut(&Value) -> Result<Option<String>, E>) -> Result<String, E> {
+        match self {
+            Value::Str(val) => return Ok(repr_str(val)),
+            Value::Bytes(data) => return Ok(bytes::repr_bytes(data)),
+            Value::ByteArray(array) => return Ok(format!("{:?}", array)),
+            Value::MemoryView(view) => return Ok(format!("{:?}", view)),
+            Value::List(items) => {
+                // a copy, as __repr__ may change the list
+                let items = items.borrow().clone();
+                let mut reprs: Vec<String> = Vec::with_capacity(items.len());
+                for item in &items {
+                    reprs.push(item.repr_with(object_repr)?);
+                }
+                return Ok(format!("[{}]", reprs.join(", ")));
+            }
+            Value::Tuple(items) => {
+                let mut reprs: Vec<String> = Vec::with_capacity(items.len());
+                for item in items.iter() {
+                    reprs.push(item.repr_with(object_repr)?);
+                }
+                if reprs.len() == 1 {
+                    return Ok(format!("({},)", reprs[0]));
+                }
+                return Ok(format!("({})", reprs.join(", ")));
+            }
+            Value::Dict(dict) => {
+                let items = dict.borrow().items();
+                let mut reprs: Vec<String> = Vec::with_capacity(items.len());
+                for (key, value) in &items {
+                    reprs.push(format!("{}: {}", key.repr_with(object_repr)?, value.repr_with(object_repr)?));
+                }
+                return Ok(format!("{{{}}}", reprs.join(", ")));
+            }
+            Value::Set(items) | Value::FrozenSet(items) => {
+                let items = items.borrow().keys();
+                let mut reprs: Vec<String> = Vec::with_capacity(items.len());
+                for item in &items {
+                    reprs.push(item.repr_with(object_repr)?);
+                }
+                return Ok(match (self, reprs.is_empty()) {
+                    (Value::Set(_), true) => String::from("set()"),
+                    (Value::Set(_), false) => format!("{{{}}}", reprs.join(", ")),
+                    (_, true) => String::from("frozenset()"),
+                    (_, false) => format!("frozenset({{{}}})", reprs.join(", ")),
+                });
+            }
+            Value::Object(_) => {
+                if let Some(repr) = object_repr(self)? {
+                    return Ok(repr);
+                }
+            }
+            _ => (),
+        };
+        return Ok(self.simple_repr());
+    }
+
+    // repr() of a value that isn't a container
+    fn simple_repr(&self) -> String {
+        match self {
+            Value::Fraction(val) => return fractions::repr(val),
+            Value::Decimal(_) => return format!("Decimal('{}')", self),
+            Value::Range(start, stop, 1) => return format!("range({}, {})", start, stop),
+            Value::Range(start, stop, step) => return format!("range({}, {}, {})", start, stop, step),
+            // generator expressions are the only generators
+            Value::Iterator(_) if self.type_name() == "generator" => {
+                return format!("<generator object <genexpr> at {:#x}>", self.identity());
+            }
+            Value::Iterator(_) => return format!("<{} object at {:#x}>", self.type_name(), self.identity()),
+            Value::Function(function) => return format!("{:?}", function),
+            Value::Builtin(builtin) => return format!("{:?}", builtin),
+            Value::Class(class) => return format!("{:?}", class),
+            Value::Module(module) => return format!("{:?}", module),
+            Value::Object(object) => return format!("{:?}", object),
+            Value::Method(method) => return format!("{:?}", method),
+            Value::Property(property) => return format!("{:?}", property),
+            _ => return self.to_string(),
+        }
+    }
+}
+
+thread_local! {
+    // the containers whose repr is being made
+    static REPRS_ACTIVE: RefCell<HashSet<usize>> = RefCell::new(HashSet::new());
+}
+
+/* Marks a container's repr as being made, until it is dropped, as
+ * CPython's Py_ReprEnter does
+ */
+struct ReprGuard(usize);
+
+impl ReprGuard {
+    // None if the container is already being shown
+    fn enter(identity: usize) -> Option<ReprGuard> {
+        let entered = REPRS_ACTIVE.with(|active| active.borrow_mut().insert(identity));
+        return if entered { Some(ReprGuard(identity)) } else { None };
+    }
+}
+
+impl Drop for ReprGuard {
+    fn drop(&mut self) {
+        // (thread locals may be gone when a value is printed at exit)
+        let _ = REPRS_ACTIVE.try_with(|active| active.borrow_mut().remove(&self.0));
+    }
+}
+
+/* number of items in range(start, stop, step)
+ */
+pub fn range_len(start: i64, stop: i64, step: i64) -> i64 {
+    let (start, stop, step) = (start as i128, stop as i128, step as i128);
+    if step > 0 && start < stop {
+        return ((stop - start - 1) / step + 1) as i64;
+    }
+    if step < 0 && start > stop {
+        return ((start - stop - 1) / -step + 1) as i64;
+    }
+    return 0;
+}
+
+/* quote a string the way repr() does: single quotes unless the string
+ * contains a single quote and no double quotes
+ */
+pub fn repr_str(val: &str) -> String {
+    let quote = if val.contains('\'') && !val.contains('"') { '"' } else { '\'' };
+    let mut out = String::new();
+    out.push(quote);
+    for c in val.chars() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c == quote => {
+                out.push('\\');
+                out.push(c);
+            }
+            c if (c as u32) < 0x20 || c as u32 == 0x7f => out.push_str(&format!("\\x{:02x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push(quote);
+    return out;
+}
+
+/* A raised Python exception. kind is the exception's class name, e.g.
+ * "ZeroDivisionError".
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub struct Exception {
+    pub kind: String,
+    pub message: String,
+    // the frames the exception has propagated out of, innermost first
+    pub traceback: Vec<Frame>,
+    // line being run in the frame the exception hasn't left yet, or for a
+    // SyntaxError the line it was found on
+    pub line: Option<usize>,
+    // what Python code can see of it beyond the kind and message, boxed to
+    // keep errors that never reach Python code small
+    pub details: Option<Box<Details>>,
+}
+
+/* The parts of an exception only Python code looks at
+ */
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Details {
+    // the exception object, once Python code has raised or caught it, and
+    // what its args will be if they aren't just the message
+    pub value: Option<Value>,
+    pub args: Option<Vec<Value>>,
+    // `raise ... from cause`, and the exception being handled when this one
+    // was raised
+    pub cause: Option<Exception>,
+    pub context: Option<Exception>,
+    // raised from something (even None), so the context isn't shown
+    pub suppress_context: bool,
+}
+
+/* A function or module body an exception passed through, and the line it
+ * was running. Exceptions raised outside any statement (e.g. by the
+ * embedding API's eval) have no line.
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub struct Frame {
+    pub file: String,
+    pub line: Option<usize>,
+    pub name: String,
+}
+
+impl Exception {
+    pub fn new(kind: &str, message: &str) -> Exception {
+        return Exception {
+            kind: String::from(kind),
+            message: String::from(message),
+            traceback: Vec::new(),
+            line: None,
+            details: None,
+        };
+    }
+
+    pub fn details(&self) -> Option<&Details> {
+        return self.details.as_deref();
+    }
+
+    // the exception object, if it has been made
+    pub fn value(&self) -> Option<&Value> {
+        return self.details().and_then(|details| details.value.as_ref());
+    }
+
+    pub fn details_mut(&mut self) -> &mut Details {
+        return self.details.get_or_insert_with(Box::default);
+    }
+
+    /* record that the exception is leaving a frame of code from module;
+     * code typed at the prompt has no __file__. An exception it was raised
+     * from or while handling that was caught in this frame ends here too.
+     */
+    pub fn add_frame(&mut self, module: &Module, name: &str) {
+        let file = match module.get("__file__") {
+            Some(Value::Str(ref path)) => path.clone(),
+            _ => String::from("<stdin>"),
+        };
+        if let Some(details) = &mut self.details {
+            for chained in details.cause.iter_mut().chain(details.context.iter_mut()) {
+                if chained.line.is_some() {
+                    chained.add_frame(module, name);
+                }
+            }
+        }
+        self.traceback.push(Frame {
+            file,
+            line: self.line.take(),
+            name: String::from(name),
+        });
+    }
+
+    /* The report printed for an uncaught exception, like CPython's.
+     * Exceptions raised before any code ran (e.g. a script's SyntaxError)
+     * have no traceback and print as just the last line.
+     */
+    pub fn format_traceback(&self) -> String {
+        let mut out = String::new();
+        if let Some(details) = self.details() {
+            if let Some(cause) = &details.cause {
+                out.push_str(&cause.format_traceback());
+                out.push_str("\n\nThe above exception was the direct cause of the following exception:\n\n");
+            } else if let (Some(context), false) = (&details.context, details.suppress_context) {
+                out.push_str(&context.format_traceback());
+                out.push_str("\n\nDuring handling of the above exception, another exception occurred:\n\n");
+            }
+        }
+        if !self.traceback.is_empty() {
+            out.push_str("Traceback (most recent call last):\n");
+        }
+        let mut previous: Option<&Frame> = None;
+        let mut repeats = 0;
+        for frame in self.traceback.iter().rev() {
+            if previous == Some(frame) {
+                repeats += 1;
+            } else {
+                out.push_str(&format_repeats(repeats));
+                repeats = 0;
+            }
+            // after 3 identical frames, they are counted instead of shown
+            if repeats < 3 {
+                match frame.line {
+                    Some(line) => out.push_str(&format!("  File \"{}\", line {}, in {}\n", frame.file, line, frame.name)),
+                    None => out.push_str(&format!("  File \"{}\", in {}\n", frame.file, frame.name)),
+                };
+            }
+            previous = Some(frame);
+        }
+        out.push_str(&format_repeats(repeats));
+        out.push_str(&self.to_string());
+        return out;
+    }
+}
+
+impl std::error::Error for Exception {}
+
+fn format_repeats(repeats: usize) -> String {
+    if repeats < 3 {
+        return String::new();
+    }
+    let more = repeats - 2;
+    return format!("  [Previous line repeated {} more time{}]\n", more, if more == 1 { "" } else { "s" });
+}
+
+impl fmt::Display for Exception {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.message.is_empty() {
+            return write!(f, "{}", self.kind);
+        }
+        return write!(f, "{}: {}", self.kind, self.message);
+    }
+}
+
+/* Format a float the way Python's repr() does: shortest round-tripping
+ * digits, positional notation for exponents in [-4, 16), scientific
+ * otherwise, and always a decimal point or exponent.
+ */
+pub fn format_float(val: f64) -> String {
+    if val.is_nan() {
+        return String::from("nan");
+    }
+    if val.is_infinite() {
+        return String::from(if val > 0.0 { "inf" } else { "-inf" });
+    }
+    // {:e} gives the shortest round-tripping digits, e.g. "-1.25e-7"
+    let sci = format!("{:e}", val);
+    let (mantissa, exponent) = sci.split_at(sci.find('e').unwrap());
+    let exponent: i32 = exponent[1..].parse().unwrap();
+    let negative = mantissa.starts_with('-');
+    let digits: String = mantissa.chars().filter(|c| c.is_ascii_digit()).collect();
+    let sign = if negative { "-" } else { "" };
+
+    if !(-4..16).contains(&exponent) {
+        let mut out = String::from(sign);
+        out.push_str(&digits[..1]);
+        if digits.len() > 1 {
+            out.push('.');
+            out.push_str(&digits[1..]);
+        }
+        let exp_sign = if exponent < 0 { '-' } else { '+' };
+        out.push_str(&format!("e{}{:02}", exp_sign, exponent.abs()));
+        return out;
+    }
+    if exponent < 0 {
+        let zeros = "0".repeat((-exponent - 1) as usize);
+        return format!("{}0.{}{}", sign, zeros, digits);
+    }
+    let point = exponent as usize + 1;
+    if digits.len() <= point {
+        let zeros = "0".repeat(point - digits.len());
+        return format!("{}{}{}.0", sign, digits, zeros);
+    }
+    return format!("{}{}.{}", sign, &digits[..point], &digits[point..]);
+}
+
+/* Format a complex number the way Python's repr() does: the parts are
+ * floats without a trailing ".0", and a real part of +0 is left out along
+ * with the parentheses, e.g. "(1+2j)", "-0j", "(-0+1j)"
+ */
+pub fn format_complex(real: f64, imag: f64) -> String {
+    let part = |val: f64| {
+        let text = format_float(val);
+        return text.strip_suffix(".0").map(String::from).unwrap_or(text);
+    };
+    if real == 0.0 && real.is_sign_positive() {
+        return format!("{}j", part(imag));
+    }
+    let imag = match part(imag) {
+        text if text.starts_with('-') => text,
+        // NaN has no sign in a repr
+        text => format!("+{}", text),
+    };
+    return format!("({}{}j)", part(real), imag);
+}
+
+/* str() of a value
+ */
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::NoneT => return write!(f, "None"),
+            Value::Bool(true) => return write!(f, "True"),
+            Value::Bool(false) => return write!(f, "False"),
+            Value::Int(val) => return write!(f, "{}", val),
+            Value::BigInt(val) => return write!(f, "{}", val),
+            Value::Float(val) => return write!(f, "{}", format_float(*val)),
+            Value::Complex(real, imag) => return write!(f, "{}", format_complex(*real, *imag)),
+            Value::Fraction(val) if val.is_integer() => return write!(f, "{}", val.numer()),
+            Value::Fraction(val) => return write!(f, "{}/{}", val.numer(), val.denom()),
+            Value::Decimal(val) => return write!(f, "{}", decimal::to_string(val)),
+            Value::Str(val) => return write!(f, "{}", val),
+            _ => return write!(f, "{}", self.repr()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_float() {
+        assert_eq!(format_float(1.0), "1.0");
+        assert_eq!(format_float(-2.5), "-2.5");
+        assert_eq!(format_float(0.1), "0.1");
+        assert_eq!(format_float(0.0001), "0.0001");
+        assert_eq!(format_float(0.00001), "1e-05");
+        assert_eq!(format_float(1e16), "1e+16");
+        assert_eq!(format_float(123456789012345.6), "123456789012345.6");
+        assert_eq!(format_float(1.5e300), "1.5e+300");
+        assert_eq!(format_float(f64::INFINITY), "inf");
+        assert_eq!(format_complex(1.0, 2.0), "(1+2j)");
+        assert_eq!(format_complex(0.0, -0.0), "-0j");
+        assert_eq!(format_complex(-0.0, 1.5), "(-0+1.5j)");
+        assert_eq!(format_complex(1e16, f64::NAN), "(1e+16+nanj)");
+    }
+
+    #[test]
+    fn test_repr() {
+        assert_eq!(repr_str("it's"), "\"it's\"");
+        assert_eq!(repr_str("a\tb'\""), "'a\\tb\\'\"'");
+        let items = vec![Value::Int(1), Value::Str(String::from("x"))];
+        assert_eq!(Value::Tuple(Rc::new(items.clone())).repr(), "(1, 'x')");
+        assert_eq!(Value::List(Rc::new(RefCell::new(items))).repr(), "[1, 'x']");
+    }
+
+    #[test]
+    fn test_format_traceback() {
+        let mut err = Exception::new("SyntaxError", "invalid syntax");
+        assert_eq!(err.format_traceback(), "SyntaxError: invalid syntax");
+
+        err = Exception::new("RecursionError", "maximum recursion depth exceeded");
+        let main = Module::build_new("__main__", None);
+        for _ in 0..5 {
+            err.add_frame(&main, "f");
+        }
+        main.set("__file__", Value::Str(String::from("a.py")));
+        err.add_frame(&main, "<module>");
+        let expected = "Traceback (most recent call last):\n  File \"a.py\", in <module>\n"
+            .to_string()
+            + &"  File \"<stdin>\", in f\n".repeat(3)
+            + "  [Previous line repeated 2 more times]\n"
+            + "RecursionError: maximum recursion depth exceeded";
+        assert_eq!(err.format_traceback(), expected);
+    }
+}